@@ -7,6 +7,7 @@ import {
 } from "react";
 import { invoke } from "@tauri-apps/api/core";
 import { Muxer, ArrayBufferTarget } from "mp4-muxer";
+import { commandErrorMessage, isCommandError } from "@/types/recording";
 import type { ScreenRegion } from "@/types/recording";
 
 interface SectionSource {
@@ -340,7 +341,14 @@ export const RecordingCanvas = forwardRef<
         );
       } catch (error) {
         console.error("[Recording] Error saving video:", error);
-        onFrameErrorRef.current?.(String(error));
+        const message = commandErrorMessage(error);
+        // A failed write loses the take, unlike a bad request
+        const lost =
+          isCommandError(error) &&
+          (error.code === "io" || error.code === "output-unavailable");
+        onFrameErrorRef.current?.(
+          lost ? `Recording could not be saved to disk: ${message}` : message,
+        );
       }
     },
     [],
@@ -554,7 +562,7 @@ export const RecordingCanvas = forwardRef<
           },
           error: (error) => {
             console.error("[WebCodecs] Encoder error:", error);
-            onFrameErrorRef.current?.(String(error));
+            onFrameErrorRef.current?.(commandErrorMessage(error));
           },
         });
 
@@ -743,7 +751,7 @@ export const RecordingCanvas = forwardRef<
         `[RecordingCanvas] Failed to process frame ${frameCountRef.current}:`,
         error,
       );
-      onFrameErrorRef.current?.(String(error));
+      onFrameErrorRef.current?.(commandErrorMessage(error));
     }
   }, [compositeFrame]);
 
@@ -831,7 +839,7 @@ export const RecordingCanvas = forwardRef<
         }, 1000);
       } catch (error) {
         console.error("[RecordingCanvas] Failed to start recording:", error);
-        onFrameErrorRef.current?.(String(error));
+        onFrameErrorRef.current?.(commandErrorMessage(error));
       }
     };
 
@@ -896,7 +904,7 @@ export const RecordingCanvas = forwardRef<
                 "[WebCodecs] Error finalizing recording:",
                 error,
               );
-              onFrameErrorRef.current?.(String(error));
+              onFrameErrorRef.current?.(commandErrorMessage(error));
             }
           })();
         }
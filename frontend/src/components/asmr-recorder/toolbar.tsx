@@ -33,8 +33,8 @@ import {
 } from "lucide-react";
 import { toast } from "@/hooks/use-toast";
 import { useRecordingContext } from "@/contexts/recording-context";
-import { formatDuration, OUTPUT_RESOLUTIONS } from "@/types/recording";
-import type { VideoQuality, OutputResolution } from "@/types/recording";
+import { commandErrorMessage, formatDuration, OUTPUT_RESOLUTIONS } from "@/types/recording";
+import type { VideoQuality, OutputResolutionPreset } from "@/types/recording";
 
 export function Toolbar() {
   const [isPlaying, setIsPlaying] = useState(false);
@@ -87,7 +87,7 @@ export function Toolbar() {
     } catch (err) {
       toast({
         title: "Recording failed",
-        description: String(err),
+        description: commandErrorMessage(err),
         variant: "destructive",
       });
     }
@@ -113,7 +113,7 @@ export function Toolbar() {
       } catch (err) {
         toast({
           title: "Failed to stop recording",
-          description: String(err),
+          description: commandErrorMessage(err),
           variant: "destructive",
         });
       }
@@ -283,7 +283,7 @@ export function Toolbar() {
                   </Label>
                   <Select
                     value={externalConfig.outputResolution || "hd1080"}
-                    onValueChange={(value: OutputResolution) =>
+                    onValueChange={(value: OutputResolutionPreset) =>
                       updateExternalConfig({ outputResolution: value })
                     }
                   >
@@ -1,6 +1,6 @@
 import React, { createContext, useContext, useState, useCallback, useEffect, useRef } from "react";
 import { invoke } from "@tauri-apps/api/core";
-import { defaultRecordingConfig, defaultSectionState, defaultExternalRecordingConfig, OUTPUT_RESOLUTIONS } from "@/types/recording";
+import { commandErrorMessage, defaultRecordingConfig, defaultSectionState, defaultExternalRecordingConfig, OUTPUT_RESOLUTIONS } from "@/types/recording";
 import type { 
   RecordingConfig, 
   RecordingStatus, 
@@ -8,7 +8,8 @@ import type {
   SectionConfig, 
   SectionState,
   RecordingSource,
-  ExternalRecordingConfig 
+  ExternalRecordingConfig,
+  StopRecordingResult 
 } from "@/types/recording";
 
 interface RecordingContextValue {
@@ -66,7 +67,7 @@ export function RecordingProvider({ children }: { children: React.ReactNode }) {
       setDevices(deviceList);
     } catch (err) {
       console.error("Failed to fetch devices:", err);
-      setError(String(err));
+      setError(commandErrorMessage(err));
     }
   }, []);
 
@@ -133,7 +134,7 @@ export function RecordingProvider({ children }: { children: React.ReactNode }) {
       await invoke("start_recording", { config });
       setStatus((prev) => ({ ...prev, isRecording: true }));
     } catch (err) {
-      const errorMessage = String(err);
+      const errorMessage = commandErrorMessage(err);
       setError(errorMessage);
       throw err;
     }
@@ -143,7 +144,7 @@ export function RecordingProvider({ children }: { children: React.ReactNode }) {
   const stopRecording = useCallback(async () => {
     setError(null);
     try {
-      const outputPath = await invoke<string>("stop_recording");
+      const { outputPath } = await invoke<StopRecordingResult>("stop_recording");
       setStatus((prev) => ({
         ...prev,
         isRecording: false,
@@ -151,7 +152,7 @@ export function RecordingProvider({ children }: { children: React.ReactNode }) {
       }));
       return outputPath;
     } catch (err) {
-      const errorMessage = String(err);
+      const errorMessage = commandErrorMessage(err);
       setError(errorMessage);
       throw err;
     }
@@ -184,7 +185,7 @@ export function RecordingProvider({ children }: { children: React.ReactNode }) {
       setIsExternalRecording(true);
       setStatus((prev) => ({ ...prev, isRecording: true, durationMs: 0, frameCount: 0 }));
     } catch (err) {
-      const errorMessage = String(err);
+      const errorMessage = commandErrorMessage(err);
       setError(errorMessage);
       throw err;
     }
@@ -202,7 +203,7 @@ export function RecordingProvider({ children }: { children: React.ReactNode }) {
       // The output path will be set asynchronously when recording-canvas finishes saving
       return status.outputPath || "";
     } catch (err) {
-      const errorMessage = String(err);
+      const errorMessage = commandErrorMessage(err);
       setError(errorMessage);
       throw err;
     }
@@ -1,7 +1,7 @@
 import { useState, useCallback, useEffect, useRef } from "react";
 import { invoke } from "@tauri-apps/api/core";
-import { defaultRecordingConfig } from "@/types/recording";
-import type { RecordingConfig, RecordingStatus, DeviceList } from "@/types/recording";
+import { commandErrorMessage, defaultRecordingConfig } from "@/types/recording";
+import type { RecordingConfig, RecordingStatus, DeviceList, StopRecordingResult } from "@/types/recording";
 
 export function useRecording() {
   const [config, setConfig] = useState<RecordingConfig>(defaultRecordingConfig);
@@ -21,7 +21,7 @@ export function useRecording() {
       setDevices(deviceList);
     } catch (err) {
       console.error("Failed to fetch devices:", err);
-      setError(String(err));
+      setError(commandErrorMessage(err));
     }
   }, []);
 
@@ -64,7 +64,7 @@ export function useRecording() {
       await invoke("start_recording", { config });
       setStatus((prev) => ({ ...prev, isRecording: true }));
     } catch (err) {
-      const errorMessage = String(err);
+      const errorMessage = commandErrorMessage(err);
       setError(errorMessage);
       throw err;
     }
@@ -74,7 +74,7 @@ export function useRecording() {
   const stopRecording = useCallback(async () => {
     setError(null);
     try {
-      const outputPath = await invoke<string>("stop_recording");
+      const { outputPath } = await invoke<StopRecordingResult>("stop_recording");
       setStatus((prev) => ({
         ...prev,
         isRecording: false,
@@ -82,7 +82,7 @@ export function useRecording() {
       }));
       return outputPath;
     } catch (err) {
-      const errorMessage = String(err);
+      const errorMessage = commandErrorMessage(err);
       setError(errorMessage);
       throw err;
     }
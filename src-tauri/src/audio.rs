@@ -1,11 +1,34 @@
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use cpal::SampleFormat;
+use cpal::{BufferSize, SampleFormat, SampleRate, StreamConfig, SupportedBufferSize, SupportedStreamConfig};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use crossbeam_channel::{bounded, Receiver, Sender};
 use parking_lot::Mutex;
 use tauri::command;
 
+use crate::audio_ring::{audio_ring, ring_capacity, AudioConsumer, AudioProducer};
+use crate::monitor::MonitorTiming;
+use crate::noise_gate::NoiseGateConfig;
+use crate::recording::AudioChannelLayout;
+
+/// How often a capture following the default device checks whether it changed
+const DEFAULT_DEVICE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Wait between attempts to reopen a lost microphone
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Whether the microphone stream is open
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "lowercase")]
+pub enum MicrophoneState {
+    /// Capturing from `device`
+    Connected { device: String },
+    /// The device failed or went away; the stream is reopened once an input
+    /// device is available
+    Reconnecting,
+}
+
 /// Represents a chunk of captured audio
 #[derive(Clone)]
 pub struct AudioChunk {
@@ -19,28 +42,291 @@ pub struct AudioChunk {
     pub timestamp: Duration,
 }
 
+/// Sample rates the recording pipeline accepts
+pub const SUPPORTED_SAMPLE_RATES: [u32; 6] = [44100, 48000, 88200, 96000, 176400, 192000];
+
+/// Accepted mixer chunk sizes, in frames
+pub const MIXER_CHUNK_RANGE: std::ops::RangeInclusive<u32> = 64..=8192;
+
+/// Audio buffering
+///
+/// Smaller buffers lower the delay between sound and recording, larger ones
+/// ride out scheduling hiccups without dropouts.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioBufferConfig {
+    /// Frames per microphone callback to request (None: the device default)
+    #[serde(default)]
+    pub device_buffer_frames: Option<u32>,
+    /// Frames per mixed chunk sent to the encoder
+    #[serde(default = "default_mixer_chunk_frames")]
+    pub mixer_chunk_frames: u32,
+}
+
+fn default_mixer_chunk_frames() -> u32 {
+    1024
+}
+
+impl Default for AudioBufferConfig {
+    fn default() -> Self {
+        Self {
+            device_buffer_frames: None,
+            mixer_chunk_frames: default_mixer_chunk_frames(),
+        }
+    }
+}
+
+impl AudioBufferConfig {
+    /// Check the mixer chunk size; the device buffer is checked against what
+    /// the device supports when capture starts
+    pub fn validate(&self) -> Result<(), String> {
+        if !MIXER_CHUNK_RANGE.contains(&self.mixer_chunk_frames) {
+            return Err(format!(
+                "Mixer chunk size must be {}-{} frames (got {})",
+                MIXER_CHUNK_RANGE.start(),
+                MIXER_CHUNK_RANGE.end(),
+                self.mixer_chunk_frames
+            ));
+        }
+        if self.device_buffer_frames == Some(0) {
+            return Err("Device buffer size must be at least one frame".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Latency of the audio path as negotiated with the device
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioLatency {
+    /// Frames per microphone callback the device delivers (None until the
+    /// first callback, or without a microphone)
+    pub device_buffer_frames: Option<u32>,
+    pub device_buffer_ms: Option<f32>,
+    /// Time from capture to callback, where the host reports it
+    pub capture_delay_ms: Option<f32>,
+    pub mixer_chunk_frames: u32,
+    pub mixer_chunk_ms: f32,
+    /// Delay the microphone noise gate adds (None without a gate)
+    pub noise_gate_ms: Option<f32>,
+    /// Longest time from capture until audio reaches the encoder queue
+    pub total_ms: f32,
+    /// Frames per monitor output callback (None without monitoring, or
+    /// until the first callback)
+    pub monitor_buffer_frames: Option<u32>,
+    pub monitor_buffer_ms: Option<f32>,
+    /// Time from capture until the monitor plays it: the microphone
+    /// buffer and delay, the noise gate, the monitor's latency target, its
+    /// output buffer and the playback delay
+    pub monitor_round_trip_ms: Option<f32>,
+}
+
+impl AudioLatency {
+    /// Combine the microphone's timing with the mixer chunk size
+    pub fn new(device: Option<DeviceTiming>, mixer_chunk_frames: u32, mixer_rate: u32) -> Self {
+        let ms = |frames: u32, rate: u32| frames as f32 * 1000.0 / rate.max(1) as f32;
+        let device_buffer_ms = device.map(|d| ms(d.buffer_frames, d.sample_rate));
+        let capture_delay_ms = device
+            .and_then(|d| d.capture_delay)
+            .map(|delay| delay.as_secs_f32() * 1000.0);
+        let mixer_chunk_ms = ms(mixer_chunk_frames, mixer_rate);
+        Self {
+            device_buffer_frames: device.map(|d| d.buffer_frames),
+            device_buffer_ms,
+            capture_delay_ms,
+            mixer_chunk_frames,
+            mixer_chunk_ms,
+            noise_gate_ms: None,
+            total_ms: device_buffer_ms.unwrap_or(0.0) + capture_delay_ms.unwrap_or(0.0) + mixer_chunk_ms,
+            monitor_buffer_frames: None,
+            monitor_buffer_ms: None,
+            monitor_round_trip_ms: None,
+        }
+    }
+
+    /// Add the microphone noise gate's delay; call before `with_monitor`,
+    /// since the monitor hears the gated signal
+    pub fn with_noise_gate(mut self, gate: &NoiseGateConfig, sample_rate: u32) -> Self {
+        if gate.enabled {
+            let delay_ms = crate::noise_gate::delay_ms(sample_rate);
+            self.noise_gate_ms = Some(delay_ms);
+            self.total_ms += delay_ms;
+        }
+        self
+    }
+
+    /// Add the microphone monitor's output to the latency
+    pub fn with_monitor(mut self, monitor: Option<MonitorTiming>) -> Self {
+        let Some(monitor) = monitor else {
+            return self;
+        };
+        let buffer_ms = monitor.buffer_frames as f32 * 1000.0 / monitor.sample_rate.max(1) as f32;
+        let playback_delay_ms = monitor.playback_delay.map_or(0.0, |delay| delay.as_secs_f32() * 1000.0);
+        self.monitor_buffer_frames = Some(monitor.buffer_frames);
+        self.monitor_buffer_ms = Some(buffer_ms);
+        self.monitor_round_trip_ms = Some(
+            self.device_buffer_ms.unwrap_or(0.0)
+                + self.capture_delay_ms.unwrap_or(0.0)
+                + self.noise_gate_ms.unwrap_or(0.0)
+                + monitor.latency_ms as f32
+                + buffer_ms
+                + playback_delay_ms,
+        );
+        self
+    }
+}
+
+/// Size and delay of the latest microphone callback
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeviceTiming {
+    pub sample_rate: u32,
+    pub buffer_frames: u32,
+    pub capture_delay: Option<Duration>,
+}
+
 /// Microphone capture configuration
+#[derive(Clone)]
 pub struct MicrophoneCaptureConfig {
     /// Device name (None for default)
     pub device_name: Option<String>,
+    /// Preferred device sample rate (None for the device default)
+    pub sample_rate: Option<u32>,
+    /// Output channel layout of the captured chunks
+    pub channel_layout: AudioChannelLayout,
+    /// Device input channels to use (zero-based, None for the first one or two)
+    pub channel_map: Option<Vec<u16>>,
+    /// Frames per callback to request (None for the device default)
+    pub buffer_frames: Option<u32>,
+    /// Instant chunk timestamps count from (None for when capture starts);
+    /// captures sharing one have comparable timestamps
+    pub epoch: Option<Instant>,
 }
 
 impl Default for MicrophoneCaptureConfig {
     fn default() -> Self {
         Self {
             device_name: None,
+            sample_rate: None,
+            channel_layout: AudioChannelLayout::default(),
+            channel_map: None,
+            buffer_frames: None,
+            epoch: None,
         }
     }
 }
 
+/// Selects device input channels and folds them into the output layout
+///
+/// Multi-channel interfaces deliver every input interleaved; this picks the
+/// requested inputs instead of blindly taking the first channels.
+#[derive(Clone)]
+pub struct ChannelMapper {
+    input_channels: usize,
+    selected: Vec<usize>,
+    layout: AudioChannelLayout,
+}
+
+impl ChannelMapper {
+    /// Create a mapper for a device with `input_channels` channels
+    pub fn new(
+        input_channels: u16,
+        channel_map: Option<&[u16]>,
+        layout: AudioChannelLayout,
+    ) -> Result<Self, String> {
+        if input_channels == 0 {
+            return Err("Input device reports no channels".to_string());
+        }
+
+        let selected: Vec<usize> = match channel_map {
+            Some(map) => {
+                if map.is_empty() || map.len() > 2 {
+                    return Err(format!(
+                        "Channel map must select one or two channels, got {}",
+                        map.len()
+                    ));
+                }
+                if let Some(&invalid) = map.iter().find(|&&ch| ch >= input_channels) {
+                    return Err(format!(
+                        "Input channel {} not available (device has {} channels)",
+                        invalid + 1,
+                        input_channels
+                    ));
+                }
+                map.iter().map(|&ch| ch as usize).collect()
+            }
+            None => (0..input_channels.min(2) as usize).collect(),
+        };
+
+        if layout == AudioChannelLayout::Binaural
+            && (selected.len() != 2 || selected[0] == selected[1])
+        {
+            return Err(
+                "Binaural mode requires two distinct input channels (left and right)".to_string(),
+            );
+        }
+
+        Ok(Self {
+            input_channels: input_channels as usize,
+            selected,
+            layout,
+        })
+    }
+
+    /// Number of channels in the mapped output
+    pub fn output_channels(&self) -> u16 {
+        self.layout.channel_count()
+    }
+
+    /// Number of samples `map` yields for `input_len` device samples
+    pub fn mapped_len(&self, input_len: usize) -> usize {
+        input_len / self.input_channels * self.output_channels() as usize
+    }
+
+    /// Map interleaved device samples to the output layout, converting each
+    /// with `to_f32`; allocation free, so it can run in the audio callback
+    pub fn map<'a, T: Copy>(
+        &'a self,
+        samples: &'a [T],
+        to_f32: fn(T) -> f32,
+    ) -> impl Iterator<Item = f32> + 'a {
+        let output_channels = self.output_channels() as usize;
+        samples.chunks_exact(self.input_channels).flat_map(move |frame| {
+            let mapped = match self.layout {
+                AudioChannelLayout::Mono => {
+                    let sum: f32 = self.selected.iter().map(|&ch| to_f32(frame[ch])).sum();
+                    [sum / self.selected.len() as f32, 0.0]
+                }
+                AudioChannelLayout::Stereo | AudioChannelLayout::Binaural => {
+                    let left = to_f32(frame[self.selected[0]]);
+                    let right = to_f32(frame[self.selected[self.selected.len() - 1]]);
+                    [left, right]
+                }
+            };
+            mapped.into_iter().take(output_channels)
+        })
+    }
+
+    /// Map interleaved f32 device samples to the output layout
+    #[cfg(test)]
+    pub fn apply(&self, samples: &[f32]) -> Vec<f32> {
+        self.map(samples, |s| s).collect()
+    }
+}
+
 /// Manages microphone audio capture
 pub struct MicrophoneCapture {
     config: MicrophoneCaptureConfig,
     actual_sample_rate: u32,
     actual_channels: u16,
+    channel_mapper: ChannelMapper,
     running: Arc<Mutex<bool>>,
-    chunk_sender: Option<Sender<AudioChunk>>,
-    chunk_receiver: Option<Receiver<AudioChunk>>,
+    timing: Arc<Mutex<Option<DeviceTiming>>>,
+    state: Arc<Mutex<MicrophoneState>>,
+    /// Set to have the capture thread close and reopen the stream
+    reopen: Arc<AtomicBool>,
+    chunk_producer: Mutex<Option<AudioProducer>>,
+    chunk_consumer: Option<AudioConsumer>,
+    thread: Mutex<Option<std::thread::JoinHandle<()>>>,
 }
 
 impl MicrophoneCapture {
@@ -49,45 +335,68 @@ impl MicrophoneCapture {
         let host = cpal::default_host();
         
         // Get the input device
-        let device = if let Some(ref name) = config.device_name {
-            host.input_devices()
-                .map_err(|e| format!("Failed to enumerate devices: {}", e))?
-                .find(|d| d.name().map(|n| n == *name).unwrap_or(false))
-                .ok_or_else(|| format!("Device '{}' not found", name))?
-        } else {
-            host.default_input_device()
-                .ok_or("No default input device available")?
-        };
+        let device = find_input_device(&host, config.device_name.as_deref())?;
+        let device_name = device.name().unwrap_or_else(|_| "Unknown input device".to_string());
         
-        // Get supported config
-        let supported_config = device.default_input_config()
-            .map_err(|e| format!("Failed to get default config: {}", e))?;
+        // Get supported config, running natively at the requested rate when possible
+        let supported_config = select_input_config(&device, config.sample_rate)?;
         
         let actual_sample_rate = supported_config.sample_rate().0;
         let actual_channels = supported_config.channels();
+        stream_config(&supported_config, config.buffer_frames)?;
+        check_binaural_rate(&config, actual_sample_rate)?;
+        let channel_mapper = ChannelMapper::new(
+            actual_channels,
+            config.channel_map.as_deref(),
+            config.channel_layout,
+        )?;
         
-        // Create channel for audio chunks
-        let (sender, receiver) = bounded(30); // Buffer ~1 second of audio
+        // Preallocated ring for audio chunks, filled by the device callback
+        let (producer, consumer) = audio_ring(ring_capacity(
+            actual_sample_rate,
+            channel_mapper.output_channels(),
+        ));
         
         Ok(Self {
             config,
             actual_sample_rate,
             actual_channels,
+            channel_mapper,
             running: Arc::new(Mutex::new(false)),
-            chunk_sender: Some(sender),
-            chunk_receiver: Some(receiver),
+            timing: Arc::new(Mutex::new(None)),
+            state: Arc::new(Mutex::new(MicrophoneState::Connected { device: device_name })),
+            reopen: Arc::new(AtomicBool::new(false)),
+            chunk_producer: Mutex::new(Some(producer)),
+            chunk_consumer: Some(consumer),
+            thread: Mutex::new(None),
         })
     }
     
-    /// Get a receiver for audio chunks
-    pub fn take_receiver(&mut self) -> Option<Receiver<AudioChunk>> {
-        self.chunk_receiver.take()
+    /// Get the reading end of the audio ring
+    pub fn take_receiver(&mut self) -> Option<AudioConsumer> {
+        self.chunk_consumer.take()
+    }
+    
+    /// Callback size and delay the device settled on (None before the first callback)
+    pub fn timing(&self) -> Option<DeviceTiming> {
+        *self.timing.lock()
+    }
+    
+    /// Whether the stream is open, and on which device
+    pub fn state(&self) -> MicrophoneState {
+        self.state.lock().clone()
+    }
+    
+    /// Close and reopen the stream, e.g. after callbacks stopped coming;
+    /// the ring and timestamps carry on
+    pub fn reopen(&self) {
+        self.reopen.store(true, Ordering::Relaxed);
     }
     
     /// Start capturing audio
     /// 
     /// Note: The audio stream runs in a background thread managed by cpal.
-    /// To stop, call the stop() method which signals the running flag.
+    /// To stop, call the stop() method, which waits for the thread.
     pub fn start(&self) -> Result<(), String> {
         let mut running = self.running.lock();
         if *running {
@@ -97,82 +406,283 @@ impl MicrophoneCapture {
         drop(running);
         
         let running_clone = self.running.clone();
-        let sender = self.chunk_sender.clone()
-            .ok_or("Chunk sender not available")?;
-        let sample_rate = self.actual_sample_rate;
-        let channels = self.actual_channels;
-        let device_name = self.config.device_name.clone();
+        let producer = self.chunk_producer.lock().take()
+            .ok_or("Audio ring not available")?;
+        let config = self.config.clone();
+        let timing = self.timing.clone();
+        let state = self.state.clone();
+        let reopen = self.reopen.clone();
         
         // Spawn thread to manage the stream
-        std::thread::spawn(move || {
-            if let Err(e) = run_audio_capture(running_clone, sender, sample_rate, channels, device_name) {
-                eprintln!("Audio capture error: {}", e);
-            }
+        let thread = std::thread::spawn(move || {
+            run_audio_capture(running_clone, producer, config, timing, state, reopen);
         });
+        *self.thread.lock() = Some(thread);
         
         println!(
-            "Microphone capture started: {}Hz, {} channels",
-            self.actual_sample_rate, self.actual_channels
+            "Microphone capture started: {}Hz, {} input channels -> {} output channels",
+            self.actual_sample_rate,
+            self.actual_channels,
+            self.channel_mapper.output_channels()
         );
         
         Ok(())
     }
     
-    /// Stop capturing
+    /// Stop capturing; returns once the stream is closed, which closes the ring
     pub fn stop(&self) {
-        let mut running = self.running.lock();
-        *running = false;
+        *self.running.lock() = false;
+        if let Some(thread) = self.thread.lock().take() {
+            let _ = thread.join();
+        }
         println!("Microphone capture stopped");
     }
     
 }
 
+/// Pick the input stream config for a device
+///
+/// Uses the device default unless a different rate is requested and the device
+/// supports it natively (keeping the default channel count so channel maps stay valid).
+/// Falls back to the default config, leaving rate conversion to the mixer.
+fn select_input_config(
+    device: &cpal::Device,
+    preferred_rate: Option<u32>,
+) -> Result<SupportedStreamConfig, String> {
+    let default_config = device.default_input_config()
+        .map_err(|e| format!("Failed to get default config: {}", e))?;
+    
+    let Some(rate) = preferred_rate else {
+        return Ok(default_config);
+    };
+    if default_config.sample_rate().0 == rate {
+        return Ok(default_config);
+    }
+    
+    let ranges: Vec<_> = match device.supported_input_configs() {
+        Ok(configs) => configs
+            .filter(|c| c.channels() == default_config.channels())
+            .filter(|c| c.min_sample_rate().0 <= rate && rate <= c.max_sample_rate().0)
+            .filter(|c| {
+                matches!(c.sample_format(), SampleFormat::F32 | SampleFormat::I16 | SampleFormat::U16)
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    
+    let chosen = ranges.iter()
+        .find(|c| c.sample_format() == default_config.sample_format())
+        .or_else(|| ranges.first());
+    
+    match chosen {
+        Some(range) => Ok((*range).with_sample_rate(SampleRate(rate))),
+        None => {
+            println!(
+                "Input device does not support {}Hz, capturing at {}Hz and resampling",
+                rate,
+                default_config.sample_rate().0
+            );
+            Ok(default_config)
+        }
+    }
+}
+
+/// Stream config requesting `buffer_frames` per callback, if the device
+/// allows it
+pub(crate) fn stream_config(
+    supported_config: &SupportedStreamConfig,
+    buffer_frames: Option<u32>,
+) -> Result<StreamConfig, String> {
+    let mut config: StreamConfig = supported_config.config();
+    if let Some(frames) = buffer_frames {
+        if let SupportedBufferSize::Range { min, max } = *supported_config.buffer_size() {
+            if !(min..=max).contains(&frames) {
+                return Err(format!(
+                    "Device buffer size must be {}-{} frames (got {})",
+                    min, max, frames
+                ));
+            }
+        }
+        config.buffer_size = BufferSize::Fixed(frames);
+    }
+    Ok(config)
+}
+
+/// Record the size and delay of a callback's buffer
+fn record_timing(
+    timing: &Mutex<Option<DeviceTiming>>,
+    info: &cpal::InputCallbackInfo,
+    samples: usize,
+    input_channels: u16,
+    sample_rate: u32,
+) {
+    let timestamp = info.timestamp();
+    *timing.lock() = Some(DeviceTiming {
+        sample_rate,
+        buffer_frames: (samples / input_channels.max(1) as usize) as u32,
+        capture_delay: timestamp.callback.duration_since(&timestamp.capture),
+    });
+}
+
 /// Run the audio capture in a background thread
+///
+/// Reopens the stream when it fails, and when the default input device
+/// changes while following it, so a Bluetooth reconnect or a woken USB mic
+/// doesn't end the microphone track.
 fn run_audio_capture(
     running: Arc<Mutex<bool>>,
-    sender: Sender<AudioChunk>,
-    sample_rate: u32,
-    channels: u16,
-    device_name: Option<String>,
-) -> Result<(), String> {
+    producer: AudioProducer,
+    capture_config: MicrophoneCaptureConfig,
+    timing: Arc<Mutex<Option<DeviceTiming>>>,
+    state: Arc<Mutex<MicrophoneState>>,
+    reopen: Arc<AtomicBool>,
+) {
     let host = cpal::default_host();
+    let producer = Arc::new(Mutex::new(producer));
+    let start_time = capture_config.epoch.unwrap_or_else(Instant::now);
     
-    let device = if let Some(ref name) = device_name {
-        host.input_devices()
+    while *running.lock() {
+        let failed = Arc::new(AtomicBool::new(false));
+        let opened = open_input_stream(
+            &host,
+            &capture_config,
+            &running,
+            &producer,
+            start_time,
+            &timing,
+            &failed,
+        );
+        let (stream, device_name) = match opened {
+            Ok(opened) => opened,
+            Err(e) => {
+                eprintln!("Failed to open microphone: {}", e);
+                *state.lock() = MicrophoneState::Reconnecting;
+                sleep_while_running(&running, RECONNECT_INTERVAL);
+                continue;
+            }
+        };
+        *state.lock() = MicrophoneState::Connected { device: device_name.clone() };
+        
+        // Keep the stream alive until stopped, failed or superseded
+        let mut last_check = Instant::now();
+        while *running.lock() && !failed.load(Ordering::Relaxed) {
+            std::thread::sleep(Duration::from_millis(100));
+            if reopen.swap(false, Ordering::Relaxed) {
+                println!("Reopening microphone stream on '{}'", device_name);
+                break;
+            }
+            if capture_config.device_name.is_some() || last_check.elapsed() < DEFAULT_DEVICE_CHECK_INTERVAL {
+                continue;
+            }
+            last_check = Instant::now();
+            if let Some(default) = default_input_name(&host).filter(|n| *n != device_name) {
+                println!("Default microphone changed to '{}', switching", default);
+                break;
+            }
+        }
+        drop(stream);
+        
+        if failed.load(Ordering::Relaxed) && *running.lock() {
+            *state.lock() = MicrophoneState::Reconnecting;
+            sleep_while_running(&running, RECONNECT_INTERVAL);
+        }
+    }
+    // The ring closes when the last producer handle is dropped here
+}
+
+/// Sleep for up to `duration`, returning early once capture stops
+fn sleep_while_running(running: &Mutex<bool>, duration: Duration) {
+    let until = Instant::now() + duration;
+    while *running.lock() && Instant::now() < until {
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
+
+/// Name of the system default input device
+fn default_input_name(host: &cpal::Host) -> Option<String> {
+    host.default_input_device().and_then(|d| d.name().ok())
+}
+
+/// Find the configured input device, or the current default
+fn find_input_device(host: &cpal::Host, device_name: Option<&str>) -> Result<cpal::Device, String> {
+    match device_name {
+        Some(name) => host.input_devices()
             .map_err(|e| format!("Failed to enumerate devices: {}", e))?
-            .find(|d| d.name().map(|n| n == *name).unwrap_or(false))
-            .ok_or_else(|| format!("Device '{}' not found", name))?
-    } else {
-        host.default_input_device()
-            .ok_or("No default input device available")?
-    };
+            .find(|d| d.name().map(|n| n == name).unwrap_or(false))
+            .ok_or_else(|| format!("Device '{}' not found", name)),
+        None => host.default_input_device()
+            .ok_or_else(|| "No default input device available".to_string()),
+    }
+}
+
+/// Binaural recordings must not pass through the mixer's resampler
+fn check_binaural_rate(config: &MicrophoneCaptureConfig, actual_sample_rate: u32) -> Result<(), String> {
+    if config.channel_layout == AudioChannelLayout::Binaural {
+        if let Some(rate) = config.sample_rate.filter(|&r| r != actual_sample_rate) {
+            return Err(format!(
+                "Binaural mode requires the microphone to run at {}Hz natively (device uses {}Hz)",
+                rate, actual_sample_rate
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Open and start an input stream feeding the ring, returning it with the
+/// device's name; `failed` is set if the stream reports an error
+fn open_input_stream(
+    host: &cpal::Host,
+    capture_config: &MicrophoneCaptureConfig,
+    running: &Arc<Mutex<bool>>,
+    producer: &Arc<Mutex<AudioProducer>>,
+    start_time: Instant,
+    timing: &Arc<Mutex<Option<DeviceTiming>>>,
+    failed: &Arc<AtomicBool>,
+) -> Result<(cpal::Stream, String), String> {
+    let device = find_input_device(host, capture_config.device_name.as_deref())?;
+    let device_name = device.name().unwrap_or_else(|_| "Unknown input device".to_string());
     
-    let supported_config = device.default_input_config()
-        .map_err(|e| format!("Failed to get config: {}", e))?;
+    let supported_config = select_input_config(&device, capture_config.sample_rate)?;
     
     let sample_format = supported_config.sample_format();
-    let config = supported_config.into();
+    let sample_rate = supported_config.sample_rate().0;
+    let config = stream_config(&supported_config, capture_config.buffer_frames)?;
+    let input_channels = config.channels;
+    check_binaural_rate(capture_config, sample_rate)?;
+    // Built per stream: a newly picked default device may have other inputs
+    let channel_mapper = ChannelMapper::new(
+        input_channels,
+        capture_config.channel_map.as_deref(),
+        capture_config.channel_layout,
+    )?;
     
-    let start_time = Instant::now();
     let running_for_callback = running.clone();
+    let producer = producer.clone();
+    let timing = timing.clone();
+    let channels = channel_mapper.output_channels();
     
-    let err_fn = |err| eprintln!("Audio stream error: {}", err);
+    let failed = failed.clone();
+    let err_fn = move |err| {
+        eprintln!("Audio stream error: {}", err);
+        failed.store(true, Ordering::Relaxed);
+    };
     
     let stream = match sample_format {
         SampleFormat::F32 => {
             device.build_input_stream(
                 &config,
-                move |data: &[f32], _: &cpal::InputCallbackInfo| {
+                move |data: &[f32], info: &cpal::InputCallbackInfo| {
                     if !*running_for_callback.lock() {
                         return;
                     }
-                    let chunk = AudioChunk {
-                        samples: data.to_vec(),
+                    record_timing(&timing, info, data.len(), input_channels, sample_rate);
+                    producer.lock().push(
+                        start_time.elapsed(),
                         sample_rate,
                         channels,
-                        timestamp: start_time.elapsed(),
-                    };
-                    let _ = sender.try_send(chunk);
+                        channel_mapper.mapped_len(data.len()),
+                        channel_mapper.map(data, |s| s),
+                    );
                 },
                 err_fn,
                 None,
@@ -181,21 +691,18 @@ fn run_audio_capture(
         SampleFormat::I16 => {
             device.build_input_stream(
                 &config,
-                move |data: &[i16], _: &cpal::InputCallbackInfo| {
+                move |data: &[i16], info: &cpal::InputCallbackInfo| {
                     if !*running_for_callback.lock() {
                         return;
                     }
-                    let samples: Vec<f32> = data
-                        .iter()
-                        .map(|&s| s as f32 / 32768.0)
-                        .collect();
-                    let chunk = AudioChunk {
-                        samples,
+                    record_timing(&timing, info, data.len(), input_channels, sample_rate);
+                    producer.lock().push(
+                        start_time.elapsed(),
                         sample_rate,
                         channels,
-                        timestamp: start_time.elapsed(),
-                    };
-                    let _ = sender.try_send(chunk);
+                        channel_mapper.mapped_len(data.len()),
+                        channel_mapper.map(data, |s| s as f32 / 32768.0),
+                    );
                 },
                 err_fn,
                 None,
@@ -204,21 +711,18 @@ fn run_audio_capture(
         SampleFormat::U16 => {
             device.build_input_stream(
                 &config,
-                move |data: &[u16], _: &cpal::InputCallbackInfo| {
+                move |data: &[u16], info: &cpal::InputCallbackInfo| {
                     if !*running_for_callback.lock() {
                         return;
                     }
-                    let samples: Vec<f32> = data
-                        .iter()
-                        .map(|&s| (s as f32 - 32768.0) / 32768.0)
-                        .collect();
-                    let chunk = AudioChunk {
-                        samples,
+                    record_timing(&timing, info, data.len(), input_channels, sample_rate);
+                    producer.lock().push(
+                        start_time.elapsed(),
                         sample_rate,
                         channels,
-                        timestamp: start_time.elapsed(),
-                    };
-                    let _ = sender.try_send(chunk);
+                        channel_mapper.mapped_len(data.len()),
+                        channel_mapper.map(data, |s| (s as f32 - 32768.0) / 32768.0),
+                    );
                 },
                 err_fn,
                 None,
@@ -229,13 +733,8 @@ fn run_audio_capture(
     
     stream.play().map_err(|e| format!("Failed to start stream: {}", e))?;
     
-    // Keep the stream alive while running
-    while *running.lock() {
-        std::thread::sleep(Duration::from_millis(100));
-    }
-    
-    // Stream is dropped when function returns
-    Ok(())
+    println!("Microphone stream opened on '{}' ({}Hz, {} channels)", device_name, sample_rate, input_channels);
+    Ok((stream, device_name))
 }
 
 /// Legacy Tauri command for backward compatibility
@@ -254,3 +753,78 @@ pub fn start_audio_capture() {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_channel_mapper_selects_inputs() {
+        // 4-channel interface, record inputs 3+4 as stereo
+        let mapper = ChannelMapper::new(4, Some(&[2, 3]), AudioChannelLayout::Stereo).unwrap();
+        let samples = vec![0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8];
+        assert_eq!(mapper.apply(&samples), vec![0.3, 0.4, 0.7, 0.8]);
+
+        // Single input duplicated to both sides
+        let mapper = ChannelMapper::new(4, Some(&[1]), AudioChannelLayout::Stereo).unwrap();
+        assert_eq!(mapper.apply(&samples), vec![0.2, 0.2, 0.6, 0.6]);
+    }
+
+    #[test]
+    fn test_channel_mapper_mono_and_validation() {
+        let mapper = ChannelMapper::new(2, None, AudioChannelLayout::Mono).unwrap();
+        let mono = mapper.apply(&[0.5, 0.0, 1.0, 1.0]);
+        assert_eq!(mono.len(), 2);
+        assert!((mono[0] - 0.25).abs() < 0.001);
+        assert!((mono[1] - 1.0).abs() < 0.001);
+
+        assert!(ChannelMapper::new(2, Some(&[2]), AudioChannelLayout::Stereo).is_err());
+        assert!(ChannelMapper::new(4, Some(&[0, 1, 2]), AudioChannelLayout::Stereo).is_err());
+        
+        // Binaural needs two real ears, never a duplicated mono input
+        assert!(ChannelMapper::new(1, None, AudioChannelLayout::Binaural).is_err());
+        assert!(ChannelMapper::new(2, Some(&[0, 0]), AudioChannelLayout::Binaural).is_err());
+        assert!(ChannelMapper::new(4, Some(&[2, 3]), AudioChannelLayout::Binaural).is_ok());
+    }
+
+    #[test]
+    fn test_audio_latency() {
+        let device = DeviceTiming {
+            sample_rate: 48000,
+            buffer_frames: 480,
+            capture_delay: Some(Duration::from_millis(2)),
+        };
+        let latency = AudioLatency::new(Some(device), 1024, 48000);
+        assert_eq!(latency.device_buffer_frames, Some(480));
+        assert_eq!(latency.device_buffer_ms, Some(10.0));
+        assert!((latency.total_ms - (10.0 + 2.0 + 21.333)).abs() < 0.01);
+
+        // Monitoring adds its latency target and output buffer to the
+        // microphone's
+        let monitor = MonitorTiming {
+            sample_rate: 48000,
+            buffer_frames: 96,
+            playback_delay: Some(Duration::from_millis(1)),
+            latency_ms: 5,
+        };
+        let monitored = latency.clone().with_monitor(Some(monitor));
+        assert_eq!(monitored.monitor_buffer_frames, Some(96));
+        assert!((monitored.monitor_round_trip_ms.unwrap() - (10.0 + 2.0 + 5.0 + 2.0 + 1.0)).abs() < 0.01);
+
+        // The monitor hears the gated microphone, so the gate delays both
+        let gate = NoiseGateConfig {
+            enabled: true,
+            ..NoiseGateConfig::default()
+        };
+        let gated = latency.clone().with_noise_gate(&gate, 48000).with_monitor(Some(monitor));
+        assert!((gated.total_ms - (latency.total_ms + 10.667)).abs() < 0.01);
+        assert!((gated.monitor_round_trip_ms.unwrap() - (monitored.monitor_round_trip_ms.unwrap() + 10.667)).abs() < 0.01);
+
+        // System audio only: just the mixer
+        let latency = AudioLatency::new(None, 256, 48000).with_monitor(None);
+        assert!((latency.total_ms - 5.333).abs() < 0.01);
+        assert_eq!(latency.monitor_round_trip_ms, None);
+
+        assert!(AudioBufferConfig::default().validate().is_ok());
+        assert!(AudioBufferConfig { mixer_chunk_frames: 16, ..AudioBufferConfig::default() }.validate().is_err());
+    }
+}
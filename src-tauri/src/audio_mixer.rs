@@ -1,9 +1,30 @@
 use std::sync::Arc;
-use std::time::Duration;
-use crossbeam_channel::{bounded, Receiver, Sender, TryRecvError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use crossbeam_channel::{bounded, Receiver, Sender};
 use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
 
 use crate::audio::AudioChunk;
+use crate::audio_ring::{empty_chunk, wait_any, AudioConsumer};
+use crate::metering::{AudioLevels, MeterBank, MeteringConfig};
+use crate::noise_gate::{NoiseGateConfig, SpectralGate, HOP};
+use crate::sidecar_wav::SidecarWavSource;
+
+/// Offsets between the sources are corrected over roughly this many seconds
+const DRIFT_CORRECTION_SECS: f64 = 10.0;
+
+/// Largest speed change applied to system audio (0.1%, inaudible and well
+/// beyond the error of real device clocks)
+const MAX_DRIFT_CORRECTION: f64 = 0.001;
+
+/// Weight of each new drift measurement; callback timing jitters by about a
+/// buffer, so single measurements mean little
+const DRIFT_SMOOTHING: f64 = 0.02;
+
+/// Longest the mixer keeps draining its sources after a stop, in case a
+/// capture never closes its ring
+const STOP_DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
 
 /// Mixed audio output chunk
 #[derive(Clone)]
@@ -15,10 +36,43 @@ pub struct MixedAudioChunk {
     pub sample_rate: u32,
     /// Number of channels
     pub channels: u16,
-    /// Timestamp
+    /// Capture time of the first sample
     pub timestamp: Duration,
 }
 
+/// Rate conversion of one source
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceStats {
+    /// Rate of the latest chunk from the device
+    pub sample_rate: u32,
+    pub chunks: u64,
+    pub input_frames: u64,
+    /// Frames after conversion to the output rate
+    pub output_frames: u64,
+    /// Times the device changed rate mid-recording
+    pub rate_changes: u32,
+    /// Frames added (positive) or removed (negative) by drift correction,
+    /// beyond the plain rate conversion
+    pub corrected_frames: i64,
+    /// Chunks lost because the mixer fell more than two seconds behind
+    pub overflows: u64,
+}
+
+/// Mixer statistics for the active recording
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MixerStats {
+    /// Rate of everything sent to the encoder
+    pub output_sample_rate: u32,
+    pub mic: Option<SourceStats>,
+    pub system: Option<SourceStats>,
+    /// How far system audio runs ahead of the mic (with both captured)
+    pub drift_offset_ms: Option<f64>,
+    /// Speed change currently applied to system audio, in parts per million
+    pub drift_correction_ppm: Option<f64>,
+}
+
 /// Audio mixer configuration
 pub struct AudioMixerConfig {
     /// Output sample rate
@@ -29,8 +83,14 @@ pub struct AudioMixerConfig {
     pub mic_volume: f32,
     /// System audio volume (0.0 - 2.0)
     pub system_volume: f32,
-    /// Buffer size in samples
+    /// Frames per output chunk
     pub buffer_size: usize,
+    /// Level meter configuration
+    pub metering: MeteringConfig,
+    /// Pass microphone L/R through untouched (binaural recording)
+    pub binaural: bool,
+    /// Spectral gate on the microphone
+    pub noise_gate: NoiseGateConfig,
 }
 
 impl Default for AudioMixerConfig {
@@ -41,43 +101,86 @@ impl Default for AudioMixerConfig {
             mic_volume: 1.0,
             system_volume: 1.0,
             buffer_size: 1024,
+            metering: MeteringConfig::default(),
+            binaural: false,
+            noise_gate: NoiseGateConfig::default(),
         }
     }
 }
 
+/// Source volumes, adjustable while mixing
+#[derive(Debug, Clone, Copy)]
+struct MixerVolumes {
+    mic: f32,
+    system: f32,
+}
+
+/// State shared between the mixer handle and the mixing thread
+struct MixerControls {
+    meters: Mutex<MeterBank>,
+    volumes: Mutex<MixerVolumes>,
+    /// Receives the processed microphone signal for live monitoring
+    monitor: Mutex<Option<Sender<AudioChunk>>>,
+    /// Receives the processed microphone signal for speech recognition
+    captions: Mutex<Option<Sender<AudioChunk>>>,
+    /// Receives the mix or the processed microphone signal for a sidecar WAV
+    sidecar: Mutex<Option<(SidecarWavSource, Sender<AudioChunk>)>>,
+    /// Receives the mix for the waveform and spectrum display
+    visualizer: Mutex<Option<Sender<AudioChunk>>>,
+    stats: Mutex<MixerStats>,
+}
+
 /// Audio mixer that combines multiple audio sources
 pub struct AudioMixer {
     config: AudioMixerConfig,
     running: Arc<Mutex<bool>>,
-    mic_receiver: Option<Receiver<AudioChunk>>,
-    system_receiver: Option<Receiver<AudioChunk>>,
-    output_sender: Option<Sender<MixedAudioChunk>>,
+    mic_receiver: Mutex<Option<AudioConsumer>>,
+    system_receiver: Mutex<Option<AudioConsumer>>,
+    /// Moved to the mixing thread, so the output closes when it exits
+    output_sender: Mutex<Option<Sender<MixedAudioChunk>>>,
     output_receiver: Option<Receiver<MixedAudioChunk>>,
+    controls: Arc<MixerControls>,
+    thread: Mutex<Option<JoinHandle<()>>>,
 }
 
 impl AudioMixer {
     /// Create a new audio mixer
     pub fn new(config: AudioMixerConfig) -> Self {
         let (sender, receiver) = bounded(30);
+        let meters = MeterBank::new(config.metering, config.sample_rate, config.channels);
+        let volumes = MixerVolumes {
+            mic: config.mic_volume,
+            system: config.system_volume,
+        };
         
         Self {
+            controls: Arc::new(MixerControls {
+                meters: Mutex::new(meters),
+                volumes: Mutex::new(volumes),
+                monitor: Mutex::new(None),
+                captions: Mutex::new(None),
+                sidecar: Mutex::new(None),
+                visualizer: Mutex::new(None),
+                stats: Mutex::new(MixerStats::default()),
+            }),
             config,
             running: Arc::new(Mutex::new(false)),
-            mic_receiver: None,
-            system_receiver: None,
-            output_sender: Some(sender),
+            mic_receiver: Mutex::new(None),
+            system_receiver: Mutex::new(None),
+            output_sender: Mutex::new(Some(sender)),
             output_receiver: Some(receiver),
+            thread: Mutex::new(None),
         }
     }
     
-    /// Set the microphone audio receiver
-    pub fn set_mic_receiver(&mut self, receiver: Receiver<AudioChunk>) {
-        self.mic_receiver = Some(receiver);
+    /// Set the microphone audio ring
+    pub fn set_mic_receiver(&mut self, receiver: AudioConsumer) {
+        *self.mic_receiver.get_mut() = Some(receiver);
     }
     
-    /// Set the system audio receiver
-    pub fn set_system_receiver(&mut self, receiver: Receiver<AudioChunk>) {
-        self.system_receiver = Some(receiver);
+    /// Set the system audio ring
+    pub fn set_system_receiver(&mut self, receiver: AudioConsumer) {
+        *self.system_receiver.get_mut() = Some(receiver);
     }
     
     /// Get the mixed output receiver
@@ -85,6 +188,52 @@ impl AudioMixer {
         self.output_receiver.take()
     }
     
+    /// Get the current audio levels
+    pub fn levels(&self) -> AudioLevels {
+        self.controls.meters.lock().snapshot()
+    }
+    
+    /// Get the rate conversion and drift statistics
+    pub fn stats(&self) -> MixerStats {
+        self.controls.stats.lock().clone()
+    }
+    
+    /// Change the metering configuration (takes effect immediately)
+    pub fn set_metering_config(&self, config: MeteringConfig) {
+        self.controls.meters.lock().set_config(config);
+    }
+    
+    /// Change source volumes (0.0 - 2.0, takes effect with the next chunk)
+    pub fn set_volumes(&self, mic_volume: Option<f32>, system_volume: Option<f32>) {
+        let mut volumes = self.controls.volumes.lock();
+        if let Some(volume) = mic_volume {
+            volumes.mic = volume;
+        }
+        if let Some(volume) = system_volume {
+            volumes.system = volume;
+        }
+    }
+    
+    /// Send the processed microphone signal to a monitor (None to stop monitoring)
+    pub fn set_monitor_sender(&self, sender: Option<Sender<AudioChunk>>) {
+        *self.controls.monitor.lock() = sender;
+    }
+    
+    /// Send the processed microphone signal to live captions (None to stop)
+    pub fn set_caption_sender(&self, sender: Option<Sender<AudioChunk>>) {
+        *self.controls.captions.lock() = sender;
+    }
+    
+    /// Send the mix or the processed microphone signal to a sidecar WAV (None to stop)
+    pub fn set_sidecar_tap(&self, tap: Option<(SidecarWavSource, Sender<AudioChunk>)>) {
+        *self.controls.sidecar.lock() = tap;
+    }
+    
+    /// Send the mix to the audio visualizer (None to stop)
+    pub fn set_visualizer_sender(&self, sender: Option<Sender<AudioChunk>>) {
+        *self.controls.visualizer.lock() = sender;
+    }
+    
     /// Start mixing audio
     pub fn start(&self) -> Result<(), String> {
         let mut running = self.running.lock();
@@ -95,21 +244,33 @@ impl AudioMixer {
         drop(running);
         
         let running_clone = self.running.clone();
-        let mic_receiver = self.mic_receiver.clone();
-        let system_receiver = self.system_receiver.clone();
-        let output_sender = self.output_sender.clone()
+        let mic_receiver = self.mic_receiver.lock().take();
+        let system_receiver = self.system_receiver.lock().take();
+        let output_sender = self.output_sender.lock().take()
             .ok_or("Output sender not available")?;
+        let controls = self.controls.clone();
         let config = AudioMixerConfig {
             sample_rate: self.config.sample_rate,
             channels: self.config.channels,
             mic_volume: self.config.mic_volume,
             system_volume: self.config.system_volume,
             buffer_size: self.config.buffer_size,
+            metering: self.config.metering,
+            binaural: self.config.binaural,
+            noise_gate: self.config.noise_gate,
         };
         
-        std::thread::spawn(move || {
-            mix_loop(running_clone, mic_receiver, system_receiver, output_sender, config);
+        let thread = std::thread::spawn(move || {
+            mix_loop(
+                running_clone,
+                mic_receiver,
+                system_receiver,
+                output_sender,
+                controls,
+                config,
+            );
         });
+        *self.thread.lock() = Some(thread);
         
         println!(
             "Audio mixer started: {}Hz, {} channels",
@@ -119,10 +280,16 @@ impl AudioMixer {
         Ok(())
     }
     
-    /// Stop mixing
+    /// Stop mixing, once the audio already captured is mixed and sent
+    ///
+    /// Stop the captures first: the mixer drains its rings until they are
+    /// closed (or `STOP_DRAIN_TIMEOUT` passes), sends the remainder as a
+    /// last short chunk and closes its output.
     pub fn stop(&self) {
-        let mut running = self.running.lock();
-        *running = false;
+        *self.running.lock() = false;
+        if let Some(thread) = self.thread.lock().take() {
+            let _ = thread.join();
+        }
         println!("Audio mixer stopped");
     }
     
@@ -131,67 +298,208 @@ impl AudioMixer {
 /// The main mixing loop
 fn mix_loop(
     running: Arc<Mutex<bool>>,
-    mic_receiver: Option<Receiver<AudioChunk>>,
-    system_receiver: Option<Receiver<AudioChunk>>,
+    mut mic_receiver: Option<AudioConsumer>,
+    mut system_receiver: Option<AudioConsumer>,
     output_sender: Sender<MixedAudioChunk>,
+    controls: Arc<MixerControls>,
     config: AudioMixerConfig,
 ) {
     let mut mic_buffer: Vec<f32> = Vec::new();
     let mut system_buffer: Vec<f32> = Vec::new();
-    let mut timestamp = Duration::from_secs(0);
+    // Output chunks are stamped from the first input chunk's capture time plus
+    // the samples emitted since, so dropped output chunks show up as gaps
+    let mut origin: Option<Duration> = None;
+    let mut emitted_frames: u64 = 0;
     
     let samples_per_chunk = config.buffer_size * config.channels as usize;
+    // Each device runs on its own clock: keep system audio locked to the mic
+    let mut drift = (mic_receiver.is_some() && system_receiver.is_some())
+        .then(|| DriftCorrector::new(config.sample_rate, config.channels));
+    let mut mic_input = SourceInput::new("Microphone");
+    // The gate delays the mic by HOP frames: system audio is delayed as
+    // much, and the mix stamped that much earlier
+    let mut gate = (config.noise_gate.enabled && mic_receiver.is_some())
+        .then(|| SpectralGate::new(&config.noise_gate, config.sample_rate, config.channels));
+    let gate_delay = match gate {
+        Some(_) => Duration::from_secs_f64(HOP as f64 / config.sample_rate as f64),
+        None => Duration::ZERO,
+    };
+    if gate.is_some() && system_receiver.is_some() {
+        system_buffer.resize(HOP * config.channels as usize, 0.0);
+    }
+    let mut system_input = SourceInput::new("System audio");
+    // Chunks are read into these, reusing their sample buffers
+    let mut mic_chunk = empty_chunk();
+    let mut system_chunk = empty_chunk();
+    let mut stopped_at: Option<Instant> = None;
+    // The encoder stopped reading; later chunks aren't waited on
+    let mut output_stalled = false;
     
-    while *running.lock() {
+    loop {
+        if stopped_at.is_none() && !*running.lock() {
+            stopped_at = Some(Instant::now());
+        }
+        let volumes = *controls.volumes.lock();
+        
         // Collect samples from microphone
-        if let Some(ref receiver) = mic_receiver {
-            loop {
-                match receiver.try_recv() {
-                    Ok(chunk) => {
-                        // Resample if necessary and apply volume
-                        let processed = process_audio_chunk(
-                            &chunk,
-                            config.sample_rate,
-                            config.channels,
-                            config.mic_volume,
-                        );
-                        mic_buffer.extend(processed);
-                        timestamp = chunk.timestamp;
+        if let Some(ref mut receiver) = mic_receiver {
+            while receiver.pop(&mut mic_chunk) {
+                let chunk = &mic_chunk;
+                // Resample if necessary and apply volume
+                let processed = if config.binaural {
+                    let samples =
+                        process_binaural_chunk(chunk, config.sample_rate, volumes.mic);
+                    match samples {
+                        Some(samples) => {
+                            mic_input.track(chunk, samples.len() / 2, 1.0);
+                            samples
+                        }
+                        None => {
+                            eprintln!(
+                                "Dropping {}ch {}Hz mic chunk: binaural mode needs 2ch at {}Hz",
+                                chunk.channels, chunk.sample_rate, config.sample_rate
+                            );
+                            continue;
+                        }
                     }
-                    Err(TryRecvError::Empty) => break,
-                    Err(TryRecvError::Disconnected) => break,
+                } else {
+                    mic_input.process(
+                        chunk,
+                        config.sample_rate,
+                        config.channels,
+                        volumes.mic,
+                        1.0,
+                    )
+                };
+                if let Some(ref mut drift) = drift {
+                    drift.record_mic(chunk.timestamp, processed.len());
+                }
+                origin.get_or_insert(chunk.timestamp.saturating_sub(gate_delay));
+                let processed = match gate {
+                    Some(ref mut gate) => gate.process(&processed),
+                    None => processed,
+                };
+                if processed.is_empty() {
+                    continue;
+                }
+                controls.meters.lock().process_mic(&processed);
+                if let Some(ref monitor) = *controls.monitor.lock() {
+                    let _ = monitor.try_send(AudioChunk {
+                        samples: processed.clone(),
+                        sample_rate: config.sample_rate,
+                        channels: config.channels,
+                        timestamp: chunk.timestamp,
+                    });
                 }
+                if let Some(ref captions) = *controls.captions.lock() {
+                    let _ = captions.try_send(AudioChunk {
+                        samples: processed.clone(),
+                        sample_rate: config.sample_rate,
+                        channels: config.channels,
+                        timestamp: chunk.timestamp,
+                    });
+                }
+                if let Some((SidecarWavSource::Mic, ref sidecar)) = *controls.sidecar.lock() {
+                    let _ = sidecar.try_send(AudioChunk {
+                        samples: processed.clone(),
+                        sample_rate: config.sample_rate,
+                        channels: config.channels,
+                        timestamp: chunk.timestamp,
+                    });
+                }
+                mic_buffer.extend(processed);
             }
         }
         
         // Collect samples from system audio
-        if let Some(ref receiver) = system_receiver {
-            loop {
-                match receiver.try_recv() {
-                    Ok(chunk) => {
-                        let processed = process_audio_chunk(
-                            &chunk,
-                            config.sample_rate,
-                            config.channels,
-                            config.system_volume,
-                        );
-                        system_buffer.extend(processed);
-                    }
-                    Err(TryRecvError::Empty) => break,
-                    Err(TryRecvError::Disconnected) => break,
+        if let Some(ref mut receiver) = system_receiver {
+            while receiver.pop(&mut system_chunk) {
+                let chunk = &system_chunk;
+                let speed = drift.as_ref().map_or(1.0, |drift| drift.step());
+                let processed = system_input.process(
+                    chunk,
+                    config.sample_rate,
+                    config.channels,
+                    volumes.system,
+                    speed,
+                );
+                if let Some(ref mut drift) = drift {
+                    drift.record_system(chunk.timestamp, processed.len());
                 }
+                controls.meters.lock().process_system(&processed);
+                system_buffer.extend(processed);
+                origin.get_or_insert(chunk.timestamp.saturating_sub(gate_delay));
+            }
+        }
+        
+        *controls.stats.lock() = MixerStats {
+            output_sample_rate: config.sample_rate,
+            mic: mic_receiver.as_ref().map(|receiver| SourceStats {
+                overflows: receiver.dropped(),
+                ..mic_input.stats
+            }),
+            system: system_receiver.as_ref().map(|receiver| SourceStats {
+                overflows: receiver.dropped(),
+                ..system_input.stats
+            }),
+            drift_offset_ms: drift.as_ref().map(|drift| drift.offset() * 1000.0),
+            drift_correction_ppm: drift.as_ref().map(|drift| (drift.step() - 1.0) * 1e6),
+        };
+        
+        // Once stopped, the sources are drained when their captures have
+        // closed the rings and everything in them has been read
+        let drained = stopped_at.is_some_and(|at| {
+            at.elapsed() >= STOP_DRAIN_TIMEOUT
+                || (mic_receiver.as_ref().is_none_or(|r| r.is_finished())
+                    && system_receiver.as_ref().is_none_or(|r| r.is_finished()))
+        });
+        if drained {
+            if let Some(mut gate) = gate.take() {
+                mic_buffer.extend(gate.flush());
             }
         }
         
-        // Mix when we have enough samples
-        while mic_buffer.len() >= samples_per_chunk || system_buffer.len() >= samples_per_chunk {
+        // Mix when we have enough samples, and the remainder after draining
+        loop {
+            let buffered = mic_buffer.len().max(system_buffer.len());
+            let chunk_samples = if buffered >= samples_per_chunk {
+                samples_per_chunk
+            } else if drained && buffered > 0 {
+                buffered
+            } else {
+                break;
+            };
             let mixed = mix_buffers(
                 &mut mic_buffer,
                 &mut system_buffer,
-                samples_per_chunk,
+                chunk_samples,
             );
             
             if !mixed.is_empty() {
+                controls.meters.lock().process_output(&mixed);
+                
+                let frames = (mixed.len() / config.channels as usize) as u64;
+                let timestamp = origin.unwrap_or_default()
+                    + Duration::from_secs_f64(emitted_frames as f64 / config.sample_rate as f64);
+                emitted_frames += frames;
+                
+                if let Some((SidecarWavSource::Mixed, ref sidecar)) = *controls.sidecar.lock() {
+                    let _ = sidecar.try_send(AudioChunk {
+                        samples: mixed.clone(),
+                        sample_rate: config.sample_rate,
+                        channels: config.channels,
+                        timestamp,
+                    });
+                }
+                if let Some(ref visualizer) = *controls.visualizer.lock() {
+                    let _ = visualizer.try_send(AudioChunk {
+                        samples: mixed.clone(),
+                        sample_rate: config.sample_rate,
+                        channels: config.channels,
+                        timestamp,
+                    });
+                }
+                
                 let chunk = MixedAudioChunk {
                     samples: mixed,
                     sample_rate: config.sample_rate,
@@ -199,44 +507,158 @@ fn mix_loop(
                     timestamp,
                 };
                 
-                let _ = output_sender.try_send(chunk);
+                // While stopping the encoder is draining too, so the last
+                // chunks wait for room instead of being dropped
+                if stopped_at.is_some() && !output_stalled {
+                    output_stalled = output_sender.send_timeout(chunk, STOP_DRAIN_TIMEOUT).is_err();
+                } else {
+                    let _ = output_sender.try_send(chunk);
+                }
             }
         }
         
-        // Small sleep to prevent busy waiting
-        std::thread::sleep(Duration::from_millis(5));
+        if drained {
+            break;
+        }
+        
+        // Sleep until either source delivers audio
+        let sources: Vec<&AudioConsumer> = mic_receiver.iter().chain(system_receiver.iter()).collect();
+        wait_any(&sources);
     }
 }
 
-/// Process an audio chunk: resample if needed and apply volume
-fn process_audio_chunk(
-    chunk: &AudioChunk,
-    target_sample_rate: u32,
-    target_channels: u16,
-    volume: f32,
-) -> Vec<f32> {
-    let mut samples = chunk.samples.clone();
+/// One source's conversion to the output format
+///
+/// The resampler carries its position from chunk to chunk, so a source at
+/// another rate yields exactly the output rate's worth of frames over time
+/// instead of losing a fraction of a frame at every chunk boundary.
+pub(crate) struct SourceInput {
+    name: &'static str,
+    resampler: StreamResampler,
+    /// Output frames expected from plain rate conversion, for drift counting
+    nominal_frames: f64,
+    stats: SourceStats,
+}
+
+impl SourceInput {
+    pub(crate) fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            resampler: StreamResampler::default(),
+            nominal_frames: 0.0,
+            stats: SourceStats::default(),
+        }
+    }
     
-    // Apply volume
-    for sample in &mut samples {
-        *sample *= volume;
+    /// Apply volume and convert to the output channels and rate, played
+    /// `speed` times faster to correct drift
+    pub(crate) fn process(
+        &mut self,
+        chunk: &AudioChunk,
+        target_sample_rate: u32,
+        target_channels: u16,
+        volume: f32,
+        speed: f64,
+    ) -> Vec<f32> {
+        if self.stats.chunks > 0 && chunk.sample_rate != self.stats.sample_rate {
+            println!(
+                "{} changed rate from {}Hz to {}Hz",
+                self.name, self.stats.sample_rate, chunk.sample_rate
+            );
+            self.stats.rate_changes += 1;
+            self.resampler = StreamResampler::default();
+        }
+        
+        let mut samples: Vec<f32> = chunk.samples.iter().map(|s| s * volume).collect();
+        if chunk.channels != target_channels {
+            samples = convert_channels(&samples, chunk.channels, target_channels);
+        }
+        
+        let step = chunk.sample_rate as f64 / target_sample_rate as f64 * speed;
+        if step != 1.0 || self.resampler.is_active() {
+            samples = self.resampler.process(&samples, target_channels, step);
+        }
+        
+        let ratio = target_sample_rate as f64 / chunk.sample_rate.max(1) as f64;
+        self.track(chunk, samples.len() / target_channels.max(1) as usize, ratio);
+        samples
     }
     
-    // Convert channels if needed
-    if chunk.channels != target_channels {
-        samples = convert_channels(&samples, chunk.channels, target_channels);
+    /// Count a chunk converted to `output_frames` frames
+    fn track(&mut self, chunk: &AudioChunk, output_frames: usize, ratio: f64) {
+        let input_frames = chunk.samples.len() / chunk.channels.max(1) as usize;
+        self.nominal_frames += input_frames as f64 * ratio;
+        self.stats.sample_rate = chunk.sample_rate;
+        self.stats.chunks += 1;
+        self.stats.input_frames += input_frames as u64;
+        self.stats.output_frames += output_frames as u64;
+        self.stats.corrected_frames = self.stats.output_frames as i64 - self.nominal_frames.round() as i64;
+    }
+}
+
+/// Linear interpolation resampler for a continuous stream
+///
+/// `step` is the number of input frames consumed per output frame and may
+/// change from chunk to chunk. Output lags the input by one frame.
+#[derive(Default)]
+struct StreamResampler {
+    /// Last frame of the previous chunk
+    previous: Vec<f32>,
+    /// Read position, in frames past `previous`
+    position: f64,
+}
+
+impl StreamResampler {
+    /// Whether earlier chunks left state behind
+    fn is_active(&self) -> bool {
+        !self.previous.is_empty()
     }
     
-    // Resample if needed (simple linear interpolation)
-    if chunk.sample_rate != target_sample_rate {
-        samples = resample(&samples, chunk.sample_rate, target_sample_rate, target_channels);
+    fn process(&mut self, samples: &[f32], channels: u16, step: f64) -> Vec<f32> {
+        let channels = channels.max(1) as usize;
+        let mut input = std::mem::take(&mut self.previous);
+        input.extend_from_slice(samples);
+        let frames = input.len() / channels;
+        
+        let mut output = Vec::with_capacity((samples.len() as f64 / step) as usize + channels);
+        let mut position = self.position;
+        while position + 1.0 < frames as f64 {
+            let index = position as usize;
+            let frac = (position - index as f64) as f32;
+            for ch in 0..channels {
+                let current = input[index * channels + ch];
+                let next = input[(index + 1) * channels + ch];
+                output.push(current + (next - current) * frac);
+            }
+            position += step;
+        }
+        
+        if frames > 0 {
+            self.position = position - (frames - 1) as f64;
+            self.previous = input[(frames - 1) * channels..frames * channels].to_vec();
+        }
+        output
+    }
+}
+
+/// Process a binaural chunk: apply volume equally to both ears, nothing else
+///
+/// Returns None if the chunk is not stereo at the target rate, since
+/// converting it would mix or resample the ears.
+fn process_binaural_chunk(
+    chunk: &AudioChunk,
+    target_sample_rate: u32,
+    volume: f32,
+) -> Option<Vec<f32>> {
+    if chunk.channels != 2 || chunk.sample_rate != target_sample_rate {
+        return None;
     }
     
-    samples
+    Some(chunk.samples.iter().map(|s| s * volume).collect())
 }
 
 /// Convert audio between channel counts
-fn convert_channels(samples: &[f32], from_channels: u16, to_channels: u16) -> Vec<f32> {
+pub(crate) fn convert_channels(samples: &[f32], from_channels: u16, to_channels: u16) -> Vec<f32> {
     if from_channels == to_channels {
         return samples.to_vec();
     }
@@ -271,7 +693,7 @@ fn convert_channels(samples: &[f32], from_channels: u16, to_channels: u16) -> Ve
 }
 
 /// Simple linear interpolation resampling
-fn resample(samples: &[f32], from_rate: u32, to_rate: u32, channels: u16) -> Vec<f32> {
+pub(crate) fn resample(samples: &[f32], from_rate: u32, to_rate: u32, channels: u16) -> Vec<f32> {
     if from_rate == to_rate {
         return samples.to_vec();
     }
@@ -300,6 +722,71 @@ fn resample(samples: &[f32], from_rate: u32, to_rate: u32, channels: u16) -> Vec
     output
 }
 
+/// Sample clock of one source, measured against its capture timestamps
+#[derive(Default)]
+struct SourceClock {
+    first: Option<Duration>,
+    frames: u64,
+    /// Smoothed seconds the samples run ahead of the timestamps
+    ahead: f64,
+}
+
+impl SourceClock {
+    /// Count a chunk of `frames` frames captured at `timestamp`
+    fn record(&mut self, timestamp: Duration, frames: usize, sample_rate: u32) {
+        let first = *self.first.get_or_insert(timestamp);
+        let ahead = self.frames as f64 / sample_rate as f64
+            - timestamp.saturating_sub(first).as_secs_f64();
+        self.ahead += (ahead - self.ahead) * DRIFT_SMOOTHING;
+        self.frames += frames as u64;
+    }
+}
+
+/// Keeps system audio locked to the microphone
+///
+/// Each source's sample count is compared with its chunk timestamps; the
+/// difference between the two is the drift between the device clocks. System
+/// audio is played a fraction of a percent faster or slower until it goes
+/// away. Both are measured after conversion, so the correction itself is
+/// accounted for.
+pub(crate) struct DriftCorrector {
+    sample_rate: u32,
+    channels: usize,
+    mic: SourceClock,
+    system: SourceClock,
+}
+
+impl DriftCorrector {
+    pub(crate) fn new(sample_rate: u32, channels: u16) -> Self {
+        Self {
+            sample_rate,
+            channels: channels.max(1) as usize,
+            mic: SourceClock::default(),
+            system: SourceClock::default(),
+        }
+    }
+
+    /// Count a converted microphone chunk of `samples` interleaved samples
+    pub(crate) fn record_mic(&mut self, timestamp: Duration, samples: usize) {
+        self.mic.record(timestamp, samples / self.channels, self.sample_rate);
+    }
+
+    /// Count a converted system audio chunk of `samples` interleaved samples
+    pub(crate) fn record_system(&mut self, timestamp: Duration, samples: usize) {
+        self.system.record(timestamp, samples / self.channels, self.sample_rate);
+    }
+
+    /// Seconds system audio runs ahead of the microphone
+    fn offset(&self) -> f64 {
+        self.system.ahead - self.mic.ahead
+    }
+
+    /// Speed to play system audio at
+    pub(crate) fn step(&self) -> f64 {
+        1.0 + (self.offset() / DRIFT_CORRECTION_SECS).clamp(-MAX_DRIFT_CORRECTION, MAX_DRIFT_CORRECTION)
+    }
+}
+
 /// Mix two audio buffers together
 fn mix_buffers(
     mic_buffer: &mut Vec<f32>,
@@ -380,4 +867,74 @@ mod tests {
         assert!((mono[0] - 0.5).abs() < 0.001);
         assert!((mono[1] - 0.5).abs() < 0.001);
     }
+    
+    #[test]
+    fn test_binaural_chunk_passthrough() {
+        let chunk = AudioChunk {
+            samples: vec![0.5, -0.25, 0.1, 0.0],
+            sample_rate: 48000,
+            channels: 2,
+            timestamp: Duration::ZERO,
+        };
+        
+        // Ears stay separate, only volume is applied
+        assert_eq!(process_binaural_chunk(&chunk, 48000, 2.0), Some(vec![1.0, -0.5, 0.2, 0.0]));
+        
+        // Anything that would need resampling or channel conversion is rejected
+        assert!(process_binaural_chunk(&chunk, 44100, 1.0).is_none());
+        let mono = AudioChunk { channels: 1, ..chunk };
+        assert!(process_binaural_chunk(&mono, 48000, 1.0).is_none());
+    }
+    
+    #[test]
+    fn test_drift_correction_locks_system_to_mic() {
+        let mut drift = DriftCorrector::new(48000, 1);
+        let mut system = SourceInput::new("System audio");
+        let mut chunk = AudioChunk {
+            samples: vec![0.0; 480],
+            sample_rate: 48000,
+            channels: 1,
+            timestamp: Duration::ZERO,
+        };
+        
+        // System clock runs 500 ppm fast: 480 frames every 9.995 ms, which
+        // would put it 30 ms ahead after a minute
+        for i in 0..6000u64 {
+            drift.record_mic(Duration::from_micros(i * 10_000), 480);
+            chunk.timestamp = Duration::from_nanos(i * 9_995_000);
+            let converted = system.process(&chunk, 48000, 1, 1.0, drift.step());
+            drift.record_system(chunk.timestamp, converted.len());
+        }
+        
+        assert!(drift.offset().abs() < 0.008, "system audio {:.4}s ahead", drift.offset());
+        assert!(drift.step() > 1.0004 && drift.step() <= 1.0 + MAX_DRIFT_CORRECTION);
+        assert!(system.stats.corrected_frames < -1000);
+    }
+    
+    #[test]
+    fn test_source_rate_conversion_is_exact() {
+        // 512-frame chunks at 44.1kHz are 557.28 frames at 48kHz: converting
+        // each chunk on its own would lose a frame every few chunks
+        let mut mic = SourceInput::new("Microphone");
+        let chunk = AudioChunk {
+            samples: vec![0.25; 1024],
+            sample_rate: 44100,
+            channels: 2,
+            timestamp: Duration::ZERO,
+        };
+        let mut frames = 0;
+        for _ in 0..1000 {
+            let converted = mic.process(&chunk, 48000, 2, 1.0, 1.0);
+            assert!(converted.iter().all(|&s| (s - 0.25).abs() < 1e-6));
+            frames += converted.len() / 2;
+        }
+        let expected = 512_000.0 * 48000.0 / 44100.0;
+        assert!((frames as f64 - expected).abs() <= 2.0, "{} frames, expected {:.1}", frames, expected);
+        assert!(mic.stats.corrected_frames.abs() <= 2);
+        
+        let other_rate = AudioChunk { sample_rate: 48000, ..chunk };
+        mic.process(&other_rate, 48000, 2, 1.0, 1.0);
+        assert_eq!(mic.stats.rate_changes, 1);
+        assert_eq!(mic.stats.sample_rate, 48000);
+    }
 }
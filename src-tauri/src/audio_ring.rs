@@ -0,0 +1,313 @@
+//! Audio Ring Buffer
+//!
+//! Captured audio travels from the device callback to the mixer through a
+//! single-producer, single-consumer ring allocated up front: the callback
+//! writes samples straight into it (no `Vec` per chunk, no lock) and the
+//! mixer copies them out into a buffer it reuses. The ring holds two
+//! seconds, so a busy mixer thread catches up instead of chunks being
+//! dropped; only a mixer stalled for longer than that loses audio, and
+//! those chunks are counted.
+//!
+//! Chunk boundaries are kept, with each chunk's timestamp, rate and channel
+//! count, so the mixer's drift correction sees the same chunks as before.
+//! Each push also leaves a wakeup token in a one-slot channel, so the mixer
+//! can sleep in `wait_any` until audio arrives.
+
+use crossbeam_channel::{bounded, Receiver, Select, Sender};
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::audio::AudioChunk;
+use crate::channel_wait::IDLE_WAIT;
+
+/// Audio the ring holds before chunks are dropped
+const RING_SECONDS: usize = 2;
+
+/// Chunk slots; enough for two seconds of 64-frame callbacks at 48kHz
+const CHUNK_SLOTS: usize = 2048;
+
+/// Where a chunk's samples are and what they are
+#[derive(Clone, Copy, Default)]
+struct ChunkInfo {
+    /// Position of the first sample, counted from the start of the ring
+    start: usize,
+    len: usize,
+    sample_rate: u32,
+    channels: u16,
+    timestamp: Duration,
+}
+
+struct Ring {
+    samples: Box<[UnsafeCell<f32>]>,
+    chunks: Box<[UnsafeCell<ChunkInfo>]>,
+    /// Chunks published by the producer
+    chunks_written: AtomicUsize,
+    /// Chunks and samples released by the consumer
+    chunks_read: AtomicUsize,
+    samples_read: AtomicUsize,
+    /// Chunks dropped because the ring was full
+    dropped: AtomicU64,
+    /// The producer is gone
+    closed: AtomicBool,
+}
+
+// SAFETY: a slot is only written by the producer while it is free and only
+// read by the consumer once published; the counters order the two
+unsafe impl Sync for Ring {}
+
+/// Writing end, owned by the capture callback
+pub struct AudioProducer {
+    ring: Arc<Ring>,
+    /// Next sample position and chunk to write
+    sample_tail: usize,
+    chunk_tail: usize,
+    wake: Sender<()>,
+}
+
+/// Reading end, owned by the mixer
+pub struct AudioConsumer {
+    ring: Arc<Ring>,
+    wake: Receiver<()>,
+}
+
+/// A ring with room for `capacity` samples
+pub fn audio_ring(capacity: usize) -> (AudioProducer, AudioConsumer) {
+    let ring = Arc::new(Ring {
+        samples: (0..capacity.max(1)).map(|_| UnsafeCell::new(0.0)).collect(),
+        chunks: (0..CHUNK_SLOTS).map(|_| UnsafeCell::new(ChunkInfo::default())).collect(),
+        chunks_written: AtomicUsize::new(0),
+        chunks_read: AtomicUsize::new(0),
+        samples_read: AtomicUsize::new(0),
+        dropped: AtomicU64::new(0),
+        closed: AtomicBool::new(false),
+    });
+    // A full token slot already means "wake up", so pushes never block on it
+    let (wake_sender, wake) = bounded(1);
+    (
+        AudioProducer {
+            ring: ring.clone(),
+            sample_tail: 0,
+            chunk_tail: 0,
+            wake: wake_sender,
+        },
+        AudioConsumer { ring, wake },
+    )
+}
+
+/// Ring capacity in samples for audio at `sample_rate` with `channels`
+pub fn ring_capacity(sample_rate: u32, channels: u16) -> usize {
+    sample_rate as usize * channels.max(1) as usize * RING_SECONDS
+}
+
+impl AudioProducer {
+    /// Write a chunk of exactly `len` samples from `samples`
+    ///
+    /// Never blocks or allocates. Returns false, dropping the chunk, when
+    /// the ring has no room for it.
+    pub fn push(
+        &mut self,
+        timestamp: Duration,
+        sample_rate: u32,
+        channels: u16,
+        len: usize,
+        samples: impl IntoIterator<Item = f32>,
+    ) -> bool {
+        let ring = &*self.ring;
+        let capacity = ring.samples.len();
+        let chunks_free = CHUNK_SLOTS - (self.chunk_tail - ring.chunks_read.load(Ordering::Acquire));
+        let samples_free = capacity - (self.sample_tail - ring.samples_read.load(Ordering::Acquire));
+        if chunks_free == 0 || len > samples_free {
+            ring.dropped.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+
+        let start = self.sample_tail;
+        let mut written = 0;
+        for sample in samples.into_iter().take(len) {
+            // SAFETY: positions start..start + len are free (checked above)
+            unsafe { *ring.samples[(start + written) % capacity].get() = sample };
+            written += 1;
+        }
+        // SAFETY: the slot at chunk_tail is free (checked above)
+        unsafe {
+            *ring.chunks[self.chunk_tail % CHUNK_SLOTS].get() = ChunkInfo {
+                start,
+                len: written,
+                sample_rate,
+                channels,
+                timestamp,
+            };
+        }
+        self.sample_tail += written;
+        self.chunk_tail += 1;
+        ring.chunks_written.store(self.chunk_tail, Ordering::Release);
+        let _ = self.wake.try_send(());
+        true
+    }
+
+    /// Write a whole chunk
+    pub fn push_chunk(&mut self, chunk: &AudioChunk) -> bool {
+        self.push(
+            chunk.timestamp,
+            chunk.sample_rate,
+            chunk.channels,
+            chunk.samples.len(),
+            chunk.samples.iter().copied(),
+        )
+    }
+
+    /// Whether a chunk of `len` samples fits right now
+    pub fn fits(&self, len: usize) -> bool {
+        let ring = &*self.ring;
+        self.chunk_tail - ring.chunks_read.load(Ordering::Acquire) < CHUNK_SLOTS
+            && self.sample_tail - ring.samples_read.load(Ordering::Acquire) + len <= ring.samples.len()
+    }
+
+    /// Whether the reading end has been dropped
+    pub fn is_disconnected(&self) -> bool {
+        Arc::strong_count(&self.ring) == 1
+    }
+}
+
+impl Drop for AudioProducer {
+    fn drop(&mut self) {
+        self.ring.closed.store(true, Ordering::Release);
+    }
+}
+
+impl AudioConsumer {
+    /// Move the oldest chunk into `chunk`, reusing its sample buffer;
+    /// false when the ring is empty
+    pub fn pop(&mut self, chunk: &mut AudioChunk) -> bool {
+        let ring = &*self.ring;
+        let head = ring.chunks_read.load(Ordering::Relaxed);
+        if head == ring.chunks_written.load(Ordering::Acquire) {
+            return false;
+        }
+
+        // SAFETY: chunk `head` and its samples were published and aren't
+        // reused until chunks_read and samples_read move past them
+        let info = unsafe { *ring.chunks[head % CHUNK_SLOTS].get() };
+        let capacity = ring.samples.len();
+        chunk.samples.clear();
+        chunk
+            .samples
+            .extend((info.start..info.start + info.len).map(|i| unsafe { *ring.samples[i % capacity].get() }));
+        chunk.sample_rate = info.sample_rate;
+        chunk.channels = info.channels;
+        chunk.timestamp = info.timestamp;
+
+        ring.samples_read.store(info.start + info.len, Ordering::Release);
+        ring.chunks_read.store(head + 1, Ordering::Release);
+        true
+    }
+
+    /// The producer is gone and every chunk has been read
+    pub fn is_finished(&self) -> bool {
+        let ring = &*self.ring;
+        ring.closed.load(Ordering::Acquire)
+            && ring.chunks_read.load(Ordering::Relaxed) == ring.chunks_written.load(Ordering::Acquire)
+    }
+
+    /// Chunks dropped so far because the ring was full
+    pub fn dropped(&self) -> u64 {
+        self.ring.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Size of the ring in samples
+    pub fn capacity(&self) -> usize {
+        self.ring.samples.len()
+    }
+}
+
+/// Block until one of `consumers` has been pushed to since the last wait,
+/// or `IDLE_WAIT` passes
+///
+/// Rings whose producer is gone aren't waited on: their token channel is
+/// closed and would always be ready.
+pub fn wait_any(consumers: &[&AudioConsumer]) {
+    let open: Vec<&AudioConsumer> = consumers
+        .iter()
+        .copied()
+        .filter(|consumer| !consumer.ring.closed.load(Ordering::Acquire))
+        .collect();
+    if open.is_empty() {
+        std::thread::sleep(IDLE_WAIT);
+        return;
+    }
+
+    let mut select = Select::new();
+    for consumer in &open {
+        select.recv(&consumer.wake);
+    }
+    // Taking the token before draining means a push during the drain wakes
+    // the next wait instead of being missed
+    if let Ok(operation) = select.select_timeout(IDLE_WAIT) {
+        let index = operation.index();
+        let _ = operation.recv(&open[index].wake);
+    }
+}
+
+/// An empty chunk for `AudioConsumer::pop` to fill
+pub fn empty_chunk() -> AudioChunk {
+    AudioChunk {
+        samples: Vec::new(),
+        sample_rate: 0,
+        channels: 0,
+        timestamp: Duration::ZERO,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_keeps_chunks_and_wraps() {
+        let (mut producer, mut consumer) = audio_ring(8);
+        let mut chunk = empty_chunk();
+        assert!(!consumer.pop(&mut chunk));
+
+        assert!(producer.push(Duration::from_millis(10), 48000, 2, 4, [1.0, 2.0, 3.0, 4.0]));
+        assert!(producer.push(Duration::from_millis(20), 48000, 2, 2, [5.0, 6.0]));
+        // Only two samples left
+        assert!(!producer.push(Duration::from_millis(30), 48000, 2, 4, [0.0; 4]));
+        assert_eq!(consumer.dropped(), 1);
+
+        assert!(consumer.pop(&mut chunk));
+        assert_eq!(chunk.samples, vec![1.0, 2.0, 3.0, 4.0]);
+        assert_eq!((chunk.timestamp, chunk.sample_rate, chunk.channels), (Duration::from_millis(10), 48000, 2));
+
+        // Wraps around the end of the buffer
+        assert!(producer.push(Duration::from_millis(40), 44100, 1, 6, [7.0, 8.0, 9.0, 10.0, 11.0, 12.0]));
+        assert!(consumer.pop(&mut chunk));
+        assert_eq!(chunk.samples, vec![5.0, 6.0]);
+        assert!(consumer.pop(&mut chunk));
+        assert_eq!(chunk.samples, vec![7.0, 8.0, 9.0, 10.0, 11.0, 12.0]);
+        assert_eq!(chunk.sample_rate, 44100);
+        assert!(!consumer.pop(&mut chunk));
+
+        // Across threads, in order
+        let (mut producer, mut consumer) = audio_ring(ring_capacity(1000, 1));
+        let writer = std::thread::spawn(move || {
+            for i in 0..5000u32 {
+                while !producer.fits(3) {
+                    std::thread::yield_now();
+                }
+                assert!(producer.push(Duration::ZERO, 1000, 1, 3, [i as f32; 3]));
+            }
+        });
+        let mut next = 0;
+        while !consumer.is_finished() {
+            if consumer.pop(&mut chunk) {
+                assert_eq!(chunk.samples, vec![next as f32; 3]);
+                next += 1;
+            }
+        }
+        writer.join().unwrap();
+        assert_eq!(next, 5000);
+        assert_eq!(consumer.dropped(), 0);
+    }
+}
@@ -0,0 +1,507 @@
+//! Live Captions
+//!
+//! Runs streaming speech recognition on the processed microphone signal. The
+//! current line is published for the compositor to burn into the video, and
+//! finished lines can be written to a WebVTT file next to the recording.
+//!
+//! Recognition (Vosk) and text rendering (ab_glyph) need the `captions`
+//! feature; without it, enabling captions fails when the recording starts.
+
+use crossbeam_channel::{bounded, Receiver, RecvTimeoutError, Sender};
+use image::RgbaImage;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::audio::AudioChunk;
+use crate::audio_mixer::{convert_channels, resample};
+
+/// Sample rate fed to the recognizer
+const RECOGNIZER_SAMPLE_RATE: u32 = 16000;
+
+/// How long a finished line stays on screen without new speech
+const CAPTION_HOLD: Duration = Duration::from_secs(3);
+
+/// Lines of text shown at once (older words scroll off)
+const MAX_CAPTION_LINES: usize = 2;
+
+#[cfg(not(feature = "captions"))]
+const FEATURE_MISSING: &str = "Live captions require a build with the `captions` feature";
+
+/// Live caption configuration
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CaptionsConfig {
+    /// Run speech recognition while recording
+    #[serde(default)]
+    pub enabled: bool,
+    /// Vosk model directory
+    #[serde(default)]
+    pub model_path: Option<PathBuf>,
+    /// Draw the current line into the video
+    #[serde(default)]
+    pub burn_in: bool,
+    /// Write finished lines to `<recording>.vtt`
+    #[serde(default)]
+    pub write_vtt: bool,
+    /// Font for burned-in captions (default: a system sans-serif)
+    #[serde(default)]
+    pub font_path: Option<PathBuf>,
+}
+
+impl CaptionsConfig {
+    /// Check an enabled config has a model and an output
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.enabled {
+            return Ok(());
+        }
+        if self.model_path.is_none() {
+            return Err("Live captions need a speech recognition model path".to_string());
+        }
+        if !self.burn_in && !self.write_vtt {
+            return Err("Live captions need burn-in, a VTT file, or both".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Output of a streaming recognizer
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(not(feature = "captions"), allow(dead_code))]
+pub enum RecognizerEvent {
+    /// The current utterance so far (may still change)
+    Partial(String),
+    /// A finished utterance
+    Final(String),
+}
+
+/// Streaming speech recognizer fed with 16kHz mono samples
+pub trait SpeechRecognizer: Send {
+    /// Feed samples; returns an event when the recognized text changes
+    fn accept(&mut self, samples: &[i16]) -> Option<RecognizerEvent>;
+    /// Flush the recognizer and return the last utterance, if any
+    fn finish(&mut self) -> Option<String>;
+}
+
+/// One WebVTT cue
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cue {
+    pub start: Duration,
+    pub end: Duration,
+    pub text: String,
+}
+
+/// Turns recognizer events into timed cues
+#[derive(Debug, Default)]
+pub struct CaptionTimeline {
+    /// Capture time of the first audio of the current utterance
+    utterance_start: Option<Duration>,
+}
+
+impl CaptionTimeline {
+    /// Note that speech is in progress at `at`
+    pub fn partial(&mut self, at: Duration) {
+        self.utterance_start.get_or_insert(at);
+    }
+
+    /// Close the current utterance at `at` as a cue
+    pub fn finish(&mut self, text: &str, at: Duration) -> Option<Cue> {
+        let start = self.utterance_start.take().unwrap_or(at);
+        let text = text.trim();
+        if text.is_empty() {
+            return None;
+        }
+        Some(Cue {
+            start,
+            // Zero-length cues are dropped by players
+            end: at.max(start + Duration::from_millis(500)),
+            text: text.to_string(),
+        })
+    }
+}
+
+/// Writes cues to a WebVTT file as they are finished
+pub struct VttWriter {
+    writer: BufWriter<File>,
+}
+
+impl VttWriter {
+    /// Create the file and write the header
+    pub fn create(path: &Path) -> Result<Self, String> {
+        let file = File::create(path)
+            .map_err(|e| format!("Failed to create caption file {}: {}", path.display(), e))?;
+        let mut writer = BufWriter::new(file);
+        writer
+            .write_all(b"WEBVTT\n\n")
+            .map_err(|e| format!("Failed to write caption file: {}", e))?;
+        Ok(Self { writer })
+    }
+
+    /// Append a cue (flushed so the file is usable if the app exits)
+    pub fn write_cue(&mut self, cue: &Cue) -> Result<(), String> {
+        write!(
+            self.writer,
+            "{} --> {}\n{}\n\n",
+            vtt_timestamp(cue.start),
+            vtt_timestamp(cue.end),
+            cue.text
+        )
+        .and_then(|_| self.writer.flush())
+        .map_err(|e| format!("Failed to write caption file: {}", e))
+    }
+}
+
+/// Path of the caption file for a recording
+pub fn vtt_path(output_path: &Path) -> PathBuf {
+    output_path.with_extension("vtt")
+}
+
+/// Format a time as a WebVTT timestamp (HH:MM:SS.mmm)
+fn vtt_timestamp(time: Duration) -> String {
+    let ms = time.as_millis();
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+/// Runs recognition on a worker thread fed by the mixer's caption tap
+pub struct CaptionEngine {
+    running: Arc<Mutex<bool>>,
+    sender: Sender<AudioChunk>,
+    text: Arc<Mutex<Option<String>>>,
+}
+
+impl CaptionEngine {
+    /// Load the model and start recognizing
+    ///
+    /// `sample_rate` and `channels` describe the chunks the mixer will send.
+    pub fn start(
+        config: &CaptionsConfig,
+        output_path: &Path,
+        sample_rate: u32,
+        channels: u16,
+    ) -> Result<Self, String> {
+        config.validate()?;
+        let model_path = config.model_path.as_deref().ok_or("No caption model path")?;
+        let recognizer = open_recognizer(model_path)?;
+        let vtt = if config.write_vtt {
+            Some(VttWriter::create(&vtt_path(output_path))?)
+        } else {
+            None
+        };
+
+        let (sender, receiver) = bounded(100);
+        let running = Arc::new(Mutex::new(true));
+        let text = Arc::new(Mutex::new(None));
+
+        let running_clone = running.clone();
+        let text_clone = text.clone();
+        std::thread::spawn(move || {
+            caption_loop(
+                running_clone,
+                receiver,
+                recognizer,
+                vtt,
+                text_clone,
+                sample_rate,
+                channels,
+            );
+        });
+
+        println!("Live captions started (model: {})", model_path.display());
+
+        Ok(Self {
+            running,
+            sender,
+            text,
+        })
+    }
+
+    /// Sender for the mixer's caption tap
+    pub fn sender(&self) -> Sender<AudioChunk> {
+        self.sender.clone()
+    }
+
+    /// The line currently on screen (shared with the compositor overlay)
+    pub fn text(&self) -> Arc<Mutex<Option<String>>> {
+        self.text.clone()
+    }
+
+    /// Stop recognizing; the last utterance is written out
+    pub fn stop(&self) {
+        *self.running.lock() = false;
+    }
+}
+
+impl Drop for CaptionEngine {
+    fn drop(&mut self) {
+        *self.running.lock() = false;
+    }
+}
+
+fn caption_loop(
+    running: Arc<Mutex<bool>>,
+    receiver: Receiver<AudioChunk>,
+    mut recognizer: Box<dyn SpeechRecognizer>,
+    mut vtt: Option<VttWriter>,
+    text: Arc<Mutex<Option<String>>>,
+    sample_rate: u32,
+    channels: u16,
+) {
+    let mut timeline = CaptionTimeline::default();
+    let mut position = Duration::ZERO;
+    let mut shown_at = Instant::now();
+
+    let mut write_cue = |cue: Option<Cue>| {
+        if let (Some(cue), Some(writer)) = (cue, vtt.as_mut()) {
+            if let Err(e) = writer.write_cue(&cue) {
+                eprintln!("{}", e);
+            }
+        }
+    };
+
+    while *running.lock() {
+        // Take a finished line off screen once the speaker has gone quiet
+        if shown_at.elapsed() > CAPTION_HOLD {
+            *text.lock() = None;
+        }
+
+        let chunk = match receiver.recv_timeout(Duration::from_millis(100)) {
+            Ok(chunk) => chunk,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+
+        let frames = chunk.samples.len() / channels.max(1) as usize;
+        position = chunk.timestamp + Duration::from_secs_f64(frames as f64 / sample_rate as f64);
+
+        let mono = convert_channels(&chunk.samples, channels, 1);
+        let pcm: Vec<i16> = resample(&mono, sample_rate, RECOGNIZER_SAMPLE_RATE, 1)
+            .iter()
+            .map(|s| (s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)
+            .collect();
+
+        match recognizer.accept(&pcm) {
+            Some(RecognizerEvent::Partial(partial)) if !partial.trim().is_empty() => {
+                timeline.partial(chunk.timestamp);
+                *text.lock() = Some(partial);
+                shown_at = Instant::now();
+            }
+            Some(RecognizerEvent::Final(line)) => {
+                write_cue(timeline.finish(&line, position));
+                if !line.trim().is_empty() {
+                    *text.lock() = Some(line);
+                    shown_at = Instant::now();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(line) = recognizer.finish() {
+        write_cue(timeline.finish(&line, position));
+    }
+    *text.lock() = None;
+    println!("Live captions stopped");
+}
+
+#[cfg(feature = "captions")]
+fn open_recognizer(model_path: &Path) -> Result<Box<dyn SpeechRecognizer>, String> {
+    let model = vosk::Model::new(model_path.to_string_lossy())
+        .ok_or_else(|| format!("Failed to load caption model from {}", model_path.display()))?;
+    let recognizer = vosk::Recognizer::new(&model, RECOGNIZER_SAMPLE_RATE as f32)
+        .ok_or("Failed to create speech recognizer")?;
+    Ok(Box::new(VoskRecognizer {
+        recognizer,
+        last_partial: String::new(),
+    }))
+}
+
+#[cfg(not(feature = "captions"))]
+fn open_recognizer(_model_path: &Path) -> Result<Box<dyn SpeechRecognizer>, String> {
+    Err(FEATURE_MISSING.to_string())
+}
+
+#[cfg(feature = "captions")]
+struct VoskRecognizer {
+    recognizer: vosk::Recognizer,
+    last_partial: String,
+}
+
+#[cfg(feature = "captions")]
+impl SpeechRecognizer for VoskRecognizer {
+    fn accept(&mut self, samples: &[i16]) -> Option<RecognizerEvent> {
+        match self.recognizer.accept_waveform(samples) {
+            Ok(vosk::DecodingState::Finalized) => {
+                self.last_partial.clear();
+                let text = self.recognizer.result().single()?.text.to_string();
+                Some(RecognizerEvent::Final(text))
+            }
+            Ok(vosk::DecodingState::Running) => {
+                let partial = self.recognizer.partial_result().partial;
+                if partial == self.last_partial {
+                    return None;
+                }
+                self.last_partial = partial.to_string();
+                Some(RecognizerEvent::Partial(self.last_partial.clone()))
+            }
+            _ => None,
+        }
+    }
+
+    fn finish(&mut self) -> Option<String> {
+        let text = self.recognizer.final_result().single()?.text.to_string();
+        (!text.trim().is_empty()).then_some(text)
+    }
+}
+
+/// Draws the current caption line at the bottom of composited frames
+pub struct CaptionOverlay {
+    text: Arc<Mutex<Option<String>>>,
+    #[cfg(feature = "captions")]
+    font: ab_glyph::FontVec,
+}
+
+impl CaptionOverlay {
+    /// Load the caption font (the configured one, or the first system font found)
+    #[cfg(feature = "captions")]
+    pub fn new(text: Arc<Mutex<Option<String>>>, font_path: Option<&Path>) -> Result<Self, String> {
+        let font = crate::text_overlay::load_font(font_path)
+            .ok_or("No usable caption font found (set captions.fontPath)")?;
+        Ok(Self { text, font })
+    }
+
+    #[cfg(not(feature = "captions"))]
+    pub fn new(_text: Arc<Mutex<Option<String>>>, _font_path: Option<&Path>) -> Result<Self, String> {
+        Err(FEATURE_MISSING.to_string())
+    }
+
+    /// The line to draw, if any
+    pub fn current_text(&self) -> Option<String> {
+        self.text.lock().clone()
+    }
+
+    /// Draw `text` centered near the bottom of the frame on a dark band, faded by `opacity`
+    #[cfg(feature = "captions")]
+    pub fn draw(&self, image: &mut RgbaImage, text: &str, opacity: f32) {
+        use ab_glyph::{point, Font, PxScale, ScaleFont};
+
+        let (width, height) = image.dimensions();
+        let scale = PxScale::from((height as f32 * 0.045).max(12.0));
+        let font = self.font.as_scaled(scale);
+        let measure = |s: &str| -> f32 {
+            s.chars().map(|c| font.h_advance(font.glyph_id(c))).sum()
+        };
+
+        let lines = wrap_lines(text, width as f32 * 0.8, measure);
+        let padding = (scale.y * 0.3).ceil();
+        let line_height = font.height() + font.line_gap();
+        let block_height = line_height * lines.len() as f32;
+        let mut top = height as f32 * 0.94 - block_height;
+
+        for line in &lines {
+            let line_width = measure(line);
+            let left = (width as f32 - line_width) / 2.0;
+
+            // Background band behind the line
+            let x0 = (left - padding).max(0.0) as u32;
+            let x1 = ((left + line_width + padding) as u32).min(width);
+            let y0 = (top - padding / 2.0).max(0.0) as u32;
+            let y1 = ((top + line_height + padding / 2.0) as u32).min(height);
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    blend(image, x, y, [0, 0, 0], 0.6 * opacity);
+                }
+            }
+
+            let mut caret = left;
+            for c in line.chars() {
+                let id = font.glyph_id(c);
+                let glyph = id.with_scale_and_position(scale, point(caret, top + font.ascent()));
+                caret += font.h_advance(id);
+                let Some(outlined) = self.font.outline_glyph(glyph) else {
+                    continue;
+                };
+                let bounds = outlined.px_bounds();
+                outlined.draw(|gx, gy, coverage| {
+                    let x = bounds.min.x as i32 + gx as i32;
+                    let y = bounds.min.y as i32 + gy as i32;
+                    if x >= 0 && y >= 0 && (x as u32) < width && (y as u32) < height {
+                        blend(image, x as u32, y as u32, [255, 255, 255], coverage * opacity);
+                    }
+                });
+            }
+            top += line_height;
+        }
+    }
+
+    #[cfg(not(feature = "captions"))]
+    pub fn draw(&self, _image: &mut RgbaImage, _text: &str, _opacity: f32) {}
+}
+
+#[cfg(feature = "captions")]
+fn blend(image: &mut RgbaImage, x: u32, y: u32, color: [u8; 3], alpha: f32) {
+    let pixel = image.get_pixel_mut(x, y);
+    for (channel, value) in pixel.0.iter_mut().zip(color) {
+        *channel = (*channel as f32 * (1.0 - alpha) + value as f32 * alpha).round() as u8;
+    }
+}
+
+/// Break text into lines no wider than `max_width`, keeping only the last
+/// `MAX_CAPTION_LINES` so the newest words stay visible
+#[cfg_attr(not(feature = "captions"), allow(dead_code))]
+fn wrap_lines(text: &str, max_width: f32, measure: impl Fn(&str) -> f32) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+
+    for word in text.split_whitespace() {
+        let candidate = if current.is_empty() {
+            word.to_string()
+        } else {
+            format!("{} {}", current, word)
+        };
+        if measure(&candidate) > max_width && !current.is_empty() {
+            lines.push(std::mem::replace(&mut current, word.to_string()));
+        } else {
+            current = candidate;
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+
+    let skip = lines.len().saturating_sub(MAX_CAPTION_LINES);
+    lines.split_off(skip)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_caption_cues_and_wrapping() {
+        let mut timeline = CaptionTimeline::default();
+        timeline.partial(Duration::from_millis(1200));
+        timeline.partial(Duration::from_millis(1500));
+        let cue = timeline.finish(" hello there ", Duration::from_millis(2750)).unwrap();
+        assert_eq!(cue.text, "hello there");
+        assert_eq!(
+            format!("{} --> {}", vtt_timestamp(cue.start), vtt_timestamp(cue.end)),
+            "00:00:01.200 --> 00:00:02.750"
+        );
+        assert!(timeline.finish("  ", Duration::from_secs(3)).is_none());
+        assert_eq!(vtt_timestamp(Duration::from_millis(3_723_004)), "01:02:03.004");
+
+        // One unit per character, ten per line
+        let lines = wrap_lines("one two three four five six seven", 10.0, |s| s.len() as f32);
+        assert_eq!(lines, vec!["five six", "seven"]);
+    }
+}
@@ -0,0 +1,138 @@
+//! Capture Backends
+//!
+//! What the manager needs from a source of screen frames, webcam frames or
+//! audio, so the devices and the synthetic generators (see
+//! `synthetic_sources`) can stand in for each other. Each backend hands out
+//! its receiver once, delivers between `start` and `stop`, and reports its
+//! health the way the device it replaces does.
+
+use crossbeam_channel::Receiver;
+
+use crate::audio::{DeviceTiming, MicrophoneCapture, MicrophoneState};
+use crate::audio_ring::AudioConsumer;
+use crate::screen::{ScreenCapture, ScreenFrame};
+use crate::system_audio::SystemAudioCapture;
+use crate::webcam::{WebcamCapture, WebcamFrame, WebcamState};
+
+/// A source of screen frames
+pub trait ScreenCaptureBackend: Send {
+    /// Size of the frames delivered
+    fn dimensions(&self) -> (u32, u32);
+    fn take_receiver(&mut self) -> Option<Receiver<ScreenFrame>>;
+    fn start(&self) -> Result<(), String>;
+    fn stop(&self);
+    /// Frames and "nothing changed" results so far, for the stall watchdog
+    fn heartbeats(&self) -> u64;
+    /// Start over after a stall
+    fn restart(&mut self) -> Result<(), String>;
+}
+
+/// A source of webcam frames
+pub trait WebcamCaptureBackend: Send {
+    fn take_receiver(&mut self) -> Option<Receiver<WebcamFrame>>;
+    fn start(&self) -> Result<(), String>;
+    fn stop(&self);
+    fn state(&self) -> WebcamState;
+}
+
+/// A source of audio chunks
+pub trait AudioCaptureBackend: Send {
+    fn take_receiver(&mut self) -> Option<AudioConsumer>;
+    fn start(&self) -> Result<(), String>;
+    fn stop(&self);
+}
+
+/// A source of microphone audio
+pub trait MicrophoneCaptureBackend: AudioCaptureBackend {
+    fn state(&self) -> MicrophoneState;
+    /// Size and delay of the latest callback, when there is a device
+    fn timing(&self) -> Option<DeviceTiming>;
+    /// Open the device again after a stall
+    fn reopen(&self);
+}
+
+impl ScreenCaptureBackend for ScreenCapture {
+    fn dimensions(&self) -> (u32, u32) {
+        ScreenCapture::dimensions(self)
+    }
+
+    fn take_receiver(&mut self) -> Option<Receiver<ScreenFrame>> {
+        ScreenCapture::take_receiver(self)
+    }
+
+    fn start(&self) -> Result<(), String> {
+        ScreenCapture::start(self)
+    }
+
+    fn stop(&self) {
+        ScreenCapture::stop(self)
+    }
+
+    fn heartbeats(&self) -> u64 {
+        ScreenCapture::heartbeats(self)
+    }
+
+    fn restart(&mut self) -> Result<(), String> {
+        ScreenCapture::restart(self)
+    }
+}
+
+impl WebcamCaptureBackend for WebcamCapture {
+    fn take_receiver(&mut self) -> Option<Receiver<WebcamFrame>> {
+        WebcamCapture::take_receiver(self)
+    }
+
+    fn start(&self) -> Result<(), String> {
+        WebcamCapture::start(self)
+    }
+
+    fn stop(&self) {
+        WebcamCapture::stop(self)
+    }
+
+    fn state(&self) -> WebcamState {
+        WebcamCapture::state(self)
+    }
+}
+
+impl AudioCaptureBackend for MicrophoneCapture {
+    fn take_receiver(&mut self) -> Option<AudioConsumer> {
+        MicrophoneCapture::take_receiver(self)
+    }
+
+    fn start(&self) -> Result<(), String> {
+        MicrophoneCapture::start(self)
+    }
+
+    fn stop(&self) {
+        MicrophoneCapture::stop(self)
+    }
+}
+
+impl MicrophoneCaptureBackend for MicrophoneCapture {
+    fn state(&self) -> MicrophoneState {
+        MicrophoneCapture::state(self)
+    }
+
+    fn timing(&self) -> Option<DeviceTiming> {
+        MicrophoneCapture::timing(self)
+    }
+
+    fn reopen(&self) {
+        MicrophoneCapture::reopen(self)
+    }
+}
+
+impl AudioCaptureBackend for SystemAudioCapture {
+    fn take_receiver(&mut self) -> Option<AudioConsumer> {
+        SystemAudioCapture::take_receiver(self)
+    }
+
+    fn start(&self) -> Result<(), String> {
+        SystemAudioCapture::start(self)
+    }
+
+    fn stop(&self) {
+        SystemAudioCapture::stop(self)
+    }
+}
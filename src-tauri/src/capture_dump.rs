@@ -0,0 +1,818 @@
+//! Raw Capture Dumps
+//!
+//! A debugging aid: with `captureDump.enabled`, every screen and webcam frame
+//! and every microphone and system audio chunk is written to
+//! `<recording>.dump/` exactly as the capture delivered it, before the
+//! compositor, mixer or encoder see it. Records go into fixed-size segment
+//! files and the oldest segment is deleted once the dump grows past its size
+//! limit, so a long session keeps only its last minutes.
+//!
+//! `manifest.json` holds the recording's config, so `replay_capture_dump`
+//! can run the dump back through the same pipeline at its original pace,
+//! without any capture devices. A pipeline bug a user hit can then be
+//! reproduced from their dump rather than their hardware.
+
+use crossbeam_channel::{bounded, Receiver, RecvTimeoutError, SendTimeoutError, Sender, TrySendError};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::audio::AudioChunk;
+use crate::audio_ring::{audio_ring, empty_chunk, ring_capacity, wait_any, AudioConsumer, AudioProducer};
+use crate::frame_pool::FrameBuffer;
+use crate::recording::{CapturePixelFormat, RecordingConfig};
+use crate::screen::ScreenFrame;
+use crate::webcam::WebcamFrame;
+
+/// Format version written to the manifest
+const DUMP_VERSION: u32 = 1;
+
+/// First bytes of every segment file
+const SEGMENT_MAGIC: &[u8; 8] = b"ASMRDMP1";
+
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// Allowed dump size limits, in megabytes
+pub const DUMP_SIZE_RANGE_MB: std::ops::RangeInclusive<u32> = 64..=65536;
+
+/// Segments per size limit: the dump is pruned a quarter at a time
+const SEGMENTS_PER_LIMIT: u64 = 4;
+
+/// Records waiting to be written before new ones are dropped
+const WRITE_QUEUE: usize = 256;
+
+/// Capacity of the channels replayed records are sent on
+const REPLAY_QUEUE: usize = 30;
+
+/// How often blocked workers check whether they should stop
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How often audio replays check a full ring for room
+const AUDIO_POLL_INTERVAL: Duration = Duration::from_millis(2);
+
+/// Raw capture dump settings
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CaptureDumpConfig {
+    /// Write captured frames and audio to `<recording>.dump/`
+    #[serde(default)]
+    pub enabled: bool,
+    /// Size the dump is kept under; the oldest records are deleted past it
+    #[serde(default = "default_max_megabytes")]
+    pub max_megabytes: u32,
+}
+
+fn default_max_megabytes() -> u32 {
+    2048
+}
+
+impl Default for CaptureDumpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_megabytes: default_max_megabytes(),
+        }
+    }
+}
+
+impl CaptureDumpConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !DUMP_SIZE_RANGE_MB.contains(&self.max_megabytes) {
+            return Err(format!(
+                "Capture dump size must be {}-{} MB (got {})",
+                DUMP_SIZE_RANGE_MB.start(),
+                DUMP_SIZE_RANGE_MB.end(),
+                self.max_megabytes
+            ));
+        }
+        Ok(())
+    }
+
+    fn max_bytes(&self) -> u64 {
+        self.max_megabytes as u64 * 1024 * 1024
+    }
+}
+
+/// Directory a recording's dump is written to
+pub fn dump_dir(recording: &Path) -> PathBuf {
+    recording.with_extension("dump")
+}
+
+/// What a dump was recorded with, and how much of it survived
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DumpManifest {
+    pub version: u32,
+    /// Config of the recording the dump was taken from
+    pub config: RecordingConfig,
+    /// Size of the captured display, for resolving `MatchSource`
+    pub source_size: Option<(u32, u32)>,
+    /// Records written, including those in pruned segments
+    pub records: u64,
+    /// Records dropped because the writer fell behind
+    pub dropped_records: u64,
+    /// Segments deleted to stay under the size limit
+    pub pruned_segments: u64,
+}
+
+impl DumpManifest {
+    fn write(&self, dir: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize dump manifest: {}", e))?;
+        std::fs::write(dir.join(MANIFEST_FILE), json)
+            .map_err(|e| format!("Failed to write dump manifest: {}", e))
+    }
+
+    fn read(dir: &Path) -> Result<Self, String> {
+        let json = std::fs::read_to_string(dir.join(MANIFEST_FILE))
+            .map_err(|e| format!("Failed to read {}: {}", dir.join(MANIFEST_FILE).display(), e))?;
+        let manifest: Self = serde_json::from_str(&json)
+            .map_err(|e| format!("Invalid dump manifest: {}", e))?;
+        if manifest.version != DUMP_VERSION {
+            return Err(format!("Unsupported dump version {}", manifest.version));
+        }
+        Ok(manifest)
+    }
+}
+
+/// One captured item, as delivered by its capture
+#[derive(Clone)]
+pub enum DumpRecord {
+    Screen(ScreenFrame),
+    Webcam(WebcamFrame),
+    Mic(AudioChunk),
+    System(AudioChunk),
+}
+
+impl DumpRecord {
+    fn timestamp(&self) -> Duration {
+        match self {
+            Self::Screen(frame) => frame.timestamp,
+            Self::Webcam(frame) => frame.timestamp,
+            Self::Mic(chunk) | Self::System(chunk) => chunk.timestamp,
+        }
+    }
+
+    fn set_timestamp(&mut self, timestamp: Duration) {
+        match self {
+            Self::Screen(frame) => frame.timestamp = timestamp,
+            Self::Webcam(frame) => frame.timestamp = timestamp,
+            Self::Mic(chunk) | Self::System(chunk) => chunk.timestamp = timestamp,
+        }
+    }
+
+    /// Append the record's little-endian encoding; returns its length
+    fn encode(&self, out: &mut impl Write) -> std::io::Result<u64> {
+        let kind: u8 = match self {
+            Self::Screen(_) => 0,
+            Self::Webcam(_) => 1,
+            Self::Mic(_) => 2,
+            Self::System(_) => 3,
+        };
+        out.write_all(&[kind])?;
+        out.write_all(&(self.timestamp().as_micros() as u64).to_le_bytes())?;
+        let body = match self {
+            Self::Screen(frame) => {
+                let format: u8 = match frame.pixel_format {
+                    CapturePixelFormat::Bgra => 0,
+                    CapturePixelFormat::Nv12 => 1,
+                };
+                out.write_all(&frame.width.to_le_bytes())?;
+                out.write_all(&frame.height.to_le_bytes())?;
+                out.write_all(&(frame.stride as u32).to_le_bytes())?;
+                out.write_all(&[format])?;
+                write_bytes(out, &frame.data)?;
+                13 + 4 + frame.data.len() as u64
+            }
+            Self::Webcam(frame) => {
+                out.write_all(&frame.width.to_le_bytes())?;
+                out.write_all(&frame.height.to_le_bytes())?;
+                write_bytes(out, &frame.data)?;
+                8 + 4 + frame.data.len() as u64
+            }
+            Self::Mic(chunk) | Self::System(chunk) => {
+                out.write_all(&chunk.sample_rate.to_le_bytes())?;
+                out.write_all(&chunk.channels.to_le_bytes())?;
+                out.write_all(&(chunk.samples.len() as u32).to_le_bytes())?;
+                for sample in &chunk.samples {
+                    out.write_all(&sample.to_le_bytes())?;
+                }
+                6 + 4 + chunk.samples.len() as u64 * 4
+            }
+        };
+        Ok(9 + body)
+    }
+
+    /// Read the next record; None at the end of the segment
+    ///
+    /// A record cut off by a crash also ends the segment.
+    fn decode(input: &mut impl Read) -> Result<Option<Self>, String> {
+        let mut kind = [0u8];
+        match input.read_exact(&mut kind) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(format!("Failed to read dump: {}", e)),
+        }
+        match Self::decode_body(kind[0], input) {
+            Ok(record) => Ok(Some(record)),
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                eprintln!("Dump segment ends in a partial record, skipping it");
+                Ok(None)
+            }
+            Err(e) => Err(format!("Failed to read dump: {}", e)),
+        }
+    }
+
+    fn decode_body(kind: u8, input: &mut impl Read) -> std::io::Result<Self> {
+        let timestamp = Duration::from_micros(read_u64(input)?);
+        let record = match kind {
+            0 => {
+                let width = read_u32(input)?;
+                let height = read_u32(input)?;
+                let stride = read_u32(input)? as usize;
+                let mut format = [0u8];
+                input.read_exact(&mut format)?;
+                let pixel_format = match format[0] {
+                    0 => CapturePixelFormat::Bgra,
+                    1 => CapturePixelFormat::Nv12,
+                    other => return Err(invalid(format!("unknown pixel format {}", other))),
+                };
+                Self::Screen(ScreenFrame {
+                    data: FrameBuffer::shared(read_bytes(input)?),
+                    width,
+                    height,
+                    stride,
+                    timestamp,
+                    pixel_format,
+                })
+            }
+            1 => {
+                let width = read_u32(input)?;
+                let height = read_u32(input)?;
+                Self::Webcam(WebcamFrame {
+                    data: read_bytes(input)?,
+                    width,
+                    height,
+                    timestamp,
+                })
+            }
+            2 | 3 => {
+                let sample_rate = read_u32(input)?;
+                let mut channels = [0u8; 2];
+                input.read_exact(&mut channels)?;
+                let count = read_u32(input)? as usize;
+                let bytes = read_bytes_of(input, count * 4)?;
+                let chunk = AudioChunk {
+                    samples: bytes
+                        .chunks_exact(4)
+                        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                        .collect(),
+                    sample_rate,
+                    channels: u16::from_le_bytes(channels),
+                    timestamp,
+                };
+                if kind == 2 {
+                    Self::Mic(chunk)
+                } else {
+                    Self::System(chunk)
+                }
+            }
+            other => return Err(invalid(format!("unknown record kind {}", other))),
+        };
+        Ok(record)
+    }
+}
+
+fn write_bytes(out: &mut impl Write, data: &[u8]) -> std::io::Result<()> {
+    out.write_all(&(data.len() as u32).to_le_bytes())?;
+    out.write_all(data)
+}
+
+fn read_u32(input: &mut impl Read) -> std::io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(input: &mut impl Read) -> std::io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_bytes(input: &mut impl Read) -> std::io::Result<Vec<u8>> {
+    let len = read_u32(input)? as usize;
+    read_bytes_of(input, len)
+}
+
+fn read_bytes_of(input: &mut impl Read, len: usize) -> std::io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    input.take(len as u64).read_to_end(&mut data)?;
+    if data.len() < len {
+        return Err(ErrorKind::UnexpectedEof.into());
+    }
+    Ok(data)
+}
+
+fn invalid(message: String) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, message)
+}
+
+fn segment_path(dir: &Path, index: u64) -> PathBuf {
+    dir.join(format!("segment_{:06}.bin", index))
+}
+
+/// Segment files in `dir`, oldest first
+fn list_segments(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut segments: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension().is_some_and(|ext| ext == "bin")
+                && path.file_name().is_some_and(|name| name.to_string_lossy().starts_with("segment_"))
+        })
+        .collect();
+    segments.sort();
+    Ok(segments)
+}
+
+/// Segment files of a dump being written, pruned to stay under the limit
+struct SegmentRing {
+    dir: PathBuf,
+    segment_bytes: u64,
+    max_bytes: u64,
+    /// Closed segments and their sizes, oldest first
+    closed: VecDeque<(PathBuf, u64)>,
+    current: BufWriter<File>,
+    current_bytes: u64,
+    next_index: u64,
+    pruned: u64,
+}
+
+impl SegmentRing {
+    fn create(dir: &Path, max_bytes: u64) -> Result<Self, String> {
+        let (current, current_bytes) = Self::open_segment(dir, 0)?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            segment_bytes: max_bytes / SEGMENTS_PER_LIMIT,
+            max_bytes,
+            closed: VecDeque::new(),
+            current,
+            current_bytes,
+            next_index: 1,
+            pruned: 0,
+        })
+    }
+
+    fn open_segment(dir: &Path, index: u64) -> Result<(BufWriter<File>, u64), String> {
+        let path = segment_path(dir, index);
+        let file = File::create(&path)
+            .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+        let mut writer = BufWriter::new(file);
+        writer
+            .write_all(SEGMENT_MAGIC)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        Ok((writer, SEGMENT_MAGIC.len() as u64))
+    }
+
+    fn write(&mut self, record: &DumpRecord) -> Result<(), String> {
+        let has_records = self.current_bytes > SEGMENT_MAGIC.len() as u64;
+        if has_records && self.current_bytes >= self.segment_bytes {
+            self.roll()?;
+        }
+        self.current_bytes += record
+            .encode(&mut self.current)
+            .map_err(|e| format!("Failed to write dump: {}", e))?;
+        Ok(())
+    }
+
+    /// Start a new segment, deleting the oldest ones past the limit
+    fn roll(&mut self) -> Result<(), String> {
+        let (next, next_bytes) = Self::open_segment(&self.dir, self.next_index)?;
+        let mut finished = std::mem::replace(&mut self.current, next);
+        finished.flush().map_err(|e| format!("Failed to write dump: {}", e))?;
+        self.closed.push_back((segment_path(&self.dir, self.next_index - 1), self.current_bytes));
+        self.current_bytes = next_bytes;
+        self.next_index += 1;
+
+        let mut total: u64 = self.closed.iter().map(|(_, size)| size).sum();
+        while total + self.current_bytes > self.max_bytes {
+            let Some((path, size)) = self.closed.pop_front() else { break };
+            if let Err(e) = std::fs::remove_file(&path) {
+                eprintln!("Failed to prune {}: {}", path.display(), e);
+            }
+            total -= size;
+            self.pruned += 1;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<u64, String> {
+        self.current.flush().map_err(|e| format!("Failed to write dump: {}", e))?;
+        Ok(self.pruned)
+    }
+}
+
+/// Writer for a recording's capture dump
+pub struct CaptureDump {
+    running: Arc<Mutex<bool>>,
+    sender: Sender<DumpRecord>,
+    dropped: Arc<Mutex<u64>>,
+}
+
+impl CaptureDump {
+    /// Create `<recording>.dump/` and start the writer
+    pub fn start(
+        recording: &Path,
+        config: &RecordingConfig,
+        source_size: Option<(u32, u32)>,
+    ) -> Result<Self, String> {
+        let dir = dump_dir(recording);
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        for stale in list_segments(&dir)? {
+            let _ = std::fs::remove_file(stale);
+        }
+
+        let mut manifest = DumpManifest {
+            version: DUMP_VERSION,
+            config: config.clone(),
+            source_size,
+            records: 0,
+            dropped_records: 0,
+            pruned_segments: 0,
+        };
+        // Written now as well, so a dump from a crashed session can be replayed
+        manifest.write(&dir)?;
+        let ring = SegmentRing::create(&dir, config.capture_dump.max_bytes())?;
+
+        let running = Arc::new(Mutex::new(true));
+        let dropped = Arc::new(Mutex::new(0));
+        let (sender, receiver) = bounded::<DumpRecord>(WRITE_QUEUE);
+
+        println!("Dumping raw capture to {}", dir.display());
+        let running_clone = running.clone();
+        let dropped_clone = dropped.clone();
+        std::thread::spawn(move || {
+            match write_loop(running_clone, receiver, ring) {
+                Ok((records, pruned)) => {
+                    manifest.records = records;
+                    manifest.dropped_records = *dropped_clone.lock();
+                    manifest.pruned_segments = pruned;
+                    if let Err(e) = manifest.write(&dir) {
+                        eprintln!("{}", e);
+                    }
+                    println!("Capture dump finished: {} records in {}", records, dir.display());
+                }
+                Err(e) => eprintln!("Capture dump stopped: {}", e),
+            }
+        });
+
+        Ok(Self {
+            running,
+            sender,
+            dropped,
+        })
+    }
+
+    /// Pass `input` through, writing a copy of each item to the dump
+    ///
+    /// Items are dropped from the dump rather than delayed when the writer
+    /// falls behind, so dumping never holds up the recording.
+    pub fn tap<T: Clone + Send + 'static>(
+        &self,
+        input: Receiver<T>,
+        record: fn(T) -> DumpRecord,
+    ) -> Receiver<T> {
+        let (sender, output) = bounded(input.capacity().unwrap_or(REPLAY_QUEUE));
+        let dump = self.sender.clone();
+        let dropped = self.dropped.clone();
+
+        std::thread::spawn(move || {
+            for item in input.iter() {
+                if let Err(TrySendError::Full(_)) = dump.try_send(record(item.clone())) {
+                    *dropped.lock() += 1;
+                }
+                if sender.send(item).is_err() {
+                    break;
+                }
+            }
+        });
+
+        output
+    }
+
+    /// Copy every chunk read from an audio ring into the dump
+    ///
+    /// Chunks are passed on through a ring of the same size, so the mixer
+    /// still reads them without a channel in between.
+    pub fn tap_audio(&self, mut input: AudioConsumer, record: fn(AudioChunk) -> DumpRecord) -> AudioConsumer {
+        let (mut producer, output) = audio_ring(input.capacity());
+        let dump = self.sender.clone();
+        let dropped = self.dropped.clone();
+
+        std::thread::spawn(move || {
+            let mut chunk = empty_chunk();
+            while !input.is_finished() && !producer.is_disconnected() {
+                if !input.pop(&mut chunk) {
+                    wait_any(&[&input]);
+                    continue;
+                }
+                if let Err(TrySendError::Full(_)) = dump.try_send(record(chunk.clone())) {
+                    *dropped.lock() += 1;
+                }
+                producer.push_chunk(&chunk);
+            }
+        });
+
+        output
+    }
+
+    /// Write what is queued and close the dump
+    pub fn stop(&self) {
+        *self.running.lock() = false;
+    }
+}
+
+impl Drop for CaptureDump {
+    fn drop(&mut self) {
+        *self.running.lock() = false;
+    }
+}
+
+/// Write records until stopped; returns the records written and segments pruned
+fn write_loop(
+    running: Arc<Mutex<bool>>,
+    receiver: Receiver<DumpRecord>,
+    mut ring: SegmentRing,
+) -> Result<(u64, u64), String> {
+    let mut records = 0;
+    loop {
+        match receiver.recv_timeout(POLL_INTERVAL) {
+            Ok(record) => {
+                ring.write(&record)?;
+                records += 1;
+            }
+            Err(RecvTimeoutError::Timeout) if *running.lock() => {}
+            Err(_) => break,
+        }
+    }
+    for record in receiver.try_iter() {
+        ring.write(&record)?;
+        records += 1;
+    }
+    Ok((records, ring.finish()?))
+}
+
+/// Receivers standing in for the capture devices during a replay
+#[derive(Default)]
+pub struct ReplaySources {
+    pub screen: Option<Receiver<ScreenFrame>>,
+    pub webcam: Option<Receiver<WebcamFrame>>,
+    pub mic: Option<AudioConsumer>,
+    pub system: Option<AudioConsumer>,
+}
+
+#[derive(Default)]
+struct ReplaySenders {
+    screen: Option<Sender<ScreenFrame>>,
+    webcam: Option<Sender<WebcamFrame>>,
+    mic: Option<AudioProducer>,
+    system: Option<AudioProducer>,
+}
+
+/// A dump opened for replay
+pub struct CaptureReplay {
+    manifest: DumpManifest,
+    segments: Vec<PathBuf>,
+    /// Timestamp of the first remaining record
+    origin: Duration,
+    /// Time from the first remaining record to the last
+    duration: Duration,
+}
+
+impl CaptureReplay {
+    pub fn open(dir: &Path) -> Result<Self, String> {
+        let manifest = DumpManifest::read(dir)?;
+        let segments = list_segments(dir)?;
+
+        let first = segments.first().map(|path| read_segment(path, |_| false)).transpose()?.flatten();
+        let mut last = None;
+        if let Some(path) = segments.last() {
+            read_segment(path, |record| {
+                last = Some(record.timestamp());
+                true
+            })?;
+        }
+        let (Some(origin), Some(last)) = (first, last) else {
+            return Err(format!("{} has no captured records", dir.display()));
+        };
+
+        Ok(Self {
+            manifest,
+            segments,
+            origin,
+            duration: last.saturating_sub(origin),
+        })
+    }
+
+    /// Config of the recording the dump was taken from
+    pub fn config(&self) -> &RecordingConfig {
+        &self.manifest.config
+    }
+
+    pub fn source_size(&self) -> Option<(u32, u32)> {
+        self.manifest.source_size
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Start feeding the records at their original pace
+    ///
+    /// Timestamps are shifted so the first remaining record is at zero. The
+    /// feed stops at the end of the dump or once `stop_signal` is set.
+    pub fn start(self, stop_signal: Arc<Mutex<bool>>) -> ReplaySources {
+        let config = &self.manifest.config;
+        let mut sources = ReplaySources::default();
+        let mut senders = ReplaySenders::default();
+        if config.capture_screen {
+            let (sender, receiver) = bounded(REPLAY_QUEUE);
+            (senders.screen, sources.screen) = (Some(sender), Some(receiver));
+        }
+        if config.capture_webcam {
+            let (sender, receiver) = bounded(REPLAY_QUEUE);
+            (senders.webcam, sources.webcam) = (Some(sender), Some(receiver));
+        }
+        let audio_capacity = ring_capacity(config.audio_sample_rate, config.audio_channels.channel_count());
+        if config.capture_mic {
+            let (producer, consumer) = audio_ring(audio_capacity);
+            (senders.mic, sources.mic) = (Some(producer), Some(consumer));
+        }
+        if config.capture_system_audio {
+            let (producer, consumer) = audio_ring(audio_capacity);
+            (senders.system, sources.system) = (Some(producer), Some(consumer));
+        }
+
+        std::thread::spawn(move || {
+            if let Err(e) = replay_loop(&self.segments, self.origin, &stop_signal, &mut senders) {
+                eprintln!("Capture replay stopped: {}", e);
+            }
+            println!("Capture replay finished");
+        });
+
+        sources
+    }
+}
+
+/// Read a segment's records in order until `visit` returns false
+///
+/// Returns the timestamp of the first record.
+fn read_segment(
+    path: &Path,
+    mut visit: impl FnMut(DumpRecord) -> bool,
+) -> Result<Option<Duration>, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut reader = BufReader::new(file);
+    let mut magic = [0u8; 8];
+    if reader.read_exact(&mut magic).is_err() || &magic != SEGMENT_MAGIC {
+        return Err(format!("{} is not a capture dump segment", path.display()));
+    }
+
+    let mut first = None;
+    while let Some(record) = DumpRecord::decode(&mut reader)? {
+        first.get_or_insert(record.timestamp());
+        if !visit(record) {
+            break;
+        }
+    }
+    Ok(first)
+}
+
+fn replay_loop(
+    segments: &[PathBuf],
+    origin: Duration,
+    stop_signal: &Mutex<bool>,
+    senders: &mut ReplaySenders,
+) -> Result<(), String> {
+    let start = Instant::now();
+    for path in segments {
+        let mut stopped = false;
+        read_segment(path, |mut record| {
+            let at = record.timestamp().saturating_sub(origin);
+            record.set_timestamp(at);
+
+            while let Some(wait) = at.checked_sub(start.elapsed()).filter(|wait| !wait.is_zero()) {
+                if *stop_signal.lock() {
+                    stopped = true;
+                    return false;
+                }
+                std::thread::sleep(wait.min(POLL_INTERVAL));
+            }
+
+            let sent = match record {
+                DumpRecord::Screen(frame) => send(&senders.screen, frame, stop_signal),
+                DumpRecord::Webcam(frame) => send(&senders.webcam, frame, stop_signal),
+                DumpRecord::Mic(chunk) => push(&mut senders.mic, &chunk, stop_signal),
+                DumpRecord::System(chunk) => push(&mut senders.system, &chunk, stop_signal),
+            };
+            stopped = !sent;
+            sent
+        })?;
+        if stopped {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Send without dropping, so a replay delivers every record; false once stopped
+fn send<T>(sender: &Option<Sender<T>>, mut item: T, stop_signal: &Mutex<bool>) -> bool {
+    let Some(sender) = sender else { return true };
+    loop {
+        match sender.send_timeout(item, POLL_INTERVAL) {
+            Ok(()) => return true,
+            Err(SendTimeoutError::Timeout(unsent)) if !*stop_signal.lock() => item = unsent,
+            Err(_) => return false,
+        }
+    }
+}
+
+/// `send` for audio rings: waits for room rather than dropping the chunk
+fn push(producer: &mut Option<AudioProducer>, chunk: &AudioChunk, stop_signal: &Mutex<bool>) -> bool {
+    let Some(producer) = producer else { return true };
+    while !producer.fits(chunk.samples.len()) {
+        if producer.is_disconnected() || *stop_signal.lock() {
+            return false;
+        }
+        std::thread::sleep(AUDIO_POLL_INTERVAL);
+    }
+    producer.push_chunk(chunk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dump_round_trip_and_pruning() {
+        let dir = std::env::temp_dir().join(format!("asmr_dump_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let screen = DumpRecord::Screen(ScreenFrame {
+            data: FrameBuffer::shared(vec![7; 64]),
+            width: 4,
+            height: 4,
+            stride: 16,
+            timestamp: Duration::from_millis(33),
+            pixel_format: CapturePixelFormat::Bgra,
+        });
+        let mic = DumpRecord::Mic(AudioChunk {
+            samples: vec![0.25, -0.5],
+            sample_rate: 48000,
+            channels: 2,
+            timestamp: Duration::from_micros(1500),
+        });
+
+        // One record per segment, with room for the last four
+        let screen_bytes = screen.encode(&mut Vec::new()).unwrap() + 8;
+        let mic_bytes = mic.encode(&mut Vec::new()).unwrap() + 8;
+        let mut ring = SegmentRing::create(&dir, 2 * (screen_bytes + mic_bytes)).unwrap();
+        ring.segment_bytes = 1;
+        for _ in 0..4 {
+            ring.write(&screen).unwrap();
+            ring.write(&mic).unwrap();
+        }
+        assert_eq!(ring.finish().unwrap(), 4);
+
+        let segments = list_segments(&dir).unwrap();
+        assert_eq!(segments.len(), 4);
+        let mut records = Vec::new();
+        for path in &segments {
+            read_segment(path, |record| {
+                records.push(record);
+                true
+            })
+            .unwrap();
+        }
+        assert_eq!(records.len(), 4);
+        match (&records[0], &records[1]) {
+            (DumpRecord::Screen(frame), DumpRecord::Mic(chunk)) => {
+                assert_eq!((frame.width, frame.stride, frame.data.len()), (4, 16, 64));
+                assert_eq!(frame.timestamp, Duration::from_millis(33));
+                assert_eq!(chunk.samples, vec![0.25, -0.5]);
+                assert_eq!(chunk.timestamp, Duration::from_micros(1500));
+            }
+            _ => panic!("records out of order"),
+        }
+
+        assert!(CaptureDumpConfig { enabled: true, max_megabytes: 1 }.validate().is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,169 @@
+//! Capture Stall Watchdog
+//!
+//! A capture source can stop delivering without reporting an error: a
+//! display's duplication session is lost after a resolution change, an audio
+//! driver stops calling back after sleep. The recording then carries on with
+//! a frozen picture or silence. The watchdog follows each source's count of
+//! frames or chunks; once a count hasn't moved for the configured time the
+//! manager restarts that source, and the recording keeps going.
+//!
+//! The webcam is left out, as it already reopens itself when frames stop,
+//! and so is system audio, which legitimately delivers nothing while the
+//! machine is silent on some platforms.
+
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// Allowed stall times, in seconds (0 turns the watchdog off)
+pub const STALL_SECS_RANGE: std::ops::RangeInclusive<u32> = 0..=60;
+
+/// Watchdog settings
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CaptureWatchdogConfig {
+    /// Seconds without frames or audio before a source is restarted (0: never)
+    #[serde(default = "default_stall_secs")]
+    pub stall_secs: u32,
+}
+
+fn default_stall_secs() -> u32 {
+    5
+}
+
+impl Default for CaptureWatchdogConfig {
+    fn default() -> Self {
+        Self {
+            stall_secs: default_stall_secs(),
+        }
+    }
+}
+
+impl CaptureWatchdogConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !STALL_SECS_RANGE.contains(&self.stall_secs) {
+            return Err(format!(
+                "Capture stall time must be {}-{} seconds (got {})",
+                STALL_SECS_RANGE.start(),
+                STALL_SECS_RANGE.end(),
+                self.stall_secs
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Source the watchdog follows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchedSource {
+    Screen,
+    Microphone,
+}
+
+impl WatchedSource {
+    pub fn label(self) -> &'static str {
+        match self {
+            WatchedSource::Screen => "Screen capture",
+            WatchedSource::Microphone => "Microphone",
+        }
+    }
+}
+
+/// What a check found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchdogEvent {
+    /// Nothing arrived for the stall time; restart the source
+    Stalled,
+    /// Data arrived again after a restart
+    Recovered,
+}
+
+struct SourceWatch {
+    source: WatchedSource,
+    count: u64,
+    changed_at: Instant,
+    restarted: bool,
+}
+
+/// Stall detection for the recording's sources
+pub struct CaptureWatchdog {
+    stall: Option<Duration>,
+    sources: Vec<SourceWatch>,
+}
+
+impl CaptureWatchdog {
+    pub fn new(config: CaptureWatchdogConfig) -> Self {
+        Self {
+            stall: (config.stall_secs > 0).then(|| Duration::from_secs(config.stall_secs as u64)),
+            sources: Vec::new(),
+        }
+    }
+
+    /// Compare `source`'s running count of frames or chunks with the last one
+    ///
+    /// A stalled source is reported again every stall period until it
+    /// delivers, so a restart that didn't help is retried.
+    pub fn check(&mut self, source: WatchedSource, count: u64, now: Instant) -> Option<WatchdogEvent> {
+        let stall = self.stall?;
+        let Some(watch) = self.sources.iter_mut().find(|w| w.source == source) else {
+            self.sources.push(SourceWatch {
+                source,
+                count,
+                changed_at: now,
+                restarted: false,
+            });
+            return None;
+        };
+
+        if count != watch.count {
+            watch.count = count;
+            watch.changed_at = now;
+            return std::mem::take(&mut watch.restarted).then_some(WatchdogEvent::Recovered);
+        }
+        if now.duration_since(watch.changed_at) < stall {
+            return None;
+        }
+        watch.changed_at = now;
+        watch.restarted = true;
+        Some(WatchdogEvent::Stalled)
+    }
+
+    /// Start `source`'s stall time over, e.g. while it is reconnecting by itself
+    pub fn reset(&mut self, source: WatchedSource, now: Instant) {
+        if let Some(watch) = self.sources.iter_mut().find(|w| w.source == source) {
+            watch.changed_at = now;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watchdog_reports_stall_and_recovery() {
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let mut watchdog = CaptureWatchdog::new(CaptureWatchdogConfig { stall_secs: 5 });
+
+        assert_eq!(watchdog.check(WatchedSource::Screen, 10, at(0)), None);
+        assert_eq!(watchdog.check(WatchedSource::Screen, 40, at(3)), None);
+        assert_eq!(watchdog.check(WatchedSource::Screen, 40, at(7)), None);
+        assert_eq!(watchdog.check(WatchedSource::Screen, 40, at(8)), Some(WatchdogEvent::Stalled));
+        // Retried every stall period while it stays stuck
+        assert_eq!(watchdog.check(WatchedSource::Screen, 40, at(12)), None);
+        assert_eq!(watchdog.check(WatchedSource::Screen, 40, at(13)), Some(WatchdogEvent::Stalled));
+        assert_eq!(watchdog.check(WatchedSource::Screen, 41, at(14)), Some(WatchdogEvent::Recovered));
+        assert_eq!(watchdog.check(WatchedSource::Screen, 42, at(15)), None);
+
+        // Sources are followed separately
+        assert_eq!(watchdog.check(WatchedSource::Microphone, 0, at(15)), None);
+        watchdog.reset(WatchedSource::Microphone, at(19));
+        assert_eq!(watchdog.check(WatchedSource::Microphone, 0, at(21)), None);
+
+        let mut off = CaptureWatchdog::new(CaptureWatchdogConfig { stall_secs: 0 });
+        assert_eq!(off.check(WatchedSource::Screen, 0, at(0)), None);
+        assert_eq!(off.check(WatchedSource::Screen, 0, at(100)), None);
+        assert!(CaptureWatchdogConfig { stall_secs: 61 }.validate().is_err());
+    }
+}
@@ -0,0 +1,72 @@
+//! Waiting on Pipeline Channels
+//!
+//! The compositor and encoder loops block here until one of their input
+//! channels has something to read, instead of sleeping a fixed interval and
+//! polling. The wait gives up after `IDLE_WAIT` so the loops still notice
+//! their stop flags and scheduled work when no input arrives.
+//!
+//! A closed channel always counts as ready in a select, which would turn the
+//! wait into a busy loop, so a receiver found closed is dropped from its
+//! `Option` and no longer waited on.
+
+use crossbeam_channel::{Receiver, Select};
+use std::time::Duration;
+
+/// Longest a loop blocks without input before checking its flags again
+pub const IDLE_WAIT: Duration = Duration::from_millis(50);
+
+/// Block until `receiver` has a message, or `IDLE_WAIT` passes
+pub fn wait_for<A>(receiver: &mut Option<Receiver<A>>) {
+    wait_for_either(receiver, &mut None::<Receiver<()>>);
+}
+
+/// Block until `first` or `second` has a message, or `IDLE_WAIT` passes
+pub fn wait_for_either<A, B>(first: &mut Option<Receiver<A>>, second: &mut Option<Receiver<B>>) {
+    let mut select = Select::new();
+    let first_index = first.as_ref().map(|r| select.recv(r));
+    let second_index = second.as_ref().map(|r| select.recv(r));
+    if first_index.is_none() && second_index.is_none() {
+        std::thread::sleep(IDLE_WAIT);
+        return;
+    }
+
+    let Ok(ready) = select.ready_timeout(IDLE_WAIT) else {
+        return;
+    };
+    drop(select);
+    // Each receiver has a single reader, so ready but empty means closed
+    if Some(ready) == first_index && first.as_ref().is_some_and(|r| r.is_empty()) {
+        *first = None;
+    } else if Some(ready) == second_index && second.as_ref().is_some_and(|r| r.is_empty()) {
+        *second = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossbeam_channel::bounded;
+    use std::time::Instant;
+
+    #[test]
+    fn test_wait_returns_on_message_and_forgets_closed_channels() {
+        let (sender, receiver) = bounded::<u32>(4);
+        let mut frames = Some(receiver);
+        let mut audio = None::<Receiver<u8>>;
+
+        let started = Instant::now();
+        wait_for_either(&mut frames, &mut audio);
+        assert!(started.elapsed() >= IDLE_WAIT);
+
+        sender.send(1).unwrap();
+        let started = Instant::now();
+        wait_for_either(&mut frames, &mut audio);
+        assert!(started.elapsed() < IDLE_WAIT);
+        assert!(frames.is_some());
+        assert_eq!(frames.as_ref().unwrap().try_recv(), Ok(1));
+
+        drop(sender);
+        wait_for(&mut frames);
+        assert!(frames.is_none());
+    }
+}
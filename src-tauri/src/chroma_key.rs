@@ -0,0 +1,173 @@
+//! Chroma Key
+//!
+//! Green-screen keying for the webcam overlay. Pixels are compared to the key
+//! color by chroma alone (BT.709 Cb/Cr), so shading on the backdrop keys out
+//! as well as its brightest part. Pixels within `similarity` of the key become
+//! transparent, with a `smoothness`-wide soft edge, and the key color's tint
+//! is pulled out of what remains so the subject doesn't glow green.
+
+use image::Rgba;
+use serde::{Deserialize, Serialize};
+
+/// Shortest key chroma accepted; grays have no hue to key on
+const MIN_KEY_CHROMA: f32 = 0.05;
+
+/// Chroma key settings for the webcam
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChromaKeyConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Backdrop color, RGB
+    #[serde(default = "default_key_color")]
+    pub key_color: [u8; 3],
+    /// Chroma distance from the key that is fully transparent (0-1)
+    #[serde(default = "default_similarity")]
+    pub similarity: f32,
+    /// Width of the partly transparent edge beyond `similarity` (0-1)
+    #[serde(default = "default_smoothness")]
+    pub smoothness: f32,
+    /// How much of the key color's tint is removed from kept pixels (0-1)
+    #[serde(default = "default_spill")]
+    pub spill: f32,
+}
+
+fn default_key_color() -> [u8; 3] {
+    [0, 255, 0]
+}
+
+fn default_similarity() -> f32 {
+    0.4
+}
+
+fn default_smoothness() -> f32 {
+    0.08
+}
+
+fn default_spill() -> f32 {
+    0.5
+}
+
+impl Default for ChromaKeyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            key_color: default_key_color(),
+            similarity: default_similarity(),
+            smoothness: default_smoothness(),
+            spill: default_spill(),
+        }
+    }
+}
+
+impl ChromaKeyConfig {
+    /// Check the amounts are in range and the key color has a hue to key on
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, value) in [
+            ("similarity", self.similarity),
+            ("smoothness", self.smoothness),
+            ("spill", self.spill),
+        ] {
+            if !(0.0..=1.0).contains(&value) {
+                return Err(format!("Chroma key {} must be between 0.0 and 1.0, got {}", name, value));
+            }
+        }
+        if self.enabled && ChromaKeyer::new(self).key_length < MIN_KEY_CHROMA {
+            return Err("Chroma key color must not be gray, black or white".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Keys pixels against a `ChromaKeyConfig`
+pub struct ChromaKeyer {
+    /// Key color chroma (Cb, Cr)
+    key: (f32, f32),
+    /// Length of `key`, for the spill direction
+    key_length: f32,
+    similarity: f32,
+    smoothness: f32,
+    spill: f32,
+}
+
+impl ChromaKeyer {
+    pub fn new(config: &ChromaKeyConfig) -> Self {
+        let [r, g, b] = config.key_color.map(|c| c as f32 / 255.0);
+        let (_, cb, cr) = to_ycbcr(r, g, b);
+        Self {
+            key: (cb, cr),
+            key_length: cb.hypot(cr),
+            similarity: config.similarity,
+            smoothness: config.smoothness,
+            spill: config.spill,
+        }
+    }
+
+    /// Key one pixel: the alpha drops toward the key color and the key's tint
+    /// is suppressed in the color
+    pub fn key(&self, pixel: Rgba<u8>) -> Rgba<u8> {
+        let [r, g, b, a] = pixel.0.map(|c| c as f32 / 255.0);
+        let (luma, mut cb, mut cr) = to_ycbcr(r, g, b);
+
+        let distance = (cb - self.key.0).hypot(cr - self.key.1);
+        let alpha = if distance <= self.similarity {
+            0.0
+        } else if self.smoothness > 0.0 {
+            ((distance - self.similarity) / self.smoothness).min(1.0)
+        } else {
+            1.0
+        };
+
+        // Remove part of the chroma that points the same way as the key's
+        if self.key_length > 0.0 {
+            let (dir_cb, dir_cr) = (self.key.0 / self.key_length, self.key.1 / self.key_length);
+            let along = cb * dir_cb + cr * dir_cr;
+            if along > 0.0 {
+                cb -= dir_cb * along * self.spill;
+                cr -= dir_cr * along * self.spill;
+            }
+        }
+
+        let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+        Rgba([
+            channel(luma + 1.5748 * cr),
+            channel(luma - 0.1873 * cb - 0.4681 * cr),
+            channel(luma + 1.8556 * cb),
+            channel(a * alpha),
+        ])
+    }
+}
+
+/// BT.709 luma and chroma, chroma centered on 0
+fn to_ycbcr(r: f32, g: f32, b: f32) -> (f32, f32, f32) {
+    let luma = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    (luma, (b - luma) / 1.8556, (r - luma) / 1.5748)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_green_keys_out_and_spill_is_removed() {
+        let keyer = ChromaKeyer::new(&ChromaKeyConfig { enabled: true, ..ChromaKeyConfig::default() });
+
+        // Backdrop, bright or shaded, is transparent
+        assert_eq!(keyer.key(Rgba([0, 255, 0, 255])).0[3], 0);
+        assert_eq!(keyer.key(Rgba([40, 160, 50, 255])).0[3], 0);
+
+        // A skin tone is kept as it was
+        let skin = keyer.key(Rgba([224, 172, 140, 255]));
+        assert_eq!(skin.0[3], 255);
+        assert!(skin.0.iter().zip([224, 172, 140]).all(|(a, b)| a.abs_diff(b) <= 1));
+
+        // A green-tinted gray is kept but loses some of its green
+        let tinted = keyer.key(Rgba([120, 150, 120, 255]));
+        assert_eq!(tinted.0[3], 255);
+        assert!(tinted.0[1] < 150);
+
+        assert!(ChromaKeyConfig { enabled: true, key_color: [128, 128, 128], ..ChromaKeyConfig::default() }
+            .validate()
+            .is_err());
+    }
+}
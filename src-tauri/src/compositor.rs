@@ -1,23 +1,44 @@
-use crate::recording::PipPosition;
+use crate::captions::CaptionOverlay;
+use crate::chroma_key::{ChromaKeyConfig, ChromaKeyer};
+use crate::cursor::CursorOverlay;
+use crate::frame_pool::{FrameBuffer, FramePool};
+#[cfg(feature = "gpu")]
+use crate::gpu_compositor::GpuCompositor;
+use crate::recording::{CapturePixelFormat, PipFit, PipPosition, PipShape, WatermarkConfig};
 use crate::screen::ScreenFrame;
+use crate::text_overlay::TextOverlay;
 use crate::webcam::WebcamFrame;
 use image::{ImageBuffer, Rgba, RgbaImage};
+#[cfg(feature = "gpu")]
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Pixel layout of a composited frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameFormat {
+    /// RGBA (webcam overlay or scaling was applied, or sent by the frontend)
+    Rgba,
+    /// BGRA straight from screen capture (fast path - no color conversion needed)
+    Bgra,
+    /// NV12 straight from screen capture (fast path - Y plane, then interleaved UV)
+    Nv12,
+}
 
 /// A composited video frame ready for encoding
 #[derive(Clone)]
 #[allow(dead_code)]
 pub struct CompositeFrame {
-    /// Pixel data (RGBA or BGRA depending on is_bgra flag)
-    pub data: Vec<u8>,
+    /// Pixel data, tightly packed in `format`; cloning the frame shares it
+    pub data: Arc<FrameBuffer>,
     /// Frame width
     pub width: u32,
     /// Frame height
     pub height: u32,
-    /// Timestamp
+    /// Capture time since the recording started (sets the frame's PTS)
     pub timestamp: std::time::Duration,
-    /// If true, data is in BGRA format (fast path - no color conversion needed)
-    /// If false, data is in RGBA format (webcam overlay was applied)
-    pub is_bgra: bool,
+    /// Layout of `data`
+    pub format: FrameFormat,
 }
 
 /// Video compositor configuration
@@ -32,8 +53,27 @@ pub struct CompositorConfig {
     pub pip_position: PipPosition,
     /// Webcam size as percentage of output (10-50)
     pub pip_size_percent: u32,
+    /// Free-form left edge as a fraction of the output width (overrides the corner)
+    pub pip_x: Option<f32>,
+    /// Free-form top edge as a fraction of the output height (overrides the corner)
+    pub pip_y: Option<f32>,
+    /// Free-form width as a fraction of the output width (overrides the percentage)
+    pub pip_width: Option<f32>,
     /// Padding from edges in pixels
     pub pip_padding: u32,
+    /// How the webcam picture fills the PiP area
+    pub pip_fit: PipFit,
+    /// Outline of the PiP
+    pub pip_shape: PipShape,
+    /// Green-screen keying of the webcam PiP
+    pub chroma_key: ChromaKeyConfig,
+    /// Flip the webcam horizontally (selfie view)
+    pub mirror_webcam: bool,
+    /// Logo drawn over every output frame
+    pub watermark: Option<WatermarkConfig>,
+    /// Scale and blend on the GPU when one is available (`gpu` feature)
+    #[cfg_attr(not(feature = "gpu"), allow(dead_code))]
+    pub use_gpu: bool,
 }
 
 impl Default for CompositorConfig {
@@ -44,50 +84,451 @@ impl Default for CompositorConfig {
             include_webcam: false,
             pip_position: PipPosition::TopRight,
             pip_size_percent: 25,
+            pip_x: None,
+            pip_y: None,
+            pip_width: None,
             pip_padding: 20,
+            pip_fit: PipFit::default(),
+            pip_shape: PipShape::default(),
+            chroma_key: ChromaKeyConfig::default(),
+            mirror_webcam: false,
+            watermark: None,
+            use_gpu: false,
         }
     }
 }
 
+/// A layer of the composited output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LayerKind {
+    /// Screen capture, scaled to the output size
+    Screen,
+    /// Webcam picture-in-picture
+    Webcam,
+    /// Text overlays (titles, timestamps)
+    Text,
+    /// Burned-in live captions
+    Captions,
+}
+
+/// Stacking and blending of one layer
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Layer {
+    pub kind: LayerKind,
+    /// Layers are drawn from the lowest z-order up
+    pub z_order: i32,
+    /// 0.0 (transparent) - 1.0 (opaque)
+    pub opacity: f32,
+}
+
+/// Change to one layer; unset fields are left as they are
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LayerUpdate {
+    pub kind: LayerKind,
+    #[serde(default)]
+    pub z_order: Option<i32>,
+    #[serde(default)]
+    pub opacity: Option<f32>,
+}
+
+/// Default stack: screen, then webcam, then text, then captions on top
+fn default_layers() -> Vec<Layer> {
+    [LayerKind::Screen, LayerKind::Webcam, LayerKind::Text, LayerKind::Captions]
+        .into_iter()
+        .enumerate()
+        .map(|(z_order, kind)| Layer {
+            kind,
+            z_order: z_order as i32,
+            opacity: 1.0,
+        })
+        .collect()
+}
+
+/// What a GPU draw fills its rectangle with
+#[cfg(any(feature = "gpu", test))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DrawSource {
+    /// The screen frame, scaled to the rectangle
+    Screen,
+    /// The webcam frame, scaled to the rectangle
+    Webcam,
+    /// A solid RGBA color (0.0 - 1.0)
+    Color([f32; 4]),
+}
+
+/// One quad of a GPU-composited frame, in output pixels
+#[cfg(any(feature = "gpu", test))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LayerDraw {
+    pub source: DrawSource,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Part of the source shown: left, top, right, bottom as fractions
+    pub uv: [f32; 4],
+    /// Corner radius in output pixels (0 for a plain rectangle)
+    pub radius: f32,
+    /// Only fill a band this wide inside the outline (0 fills the whole shape)
+    pub ring: f32,
+    pub opacity: f32,
+}
+
+/// Where the webcam picture is drawn and which part of it is shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PipPlacement {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    /// Source rectangle: x, y, width, height in webcam pixels
+    crop: (u32, u32, u32, u32),
+    /// Corner radius of the shape, in output pixels
+    radius: u32,
+}
+
+/// Decoded watermark, placed for the output size
+struct Watermark {
+    image: RgbaImage,
+    x: u32,
+    y: u32,
+    opacity: f32,
+}
+
 /// Video compositor that combines screen capture and webcam into a single frame
 pub struct VideoCompositor {
     config: CompositorConfig,
-    /// Cached PiP dimensions
+    /// Configured output size; `config` holds the size frames are drawn at,
+    /// smaller while adaptive quality has reduced it
+    full_size: (u32, u32),
+    /// Cached PiP area (4:3, the webcam picture is fitted into it)
     pip_width: u32,
     pip_height: u32,
     /// Cached PiP position
     pip_x: u32,
     pip_y: u32,
+    /// Burned-in live captions
+    caption_overlay: Option<CaptionOverlay>,
+    /// Text overlays, drawn in order
+    text_overlays: Vec<TextOverlay>,
+    /// Whether each text overlay is shown
+    text_visible: Vec<bool>,
+    /// Text overlays and the watermark are shown at all
+    overlays_visible: bool,
+    watermark_visible: bool,
+    /// Cursor ring and click ripples, drawn on the screen layer
+    cursor_overlay: Option<CursorOverlay>,
+    /// Logo drawn on top of everything, decoded once
+    watermark: Option<Watermark>,
+    /// Layers in drawing order (sorted by z-order)
+    layers: Vec<Layer>,
+    /// Buffers composited frames are drawn into, back from the encoder
+    pool: FramePool,
+    /// GPU renderer (None when disabled, unavailable or after a GPU error)
+    #[cfg(feature = "gpu")]
+    gpu: Mutex<Option<GpuCompositor>>,
 }
 
 impl VideoCompositor {
     /// Create a new video compositor
     pub fn new(config: CompositorConfig) -> Self {
-        // Calculate PiP dimensions based on percentage
-        let pip_width = (config.output_width * config.pip_size_percent) / 100;
-        let pip_height = (pip_width * 3) / 4; // Assume 4:3 aspect ratio for webcam
+        let mut compositor = Self {
+            full_size: (config.output_width, config.output_height),
+            config,
+            pip_width: 0,
+            pip_height: 0,
+            pip_x: 0,
+            pip_y: 0,
+            caption_overlay: None,
+            text_overlays: Vec::new(),
+            text_visible: Vec::new(),
+            overlays_visible: true,
+            watermark_visible: true,
+            cursor_overlay: None,
+            watermark: None,
+            layers: default_layers(),
+            pool: FramePool::new(),
+            #[cfg(feature = "gpu")]
+            gpu: Mutex::new(None),
+        };
+        #[cfg(feature = "gpu")]
+        if compositor.config.use_gpu {
+            match GpuCompositor::new(compositor.config.output_width, compositor.config.output_height) {
+                Ok(gpu) => {
+                    println!("Compositing on the GPU ({})", gpu.adapter_name());
+                    *compositor.gpu.lock() = Some(gpu);
+                }
+                Err(e) => eprintln!("GPU compositing unavailable, using the CPU: {}", e),
+            }
+        }
+        if let Some(ref watermark) = compositor.config.watermark {
+            match compositor.load_watermark(watermark) {
+                Ok(loaded) => compositor.watermark = Some(loaded),
+                Err(e) => eprintln!("Recording without the watermark: {}", e),
+            }
+        }
+        compositor.update_pip_geometry();
+        compositor
+    }
+    
+    /// Draw frames at `percent`% of the output size (the encoder scales them
+    /// back up); 100 restores the full size
+    pub fn set_render_scale(&mut self, percent: u32) {
+        let (full_width, full_height) = self.full_size;
+        // Even dimensions, as NV12 and 4:2:0 encoders need
+        let scaled = |size: u32| ((size * percent.clamp(1, 100) / 100) & !1).max(2);
+        let (width, height) = (scaled(full_width), scaled(full_height));
+        if (width, height) == (self.config.output_width, self.config.output_height) {
+            return;
+        }
+        println!("Compositing at {}x{} ({}% of {}x{})", width, height, percent, full_width, full_height);
+        self.config.output_width = width;
+        self.config.output_height = height;
         
-        // Calculate PiP position
-        let (pip_x, pip_y) = Self::calculate_pip_position(
-            config.output_width,
-            config.output_height,
-            pip_width,
-            pip_height,
-            config.pip_position,
-            config.pip_padding,
+        #[cfg(feature = "gpu")]
+        {
+            let mut gpu = self.gpu.lock();
+            if gpu.is_some() {
+                *gpu = GpuCompositor::new(width, height)
+                    .map_err(|e| eprintln!("GPU compositing unavailable at {}x{}, using the CPU: {}", width, height, e))
+                    .ok();
+            }
+        }
+        if let Some(watermark) = self.config.watermark.clone() {
+            self.watermark = self.load_watermark(&watermark).ok();
+        }
+        self.update_pip_geometry();
+    }
+    
+    /// Decode the watermark image and place it in its corner, scaled down to
+    /// fit the output if needed
+    fn load_watermark(&self, config: &WatermarkConfig) -> Result<Watermark, String> {
+        let mut image = image::open(&config.path)
+            .map_err(|e| format!("Failed to load watermark {}: {}", config.path.display(), e))?
+            .to_rgba8();
+        let (output_width, output_height) = (self.config.output_width, self.config.output_height);
+        if image.width() > output_width || image.height() > output_height {
+            image = image::DynamicImage::ImageRgba8(image)
+                .resize(output_width, output_height, image::imageops::FilterType::Triangle)
+                .to_rgba8();
+        }
+        let (x, y) = Self::calculate_pip_position(
+            output_width,
+            output_height,
+            image.width(),
+            image.height(),
+            config.position,
+            self.config.pip_padding,
         );
+        Ok(Watermark {
+            image,
+            x,
+            y,
+            opacity: config.opacity,
+        })
+    }
+    
+    /// Alpha-blend the watermark onto a finished frame
+    fn draw_watermark(&self, output: &mut RgbaImage) {
+        let Some(watermark) = self.shown_watermark() else {
+            return;
+        };
+        for (x, y, pixel) in watermark.image.enumerate_pixels() {
+            let (out_x, out_y) = (watermark.x + x, watermark.y + y);
+            let alpha = watermark.opacity * pixel.0[3] as f32 / 255.0;
+            if alpha > 0.0 && out_x < output.width() && out_y < output.height() {
+                blend_pixel(output, out_x, out_y, *pixel, alpha);
+            }
+        }
+    }
+    
+    /// Recalculate cached PiP size and position from the config
+    fn update_pip_geometry(&mut self) {
+        // Calculate PiP dimensions based on percentage, or the free-form width
+        self.pip_width = match self.config.pip_width {
+            Some(fraction) => (self.config.output_width as f32 * fraction).round() as u32,
+            None => (self.config.output_width * self.config.pip_size_percent) / 100,
+        };
+        self.pip_height = (self.pip_width * 3) / 4; // Assume 4:3 aspect ratio for webcam
+        self.pip_height = self.pip_height.min(self.config.output_height);
         
-        Self {
-            config,
-            pip_width,
-            pip_height,
-            pip_x,
-            pip_y,
+        // Calculate PiP position
+        let (pip_x, pip_y) = self.pip_origin(self.pip_width, self.pip_height);
+        self.pip_x = pip_x;
+        self.pip_y = pip_y;
+    }
+    
+    /// Move or resize the webcam PiP (takes effect on the next frame)
+    ///
+    /// Choosing a corner drops the free-form position and choosing a
+    /// percentage drops the free-form width.
+    pub fn set_pip_layout(&mut self, position: Option<PipPosition>, size_percent: Option<u32>) {
+        if let Some(position) = position {
+            self.config.pip_position = position;
+            self.config.pip_x = None;
+            self.config.pip_y = None;
         }
+        if let Some(size_percent) = size_percent {
+            self.config.pip_size_percent = size_percent;
+            self.config.pip_width = None;
+        }
+        self.update_pip_geometry();
     }
     
-    /// Calculate the top-left corner position for PiP overlay
-    fn calculate_pip_position(
+    /// Place or size the webcam PiP freely, as fractions of the output
+    /// (takes effect on the next frame)
+    pub fn set_pip_placement(&mut self, x: Option<f32>, y: Option<f32>, width: Option<f32>) {
+        if x.is_none() && y.is_none() && width.is_none() {
+            return;
+        }
+        self.config.pip_x = x.or(self.config.pip_x);
+        self.config.pip_y = y.or(self.config.pip_y);
+        self.config.pip_width = width.or(self.config.pip_width);
+        self.update_pip_geometry();
+    }
+    
+    /// Change how the webcam picture fills the PiP area
+    pub fn set_pip_fit(&mut self, fit: PipFit) {
+        self.config.pip_fit = fit;
+    }
+    
+    /// Change the outline of the PiP
+    pub fn set_pip_shape(&mut self, shape: PipShape) {
+        self.config.pip_shape = shape;
+    }
+    
+    /// Change the webcam's chroma key
+    pub fn set_chroma_key(&mut self, chroma_key: ChromaKeyConfig) {
+        self.config.chroma_key = chroma_key;
+    }
+    
+    /// Flip the webcam horizontally or back
+    pub fn set_mirror_webcam(&mut self, mirror: bool) {
+        self.config.mirror_webcam = mirror;
+    }
+    
+    /// Show or hide the webcam PiP
+    pub fn set_webcam_visible(&mut self, visible: bool) {
+        self.config.include_webcam = visible;
+    }
+    
+    /// Burn live captions into composited frames (None to stop)
+    pub fn set_caption_overlay(&mut self, overlay: Option<CaptionOverlay>) {
+        self.caption_overlay = overlay;
+    }
+    
+    /// Burn text overlays into composited frames (empty to stop)
+    pub fn set_text_overlays(&mut self, overlays: Vec<TextOverlay>) {
+        self.text_visible = vec![true; overlays.len()];
+        self.text_overlays = overlays;
+    }
+    
+    /// Show or hide the text overlays and the watermark together
+    pub fn set_overlays_visible(&mut self, visible: bool) {
+        self.overlays_visible = visible;
+    }
+    
+    /// Show or hide one text overlay (out-of-range indexes are ignored)
+    pub fn set_text_overlay_visible(&mut self, index: usize, visible: bool) {
+        if let Some(shown) = self.text_visible.get_mut(index) {
+            *shown = visible;
+        }
+    }
+    
+    /// Show or hide the watermark
+    pub fn set_watermark_visible(&mut self, visible: bool) {
+        self.watermark_visible = visible;
+    }
+    
+    /// The text overlays currently shown, in drawing order
+    fn visible_text_overlays(&self) -> impl Iterator<Item = &TextOverlay> {
+        self.text_overlays
+            .iter()
+            .zip(&self.text_visible)
+            .filter(|(_, &visible)| visible && self.overlays_visible)
+            .map(|(overlay, _)| overlay)
+    }
+    
+    /// The watermark, if it is currently shown
+    fn shown_watermark(&self) -> Option<&Watermark> {
+        self.watermark.as_ref().filter(|_| self.overlays_visible && self.watermark_visible)
+    }
+    
+    /// Highlight the mouse cursor and clicks on the screen layer (None to stop)
+    pub fn set_cursor_overlay(&mut self, overlay: Option<CursorOverlay>) {
+        self.cursor_overlay = overlay;
+    }
+    
+    /// The layer stack, bottom first
+    pub fn layers(&self) -> Vec<Layer> {
+        self.layers.clone()
+    }
+    
+    /// Change the z-order and opacity of layers (takes effect on the next frame)
+    ///
+    /// Layers with equal z-order keep their previous relative order.
+    pub fn set_layer_properties(&mut self, updates: &[LayerUpdate]) -> Result<(), String> {
+        for update in updates {
+            if let Some(opacity) = update.opacity {
+                if !(0.0..=1.0).contains(&opacity) {
+                    return Err(format!(
+                        "Layer opacity must be between 0.0 and 1.0, got {}",
+                        opacity
+                    ));
+                }
+            }
+        }
+        
+        for update in updates {
+            let Some(layer) = self.layers.iter_mut().find(|l| l.kind == update.kind) else {
+                continue;
+            };
+            if let Some(z_order) = update.z_order {
+                layer.z_order = z_order;
+            }
+            if let Some(opacity) = update.opacity {
+                layer.opacity = opacity;
+            }
+        }
+        self.layers.sort_by_key(|l| l.z_order);
+        Ok(())
+    }
+    
+    fn opacity(&self, kind: LayerKind) -> f32 {
+        self.layers
+            .iter()
+            .find(|l| l.kind == kind)
+            .map_or(1.0, |l| l.opacity)
+    }
+    
+    /// Top-left corner of a `width`x`height` PiP: the free-form position
+    /// where set, kept inside the frame, otherwise against the corner
+    fn pip_origin(&self, width: u32, height: u32) -> (u32, u32) {
+        let (output_width, output_height) = (self.config.output_width, self.config.output_height);
+        let (corner_x, corner_y) = Self::calculate_pip_position(
+            output_width,
+            output_height,
+            width,
+            height,
+            self.config.pip_position,
+            self.config.pip_padding,
+        );
+        let free = |fraction: Option<f32>, output: u32, size: u32, corner: u32| match fraction {
+            Some(fraction) => ((output as f32 * fraction).round() as u32).min(output.saturating_sub(size)),
+            None => corner,
+        };
+        (
+            free(self.config.pip_x, output_width, width, corner_x),
+            free(self.config.pip_y, output_height, height, corner_y),
+        )
+    }
+    
+    /// Calculate the top-left corner position for PiP overlay (or any other
+    /// box placed in a corner)
+    pub(crate) fn calculate_pip_position(
         output_width: u32,
         output_height: u32,
         pip_width: u32,
@@ -97,61 +538,342 @@ impl VideoCompositor {
     ) -> (u32, u32) {
         match position {
             PipPosition::TopLeft => (padding, padding),
-            PipPosition::TopRight => (output_width - pip_width - padding, padding),
-            PipPosition::BottomLeft => (padding, output_height - pip_height - padding),
+            PipPosition::TopRight => (output_width.saturating_sub(pip_width + padding), padding),
+            PipPosition::BottomLeft => (padding, output_height.saturating_sub(pip_height + padding)),
             PipPosition::BottomRight => (
-                output_width - pip_width - padding,
-                output_height - pip_height - padding,
+                output_width.saturating_sub(pip_width + padding),
+                output_height.saturating_sub(pip_height + padding),
             ),
         }
     }
     
+    /// Place a `frame_width`x`frame_height` webcam picture in the PiP area
+    /// according to the fit mode and shape
+    ///
+    /// A circle fills a square as tall as the area, so it always crops.
+    fn pip_placement(&self, frame_width: u32, frame_height: u32) -> PipPlacement {
+        let (area, fit) = match self.config.pip_shape {
+            PipShape::Circle => {
+                let side = self.pip_width.min(self.pip_height);
+                let (x, y) = self.pip_origin(side, side);
+                (PipPlacement { x, y, width: side, height: side, crop: (0, 0, 0, 0), radius: side / 2 }, PipFit::Cover)
+            }
+            PipShape::RoundedRect { radius } => (
+                PipPlacement {
+                    x: self.pip_x,
+                    y: self.pip_y,
+                    width: self.pip_width,
+                    height: self.pip_height,
+                    crop: (0, 0, 0, 0),
+                    radius,
+                },
+                self.config.pip_fit,
+            ),
+            PipShape::Rectangle => (
+                PipPlacement {
+                    x: self.pip_x,
+                    y: self.pip_y,
+                    width: self.pip_width,
+                    height: self.pip_height,
+                    crop: (0, 0, 0, 0),
+                    radius: 0,
+                },
+                self.config.pip_fit,
+            ),
+        };
+        let (area_width, area_height) = (area.width, area.height);
+        let full = (0, 0, frame_width, frame_height);
+        let area = PipPlacement { crop: full, ..area };
+        if frame_width == 0 || frame_height == 0 || area_width == 0 || area_height == 0 {
+            return area;
+        }
+        
+        // Cross-multiplied aspect ratios, frame vs area
+        let frame_wider = frame_width as u64 * area_height as u64 > frame_height as u64 * area_width as u64;
+        match fit {
+            PipFit::Stretch => area,
+            PipFit::Contain => {
+                let (width, height) = if frame_wider {
+                    (area_width, (area_width as u64 * frame_height as u64 / frame_width as u64) as u32)
+                } else {
+                    ((area_height as u64 * frame_width as u64 / frame_height as u64) as u32, area_height)
+                };
+                // Keep the picture against the same corner as the area
+                let (x, y) = self.pip_origin(width, height);
+                PipPlacement { x, y, width, height, ..area }
+            }
+            PipFit::Cover => {
+                let crop = if frame_wider {
+                    let width = (frame_height as u64 * area_width as u64 / area_height as u64) as u32;
+                    ((frame_width - width) / 2, 0, width, frame_height)
+                } else {
+                    let height = (frame_width as u64 * area_height as u64 / area_width as u64) as u32;
+                    (0, (frame_height - height) / 2, frame_width, height)
+                };
+                PipPlacement { crop, ..area }
+            }
+        }
+    }
+    
     /// Composite a screen frame with optional webcam overlay
     pub fn composite(
         &self,
         screen_frame: &ScreenFrame,
         webcam_frame: Option<&WebcamFrame>,
     ) -> CompositeFrame {
-        // Fast path: if no webcam overlay and dimensions match, skip BGRA→RGBA conversion
-        // This is significantly faster because FFmpeg can handle BGRA→YUV directly
+        let caption = self.caption_overlay.as_ref().and_then(|overlay| overlay.current_text());
+
+        // Fast path: if no webcam overlay and dimensions match, skip conversion to RGBA
+        // This is significantly faster because FFmpeg can handle BGRA/NV12→YUV directly
         if !self.config.include_webcam
+            && caption.is_none()
+            && self.visible_text_overlays().next().is_none()
+            && self.cursor_overlay.is_none()
+            && self.shown_watermark().is_none()
+            && self.opacity(LayerKind::Screen) >= 1.0
             && screen_frame.width == self.config.output_width
             && screen_frame.height == self.config.output_height
         {
             return self.composite_fast_path(screen_frame);
         }
 
-        // Slow path: need to use image processing for webcam overlay or scaling
-        let mut output = self.prepare_base_frame(screen_frame);
+        #[cfg(feature = "gpu")]
+        if let Some(frame) = self.composite_gpu(Some(screen_frame), webcam_frame, caption.as_deref()) {
+            return frame;
+        }
 
-        // Add webcam overlay if enabled and frame is available
-        if self.config.include_webcam {
-            if let Some(webcam) = webcam_frame {
-                self.overlay_webcam(&mut output, webcam);
+        // Slow path: need to use image processing for webcam overlay or scaling.
+        // Layers are drawn bottom-up onto black; an opaque bottom screen layer
+        // is used as the canvas directly.
+        let mut canvas: Option<RgbaImage> = None;
+        for layer in &self.layers {
+            match layer.kind {
+                LayerKind::Screen => {
+                    let mut base = self.prepare_base_frame(screen_frame);
+                    if let Some(ref cursor) = self.cursor_overlay {
+                        cursor.draw(&mut base);
+                    }
+                    match canvas {
+                        None if layer.opacity >= 1.0 => canvas = Some(base),
+                        _ => {
+                            let output = canvas.get_or_insert_with(|| self.black_canvas());
+                            for (x, y, pixel) in base.enumerate_pixels() {
+                                blend_pixel(output, x, y, *pixel, layer.opacity);
+                            }
+                        }
+                    }
+                }
+                // Add webcam overlay if enabled and frame is available
+                LayerKind::Webcam => {
+                    if let (true, Some(webcam)) = (self.config.include_webcam, webcam_frame) {
+                        let output = canvas.get_or_insert_with(|| self.black_canvas());
+                        self.overlay_webcam(output, webcam, layer.opacity);
+                    }
+                }
+                LayerKind::Text => {
+                    if self.visible_text_overlays().next().is_some() {
+                        let output = canvas.get_or_insert_with(|| self.black_canvas());
+                        for overlay in self.visible_text_overlays() {
+                            overlay.draw(output, screen_frame.timestamp, layer.opacity);
+                        }
+                    }
+                }
+                LayerKind::Captions => {
+                    if let (Some(overlay), Some(text)) = (&self.caption_overlay, &caption) {
+                        let output = canvas.get_or_insert_with(|| self.black_canvas());
+                        overlay.draw(output, text, layer.opacity);
+                    }
+                }
             }
         }
+        let mut output = canvas.unwrap_or_else(|| self.black_canvas());
+        self.draw_watermark(&mut output);
 
         CompositeFrame {
-            data: output.into_raw(),
+            data: self.pool.share(output.into_raw()),
             width: self.config.output_width,
             height: self.config.output_height,
             timestamp: screen_frame.timestamp,
-            is_bgra: false, // RGBA format after image processing
+            format: FrameFormat::Rgba, // RGBA format after image processing
+        }
+    }
+
+    /// Scale and blend on the GPU; None to use the CPU path instead
+    ///
+    /// Text and captions are drawn onto the downloaded frame, so they are only
+    /// handled here while they are above the screen and webcam.
+    #[cfg(feature = "gpu")]
+    fn composite_gpu(
+        &self,
+        screen_frame: Option<&ScreenFrame>,
+        webcam_frame: Option<&WebcamFrame>,
+        caption: Option<&str>,
+    ) -> Option<CompositeFrame> {
+        let mut gpu = self.gpu.lock();
+        let renderer = gpu.as_mut()?;
+        let drawn_on_cpu = |kind| match kind {
+            LayerKind::Text => self.visible_text_overlays().next().is_some(),
+            LayerKind::Captions => caption.is_some(),
+            LayerKind::Screen | LayerKind::Webcam => false,
+        };
+        let first_cpu_layer = self.layers.iter().position(|l| drawn_on_cpu(l.kind)).unwrap_or(self.layers.len());
+        if self.layers[first_cpu_layer..].iter().any(|l| matches!(l.kind, LayerKind::Screen | LayerKind::Webcam)) {
+            return None;
+        }
+        // Chroma keying and the cursor highlight are only done on the CPU
+        if self.config.chroma_key.enabled && self.config.include_webcam && screen_frame.is_some() {
+            return None;
+        }
+        if self.cursor_overlay.is_some() && screen_frame.is_some() {
+            return None;
+        }
+
+        let webcam_frame = webcam_frame.filter(|_| self.config.include_webcam || screen_frame.is_none());
+        let draws = match screen_frame {
+            Some(_) => self.gpu_draws(webcam_frame.map(|f| (f.width, f.height))),
+            None => {
+                let mut draw = self.full_frame_draw(DrawSource::Webcam, 1.0);
+                draw.uv = self.mirrored_uv(draw.uv);
+                vec![draw]
+            }
+        };
+        let data = match renderer.render(screen_frame, webcam_frame, &draws) {
+            Ok(data) => data,
+            Err(e) => {
+                eprintln!("GPU compositing failed, using the CPU from now on: {}", e);
+                *gpu = None;
+                return None;
+            }
+        };
+
+        let timestamp = screen_frame.map_or_else(
+            || webcam_frame.map(|f| f.timestamp).unwrap_or_default(),
+            |f| f.timestamp,
+        );
+        let mut output = RgbaImage::from_raw(self.config.output_width, self.config.output_height, data)?;
+        for layer in &self.layers[first_cpu_layer..] {
+            match layer.kind {
+                LayerKind::Text => {
+                    for overlay in self.visible_text_overlays() {
+                        overlay.draw(&mut output, timestamp, layer.opacity);
+                    }
+                }
+                LayerKind::Captions => {
+                    if let (Some(overlay), Some(text)) = (&self.caption_overlay, caption) {
+                        overlay.draw(&mut output, text, layer.opacity);
+                    }
+                }
+                LayerKind::Screen | LayerKind::Webcam => {}
+            }
+        }
+        self.draw_watermark(&mut output);
+
+        Some(CompositeFrame {
+            data: self.pool.share(output.into_raw()),
+            width: self.config.output_width,
+            height: self.config.output_height,
+            timestamp,
+            format: FrameFormat::Rgba,
+        })
+    }
+
+    /// GPU draws for the screen and webcam layers, bottom first (the same
+    /// geometry as the CPU path, PiP border included)
+    ///
+    /// `webcam` is the size of the webcam frame, if there is one.
+    #[cfg(any(feature = "gpu", test))]
+    fn gpu_draws(&self, webcam: Option<(u32, u32)>) -> Vec<LayerDraw> {
+        let mut draws = Vec::new();
+        for layer in &self.layers {
+            match layer.kind {
+                LayerKind::Screen => draws.push(self.full_frame_draw(DrawSource::Screen, layer.opacity)),
+                LayerKind::Webcam if webcam.is_some() && self.config.include_webcam => {
+                    let (frame_width, frame_height) = webcam.unwrap_or_default();
+                    let pip = self.pip_placement(frame_width, frame_height);
+                    
+                    // The border is a ring, so a translucent webcam still
+                    // shows the screen through it
+                    draws.push(LayerDraw {
+                        source: DrawSource::Color([1.0, 1.0, 1.0, 1.0]),
+                        x: pip.x.saturating_sub(PIP_BORDER),
+                        y: pip.y.saturating_sub(PIP_BORDER),
+                        width: pip.width + PIP_BORDER * 2,
+                        height: pip.height + PIP_BORDER * 2,
+                        uv: [0.0, 0.0, 1.0, 1.0],
+                        radius: border_radius(pip.radius) as f32,
+                        ring: PIP_BORDER as f32,
+                        opacity: layer.opacity,
+                    });
+                    
+                    let (crop_x, crop_y, crop_width, crop_height) = pip.crop;
+                    let (frame_width, frame_height) = (frame_width.max(1) as f32, frame_height.max(1) as f32);
+                    draws.push(LayerDraw {
+                        source: DrawSource::Webcam,
+                        x: pip.x,
+                        y: pip.y,
+                        width: pip.width,
+                        height: pip.height,
+                        uv: self.mirrored_uv([
+                            crop_x as f32 / frame_width,
+                            crop_y as f32 / frame_height,
+                            (crop_x + crop_width) as f32 / frame_width,
+                            (crop_y + crop_height) as f32 / frame_height,
+                        ]),
+                        radius: pip.radius as f32,
+                        ring: 0.0,
+                        opacity: layer.opacity,
+                    });
+                }
+                LayerKind::Webcam | LayerKind::Text | LayerKind::Captions => {}
+            }
+        }
+        draws
+    }
+
+    /// Webcam texture coordinates, with left and right swapped when mirroring
+    #[cfg(any(feature = "gpu", test))]
+    fn mirrored_uv(&self, uv: [f32; 4]) -> [f32; 4] {
+        let [left, top, right, bottom] = uv;
+        if self.config.mirror_webcam {
+            [right, top, left, bottom]
+        } else {
+            uv
         }
     }
 
-    /// Fast path compositing: directly pass BGRA data to encoder without conversion
+    #[cfg(any(feature = "gpu", test))]
+    fn full_frame_draw(&self, source: DrawSource, opacity: f32) -> LayerDraw {
+        LayerDraw {
+            source,
+            x: 0,
+            y: 0,
+            width: self.config.output_width,
+            height: self.config.output_height,
+            uv: [0.0, 0.0, 1.0, 1.0],
+            radius: 0.0,
+            ring: 0.0,
+            opacity,
+        }
+    }
+
+    /// Fast path compositing: directly pass captured data to encoder without conversion
     ///
-    /// This bypasses the expensive BGRA→RGBA conversion when:
+    /// This bypasses the expensive conversion to RGBA when:
     /// - Webcam overlay is disabled
     /// - Screen dimensions match output dimensions (no scaling needed)
     fn composite_fast_path(&self, screen_frame: &ScreenFrame) -> CompositeFrame {
+        let (data, format) = match screen_frame.pixel_format {
+            // BGRA format - encoder will use BGRA→YUV conversion
+            CapturePixelFormat::Bgra => (screen_frame.packed_bgra(&self.pool), FrameFormat::Bgra),
+            // NV12 format - already YUV, encoder only reorders planes
+            CapturePixelFormat::Nv12 => (screen_frame.packed_nv12(&self.pool), FrameFormat::Nv12),
+        };
         CompositeFrame {
-            data: screen_frame.to_packed_bgra(),
+            data,
             width: screen_frame.width,
             height: screen_frame.height,
             timestamp: screen_frame.timestamp,
-            is_bgra: true, // BGRA format - encoder will use BGRA→YUV conversion
+            format,
         }
     }
     
@@ -159,8 +881,9 @@ impl VideoCompositor {
     /// 
     /// This scales the screen frame to output dimensions if necessary
     fn prepare_base_frame(&self, screen_frame: &ScreenFrame) -> RgbaImage {
-        // Convert BGRA to RGBA
-        let rgba_data = screen_frame.to_rgba();
+        // Convert BGRA/NV12 to RGBA
+        let mut rgba_data = self.pool.take(screen_frame.width as usize * screen_frame.height as usize * 4);
+        screen_frame.append_rgba(&mut rgba_data);
         
         // Create image from raw data
         let screen_image: RgbaImage = ImageBuffer::from_raw(
@@ -173,19 +896,33 @@ impl VideoCompositor {
         if screen_frame.width != self.config.output_width 
             || screen_frame.height != self.config.output_height 
         {
-            image::imageops::resize(
+            let scaled = image::imageops::resize(
                 &screen_image,
                 self.config.output_width,
                 self.config.output_height,
                 image::imageops::FilterType::Triangle,
-            )
+            );
+            self.pool.recycle(screen_image.into_raw());
+            scaled
         } else {
             screen_image
         }
     }
     
+    /// Opaque black frame at the output size
+    fn black_canvas(&self) -> RgbaImage {
+        let (width, height) = (self.config.output_width, self.config.output_height);
+        let len = width as usize * height as usize * 4;
+        let mut data = self.pool.take(len);
+        data.resize(len, 0);
+        for pixel in data.chunks_exact_mut(4) {
+            pixel[3] = 255;
+        }
+        RgbaImage::from_raw(width, height, data).expect("Canvas has the output size")
+    }
+    
     /// Overlay webcam frame onto the output image
-    fn overlay_webcam(&self, output: &mut RgbaImage, webcam_frame: &WebcamFrame) {
+    fn overlay_webcam(&self, output: &mut RgbaImage, webcam_frame: &WebcamFrame, opacity: f32) {
         // Convert webcam frame to RGBA and create image
         let rgba_data = webcam_frame.to_rgba();
         let webcam_image: RgbaImage = ImageBuffer::from_raw(
@@ -194,32 +931,52 @@ impl VideoCompositor {
             rgba_data,
         ).expect("Failed to create image from webcam frame");
         
-        // Scale webcam to PiP size
-        let scaled_webcam = image::imageops::resize(
-            &webcam_image,
-            self.pip_width,
-            self.pip_height,
+        // Crop to the part shown by the fit mode and scale it to the PiP
+        let pip = self.pip_placement(webcam_frame.width, webcam_frame.height);
+        let (crop_x, crop_y, crop_width, crop_height) = pip.crop;
+        let shown = image::imageops::crop_imm(&webcam_image, crop_x, crop_y, crop_width, crop_height);
+        let mut scaled_webcam = image::imageops::resize(
+            &*shown,
+            pip.width,
+            pip.height,
             image::imageops::FilterType::Triangle,
         );
+        // The fit crops around the center, so mirroring afterwards is the same
+        if self.config.mirror_webcam {
+            image::imageops::flip_horizontal_in_place(&mut scaled_webcam);
+        }
         
-        // Draw border around PiP (optional visual enhancement)
-        let border_width = 2u32;
+        // A keyed webcam has no box around it
+        let keyer = self.config.chroma_key.enabled.then(|| ChromaKeyer::new(&self.config.chroma_key));
+        
+        // Draw border around PiP (optional visual enhancement), following
+        // its shape; edges of rounded shapes are anti-aliased
+        let border_width = PIP_BORDER;
         let border_color = Rgba([255, 255, 255, 200]);
+        let outer_width = pip.width + border_width * 2;
+        let outer_height = pip.height + border_width * 2;
+        let outer_radius = border_radius(pip.radius) as f32;
         
         // Draw border
-        for x in 0..self.pip_width + border_width * 2 {
-            for y in 0..self.pip_height + border_width * 2 {
-                let out_x = self.pip_x.saturating_sub(border_width) + x;
-                let out_y = self.pip_y.saturating_sub(border_width) + y;
-                
-                if out_x < self.config.output_width && out_y < self.config.output_height {
-                    let is_border = x < border_width 
-                        || x >= self.pip_width + border_width
-                        || y < border_width 
-                        || y >= self.pip_height + border_width;
+        if keyer.is_none() {
+            for x in 0..outer_width {
+                for y in 0..outer_height {
+                    let out_x = pip.x.saturating_sub(border_width) + x;
+                    let out_y = pip.y.saturating_sub(border_width) + y;
                     
-                    if is_border {
-                        output.put_pixel(out_x, out_y, border_color);
+                    if out_x < self.config.output_width && out_y < self.config.output_height {
+                        let outer = shape_coverage(x, y, outer_width, outer_height, outer_radius);
+                        let inner = match (x.checked_sub(border_width), y.checked_sub(border_width)) {
+                            (Some(x), Some(y)) if x < pip.width && y < pip.height => {
+                                shape_coverage(x, y, pip.width, pip.height, pip.radius as f32)
+                            }
+                            _ => 0.0,
+                        };
+                        let coverage = outer - inner;
+                        
+                        if coverage > 0.0 {
+                            blend_pixel(output, out_x, out_y, border_color, opacity * coverage);
+                        }
                     }
                 }
             }
@@ -227,11 +984,19 @@ impl VideoCompositor {
         
         // Overlay the scaled webcam
         for (x, y, pixel) in scaled_webcam.enumerate_pixels() {
-            let out_x = self.pip_x + x;
-            let out_y = self.pip_y + y;
+            let out_x = pip.x + x;
+            let out_y = pip.y + y;
             
             if out_x < self.config.output_width && out_y < self.config.output_height {
-                output.put_pixel(out_x, out_y, *pixel);
+                let mut coverage = shape_coverage(x, y, pip.width, pip.height, pip.radius as f32);
+                let mut pixel = *pixel;
+                if let Some(ref keyer) = keyer {
+                    pixel = keyer.key(pixel);
+                    coverage *= pixel.0[3] as f32 / 255.0;
+                }
+                if coverage > 0.0 {
+                    blend_pixel(output, out_x, out_y, pixel, opacity * coverage);
+                }
             }
         }
     }
@@ -240,6 +1005,11 @@ impl VideoCompositor {
     ///
     /// This is useful when only webcam recording is selected
     pub fn composite_webcam_only(&self, webcam_frame: &WebcamFrame) -> CompositeFrame {
+        #[cfg(feature = "gpu")]
+        if let Some(frame) = self.composite_gpu(None, Some(webcam_frame), None) {
+            return frame;
+        }
+
         // Convert and scale webcam to fill output
         let rgba_data = webcam_frame.to_rgba();
         let webcam_image: RgbaImage = ImageBuffer::from_raw(
@@ -249,24 +1019,69 @@ impl VideoCompositor {
         )
         .expect("Failed to create image from webcam frame");
 
-        let scaled = image::imageops::resize(
+        let mut scaled = image::imageops::resize(
             &webcam_image,
             self.config.output_width,
             self.config.output_height,
             image::imageops::FilterType::Triangle,
         );
+        if self.config.mirror_webcam {
+            image::imageops::flip_horizontal_in_place(&mut scaled);
+        }
+        for overlay in self.visible_text_overlays() {
+            overlay.draw(&mut scaled, webcam_frame.timestamp, self.opacity(LayerKind::Text));
+        }
+        self.draw_watermark(&mut scaled);
 
         CompositeFrame {
-            data: scaled.into_raw(),
+            data: self.pool.share(scaled.into_raw()),
             width: self.config.output_width,
             height: self.config.output_height,
             timestamp: webcam_frame.timestamp,
-            is_bgra: false, // RGBA format after image processing
+            format: FrameFormat::Rgba, // RGBA format after image processing
         }
     }
     
 }
 
+/// Width of the line around the webcam PiP, in pixels
+const PIP_BORDER: u32 = 2;
+
+/// Corner radius of the PiP border's outer edge (square corners stay square)
+fn border_radius(radius: u32) -> u32 {
+    if radius == 0 {
+        0
+    } else {
+        radius + PIP_BORDER
+    }
+}
+
+/// How much of pixel (x, y) of a `width`x`height` box lies inside the box
+/// with its corners rounded to `radius` (0.0 - 1.0, anti-aliased over a pixel)
+///
+/// The radius is capped at half the shorter side, which makes a square a circle.
+fn shape_coverage(x: u32, y: u32, width: u32, height: u32, radius: f32) -> f32 {
+    let radius = radius.min(width.min(height) as f32 / 2.0);
+    let (half_width, half_height) = (width as f32 / 2.0, height as f32 / 2.0);
+    // Signed distance from the pixel center to the rounded rectangle
+    let dx = (x as f32 + 0.5 - half_width).abs() - (half_width - radius);
+    let dy = (y as f32 + 0.5 - half_height).abs() - (half_height - radius);
+    let distance = dx.max(0.0).hypot(dy.max(0.0)) + dx.max(dy).min(0.0) - radius;
+    (0.5 - distance).clamp(0.0, 1.0)
+}
+
+/// Draw `color` over a pixel at `opacity` (fully opaque replaces it)
+pub(crate) fn blend_pixel(image: &mut RgbaImage, x: u32, y: u32, color: Rgba<u8>, opacity: f32) {
+    if opacity >= 1.0 {
+        image.put_pixel(x, y, color);
+        return;
+    }
+    let pixel = image.get_pixel_mut(x, y);
+    for (channel, value) in pixel.0.iter_mut().zip(color.0).take(3) {
+        *channel = (*channel as f32 * (1.0 - opacity) + value as f32 * opacity).round() as u8;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -294,9 +1109,166 @@ mod tests {
             include_webcam: true,
             pip_position: PipPosition::TopRight,
             pip_size_percent: 25,
+            pip_x: None,
+            pip_y: None,
+            pip_width: None,
             pip_padding: 20,
+            pip_fit: PipFit::Stretch,
+            pip_shape: PipShape::Rectangle,
+            chroma_key: ChromaKeyConfig::default(),
+            mirror_webcam: false,
+            watermark: None,
+            use_gpu: false,
         };
         
-        let _compositor = VideoCompositor::new(config);
+        let mut compositor = VideoCompositor::new(config);
+        assert_eq!((compositor.pip_x, compositor.pip_y), (1920 - 480 - 20, 20));
+        
+        compositor.set_pip_layout(Some(PipPosition::BottomLeft), Some(50));
+        assert_eq!((compositor.pip_width, compositor.pip_height), (960, 720));
+        assert_eq!((compositor.pip_x, compositor.pip_y), (20, 1080 - 720 - 20));
+    }
+    
+    #[test]
+    fn test_free_form_pip_placement() {
+        let mut compositor = VideoCompositor::new(CompositorConfig::default());
+        compositor.set_pip_placement(Some(0.5), Some(0.25), Some(0.2));
+        assert_eq!((compositor.pip_width, compositor.pip_height), (384, 288));
+        assert_eq!((compositor.pip_x, compositor.pip_y), (960, 270));
+        
+        // Kept inside the frame
+        compositor.set_pip_placement(Some(1.0), Some(1.0), None);
+        assert_eq!((compositor.pip_x, compositor.pip_y), (1920 - 384, 1080 - 288));
+        
+        // Picking a corner and size goes back to the corner layout
+        compositor.set_pip_layout(Some(PipPosition::TopLeft), Some(25));
+        assert_eq!((compositor.pip_x, compositor.pip_y, compositor.pip_width), (20, 20, 480));
+    }
+    
+    #[test]
+    fn test_layer_order_and_opacity() {
+        let mut compositor = VideoCompositor::new(CompositorConfig::default());
+        
+        // Webcam to the bottom, screen at half opacity
+        compositor
+            .set_layer_properties(&[
+                LayerUpdate { kind: LayerKind::Webcam, z_order: Some(-1), opacity: None },
+                LayerUpdate { kind: LayerKind::Screen, z_order: None, opacity: Some(0.5) },
+            ])
+            .unwrap();
+        let kinds: Vec<LayerKind> = compositor.layers().iter().map(|l| l.kind).collect();
+        assert_eq!(kinds, [LayerKind::Webcam, LayerKind::Screen, LayerKind::Text, LayerKind::Captions]);
+        assert_eq!(compositor.opacity(LayerKind::Screen), 0.5);
+        
+        assert!(compositor
+            .set_layer_properties(&[LayerUpdate { kind: LayerKind::Screen, z_order: None, opacity: Some(1.5) }])
+            .is_err());
+        
+        let mut image = RgbaImage::from_pixel(1, 1, Rgba([0, 0, 0, 255]));
+        blend_pixel(&mut image, 0, 0, Rgba([200, 100, 50, 255]), 0.5);
+        assert_eq!(image.get_pixel(0, 0).0, [100, 50, 25, 255]);
+    }
+    
+    #[test]
+    fn test_gpu_draws_follow_layers() {
+        let mut compositor = VideoCompositor::new(CompositorConfig {
+            include_webcam: true,
+            ..CompositorConfig::default()
+        });
+        compositor
+            .set_layer_properties(&[LayerUpdate { kind: LayerKind::Webcam, z_order: None, opacity: Some(0.5) }])
+            .unwrap();
+        
+        let draws = compositor.gpu_draws(Some((640, 480)));
+        let sources: Vec<DrawSource> = draws.iter().map(|d| d.source).collect();
+        assert_eq!(sources, [DrawSource::Screen, DrawSource::Color([1.0; 4]), DrawSource::Webcam]);
+        assert_eq!((draws[0].width, draws[0].height, draws[0].opacity), (1920, 1080, 1.0));
+        // The border is a ring around the PiP
+        assert_eq!((draws[1].x, draws[1].y, draws[1].width, draws[1].height), (1418, 18, 484, 364));
+        assert_eq!(draws[1].ring, 2.0);
+        assert_eq!((draws[2].x, draws[2].y, draws[2].opacity), (1420, 20, 0.5));
+        
+        assert_eq!(compositor.gpu_draws(None).len(), 1);
+        
+        // Mirroring swaps the webcam's left and right edges
+        compositor.set_mirror_webcam(true);
+        assert_eq!(compositor.gpu_draws(Some((640, 480)))[2].uv, [1.0, 0.0, 0.0, 1.0]);
+    }
+    
+    #[test]
+    fn test_pip_fit_keeps_webcam_aspect() {
+        // 480x360 PiP area at (1420, 20); a 16:9 webcam
+        let mut compositor = VideoCompositor::new(CompositorConfig::default());
+        let placement = |compositor: &VideoCompositor| compositor.pip_placement(1280, 720);
+        
+        let contain = placement(&compositor);
+        assert_eq!((contain.x, contain.y, contain.width, contain.height), (1420, 20, 480, 270));
+        assert_eq!(contain.crop, (0, 0, 1280, 720));
+        
+        compositor.set_pip_fit(PipFit::Cover);
+        let cover = placement(&compositor);
+        assert_eq!((cover.x, cover.y, cover.width, cover.height), (1420, 20, 480, 360));
+        assert_eq!(cover.crop, (160, 0, 960, 720));
+        
+        compositor.set_pip_fit(PipFit::Stretch);
+        assert_eq!(placement(&compositor).crop, (0, 0, 1280, 720));
+        assert_eq!(placement(&compositor).height, 360);
+        
+        // Contain at the bottom keeps the padding below the picture
+        compositor.set_pip_fit(PipFit::Contain);
+        compositor.set_pip_layout(Some(PipPosition::BottomLeft), None);
+        assert_eq!((placement(&compositor).x, placement(&compositor).y), (20, 1080 - 270 - 20));
+    }
+    
+    #[test]
+    fn test_watermark_is_blended_in_its_corner() {
+        let path = std::env::temp_dir().join(format!("asmr_watermark_{}.png", std::process::id()));
+        RgbaImage::from_pixel(4, 4, Rgba([200, 0, 0, 128])).save(&path).unwrap();
+        let mut compositor = VideoCompositor::new(CompositorConfig {
+            output_width: 64,
+            output_height: 32,
+            pip_padding: 2,
+            watermark: Some(WatermarkConfig {
+                path: path.clone(),
+                position: PipPosition::BottomRight,
+                opacity: 1.0,
+            }),
+            ..CompositorConfig::default()
+        });
+        std::fs::remove_file(&path).unwrap();
+        
+        let mut frame = RgbaImage::from_pixel(64, 32, Rgba([0, 0, 0, 255]));
+        compositor.draw_watermark(&mut frame);
+        // Half transparent in the image itself
+        assert_eq!(frame.get_pixel(64 - 2 - 4, 32 - 2 - 4).0, [100, 0, 0, 255]);
+        assert_eq!(frame.get_pixel(64 - 2, 32 - 2).0, [0, 0, 0, 255]);
+        
+        // Hidden mid-recording, on its own or with all overlays
+        let mut frame = RgbaImage::from_pixel(64, 32, Rgba([0, 0, 0, 255]));
+        compositor.set_watermark_visible(false);
+        compositor.draw_watermark(&mut frame);
+        compositor.set_watermark_visible(true);
+        compositor.set_overlays_visible(false);
+        compositor.draw_watermark(&mut frame);
+        assert_eq!(frame.get_pixel(64 - 2 - 4, 32 - 2 - 4).0, [0, 0, 0, 255]);
+    }
+    
+    #[test]
+    fn test_pip_shape_masks_edges() {
+        // A circle is a square as tall as the area, cropped from the webcam
+        let mut compositor = VideoCompositor::new(CompositorConfig::default());
+        compositor.set_pip_shape(PipShape::Circle);
+        let circle = compositor.pip_placement(1280, 720);
+        assert_eq!((circle.x, circle.y, circle.width, circle.height), (1920 - 360 - 20, 20, 360, 360));
+        assert_eq!((circle.crop, circle.radius), ((280, 0, 720, 720), 180));
+        
+        // Corners are cut, the middle and straight edges are kept, and the
+        // curve is anti-aliased
+        assert_eq!(shape_coverage(0, 0, 100, 60, 0.0), 1.0);
+        assert_eq!(shape_coverage(0, 0, 100, 60, 20.0), 0.0);
+        assert_eq!(shape_coverage(50, 0, 100, 60, 20.0), 1.0);
+        assert_eq!(shape_coverage(0, 30, 100, 60, 20.0), 1.0);
+        let edge = shape_coverage(6, 5, 100, 60, 20.0);
+        assert!(edge > 0.0 && edge < 1.0, "{}", edge);
     }
 }
@@ -0,0 +1,266 @@
+//! Joining Recordings
+//!
+//! Concatenates recordings made with the same settings - the parts of a
+//! session recorded in pieces, or several takes - into one file without
+//! re-encoding. Every part must have the same container, codecs, video size
+//! and audio format as the first; packets are copied as they are, each part's
+//! timestamps shifted to start where the previous part ended.
+//!
+//! Only the main video and audio tracks are kept. Joining needs the `ffmpeg`
+//! feature.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::command;
+
+use crate::output_paths::{allowed_output_dirs, check_output_path};
+use crate::recording::RecordingError;
+
+/// Outcome of a join
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConcatReport {
+    pub output_path: String,
+    /// Number of recordings joined
+    pub parts: usize,
+    pub duration_ms: u64,
+}
+
+/// The properties parts must share to be joined losslessly
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(not(feature = "ffmpeg"), allow(dead_code))]
+struct PartFormat {
+    /// Codec name, width and height
+    video: Option<(String, u32, u32)>,
+    /// Codec name, sample rate and channels
+    audio: Option<(String, u32, u16)>,
+}
+
+/// Check there is something to join and the output has the parts' extension
+fn validate_paths(paths: &[PathBuf], output: &Path) -> Result<(), String> {
+    if paths.len() < 2 {
+        return Err("Choose at least two recordings to join".to_string());
+    }
+    let extension = |path: &Path| path.extension().map(|ext| ext.to_string_lossy().to_lowercase());
+    let first = extension(&paths[0]);
+    if let Some(path) = paths.iter().find(|path| extension(path) != first) {
+        return Err(format!(
+            "{} is in a different format from {}",
+            path.display(),
+            paths[0].display()
+        ));
+    }
+    if extension(output) != first {
+        return Err(format!(
+            "The joined file must be a .{} like the recordings",
+            first.unwrap_or_default()
+        ));
+    }
+    if paths.iter().any(|path| path == output) {
+        return Err("The joined file can't replace one of the recordings".to_string());
+    }
+    Ok(())
+}
+
+/// Resolve the recordings and the joined file, each of which must be inside
+/// an allowed output directory, and check they can be joined
+fn check_paths(paths: &[PathBuf], output_path: &Path) -> Result<(Vec<PathBuf>, PathBuf), RecordingError> {
+    if let Some(path) = paths.iter().find(|path| !path.is_file()) {
+        return Err(format!("Recording not found: {}", path.display()).into());
+    }
+    let allowed = allowed_output_dirs();
+    let parts = paths
+        .iter()
+        .map(|path| check_output_path(path, &allowed))
+        .collect::<Result<Vec<_>, _>>()?;
+    let output_path = check_output_path(output_path, &allowed)?;
+    validate_paths(&parts, &output_path).map_err(RecordingError::InvalidConfig)?;
+    Ok((parts, output_path))
+}
+
+/// Check `part` can be appended to recordings in the `first` format
+#[cfg_attr(not(feature = "ffmpeg"), allow(dead_code))]
+fn check_compatible(first: &PartFormat, part: &PartFormat, path: &Path) -> Result<(), String> {
+    if first.video != part.video {
+        let describe = |video: &Option<(String, u32, u32)>| match video {
+            Some((codec, width, height)) => format!("{} {}x{}", codec, width, height),
+            None => "no".to_string(),
+        };
+        return Err(format!(
+            "{} has {} video where the first recording has {}",
+            path.display(),
+            describe(&part.video),
+            describe(&first.video)
+        ));
+    }
+    if first.audio != part.audio {
+        let describe = |audio: &Option<(String, u32, u16)>| match audio {
+            Some((codec, rate, channels)) => format!("{} {}Hz {}-channel", codec, rate, channels),
+            None => "no".to_string(),
+        };
+        return Err(format!(
+            "{} has {} audio where the first recording has {}",
+            path.display(),
+            describe(&part.audio),
+            describe(&first.audio)
+        ));
+    }
+    Ok(())
+}
+
+/// Join the recordings at `paths`, in order, into `output`
+#[cfg(feature = "ffmpeg")]
+pub fn concat_files(paths: &[PathBuf], output_path: &Path) -> Result<ConcatReport, RecordingError> {
+    use crate::media_file::{add_copy_stream, create_output, open_input, rewrite, suffix, write_header};
+    use ffmpeg_next::media::Type;
+    use ffmpeg_next::Rescale;
+
+    let (paths, output_path) = check_paths(paths, output_path)?;
+    let (paths, output_path) = (paths.as_slice(), output_path.as_path());
+    if output_path.exists() {
+        return Err(format!("{} already exists", output_path.display()).into());
+    }
+    ffmpeg_next::init().map_err(|e| format!("FFmpeg init failed: {}", e))?;
+
+    let formats = paths.iter().map(|path| probe(path)).collect::<Result<Vec<_>, _>>()?;
+    for (path, format) in paths.iter().zip(&formats).skip(1) {
+        check_compatible(&formats[0], format, path)?;
+    }
+
+    let micros = ffmpeg_next::Rational(1, 1_000_000);
+    let mut duration_us = 0;
+    rewrite(output_path, suffix::JOINING, |temp_path| {
+        let first = open_input(&paths[0])?;
+        let mut output = create_output(temp_path, &first)?;
+        // Output stream for the main track of each kind
+        let mut tracks = Vec::new();
+        for kind in [Type::Video, Type::Audio] {
+            if let Some(stream) = first.streams().best(kind) {
+                if let Some(index) = add_copy_stream(&mut output, &stream)? {
+                    tracks.push((kind, index));
+                }
+            }
+        }
+        write_header(&mut output, temp_path)?;
+        let time_bases: Vec<_> = output.streams().map(|s| s.time_base()).collect();
+
+        // Where the next part starts in the joined file
+        let mut offset_us: i64 = 0;
+        for path in paths {
+            let mut input = open_input(path)?;
+            let start_us = input.start_time().max(0);
+            // Input stream index to output stream index
+            let map: Vec<(usize, usize)> = tracks
+                .iter()
+                .filter_map(|&(kind, index)| input.streams().best(kind).map(|s| (s.index(), index)))
+                .collect();
+
+            let mut end_us = offset_us;
+            for (stream, mut packet) in input.packets() {
+                let Some(&(_, index)) = map.iter().find(|(input_index, _)| *input_index == stream.index()) else {
+                    continue;
+                };
+                let time_base = time_bases[index];
+                let shift = (offset_us - start_us).rescale(micros, time_base);
+                packet.rescale_ts(stream.time_base(), time_base);
+                packet.set_pts(packet.pts().map(|pts| pts + shift));
+                packet.set_dts(packet.dts().map(|dts| dts + shift));
+                if let Some(pts) = packet.pts() {
+                    end_us = end_us.max((pts + packet.duration()).rescale(time_base, micros));
+                }
+                packet.set_stream(index);
+                packet.set_position(-1);
+                packet
+                    .write_interleaved(&mut output)
+                    .map_err(|e| format!("Failed to copy packet from {}: {}", path.display(), e))?;
+            }
+            // The longer of the tracks decides, keeping the next part in sync
+            offset_us = end_us;
+        }
+        duration_us = offset_us;
+
+        output
+            .write_trailer()
+            .map_err(|e| format!("Failed to write trailer: {}", e))
+    })?;
+
+    println!("Joined {} recordings into {}", paths.len(), output_path.display());
+    Ok(ConcatReport {
+        output_path: output_path.to_string_lossy().to_string(),
+        parts: paths.len(),
+        duration_ms: (duration_us / 1000) as u64,
+    })
+}
+
+#[cfg(not(feature = "ffmpeg"))]
+pub fn concat_files(paths: &[PathBuf], output_path: &Path) -> Result<ConcatReport, RecordingError> {
+    check_paths(paths, output_path)?;
+    Err("Joining recordings requires FFmpeg support".to_string().into())
+}
+
+/// Codecs, video size and audio format of the main tracks of a recording
+#[cfg(feature = "ffmpeg")]
+fn probe(path: &Path) -> Result<PartFormat, String> {
+    use ffmpeg_next::codec::context::Context;
+    use ffmpeg_next::media::Type;
+
+    let input = crate::media_file::open_input(path)?;
+    let video = match input.streams().best(Type::Video) {
+        Some(stream) => {
+            let decoder = Context::from_parameters(stream.parameters())
+                .and_then(|context| context.decoder().video())
+                .map_err(|e| format!("Failed to read video of {}: {}", path.display(), e))?;
+            Some((format!("{:?}", decoder.id()), decoder.width(), decoder.height()))
+        }
+        None => None,
+    };
+    let audio = match input.streams().best(Type::Audio) {
+        Some(stream) => {
+            let decoder = Context::from_parameters(stream.parameters())
+                .and_then(|context| context.decoder().audio())
+                .map_err(|e| format!("Failed to read audio of {}: {}", path.display(), e))?;
+            Some((format!("{:?}", decoder.id()), decoder.rate(), decoder.channels()))
+        }
+        None => None,
+    };
+    Ok(PartFormat { video, audio })
+}
+
+/// Tauri command: Join recordings with the same settings into `output`,
+/// without re-encoding
+///
+/// The recordings are left as they are. They and `output` must all be
+/// inside an allowed output directory.
+#[command]
+pub async fn concat_recordings(paths: Vec<String>, output: String) -> Result<ConcatReport, RecordingError> {
+    let paths: Vec<PathBuf> = paths.into_iter().map(PathBuf::from).collect();
+    tauri::async_runtime::spawn_blocking(move || concat_files(&paths, Path::new(&output)))
+        .await
+        .map_err(|e| format!("Joining recordings failed: {}", e))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_matching_recordings_join() {
+        let parts = [PathBuf::from("/tmp/part1.mp4"), PathBuf::from("/tmp/part2.mp4")];
+        assert!(validate_paths(&parts, Path::new("/tmp/joined.mp4")).is_ok());
+        assert!(validate_paths(&parts[..1], Path::new("/tmp/joined.mp4")).is_err());
+        assert!(validate_paths(&parts, Path::new("/tmp/joined.mkv")).is_err());
+        assert!(validate_paths(&parts, Path::new("/tmp/part2.mp4")).is_err());
+
+        let first = PartFormat {
+            video: Some(("H264".to_string(), 1920, 1080)),
+            audio: Some(("AAC".to_string(), 48_000, 2)),
+        };
+        assert!(check_compatible(&first, &first.clone(), &parts[1]).is_ok());
+        let smaller = PartFormat {
+            video: Some(("H264".to_string(), 1280, 720)),
+            ..first.clone()
+        };
+        let error = check_compatible(&first, &smaller, &parts[1]).unwrap_err();
+        assert!(error.contains("H264 1280x720 video"));
+    }
+}
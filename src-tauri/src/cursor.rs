@@ -0,0 +1,286 @@
+//! Cursor Highlight
+//!
+//! Polls the global mouse position and buttons while recording, and has the
+//! compositor draw a highlight ring around the cursor and a ripple for each
+//! click onto the screen layer, as tutorial screencasts usually do.
+//!
+//! Polling (device_query) needs the `cursor` feature; without it, enabling the
+//! highlight fails when the recording starts.
+
+use image::{Rgba, RgbaImage};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How long a click ripple stays visible
+const RIPPLE_DURATION: Duration = Duration::from_millis(500);
+
+/// How often the mouse is polled
+#[cfg(feature = "cursor")]
+const POLL_INTERVAL: Duration = Duration::from_millis(8);
+
+/// Smallest and largest accepted highlight radius, in output pixels
+const RADIUS_RANGE: std::ops::RangeInclusive<f32> = 4.0..=200.0;
+
+#[cfg(not(feature = "cursor"))]
+const FEATURE_MISSING: &str = "Cursor highlighting requires a build with the `cursor` feature";
+
+/// Cursor highlight settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CursorHighlightConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// RGB of the ring and ripples
+    #[serde(default = "default_color")]
+    pub color: [u8; 3],
+    /// Ring radius in output pixels
+    #[serde(default = "default_radius")]
+    pub radius: f32,
+    /// Draw a ripple where the mouse is clicked
+    #[serde(default = "default_show_clicks")]
+    pub show_clicks: bool,
+}
+
+fn default_color() -> [u8; 3] {
+    [255, 214, 0]
+}
+
+fn default_radius() -> f32 {
+    24.0
+}
+
+fn default_show_clicks() -> bool {
+    true
+}
+
+impl Default for CursorHighlightConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            color: default_color(),
+            radius: default_radius(),
+            show_clicks: default_show_clicks(),
+        }
+    }
+}
+
+impl CursorHighlightConfig {
+    /// Check the radius is in the accepted range
+    pub fn validate(&self) -> Result<(), String> {
+        if !RADIUS_RANGE.contains(&self.radius) {
+            return Err(format!(
+                "Cursor highlight radius must be {}-{} pixels, got {}",
+                RADIUS_RANGE.start(),
+                RADIUS_RANGE.end(),
+                self.radius
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The captured display, in the units the mouse position is reported in
+/// (points on macOS, pixels elsewhere)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DisplayBounds {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl DisplayBounds {
+    /// Bounds of display `display_index`, in the order the screen capture
+    /// numbers them (as `CaptureRegion::of_window` assumes)
+    pub fn of_display(app: &tauri::AppHandle, display_index: usize) -> Result<Self, String> {
+        let monitors = app
+            .available_monitors()
+            .map_err(|e| format!("Failed to list monitors: {}", e))?;
+        let monitor = monitors
+            .get(display_index)
+            .or(monitors.first())
+            .ok_or("No monitor to track the cursor on")?;
+        let scale = if cfg!(target_os = "macos") { monitor.scale_factor() } else { 1.0 };
+        let (position, size) = (monitor.position(), monitor.size());
+        Ok(Self {
+            x: position.x as f64 / scale,
+            y: position.y as f64 / scale,
+            width: size.width as f64 / scale,
+            height: size.height as f64 / scale,
+        })
+    }
+
+    /// Mouse position as fractions of the display; None when it is on another one
+    #[cfg(any(feature = "cursor", test))]
+    fn locate(&self, x: i32, y: i32) -> Option<(f64, f64)> {
+        let fx = (x as f64 - self.x) / self.width;
+        let fy = (y as f64 - self.y) / self.height;
+        ((0.0..1.0).contains(&fx) && (0.0..1.0).contains(&fy)).then_some((fx, fy))
+    }
+}
+
+/// A click, as fractions of the display
+struct Click {
+    x: f64,
+    y: f64,
+    at: Instant,
+}
+
+/// Latest mouse state, shared between the poller and the compositor
+#[derive(Default)]
+struct CursorState {
+    /// As fractions of the display; None while the cursor is elsewhere
+    position: Option<(f64, f64)>,
+    /// Clicks whose ripple is still showing, oldest first
+    clicks: VecDeque<Click>,
+}
+
+/// Polls the mouse on its own thread
+pub struct CursorTracker {
+    state: Arc<Mutex<CursorState>>,
+    running: Arc<Mutex<bool>>,
+}
+
+impl CursorTracker {
+    /// Start polling the mouse over `bounds`
+    #[cfg(feature = "cursor")]
+    pub fn start(bounds: DisplayBounds) -> Result<Self, String> {
+        let state = Arc::new(Mutex::new(CursorState::default()));
+        let running = Arc::new(Mutex::new(true));
+        let thread_state = state.clone();
+        let thread_running = running.clone();
+
+        std::thread::Builder::new()
+            .name("cursor-tracker".to_string())
+            .spawn(move || {
+                let device = device_query::DeviceState::new();
+                let mut was_pressed = false;
+                while *thread_running.lock() {
+                    let mouse = device_query::DeviceQuery::get_mouse(&device);
+                    // Index 0 is unused; 1 onwards are the buttons
+                    let pressed = mouse.button_pressed.iter().skip(1).any(|&down| down);
+                    let position = bounds.locate(mouse.coords.0, mouse.coords.1);
+                    {
+                        let mut state = thread_state.lock();
+                        state.position = position;
+                        if let (true, false, Some((x, y))) = (pressed, was_pressed, position) {
+                            state.clicks.push_back(Click { x, y, at: Instant::now() });
+                        }
+                        while state.clicks.front().is_some_and(|c| c.at.elapsed() > RIPPLE_DURATION) {
+                            state.clicks.pop_front();
+                        }
+                    }
+                    was_pressed = pressed;
+                    std::thread::sleep(POLL_INTERVAL);
+                }
+                println!("Cursor tracker stopped");
+            })
+            .map_err(|e| format!("Failed to start cursor tracker: {}", e))?;
+
+        println!("Cursor tracker started");
+        Ok(Self { state, running })
+    }
+
+    #[cfg(not(feature = "cursor"))]
+    pub fn start(_bounds: DisplayBounds) -> Result<Self, String> {
+        Err(FEATURE_MISSING.to_string())
+    }
+
+    /// Overlay that draws the tracked cursor with `config`
+    pub fn overlay(&self, config: CursorHighlightConfig) -> CursorOverlay {
+        CursorOverlay {
+            state: self.state.clone(),
+            config,
+        }
+    }
+
+    pub fn stop(&self) {
+        *self.running.lock() = false;
+    }
+}
+
+/// Draws the cursor ring and click ripples onto screen frames
+pub struct CursorOverlay {
+    state: Arc<Mutex<CursorState>>,
+    config: CursorHighlightConfig,
+}
+
+impl CursorOverlay {
+    /// Draw onto a screen frame scaled to `image`'s size
+    pub fn draw(&self, image: &mut RgbaImage) {
+        let (width, height) = (image.width() as f64, image.height() as f64);
+        let color = self.config.color;
+        let radius = self.config.radius;
+        let state = self.state.lock();
+
+        if self.config.show_clicks {
+            for click in &state.clicks {
+                let age = click.at.elapsed().as_secs_f32() / RIPPLE_DURATION.as_secs_f32();
+                if age < 1.0 {
+                    let ripple = radius * (1.0 + 1.5 * age);
+                    let center = ((click.x * width) as f32, (click.y * height) as f32);
+                    draw_ring(image, center, ripple - 3.0, ripple, color, 0.8 * (1.0 - age));
+                }
+            }
+        }
+        if let Some((x, y)) = state.position {
+            let center = ((x * width) as f32, (y * height) as f32);
+            draw_ring(image, center, 0.0, radius - 2.0, color, 0.3);
+            draw_ring(image, center, radius - 2.0, radius, color, 0.9);
+        }
+    }
+}
+
+/// Blend the band between `inner` and `outer` radius around `center`,
+/// anti-aliased at both edges (`inner` 0 fills a disk)
+fn draw_ring(image: &mut RgbaImage, center: (f32, f32), inner: f32, outer: f32, color: [u8; 3], alpha: f32) {
+    let (cx, cy) = center;
+    let x0 = (cx - outer - 1.0).max(0.0) as u32;
+    let y0 = (cy - outer - 1.0).max(0.0) as u32;
+    let x1 = ((cx + outer + 1.0).max(0.0) as u32).min(image.width());
+    let y1 = ((cy + outer + 1.0).max(0.0) as u32).min(image.height());
+    let [r, g, b] = color;
+
+    for y in y0..y1 {
+        for x in x0..x1 {
+            let distance = (x as f32 + 0.5 - cx).hypot(y as f32 + 0.5 - cy);
+            let mut coverage = (outer - distance + 0.5).clamp(0.0, 1.0);
+            if inner > 0.0 {
+                coverage *= (distance - inner + 0.5).clamp(0.0, 1.0);
+            }
+            if coverage > 0.0 {
+                crate::compositor::blend_pixel(image, x, y, Rgba([r, g, b, 255]), alpha * coverage);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_ring_and_ripple() {
+        let bounds = DisplayBounds { x: 1920.0, y: 0.0, width: 1920.0, height: 1080.0 };
+        assert_eq!(bounds.locate(1920 + 480, 270), Some((0.25, 0.25)));
+        assert_eq!(bounds.locate(100, 270), None);
+
+        let state = Arc::new(Mutex::new(CursorState {
+            position: Some((0.25, 0.25)),
+            clicks: VecDeque::from([Click { x: 0.75, y: 0.75, at: Instant::now() }]),
+        }));
+        let overlay = CursorOverlay { state, config: CursorHighlightConfig::default() };
+        let mut image = RgbaImage::from_pixel(400, 200, Rgba([0, 0, 0, 255]));
+        overlay.draw(&mut image);
+
+        // Ring edge, tinted middle, ripple around the click, untouched elsewhere
+        assert!(image.get_pixel(100 + 23, 50).0[0] > 200);
+        let middle = image.get_pixel(100, 50).0;
+        assert!(middle[0] > 50 && middle[0] < 120, "{:?}", middle);
+        assert!(image.get_pixel(300 + 23, 150).0[0] > 150);
+        assert_eq!(image.get_pixel(200, 100).0, [0, 0, 0, 255]);
+    }
+}
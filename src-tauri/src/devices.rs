@@ -0,0 +1,123 @@
+//! Capture Device Resolution
+//!
+//! Maps the device IDs reported by `get_available_devices` back to the handles
+//! each capture backend needs, and falls back to the system default when a
+//! requested device is no longer present.
+
+use cpal::traits::{DeviceTrait, HostTrait};
+
+use crate::recording::{DeviceFallback, DeviceInfo, DeviceKind};
+
+/// List available webcams as `webcam_<index>` IDs
+pub fn list_webcams() -> Vec<DeviceInfo> {
+    use nokhwa::utils::ApiBackend;
+
+    match nokhwa::query(ApiBackend::Auto) {
+        Ok(cameras) if !cameras.is_empty() => cameras
+            .iter()
+            .enumerate()
+            .map(|(i, camera)| DeviceInfo {
+                id: webcam_id(i),
+                name: camera.human_name(),
+            })
+            .collect(),
+        _ => vec![DeviceInfo {
+            id: webcam_id(0),
+            name: "Default Camera".to_string(),
+        }],
+    }
+}
+
+fn webcam_id(index: usize) -> String {
+    format!("webcam_{}", index)
+}
+
+/// Resolve a microphone ID (its device name) to a connected input device
+///
+/// Returns `None` for the default device, plus a fallback notice if the
+/// requested device is missing.
+pub fn resolve_microphone(requested: Option<&str>) -> (Option<String>, Option<DeviceFallback>) {
+    let Some(id) = requested else {
+        return (None, None);
+    };
+
+    let found = cpal::default_host()
+        .input_devices()
+        .map(|mut devices| devices.any(|d| d.name().map(|n| n == id).unwrap_or(false)))
+        .unwrap_or(false);
+
+    if found {
+        (Some(id.to_string()), None)
+    } else {
+        (None, Some(fallback(DeviceKind::Microphone, id)))
+    }
+}
+
+/// Resolve a webcam ID to a camera index
+pub fn resolve_webcam(requested: Option<&str>) -> (usize, Option<DeviceFallback>) {
+    let Some(id) = requested else {
+        return (0, None);
+    };
+
+    let available = list_webcams();
+    let index = id
+        .strip_prefix("webcam_")
+        .and_then(|n| n.parse::<usize>().ok())
+        .filter(|_| available.iter().any(|d| d.id == id));
+
+    match index {
+        Some(index) => (index, None),
+        None => (0, Some(fallback(DeviceKind::Webcam, id))),
+    }
+}
+
+/// Resolve a screen ID to a display index
+pub fn resolve_screen(requested: Option<&str>) -> (usize, Option<DeviceFallback>) {
+    let Some(id) = requested else {
+        return (0, None);
+    };
+
+    match screen_index(id) {
+        Some(index) => (index, None),
+        None => (0, Some(fallback(DeviceKind::Screen, id))),
+    }
+}
+
+/// On macOS screen IDs carry the CoreGraphics display ID, which is looked up
+/// in the current display list
+#[cfg(target_os = "macos")]
+pub(crate) fn screen_index(id: &str) -> Option<usize> {
+    let display_id: u32 = id.strip_prefix("screen_")?.parse().ok()?;
+    let content = screencapturekit::prelude::SCShareableContent::get().ok()?;
+    content
+        .displays()
+        .iter()
+        .position(|d| d.display_id() == display_id)
+}
+
+#[cfg(target_os = "windows")]
+pub(crate) fn screen_index(id: &str) -> Option<usize> {
+    let index: usize = id.strip_prefix("screen_")?.parse().ok()?;
+    windows_capture::monitor::Monitor::from_index(index).ok()?;
+    Some(index)
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+pub(crate) fn screen_index(id: &str) -> Option<usize> {
+    let index: usize = id.strip_prefix("screen_")?.parse().ok()?;
+    let count = scrap::Display::all().ok()?.len();
+    (index < count).then_some(index)
+}
+
+fn fallback(kind: DeviceKind, requested_id: &str) -> DeviceFallback {
+    println!("{:?} '{}' not found, using default device", kind, requested_id);
+    DeviceFallback {
+        kind,
+        requested_id: requested_id.to_string(),
+        message: format!(
+            "{} \"{}\" is not available; recording with the default device instead",
+            kind.label(),
+            requested_id
+        ),
+    }
+}
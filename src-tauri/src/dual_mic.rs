@@ -0,0 +1,364 @@
+//! Two-Microphone Binaural Capture
+//!
+//! DIY binaural rigs often put a USB microphone in each ear of a dummy head
+//! instead of running two capsules into one stereo interface. With `dualMic`
+//! set, each ear is captured from its own device as mono and the two are
+//! merged into the left and right channels of one stereo stream. That
+//! stream is passed to the mixer as the binaural microphone.
+//!
+//! Two devices run on two clocks and can't be sample-locked. Both ears are
+//! captured against one clock and placed by their chunk timestamps: the
+//! ear that starts later is padded with silence from the common start, and
+//! audio arriving after a gap lands at its own time rather than straight
+//! after what came before. Between those steps the right ear is converted to
+//! the output rate and kept in step with the left the same way the mixer
+//! keeps system audio in step with the microphone. If one ear stops
+//! delivering, for example while its device reconnects, it is filled with
+//! silence so the other ear carries on undelayed.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::audio::AudioChunk;
+use crate::audio_mixer::{DriftCorrector, SourceInput};
+use crate::audio_ring::{audio_ring, empty_chunk, ring_capacity, wait_any, AudioConsumer, AudioProducer};
+
+/// Allowed per-ear gains
+pub const EAR_GAIN_RANGE: std::ops::RangeInclusive<f32> = 0.0..=2.0;
+
+/// How far one ear may run ahead before the other is treated as silent
+const MAX_EAR_LAG_MS: u32 = 200;
+
+/// How far an ear's chunk may land from where its timestamp puts it before
+/// it is padded or trimmed into place; callback jitter stays within this
+const EAR_ALIGN_TOLERANCE_MS: u32 = 10;
+
+/// Two-microphone binaural settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DualMicConfig {
+    /// Microphone ID (from `get_available_devices`) of the left ear
+    pub left_device_id: String,
+    /// Microphone ID of the right ear
+    pub right_device_id: String,
+    /// Gain applied to the left ear before the microphone volume
+    #[serde(default = "default_gain")]
+    pub left_gain: f32,
+    /// Gain applied to the right ear before the microphone volume
+    #[serde(default = "default_gain")]
+    pub right_gain: f32,
+}
+
+fn default_gain() -> f32 {
+    1.0
+}
+
+impl DualMicConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.left_device_id.is_empty() || self.right_device_id.is_empty() {
+            return Err("Both ear microphones must be set".to_string());
+        }
+        if self.left_device_id == self.right_device_id {
+            return Err("The left and right ear need two different microphones".to_string());
+        }
+        for (ear, gain) in [("Left", self.left_gain), ("Right", self.right_gain)] {
+            if !EAR_GAIN_RANGE.contains(&gain) {
+                return Err(format!(
+                    "{} ear gain must be {}-{} (got {})",
+                    ear,
+                    EAR_GAIN_RANGE.start(),
+                    EAR_GAIN_RANGE.end(),
+                    gain
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Merge the ears' mono rings into one stereo ring at `sample_rate`
+///
+/// The merged ring closes once both ear rings have closed and the audio in
+/// them has been passed on.
+pub fn merge_ears(
+    left: AudioConsumer,
+    right: AudioConsumer,
+    sample_rate: u32,
+    config: &DualMicConfig,
+) -> AudioConsumer {
+    let (producer, consumer) = audio_ring(ring_capacity(sample_rate, 2));
+    let pair = EarPair::new(sample_rate, config.left_gain, config.right_gain);
+    std::thread::spawn(move || {
+        merge_loop(left, right, producer, pair);
+        println!("Ear merging finished");
+    });
+    consumer
+}
+
+fn merge_loop(mut left: AudioConsumer, mut right: AudioConsumer, mut producer: AudioProducer, mut pair: EarPair) {
+    let mut chunk = empty_chunk();
+    let mut origin: Option<Duration> = None;
+    let mut emitted_frames: u64 = 0;
+
+    loop {
+        while left.pop(&mut chunk) {
+            origin.get_or_insert(chunk.timestamp);
+            pair.push_left(&chunk);
+        }
+        while right.pop(&mut chunk) {
+            origin.get_or_insert(chunk.timestamp);
+            pair.push_right(&chunk);
+        }
+
+        let finished = left.is_finished() && right.is_finished();
+        let samples = pair.take_stereo(finished);
+        if !samples.is_empty() {
+            let frames = (samples.len() / 2) as u64;
+            let merged = AudioChunk {
+                samples,
+                sample_rate: pair.sample_rate,
+                channels: 2,
+                timestamp: origin.unwrap_or_default()
+                    + Duration::from_secs_f64(emitted_frames as f64 / pair.sample_rate as f64),
+            };
+            emitted_frames += frames;
+            if producer.is_disconnected() {
+                break;
+            }
+            producer.push_chunk(&merged);
+        }
+
+        if finished {
+            break;
+        }
+        wait_any(&[&left, &right]);
+    }
+}
+
+/// The two ears' converted audio, waiting to be interleaved
+struct EarPair {
+    sample_rate: u32,
+    left_gain: f32,
+    right_gain: f32,
+    left: SourceInput,
+    right: SourceInput,
+    /// Keeps the right ear's clock locked to the left's (the left ear takes
+    /// the microphone's place in it, the right ear system audio's)
+    drift: DriftCorrector,
+    left_buffer: Vec<f32>,
+    right_buffer: Vec<f32>,
+    /// Whether each ear has delivered yet; its first chunk is placed exactly
+    left_started: bool,
+    right_started: bool,
+    /// Frame on the shared capture clock of the next frame to interleave
+    /// (None until either ear delivers)
+    next_frame: Option<i64>,
+    max_lag: usize,
+    tolerance: usize,
+}
+
+impl EarPair {
+    fn new(sample_rate: u32, left_gain: f32, right_gain: f32) -> Self {
+        Self {
+            sample_rate,
+            left_gain,
+            right_gain,
+            left: SourceInput::new("Left ear"),
+            right: SourceInput::new("Right ear"),
+            drift: DriftCorrector::new(sample_rate, 1),
+            left_buffer: Vec::new(),
+            right_buffer: Vec::new(),
+            left_started: false,
+            right_started: false,
+            next_frame: None,
+            max_lag: (sample_rate * MAX_EAR_LAG_MS / 1000) as usize,
+            tolerance: (sample_rate * EAR_ALIGN_TOLERANCE_MS / 1000) as usize,
+        }
+    }
+
+    fn push_left(&mut self, chunk: &AudioChunk) {
+        let samples = self.left.process(chunk, self.sample_rate, 1, self.left_gain, 1.0);
+        self.drift.record_mic(chunk.timestamp, samples.len());
+        let first = !std::mem::replace(&mut self.left_started, true);
+        let samples = self.align(samples, chunk.timestamp, self.left_buffer.len(), first, "Left");
+        self.left_buffer.extend(samples);
+    }
+
+    fn push_right(&mut self, chunk: &AudioChunk) {
+        let speed = self.drift.step();
+        let samples = self.right.process(chunk, self.sample_rate, 1, self.right_gain, speed);
+        self.drift.record_system(chunk.timestamp, samples.len());
+        let first = !std::mem::replace(&mut self.right_started, true);
+        let samples = self.align(samples, chunk.timestamp, self.right_buffer.len(), first, "Right");
+        self.right_buffer.extend(samples);
+    }
+
+    /// Pad or trim an ear's converted chunk so it lands where its timestamp
+    /// puts it, after the `buffered` frames of that ear still waiting
+    ///
+    /// Chunks are stamped when their callback runs, so the last sample is
+    /// taken to be at the timestamp. An ear's first chunk is placed exactly;
+    /// later ones only once they are more than `EAR_ALIGN_TOLERANCE_MS` off.
+    fn align(
+        &mut self,
+        mut samples: Vec<f32>,
+        timestamp: Duration,
+        buffered: usize,
+        first: bool,
+        ear: &str,
+    ) -> Vec<f32> {
+        let end = (timestamp.as_secs_f64() * self.sample_rate as f64).round() as i64;
+        let start = end - samples.len() as i64;
+        let next = *self.next_frame.get_or_insert(start);
+        let offset = start - (next + buffered as i64);
+        if offset == 0 || (!first && offset.unsigned_abs() as usize <= self.tolerance) {
+            return samples;
+        }
+        if !first {
+            eprintln!(
+                "{} ear microphone is {:.1}ms off; moving it back in line",
+                ear,
+                offset as f64 * 1000.0 / self.sample_rate as f64
+            );
+        }
+        // Its clock is measured afresh from the new position
+        self.drift = DriftCorrector::new(self.sample_rate, 1);
+        if offset > 0 {
+            let mut padded = vec![0.0; offset as usize];
+            padded.extend(samples);
+            padded
+        } else {
+            let late = (offset.unsigned_abs() as usize).min(samples.len());
+            samples.drain(..late);
+            samples
+        }
+    }
+
+    /// Interleave the frames both ears have, as left/right pairs
+    ///
+    /// An ear that falls more than `MAX_EAR_LAG_MS` behind is padded with
+    /// silence up to the other; with `flush` everything left is passed on.
+    fn take_stereo(&mut self, flush: bool) -> Vec<f32> {
+        let (left, right) = (self.left_buffer.len(), self.right_buffer.len());
+        if flush || left.abs_diff(right) > self.max_lag {
+            if !flush {
+                let ear = if left < right { "Left" } else { "Right" };
+                eprintln!("{} ear microphone stopped delivering; filling it with silence", ear);
+                // Its clock is measured afresh once it delivers again
+                self.drift = DriftCorrector::new(self.sample_rate, 1);
+            }
+            let frames = left.max(right);
+            self.left_buffer.resize(frames, 0.0);
+            self.right_buffer.resize(frames, 0.0);
+        }
+
+        let frames = self.left_buffer.len().min(self.right_buffer.len());
+        if let Some(ref mut next) = self.next_frame {
+            *next += frames as i64;
+        }
+        let mut samples = Vec::with_capacity(frames * 2);
+        for (l, r) in self.left_buffer.drain(..frames).zip(self.right_buffer.drain(..frames)) {
+            samples.push(l);
+            samples.push(r);
+        }
+        samples
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A mono chunk of `frames` frames whose first frame is frame `at` of
+    /// the shared capture clock
+    fn mono(value: f32, frames: usize, at: usize) -> AudioChunk {
+        AudioChunk {
+            samples: vec![value; frames],
+            sample_rate: 48000,
+            channels: 1,
+            timestamp: Duration::from_secs_f64((at + frames) as f64 / 48000.0),
+        }
+    }
+
+    #[test]
+    fn test_ears_are_interleaved_with_their_gains() {
+        let mut pair = EarPair::new(48000, 1.0, 0.5);
+        pair.push_left(&mono(0.5, 480, 0));
+        pair.push_right(&mono(-0.5, 240, 0));
+
+        // Only what both ears have is paired; the rest waits for the right ear
+        let stereo = pair.take_stereo(false);
+        assert_eq!(stereo.len(), 480);
+        assert_eq!(&stereo[..2], &[0.5, -0.25]);
+        assert_eq!(pair.left_buffer.len(), 240);
+
+        // A stereo chunk on one ear is folded to mono
+        let stereo_chunk = AudioChunk {
+            samples: [0.2, 0.4].repeat(240),
+            channels: 2,
+            ..mono(0.0, 240, 240)
+        };
+        pair.push_right(&stereo_chunk);
+        assert!((pair.take_stereo(false)[1] - 0.15).abs() < 1e-6);
+
+        // A silent ear is padded once the other is 200ms ahead
+        pair.push_left(&mono(0.5, 9600, 480));
+        assert!(pair.take_stereo(false).is_empty());
+        pair.push_left(&mono(0.5, 1, 10080));
+        let padded = pair.take_stereo(false);
+        assert_eq!(padded.len(), 9601 * 2);
+        assert!(padded.iter().skip(1).step_by(2).all(|&s| s == 0.0));
+
+        pair.push_left(&mono(0.5, 10, 10081));
+        assert_eq!(pair.take_stereo(true).len(), 20);
+
+        let same = DualMicConfig {
+            left_device_id: "USB Mic".to_string(),
+            right_device_id: "USB Mic".to_string(),
+            left_gain: 1.0,
+            right_gain: 1.0,
+        };
+        assert!(same.validate().is_err());
+        let loud = DualMicConfig {
+            right_device_id: "USB Mic (2)".to_string(),
+            right_gain: 3.0,
+            ..same.clone()
+        };
+        assert!(loud.validate().is_err());
+        assert!(DualMicConfig { right_gain: 1.5, ..loud }.validate().is_ok());
+    }
+
+    #[test]
+    fn test_ears_are_aligned_by_timestamp() {
+        let mut pair = EarPair::new(48000, 1.0, 1.0);
+
+        // The right ear starts 30ms after the left and is padded from the start
+        pair.push_left(&mono(0.5, 4800, 0));
+        pair.push_right(&mono(-0.5, 3360, 1440));
+        let stereo = pair.take_stereo(false);
+        assert_eq!(stereo.len(), 4800 * 2);
+        assert!(stereo[..1440 * 2].iter().skip(1).step_by(2).all(|&s| s == 0.0));
+        assert_eq!(stereo[1440 * 2 + 1], -0.5);
+
+        // Audio after a 50ms dropout lands at its own time
+        pair.push_left(&mono(0.5, 4800, 4800));
+        pair.push_right(&mono(-0.5, 2400, 7200));
+        let stereo = pair.take_stereo(false);
+        assert_eq!(stereo.len(), 4800 * 2);
+        assert_eq!(stereo[2399 * 2 + 1], 0.0);
+        assert_eq!(stereo[2400 * 2 + 1], -0.5);
+
+        // Audio stamped before what was already passed on is dropped
+        pair.push_left(&mono(0.5, 480, 9600));
+        pair.push_right(&mono(-0.5, 1440, 8640));
+        assert_eq!(pair.take_stereo(false).len(), 480 * 2);
+        assert!(pair.right_buffer.is_empty());
+
+        // Jitter within the tolerance is left alone
+        pair.push_left(&mono(0.5, 480, 10080));
+        pair.push_right(&mono(-0.5, 480, 10080 + 48));
+        assert_eq!(pair.take_stereo(false).len(), 480 * 2);
+        assert!(pair.left_buffer.is_empty() && pair.right_buffer.is_empty());
+    }
+}
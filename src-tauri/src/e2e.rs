@@ -0,0 +1,310 @@
+//! End-to-End Pipeline Tests
+//!
+//! Runs the audio mixer and encoder with synthetic sources for a few seconds,
+//! then probes the written file with FFmpeg to check stream layout, duration,
+//! frame rate and audio/video alignment. Enabled with the `e2e` feature:
+//!
+//! ```text
+//! cargo test --features e2e e2e::
+//! ```
+
+use std::f32::consts::TAU;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::{bounded, Sender};
+use parking_lot::Mutex;
+
+use crate::audio::AudioChunk;
+use crate::audio_mixer::{AudioMixer, AudioMixerConfig};
+use crate::audio_ring::{audio_ring, ring_capacity, AudioProducer};
+use crate::compositor::{CompositeFrame, FrameFormat};
+use crate::encoder::{Encoder, EncoderConfig};
+use crate::frame_pool::FrameBuffer;
+use crate::recording::VideoQuality;
+
+/// Mock audio chunk length in milliseconds
+const AUDIO_CHUNK_MS: u32 = 10;
+
+/// Parameters of a synthetic recording
+#[derive(Debug, Clone)]
+pub struct PipelineRun {
+    pub duration_ms: u64,
+    pub width: u32,
+    pub height: u32,
+    pub frame_rate: u32,
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub quality: VideoQuality,
+}
+
+impl Default for PipelineRun {
+    fn default() -> Self {
+        Self {
+            duration_ms: 3000,
+            width: 640,
+            height: 360,
+            frame_rate: 30,
+            sample_rate: 48000,
+            channels: 2,
+            quality: VideoQuality::Low,
+        }
+    }
+}
+
+/// What FFmpeg reports about a finished recording
+#[derive(Debug, Clone)]
+pub struct OutputProbe {
+    pub video_streams: usize,
+    pub audio_streams: usize,
+    /// Container duration in seconds
+    pub duration_secs: f64,
+    /// Average video frame rate
+    pub frame_rate: f64,
+    pub video_duration_secs: f64,
+    pub audio_duration_secs: f64,
+    /// Audio start minus video start, in seconds
+    pub av_offset_secs: f64,
+}
+
+impl OutputProbe {
+    /// Compare against the run, allowing `tolerance_secs` of timing error
+    pub fn verify(&self, run: &PipelineRun, tolerance_secs: f64) -> Result<(), String> {
+        let expected_secs = run.duration_ms as f64 / 1000.0;
+        let mut failures = Vec::new();
+
+        if self.video_streams != 1 || self.audio_streams != 1 {
+            failures.push(format!(
+                "expected 1 video and 1 audio stream, found {} and {}",
+                self.video_streams, self.audio_streams
+            ));
+        }
+        for (name, secs) in [
+            ("container", self.duration_secs),
+            ("video", self.video_duration_secs),
+            ("audio", self.audio_duration_secs),
+        ] {
+            if (secs - expected_secs).abs() > tolerance_secs {
+                failures.push(format!(
+                    "{} duration {:.3}s, expected {:.3}s",
+                    name, secs, expected_secs
+                ));
+            }
+        }
+        if (self.frame_rate - run.frame_rate as f64).abs() > 0.5 {
+            failures.push(format!(
+                "frame rate {:.2}, expected {}",
+                self.frame_rate, run.frame_rate
+            ));
+        }
+        if self.av_offset_secs.abs() > tolerance_secs {
+            failures.push(format!("A/V offset {:.3}s", self.av_offset_secs));
+        }
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(failures.join("; "))
+        }
+    }
+}
+
+/// Record `run` to `output_path` using mock sources and the real mixer and encoder
+pub fn run_pipeline(run: &PipelineRun, output_path: &Path) -> Result<(), String> {
+    let (video_tx, video_rx) = bounded(30);
+    let (mic_tx, mic_rx) = audio_ring(ring_capacity(run.sample_rate, run.channels));
+    let (error_tx, error_rx) = bounded(1);
+
+    let mut mixer = AudioMixer::new(AudioMixerConfig {
+        sample_rate: run.sample_rate,
+        channels: run.channels,
+        ..AudioMixerConfig::default()
+    });
+    mixer.set_mic_receiver(mic_rx);
+    let mixed_rx = mixer
+        .take_output_receiver()
+        .ok_or("Mixer output receiver not available")?;
+
+    let mut encoder = Encoder::new(EncoderConfig {
+        output_path: output_path.to_string_lossy().to_string(),
+        width: run.width,
+        height: run.height,
+        frame_rate: run.frame_rate,
+        quality: run.quality,
+        audio_sample_rate: run.sample_rate,
+        audio_channels: run.channels,
+        // Keep results independent of the machine's GPU
+        hardware_acceleration: false,
+        ..EncoderConfig::default()
+    });
+    encoder.set_video_receiver(video_rx);
+    encoder.set_audio_receiver(mixed_rx);
+    encoder.set_error_sender(error_tx);
+    encoder.stop_at(run.duration_ms);
+
+    let running = Arc::new(Mutex::new(true));
+    mixer.start()?;
+    encoder.start()?;
+    spawn_mock_video(running.clone(), video_tx, run.clone());
+    spawn_mock_audio(running.clone(), mic_tx, run.sample_rate, run.channels);
+
+    // The scheduled stop finalizes the file; allow time for encoder startup and flush
+    let deadline = Instant::now() + Duration::from_millis(run.duration_ms + 10_000);
+    while encoder.is_running() && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(50));
+    }
+
+    *running.lock() = false;
+    mixer.stop();
+
+    if let Ok(e) = error_rx.try_recv() {
+        return Err(format!("Encoder failed: {}", e));
+    }
+    if encoder.is_running() {
+        let _ = encoder.stop();
+        return Err("Encoder did not finish after the scheduled stop".to_string());
+    }
+    Ok(())
+}
+
+/// Read stream layout and timing from a recorded file
+pub fn probe(path: &Path) -> Result<OutputProbe, String> {
+    use ffmpeg_next::media::Type;
+
+    ffmpeg_next::init().map_err(|e| format!("FFmpeg init failed: {}", e))?;
+    let input = ffmpeg_next::format::input(&path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+
+    let mut probe = OutputProbe {
+        video_streams: 0,
+        audio_streams: 0,
+        duration_secs: input.duration() as f64 / ffmpeg_next::ffi::AV_TIME_BASE as f64,
+        frame_rate: 0.0,
+        video_duration_secs: 0.0,
+        audio_duration_secs: 0.0,
+        av_offset_secs: 0.0,
+    };
+    let mut video_start = 0.0;
+    let mut audio_start = 0.0;
+
+    for stream in input.streams() {
+        let time_base = f64::from(stream.time_base());
+        let start = stream.start_time().max(0) as f64 * time_base;
+        let duration = stream.duration().max(0) as f64 * time_base;
+        match stream.parameters().medium() {
+            Type::Video => {
+                probe.video_streams += 1;
+                probe.frame_rate = f64::from(stream.avg_frame_rate());
+                probe.video_duration_secs = duration;
+                video_start = start;
+            }
+            Type::Audio => {
+                probe.audio_streams += 1;
+                probe.audio_duration_secs = duration;
+                audio_start = start;
+            }
+            _ => {}
+        }
+    }
+    probe.av_offset_secs = audio_start - video_start;
+
+    Ok(probe)
+}
+
+/// Send BGRA frames with a moving bar at the configured frame rate
+fn spawn_mock_video(running: Arc<Mutex<bool>>, sender: Sender<CompositeFrame>, run: PipelineRun) {
+    std::thread::spawn(move || {
+        let interval = Duration::from_secs_f64(1.0 / run.frame_rate as f64);
+        let start = Instant::now();
+        let mut index: u64 = 0;
+
+        while *running.lock() {
+            let bar_x = (index * 8 % run.width as u64) as u32;
+            let mut data = vec![0u8; (run.width * run.height * 4) as usize];
+            for (i, pixel) in data.chunks_exact_mut(4).enumerate() {
+                let x = i as u32 % run.width;
+                let lit = x >= bar_x && x < bar_x + 32;
+                pixel.copy_from_slice(if lit { &[255, 255, 255, 255] } else { &[64, 32, 16, 255] });
+            }
+
+            let frame = CompositeFrame {
+                data: FrameBuffer::shared(data),
+                width: run.width,
+                height: run.height,
+                timestamp: start.elapsed(),
+                format: FrameFormat::Bgra,
+            };
+            if sender.send(frame).is_err() {
+                break;
+            }
+
+            index += 1;
+            let next = interval * index as u32;
+            if let Some(wait) = next.checked_sub(start.elapsed()) {
+                std::thread::sleep(wait);
+            }
+        }
+    });
+}
+
+/// Send a 440Hz sine tone in 10ms chunks, paced in real time
+fn spawn_mock_audio(
+    running: Arc<Mutex<bool>>,
+    mut producer: AudioProducer,
+    sample_rate: u32,
+    channels: u16,
+) {
+    std::thread::spawn(move || {
+        let frames_per_chunk = (sample_rate * AUDIO_CHUNK_MS / 1000) as usize;
+        let interval = Duration::from_millis(AUDIO_CHUNK_MS as u64);
+        let start = Instant::now();
+        let mut position: u64 = 0;
+        let mut chunks: u32 = 0;
+
+        while *running.lock() {
+            let mut samples = Vec::with_capacity(frames_per_chunk * channels as usize);
+            for i in 0..frames_per_chunk as u64 {
+                let t = (position + i) as f32 / sample_rate as f32;
+                let value = (TAU * 440.0 * t).sin() * 0.25;
+                for _ in 0..channels {
+                    samples.push(value);
+                }
+            }
+            position += frames_per_chunk as u64;
+
+            let chunk = AudioChunk {
+                samples,
+                sample_rate,
+                channels,
+                timestamp: start.elapsed(),
+            };
+            if producer.is_disconnected() {
+                break;
+            }
+            producer.push_chunk(&chunk);
+
+            chunks += 1;
+            if let Some(wait) = (interval * chunks).checked_sub(start.elapsed()) {
+                std::thread::sleep(wait);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pipeline_output_matches_config() {
+        let run = PipelineRun::default();
+        let output = std::env::temp_dir().join(format!("asmr_e2e_{}.mp4", std::process::id()));
+
+        run_pipeline(&run, &output).expect("pipeline run failed");
+        let probe = probe(&output).expect("probe failed");
+        let _ = std::fs::remove_file(&output);
+
+        probe.verify(&run, 0.1).unwrap_or_else(|e| panic!("{}: {:?}", e, probe));
+    }
+}
@@ -0,0 +1,335 @@
+//! Encode Benchmark
+//!
+//! Measures how fast this machine can encode video, so the UI can recommend
+//! settings it will sustain. Synthetic frames - a scrolling gradient with
+//! noise, which H.264 finds about as hard as busy screen content - are pushed
+//! through the real encoder as fast as it takes them: first the hardware
+//! H.264 encoder, if one opens, then libx264 at each of `X264_PRESETS`. Each
+//! candidate runs for the requested time and reports the frame rate it
+//! reached.
+//!
+//! The files are written to a temporary directory, removed afterwards.
+//! Capture and compositing need CPU time too, so a candidate only counts as
+//! keeping up when it beats `TARGET_FRAME_RATE` by `HEADROOM`. Run the
+//! benchmark while nothing is recording; the two would slow each other down.
+
+use serde::{Deserialize, Serialize};
+use tauri::command;
+
+use crate::recording::{OutputResolution, RecordingError};
+
+/// Allowed run time per candidate, in seconds
+pub const BENCHMARK_DURATION_RANGE: std::ops::RangeInclusive<u32> = 1..=10;
+
+/// x264 presets measured, fastest first
+#[cfg(feature = "ffmpeg")]
+const X264_PRESETS: &[&str] = &["ultrafast", "superfast", "veryfast", "faster", "fast", "medium"];
+
+/// Frame rate the results are judged against
+#[cfg_attr(not(any(feature = "ffmpeg", test)), allow(dead_code))]
+const TARGET_FRAME_RATE: u32 = 30;
+
+/// How much faster than the target a candidate has to encode to leave room
+/// for capture and compositing
+#[cfg_attr(not(any(feature = "ffmpeg", test)), allow(dead_code))]
+const HEADROOM: f64 = 1.25;
+
+/// Longest a candidate may take to encode its first frame
+#[cfg(feature = "ffmpeg")]
+const STARTUP_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Distinct synthetic frames, cycled through
+#[cfg_attr(not(any(feature = "ffmpeg", test)), allow(dead_code))]
+const SYNTHETIC_FRAMES: usize = 4;
+
+/// How one encoder setting did
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BenchmarkResult {
+    /// Video encoder, e.g. "h264_nvenc" or "libx264"
+    pub codec: String,
+    /// x264 preset (None for hardware encoders)
+    pub preset: Option<String>,
+    /// Frames encoded per second
+    pub fps: f64,
+    /// Fast enough to record at the target frame rate
+    pub sustainable: bool,
+    /// Why the candidate couldn't be measured
+    pub error: Option<String>,
+}
+
+/// Benchmark results for one resolution
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EncodeBenchmark {
+    pub width: u32,
+    pub height: u32,
+    /// Frame rate `sustainable` is judged against
+    pub target_frame_rate: u32,
+    pub results: Vec<BenchmarkResult>,
+    /// Index into `results` of the setting to record with: the hardware
+    /// encoder if it keeps up, otherwise the slowest (best quality) x264
+    /// preset that does
+    pub recommended: Option<usize>,
+}
+
+/// Whether `fps` keeps up with the target frame rate, with headroom
+#[cfg_attr(not(any(feature = "ffmpeg", test)), allow(dead_code))]
+fn sustainable(fps: f64) -> bool {
+    fps >= TARGET_FRAME_RATE as f64 * HEADROOM
+}
+
+/// The result to recommend; results are in the order they were measured
+#[cfg_attr(not(any(feature = "ffmpeg", test)), allow(dead_code))]
+fn recommend(results: &[BenchmarkResult]) -> Option<usize> {
+    let usable = |result: &BenchmarkResult| result.error.is_none() && result.sustainable;
+    results
+        .iter()
+        .position(|result| result.preset.is_none() && usable(result))
+        .or_else(|| results.iter().rposition(|result| result.preset.is_some() && usable(result)))
+}
+
+/// BGRA frames of a gradient scrolled a little further in each, with noise
+#[cfg_attr(not(any(feature = "ffmpeg", test)), allow(dead_code))]
+fn synthetic_frames(width: u32, height: u32) -> Vec<Vec<u8>> {
+    let mut seed: u32 = 0x2545_f491;
+    (0..SYNTHETIC_FRAMES)
+        .map(|index| {
+            let shift = index as u32 * 24;
+            let mut data = vec![0u8; width as usize * height as usize * 4];
+            for (i, pixel) in data.chunks_exact_mut(4).enumerate() {
+                let x = (i as u32 % width + shift) % width;
+                let y = i as u32 / width;
+                // xorshift noise, so no two frames or rows are alike
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                let noise = (seed & 0x1f) as u8;
+                pixel[0] = ((x * 255 / width) as u8).wrapping_add(noise);
+                pixel[1] = ((y * 255 / height) as u8).wrapping_add(noise);
+                pixel[2] = (((x + y) * 127 / (width + height)) as u8).wrapping_add(noise);
+                pixel[3] = 255;
+            }
+            data
+        })
+        .collect()
+}
+
+/// Run every candidate at `width` x `height` for `duration_secs` each
+#[cfg(feature = "ffmpeg")]
+pub fn run_benchmark(width: u32, height: u32, duration_secs: u32) -> Result<EncodeBenchmark, String> {
+    use crate::frame_pool::FrameBuffer;
+    use std::sync::Arc;
+
+    let dir = std::env::temp_dir().join(format!("asmr_benchmark_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create benchmark directory: {}", e))?;
+    let frames: Vec<Arc<FrameBuffer>> = synthetic_frames(width, height)
+        .into_iter()
+        .map(FrameBuffer::shared)
+        .collect();
+    let duration = std::time::Duration::from_secs(duration_secs as u64);
+
+    let mut results = Vec::new();
+    let hardware = measure(&dir, &frames, width, height, duration, None);
+    // With no hardware encoder the encoder falls back to libx264, which the
+    // presets below cover
+    if hardware.codec != "libx264" {
+        results.push(hardware);
+    }
+    for &preset in X264_PRESETS {
+        results.push(measure(&dir, &frames, width, height, duration, Some(preset)));
+    }
+
+    if let Err(e) = std::fs::remove_dir_all(&dir) {
+        eprintln!("Failed to remove benchmark directory: {}", e);
+    }
+    let recommended = recommend(&results);
+    Ok(EncodeBenchmark {
+        width,
+        height,
+        target_frame_rate: TARGET_FRAME_RATE,
+        results,
+        recommended,
+    })
+}
+
+#[cfg(not(feature = "ffmpeg"))]
+pub fn run_benchmark(_width: u32, _height: u32, _duration_secs: u32) -> Result<EncodeBenchmark, String> {
+    Err("Encode benchmark requires FFmpeg support".to_string())
+}
+
+/// Encode `frames` for `duration` with the hardware encoder (no preset) or
+/// libx264 at `preset`
+#[cfg(feature = "ffmpeg")]
+fn measure(
+    dir: &std::path::Path,
+    frames: &[std::sync::Arc<crate::frame_pool::FrameBuffer>],
+    width: u32,
+    height: u32,
+    duration: std::time::Duration,
+    preset: Option<&str>,
+) -> BenchmarkResult {
+    use crate::compositor::{CompositeFrame, FrameFormat};
+    use crate::encoder::{Encoder, EncoderConfig};
+    use crate::recording::AdvancedQuality;
+    use crossbeam_channel::{bounded, SendTimeoutError};
+    use std::time::{Duration, Instant};
+
+    let mut result = BenchmarkResult {
+        codec: if preset.is_some() { "libx264" } else { "hardware" }.to_string(),
+        preset: preset.map(str::to_string),
+        fps: 0.0,
+        sustainable: false,
+        error: None,
+    };
+    let (frame_tx, frame_rx) = bounded(2);
+    let (error_tx, error_rx) = bounded(1);
+    let mut encoder = Encoder::new(EncoderConfig {
+        output_path: dir
+            .join(format!("{}.mp4", preset.unwrap_or("hardware")))
+            .to_string_lossy()
+            .to_string(),
+        width,
+        height,
+        frame_rate: TARGET_FRAME_RATE,
+        hardware_acceleration: preset.is_none(),
+        advanced_quality: Some(AdvancedQuality {
+            preset: preset.map(str::to_string),
+            ..AdvancedQuality::default()
+        }),
+        ..EncoderConfig::default()
+    });
+    encoder.set_video_receiver(frame_rx);
+    encoder.set_error_sender(error_tx);
+    if let Err(e) = encoder.start() {
+        result.error = Some(e);
+        return result;
+    }
+
+    // Frames are stamped one frame interval apart, so none are dropped as
+    // jitter however fast they arrive
+    let interval = Duration::from_secs_f64(1.0 / TARGET_FRAME_RATE as f64);
+    let opened = Instant::now();
+    let mut started = None;
+    let mut sent: u32 = 0;
+    loop {
+        if let Ok(e) = error_rx.try_recv() {
+            result.error = Some(e);
+            break;
+        }
+        // Timed from the first frame taken, leaving out encoder startup
+        if encoder.frames_encoded() > 0 && started.is_none() {
+            started = Some((Instant::now(), encoder.frames_encoded()));
+        }
+        if started.is_some_and(|(at, _)| at.elapsed() >= duration) {
+            break;
+        }
+        if started.is_none() && opened.elapsed() >= STARTUP_TIMEOUT {
+            result.error = Some("Encoder did not take any frames".to_string());
+            break;
+        }
+        let frame = CompositeFrame {
+            data: frames[sent as usize % frames.len()].clone(),
+            width,
+            height,
+            timestamp: interval * sent,
+            format: FrameFormat::Bgra,
+        };
+        match frame_tx.send_timeout(frame, Duration::from_millis(100)) {
+            Ok(()) => sent += 1,
+            Err(SendTimeoutError::Timeout(_)) => {}
+            Err(SendTimeoutError::Disconnected(_)) => break,
+        }
+    }
+    if let Some((at, first)) = started {
+        result.fps = (encoder.frames_encoded() - first) as f64 / at.elapsed().as_secs_f64();
+    }
+    if let Some(name) = encoder.video_codec_name() {
+        result.codec = name;
+    }
+    result.sustainable = result.error.is_none() && sustainable(result.fps);
+
+    // Closing the channel lets the encoder drain and finalize the file
+    drop(frame_tx);
+    if let Err(e) = encoder.finish() {
+        eprintln!("Encode benchmark: {}", e);
+    }
+    // Its thumbnail is written after that; wait for it before the directory
+    // is removed
+    let deadline = Instant::now() + Duration::from_secs(30);
+    while !encoder.is_finished() && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    println!(
+        "Encode benchmark: {} {} at {}x{}: {:.1} fps",
+        result.codec,
+        result.preset.as_deref().unwrap_or(""),
+        width,
+        height,
+        result.fps
+    );
+    result
+}
+
+/// Tauri command: Measure the frame rate each encoder setting reaches at
+/// `resolution`, running each for `duration_secs`
+///
+/// Takes about seven times `duration_secs` in all.
+#[command]
+pub async fn run_encode_benchmark(
+    resolution: OutputResolution,
+    duration_secs: u32,
+) -> Result<EncodeBenchmark, RecordingError> {
+    if !BENCHMARK_DURATION_RANGE.contains(&duration_secs) {
+        return Err(RecordingError::InvalidConfig(format!(
+            "Benchmark duration must be {}-{} seconds (got {})",
+            BENCHMARK_DURATION_RANGE.start(),
+            BENCHMARK_DURATION_RANGE.end(),
+            duration_secs
+        )));
+    }
+    let (width, height) = resolution.dimensions();
+    tauri::async_runtime::spawn_blocking(move || run_benchmark(width, height, duration_secs))
+        .await
+        .map_err(|e| format!("Encode benchmark failed: {}", e))?
+        .map_err(RecordingError::Failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(preset: Option<&str>, fps: f64) -> BenchmarkResult {
+        BenchmarkResult {
+            codec: if preset.is_some() { "libx264" } else { "h264_nvenc" }.to_string(),
+            preset: preset.map(str::to_string),
+            fps,
+            sustainable: sustainable(fps),
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_recommends_hardware_then_slowest_sustainable_preset() {
+        // 30fps needs 37.5 with headroom
+        assert!(!sustainable(36.0));
+        assert!(sustainable(40.0));
+
+        let mut results = vec![
+            result(None, 240.0),
+            result(Some("ultrafast"), 90.0),
+            result(Some("superfast"), 60.0),
+            result(Some("veryfast"), 41.0),
+            result(Some("faster"), 25.0),
+        ];
+        assert_eq!(recommend(&results), Some(0));
+        results[0].error = Some("Device lost".to_string());
+        assert_eq!(recommend(&results), Some(3));
+        assert_eq!(recommend(&results[4..]), None);
+
+        let frames = synthetic_frames(64, 36);
+        assert_eq!(frames.len(), SYNTHETIC_FRAMES);
+        assert_eq!(frames[0].len(), 64 * 36 * 4);
+        assert_ne!(frames[0], frames[1]);
+    }
+}
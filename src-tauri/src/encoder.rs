@@ -1,13 +1,32 @@
+use std::path::PathBuf;
 use std::sync::Arc;
-use crossbeam_channel::{Receiver, Sender};
+use std::time::Duration;
+use crossbeam_channel::{bounded, Receiver, RecvTimeoutError, Sender};
 use parking_lot::Mutex;
 
 use crate::compositor::CompositeFrame;
 use crate::audio_mixer::MixedAudioChunk;
-use crate::recording::VideoQuality;
+use crate::channel_wait::wait_for;
+use crate::recording::{AdvancedQuality, AudioBitDepth, AudioCodec, FileMetadata, OutputFormat, TrackMetadata, VideoQuality};
+use crate::mastering::MasteringConfig;
+use crate::markers::Marker;
+use crate::output_check::{FinishedSink, OutputVerification};
+use crate::silence::SilenceConfig;
+use crate::streaming::{StreamFormat, StreamInputs};
+use crate::quality_governor::QualityControls;
+use crate::thread_priority::{self, ThreadRole};
 
 #[cfg(feature = "ffmpeg")]
 use ffmpeg_next::channel_layout::ChannelLayout;
+#[cfg(feature = "ffmpeg")]
+use crate::compositor::FrameFormat;
+
+/// Longest `Encoder::finish` waits for the inputs to close and drain before
+/// stopping encoding where it is
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Longest `Encoder::finish` then waits for the file to be finalized
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(30);
 
 /// Encoder configuration
 pub struct EncoderConfig {
@@ -25,6 +44,39 @@ pub struct EncoderConfig {
     pub audio_sample_rate: u32,
     /// Audio channels
     pub audio_channels: u16,
+    /// Tag the audio track as binaural
+    pub binaural: bool,
+    /// Title and language written on the audio track
+    pub audio_track: TrackMetadata,
+    /// Container tags
+    pub metadata: FileMetadata,
+    /// Try hardware H.264 encoders before libx264 (MP4 and MKV)
+    pub hardware_acceleration: bool,
+    /// Container and codecs
+    pub output_format: OutputFormat,
+    /// Audio codec (None: the format's default)
+    pub audio_codec: Option<AudioCodec>,
+    /// AAC/Opus bitrate in kbps (None: the quality preset's)
+    pub audio_bitrate_kbps: Option<u32>,
+    /// PCM sample format (None: 32-bit float)
+    pub audio_bit_depth: Option<AudioBitDepth>,
+    /// Explicit CRF/bitrate/preset overriding `quality` where set
+    pub advanced_quality: Option<AdvancedQuality>,
+    /// Directory to continue in if writes to `output_path` start failing
+    pub failover_dir: Option<PathBuf>,
+    /// Write fragmented MP4 (append sessions; readable if recording is cut short)
+    pub fragmented: bool,
+    /// Earlier sitting to continue: its audio and video are copied in first and
+    /// the finished file replaces it
+    pub append_to: Option<PathBuf>,
+    /// Bring the finished file to this integrated loudness (LUFS)
+    pub normalize_lufs: Option<f32>,
+    /// Silence markers or trimming for the finished file
+    pub silence: SilenceConfig,
+    /// High-pass, de-esser and normalization for the finished file
+    pub mastering: MasteringConfig,
+    /// Adaptive quality adjustments made while recording (None: fixed quality)
+    pub quality_controls: Option<Arc<QualityControls>>,
 }
 
 impl Default for EncoderConfig {
@@ -37,10 +89,104 @@ impl Default for EncoderConfig {
             quality: VideoQuality::Medium,
             audio_sample_rate: 48000,
             audio_channels: 2,
+            binaural: false,
+            audio_track: TrackMetadata::default(),
+            metadata: FileMetadata::default(),
+            hardware_acceleration: true,
+            output_format: OutputFormat::Mp4,
+            audio_codec: None,
+            audio_bitrate_kbps: None,
+            audio_bit_depth: None,
+            advanced_quality: None,
+            failover_dir: None,
+            fragmented: false,
+            append_to: None,
+            normalize_lufs: None,
+            silence: SilenceConfig::default(),
+            mastering: MasteringConfig::default(),
+            quality_controls: None,
         }
     }
 }
 
+/// The encoder switched to the failover drive
+#[derive(Debug, Clone)]
+pub struct OutputFailover {
+    /// Output time of the switch
+    pub at_ms: u64,
+    /// File that could no longer be written
+    pub failed_path: String,
+    /// File the recording continues in
+    pub path: String,
+    /// Write error on the failed file
+    pub reason: String,
+}
+
+#[cfg(feature = "ffmpeg")]
+impl EncoderConfig {
+    fn advanced(&self) -> AdvancedQuality {
+        self.advanced_quality.clone().unwrap_or_default()
+    }
+
+    /// H.264 CRF
+    fn crf(&self) -> u32 {
+        self.advanced().crf.unwrap_or_else(|| self.quality.crf())
+    }
+
+    /// VP9 CRF
+    fn vp9_crf(&self) -> u32 {
+        self.advanced().crf.unwrap_or_else(|| self.quality.vp9_crf())
+    }
+
+    /// Target video bitrate in kbps
+    fn video_bitrate_kbps(&self) -> u32 {
+        self.advanced().bitrate_kbps.unwrap_or_else(|| self.quality.video_bitrate())
+    }
+
+    /// AAC/Opus bitrate in kbps
+    fn audio_bitrate_kbps(&self) -> u32 {
+        self.audio_bitrate_kbps.unwrap_or_else(|| self.quality.audio_bitrate())
+    }
+
+    /// libx264 preset ("ultrafast" keeps up with real-time capture)
+    fn x264_preset(&self) -> String {
+        self.advanced().preset.unwrap_or_else(|| "ultrafast".to_string())
+    }
+
+    /// libx264 tuning ("zerolatency" encodes slices of each frame in parallel
+    /// without holding frames back)
+    fn x264_tune(&self) -> String {
+        self.advanced().tune.unwrap_or_else(|| "zerolatency".to_string())
+    }
+
+    /// Software encoder threads, sized to this machine unless configured
+    fn encoder_threads(&self) -> u32 {
+        self.advanced().threads.unwrap_or_else(|| {
+            let cores = std::thread::available_parallelism().map_or(1, |cores| cores.get());
+            default_encoder_threads(cores, self.height)
+        })
+    }
+
+    /// Add the peak bitrate cap, if any, to encoder options
+    fn set_rate_limit(&self, options: &mut ffmpeg_next::Dictionary) {
+        if let Some(max) = self.advanced().max_bitrate_kbps {
+            options.set("maxrate", &format!("{}k", max));
+            // One second of buffer at the peak rate
+            options.set("bufsize", &format!("{}k", max));
+        }
+    }
+}
+
+/// Encoder threads for `cores` CPU cores and frames `height` pixels tall
+///
+/// One core is left for capture and compositing. Below 1440p more than eight
+/// slice threads cost quality for little speed; 1440p and 4K get up to 16.
+#[cfg_attr(not(feature = "ffmpeg"), allow(dead_code))]
+fn default_encoder_threads(cores: usize, height: u32) -> u32 {
+    let cap = if height >= 1440 { 16 } else { 8 };
+    (cores.saturating_sub(1) as u32).clamp(1, cap)
+}
+
 /// Video/Audio encoder
 /// 
 /// When compiled with the `ffmpeg` feature, uses FFmpeg for encoding.
@@ -51,19 +197,53 @@ pub struct Encoder {
     video_receiver: Option<Receiver<CompositeFrame>>,
     audio_receiver: Option<Receiver<MixedAudioChunk>>,
     frames_encoded: Arc<Mutex<u64>>,
+    /// Gaps in the audio filled with silence
+    audio_underruns: Arc<Mutex<u64>>,
     error_sender: Option<Sender<String>>,
+    failover_sender: Option<Sender<OutputFailover>>,
+    /// Output time (ms) at which encoding ends on its own
+    stop_at_ms: Arc<Mutex<Option<u64>>>,
+    /// Markers written as chapters when the file is finalized
+    markers: Arc<Mutex<Vec<Marker>>>,
+    /// Live streams that received frames and audio are also handed to
+    stream: Arc<Mutex<Vec<StreamInputs>>>,
+    /// Name of the video encoder opened, e.g. "libx264"
+    video_codec_name: Arc<Mutex<Option<String>>>,
+    /// The finalized file read back and checked against what was written,
+    /// before the finishing passes
+    verification: Arc<Mutex<Option<OutputVerification>>>,
+    /// Told how the file checks out once the finishing passes are done
+    finished_sink: Mutex<Option<FinishedSink>>,
+    /// The encoding thread has finalized the file and exited
+    finished: Arc<Mutex<bool>>,
+    /// Taken by the encoding thread, which acknowledges on it once the file
+    /// is flushed and its trailer written
+    flushed_sender: Mutex<Option<Sender<()>>>,
+    flushed: Receiver<()>,
 }
 
 impl Encoder {
     /// Create a new encoder
     pub fn new(config: EncoderConfig) -> Self {
+        let (flushed_sender, flushed) = bounded(1);
         Self {
             config,
             running: Arc::new(Mutex::new(false)),
             video_receiver: None,
             audio_receiver: None,
             frames_encoded: Arc::new(Mutex::new(0)),
+            audio_underruns: Arc::new(Mutex::new(0)),
             error_sender: None,
+            failover_sender: None,
+            stop_at_ms: Arc::new(Mutex::new(None)),
+            markers: Arc::new(Mutex::new(Vec::new())),
+            stream: Arc::new(Mutex::new(Vec::new())),
+            video_codec_name: Arc::new(Mutex::new(None)),
+            verification: Arc::new(Mutex::new(None)),
+            finished_sink: Mutex::new(None),
+            finished: Arc::new(Mutex::new(false)),
+            flushed_sender: Mutex::new(Some(flushed_sender)),
+            flushed,
         }
     }
     
@@ -81,6 +261,83 @@ impl Encoder {
     pub fn set_error_sender(&mut self, sender: Sender<String>) {
         self.error_sender = Some(sender);
     }
+
+    /// Set the sender notified when output moves to the failover drive
+    pub fn set_failover_sender(&mut self, sender: Sender<OutputFailover>) {
+        self.failover_sender = Some(sender);
+    }
+
+    /// Set the sink told how the final file checks out after the finishing
+    /// passes
+    pub fn set_finished_sink(&mut self, sink: FinishedSink) {
+        *self.finished_sink.lock() = Some(sink);
+    }
+    
+    /// End the file exactly at `timestamp_ms` of output time
+    ///
+    /// Frames and samples past that point are dropped, then the encoder
+    /// flushes and finalizes the file by itself.
+    pub fn stop_at(&self, timestamp_ms: u64) {
+        *self.stop_at_ms.lock() = Some(timestamp_ms);
+    }
+    
+    /// Mark a point of the recording, to become a chapter of the file
+    pub fn add_marker(&self, marker: Marker) {
+        self.markers.lock().push(marker);
+    }
+    
+    /// Also hand frames and audio to these live streams (empty to stop)
+    pub fn set_stream_inputs(&self, inputs: Vec<StreamInputs>) {
+        *self.stream.lock() = inputs;
+    }
+    
+    /// Frame size and rate, and audio format, of the output
+    pub fn stream_format(&self) -> StreamFormat {
+        StreamFormat {
+            width: self.config.width,
+            height: self.config.height,
+            frame_rate: self.config.frame_rate,
+            sample_rate: self.config.audio_sample_rate,
+            channels: self.config.audio_channels,
+        }
+    }
+    
+    /// Frames encoded so far
+    pub fn frames_encoded(&self) -> u64 {
+        *self.frames_encoded.lock()
+    }
+    
+    /// Times audio arrived late and the gap was filled with silence
+    pub fn audio_underruns(&self) -> u64 {
+        *self.audio_underruns.lock()
+    }
+    
+    /// Whether the encoding thread is still running
+    pub fn is_running(&self) -> bool {
+        *self.running.lock()
+    }
+    
+    /// Whether the encoding thread has exited, with the file finalized
+    ///
+    /// `is_running` turns false as soon as encoding stops; this waits for
+    /// the flush, trailer and finishing passes too.
+    #[cfg_attr(not(feature = "ffmpeg"), allow(dead_code))]
+    pub fn is_finished(&self) -> bool {
+        *self.finished.lock()
+    }
+    
+    /// Name of the video encoder in use, e.g. "h264_nvenc" or "libx264",
+    /// once it has been opened
+    #[cfg_attr(not(feature = "ffmpeg"), allow(dead_code))]
+    pub fn video_codec_name(&self) -> Option<String> {
+        self.video_codec_name.lock().clone()
+    }
+    
+    /// How the finalized file checked out when read back (None until
+    /// `finish` returns, and without FFmpeg support)
+    pub fn verification(&self) -> Option<OutputVerification> {
+        self.verification.lock().clone()
+    }
     
     /// Start encoding
     pub fn start(&self) -> Result<(), String> {
@@ -95,10 +352,28 @@ impl Encoder {
         #[cfg(feature = "ffmpeg")]
         let running_control = self.running.clone();
         let frames_encoded = self.frames_encoded.clone();
+        #[cfg(feature = "ffmpeg")]
+        let audio_underruns = self.audio_underruns.clone();
         let video_receiver = self.video_receiver.clone();
         let audio_receiver = self.audio_receiver.clone();
+        let stop_at_ms = self.stop_at_ms.clone();
+        #[cfg(feature = "ffmpeg")]
+        let markers = self.markers.clone();
         #[cfg(feature = "ffmpeg")]
         let error_sender = self.error_sender.clone();
+        #[cfg(feature = "ffmpeg")]
+        let failover_sender = self.failover_sender.clone();
+        #[cfg(feature = "ffmpeg")]
+        let stream = self.stream.clone();
+        #[cfg(feature = "ffmpeg")]
+        let video_codec_name = self.video_codec_name.clone();
+        #[cfg(feature = "ffmpeg")]
+        let verification = self.verification.clone();
+        #[cfg(feature = "ffmpeg")]
+        let finished_sink = self.finished_sink.lock().take();
+        let finished = self.finished.clone();
+        let flushed = self.flushed_sender.lock().take()
+            .ok_or("Encoder already started")?;
         let config = EncoderConfig {
             output_path: self.config.output_path.clone(),
             width: self.config.width,
@@ -107,25 +382,55 @@ impl Encoder {
             quality: self.config.quality,
             audio_sample_rate: self.config.audio_sample_rate,
             audio_channels: self.config.audio_channels,
+            binaural: self.config.binaural,
+            audio_track: self.config.audio_track.clone(),
+            metadata: self.config.metadata.clone(),
+            hardware_acceleration: self.config.hardware_acceleration,
+            output_format: self.config.output_format,
+            audio_codec: self.config.audio_codec,
+            audio_bitrate_kbps: self.config.audio_bitrate_kbps,
+            audio_bit_depth: self.config.audio_bit_depth,
+            advanced_quality: self.config.advanced_quality.clone(),
+            failover_dir: self.config.failover_dir.clone(),
+            fragmented: self.config.fragmented,
+            append_to: self.config.append_to.clone(),
+            normalize_lufs: self.config.normalize_lufs,
+            silence: self.config.silence.clone(),
+            mastering: self.config.mastering.clone(),
+            quality_controls: self.config.quality_controls.clone(),
         };
         
         std::thread::spawn(move || {
+            thread_priority::elevate(ThreadRole::Encoder);
             #[cfg(feature = "ffmpeg")]
             {
                 let output_path = config.output_path.clone();
-                if let Err(e) = encode_loop_ffmpeg(
+                match encode_loop_ffmpeg(
                     running_clone,
                     frames_encoded,
+                    audio_underruns,
                     video_receiver,
                     audio_receiver,
+                    stop_at_ms,
+                    markers,
+                    failover_sender,
+                    stream,
+                    video_codec_name,
                     config,
                 ) {
-                    eprintln!("Encoder error: {}", e);
-                    if let Some(sender) = error_sender {
-                        let _ = sender.send(e.clone());
+                    Ok(output) => {
+                        *verification.lock() = Some(output.verification.clone());
+                        let _ = flushed.send(());
+                        output.finish(finished_sink);
+                    }
+                    Err(e) => {
+                        eprintln!("Encoder error: {}", e);
+                        if let Some(sender) = error_sender {
+                            let _ = sender.send(e.clone());
+                        }
+                        remove_failed_output(&output_path);
+                        *running_control.lock() = false;
                     }
-                    remove_failed_output(&output_path);
-                    *running_control.lock() = false;
                 }
             }
             
@@ -136,9 +441,12 @@ impl Encoder {
                     frames_encoded,
                     video_receiver,
                     audio_receiver,
+                    stop_at_ms,
                     config,
                 );
+                let _ = flushed.send(());
             }
+            *finished.lock() = true;
         });
         
         println!("Encoder started: {} @ {}fps", self.config.output_path, self.config.frame_rate);
@@ -147,6 +455,8 @@ impl Encoder {
     }
     
     /// Stop encoding and finalize the output file
+    ///
+    /// Frames and audio still queued are dropped; `finish` encodes them.
     pub fn stop(&self) -> Result<(), String> {
         let mut running = self.running.lock();
         *running = false;
@@ -154,6 +464,31 @@ impl Encoder {
         Ok(())
     }
     
+    /// Encode what is still queued, finalize the file, and wait until the
+    /// encoding thread acknowledges it is flushed
+    ///
+    /// Encoding ends by itself once the video and audio channels are closed
+    /// and empty, so whatever feeds them must have stopped first. If they
+    /// are still open after `DRAIN_TIMEOUT`, encoding is stopped where it is.
+    /// Finishing passes (mastering, thumbnail) run on after the
+    /// acknowledgment.
+    pub fn finish(&self) -> Result<(), String> {
+        if self.flushed_sender.lock().is_some() {
+            return Err("Encoder was not started".to_string());
+        }
+        match self.flushed.recv_timeout(DRAIN_TIMEOUT) {
+            Ok(()) => return Ok(()),
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err("Encoder exited without finalizing the file".to_string());
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+        eprintln!("Encoder inputs still open after {:?}; stopping", DRAIN_TIMEOUT);
+        self.stop()?;
+        self.flushed.recv_timeout(FLUSH_TIMEOUT)
+            .map_err(|_| "Encoder did not finalize the file".to_string())
+    }
+    
 }
 
 #[cfg(feature = "ffmpeg")]
@@ -171,8 +506,9 @@ fn remove_failed_output(output_path: &str) {
 fn encode_loop_fallback(
     running: Arc<Mutex<bool>>,
     frames_encoded: Arc<Mutex<u64>>,
-    video_receiver: Option<Receiver<CompositeFrame>>,
+    mut video_receiver: Option<Receiver<CompositeFrame>>,
     _audio_receiver: Option<Receiver<MixedAudioChunk>>,
+    stop_at_ms: Arc<Mutex<Option<u64>>>,
     config: EncoderConfig,
 ) {
     use std::path::Path;
@@ -197,17 +533,29 @@ fn encode_loop_fallback(
     let mut frame_count: u64 = 0;
     
     while *running.lock() {
+        let frame_limit = stop_at_ms.lock()
+            .map(|ms| stop_position(ms, config.frame_rate) as u64);
+        if frame_limit.is_some_and(|limit| frame_count >= limit) {
+            println!("Scheduled stop reached after {} frames", frame_count);
+            *running.lock() = false;
+            break;
+        }
+        
         // Process video frames
         if let Some(ref receiver) = video_receiver {
             while let Ok(composite_frame) = receiver.try_recv() {
+                if frame_limit.is_some_and(|limit| frame_count >= limit) {
+                    break;
+                }
+                
                 // Save frame as PNG
                 let frame_path = frames_dir.join(format!("frame_{:06}.png", frame_count));
                 
                 // Convert RGBA to image and save
-                if let Some(img) = image::RgbaImage::from_raw(
+                if let Some(img) = image::ImageBuffer::<image::Rgba<u8>, &[u8]>::from_raw(
                     config.width,
                     config.height,
-                    composite_frame.data.clone(),
+                    &composite_frame.data,
                 ) {
                     if let Err(e) = img.save(&frame_path) {
                         eprintln!("Failed to save frame: {}", e);
@@ -228,7 +576,12 @@ fn encode_loop_fallback(
             }
         }
         
-        std::thread::sleep(std::time::Duration::from_millis(1));
+        wait_for(&mut video_receiver);
+        // The compositor has stopped and every frame is saved
+        if video_receiver.is_none() {
+            *running.lock() = false;
+            break;
+        }
     }
     
     println!("Fallback encoding complete: {} frames saved to {:?}", frame_count, frames_dir);
@@ -265,83 +618,74 @@ fn encode_loop_fallback(
 fn encode_loop_ffmpeg(
     running: Arc<Mutex<bool>>,
     frames_encoded: Arc<Mutex<u64>>,
-    video_receiver: Option<Receiver<CompositeFrame>>,
-    audio_receiver: Option<Receiver<MixedAudioChunk>>,
+    audio_underruns: Arc<Mutex<u64>>,
+    mut video_receiver: Option<Receiver<CompositeFrame>>,
+    mut audio_receiver: Option<Receiver<MixedAudioChunk>>,
+    stop_at_ms: Arc<Mutex<Option<u64>>>,
+    markers: Arc<Mutex<Vec<Marker>>>,
+    failover_sender: Option<Sender<OutputFailover>>,
+    stream: Arc<Mutex<Vec<StreamInputs>>>,
+    video_codec_name: Arc<Mutex<Option<String>>>,
     config: EncoderConfig,
-) -> Result<(), String> {
+) -> Result<FinishedOutput, String> {
     use ffmpeg_next as ffmpeg;
-    use ffmpeg_next::software::scaling::{context::Context, flag::Flags};
+    use crate::channel_wait::wait_for_either;
     
     // Initialize FFmpeg
     ffmpeg::init().map_err(|e| format!("FFmpeg init failed: {}", e))?;
     
-    // Create output context
-    let mut output = ffmpeg::format::output(&config.output_path)
-        .map_err(|e| format!("Failed to create output: {}", e))?;
+    // Create output context (WebM and MKV are forced so the extension can't pick another muxer)
+    let output = create_output(&config.output_path, config.output_format)?;
     
-    // Find H.264 encoder
-    let video_codec = ffmpeg::encoder::find(ffmpeg::codec::Id::H264)
-        .ok_or("H.264 encoder not found")?;
+    // Find the audio encoder: Opus for WebM, otherwise the configured codec (AAC by default)
+    let audio_codec = match (config.output_format, config.audio_codec) {
+        (OutputFormat::Webm, _) => ffmpeg::encoder::find_by_name("libopus")
+            .or_else(|| ffmpeg::encoder::find(ffmpeg::codec::Id::OPUS))
+            .ok_or("Opus encoder not found")?,
+        (_, Some(AudioCodec::Flac)) => ffmpeg::encoder::find(ffmpeg::codec::Id::FLAC)
+            .ok_or("FLAC encoder not found")?,
+        (_, Some(AudioCodec::Pcm)) => {
+            let id = match config.audio_bit_depth {
+                Some(AudioBitDepth::Int24) => ffmpeg::codec::Id::PCM_S24LE,
+                Some(AudioBitDepth::Float32) | None => ffmpeg::codec::Id::PCM_F32LE,
+            };
+            ffmpeg::encoder::find(id).ok_or("PCM encoder not found")?
+        }
+        (_, Some(AudioCodec::Aac) | None) => ffmpeg::encoder::find(ffmpeg::codec::Id::AAC)
+            .ok_or("AAC encoder not found")?,
+    };
+    let lossless = matches!(config.audio_codec, Some(AudioCodec::Flac | AudioCodec::Pcm));
     
-    // Find AAC encoder
-    let audio_codec = ffmpeg::encoder::find(ffmpeg::codec::Id::AAC)
-        .ok_or("AAC encoder not found")?;
+    // Planar float where supported (AAC); libopus and float PCM take
+    // interleaved floats, FLAC and 24-bit PCM take 32-bit integers
+    let audio_sample_format = audio_sample_format(audio_codec);
+    
+    // Make sure the codec can carry the configured sample rate (AAC tops out at 96kHz)
+    if let Some(mut rates) = audio_codec.audio().ok().and_then(|a| a.rates()) {
+        if !rates.any(|rate| rate == config.audio_sample_rate as i32) {
+            return Err(format!(
+                "{} does not support {}Hz audio",
+                audio_codec.name(),
+                config.audio_sample_rate
+            ));
+        }
+    }
     
     let global_header = output
         .format()
         .flags()
         .contains(ffmpeg::format::flag::Flags::GLOBAL_HEADER);
     
-    let (mut video_encoder, video_stream_index, video_time_base) = {
-        let mut video_stream = output
-            .add_stream(video_codec)
-            .map_err(|e| format!("Failed to add video stream: {}", e))?;
-
-        let mut video_encoder_context = ffmpeg::codec::context::Context::new_with_codec(video_codec);
-
-        video_encoder_context.set_time_base(ffmpeg::Rational(1, config.frame_rate as i32));
-
-        if global_header {
-            video_encoder_context.set_flags(ffmpeg::codec::flag::Flags::GLOBAL_HEADER);
-        }
-
-        let mut video_encoder = video_encoder_context
-            .encoder()
-            .video()
-            .map_err(|e| format!("Failed to create video encoder: {}", e))?;
-
-        video_encoder.set_width(config.width);
-        video_encoder.set_height(config.height);
-        video_encoder.set_format(ffmpeg::format::Pixel::YUV420P);
-        video_encoder.set_frame_rate(Some(ffmpeg::Rational(config.frame_rate as i32, 1)));
-        video_encoder.set_bit_rate(config.quality.video_bitrate() as usize * 1000);
-
-        let mut video_options = ffmpeg::Dictionary::new();
-        // Use "ultrafast" preset for real-time encoding - critical for keeping up with capture
-        video_options.set("preset", "ultrafast");
-        // "zerolatency" tune optimizes for real-time recording (disables B-frames, reduces latency)
-        video_options.set("tune", "zerolatency");
-        video_options.set("crf", &config.quality.crf().to_string());
-
-        let video_encoder = video_encoder
-            .open_with(video_options)
-            .map_err(|e| format!("Failed to open video encoder: {}", e))?;
-
-        // Set stream time_base to match encoder before setting parameters
-        video_stream.set_time_base(video_encoder.time_base());
-        video_stream.set_parameters(&video_encoder);
-        let index = video_stream.index();
-        // Use encoder's time_base for consistent timestamp handling
-        let time_base = video_encoder.time_base();
-
-        (video_encoder, index, time_base)
-    };
-
-    let (mut audio_encoder, audio_stream_index, audio_time_base) = {
-        let mut audio_stream = output
-            .add_stream(audio_codec)
-            .map_err(|e| format!("Failed to add audio stream: {}", e))?;
+    // Open the video encoder (VP9 for WebM; hardware H.264 when available, otherwise libx264)
+    let VideoEncoderSetup {
+        encoder: mut video_encoder,
+        codec: video_codec,
+        input_format: video_input_format,
+        hw_frames,
+    } = open_video_encoder(&config, global_header)?;
+    *video_codec_name.lock() = Some(video_codec.name().to_string());
 
+    let mut audio_encoder = {
         let mut audio_encoder = ffmpeg::codec::context::Context::new_with_codec(audio_codec)
             .encoder()
             .audio()
@@ -354,163 +698,234 @@ fn encode_loop_ffmpeg(
             ChannelLayout::STEREO
         };
         audio_encoder.set_channel_layout(channel_layout);
-        audio_encoder
-            .set_format(ffmpeg::format::Sample::F32(ffmpeg::format::sample::Type::Planar));
+        audio_encoder.set_format(audio_sample_format);
         audio_encoder.set_time_base(ffmpeg::Rational(1, config.audio_sample_rate as i32));
-        audio_encoder.set_bit_rate(config.quality.audio_bitrate() as usize * 1000);
+        if lossless {
+            // FLAC and 24-bit PCM keep the top 24 bits of 32-bit samples
+            if audio_sample_format == ffmpeg::format::Sample::I32(ffmpeg::format::sample::Type::Packed) {
+                unsafe { (*audio_encoder.as_mut_ptr()).bits_per_raw_sample = 24 };
+            }
+        } else {
+            audio_encoder.set_bit_rate(config.audio_bitrate_kbps() as usize * 1000);
+        }
 
-        let audio_encoder = audio_encoder
+        audio_encoder
             .open()
-            .map_err(|e| format!("Failed to open audio encoder: {}", e))?;
-
-        // Set stream time_base to match encoder before setting parameters
-        audio_stream.set_time_base(audio_encoder.time_base());
-        audio_stream.set_parameters(&audio_encoder);
-        let index = audio_stream.index();
-        // Use encoder's time_base for consistent timestamp handling
-        let time_base = audio_encoder.time_base();
-
-        (audio_encoder, index, time_base)
+            .map_err(|e| format!("Failed to open audio encoder: {}", e))?
     };
     
+    // Add the streams and write the header
+    let mut segment = OutputSegment::start(
+        output,
+        config.output_path.clone(),
+        &config,
+        (video_codec, &video_encoder),
+        (audio_codec, &audio_encoder),
+    )?;
     
-    // Write header
-    output.write_header()
-        .map_err(|e| format!("Failed to write header: {}", e))?;
+    // Continue an append session: the earlier sitting goes first and new
+    // timestamps start where it ended
+    if let Some(ref previous) = config.append_to {
+        let previous_secs = segment.copy_previous(previous)?;
+        segment.video.offset =
+            -(previous_secs / f64::from(video_encoder.time_base())).round() as i64;
+        segment.audio.offset =
+            -(previous_secs / f64::from(audio_encoder.time_base())).round() as i64;
+        println!("Appending to {} after {:.1}s", previous.display(), previous_secs);
+    }
     
-    // After write_header, the muxer may have adjusted stream time_bases
-    // Get the actual stream time_bases for proper rescaling
-    let actual_video_time_base = output.stream(video_stream_index)
-        .map(|s| s.time_base())
-        .unwrap_or(video_time_base);
-    let actual_audio_time_base = output.stream(audio_stream_index)
-        .map(|s| s.time_base())
-        .unwrap_or(audio_time_base);
+    // Where to continue if writes to the output start failing
+    let mut failover_dir = config.failover_dir.clone();
+    let mut force_keyframe = false;
     
     println!("FFmpeg encoding started");
     println!("Video time_base: encoder={}/{}, stream={}/{}", 
-        video_time_base.numerator(), video_time_base.denominator(),
-        actual_video_time_base.numerator(), actual_video_time_base.denominator());
+        video_encoder.time_base().numerator(), video_encoder.time_base().denominator(),
+        segment.video.time_base.numerator(), segment.video.time_base.denominator());
     println!("Audio time_base: encoder={}/{}, stream={}/{}", 
-        audio_time_base.numerator(), audio_time_base.denominator(),
-        actual_audio_time_base.numerator(), actual_audio_time_base.denominator());
+        audio_encoder.time_base().numerator(), audio_encoder.time_base().denominator(),
+        segment.audio.time_base.numerator(), segment.audio.time_base.denominator());
     
-    let mut frame_count: i64 = 0;
-    let mut audio_pts: i64 = 0;
+    // PTS come from capture timestamps; `next_video_pts` and `audio_pts` are
+    // the first free slot in each stream
+    let mut frame_count: u64 = 0;
+    let mut next_video_pts: i64 = 0;
+    let mut audio_pts: Option<i64> = None;
+    // Slot of the last encoded frame, for adaptive quality's reduced frame rate
+    let mut last_video_pts: Option<i64> = None;
+    let mut crf_offset: u32 = 0;
     
-    // Create video frame buffer for the encoded format
+    // Create video frame buffer for the encoder's input format
     let mut yuv_frame = ffmpeg::frame::Video::new(
-        ffmpeg::format::Pixel::YUV420P,
+        video_input_format,
         config.width,
         config.height,
     );
 
-    // Create scalers for both BGRA and RGBA input formats
-    // BGRA is used for fast path (screen-only, no webcam overlay)
-    // RGBA is used when webcam overlay is applied
-    let mut bgra_scaler = Context::get(
-        ffmpeg::format::Pixel::BGRA,
-        config.width,
-        config.height,
-        ffmpeg::format::Pixel::YUV420P,
-        config.width,
-        config.height,
-        Flags::BILINEAR,
-    )
-    .map_err(|e| format!("Failed to create BGRA scaler: {}", e))?;
-
-    let mut rgba_scaler = Context::get(
-        ffmpeg::format::Pixel::RGBA,
-        config.width,
-        config.height,
-        ffmpeg::format::Pixel::YUV420P,
-        config.width,
-        config.height,
-        Flags::BILINEAR,
-    )
-    .map_err(|e| format!("Failed to create RGBA scaler: {}", e))?;
+    // Scalers are created for the layouts frames actually arrive in
+    // (BGRA or NV12 on the fast path, RGBA when the webcam overlay is applied)
+    let mut converter = FrameConverter::new(config.width, config.height, video_input_format);
     
-    // Create audio frame buffer
-    let samples_per_frame = audio_encoder.frame_size() as usize;
+    // Create audio frame buffer (PCM has no fixed frame size)
+    let samples_per_frame = match audio_encoder.frame_size() as usize {
+        0 => PCM_FRAME_SIZE,
+        size => size,
+    };
     let mut audio_frame = ffmpeg::frame::Audio::new(
-        ffmpeg::format::Sample::F32(ffmpeg::format::sample::Type::Planar),
+        audio_sample_format,
         samples_per_frame,
-        ffmpeg::ChannelLayout::STEREO,
+        audio_encoder.channel_layout(),
     );
     
     // Audio sample buffer
     let mut audio_buffer: Vec<f32> = Vec::new();
+    let loop_start = std::time::Instant::now();
     
     while *running.lock() {
+        // Scheduled stop: PTS limits for both streams
+        let stop_at = *stop_at_ms.lock();
+        let video_limit = stop_at.map(|ms| stop_position(ms, config.frame_rate));
+        let audio_limit = stop_at.map(|ms| stop_position(ms, config.audio_sample_rate));
+        if let Some(ms) = stop_at {
+            let video_done = video_limit.is_some_and(|limit| next_video_pts >= limit);
+            let audio_done = audio_limit.is_some_and(|limit| audio_pts.is_some_and(|pts| pts >= limit));
+            // Don't wait forever if a source stalls
+            let overdue = loop_start.elapsed().as_millis() as u64 > ms + SCHEDULED_STOP_GRACE_MS;
+            if (video_done && audio_done) || overdue {
+                println!("Scheduled stop reached at {}ms", ms);
+                *running.lock() = false;
+                break;
+            }
+        }
+        
+        // Adaptive quality raised the CRF: libx264 reconfigures itself before
+        // the next frame (other encoders keep theirs)
+        if let Some(ref controls) = config.quality_controls {
+            let offset = controls.crf_offset();
+            if offset != crf_offset {
+                crf_offset = offset;
+                if video_codec.name() == "libx264" {
+                    let crf = (config.crf() + offset).min(MAX_X264_CRF);
+                    match set_x264_crf(&mut video_encoder, crf) {
+                        Ok(()) => println!("libx264: CRF raised to {}", crf),
+                        Err(e) => eprintln!("{}", e),
+                    }
+                }
+            }
+        }
+        
         // Process video frames
         if let Some(ref receiver) = video_receiver {
             while let Ok(composite_frame) = receiver.try_recv() {
-                // Choose the right pixel format and scaler based on input format
-                let conversion_result = if composite_frame.is_bgra {
-                    // Fast path: BGRA input (no webcam overlay, screen-only)
-                    let mut bgra_frame = ffmpeg::frame::Video::new(
-                        ffmpeg::format::Pixel::BGRA,
-                        config.width,
-                        config.height,
-                    );
-                    fill_rgba_frame(
-                        &mut bgra_frame,
-                        config.width,
-                        config.height,
-                        &composite_frame.data,
-                    );
-                    bgra_scaler.run(&bgra_frame, &mut yuv_frame)
-                } else {
-                    // Slow path: RGBA input (webcam overlay applied)
-                    let mut rgba_frame = ffmpeg::frame::Video::new(
-                        ffmpeg::format::Pixel::RGBA,
-                        config.width,
-                        config.height,
-                    );
-                    fill_rgba_frame(
-                        &mut rgba_frame,
-                        config.width,
-                        config.height,
-                        &composite_frame.data,
-                    );
-                    rgba_scaler.run(&rgba_frame, &mut yuv_frame)
-                };
-
-                if let Err(e) = conversion_result {
+                for inputs in stream.lock().iter() {
+                    inputs.offer_video(&composite_frame);
+                }
+                let pts = frame_pts(composite_frame.timestamp, config.frame_rate);
+                if let Some(limit) = video_limit.filter(|&limit| pts.max(next_video_pts) >= limit) {
+                    next_video_pts = next_video_pts.max(limit);
+                    break;
+                }
+                // A frame more than one slot behind would shift everything after it
+                if pts + 1 < next_video_pts {
+                    continue;
+                }
+                // Jitter can land two frames in one slot; the later one takes the next
+                let pts = pts.max(next_video_pts);
+                // At a reduced frame rate each encoded frame holds several slots
+                let divisor = config.quality_controls.as_ref().map_or(1, |c| c.frame_divisor()) as i64;
+                if last_video_pts.is_some_and(|last| pts < last + divisor) {
+                    continue;
+                }
+                
+                // Convert to the encoder's input format (BGRA/NV12 fast path or RGBA)
+                if let Err(e) = converter.convert(&composite_frame, &mut yuv_frame) {
                     eprintln!("Pixel format conversion error: {}", e);
                     continue;
                 }
 
-                yuv_frame.set_pts(Some(frame_count));
+                yuv_frame.set_pts(Some(pts));
+                next_video_pts = pts + 1;
+                last_video_pts = Some(pts);
+                
+                // A new output segment has to start with a keyframe
+                yuv_frame.set_kind(if std::mem::take(&mut force_keyframe) {
+                    ffmpeg::picture::Type::I
+                } else {
+                    ffmpeg::picture::Type::None
+                });
 
-                // Encode video frame
-                if let Err(e) = encode_video_frame(
-                    &mut video_encoder,
-                    &yuv_frame,
-                    &mut output,
-                    video_stream_index,
-                    actual_video_time_base,
-                ) {
+                // Encode video frame (uploading to GPU surfaces first for VAAPI)
+                let encode_result = match hw_frames {
+                    Some(ref frames) => frames.upload(&yuv_frame).and_then(|hw_frame| {
+                        encode_video_frame(&mut video_encoder, &hw_frame, &mut segment)
+                    }),
+                    None => encode_video_frame(&mut video_encoder, &yuv_frame, &mut segment),
+                };
+                if let Err(e) = encode_result {
                     eprintln!("Video encode error: {}", e);
                 }
 
                 frame_count += 1;
-                *frames_encoded.lock() = frame_count as u64;
+                *frames_encoded.lock() = frame_count;
             }
         }
         
         // Process audio chunks
         if let Some(ref receiver) = audio_receiver {
             while let Ok(audio_chunk) = receiver.try_recv() {
-                audio_buffer.extend(&audio_chunk.samples);
+                for inputs in stream.lock().iter() {
+                    inputs.offer_audio(&audio_chunk);
+                }
+                
+                // The mixer converts every source; anything else would play
+                // at the wrong speed and throw the timestamps off
+                if audio_chunk.sample_rate != config.audio_sample_rate
+                    || audio_chunk.channels != config.audio_channels
+                {
+                    eprintln!(
+                        "Dropping {}ch {}Hz audio chunk: the encoder takes {}ch {}Hz",
+                        audio_chunk.channels,
+                        audio_chunk.sample_rate,
+                        config.audio_channels,
+                        config.audio_sample_rate
+                    );
+                    continue;
+                }
+                
+                // Line the chunk up with its capture time: pad gaps left by
+                // dropped chunks with silence and trim overlap
+                let chunk_pts = frame_pts(audio_chunk.timestamp, config.audio_sample_rate);
+                let start_pts = *audio_pts.get_or_insert(chunk_pts);
+                let channels = config.audio_channels as usize;
+                let buffered = (audio_buffer.len() / channels) as i64;
+                let offset = audio_offset(chunk_pts, start_pts + buffered, config.audio_sample_rate);
+                if offset > 0 {
+                    *audio_underruns.lock() += 1;
+                    audio_buffer.resize(audio_buffer.len() + offset as usize * channels, 0.0);
+                    audio_buffer.extend(&audio_chunk.samples);
+                } else {
+                    let skip = (-offset as usize * channels).min(audio_chunk.samples.len());
+                    audio_buffer.extend(&audio_chunk.samples[skip..]);
+                }
                 
                 // Encode complete audio frames
                 while audio_buffer.len() >= samples_per_frame * config.audio_channels as usize {
-                    // Fill audio frame
-                    let samples_to_take = samples_per_frame * config.audio_channels as usize;
+                    // Fill audio frame, cutting the last one short at a scheduled stop
+                    let current_pts = audio_pts.unwrap_or_default();
+                    let remaining = audio_limit
+                        .map(|limit| (limit - current_pts.min(limit)) as usize)
+                        .unwrap_or(samples_per_frame);
+                    if remaining == 0 {
+                        audio_buffer.clear();
+                        break;
+                    }
+                    let frame_samples = samples_per_frame.min(remaining);
+                    let samples_to_take = frame_samples * config.audio_channels as usize;
                     let samples: Vec<f32> = audio_buffer.drain(0..samples_to_take).collect();
                     
-                    // Convert interleaved to planar
+                    audio_pts = Some(current_pts + frame_samples as i64);
+                    
+                    // Copy into the frame (converting interleaved to planar if needed)
                     if let Err(e) = fill_audio_frame(
                         &samples,
                         config.audio_channels,
@@ -520,63 +935,589 @@ fn encode_loop_ffmpeg(
                         continue;
                     }
                     
-                    audio_frame.set_pts(Some(audio_pts));
-                    audio_pts += samples_per_frame as i64;
+                    audio_frame.set_pts(Some(current_pts));
                     
                     // Encode audio frame
-                    if let Err(e) = encode_audio_frame(
-                        &mut audio_encoder,
-                        &audio_frame,
-                        &mut output,
-                        audio_stream_index,
-                        actual_audio_time_base,
-                    ) {
+                    if let Err(e) = encode_audio_frame(&mut audio_encoder, &audio_frame, &mut segment) {
                         eprintln!("Audio encode error: {}", e);
                     }
                 }
             }
         }
         
-        std::thread::sleep(std::time::Duration::from_millis(1));
+        // Writes are failing (drive unplugged, quota): continue in a new file
+        // on the failover drive instead of losing the rest of the recording
+        if let Some(reason) = segment.write_error() {
+            if let Some(dir) = failover_dir.take() {
+                let path = failover_path(std::path::Path::new(&config.output_path), &dir);
+                let path = path.to_string_lossy().to_string();
+                eprintln!("Writing {} failed ({}), continuing in {}", segment.path, reason, path);
+                
+                let output = create_output(&path, config.output_format)?;
+                let mut next = OutputSegment::start(
+                    output,
+                    path.clone(),
+                    &config,
+                    (video_codec, &video_encoder),
+                    (audio_codec, &audio_encoder),
+                )?;
+                // The new file starts at zero, from the next keyframe
+                next.video.offset = next_video_pts;
+                next.audio.offset = audio_pts.unwrap_or_default();
+                force_keyframe = true;
+                std::mem::replace(&mut segment, next).abandon();
+                
+                if let Some(ref sender) = failover_sender {
+                    let _ = sender.try_send(OutputFailover {
+                        at_ms: (next_video_pts as u64 * 1000) / config.frame_rate.max(1) as u64,
+                        failed_path: config.output_path.clone(),
+                        path,
+                        reason,
+                    });
+                }
+            }
+        }
+        
+        // Sleep until the next frame or chunk; past a scheduled stop the
+        // remaining frames are never read, so only audio is waited for
+        if video_limit.is_some_and(|limit| next_video_pts >= limit) {
+            wait_for(&mut audio_receiver);
+        } else {
+            wait_for_either(&mut video_receiver, &mut audio_receiver);
+        }
+        
+        // Both inputs have closed and been drained: nothing more will come
+        if video_receiver.is_none() && audio_receiver.is_none() {
+            println!("Encoder inputs closed");
+            *running.lock() = false;
+            break;
+        }
     }
     
     // Flush encoders
     println!("Flushing encoders...");
     
     // Flush video encoder
-    let _ = flush_video_encoder(
-        &mut video_encoder,
-        &mut output,
-        video_stream_index,
-        actual_video_time_base,
-    );
+    let _ = flush_video_encoder(&mut video_encoder, &mut segment);
     
     // Flush audio encoder
-    let _ = flush_audio_encoder(
-        &mut audio_encoder,
-        &mut output,
-        audio_stream_index,
-        actual_audio_time_base,
-    );
+    let _ = flush_audio_encoder(&mut audio_encoder, &mut segment);
     
-    // Write trailer
-    output.write_trailer()
+    // Markers become chapters, timed from where this file starts (a failover
+    // file starts late, an append session early)
+    let chapters = {
+        use ffmpeg::Rescale;
+        let to_ms = |pts: i64| pts.rescale(video_encoder.time_base(), ffmpeg::Rational(1, 1000));
+        let duration_ms = to_ms(next_video_pts - segment.video.offset).max(0) as u64;
+        crate::markers::chapters(&markers.lock(), to_ms(segment.video.offset), duration_ms)
+    };
+    // Fragmented MP4 has written its index already
+    if !config.fragmented {
+        if let Err(e) = crate::markers::add_chapters(&mut segment.output, &chapters) {
+            eprintln!("{}", e);
+        }
+    }
+    
+    // Write trailer (for MP4 this also rewrites the file with the moov atom first)
+    segment.output.write_trailer()
         .map_err(|e| format!("Failed to write trailer: {}", e))?;
     
+    // Read the file back while nothing else has touched it yet
+    let (written, verification) = {
+        use ffmpeg::Rescale;
+        let written = crate::output_check::WrittenOutput {
+            video_ms: (next_video_pts - segment.video.offset)
+                .rescale(video_encoder.time_base(), ffmpeg::Rational(1, 1000))
+                .max(0) as u64,
+            audio_ms: audio_pts.map(|pts| {
+                (pts - segment.audio.offset)
+                    .rescale(audio_encoder.time_base(), ffmpeg::Rational(1, 1000))
+                    .max(0) as u64
+            }),
+        };
+        (written, crate::output_check::verify(std::path::Path::new(&segment.path), &written))
+    };
+    if !verification.verified {
+        eprintln!("Output verification failed: {}", verification.issues.join("; "));
+    }
+    
+    // The joined file takes the earlier sitting's place
+    let mut finished_path = PathBuf::from(&segment.path);
+    if let Some(ref previous) = config.append_to {
+        if segment.path == config.output_path {
+            // Keep the joined file rather than fail: it holds both sittings
+            match std::fs::rename(&segment.path, previous) {
+                Ok(()) => finished_path = previous.clone(),
+                Err(e) => eprintln!(
+                    "Failed to replace {} ({}); the joined recording is {}",
+                    previous.display(),
+                    e,
+                    segment.path
+                ),
+            }
+        }
+    }
+    
     println!("Encoding complete: {} frames", frame_count);
     
-    Ok(())
+    Ok(FinishedOutput {
+        path: finished_path,
+        written,
+        verification,
+        chapters,
+        silence: config.silence,
+        mastering: config.mastering,
+        normalize_lufs: config.normalize_lufs,
+    })
+}
+
+/// A finalized recording and the passes still to run over it
+#[cfg(feature = "ffmpeg")]
+struct FinishedOutput {
+    path: PathBuf,
+    written: crate::output_check::WrittenOutput,
+    /// The file as encoded, before the finishing passes
+    verification: OutputVerification,
+    chapters: Vec<crate::markers::Chapter>,
+    silence: SilenceConfig,
+    mastering: MasteringConfig,
+    normalize_lufs: Option<f32>,
+}
+
+#[cfg(feature = "ffmpeg")]
+impl FinishedOutput {
+    /// Run the finishing passes, write the chapter list and thumbnail, and
+    /// tell `sink` how the final file checks out
+    fn finish(self, sink: Option<FinishedSink>) {
+        let finished_path = self.path;
+        let mut chapters = self.chapters;
+        let mut written = self.written;
+        let mut rewritten = false;
+        // The recording is kept as it is if any pass fails; silence is trimmed
+        // first so it can't affect the loudness measurement
+        match crate::silence::finalize(&finished_path, &self.silence) {
+            // The file's own chapters move with the cut; the list has to follow
+            Ok(Some(kept)) => {
+                chapters = crate::markers::trim_chapters(&chapters, kept.start_ms..kept.end_ms);
+                written = written.within(kept.start_ms..kept.end_ms);
+                rewritten = true;
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("Silence analysis failed: {}", e),
+        }
+        match crate::mastering::finalize(&finished_path, &self.mastering) {
+            Ok(()) => rewritten |= self.mastering.enabled,
+            Err(e) => eprintln!("Mastering failed: {}", e),
+        }
+        if let Some(target) = self.normalize_lufs {
+            match crate::loudness::normalize_file(&finished_path, target) {
+                Ok(_) => rewritten = true,
+                Err(e) => eprintln!("Loudness normalization failed: {}", e),
+            }
+        }
+
+        // The check made at stop is of the file as encoded; a rewritten file
+        // is read back again
+        let verification = if rewritten {
+            let verification = crate::output_check::verify(&finished_path, &written);
+            if !verification.verified {
+                eprintln!("Finished file verification failed: {}", verification.issues.join("; "));
+            }
+            verification
+        } else {
+            self.verification
+        };
+        if !chapters.is_empty() {
+            match crate::markers::write_chapters_file(&finished_path, &chapters) {
+                Ok(path) => println!("Chapters written: {}", path.display()),
+                Err(e) => eprintln!("Chapter list failed: {}", e),
+            }
+        }
+        match crate::thumbnail::write_thumbnail(&finished_path) {
+            Ok(path) => println!("Thumbnail written: {}", path.display()),
+            Err(e) => eprintln!("Thumbnail failed: {}", e),
+        }
+
+        if let Some(sink) = sink {
+            sink(crate::output_check::FinishedRecording {
+                output_path: finished_path,
+                verification,
+            });
+        }
+    }
+}
+
+/// Hardware H.264 encoders to try, in order of preference
+#[cfg(all(feature = "ffmpeg", target_os = "macos"))]
+const HARDWARE_ENCODERS: &[&str] = &["h264_videotoolbox"];
+#[cfg(all(feature = "ffmpeg", target_os = "windows"))]
+const HARDWARE_ENCODERS: &[&str] = &["h264_nvenc", "h264_qsv", "h264_amf"];
+#[cfg(all(feature = "ffmpeg", not(any(target_os = "macos", target_os = "windows"))))]
+const HARDWARE_ENCODERS: &[&str] = &["h264_vaapi"];
+
+/// An opened video encoder and the frames it expects
+#[cfg(feature = "ffmpeg")]
+struct VideoEncoderSetup {
+    encoder: ffmpeg_next::encoder::video::Encoder,
+    codec: ffmpeg_next::Codec,
+    /// Pixel format captured frames are converted to
+    input_format: ffmpeg_next::format::Pixel,
+    /// GPU frame pool converted frames are uploaded to (VAAPI only)
+    hw_frames: Option<HwFrames>,
+}
+
+/// Sample format for an audio encoder: 32-bit float (planar if supported),
+/// or packed 32-bit integers for encoders without float input (FLAC)
+#[cfg(feature = "ffmpeg")]
+pub(crate) fn audio_sample_format(codec: ffmpeg_next::Codec) -> ffmpeg_next::format::Sample {
+    use ffmpeg_next::format::{sample::Type, Sample};
+
+    let Some(formats) = codec.audio().ok().and_then(|audio| audio.formats()) else {
+        return Sample::F32(Type::Planar);
+    };
+    let formats: Vec<Sample> = formats.collect();
+    [Sample::F32(Type::Planar), Sample::F32(Type::Packed)]
+        .into_iter()
+        .find(|format| formats.contains(format))
+        .unwrap_or(Sample::I32(Type::Packed))
+}
+
+/// Open the video encoder for the configured format
+///
+/// WebM uses libvpx-vp9. MP4 and MKV try hardware H.264 encoders if enabled and
+/// available, otherwise libx264.
+#[cfg(feature = "ffmpeg")]
+fn open_video_encoder(config: &EncoderConfig, global_header: bool) -> Result<VideoEncoderSetup, String> {
+    if config.output_format == OutputFormat::Webm {
+        return open_vp9_encoder(config, global_header);
+    }
+
+    if config.hardware_acceleration {
+        for &name in HARDWARE_ENCODERS {
+            let Some(codec) = ffmpeg_next::encoder::find_by_name(name) else {
+                continue;
+            };
+            match open_hardware_encoder(codec, name, config, global_header) {
+                Ok(setup) => {
+                    println!("Using hardware video encoder: {}", name);
+                    return Ok(setup);
+                }
+                Err(e) => println!("Hardware encoder {} unavailable: {}", name, e),
+            }
+        }
+        println!("No hardware video encoder available, falling back to libx264");
+    }
+
+    let codec = ffmpeg_next::encoder::find_by_name("libx264")
+        .or_else(|| ffmpeg_next::encoder::find(ffmpeg_next::codec::Id::H264))
+        .ok_or("H.264 encoder not found")?;
+    let format = ffmpeg_next::format::Pixel::YUV420P;
+    let encoder = new_video_encoder(codec, config, global_header, format)?;
+
+    let mut video_options = ffmpeg_next::Dictionary::new();
+    // "ultrafast" unless overridden - slower presets may not keep up with capture
+    video_options.set("preset", &config.x264_preset());
+    // "zerolatency" unless overridden - disables B-frames and lookahead, and
+    // threads split each frame into slices instead of buffering frames
+    let tune = config.x264_tune();
+    video_options.set("tune", &tune);
+    video_options.set("crf", &config.crf().to_string());
+    let threads = config.encoder_threads();
+    video_options.set("threads", &threads.to_string());
+    if let Some(slices) = config.advanced().slices {
+        video_options.set("slices", &slices.to_string());
+    }
+    config.set_rate_limit(&mut video_options);
+    println!("libx264: preset {}, tune {}, {} threads", config.x264_preset(), tune, threads);
+
+    let encoder = encoder
+        .open_with(video_options)
+        .map_err(|e| format!("Failed to open video encoder: {}", e))?;
+
+    Ok(VideoEncoderSetup {
+        encoder,
+        codec,
+        input_format: format,
+        hw_frames: None,
+    })
+}
+
+/// Open libvpx-vp9 in realtime mode with constrained quality
+#[cfg(feature = "ffmpeg")]
+fn open_vp9_encoder(config: &EncoderConfig, global_header: bool) -> Result<VideoEncoderSetup, String> {
+    let codec = ffmpeg_next::encoder::find_by_name("libvpx-vp9")
+        .or_else(|| ffmpeg_next::encoder::find(ffmpeg_next::codec::Id::VP9))
+        .ok_or("VP9 encoder not found")?;
+    let format = ffmpeg_next::format::Pixel::YUV420P;
+    let encoder = new_video_encoder(codec, config, global_header, format)?;
+
+    let mut options = ffmpeg_next::Dictionary::new();
+    // "realtime" with high cpu-used is the only VP9 mode fast enough for live capture
+    options.set("deadline", "realtime");
+    options.set("cpu-used", "8");
+    options.set("row-mt", "1");
+    options.set("threads", &config.encoder_threads().to_string());
+    options.set("lag-in-frames", "0");
+    // CRF together with the stream bitrate gives constrained quality
+    options.set("crf", &config.vp9_crf().to_string());
+    config.set_rate_limit(&mut options);
+
+    let encoder = encoder
+        .open_with(options)
+        .map_err(|e| format!("Failed to open VP9 encoder: {}", e))?;
+
+    Ok(VideoEncoderSetup {
+        encoder,
+        codec,
+        input_format: format,
+        hw_frames: None,
+    })
+}
+
+/// Try to open one hardware encoder with low-latency settings
+#[cfg(feature = "ffmpeg")]
+fn open_hardware_encoder(
+    codec: ffmpeg_next::Codec,
+    name: &str,
+    config: &EncoderConfig,
+    global_header: bool,
+) -> Result<VideoEncoderSetup, String> {
+    use ffmpeg_next::format::Pixel;
+
+    // VAAPI only encodes GPU surfaces; the others take NV12 from system memory
+    let (format, hw_frames) = if name == "h264_vaapi" {
+        (Pixel::VAAPI, Some(HwFrames::vaapi(config.width, config.height)?))
+    } else {
+        (Pixel::NV12, None)
+    };
+
+    let mut encoder = new_video_encoder(codec, config, global_header, format)?;
+    if let Some(ref frames) = hw_frames {
+        frames.attach(&mut encoder);
+    }
+
+    let mut options = ffmpeg_next::Dictionary::new();
+    match name {
+        "h264_videotoolbox" => {
+            options.set("realtime", "1");
+            // Fail instead of silently using Apple's software encoder
+            options.set("allow_sw", "0");
+        }
+        "h264_nvenc" => {
+            options.set("preset", "p1");
+            options.set("tune", "ll");
+            options.set("rc", "vbr");
+            options.set("cq", &config.crf().to_string());
+        }
+        "h264_qsv" => options.set("preset", "veryfast"),
+        "h264_amf" => {
+            options.set("usage", "lowlatency");
+            options.set("quality", "speed");
+        }
+        _ => {}
+    }
+    config.set_rate_limit(&mut options);
+
+    let encoder = encoder
+        .open_with(options)
+        .map_err(|e| format!("Failed to open: {}", e))?;
+
+    Ok(VideoEncoderSetup {
+        encoder,
+        codec,
+        input_format: Pixel::NV12,
+        hw_frames,
+    })
+}
+
+/// Create an unopened video encoder with the shared output settings
+#[cfg(feature = "ffmpeg")]
+fn new_video_encoder(
+    codec: ffmpeg_next::Codec,
+    config: &EncoderConfig,
+    global_header: bool,
+    format: ffmpeg_next::format::Pixel,
+) -> Result<ffmpeg_next::encoder::video::Video, String> {
+    let mut context = ffmpeg_next::codec::context::Context::new_with_codec(codec);
+    context.set_time_base(ffmpeg_next::Rational(1, config.frame_rate as i32));
+    if global_header {
+        context.set_flags(ffmpeg_next::codec::flag::Flags::GLOBAL_HEADER);
+    }
+
+    let mut encoder = context
+        .encoder()
+        .video()
+        .map_err(|e| format!("Failed to create video encoder: {}", e))?;
+
+    encoder.set_width(config.width);
+    encoder.set_height(config.height);
+    encoder.set_format(format);
+    encoder.set_frame_rate(Some(ffmpeg_next::Rational(config.frame_rate as i32, 1)));
+    encoder.set_bit_rate(config.video_bitrate_kbps() as usize * 1000);
+
+    Ok(encoder)
+}
+
+/// VAAPI frame pool for uploading software frames to `h264_vaapi`
+#[cfg(feature = "ffmpeg")]
+struct HwFrames {
+    frames_ref: *mut ffmpeg_next::ffi::AVBufferRef,
+}
+
+#[cfg(feature = "ffmpeg")]
+impl HwFrames {
+    /// Open the default VAAPI device and create an NV12 surface pool
+    fn vaapi(width: u32, height: u32) -> Result<Self, String> {
+        use ffmpeg_next::ffi::*;
+
+        unsafe {
+            let mut device_ref: *mut AVBufferRef = std::ptr::null_mut();
+            let ret = av_hwdevice_ctx_create(
+                &mut device_ref,
+                AVHWDeviceType::AV_HWDEVICE_TYPE_VAAPI,
+                std::ptr::null(),
+                std::ptr::null_mut(),
+                0,
+            );
+            if ret < 0 {
+                return Err(format!("No VAAPI device ({})", ffmpeg_next::Error::from(ret)));
+            }
+
+            let mut frames_ref = av_hwframe_ctx_alloc(device_ref);
+            av_buffer_unref(&mut device_ref);
+            if frames_ref.is_null() {
+                return Err("Failed to allocate VAAPI frame pool".to_string());
+            }
+
+            let frames = (*frames_ref).data as *mut AVHWFramesContext;
+            (*frames).format = AVPixelFormat::AV_PIX_FMT_VAAPI;
+            (*frames).sw_format = AVPixelFormat::AV_PIX_FMT_NV12;
+            (*frames).width = width as i32;
+            (*frames).height = height as i32;
+            (*frames).initial_pool_size = 20;
+
+            let ret = av_hwframe_ctx_init(frames_ref);
+            if ret < 0 {
+                av_buffer_unref(&mut frames_ref);
+                return Err(format!(
+                    "Failed to initialize VAAPI frame pool ({})",
+                    ffmpeg_next::Error::from(ret)
+                ));
+            }
+
+            Ok(Self { frames_ref })
+        }
+    }
+
+    /// Give the encoder its own reference to the pool (before opening it)
+    fn attach(&self, encoder: &mut ffmpeg_next::encoder::video::Video) {
+        unsafe {
+            (*encoder.as_mut_ptr()).hw_frames_ctx = ffmpeg_next::ffi::av_buffer_ref(self.frames_ref);
+        }
+    }
+
+    /// Copy a software NV12 frame into a GPU surface
+    fn upload(&self, frame: &ffmpeg_next::frame::Video) -> Result<ffmpeg_next::frame::Video, String> {
+        use ffmpeg_next::ffi::*;
+
+        let mut hw_frame = ffmpeg_next::frame::Video::empty();
+        unsafe {
+            let ret = av_hwframe_get_buffer(self.frames_ref, hw_frame.as_mut_ptr(), 0);
+            if ret < 0 {
+                return Err(format!("Failed to get VAAPI surface ({})", ffmpeg_next::Error::from(ret)));
+            }
+            let ret = av_hwframe_transfer_data(hw_frame.as_mut_ptr(), frame.as_ptr(), 0);
+            if ret < 0 {
+                return Err(format!("Failed to upload frame ({})", ffmpeg_next::Error::from(ret)));
+            }
+        }
+        hw_frame.set_pts(frame.pts());
+        hw_frame.set_kind(frame.kind());
+        Ok(hw_frame)
+    }
+}
+
+#[cfg(feature = "ffmpeg")]
+impl Drop for HwFrames {
+    fn drop(&mut self) {
+        unsafe {
+            ffmpeg_next::ffi::av_buffer_unref(&mut self.frames_ref);
+        }
+    }
+}
+
+/// How long past a scheduled stop to wait for late streams before finalizing
+#[cfg(feature = "ffmpeg")]
+const SCHEDULED_STOP_GRACE_MS: u64 = 2000;
+
+/// Highest CRF libx264 accepts
+#[cfg(feature = "ffmpeg")]
+const MAX_X264_CRF: u32 = 51;
+
+/// Samples per audio frame for encoders without a fixed frame size (PCM)
+#[cfg(feature = "ffmpeg")]
+pub(crate) const PCM_FRAME_SIZE: usize = 1024;
+
+/// PTS (in units of `rate` per second) at which a scheduled stop cuts the stream
+fn stop_position(timestamp_ms: u64, rate: u32) -> i64 {
+    (timestamp_ms * rate as u64).div_ceil(1000) as i64
+}
+
+/// Audio drift from capture timestamps tolerated before padding or trimming
+#[cfg(any(feature = "ffmpeg", test))]
+const AUDIO_RESYNC_THRESHOLD_MS: u32 = 20;
+
+/// PTS (in units of `rate` per second) of a capture timestamp
+#[cfg(any(feature = "ffmpeg", test))]
+pub(crate) fn frame_pts(timestamp: std::time::Duration, rate: u32) -> i64 {
+    (timestamp.as_secs_f64() * rate as f64).round() as i64
+}
+
+/// Samples of silence to insert (positive) or samples to drop (negative) so
+/// that audio captured at `chunk_pts` starts there instead of at `buffer_end`
+#[cfg(any(feature = "ffmpeg", test))]
+pub(crate) fn audio_offset(chunk_pts: i64, buffer_end: i64, sample_rate: u32) -> i64 {
+    let offset = chunk_pts - buffer_end;
+    let threshold = (sample_rate * AUDIO_RESYNC_THRESHOLD_MS / 1000) as i64;
+    if offset.abs() > threshold {
+        offset
+    } else {
+        0
+    }
 }
 
 /// Fill audio frame with interleaved samples converted to planar
 #[cfg(feature = "ffmpeg")]
-fn fill_audio_frame(
+pub(crate) fn fill_audio_frame(
     interleaved: &[f32],
     channels: u16,
     frame: &mut ffmpeg_next::frame::Audio,
 ) -> Result<(), String> {
     let samples_per_channel = interleaved.len() / channels as usize;
     
+    // Integer samples (FLAC): 24-bit values in the top bits of each i32
+    if let ffmpeg_next::format::Sample::I32(_) = frame.format() {
+        let plane = frame.data_mut(0);
+        let plane_i32: &mut [i32] = unsafe {
+            std::slice::from_raw_parts_mut(plane.as_mut_ptr() as *mut i32, interleaved.len())
+        };
+        for (out, sample) in plane_i32.iter_mut().zip(interleaved) {
+            *out = ((sample.clamp(-1.0, 1.0) * 8_388_607.0).round() as i32) << 8;
+        }
+        frame.set_samples(samples_per_channel);
+        return Ok(());
+    }
+    
+    if frame.is_packed() {
+        let plane = frame.data_mut(0);
+        let plane_f32: &mut [f32] = unsafe {
+            std::slice::from_raw_parts_mut(plane.as_mut_ptr() as *mut f32, interleaved.len())
+        };
+        plane_f32.copy_from_slice(interleaved);
+        frame.set_samples(samples_per_channel);
+        return Ok(());
+    }
+    
     for ch in 0..channels as usize {
         let plane = frame.data_mut(ch);
         let plane_f32: &mut [f32] = unsafe {
@@ -596,14 +1537,27 @@ fn fill_audio_frame(
     Ok(())
 }
 
+/// Change libx264's CRF mid-recording; it takes effect from the next frame
+#[cfg(feature = "ffmpeg")]
+fn set_x264_crf(encoder: &mut ffmpeg_next::encoder::video::Video, crf: u32) -> Result<(), String> {
+    let name = std::ffi::CString::new("crf").expect("no NUL in option name");
+    let value = std::ffi::CString::new(crf.to_string()).expect("no NUL in a number");
+    // SAFETY: libx264's private options live in `priv_data` while the encoder is open
+    let result = unsafe {
+        ffmpeg_next::ffi::av_opt_set((*encoder.as_mut_ptr()).priv_data, name.as_ptr(), value.as_ptr(), 0)
+    };
+    if result < 0 {
+        return Err(format!("Failed to change the CRF: {}", ffmpeg_next::Error::from(result)));
+    }
+    Ok(())
+}
+
 /// Encode a video frame
 #[cfg(feature = "ffmpeg")]
 fn encode_video_frame(
     encoder: &mut ffmpeg_next::encoder::video::Video,
     frame: &ffmpeg_next::frame::Video,
-    output: &mut ffmpeg_next::format::context::Output,
-    stream_index: usize,
-    time_base: ffmpeg_next::Rational,
+    segment: &mut OutputSegment,
 ) -> Result<(), String> {
     let mut packet = ffmpeg_next::Packet::empty();
     
@@ -611,10 +1565,7 @@ fn encode_video_frame(
         .map_err(|e| format!("Failed to send video frame: {}", e))?;
     
     while encoder.receive_packet(&mut packet).is_ok() {
-        packet.set_stream(stream_index);
-        packet.rescale_ts(encoder.time_base(), time_base);
-        
-        packet.write_interleaved(output)
+        segment.write(&mut packet, ffmpeg_next::media::Type::Video, encoder.time_base())
             .map_err(|e| format!("Failed to write video packet: {}", e))?;
     }
     
@@ -626,9 +1577,7 @@ fn encode_video_frame(
 fn encode_audio_frame(
     encoder: &mut ffmpeg_next::encoder::audio::Audio,
     frame: &ffmpeg_next::frame::Audio,
-    output: &mut ffmpeg_next::format::context::Output,
-    stream_index: usize,
-    time_base: ffmpeg_next::Rational,
+    segment: &mut OutputSegment,
 ) -> Result<(), String> {
     let mut packet = ffmpeg_next::Packet::empty();
     
@@ -636,10 +1585,7 @@ fn encode_audio_frame(
         .map_err(|e| format!("Failed to send audio frame: {}", e))?;
     
     while encoder.receive_packet(&mut packet).is_ok() {
-        packet.set_stream(stream_index);
-        packet.rescale_ts(encoder.time_base(), time_base);
-        
-        packet.write_interleaved(output)
+        segment.write(&mut packet, ffmpeg_next::media::Type::Audio, encoder.time_base())
             .map_err(|e| format!("Failed to write audio packet: {}", e))?;
     }
     
@@ -650,9 +1596,7 @@ fn encode_audio_frame(
 #[cfg(feature = "ffmpeg")]
 fn flush_video_encoder(
     encoder: &mut ffmpeg_next::encoder::video::Video,
-    output: &mut ffmpeg_next::format::context::Output,
-    stream_index: usize,
-    time_base: ffmpeg_next::Rational,
+    segment: &mut OutputSegment,
 ) -> Result<(), String> {
     let mut packet = ffmpeg_next::Packet::empty();
     
@@ -660,10 +1604,7 @@ fn flush_video_encoder(
         .map_err(|e| format!("Failed to send EOF: {}", e))?;
     
     while encoder.receive_packet(&mut packet).is_ok() {
-        packet.set_stream(stream_index);
-        packet.rescale_ts(encoder.time_base(), time_base);
-        
-        let _ = packet.write_interleaved(output);
+        let _ = segment.write(&mut packet, ffmpeg_next::media::Type::Video, encoder.time_base());
     }
     
     Ok(())
@@ -672,9 +1613,7 @@ fn flush_video_encoder(
 #[cfg(feature = "ffmpeg")]
 fn flush_audio_encoder(
     encoder: &mut ffmpeg_next::encoder::audio::Audio,
-    output: &mut ffmpeg_next::format::context::Output,
-    stream_index: usize,
-    time_base: ffmpeg_next::Rational,
+    segment: &mut OutputSegment,
 ) -> Result<(), String> {
     let mut packet = ffmpeg_next::Packet::empty();
 
@@ -683,31 +1622,429 @@ fn flush_audio_encoder(
         .map_err(|e| format!("Failed to send EOF: {}", e))?;
 
     while encoder.receive_packet(&mut packet).is_ok() {
-        packet.set_stream(stream_index);
-        packet.rescale_ts(encoder.time_base(), time_base);
-
-        let _ = packet.write_interleaved(output);
+        let _ = segment.write(&mut packet, ffmpeg_next::media::Type::Audio, encoder.time_base());
     }
 
     Ok(())
 }
 
+/// Create the muxer for an output file
 #[cfg(feature = "ffmpeg")]
-fn fill_rgba_frame(
-    frame: &mut ffmpeg_next::frame::Video,
+fn create_output(
+    path: &str,
+    format: OutputFormat,
+) -> Result<ffmpeg_next::format::context::Output, String> {
+    match format {
+        OutputFormat::Mp4 => ffmpeg_next::format::output(&path),
+        OutputFormat::Webm => ffmpeg_next::format::output_as(&path, "webm"),
+        OutputFormat::Mkv => ffmpeg_next::format::output_as(&path, "matroska"),
+    }
+    .map_err(|e| format!("Failed to create output: {}", e))
+}
+
+/// A stream in an output segment
+#[cfg(feature = "ffmpeg")]
+#[derive(Clone, Copy)]
+struct SegmentStream {
+    index: usize,
+    /// Stream time base chosen by the muxer
+    time_base: ffmpeg_next::Rational,
+    /// Encoder PTS at which this segment starts: earlier packets are dropped
+    /// after a failover; negative when appending to an earlier sitting
+    offset: i64,
+}
+
+/// An output file being written
+///
+/// A recording normally has one segment; a failover starts a second one on
+/// another drive, fed by the same encoders.
+#[cfg(feature = "ffmpeg")]
+struct OutputSegment {
+    output: ffmpeg_next::format::context::Output,
+    path: String,
+    video: SegmentStream,
+    audio: SegmentStream,
+}
+
+#[cfg(feature = "ffmpeg")]
+impl OutputSegment {
+    /// Add the video and audio streams and write the header
+    fn start(
+        mut output: ffmpeg_next::format::context::Output,
+        path: String,
+        config: &EncoderConfig,
+        (video_codec, video_encoder): (ffmpeg_next::Codec, &ffmpeg_next::encoder::video::Encoder),
+        (audio_codec, audio_encoder): (ffmpeg_next::Codec, &ffmpeg_next::encoder::audio::Encoder),
+    ) -> Result<Self, String> {
+        let video_index = {
+            let mut video_stream = output
+                .add_stream(video_codec)
+                .map_err(|e| format!("Failed to add video stream: {}", e))?;
+
+            // Set stream time_base to match encoder before setting parameters
+            video_stream.set_time_base(video_encoder.time_base());
+            video_stream.set_parameters(video_encoder);
+            video_stream.index()
+        };
+
+        let audio_index = {
+            let mut audio_stream = output
+                .add_stream(audio_codec)
+                .map_err(|e| format!("Failed to add audio stream: {}", e))?;
+
+            audio_stream.set_time_base(audio_encoder.time_base());
+            audio_stream.set_parameters(audio_encoder);
+
+            // Label the track so players/editors don't show "Track 2"
+            let mut metadata = ffmpeg_next::Dictionary::new();
+            if let Some(ref title) = config.audio_track.title {
+                metadata.set("title", title);
+                if config.output_format == OutputFormat::Mp4 {
+                    // Shown as the track name by editors that ignore the udta title
+                    metadata.set("handler_name", title);
+                }
+            }
+            if let Some(ref language) = config.audio_track.language {
+                metadata.set("language", language);
+            }
+            audio_stream.set_metadata(metadata);
+            audio_stream.index()
+        };
+
+        let mut metadata = ffmpeg_next::Dictionary::new();
+        for (key, value) in config.metadata.entries() {
+            metadata.set(key, value);
+        }
+        if config.binaural && config.metadata.comment.is_none() {
+            metadata.set("comment", "Binaural recording - listen with headphones");
+        }
+        output.set_metadata(metadata);
+
+        // Write header (MP4 moves the moov atom to the front when finalized, so the
+        // file is seekable and uploadable without a remux; fragmented MP4 stays
+        // readable up to the last fragment if recording is cut short)
+        let mut muxer_options = ffmpeg_next::Dictionary::new();
+        if config.output_format == OutputFormat::Mp4 {
+            if config.fragmented {
+                muxer_options.set("movflags", "+frag_keyframe+empty_moov+default_base_moof");
+            } else {
+                muxer_options.set("movflags", "+faststart");
+            }
+        }
+        output.write_header_with(muxer_options)
+            .map(|_| ())
+            .map_err(|e| format!("Failed to write header: {}", e))?;
+
+        // After write_header, the muxer may have adjusted stream time_bases
+        let stream = |index: usize, fallback: ffmpeg_next::Rational| SegmentStream {
+            index,
+            time_base: output.stream(index).map(|s| s.time_base()).unwrap_or(fallback),
+            offset: 0,
+        };
+        let video = stream(video_index, video_encoder.time_base());
+        let audio = stream(audio_index, audio_encoder.time_base());
+
+        Ok(Self {
+            output,
+            path,
+            video,
+            audio,
+        })
+    }
+
+    /// Write an encoded packet to the video or audio stream
+    fn write(
+        &mut self,
+        packet: &mut ffmpeg_next::Packet,
+        medium: ffmpeg_next::media::Type,
+        encoder_time_base: ffmpeg_next::Rational,
+    ) -> Result<(), ffmpeg_next::Error> {
+        let stream = match medium {
+            ffmpeg_next::media::Type::Video => self.video,
+            _ => self.audio,
+        };
+
+        // Packets still buffered in the encoder from before a failover
+        if packet.pts().is_some_and(|pts| pts < stream.offset) {
+            return Ok(());
+        }
+        if stream.offset != 0 {
+            packet.set_pts(packet.pts().map(|pts| pts - stream.offset));
+            packet.set_dts(packet.dts().map(|dts| dts - stream.offset));
+        }
+
+        packet.set_stream(stream.index);
+        packet.rescale_ts(encoder_time_base, stream.time_base);
+        packet.write_interleaved(&mut self.output)
+    }
+
+    /// Copy an earlier sitting's packets in ahead of new ones
+    ///
+    /// Returns the sitting's length in seconds. Its streams must match this
+    /// segment's codecs, frame size and audio format.
+    fn copy_previous(&mut self, path: &std::path::Path) -> Result<f64, String> {
+        use ffmpeg_next::media::Type;
+
+        let mut input = ffmpeg_next::format::input(&path)
+            .map_err(|e| format!("Failed to open {} to append to: {}", path.display(), e))?;
+
+        let mut targets = Vec::new();
+        for stream in input.streams() {
+            let target = match stream.parameters().medium() {
+                Type::Video => Some(self.video),
+                Type::Audio => Some(self.audio),
+                _ => None,
+            };
+            if let Some(target) = target {
+                let ours = self
+                    .output
+                    .stream(target.index)
+                    .map(|s| s.parameters())
+                    .ok_or("Output stream missing")?;
+                if !same_stream_format(&stream.parameters(), &ours) {
+                    return Err(format!(
+                        "{} was recorded with different settings; use the same format, resolution, frame rate and audio settings to append",
+                        path.display()
+                    ));
+                }
+            }
+            targets.push(target.map(|t| (t, stream.time_base())));
+        }
+
+        let mut end_secs: f64 = 0.0;
+        for (stream, mut packet) in input.packets() {
+            let Some(Some((target, time_base))) = targets.get(stream.index()).copied() else {
+                continue;
+            };
+            packet.rescale_ts(time_base, target.time_base);
+            if let Some(pts) = packet.pts() {
+                let end = (pts + packet.duration()) as f64 * f64::from(target.time_base);
+                end_secs = end_secs.max(end);
+            }
+            packet.set_stream(target.index);
+            packet.set_position(-1);
+            packet
+                .write_interleaved(&mut self.output)
+                .map_err(|e| format!("Failed to copy earlier recording: {}", e))?;
+        }
+
+        Ok(end_secs)
+    }
+
+    /// The I/O error that writes to this file have hit, if any
+    fn write_error(&self) -> Option<String> {
+        let error = unsafe {
+            let pb = (*self.output.as_ptr()).pb;
+            if pb.is_null() {
+                return None;
+            }
+            (*pb).error
+        };
+        (error < 0).then(|| ffmpeg_next::Error::from(error).to_string())
+    }
+
+    /// Give up on this file after a failover, finishing it if the drive allows
+    fn abandon(mut self) {
+        if let Err(e) = self.output.write_trailer() {
+            eprintln!("Could not finalize {}: {}", self.path, e);
+        }
+    }
+}
+
+/// Whether two streams can be joined without re-encoding
+#[cfg(feature = "ffmpeg")]
+fn same_stream_format(a: &ffmpeg_next::codec::Parameters, b: &ffmpeg_next::codec::Parameters) -> bool {
+    unsafe {
+        let (a, b) = (&*a.as_ptr(), &*b.as_ptr());
+        a.codec_id == b.codec_id
+            && a.width == b.width
+            && a.height == b.height
+            && a.sample_rate == b.sample_rate
+            && a.ch_layout.nb_channels == b.ch_layout.nb_channels
+    }
+}
+
+/// Path of the file a recording continues in on the failover drive
+#[cfg(any(feature = "ffmpeg", test))]
+fn failover_path(output_path: &std::path::Path, failover_dir: &std::path::Path) -> PathBuf {
+    let stem = output_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("recording");
+    let extension = output_path
+        .extension()
+        .and_then(|s| s.to_str())
+        .unwrap_or("mp4");
+
+    // Never overwrite an earlier failover file
+    let mut path = failover_dir.join(format!("{}_part2.{}", stem, extension));
+    let mut part = 2;
+    while path.exists() {
+        part += 1;
+        path = failover_dir.join(format!("{}_part{}.{}", stem, part, extension));
+    }
+    path
+}
+
+/// Converts composited frames to the encoder's input format
+///
+/// Frames are read in place (no per-frame copy into an FFmpeg buffer), and a
+/// swscale context is created the first time each source layout is seen.
+/// Frames smaller than the output (composited at a reduced size by adaptive
+/// quality) are scaled up in the same pass.
+#[cfg(feature = "ffmpeg")]
+pub(crate) struct FrameConverter {
     width: u32,
     height: u32,
+    output_format: ffmpeg_next::format::Pixel,
+    scalers: Vec<((FrameFormat, u32, u32), ffmpeg_next::software::scaling::Context)>,
+}
+
+#[cfg(feature = "ffmpeg")]
+impl FrameConverter {
+    pub(crate) fn new(width: u32, height: u32, output_format: ffmpeg_next::format::Pixel) -> Self {
+        Self {
+            width,
+            height,
+            output_format,
+            scalers: Vec::new(),
+        }
+    }
+
+    pub(crate) fn convert(
+        &mut self,
+        frame: &CompositeFrame,
+        output: &mut ffmpeg_next::frame::Video,
+    ) -> Result<(), String> {
+        use ffmpeg_next::format::Pixel;
+        use ffmpeg_next::software::scaling::{Context, Flags};
+
+        let pixels = frame.width as usize * frame.height as usize;
+        let (pixel, expected_len) = match frame.format {
+            FrameFormat::Rgba => (Pixel::RGBA, pixels * 4),
+            FrameFormat::Bgra => (Pixel::BGRA, pixels * 4),
+            FrameFormat::Nv12 => (Pixel::NV12, pixels * 3 / 2),
+        };
+        if frame.width > self.width
+            || frame.height > self.height
+            || frame.width == 0
+            || frame.height == 0
+            || frame.data.len() < expected_len
+        {
+            return Err(format!(
+                "Frame is {}x{} ({} bytes), expected up to {}x{} ({} bytes)",
+                frame.width,
+                frame.height,
+                frame.data.len(),
+                self.width,
+                self.height,
+                expected_len
+            ));
+        }
+
+        let layout = (frame.format, frame.width, frame.height);
+        let index = match self.scalers.iter().position(|(known, _)| *known == layout) {
+            Some(index) => index,
+            None => {
+                let scaler = Context::get(
+                    pixel,
+                    frame.width,
+                    frame.height,
+                    self.output_format,
+                    self.width,
+                    self.height,
+                    Flags::BILINEAR,
+                )
+                .map_err(|e| format!("Failed to create {:?} scaler: {}", pixel, e))?;
+                println!(
+                    "Created {:?} {}x{} -> {:?} scaler",
+                    pixel, frame.width, frame.height, self.output_format
+                );
+                self.scalers.push((layout, scaler));
+                self.scalers.len() - 1
+            }
+        };
+
+        // The source frame only points into `frame.data`, which outlives it
+        let source = borrowed_frame(&frame.data, pixel, frame.width, frame.height);
+        self.scalers[index]
+            .1
+            .run(&source, output)
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Wrap tightly packed RGBA, BGRA or NV12 data in a frame without copying it
+///
+/// The frame owns no buffers (dropping it frees nothing), so it must not be
+/// used after `data` is dropped.
+#[cfg(feature = "ffmpeg")]
+fn borrowed_frame(
     data: &[u8],
-) {
-    let stride = frame.stride(0) as usize;
-    let row_bytes = (width * 4) as usize;
-    let frame_data = frame.data_mut(0);
-
-    for y in 0..height as usize {
-        let src_start = y * row_bytes;
-        let dst_start = y * stride;
-        let src = &data[src_start..src_start + row_bytes];
-        let dst = &mut frame_data[dst_start..dst_start + row_bytes];
-        dst.copy_from_slice(src);
+    pixel: ffmpeg_next::format::Pixel,
+    width: u32,
+    height: u32,
+) -> ffmpeg_next::frame::Video {
+    let mut frame = ffmpeg_next::frame::Video::empty();
+    frame.set_format(pixel);
+    frame.set_width(width);
+    frame.set_height(height);
+
+    let base = data.as_ptr() as *mut u8;
+    unsafe {
+        let raw = frame.as_mut_ptr();
+        if pixel == ffmpeg_next::format::Pixel::NV12 {
+            // Y plane, then interleaved UV at half height
+            (*raw).data[0] = base;
+            (*raw).linesize[0] = width as i32;
+            (*raw).data[1] = base.add(width as usize * height as usize);
+            (*raw).linesize[1] = width as i32;
+        } else {
+            (*raw).data[0] = base;
+            (*raw).linesize[0] = width as i32 * 4;
+        }
+    }
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_default_encoder_threads() {
+        assert_eq!(default_encoder_threads(1, 1080), 1);
+        assert_eq!(default_encoder_threads(6, 1080), 5);
+        assert_eq!(default_encoder_threads(32, 1080), 8);
+        assert_eq!(default_encoder_threads(32, 2160), 16);
+    }
+
+    #[test]
+    fn test_timestamp_pts() {
+        assert_eq!(frame_pts(Duration::from_millis(100), 30), 3);
+        assert_eq!(frame_pts(Duration::from_millis(1016), 30), 30);
+        assert_eq!(frame_pts(Duration::from_millis(250), 48000), 12000);
+
+        // 10ms of jitter is ignored, a 100ms dropped chunk is padded, overlap is trimmed
+        assert_eq!(audio_offset(48480, 48000, 48000), 0);
+        assert_eq!(audio_offset(52800, 48000, 48000), 4800);
+        assert_eq!(audio_offset(43200, 48000, 48000), -4800);
+    }
+
+    #[test]
+    fn test_failover_path() {
+        let dir = std::env::temp_dir().join(format!("asmr_failover_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let primary = std::path::Path::new("/media/usb/session.mkv");
+
+        let first = failover_path(primary, &dir);
+        assert_eq!(first, dir.join("session_part2.mkv"));
+
+        // An earlier failover file is kept
+        std::fs::write(&first, b"").unwrap();
+        assert_eq!(failover_path(primary, &dir), dir.join("session_part3.mkv"));
+
+        let _ = std::fs::remove_dir_all(&dir);
     }
 }
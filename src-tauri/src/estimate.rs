@@ -0,0 +1,148 @@
+//! Recording Size Estimates
+//!
+//! Estimates file size from the configured encoder bitrates and compares it to
+//! the free space at the output location. Video is encoded with CRF, so real
+//! sizes vary with content; the estimate uses the nominal target bitrate.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::command;
+
+use crate::recording::{AudioCodec, RecordingConfig, RecordingError};
+
+/// Approximate MP4 container overhead (headers, index, packet framing)
+const CONTAINER_OVERHEAD: f64 = 1.01;
+
+/// Expected size of a recording and how much fits on disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingEstimate {
+    pub video_bitrate_kbps: u32,
+    pub audio_bitrate_kbps: u32,
+    /// Expected bytes per hour of recording
+    pub bytes_per_hour: u64,
+    /// Expected size for the requested duration
+    pub estimated_bytes: u64,
+    /// Free space at the output location (None if it could not be determined)
+    pub available_bytes: Option<u64>,
+    /// Hours of recording that fit in the free space
+    pub hours_remaining: Option<f64>,
+}
+
+/// Compute the estimate for a config, given the free space at the output location
+pub fn estimate(
+    config: &RecordingConfig,
+    duration_secs: f64,
+    available_bytes: Option<u64>,
+) -> RecordingEstimate {
+    let video_bitrate_kbps = config
+        .advanced_quality
+        .as_ref()
+        .and_then(|a| a.bitrate_kbps)
+        .unwrap_or_else(|| config.video_quality.video_bitrate());
+    let audio_bitrate_kbps = audio_bitrate_kbps(config);
+
+    let bytes_per_sec =
+        (video_bitrate_kbps + audio_bitrate_kbps) as f64 * 1000.0 / 8.0 * CONTAINER_OVERHEAD;
+    let bytes_per_hour = (bytes_per_sec * 3600.0) as u64;
+    let estimated_bytes = (bytes_per_sec * duration_secs.max(0.0)) as u64;
+
+    let hours_remaining = available_bytes.map(|free| free as f64 / bytes_per_hour as f64);
+
+    RecordingEstimate {
+        video_bitrate_kbps,
+        audio_bitrate_kbps,
+        bytes_per_hour,
+        estimated_bytes,
+        available_bytes,
+        hours_remaining,
+    }
+}
+
+/// Nominal audio bitrate; lossless codecs are sized from the raw PCM rate
+fn audio_bitrate_kbps(config: &RecordingConfig) -> u32 {
+    let pcm_kbps = |bits: u32| {
+        config.audio_sample_rate * config.audio_channels.channel_count() as u32 * bits / 1000
+    };
+    match config.audio_codec {
+        Some(AudioCodec::Pcm) => pcm_kbps(config.audio_bit_depth.map_or(32, |depth| depth.bits())),
+        // FLAC typically compresses 24-bit audio to about 60%
+        Some(AudioCodec::Flac) => pcm_kbps(24) * 3 / 5,
+        Some(AudioCodec::Aac) | None => config
+            .audio_bitrate_kbps
+            .unwrap_or_else(|| config.video_quality.audio_bitrate()),
+    }
+}
+
+/// Directory the recording will be written to
+fn output_directory(config: &RecordingConfig) -> PathBuf {
+    if let Some(parent) = config.output_path.as_ref().and_then(|p| p.parent()) {
+        if !parent.as_os_str().is_empty() {
+            return parent.to_path_buf();
+        }
+    }
+
+    #[cfg(debug_assertions)]
+    {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../test-results")
+    }
+
+    #[cfg(not(debug_assertions))]
+    {
+        dirs::video_dir().unwrap_or_else(|| PathBuf::from("."))
+    }
+}
+
+/// Free space on the volume containing `path` (walks up to the nearest existing directory)
+fn available_space(path: &Path) -> Option<u64> {
+    path.ancestors()
+        .find(|p| p.exists())
+        .and_then(|p| fs2::available_space(p).ok())
+}
+
+/// Tauri command: Estimate file size and remaining disk time for a recording config
+#[command]
+pub fn estimate_recording(
+    config: RecordingConfig,
+    duration_secs: f64,
+) -> Result<RecordingEstimate, RecordingError> {
+    if !duration_secs.is_finite() || duration_secs < 0.0 {
+        return Err(RecordingError::InvalidConfig(format!("Invalid duration: {}", duration_secs)));
+    }
+
+    let available = available_space(&output_directory(&config));
+    Ok(estimate(&config, duration_secs, available))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recording::VideoQuality;
+
+    #[test]
+    fn test_estimate_scales_with_duration_and_space() {
+        let config = RecordingConfig {
+            video_quality: VideoQuality::Medium,
+            ..RecordingConfig::default()
+        };
+
+        // 5000 + 192 kbps for one hour plus overhead
+        let result = estimate(&config, 3600.0, Some(10_000_000_000));
+        let expected = (5192.0 * 1000.0 / 8.0 * 3600.0 * CONTAINER_OVERHEAD) as u64;
+        assert_eq!(result.bytes_per_hour, expected);
+        assert_eq!(result.estimated_bytes, expected);
+
+        let hours = result.hours_remaining.unwrap();
+        assert!((hours - 10_000_000_000.0 / expected as f64).abs() < 1e-9);
+
+        assert!(estimate(&config, 60.0, None).hours_remaining.is_none());
+
+        // Low-quality video with full-rate audio
+        let config = RecordingConfig {
+            video_quality: VideoQuality::Low,
+            audio_bitrate_kbps: Some(256),
+            ..RecordingConfig::default()
+        };
+        assert_eq!(estimate(&config, 60.0, None).audio_bitrate_kbps, 256);
+    }
+}
@@ -3,19 +3,50 @@
 //! This module handles recording when video frames are sent from the frontend
 //! instead of being captured natively. This enables WYSIWYG recording where
 //! the frontend composites multiple sources and sends the combined frames.
+//!
+//! Alternatively the recorder can natively capture the screen area under the
+//! app's webview, which keeps the WYSIWYG output without shipping frames
+//! over IPC. The area is looked up again for every frame, so moving or
+//! resizing the window is followed; anything in front of it is recorded too.
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use crossbeam_channel::{bounded, Sender, Receiver};
+use tauri::AppHandle;
+use crossbeam_channel::{bounded, Receiver, RecvTimeoutError, Sender, TrySendError};
 use parking_lot::Mutex;
 
-use crate::audio::{MicrophoneCapture, MicrophoneCaptureConfig};
-use crate::audio_mixer::{AudioMixer, AudioMixerConfig};
-use crate::compositor::CompositeFrame;
-use crate::encoder::{Encoder, EncoderConfig};
-use crate::recording::{ExternalRecordingConfig, RecordingStatus};
+use crate::audio::{AudioLatency, MicrophoneCapture, MicrophoneCaptureConfig, SUPPORTED_SAMPLE_RATES};
+use crate::audio_mixer::{AudioMixer, AudioMixerConfig, MixerStats};
+use crate::metering::{AudioLevels, MeteringConfig};
+use crate::monitor::{MicMonitor, MonitorConfig};
+use crate::visualizer::{AudioVisualizer, VisualizerConfig};
+use crate::captions::CaptionEngine;
+use crate::sidecar_wav::{SidecarWav, SidecarWavSource};
+use crate::loudness::validate_target;
+use crate::markers::Marker;
+use crate::output_check::{self, OutputVerification};
+use crate::output_paths::{allowed_output_dirs, check_output_dir, check_output_path, preflight_output};
+use crate::monitor_feed::MonitorFeed;
+use crate::preview_server::random_token;
+use crate::ndi::NdiSink;
+use crate::streaming::{MonitorJoin, StreamingConfig, StreamingSink, WebRtcConfig, HLS_PLAYLIST};
+use crate::pipeline_stats::{FrameCounters, PipelineProbe, PipelineStats};
+use crate::queue_memory::{frame_bytes, QueueMemory};
+use crate::queue_watch::{app_sink, channel_probe, QueueWatch, WatchedQueue};
+use crate::quality_governor::{self, QualityControls, QualityGovernor, QualityStep};
+use crate::compositor::{CompositeFrame, CompositorConfig, VideoCompositor};
+use crate::devices::resolve_microphone;
+use crate::encoder::{Encoder, EncoderConfig, OutputFailover};
+use crate::frame_compression::FrameCompression;
+use crate::frame_guard::FrameGuard;
+use crate::frame_pool::FramePool;
+use crate::frame_ingest::{FrameIngest, FramePayload};
+use crate::recording::{ActiveConfigUpdate, AudioBitDepth, AudioChannelLayout, CapturePixelFormat, ExternalFrameSource, ExternalRecordingConfig, RecordingError, RecordingEvent, RecordingStatus, StartRecordingResult, append_part_path, session_file_name, validate_audio_bitrate, validate_failover_dir, validate_session_id};
+use crate::screen::{screen_capture_error, ScreenCapture, ScreenCaptureConfig, ScreenFrame, WindowArea};
+use crate::settings::AppSettings;
 use crate::system_audio::{SystemAudioCapture, SystemAudioCaptureConfig};
+use crate::thread_priority::{self, ThreadRole};
 
 /// External Frame Recorder - records video frames sent from the frontend
 pub struct ExternalRecorder {
@@ -31,16 +62,66 @@ pub struct ExternalRecorder {
     system_audio_capture: Option<SystemAudioCapture>,
     /// Audio mixer component
     audio_mixer: Option<AudioMixer>,
+    /// Live microphone monitor
+    mic_monitor: Option<MicMonitor>,
+    /// Waveform and spectrum events for the mix
+    visualizer: Option<AudioVisualizer>,
+    /// Live captions (VTT only; the frontend draws the video)
+    captions: Option<CaptionEngine>,
+    /// Uncompressed copy of the audio next to the video
+    sidecar_wav: Option<SidecarWav>,
     /// Encoder
     encoder: Option<Encoder>,
+    /// Live stream fed by the encoder
+    streaming: Option<StreamingSink>,
+    /// HLS segments and playlist fed by the encoder
+    hls: Option<StreamingSink>,
+    /// WebRTC publish for remote monitoring, with where to watch it
+    webrtc: Option<(StreamingSink, MonitorJoin)>,
+    /// Audio-only stream to an Icecast server, fed by the encoder
+    icecast: Option<StreamingSink>,
+    /// NDI source fed by the encoder
+    ndi: Option<NdiSink>,
     /// Encoder error receiver
     encoder_error_receiver: Option<Receiver<String>>,
-    /// Frame sender channel (for receiving frames from Tauri commands)
-    frame_sender: Option<Sender<CompositeFrame>>,
+    /// Encoder switches to the failover drive
+    encoder_failover_receiver: Option<Receiver<OutputFailover>>,
+    /// Watermark events for the encoder's input queues
+    queue_watch: Option<QueueWatch>,
+    /// Adaptive quality adjustments (None when it's off)
+    quality_controls: Option<Arc<QualityControls>>,
+    /// Steps quality down under sustained pressure
+    quality_governor: Option<QualityGovernor>,
+    /// Memory held by the pipeline's queues, against the configured cap
+    queue_memory: Option<Arc<QueueMemory>>,
+    /// Frame counters and queue depths for the pipeline statistics
+    pipeline: Option<PipelineProbe>,
+    /// Running app, for emitting events (None until the app is set up)
+    app_handle: Option<AppHandle>,
+    /// Decode workers feeding the encoder's frame channel
+    ingest: Option<FrameIngest>,
+    /// Native capture of the display under the webview (window area frame source)
+    area_capture: Option<ScreenCapture>,
+    /// Thread cropping captured frames for the encoder
+    area_thread: Option<std::thread::JoinHandle<()>>,
+    /// The webview whose screen area is captured
+    window_area: Option<WindowArea>,
+    /// Downscaled copy of the output for a monitor window
+    monitor_feed: MonitorFeed,
     /// Recording start time
     start_time: Option<Instant>,
     /// Frame count
     frame_count: Arc<Mutex<u64>>,
+    /// Validation and rejection counts for incoming frames
+    frame_guard: Option<FrameGuard>,
+    /// Level meter configuration (kept across sessions)
+    metering_config: MeteringConfig,
+    /// Output time (ms) at which the recording ends by itself
+    scheduled_stop_ms: Option<u64>,
+    /// Output path of a recording that ended at its scheduled stop
+    completed_output: Option<String>,
+    /// How the last finished file checked out when read back
+    output_verification: Option<OutputVerification>,
 }
 
 impl ExternalRecorder {
@@ -53,17 +134,45 @@ impl ExternalRecorder {
             mic_capture: None,
             system_audio_capture: None,
             audio_mixer: None,
+            mic_monitor: None,
+            visualizer: None,
+            captions: None,
+            sidecar_wav: None,
             encoder: None,
+            streaming: None,
+            hls: None,
+            webrtc: None,
+            icecast: None,
+            ndi: None,
             encoder_error_receiver: None,
-            frame_sender: None,
+            encoder_failover_receiver: None,
+            queue_watch: None,
+            quality_controls: None,
+            quality_governor: None,
+            queue_memory: None,
+            pipeline: None,
+            app_handle: None,
+            ingest: None,
+            area_capture: None,
+            area_thread: None,
+            window_area: None,
+            monitor_feed: MonitorFeed::default(),
             start_time: None,
             frame_count: Arc::new(Mutex::new(0)),
+            frame_guard: None,
+            metering_config: MeteringConfig::default(),
+            scheduled_stop_ms: None,
+            completed_output: None,
+            output_verification: None,
         }
     }
 
     /// Get the current recording status
     pub fn status(&mut self) -> RecordingStatus {
+        self.handle_encoder_failover();
         self.handle_encoder_errors();
+        self.handle_stream_errors();
+        self.handle_scheduled_stop();
         
         let mut status = self.status.lock().clone();
         
@@ -74,21 +183,434 @@ impl ExternalRecorder {
             }
             status.frame_count = *self.frame_count.lock();
         }
+        if let Some(ref guard) = self.frame_guard {
+            status.frame_rejections = guard.rejections();
+        }
+        status.queue_memory_bytes = self.queue_memory.as_ref().map_or(0, |m| m.bytes());
         
         status
     }
 
+    /// Get the current audio levels (None when not recording)
+    pub fn audio_levels(&self) -> Option<AudioLevels> {
+        self.audio_mixer.as_ref().map(|m| m.levels())
+    }
+
+    /// Get the mixer's rate conversion and drift statistics (None when not recording)
+    pub fn audio_stats(&self) -> Option<MixerStats> {
+        self.audio_mixer.as_ref().map(|m| m.stats())
+    }
+
+    /// Get frame rates, frame losses and queue depths (None when not recording)
+    pub fn pipeline_stats(&mut self) -> Option<PipelineStats> {
+        let (frames_encoded, audio_underruns) = self.encoder.as_ref()
+            .map(|e| (e.frames_encoded(), e.audio_underruns()))
+            .unwrap_or_default();
+        self.pipeline.as_mut().map(|p| p.stats(frames_encoded, audio_underruns))
+    }
+
+    /// Get the latency of the audio path (None when not recording)
+    pub fn audio_latency(&self) -> Option<AudioLatency> {
+        self.audio_mixer.as_ref()?;
+        let config = self.config.as_ref()?;
+        Some(AudioLatency::new(
+            self.mic_capture.as_ref().and_then(|m| m.timing()),
+            config.audio_buffer.mixer_chunk_frames,
+            config.audio_sample_rate,
+        )
+        .with_noise_gate(&config.noise_gate, config.audio_sample_rate)
+        .with_monitor(self.mic_monitor.as_ref().and_then(|m| m.timing())))
+    }
+
+    /// Change the metering configuration for this and future recordings
+    pub fn set_metering_config(&mut self, config: MeteringConfig) {
+        self.metering_config = config;
+        if let Some(ref mixer) = self.audio_mixer {
+            mixer.set_metering_config(config);
+        }
+    }
+
+    /// Share the monitor feed that recorded frames are offered to
+    pub fn set_monitor_feed(&mut self, feed: MonitorFeed) {
+        self.monitor_feed = feed;
+    }
+
+    /// Give the recorder the running app, for emitting events
+    pub fn set_app_handle(&mut self, app: AppHandle) {
+        self.app_handle = Some(app);
+    }
+
+    /// Start, change or stop live microphone monitoring for the active recording
+    pub fn set_mic_monitor(&mut self, monitor_config: Option<MonitorConfig>) -> Result<(), RecordingError> {
+        let config = self.config.as_mut().ok_or(RecordingError::NotRecording)?;
+        let mixer = self.audio_mixer.as_ref().ok_or(RecordingError::NotRecording)?;
+        if !config.capture_mic {
+            return Err("Microphone is not being recorded".to_string().into());
+        }
+
+        if let Some(monitor) = self.mic_monitor.take() {
+            mixer.set_monitor_sender(None);
+            monitor.stop();
+        }
+
+        if let Some(ref monitor_config) = monitor_config {
+            let monitor = MicMonitor::start(
+                monitor_config.clone(),
+                config.audio_sample_rate,
+                config.audio_channels.channel_count(),
+            )?;
+            mixer.set_monitor_sender(Some(monitor.sender()));
+            self.mic_monitor = Some(monitor);
+        }
+
+        config.mic_monitor = monitor_config;
+        Ok(())
+    }
+
+    /// Start, change or stop the waveform and spectrum events for the active recording
+    pub fn set_audio_visualizer(&mut self, visualizer_config: Option<VisualizerConfig>) -> Result<(), RecordingError> {
+        let config = self.config.as_mut().ok_or(RecordingError::NotRecording)?;
+        let mixer = self.audio_mixer.as_ref().ok_or("No audio is being recorded")?;
+
+        if let Some(visualizer) = self.visualizer.take() {
+            mixer.set_visualizer_sender(None);
+            visualizer.stop();
+        }
+
+        if let Some(ref visualizer_config) = visualizer_config {
+            let app = self.app_handle.clone().ok_or("App is not ready for events")?;
+            let visualizer = AudioVisualizer::start(
+                visualizer_config.clone(),
+                config.audio_sample_rate,
+                config.audio_channels.channel_count(),
+                app,
+            )?;
+            mixer.set_visualizer_sender(Some(visualizer.sender()));
+            self.visualizer = Some(visualizer);
+        }
+
+        config.audio_visualizer = visualizer_config;
+        Ok(())
+    }
+
+    /// Start pushing the active recording to an RTMP server as well
+    pub fn start_streaming(&mut self, stream_config: StreamingConfig) -> Result<(), RecordingError> {
+        let encoder = self.encoder.as_ref().ok_or(RecordingError::NotRecording)?;
+        if self.streaming.is_some() {
+            return Err("Already streaming".to_string().into());
+        }
+
+        let sink = StreamingSink::start(stream_config.target()?, encoder.stream_format())?;
+        self.streaming = Some(sink);
+        self.connect_streams();
+        self.status.lock().streaming = true;
+        Ok(())
+    }
+
+    /// Stop the live stream, if any; the recording carries on
+    pub fn stop_streaming(&mut self) {
+        let Some(sink) = self.streaming.take() else {
+            return;
+        };
+        self.connect_streams();
+        sink.stop();
+        self.status.lock().streaming = false;
+    }
+
+    /// Publish the active recording over WebRTC for collaborators to watch
+    pub fn start_webrtc(&mut self, webrtc_config: WebRtcConfig) -> Result<MonitorJoin, RecordingError> {
+        let encoder = self.encoder.as_ref().ok_or(RecordingError::NotRecording)?;
+        if self.webrtc.is_some() {
+            return Err("Remote monitoring is already on".to_string().into());
+        }
+
+        let format = encoder.stream_format();
+        let token = random_token()?;
+        let sink = StreamingSink::start(webrtc_config.target(&token, format.sample_rate)?, format)?;
+        let join = webrtc_config.join(&token);
+        self.webrtc = Some((sink, join.clone()));
+        self.connect_streams();
+        Ok(join)
+    }
+
+    /// Where the remote monitor can be watched, while it is on
+    pub fn webrtc_join(&self) -> Option<MonitorJoin> {
+        self.webrtc.as_ref().map(|(_, join)| join.clone())
+    }
+
+    /// Stop remote monitoring, if it is on; the recording carries on
+    pub fn stop_webrtc(&mut self) {
+        if let Some((sink, _)) = self.webrtc.take() {
+            self.connect_streams();
+            sink.stop();
+        }
+    }
+
+    /// Stop writing HLS, if it is on
+    fn stop_hls(&mut self) {
+        if let Some(sink) = self.hls.take() {
+            self.connect_streams();
+            sink.stop();
+        }
+    }
+
+    /// Stop the Icecast stream, if it is on
+    fn stop_icecast(&mut self) {
+        if let Some(sink) = self.icecast.take() {
+            self.connect_streams();
+            sink.stop();
+        }
+    }
+
+    /// Take the NDI source off the network, if it is on
+    fn stop_ndi(&mut self) {
+        if let Some(sink) = self.ndi.take() {
+            self.connect_streams();
+            sink.stop();
+        }
+    }
+
+    /// Start the HLS preview, the Icecast stream and the NDI source, which
+    /// are fed by `encoder` and so start with it; returns the HLS playlist
+    fn start_sinks(
+        &mut self,
+        config: &ExternalRecordingConfig,
+        hls_dir: Option<&Path>,
+        encoder: &Encoder,
+    ) -> Result<Option<PathBuf>, RecordingError> {
+        let mut hls_playlist = None;
+        if let (Some(hls), Some(dir)) = (&config.hls, hls_dir) {
+            self.hls = Some(StreamingSink::start(hls.target(dir)?, encoder.stream_format())?);
+            hls_playlist = Some(dir.join(HLS_PLAYLIST));
+        }
+        if let Some(ref icecast) = config.icecast {
+            let format = encoder.stream_format();
+            self.icecast = Some(StreamingSink::start(icecast.target(format.sample_rate)?, format)?);
+        }
+        if let Some(ref ndi) = config.ndi {
+            self.ndi = Some(NdiSink::start(ndi, encoder.stream_format())?);
+        }
+        Ok(hls_playlist)
+    }
+
+    /// Stop every stream and output the encoder feeds
+    fn stop_sinks(&mut self) {
+        self.stop_streaming();
+        self.stop_webrtc();
+        self.stop_hls();
+        self.stop_icecast();
+        self.stop_ndi();
+    }
+
+    /// Hand the encoder's input to every running stream
+    fn connect_streams(&self) {
+        if let Some(ref encoder) = self.encoder {
+            let inputs = self
+                .streaming
+                .iter()
+                .chain(&self.hls)
+                .chain(self.webrtc.iter().map(|(sink, _)| sink))
+                .chain(&self.icecast)
+                .map(|sink| sink.inputs())
+                .chain(self.ndi.iter().map(|sink| sink.inputs()))
+                .collect();
+            encoder.set_stream_inputs(inputs);
+        }
+    }
+
+    /// Schedule the recording to end exactly at `timestamp_ms` from its start
+    pub fn stop_at(&mut self, timestamp_ms: u64) -> Result<(), RecordingError> {
+        let encoder = self.encoder.as_ref().ok_or(RecordingError::NotRecording)?;
+
+        let elapsed_ms = self.start_time.map(|t| t.elapsed().as_millis() as u64).unwrap_or(0);
+        if timestamp_ms <= elapsed_ms {
+            return Err(format!(
+                "Stop time {}ms has already passed (recording is at {}ms)",
+                timestamp_ms, elapsed_ms
+            ).into());
+        }
+
+        encoder.stop_at(timestamp_ms);
+        self.scheduled_stop_ms = Some(timestamp_ms);
+        println!("Recording will stop at {}ms", timestamp_ms);
+
+        Ok(())
+    }
+
+    /// Mark the current point of the recording; written as a chapter
+    pub fn add_marker(&mut self, label: &str) -> Result<Marker, RecordingError> {
+        let encoder = self.encoder.as_ref().ok_or(RecordingError::NotRecording)?;
+
+        // Frames are placed in the file by their own timestamps, so the
+        // newest one is where the recording is; native window area capture
+        // has no ingest and follows the clock
+        let at_ms = self
+            .ingest
+            .as_ref()
+            .and_then(|ingest| ingest.position_ms())
+            .or_else(|| self.start_time.map(|t| t.elapsed().as_millis() as u64))
+            .unwrap_or(0);
+        let marker = Marker {
+            at_ms,
+            label: label.trim().to_string(),
+        };
+        encoder.add_marker(marker.clone());
+        println!("Marker '{}' at {}ms", marker.label, marker.at_ms);
+
+        Ok(marker)
+    }
+
+    /// Apply non-structural settings to the running pipeline
+    ///
+    /// Only volumes apply here; the frontend renders the video layout itself.
+    pub fn update_active_config(&mut self, update: &ActiveConfigUpdate) -> Result<(), RecordingError> {
+        update.validate()?;
+
+        if update.webcam_visible.is_some()
+            || update.webcam_position.is_some()
+            || update.webcam_size.is_some()
+            || update.webcam_x.is_some()
+            || update.webcam_y.is_some()
+            || update.webcam_width.is_some()
+            || update.webcam_fit.is_some()
+            || update.webcam_shape.is_some()
+            || update.webcam_chroma_key.is_some()
+            || update.mirror_webcam.is_some()
+        {
+            return Err(
+                "Webcam layout is controlled by the frontend for external recordings".into(),
+            );
+        }
+        if update.overlays_visible.is_some()
+            || update.text_overlays_visible.is_some()
+            || update.timer_visible.is_some()
+            || update.watermark_visible.is_some()
+            || update.cursor_highlight.is_some()
+        {
+            return Err("Overlays are drawn by the frontend for external recordings".into());
+        }
+
+        let config = self.config.as_mut().ok_or(RecordingError::NotRecording)?;
+
+        if let Some(ref mixer) = self.audio_mixer {
+            mixer.set_volumes(update.mic_volume, update.system_volume);
+        }
+        if let Some(volume) = update.mic_volume {
+            config.mic_volume = volume;
+        }
+        if let Some(volume) = update.system_volume {
+            config.system_volume = volume;
+        }
+
+        Ok(())
+    }
+
+    /// Check the settings that don't depend on devices or output paths
+    fn validate_config(config: &ExternalRecordingConfig) -> Result<(), String> {
+        if !SUPPORTED_SAMPLE_RATES.contains(&config.audio_sample_rate) {
+            return Err(format!("Unsupported audio sample rate: {}Hz", config.audio_sample_rate));
+        }
+        config.output_format.validate_sample_rate(config.audio_sample_rate)?;
+        config.output_format.validate_audio_codec(config.audio_codec)?;
+        config.output_format.validate_codec_sample_rate(config.audio_codec, config.audio_sample_rate)?;
+        if let Some(depth) = config.audio_bit_depth {
+            depth.validate_for(config.audio_codec)?;
+        }
+        if let Some(kbps) = config.audio_bitrate_kbps {
+            validate_audio_bitrate(kbps, config.audio_codec)?;
+        }
+        if let Some(target) = config.normalize_lufs {
+            validate_target(target)?;
+        }
+        if let Some(ref advanced) = config.advanced_quality {
+            advanced.validate(config.output_format)?;
+        }
+        config.audio_track.validate()?;
+        config.metadata.validate()?;
+        validate_failover_dir(config.failover_dir.as_ref())?;
+        if let Some(ref hls) = config.hls {
+            hls.validate()?;
+        }
+        if let Some(ref icecast) = config.icecast {
+            icecast.target(config.audio_sample_rate)?;
+        }
+        if let Some(ref ndi) = config.ndi {
+            ndi.validate()?;
+        }
+        if let Some(ref id) = config.append_session_id {
+            validate_session_id(id)?;
+            // Per-file outputs would overwrite the earlier sitting's
+            if config.sidecar_wav.enabled || config.captions.write_vtt {
+                return Err("Sidecar WAV and caption files aren't available for append sessions".to_string());
+            }
+        }
+        config.captions.validate()?;
+        config.queue_watermarks.validate()?;
+        config.adaptive_quality.validate()?;
+        config.queue_memory.validate()?;
+        config.silence.validate()?;
+        config.mastering.validate()?;
+        config.noise_gate.validate()?;
+        if config.mastering.loudness_target().is_some() && config.normalize_lufs.is_some() {
+            return Err("Set the loudness target in either the mastering chain or normalizeLufs, not both".to_string());
+        }
+        config.audio_buffer.validate()?;
+        if config.captions.enabled && !config.capture_mic {
+            return Err("Live captions need the microphone to be captured".to_string());
+        }
+        if config.sidecar_wav.enabled
+            && config.sidecar_wav.source == SidecarWavSource::Mic
+            && !config.capture_mic
+        {
+            return Err("A microphone sidecar WAV needs the microphone to be captured".to_string());
+        }
+        if config.captions.burn_in {
+            return Err("Burned-in captions aren't available for external frames; draw them in the frontend".to_string());
+        }
+        FrameGuard::validate_dimensions(config.output_width, config.output_height)?;
+        Ok(())
+    }
+
     /// Start recording with the given configuration
-    pub fn start(&mut self, config: ExternalRecordingConfig) -> Result<(), String> {
+    ///
+    /// `window_area` is the calling webview; it is only used (and required)
+    /// with the window area frame source.
+    pub fn start(
+        &mut self,
+        config: ExternalRecordingConfig,
+        window_area: Option<WindowArea>,
+    ) -> Result<StartRecordingResult, RecordingError> {
         // Check if already recording
         if self.status.lock().is_recording {
-            return Err("Recording already in progress".to_string());
+            return Err(RecordingError::AlreadyRecording);
         }
+        Self::validate_config(&config).map_err(RecordingError::InvalidConfig)?;
+
+        // Paths from the frontend must stay inside the allowed directories
+        let allowed_dirs = allowed_output_dirs();
+        let requested_path = config
+            .output_path
+            .as_deref()
+            .map(|path| check_output_path(path, &allowed_dirs))
+            .transpose()?;
+        if let Some(ref dir) = config.failover_dir {
+            check_output_dir(dir, &allowed_dirs)?;
+        }
+        let hls_dir = config
+            .hls
+            .as_ref()
+            .map(|hls| check_output_dir(&hls.directory, &allowed_dirs))
+            .transpose()?;
 
         // Generate output path if not provided
-        let output_path = config.output_path.clone().unwrap_or_else(|| {
-            let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
-            let filename = format!("recording_{}.mp4", timestamp);
+        let output_path = requested_path.unwrap_or_else(|| {
+            let filename = match config.append_session_id {
+                Some(ref id) => session_file_name(id, config.output_format),
+                None => {
+                    let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
+                    format!("recording_{}.{}", timestamp, config.output_format.extension())
+                }
+            };
 
             // In debug/dev mode, save to test-results directory
             #[cfg(debug_assertions)]
@@ -114,17 +636,60 @@ impl ExternalRecorder {
                     .join(filename)
             }
         });
+        preflight_output(&output_path)?;
+
+        self.completed_output = None;
+        self.output_verification = None;
 
         // Reset stop signal
         *self.stop_signal.lock() = false;
         *self.frame_count.lock() = 0;
+        self.frame_guard = Some(FrameGuard::new(
+            config.output_width,
+            config.output_height,
+            config.frame_rate.unwrap_or(30),
+        ));
+
+        // Resolve the microphone, preferring the requested one, then the last used one
+        let remembered = AppSettings::load().last_devices;
+        let mut fallback_applied = Vec::new();
+        let mut used_microphone = remembered.microphone.clone();
+
+        // Initialize native capture of the webview if requested
+        if config.frame_source == ExternalFrameSource::WindowArea {
+            let area = window_area.ok_or("Window area capture needs the webview")?;
+            let region = area.region()?;
+            let screen_config = ScreenCaptureConfig {
+                fps: config.frame_rate.unwrap_or(30),
+                display_index: region.display_index,
+                pixel_format: CapturePixelFormat::Bgra,
+            };
+            let capture = ScreenCapture::new(screen_config)
+                .map_err(|e| screen_capture_error("Failed to initialize window area capture", e))?;
+            self.area_capture = Some(capture);
+            self.window_area = Some(area);
+        }
 
         // Initialize microphone capture if enabled
         if config.capture_mic {
-            let mic_config = MicrophoneCaptureConfig::default();
+            let mic_id = config.mic_device_id.clone().or(remembered.microphone);
+            let (device_name, fallback) = resolve_microphone(mic_id.as_deref());
+            match fallback {
+                Some(notice) => fallback_applied.push(notice),
+                None => used_microphone = mic_id,
+            }
+
+            let mic_config = MicrophoneCaptureConfig {
+                device_name,
+                sample_rate: Some(config.audio_sample_rate),
+                channel_layout: config.audio_channels,
+                channel_map: config.mic_channel_map.clone(),
+                buffer_frames: config.audio_buffer.device_buffer_frames,
+                epoch: None,
+            };
 
             let mic_capture = MicrophoneCapture::new(mic_config)
-                .map_err(|e| format!("Failed to initialize microphone: {}", e))?;
+                .map_err(|e| RecordingError::Device(format!("Failed to initialize microphone: {}", e)))?;
 
             self.mic_capture = Some(mic_capture);
         }
@@ -148,27 +713,120 @@ impl ExternalRecorder {
         }
 
         // Initialize audio mixer
-        let mixer_config = AudioMixerConfig::default();
+        let mixer_config = AudioMixerConfig {
+            sample_rate: config.audio_sample_rate,
+            channels: config.audio_channels.channel_count(),
+            mic_volume: config.mic_volume,
+            system_volume: config.system_volume,
+            metering: self.metering_config,
+            binaural: config.audio_channels == AudioChannelLayout::Binaural,
+            buffer_size: config.audio_buffer.mixer_chunk_frames as usize,
+            noise_gate: config.noise_gate,
+        };
         self.audio_mixer = Some(AudioMixer::new(mixer_config));
 
+        // Start live captions before capture so no speech is missed
+        if config.captions.enabled {
+            let engine = CaptionEngine::start(
+                &config.captions,
+                &output_path,
+                config.audio_sample_rate,
+                config.audio_channels.channel_count(),
+            )?;
+            if let Some(ref mixer) = self.audio_mixer {
+                mixer.set_caption_sender(Some(engine.sender()));
+            }
+            self.captions = Some(engine);
+        }
+
+        // The sidecar WAV is fed by the mixer, so it starts with the audio
+        if config.sidecar_wav.enabled {
+            let sidecar = SidecarWav::start(
+                &config.sidecar_wav,
+                &output_path,
+                config.audio_sample_rate,
+                config.audio_channels.channel_count(),
+                config.audio_bit_depth.unwrap_or(AudioBitDepth::Float32),
+            )?;
+            if let Some(ref mixer) = self.audio_mixer {
+                mixer.set_sidecar_tap(Some(sidecar.tap()));
+            }
+            self.sidecar_wav = Some(sidecar);
+        }
+
         // Initialize encoder
+        // Continuing an append session records to a part file that is joined
+        // onto the earlier sitting when the encoder finishes
+        let append_to = config.append_session_id.as_ref()
+            .and_then(|_| append_part_path(&output_path))
+            .map(|part| (part, output_path.clone()));
+        // Shared by the adaptive quality governor and the encoder
+        self.quality_controls = config.adaptive_quality.enabled
+            .then(|| Arc::new(QualityControls::default()));
         let encoder_config = EncoderConfig {
-            output_path: output_path.to_string_lossy().to_string(),
+            output_path: append_to.as_ref()
+                .map_or(output_path.as_path(), |(part, _)| part.as_path())
+                .to_string_lossy()
+                .to_string(),
             width: config.output_width,
             height: config.output_height,
             frame_rate: config.frame_rate.unwrap_or(30),
             quality: config.video_quality,
-            audio_sample_rate: 48000,
-            audio_channels: 2,
+            audio_sample_rate: config.audio_sample_rate,
+            audio_channels: config.audio_channels.channel_count(),
+            binaural: config.audio_channels == AudioChannelLayout::Binaural,
+            hardware_acceleration: config.hardware_encoding,
+            output_format: config.output_format,
+            audio_codec: config.audio_codec,
+            audio_bitrate_kbps: config.audio_bitrate_kbps,
+            audio_bit_depth: config.audio_bit_depth,
+            normalize_lufs: config.normalize_lufs,
+            silence: config.silence.clone(),
+            mastering: config.mastering.clone(),
+            quality_controls: self.quality_controls.clone(),
+            advanced_quality: config.advanced_quality.clone(),
+            failover_dir: config.failover_dir.clone(),
+            fragmented: config.append_session_id.is_some(),
+            append_to: append_to.map(|(_, session)| session),
+            metadata: config.metadata.clone(),
+            audio_track: config.audio_track.with_default_title(
+                config.capture_mic,
+                config.capture_system_audio,
+                config.audio_channels,
+            ),
         };
 
-        self.encoder = Some(Encoder::new(encoder_config));
+        let encoder = Encoder::new(encoder_config);
+
+        // A sink left running by a failed start would keep its connection open
+        let hls_playlist = self
+            .start_sinks(&config, hls_dir.as_deref(), &encoder)
+            .inspect_err(|_| self.stop_sinks())?;
+        self.encoder = Some(encoder);
+        self.connect_streams();
 
         // Store config
         self.config = Some(config);
 
         // Connect components and start capture
-        self.start_pipeline(output_path.clone())?;
+        self.start_pipeline(output_path.clone()).inspect_err(|_| self.stop_sinks())?;
+
+        // Monitoring is optional; a missing output device must not stop the recording
+        let monitor_config = self.config.as_ref()
+            .filter(|c| c.capture_mic)
+            .and_then(|c| c.mic_monitor.clone());
+        if let Some(monitor_config) = monitor_config {
+            if let Err(e) = self.set_mic_monitor(Some(monitor_config)) {
+                eprintln!("Mic monitoring unavailable: {}", e);
+            }
+        }
+
+        let visualizer_config = self.config.as_ref().and_then(|c| c.audio_visualizer.clone());
+        if let Some(visualizer_config) = visualizer_config {
+            if let Err(e) = self.set_audio_visualizer(Some(visualizer_config)) {
+                eprintln!("Audio visualizer unavailable: {}", e);
+            }
+        }
 
         // Update status
         {
@@ -176,19 +834,28 @@ impl ExternalRecorder {
             status.is_recording = true;
             status.duration_ms = 0;
             status.frame_count = 0;
-            status.output_path = Some(output_path);
+            status.output_path = Some(output_path.clone());
             status.error = None;
+            status.events.clear();
         }
 
         self.start_time = Some(Instant::now());
 
+        if let Err(e) = AppSettings::update(|s| s.last_devices.microphone = used_microphone) {
+            eprintln!("Failed to save last used microphone: {}", e);
+        }
+
         println!("External recorder started");
 
-        Ok(())
+        Ok(StartRecordingResult {
+            output_path: Some(output_path),
+            fallback_applied,
+            hls_playlist,
+        })
     }
 
     /// Start the recording pipeline
-    fn start_pipeline(&mut self, _output_path: PathBuf) -> Result<(), String> {
+    fn start_pipeline(&mut self, _output_path: PathBuf) -> Result<(), RecordingError> {
         // Get receivers from audio capture components
         let mic_receiver = self.mic_capture.as_mut().and_then(|c| c.take_receiver());
 
@@ -216,10 +883,94 @@ impl ExternalRecorder {
         // Create channel for video frames from frontend
         // Buffer size: 120 frames = ~4 seconds at 30fps
         let (frame_sender, frame_receiver) = bounded::<CompositeFrame>(120);
-        self.frame_sender = Some(frame_sender);
+        let frame_counters = Arc::new(FrameCounters::default());
+        let area_receiver = match self.area_capture {
+            Some(ref mut capture) => Some(
+                capture.take_receiver().ok_or("Window area capture receiver not available")?,
+            ),
+            None => None,
+        };
+
+        // Frames are counted at the size they are captured or sent at
+        let config = self.config.as_ref().ok_or("No recording configuration")?;
+        let mut queue_memory = QueueMemory::new(config.queue_memory);
+        if let (Some(receiver), Some(capture)) = (&area_receiver, &self.area_capture) {
+            let (width, height) = capture.dimensions();
+            queue_memory.watch(receiver.clone(), frame_bytes(width, height));
+        }
+        queue_memory.watch(frame_receiver.clone(), frame_bytes(config.output_width, config.output_height));
+        if let Some(ref receiver) = mixed_audio_receiver {
+            let chunk_samples = config.audio_buffer.mixer_chunk_frames as u64
+                * config.audio_channels.channel_count() as u64;
+            queue_memory.watch(receiver.clone(), chunk_samples * 4);
+        }
+        let queue_memory = Arc::new(queue_memory);
+        self.queue_memory = Some(queue_memory.clone());
+
+        let source_queue = if let Some(screen_receiver) = area_receiver {
+            // Captured webview frames are cropped and scaled on their own thread
+            let area = self.window_area.clone().ok_or("No window area")?;
+            let config = self.config.as_ref().ok_or("No recording configuration")?;
+            let compositor = VideoCompositor::new(CompositorConfig {
+                output_width: config.output_width,
+                output_height: config.output_height,
+                ..CompositorConfig::default()
+            });
+            let source_queue = channel_probe(screen_receiver.clone());
+            let stop_signal = self.stop_signal.clone();
+            let frame_count = self.frame_count.clone();
+            let frames = frame_counters.clone();
+            let monitor_feed = self.monitor_feed.clone();
+            self.area_thread = Some(std::thread::spawn(move || {
+                thread_priority::elevate(ThreadRole::Compositor);
+                window_area_loop(
+                    stop_signal,
+                    screen_receiver,
+                    area,
+                    compositor,
+                    frame_sender,
+                    frame_count,
+                    frames,
+                    monitor_feed,
+                    queue_memory,
+                );
+            }));
+            source_queue
+        } else {
+            // Frames are decoded (and decompressed) on workers before reaching the encoder
+            let compression = self.config.as_ref().map(|c| c.frame_compression).unwrap_or_default();
+            let rejections = self.frame_guard.as_ref()
+                .ok_or("No recording configuration")?
+                .rejection_counter();
+            let ingest = FrameIngest::start(
+                compression,
+                frame_sender,
+                self.frame_count.clone(),
+                rejections,
+                frame_counters.clone(),
+                self.monitor_feed.clone(),
+                queue_memory,
+            );
+            let source_queue = ingest.queue_probe();
+            self.ingest = Some(ingest);
+            source_queue
+        };
+        self.pipeline = Some(PipelineProbe::new(
+            frame_counters,
+            Some(source_queue),
+            channel_probe(frame_receiver.clone()),
+        ));
+
+        // The encoder's input queues, watched through receiver clones
+        let mut watched_queues = vec![(WatchedQueue::Composite, channel_probe(frame_receiver.clone()))];
+        if let Some(ref receiver) = mixed_audio_receiver {
+            watched_queues.push((WatchedQueue::Audio, channel_probe(receiver.clone())));
+        }
+        let governor_queue = channel_probe(frame_receiver.clone());
 
         // Create channel for encoder errors
         let (error_sender, error_receiver) = bounded::<String>(1);
+        let (failover_sender, failover_receiver) = bounded::<OutputFailover>(1);
 
         // Connect encoder
         if let Some(ref mut encoder) = self.encoder {
@@ -228,12 +979,18 @@ impl ExternalRecorder {
                 encoder.set_audio_receiver(receiver);
             }
             encoder.set_error_sender(error_sender);
+            if let Some(ref app) = self.app_handle {
+                encoder.set_finished_sink(output_check::app_sink(app.clone()));
+            }
+            encoder.set_failover_sender(failover_sender);
         }
         self.encoder_error_receiver = Some(error_receiver);
+        self.encoder_failover_receiver = Some(failover_receiver);
 
         // Start audio components
         if let Some(ref capture) = self.mic_capture {
-            capture.start()?;
+            capture.start()
+                .map_err(|e| RecordingError::Device(format!("Failed to start microphone: {}", e)))?;
         }
 
         if let Some(ref capture) = self.system_audio_capture {
@@ -245,104 +1002,141 @@ impl ExternalRecorder {
         }
 
         if let Some(ref encoder) = self.encoder {
-            encoder.start()?;
+            encoder.start().map_err(RecordingError::Encoder)?;
+        }
+
+        if let Some(ref app) = self.app_handle {
+            let config = self.config.as_ref().map(|c| c.queue_watermarks).unwrap_or_default();
+            self.queue_watch = Some(QueueWatch::start(config, watched_queues, app_sink(app.clone())));
+        }
+
+        // Frames arrive at the size the frontend (or window area capture) makes
+        // them, so only CRF and frame rate are stepped
+        if let Some(controls) = self.quality_controls.clone() {
+            let config = self.config.as_ref().map(|c| c.adaptive_quality).unwrap_or_default();
+            let sink = match self.app_handle {
+                Some(ref app) => quality_governor::app_sink(app.clone()),
+                None => Box::new(|_| {}),
+            };
+            self.quality_governor = Some(QualityGovernor::start(
+                config,
+                vec![QualityStep::HigherCrf, QualityStep::HalfFrameRate],
+                controls,
+                governor_queue,
+                sink,
+            ));
+        }
+
+        if let Some(ref capture) = self.area_capture {
+            capture.start()
+                .map_err(|e| screen_capture_error("Failed to start window area capture", e))?;
         }
 
         Ok(())
     }
 
     /// Receive a video frame from the frontend
+    ///
+    /// `timestamp_ms` is the capture time since the recording started and
+    /// sets the frame's position in the output.
     pub fn receive_frame(
         &mut self,
         data: Vec<u8>,
         width: u32,
         height: u32,
         timestamp_ms: u64,
-    ) -> Result<(), String> {
+    ) -> Result<(), RecordingError> {
         // Check if recording
         if !self.status.lock().is_recording {
-            return Err("Not recording".to_string());
+            return Err(RecordingError::NotRecording);
         }
 
-        // Log first frame
-        let count = *self.frame_count.lock();
-        if count == 0 {
-            println!("Received first frame: {}x{}, {} bytes, timestamp: {}ms", 
-                width, height, data.len(), timestamp_ms);
-        }
+        // Validate size, dimensions and rate (RGBA = 4 bytes per pixel)
+        let guard = self.frame_guard.as_mut().ok_or("No recording configuration")?;
+        guard.check_frame(data.len(), width, height, Instant::now())?;
 
-        // Validate frame dimensions
-        let config = self.config.as_ref().ok_or("No recording configuration")?;
-        if width != config.output_width || height != config.output_height {
-            return Err(format!(
-                "Frame dimensions {}x{} don't match config {}x{}",
-                width, height, config.output_width, config.output_height
-            ));
+        let ingest = self.ingest.as_mut().ok_or("This recording captures its window area natively and doesn't accept frames")?;
+        ingest.submit(FramePayload::Rgba(data), width, height, timestamp_ms).map_err(Into::into)
+    }
+
+    /// Receive a base64-encoded RGBA frame (optionally compressed)
+    ///
+    /// Only the payload length and rate are checked here; decoding and
+    /// decompression happen on the ingest workers, outside the recorder lock.
+    pub fn receive_frame_base64(
+        &mut self,
+        data_base64: String,
+        width: u32,
+        height: u32,
+        timestamp_ms: u64,
+    ) -> Result<(), RecordingError> {
+        if !self.status.lock().is_recording {
+            return Err(RecordingError::NotRecording);
         }
 
-        // Validate data size (RGBA = 4 bytes per pixel)
-        let expected_size = (width * height * 4) as usize;
-        if data.len() != expected_size {
-            return Err(format!(
-                "Frame data size {} doesn't match expected {} ({}x{}x4)",
-                data.len(),
-                expected_size,
-                width,
-                height
-            ));
+        let compressed = self.config.as_ref()
+            .is_some_and(|c| c.frame_compression != FrameCompression::None);
+        let guard = self.frame_guard.as_mut().ok_or("No recording configuration")?;
+        if compressed {
+            guard.check_compressed_base64(data_base64.len(), width, height)?;
+        } else {
+            guard.check_base64(data_base64.len(), width, height)?;
         }
+        guard.admit(Instant::now())?;
 
-        // Create composite frame
-        let frame = CompositeFrame {
-            data,
-            width,
-            height,
-            timestamp: Duration::from_millis(timestamp_ms),
-            is_bgra: false, // Frontend sends RGBA
-        };
+        let ingest = self.ingest.as_mut().ok_or("This recording captures its window area natively and doesn't accept frames")?;
+        ingest.submit(FramePayload::Base64(data_base64), width, height, timestamp_ms).map_err(Into::into)
+    }
 
-        // Send to encoder
-        if let Some(ref sender) = self.frame_sender {
-            match sender.try_send(frame) {
-                Ok(()) => {
-                    let mut count = self.frame_count.lock();
-                    *count += 1;
-                    
-                    // Update status periodically
-                    if *count % 30 == 0 {
-                        let mut status = self.status.lock();
-                        status.frame_count = *count;
-                        if let Some(start) = self.start_time {
-                            status.duration_ms = start.elapsed().as_millis() as u64;
-                        }
-                    }
-                    
-                    Ok(())
-                }
-                Err(crossbeam_channel::TrySendError::Full(_)) => {
-                    // Queue is full, skip this frame (backpressure)
-                    Ok(())
-                }
-                Err(crossbeam_channel::TrySendError::Disconnected(_)) => {
-                    Err("Encoder disconnected".to_string())
-                }
-            }
-        } else {
-            Err("Frame sender not initialized".to_string())
+    /// Receive a frame as raw bytes - RGBA, or compressed with the
+    /// recording's frame compression - without base64 in between
+    pub fn receive_frame_bytes(
+        &mut self,
+        data: Vec<u8>,
+        width: u32,
+        height: u32,
+        timestamp_ms: u64,
+    ) -> Result<(), RecordingError> {
+        let compressed = self.config.as_ref()
+            .is_some_and(|c| c.frame_compression != FrameCompression::None);
+        if !compressed {
+            return self.receive_frame(data, width, height, timestamp_ms);
+        }
+        if !self.status.lock().is_recording {
+            return Err(RecordingError::NotRecording);
         }
+
+        let guard = self.frame_guard.as_mut().ok_or("No recording configuration")?;
+        guard.check_compressed(data.len(), width, height)?;
+        guard.admit(Instant::now())?;
+
+        let ingest = self.ingest.as_mut().ok_or("This recording captures its window area natively and doesn't accept frames")?;
+        ingest.submit(FramePayload::Compressed(data), width, height, timestamp_ms).map_err(Into::into)
     }
 
     /// Stop recording
-    pub fn stop(&mut self) -> Result<String, String> {
+    pub fn stop(&mut self) -> Result<String, RecordingError> {
         if !self.status.lock().is_recording {
-            return Err("No recording in progress".to_string());
+            // A scheduled stop may already have finished the file
+            return self.completed_output.take()
+                .ok_or(RecordingError::NotRecording);
         }
 
         // Signal stop
         *self.stop_signal.lock() = true;
 
-        // Close frame sender to signal encoder
-        self.frame_sender = None;
+        // Frames already queued are decoded and passed on; the workers'
+        // exit closes the encoder's frame channel
+        if let Some(ingest) = self.ingest.take() {
+            ingest.finish();
+        }
+
+        if let Some(ref capture) = self.area_capture {
+            capture.stop();
+        }
+        if let Some(thread) = self.area_thread.take() {
+            let _ = thread.join();
+        }
 
         // Stop all components
         if let Some(ref capture) = self.mic_capture {
@@ -353,16 +1147,44 @@ impl ExternalRecorder {
             capture.stop();
         }
 
+        if let Some(monitor) = self.mic_monitor.take() {
+            monitor.stop();
+        }
+
+        if let Some(visualizer) = self.visualizer.take() {
+            visualizer.stop();
+        }
+
+        // Mixes what the captures left in the rings, then closes the
+        // encoder's audio channel
         if let Some(ref mixer) = self.audio_mixer {
             mixer.stop();
         }
 
-        if let Some(ref encoder) = self.encoder {
-            let _ = encoder.stop();
+        if let Some(captions) = self.captions.take() {
+            captions.stop();
         }
 
-        // Wait a moment for threads to finish
-        std::thread::sleep(Duration::from_millis(500));
+        if let Some(sidecar) = self.sidecar_wav.take() {
+            sidecar.stop();
+        }
+
+        if let Some(watch) = self.queue_watch.take() {
+            watch.stop();
+        }
+
+        if let Some(governor) = self.quality_governor.take() {
+            governor.stop();
+        }
+
+        self.stop_sinks();
+
+        // The encoder drains both channels and acknowledges the flush
+        let flushed = match self.encoder {
+            Some(ref encoder) => encoder.finish(),
+            None => Ok(()),
+        };
+        self.output_verification = self.encoder.as_ref().and_then(|e| e.verification());
 
         // Get output path before clearing
         let output_path = self.status.lock().output_path.clone();
@@ -375,18 +1197,30 @@ impl ExternalRecorder {
 
         // Clear components
         self.config = None;
+        self.area_capture = None;
+        self.window_area = None;
         self.mic_capture = None;
         self.system_audio_capture = None;
         self.audio_mixer = None;
         self.encoder = None;
         self.encoder_error_receiver = None;
+        self.encoder_failover_receiver = None;
+        self.pipeline = None;
+        self.queue_memory = None;
+        self.scheduled_stop_ms = None;
         self.start_time = None;
 
         println!("External recorder stopped");
 
+        flushed.map_err(RecordingError::Encoder)?;
         output_path
             .map(|p| p.to_string_lossy().to_string())
-            .ok_or_else(|| "No output path".to_string())
+            .ok_or_else(|| RecordingError::Failed("No output path".to_string()))
+    }
+
+    /// Take how the file `stop` finished checked out when read back
+    pub fn take_output_verification(&mut self) -> Option<OutputVerification> {
+        self.output_verification.take()
     }
 
     /// Check if recording is in progress
@@ -399,6 +1233,59 @@ impl ExternalRecorder {
         *self.frame_count.lock()
     }
 
+    /// Log a switch to the failover drive and point the status at the new file
+    fn handle_encoder_failover(&mut self) {
+        let Some(failover) = self
+            .encoder_failover_receiver
+            .as_ref()
+            .and_then(|r| r.try_recv().ok())
+        else {
+            return;
+        };
+
+        let mut status = self.status.lock();
+        status.events.push(RecordingEvent {
+            at_ms: failover.at_ms,
+            message: format!(
+                "Writing to {} failed ({}); continuing in {}",
+                failover.failed_path, failover.reason, failover.path
+            ),
+        });
+        status.output_path = Some(PathBuf::from(failover.path));
+    }
+
+    /// Note streams that ended by themselves; the recording carries on
+    fn handle_stream_errors(&mut self) {
+        // Drops and reconnects the stream rode out
+        let mut messages = self.streaming.as_ref().map_or_else(Vec::new, |s| s.notices());
+        if let Some(error) = self.streaming.as_ref().and_then(|s| s.error()) {
+            self.stop_streaming();
+            messages.push(format!("Streaming stopped: {}", error));
+        }
+        if let Some(error) = self.hls.as_ref().and_then(|s| s.error()) {
+            self.stop_hls();
+            messages.push(format!("HLS output stopped: {}", error));
+        }
+        if let Some(error) = self.icecast.as_ref().and_then(|s| s.error()) {
+            self.stop_icecast();
+            messages.push(format!("Icecast stream stopped: {}", error));
+        }
+        if let Some(error) = self.webrtc.as_ref().and_then(|(s, _)| s.error()) {
+            self.stop_webrtc();
+            messages.push(format!("Remote monitoring stopped: {}", error));
+        }
+        if let Some(error) = self.ndi.as_ref().and_then(|s| s.error()) {
+            self.stop_ndi();
+            messages.push(format!("NDI output stopped: {}", error));
+        }
+
+        let at_ms = self.start_time.map_or(0, |start| start.elapsed().as_millis() as u64);
+        let mut status = self.status.lock();
+        for message in messages {
+            status.events.push(RecordingEvent { at_ms, message });
+        }
+    }
+
     fn handle_encoder_errors(&mut self) {
         let error_message = match self.encoder_error_receiver.as_ref() {
             Some(receiver) => receiver.try_recv().ok(),
@@ -410,6 +1297,20 @@ impl ExternalRecorder {
         }
     }
 
+    /// Finish the recording once the encoder has reached a scheduled stop
+    fn handle_scheduled_stop(&mut self) {
+        if self.scheduled_stop_ms.is_none() {
+            return;
+        }
+        let finished = self.encoder.as_ref().is_some_and(|e| !e.is_running());
+        if finished {
+            match self.stop() {
+                Ok(path) => self.completed_output = Some(path),
+                Err(e) => eprintln!("Failed to finish scheduled stop: {}", e),
+            }
+        }
+    }
+
     fn handle_encoder_failure(&mut self, message: String) {
         eprintln!("Encoder failure: {}", message);
         let _ = self.stop();
@@ -419,6 +1320,70 @@ impl ExternalRecorder {
     }
 }
 
+/// Crop captured frames to where the webview is now and scale them to the
+/// output size
+#[allow(clippy::too_many_arguments)]
+fn window_area_loop(
+    stop_signal: Arc<Mutex<bool>>,
+    receiver: Receiver<ScreenFrame>,
+    area: WindowArea,
+    compositor: VideoCompositor,
+    sender: Sender<CompositeFrame>,
+    frame_count: Arc<Mutex<u64>>,
+    frames: Arc<FrameCounters>,
+    monitor_feed: MonitorFeed,
+    queue_memory: Arc<QueueMemory>,
+) {
+    let Ok(mut region) = area.region() else {
+        eprintln!("Window area capture lost the window");
+        return;
+    };
+    println!("Window area capture loop started ({:?})", region);
+    // Cropped frames are packed, so the compositor passes them on as they are
+    let pool = FramePool::new();
+
+    while !*stop_signal.lock() {
+        let mut frame = match receiver.recv_timeout(Duration::from_millis(100)) {
+            Ok(frame) => frame,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+
+        // Only the latest frame matters if capture got ahead of us
+        frames.captured();
+        for newer in receiver.try_iter() {
+            frames.captured();
+            frames.skipped();
+            frame = newer;
+        }
+
+        // Past the queue memory cap frames are dropped until the encoder catches up
+        if queue_memory.over_cap() {
+            frames.dropped();
+            continue;
+        }
+
+        // Follow the window; the capture stays on the display it started on,
+        // so on another display the last area on this one is kept
+        match area.region() {
+            Ok(current) if current.display_index == region.display_index => region = current,
+            Ok(_) => {}
+            Err(e) => eprintln!("Failed to locate the window: {}", e),
+        }
+
+        let composite = compositor.composite(&frame.crop_bgra(&region, &pool), None);
+        monitor_feed.offer(&composite);
+        match sender.try_send(composite) {
+            Ok(()) => *frame_count.lock() += 1,
+            // Encoder queue is full, skip this frame (backpressure)
+            Err(TrySendError::Full(_)) => frames.dropped(),
+            Err(TrySendError::Disconnected(_)) => break,
+        }
+    }
+
+    println!("Window area capture loop stopped");
+}
+
 impl Default for ExternalRecorder {
     fn default() -> Self {
         Self::new()
@@ -0,0 +1,58 @@
+//! Compressed Frame Transport
+//!
+//! Screen content is mostly static, so in WYSIWYG mode the frontend can
+//! compress RGBA frames with LZ4 or zstd before sending them over IPC, base64
+//! encoded or as raw bytes. Frames are decompressed on the ingest workers (see
+//! `frame_ingest`), so the receiving commands return as soon as the payload
+//! is queued.
+//!
+//! LZ4 payloads are raw LZ4 blocks (no frame header or size prefix); zstd
+//! payloads are standard zstd frames. Both must expand to exactly
+//! `width * height * 4` bytes.
+
+use serde::{Deserialize, Serialize};
+
+/// Compression applied by the frontend to external frames
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FrameCompression {
+    /// Plain RGBA
+    #[default]
+    None,
+    /// Raw LZ4 block
+    Lz4,
+    /// zstd frame
+    Zstd,
+}
+
+impl FrameCompression {
+    /// Decompress a frame that must expand to exactly `expected_len` bytes
+    pub fn decompress(&self, data: Vec<u8>, expected_len: usize) -> Result<Vec<u8>, String> {
+        let decompressed = match self {
+            FrameCompression::None => data,
+            FrameCompression::Lz4 => lz4_flex::block::decompress(&data, expected_len)
+                .map_err(|e| format!("LZ4 decompression failed: {}", e))?,
+            FrameCompression::Zstd => zstd::bulk::decompress(&data, expected_len)
+                .map_err(|e| format!("zstd decompression failed: {}", e))?,
+        };
+        Ok(decompressed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decompress_round_trip() {
+        let frame: Vec<u8> = (0..64u8).cycle().take(4 * 4 * 4).collect();
+
+        let lz4 = lz4_flex::block::compress(&frame);
+        assert_eq!(FrameCompression::Lz4.decompress(lz4, frame.len()).unwrap(), frame);
+
+        let zstd = zstd::bulk::compress(&frame, 1).unwrap();
+        assert_eq!(FrameCompression::Zstd.decompress(zstd, frame.len()).unwrap(), frame);
+
+        assert!(FrameCompression::Lz4.decompress(vec![0xff; 8], frame.len()).is_err());
+    }
+}
@@ -0,0 +1,214 @@
+//! External Frame Validation
+//!
+//! Guards the `receive_video_frame` commands against malformed or runaway
+//! input from the frontend. Payload sizes and dimensions are checked before
+//! anything is decoded or queued, frames arriving faster than the recording
+//! can use them are refused, and every rejection is counted for the status.
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Largest accepted frame width or height (8K UHD)
+pub const MAX_FRAME_DIMENSION: u32 = 7680;
+
+/// Largest accepted decoded frame payload (8K UHD RGBA)
+pub const MAX_FRAME_BYTES: usize = 7680 * 4320 * 4;
+
+/// Window over which incoming frames are counted
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Frames accepted per window, as a multiple of the recording frame rate
+const RATE_LIMIT_FACTOR: u32 = 2;
+
+/// Frames refused by the external recorder, by reason
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FrameRejections {
+    /// Payload larger than any valid frame
+    pub oversized: u64,
+    /// Zero, oversized or mismatched width/height
+    pub invalid_dimensions: u64,
+    /// Payload length doesn't match width x height x 4
+    pub size_mismatch: u64,
+    /// Base64 or compressed payload that failed to decode
+    pub invalid_encoding: u64,
+    /// Frames over the rate limit
+    pub rate_limited: u64,
+}
+
+impl FrameRejections {
+    /// Total frames rejected
+    pub fn total(&self) -> u64 {
+        self.oversized + self.invalid_dimensions + self.size_mismatch + self.invalid_encoding + self.rate_limited
+    }
+}
+
+/// Validates frames for one recording and counts rejections
+pub struct FrameGuard {
+    width: u32,
+    height: u32,
+    max_frames_per_window: u32,
+    window_start: Option<Instant>,
+    window_frames: u32,
+    /// Shared with the ingest workers, which reject frames they can't decode
+    rejections: Arc<Mutex<FrameRejections>>,
+}
+
+impl FrameGuard {
+    /// Create a guard for frames of `width` x `height` at `frame_rate`
+    pub fn new(width: u32, height: u32, frame_rate: u32) -> Self {
+        Self {
+            width,
+            height,
+            max_frames_per_window: frame_rate.max(1) * RATE_LIMIT_FACTOR,
+            window_start: None,
+            window_frames: 0,
+            rejections: Arc::new(Mutex::new(FrameRejections::default())),
+        }
+    }
+
+    /// Check that dimensions are usable for a recording
+    pub fn validate_dimensions(width: u32, height: u32) -> Result<(), String> {
+        if width == 0 || height == 0 || width > MAX_FRAME_DIMENSION || height > MAX_FRAME_DIMENSION {
+            return Err(format!(
+                "Invalid frame dimensions {}x{} (must be 1-{} pixels per side)",
+                width, height, MAX_FRAME_DIMENSION
+            ));
+        }
+        if width as usize * height as usize * 4 > MAX_FRAME_BYTES {
+            return Err(format!("Frame dimensions {}x{} exceed the 8K pixel limit", width, height));
+        }
+        Ok(())
+    }
+
+    /// Check a base64 payload's length before it is queued for decoding
+    pub fn check_base64(&mut self, encoded_len: usize, width: u32, height: u32) -> Result<(), String> {
+        self.check_payload(encoded_len, MAX_FRAME_BYTES.div_ceil(3) * 4, width, height)?;
+
+        let expected = (width as usize * height as usize * 4).div_ceil(3) * 4;
+        if encoded_len != expected {
+            self.rejections.lock().size_mismatch += 1;
+            return Err(format!(
+                "Base64 frame length {} doesn't match expected {} for {}x{}",
+                encoded_len, expected, width, height
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check a base64 payload of compressed data before decoding it
+    ///
+    /// The decompressed size is verified later by the ingest workers.
+    pub fn check_compressed_base64(&mut self, encoded_len: usize, width: u32, height: u32) -> Result<(), String> {
+        self.check_payload(encoded_len, MAX_FRAME_BYTES.div_ceil(3) * 4, width, height)
+    }
+
+    /// Check a binary payload of compressed data before it is queued
+    ///
+    /// The decompressed size is verified later by the ingest workers.
+    pub fn check_compressed(&mut self, data_len: usize, width: u32, height: u32) -> Result<(), String> {
+        self.check_payload(data_len, MAX_FRAME_BYTES, width, height)
+    }
+
+    /// Check a decoded RGBA frame and count it against the rate limit
+    pub fn check_frame(&mut self, data_len: usize, width: u32, height: u32, now: Instant) -> Result<(), String> {
+        self.check_payload(data_len, MAX_FRAME_BYTES, width, height)?;
+
+        let expected = width as usize * height as usize * 4;
+        if data_len != expected {
+            self.rejections.lock().size_mismatch += 1;
+            return Err(format!(
+                "Frame data size {} doesn't match expected {} ({}x{}x4)",
+                data_len, expected, width, height
+            ));
+        }
+
+        self.admit(now)
+    }
+
+    /// Count a well-formed frame against the rate limit
+    pub fn admit(&mut self, now: Instant) -> Result<(), String> {
+        match self.window_start {
+            Some(start) if now.duration_since(start) < RATE_WINDOW => {}
+            _ => {
+                self.window_start = Some(now);
+                self.window_frames = 0;
+            }
+        }
+        if self.window_frames >= self.max_frames_per_window {
+            self.rejections.lock().rate_limited += 1;
+            return Err(format!(
+                "Frame rate limit exceeded ({} frames per second)",
+                self.max_frames_per_window
+            ));
+        }
+        self.window_frames += 1;
+
+        Ok(())
+    }
+
+    /// Rejection counts so far
+    pub fn rejections(&self) -> FrameRejections {
+        self.rejections.lock().clone()
+    }
+
+    /// Shared counters for rejections made outside the guard
+    pub fn rejection_counter(&self) -> Arc<Mutex<FrameRejections>> {
+        self.rejections.clone()
+    }
+
+    fn check_payload(&mut self, len: usize, max_len: usize, width: u32, height: u32) -> Result<(), String> {
+        if len > max_len {
+            self.rejections.lock().oversized += 1;
+            return Err(format!("Frame payload of {} bytes exceeds the size limit", len));
+        }
+        self.check_dimensions(width, height)
+    }
+
+    fn check_dimensions(&mut self, width: u32, height: u32) -> Result<(), String> {
+        if width != self.width || height != self.height {
+            self.rejections.lock().invalid_dimensions += 1;
+            return Err(format!(
+                "Frame dimensions {}x{} don't match config {}x{}",
+                width, height, self.width, self.height
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_guard_rejects_and_counts() {
+        let mut guard = FrameGuard::new(4, 2, 1);
+        let now = Instant::now();
+
+        assert!(guard.check_frame(MAX_FRAME_BYTES + 1, 4, 2, now).is_err());
+        assert!(guard.check_frame(32, 8, 1, now).is_err());
+        assert!(guard.check_frame(31, 4, 2, now).is_err());
+        assert!(guard.check_base64(40, 4, 2).is_err());
+        assert!(guard.check_base64(44, 4, 2).is_ok());
+
+        // Two frames per second at 1fps, then the window resets
+        assert!(guard.check_frame(32, 4, 2, now).is_ok());
+        assert!(guard.check_frame(32, 4, 2, now).is_ok());
+        assert!(guard.check_frame(32, 4, 2, now).is_err());
+        assert!(guard.check_frame(32, 4, 2, now + RATE_WINDOW).is_ok());
+
+        let rejections = guard.rejections();
+        assert_eq!(rejections.oversized, 1);
+        assert_eq!(rejections.invalid_dimensions, 1);
+        assert_eq!(rejections.size_mismatch, 2);
+        assert_eq!(rejections.rate_limited, 1);
+        assert_eq!(rejections.total(), 5);
+
+        assert!(FrameGuard::validate_dimensions(0, 1080).is_err());
+        assert!(FrameGuard::validate_dimensions(7680, 7680).is_err());
+        assert!(FrameGuard::validate_dimensions(1920, 1080).is_ok());
+    }
+}
@@ -0,0 +1,365 @@
+//! External Frame Ingest
+//!
+//! Base64 decoding, decompression and size validation of frontend frames run
+//! on a small worker pool, so the recorder lock is only held for the cheap
+//! length and rate checks. Frames are numbered when submitted and put back in
+//! order before they reach the encoder, which timestamps frames by arrival.
+//!
+//! `receive_video_frame_raw` skips base64 altogether: the frame is the raw
+//! IPC request body (an `ArrayBuffer` or `Uint8Array` passed to `invoke`),
+//! and its size and time travel in `X-Frame-*` headers.
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use crossbeam_channel::{bounded, Receiver, Sender, TrySendError};
+use parking_lot::Mutex;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::compositor::{CompositeFrame, FrameFormat};
+use crate::frame_compression::FrameCompression;
+use crate::frame_guard::FrameRejections;
+use crate::frame_pool::FrameBuffer;
+use crate::monitor_feed::MonitorFeed;
+use crate::pipeline_stats::FrameCounters;
+use crate::queue_memory::QueueMemory;
+use crate::queue_watch::{channel_probe, QueueProbe};
+
+/// Upper bound on decode workers
+const MAX_WORKERS: usize = 4;
+
+/// Frame data as received by a command
+pub enum FramePayload {
+    /// Raw RGBA bytes
+    Rgba(Vec<u8>),
+    /// Base64 text, compressed with the recording's frame compression
+    Base64(String),
+    /// Binary data, compressed with the recording's frame compression
+    Compressed(Vec<u8>),
+}
+
+/// Request headers carrying a raw frame's width, height and capture time
+pub const FRAME_WIDTH_HEADER: &str = "x-frame-width";
+pub const FRAME_HEIGHT_HEADER: &str = "x-frame-height";
+pub const FRAME_TIMESTAMP_HEADER: &str = "x-frame-timestamp-ms";
+
+/// Width, height and timestamp (ms) of a raw frame from its request headers
+pub fn raw_frame_headers(headers: &tauri::http::HeaderMap) -> Result<(u32, u32, u64), String> {
+    fn number<T: std::str::FromStr>(headers: &tauri::http::HeaderMap, name: &str) -> Result<T, String> {
+        headers
+            .get(name)
+            .ok_or_else(|| format!("Missing {} header", name))?
+            .to_str()
+            .ok()
+            .and_then(|value| value.trim().parse().ok())
+            .ok_or_else(|| format!("Invalid {} header", name))
+    }
+    Ok((
+        number(headers, FRAME_WIDTH_HEADER)?,
+        number(headers, FRAME_HEIGHT_HEADER)?,
+        number(headers, FRAME_TIMESTAMP_HEADER)?,
+    ))
+}
+
+struct IngestJob {
+    seq: u64,
+    payload: FramePayload,
+    width: u32,
+    height: u32,
+    timestamp_ms: u64,
+}
+
+/// Decode result for one job (None if the frame was rejected)
+type Decoded = (u64, Option<CompositeFrame>);
+
+/// Worker pool feeding the encoder's frame channel
+pub struct FrameIngest {
+    sender: Sender<IngestJob>,
+    /// Never read; only for the queue depth
+    queue: Receiver<IngestJob>,
+    frames: Arc<FrameCounters>,
+    queue_memory: Arc<QueueMemory>,
+    next_seq: u64,
+    /// Timestamp of the newest frame submitted
+    latest_ms: Option<u64>,
+    /// Decode workers and the reorder thread
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl FrameIngest {
+    /// Start the workers; they exit once the ingest is dropped and drained
+    pub fn start(
+        compression: FrameCompression,
+        output: Sender<CompositeFrame>,
+        frame_count: Arc<Mutex<u64>>,
+        rejections: Arc<Mutex<FrameRejections>>,
+        frames: Arc<FrameCounters>,
+        monitor_feed: MonitorFeed,
+        queue_memory: Arc<QueueMemory>,
+    ) -> Self {
+        let workers = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+            .clamp(1, MAX_WORKERS);
+
+        // Small queues: frames waiting here are already late
+        let (sender, receiver) = bounded::<IngestJob>(workers * 2);
+        let (decoded_sender, decoded_receiver) = bounded::<Decoded>(workers * 4);
+
+        let mut threads: Vec<JoinHandle<()>> = (0..workers)
+            .map(|_| {
+                let receiver = receiver.clone();
+                let decoded_sender = decoded_sender.clone();
+                let rejections = rejections.clone();
+                std::thread::spawn(move || {
+                    decode_loop(compression, receiver, decoded_sender, rejections);
+                })
+            })
+            .collect();
+        drop(decoded_sender);
+
+        let reorder_frames = frames.clone();
+        threads.push(std::thread::spawn(move || {
+            reorder_loop(decoded_receiver, output, frame_count, reorder_frames, monitor_feed);
+        }));
+
+        println!("Frame ingest started with {} workers", workers);
+
+        Self {
+            sender,
+            queue: receiver,
+            frames,
+            queue_memory,
+            next_seq: 0,
+            latest_ms: None,
+            threads,
+        }
+    }
+
+    /// Close the queue and wait until every queued frame has been decoded
+    /// and passed on; the output channel is closed when this returns
+    pub fn finish(self) {
+        let Self { sender, queue, threads, .. } = self;
+        drop(sender);
+        drop(queue);
+        for thread in threads {
+            let _ = thread.join();
+        }
+    }
+
+    /// Time into the recording of the newest frame submitted, which places
+    /// frames in the file (None before the first)
+    pub fn position_ms(&self) -> Option<u64> {
+        self.latest_ms
+    }
+
+    /// Depth of the queue in front of the decode workers
+    pub fn queue_probe(&self) -> QueueProbe {
+        channel_probe(self.queue.clone())
+    }
+
+    /// Queue a frame, dropping it if the workers are behind (backpressure) or
+    /// the queues are at their memory cap
+    pub fn submit(
+        &mut self,
+        payload: FramePayload,
+        width: u32,
+        height: u32,
+        timestamp_ms: u64,
+    ) -> Result<(), String> {
+        if self.next_seq == 0 {
+            let len = match payload {
+                FramePayload::Rgba(ref data) | FramePayload::Compressed(ref data) => data.len(),
+                FramePayload::Base64(ref text) => text.len(),
+            };
+            println!("Received first frame: {}x{}, {} bytes, timestamp: {}ms",
+                width, height, len, timestamp_ms);
+        }
+
+        self.latest_ms = Some(self.latest_ms.map_or(timestamp_ms, |latest| latest.max(timestamp_ms)));
+        self.frames.captured();
+        if self.queue_memory.over_cap() {
+            self.frames.dropped();
+            return Ok(());
+        }
+        let job = IngestJob {
+            seq: self.next_seq,
+            payload,
+            width,
+            height,
+            timestamp_ms,
+        };
+        match self.sender.try_send(job) {
+            Ok(()) => {
+                self.next_seq += 1;
+                Ok(())
+            }
+            Err(TrySendError::Full(_)) => {
+                self.frames.dropped();
+                Ok(())
+            }
+            Err(TrySendError::Disconnected(_)) => Err("Frame ingest stopped".to_string()),
+        }
+    }
+}
+
+fn decode_loop(
+    compression: FrameCompression,
+    receiver: Receiver<IngestJob>,
+    decoded_sender: Sender<Decoded>,
+    rejections: Arc<Mutex<FrameRejections>>,
+) {
+    for job in receiver {
+        let frame = decode_frame(job.payload, job.width, job.height, compression, &rejections)
+            .map(|data| CompositeFrame {
+                data: FrameBuffer::shared(data),
+                width: job.width,
+                height: job.height,
+                timestamp: Duration::from_millis(job.timestamp_ms),
+                format: FrameFormat::Rgba, // Frontend sends RGBA
+            });
+
+        // Rejected frames are still reported so the reorder stage can skip them
+        if decoded_sender.send((job.seq, frame)).is_err() {
+            break;
+        }
+    }
+}
+
+/// Decode and decompress a payload into exactly `width * height * 4` RGBA bytes
+fn decode_frame(
+    payload: FramePayload,
+    width: u32,
+    height: u32,
+    compression: FrameCompression,
+    rejections: &Mutex<FrameRejections>,
+) -> Option<Vec<u8>> {
+    let expected_len = width as usize * height as usize * 4;
+
+    let decoded = match payload {
+        FramePayload::Rgba(data) => Ok(data),
+        FramePayload::Base64(text) => STANDARD
+            .decode(text)
+            .map_err(|e| format!("Failed to decode base64: {}", e))
+            .and_then(|bytes| compression.decompress(bytes, expected_len)),
+        FramePayload::Compressed(bytes) => compression.decompress(bytes, expected_len),
+    };
+    let data = match decoded {
+        Ok(data) => data,
+        Err(e) => {
+            rejections.lock().invalid_encoding += 1;
+            eprintln!("{}", e);
+            return None;
+        }
+    };
+
+    if data.len() != expected_len {
+        rejections.lock().size_mismatch += 1;
+        eprintln!(
+            "Frame data size {} doesn't match expected {} ({}x{}x4)",
+            data.len(),
+            expected_len,
+            width,
+            height
+        );
+        return None;
+    }
+
+    Some(data)
+}
+
+/// Forward decoded frames to the encoder in submission order
+fn reorder_loop(
+    receiver: Receiver<Decoded>,
+    output: Sender<CompositeFrame>,
+    frame_count: Arc<Mutex<u64>>,
+    frames: Arc<FrameCounters>,
+    monitor_feed: MonitorFeed,
+) {
+    let mut pending: BTreeMap<u64, Option<CompositeFrame>> = BTreeMap::new();
+    let mut next_seq: u64 = 0;
+
+    for (seq, frame) in receiver {
+        pending.insert(seq, frame);
+
+        while let Some(frame) = pending.remove(&next_seq) {
+            next_seq += 1;
+            let Some(frame) = frame else {
+                continue;
+            };
+            monitor_feed.offer(&frame);
+            match output.try_send(frame) {
+                Ok(()) => *frame_count.lock() += 1,
+                // Encoder queue is full, skip this frame (backpressure)
+                Err(TrySendError::Full(_)) => frames.dropped(),
+                Err(TrySendError::Disconnected(_)) => return,
+            }
+        }
+    }
+
+    println!("Frame ingest stopped");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ingest_keeps_submission_order() {
+        let (output, frames) = bounded(64);
+        let frame_count = Arc::new(Mutex::new(0));
+        let rejections = Arc::new(Mutex::new(FrameRejections::default()));
+        let mut ingest = FrameIngest::start(
+            FrameCompression::None,
+            output,
+            frame_count.clone(),
+            rejections.clone(),
+            Arc::new(FrameCounters::default()),
+            MonitorFeed::default(),
+            Arc::new(QueueMemory::new(Default::default())),
+        );
+
+        for i in 0..12u8 {
+            let payload = if i == 5 {
+                FramePayload::Base64("not base64!".to_string())
+            } else {
+                FramePayload::Base64(STANDARD.encode([i; 8]))
+            };
+            while ingest.sender.is_full() {
+                std::thread::yield_now();
+            }
+            ingest.submit(payload, 2, 1, i as u64).unwrap();
+        }
+        ingest.finish();
+        assert_eq!(*frame_count.lock(), 11);
+
+        let order: Vec<u8> = frames.iter().map(|f| f.data[0]).collect();
+        let expected: Vec<u8> = (0..12).filter(|&i| i != 5).collect();
+        assert_eq!(order, expected);
+        assert_eq!(*frame_count.lock(), 11);
+        assert_eq!(rejections.lock().invalid_encoding, 1);
+    }
+
+    #[test]
+    fn test_raw_frames_read_headers_and_compressed_bytes() {
+        use tauri::http::{HeaderMap, HeaderValue};
+
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Frame-Width", HeaderValue::from_static("1920"));
+        headers.insert("X-Frame-Height", HeaderValue::from_static("1080"));
+        assert!(raw_frame_headers(&headers).unwrap_err().contains(FRAME_TIMESTAMP_HEADER));
+        headers.insert("X-Frame-Timestamp-Ms", HeaderValue::from_static("1500"));
+        assert_eq!(raw_frame_headers(&headers).unwrap(), (1920, 1080, 1500));
+        headers.insert("X-Frame-Width", HeaderValue::from_static("-4"));
+        assert!(raw_frame_headers(&headers).is_err());
+
+        let rejections = Mutex::new(FrameRejections::default());
+        let frame = vec![9u8; 2 * 2 * 4];
+        let lz4 = FramePayload::Compressed(lz4_flex::block::compress(&frame));
+        assert_eq!(decode_frame(lz4, 2, 2, FrameCompression::Lz4, &rejections), Some(frame));
+        let garbage = FramePayload::Compressed(vec![0xff; 8]);
+        assert_eq!(decode_frame(garbage, 2, 2, FrameCompression::Lz4, &rejections), None);
+        assert_eq!(rejections.lock().invalid_encoding, 1);
+    }
+}
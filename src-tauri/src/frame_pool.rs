@@ -0,0 +1,141 @@
+//! Frame Buffer Pool
+//!
+//! Frame pixels travel from screen capture through the compositor to the
+//! encoder (and the monitor feeds, streams and virtual camera) as
+//! `Arc<FrameBuffer>`: handing a frame on, or to several consumers, shares
+//! the buffer instead of copying it. Buffers are read-only once shared.
+//!
+//! Allocations come from a `FramePool` and go back to it when the last
+//! frame using them is dropped, so at 4K/60 the same few dozen megabytes are
+//! reused instead of allocated and freed for every frame.
+
+use parking_lot::Mutex;
+use std::ops::Deref;
+use std::sync::{Arc, Weak};
+
+/// Idle buffers a pool keeps; more are freed (a 4K BGRA frame is 33 MB)
+const MAX_IDLE_BUFFERS: usize = 4;
+
+type FreeList = Mutex<Vec<Vec<u8>>>;
+
+/// Recycles frame allocations between the stages of the pipeline
+#[derive(Clone, Default)]
+pub struct FramePool {
+    free: Arc<FreeList>,
+}
+
+impl FramePool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An empty vector with room for `capacity` bytes, reusing an idle
+    /// allocation when there is one
+    pub fn take(&self, capacity: usize) -> Vec<u8> {
+        let mut data = self.free.lock().pop().unwrap_or_default();
+        data.clear();
+        data.reserve(capacity);
+        data
+    }
+
+    /// Give an allocation that was never shared back to the pool
+    pub fn recycle(&self, data: Vec<u8>) {
+        recycle(&self.free, data);
+    }
+
+    /// Share `data` as a frame buffer that returns to this pool once the
+    /// last frame using it is dropped
+    pub fn share(&self, data: Vec<u8>) -> Arc<FrameBuffer> {
+        Arc::new(FrameBuffer {
+            data,
+            pool: Some(Arc::downgrade(&self.free)),
+        })
+    }
+
+    /// A shared copy of `data` in a pooled buffer
+    pub fn copy(&self, data: &[u8]) -> Arc<FrameBuffer> {
+        let mut buffer = self.take(data.len());
+        buffer.extend_from_slice(data);
+        self.share(buffer)
+    }
+
+    /// Idle allocations waiting to be reused
+    #[cfg(test)]
+    fn idle(&self) -> usize {
+        self.free.lock().len()
+    }
+}
+
+fn recycle(free: &FreeList, data: Vec<u8>) {
+    let mut free = free.lock();
+    if data.capacity() > 0 && free.len() < MAX_IDLE_BUFFERS {
+        free.push(data);
+    }
+}
+
+/// Pixel data of a frame, shared read-only between the stages using it
+pub struct FrameBuffer {
+    data: Vec<u8>,
+    /// Pool the allocation goes back to (None for buffers made outside one)
+    pool: Option<Weak<FreeList>>,
+}
+
+impl FrameBuffer {
+    /// Share `data` without a pool, for frames made outside the capture
+    /// pipeline (tests, the frontend, dump replays)
+    pub fn shared(data: Vec<u8>) -> Arc<Self> {
+        Arc::new(Self { data, pool: None })
+    }
+}
+
+impl Deref for FrameBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl Drop for FrameBuffer {
+    fn drop(&mut self) {
+        if let Some(free) = self.pool.as_ref().and_then(Weak::upgrade) {
+            recycle(&free, std::mem::take(&mut self.data));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buffers_return_to_pool_when_last_frame_drops() {
+        let pool = FramePool::new();
+        let frame = pool.copy(&[1, 2, 3, 4]);
+        let address = frame.as_ptr();
+        let consumer = Arc::clone(&frame);
+        assert_eq!(&consumer[..], &[1, 2, 3, 4]);
+
+        drop(frame);
+        assert_eq!(pool.idle(), 0);
+        drop(consumer);
+        assert_eq!(pool.idle(), 1);
+
+        // The next frame reuses the allocation
+        let reused = pool.take(4);
+        assert!(reused.is_empty());
+        assert_eq!(reused.as_ptr(), address);
+        pool.recycle(reused);
+
+        // Only a few idle buffers are kept
+        let frames: Vec<_> = (0..MAX_IDLE_BUFFERS + 2).map(|_| pool.copy(&[0; 16])).collect();
+        drop(frames);
+        assert_eq!(pool.idle(), MAX_IDLE_BUFFERS);
+
+        // Buffers outliving their pool are simply freed
+        let orphan = pool.copy(&[5]);
+        drop(pool);
+        drop(orphan);
+        assert_eq!(&FrameBuffer::shared(vec![7])[..], &[7]);
+    }
+}
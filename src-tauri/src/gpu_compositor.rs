@@ -0,0 +1,594 @@
+//! GPU Compositing
+//!
+//! Scales the screen frame and blends the webcam PiP with wgpu instead of
+//! resizing RGBA images on the CPU, which can't keep up at 4K. Screen frames
+//! are uploaded as captured (BGRA, or NV12 as separate Y and UV planes
+//! converted in the shader), the layers are drawn as quads onto an output
+//! texture, and the result is read back as RGBA for the encoder.
+//!
+//! Any GPU error makes the compositor fall back to the CPU path for the rest
+//! of the recording.
+
+use crossbeam_channel::bounded;
+
+use crate::compositor::{DrawSource, LayerDraw};
+use crate::recording::CapturePixelFormat;
+use crate::screen::ScreenFrame;
+use crate::webcam::WebcamFrame;
+
+const SHADER: &str = r#"
+struct Draw {
+    rect: vec4<f32>,
+    uv: vec4<f32>,
+    color: vec4<f32>,
+    opacity: f32,
+    mode: u32,
+    _pad: vec2<u32>,
+    // Width, height, corner radius and ring width in output pixels
+    mask: vec4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> draw: Draw;
+@group(0) @binding(1) var plane0: texture_2d<f32>;
+@group(0) @binding(2) var plane1: texture_2d<f32>;
+@group(0) @binding(3) var source_sampler: sampler;
+
+struct VertexOut {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) local: vec2<f32>,
+}
+
+// Quad as a 4-vertex triangle strip; `rect` is top-left and bottom-right in NDC
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOut {
+    let corner = vec2<f32>(f32(index & 1u), f32(index >> 1u));
+    var out: VertexOut;
+    out.position = vec4<f32>(mix(draw.rect.xy, draw.rect.zw, corner), 0.0, 1.0);
+    out.uv = mix(draw.uv.xy, draw.uv.zw, corner);
+    out.local = corner * draw.mask.xy;
+    return out;
+}
+
+// Coverage of `p` by a `size` box with rounded corners, as shape_coverage
+fn coverage(p: vec2<f32>, size: vec2<f32>, radius: f32) -> f32 {
+    let r = min(radius, min(size.x, size.y) / 2.0);
+    let d = abs(p - size / 2.0) - (size / 2.0 - vec2<f32>(r));
+    let distance = length(max(d, vec2<f32>(0.0))) + min(max(d.x, d.y), 0.0) - r;
+    return clamp(0.5 - distance, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(in: VertexOut) -> @location(0) vec4<f32> {
+    let sample0 = textureSample(plane0, source_sampler, in.uv);
+    let sample1 = textureSample(plane1, source_sampler, in.uv);
+
+    var rgb = sample0.rgb;
+    if draw.mode == 1u {
+        // Video-range BT.709, as in ScreenFrame::to_rgba
+        let luma = (sample0.r - 16.0 / 255.0) * 1.164;
+        let uv = sample1.rg - vec2<f32>(128.0 / 255.0);
+        rgb = vec3<f32>(
+            luma + 1.793 * uv.y,
+            luma - 0.213 * uv.x - 0.533 * uv.y,
+            luma + 2.112 * uv.x,
+        );
+    } else if draw.mode == 2u {
+        rgb = draw.color.rgb;
+    }
+    var alpha = coverage(in.local, draw.mask.xy, draw.mask.z);
+    let ring = draw.mask.w;
+    if ring > 0.0 {
+        let inner = draw.mask.xy - vec2<f32>(ring * 2.0);
+        alpha -= coverage(in.local - vec2<f32>(ring), inner, max(draw.mask.z - ring, 0.0))
+            * f32(all(in.local >= vec2<f32>(ring)) && all(in.local < draw.mask.xy - vec2<f32>(ring)));
+    }
+    return vec4<f32>(rgb, draw.opacity * alpha);
+}
+"#;
+
+/// Shader modes (`Draw.mode`)
+const MODE_TEXTURE: u32 = 0;
+const MODE_NV12: u32 = 1;
+const MODE_COLOR: u32 = 2;
+
+/// Size of the `Draw` uniform
+const UNIFORM_SIZE: u64 = 80;
+
+/// A source texture, recreated when the frame size or format changes
+struct Plane {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+}
+
+pub struct GpuCompositor {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    adapter_name: String,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    width: u32,
+    height: u32,
+    target: wgpu::Texture,
+    target_view: wgpu::TextureView,
+    readback: wgpu::Buffer,
+    padded_row_bytes: u32,
+    /// Bound as plane 1 when a source has only one plane
+    dummy: Plane,
+    screen: Option<Plane>,
+    screen_uv: Option<Plane>,
+    screen_mode: u32,
+    webcam: Option<Plane>,
+    /// One uniform buffer per draw of a frame
+    uniforms: Vec<wgpu::Buffer>,
+}
+
+impl GpuCompositor {
+    /// Open the default high-performance adapter and build the pipeline for
+    /// `width`x`height` output
+    pub fn new(width: u32, height: u32) -> Result<Self, String> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            force_fallback_adapter: false,
+            compatible_surface: None,
+        }))
+        .ok_or("No GPU adapter found")?;
+
+        let limits = adapter.limits();
+        if width > limits.max_texture_dimension_2d || height > limits.max_texture_dimension_2d {
+            return Err(format!(
+                "{}x{} is larger than the GPU's {}px texture limit",
+                width, height, limits.max_texture_dimension_2d
+            ));
+        }
+
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("compositor"),
+                required_features: wgpu::Features::empty(),
+                required_limits: limits,
+                memory_hints: wgpu::MemoryHints::Performance,
+            },
+            None,
+        ))
+        .map_err(|e| format!("Failed to open GPU device: {}", e))?;
+        // Errors inside a frame are caught with error scopes; anything else
+        // (such as a lost device) surfaces on the next frame's scope
+        device.on_uncaptured_error(Box::new(|e| eprintln!("GPU error: {}", e)));
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("compositor"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("compositor"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                texture_entry(1),
+                texture_entry(2),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("compositor"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        // Layers blend over what is below at their opacity, like blend_pixel;
+        // the output stays opaque
+        let blend = wgpu::BlendState {
+            color: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::SrcAlpha,
+                dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                operation: wgpu::BlendOperation::Add,
+            },
+            alpha: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::Zero,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+        };
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("compositor"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: wgpu::TextureFormat::Rgba8Unorm,
+                    blend: Some(blend),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("compositor"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let target = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("compositor output"),
+            size: extent(width, height),
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let target_view = target.create_view(&wgpu::TextureViewDescriptor::default());
+
+        // Buffer copies need rows aligned to 256 bytes
+        let padded_row_bytes = (width * 4).div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("compositor readback"),
+            size: padded_row_bytes as u64 * height as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let dummy = create_plane(&device, 1, 1, wgpu::TextureFormat::Rgba8Unorm);
+
+        if let Some(e) = pollster::block_on(device.pop_error_scope()) {
+            return Err(format!("Failed to build the GPU pipeline: {}", e));
+        }
+
+        Ok(Self {
+            adapter_name: adapter.get_info().name,
+            device,
+            queue,
+            pipeline,
+            bind_group_layout,
+            sampler,
+            width,
+            height,
+            target,
+            target_view,
+            readback,
+            padded_row_bytes,
+            dummy,
+            screen: None,
+            screen_uv: None,
+            screen_mode: MODE_TEXTURE,
+            webcam: None,
+            uniforms: Vec::new(),
+        })
+    }
+
+    pub fn adapter_name(&self) -> &str {
+        &self.adapter_name
+    }
+
+    /// Draw `draws` in order over black and read the frame back as RGBA
+    pub fn render(
+        &mut self,
+        screen: Option<&ScreenFrame>,
+        webcam: Option<&WebcamFrame>,
+        draws: &[LayerDraw],
+    ) -> Result<Vec<u8>, String> {
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let drawn = self.draw(screen, webcam, draws);
+        let scope_error = pollster::block_on(self.device.pop_error_scope());
+        // The readback buffer is mapped once `draw` succeeds
+        drawn?;
+        if let Some(e) = scope_error {
+            self.readback.unmap();
+            return Err(e.to_string());
+        }
+
+        let row_bytes = self.width as usize * 4;
+        let mut rgba = Vec::with_capacity(row_bytes * self.height as usize);
+        {
+            let data = self.readback.slice(..).get_mapped_range();
+            for row in data.chunks_exact(self.padded_row_bytes as usize) {
+                rgba.extend_from_slice(&row[..row_bytes]);
+            }
+        }
+        self.readback.unmap();
+        Ok(rgba)
+    }
+
+    /// Upload the frames, draw, and map the output into the readback buffer
+    fn draw(
+        &mut self,
+        screen: Option<&ScreenFrame>,
+        webcam: Option<&WebcamFrame>,
+        draws: &[LayerDraw],
+    ) -> Result<(), String> {
+        if let Some(frame) = screen {
+            self.upload_screen(frame)?;
+        }
+        if let Some(frame) = webcam {
+            self.upload_webcam(frame);
+        }
+
+        while self.uniforms.len() < draws.len() {
+            self.uniforms.push(self.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("compositor draw"),
+                size: UNIFORM_SIZE,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }));
+        }
+
+        let mut bind_groups = Vec::with_capacity(draws.len());
+        for (draw, uniform) in draws.iter().zip(&self.uniforms) {
+            let (plane0, plane1, mode, color) = match draw.source {
+                DrawSource::Screen => {
+                    let plane = self.screen.as_ref().ok_or("No screen frame uploaded")?;
+                    let uv = self.screen_uv.as_ref().filter(|_| self.screen_mode == MODE_NV12);
+                    (plane, uv.unwrap_or(&self.dummy), self.screen_mode, [0.0; 4])
+                }
+                DrawSource::Webcam => {
+                    let plane = self.webcam.as_ref().ok_or("No webcam frame uploaded")?;
+                    (plane, &self.dummy, MODE_TEXTURE, [0.0; 4])
+                }
+                DrawSource::Color(color) => (&self.dummy, &self.dummy, MODE_COLOR, color),
+            };
+
+            let rect = [
+                draw.x as f32 / self.width as f32 * 2.0 - 1.0,
+                1.0 - draw.y as f32 / self.height as f32 * 2.0,
+                (draw.x + draw.width) as f32 / self.width as f32 * 2.0 - 1.0,
+                1.0 - (draw.y + draw.height) as f32 / self.height as f32 * 2.0,
+            ];
+            let mut bytes = Vec::with_capacity(UNIFORM_SIZE as usize);
+            for value in rect.iter().chain(&draw.uv).chain(&color).chain([&draw.opacity]) {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            for value in [mode, 0, 0] {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            let mask = [draw.width as f32, draw.height as f32, draw.radius, draw.ring];
+            for value in mask {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            self.queue.write_buffer(uniform, 0, &bytes);
+
+            bind_groups.push(self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("compositor draw"),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: uniform.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&plane0.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(&plane1.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                ],
+            }));
+        }
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("compositor"),
+        });
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("compositor"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.target_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            for bind_group in &bind_groups {
+                pass.set_bind_group(0, bind_group, &[]);
+                pass.draw(0..4, 0..1);
+            }
+        }
+        encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfo {
+                texture: &self.target,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::TexelCopyBufferInfo {
+                buffer: &self.readback,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(self.padded_row_bytes),
+                    rows_per_image: Some(self.height),
+                },
+            },
+            extent(self.width, self.height),
+        );
+        self.queue.submit(Some(encoder.finish()));
+
+        let (sender, receiver) = bounded(1);
+        self.readback.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        let _ = self.device.poll(wgpu::Maintain::Wait);
+        receiver
+            .recv()
+            .map_err(|_| "GPU readback was cancelled".to_string())?
+            .map_err(|e| format!("Failed to read back frame: {}", e))
+    }
+
+    /// Upload a screen frame as captured, row padding included
+    fn upload_screen(&mut self, frame: &ScreenFrame) -> Result<(), String> {
+        let (width, height, stride) = (frame.width, frame.height, frame.stride as u32);
+        let luma_bytes = match frame.pixel_format {
+            CapturePixelFormat::Bgra => width * 4,
+            CapturePixelFormat::Nv12 => width,
+        };
+        let rows = match frame.pixel_format {
+            CapturePixelFormat::Bgra => height,
+            CapturePixelFormat::Nv12 => height + height / 2,
+        };
+        if width == 0 || height == 0 || stride < luma_bytes
+            || frame.data.len() < (stride * (rows - 1) + luma_bytes) as usize
+        {
+            return Err(format!("Screen frame data doesn't match {}x{}", width, height));
+        }
+
+        match frame.pixel_format {
+            CapturePixelFormat::Bgra => {
+                let plane = ensure_plane(&self.device, &mut self.screen, width, height, wgpu::TextureFormat::Bgra8Unorm);
+                write_plane(&self.queue, plane, &frame.data, 0, stride);
+                self.screen_mode = MODE_TEXTURE;
+            }
+            CapturePixelFormat::Nv12 => {
+                let luma = ensure_plane(&self.device, &mut self.screen, width, height, wgpu::TextureFormat::R8Unorm);
+                write_plane(&self.queue, luma, &frame.data, 0, stride);
+                let chroma = ensure_plane(
+                    &self.device,
+                    &mut self.screen_uv,
+                    width / 2,
+                    height / 2,
+                    wgpu::TextureFormat::Rg8Unorm,
+                );
+                write_plane(&self.queue, chroma, &frame.data, stride as u64 * height as u64, stride);
+                self.screen_mode = MODE_NV12;
+            }
+        }
+        Ok(())
+    }
+
+    /// Upload a webcam frame (RGB, expanded to RGBA; webcam frames are small)
+    fn upload_webcam(&mut self, frame: &WebcamFrame) {
+        let rgba = frame.to_rgba();
+        let plane = ensure_plane(
+            &self.device,
+            &mut self.webcam,
+            frame.width,
+            frame.height,
+            wgpu::TextureFormat::Rgba8Unorm,
+        );
+        write_plane(&self.queue, plane, &rgba, 0, frame.width * 4);
+    }
+}
+
+fn extent(width: u32, height: u32) -> wgpu::Extent3d {
+    wgpu::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    }
+}
+
+fn create_plane(device: &wgpu::Device, width: u32, height: u32, format: wgpu::TextureFormat) -> Plane {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("compositor source"),
+        size: extent(width, height),
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    Plane {
+        texture,
+        view,
+        width,
+        height,
+        format,
+    }
+}
+
+/// The plane in `slot`, recreated if its size or format differs
+fn ensure_plane<'a>(
+    device: &wgpu::Device,
+    slot: &'a mut Option<Plane>,
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+) -> &'a Plane {
+    let matches = slot
+        .as_ref()
+        .is_some_and(|p| (p.width, p.height, p.format) == (width, height, format));
+    if !matches {
+        *slot = Some(create_plane(device, width, height, format));
+    }
+    slot.as_ref().expect("plane was just created")
+}
+
+fn write_plane(queue: &wgpu::Queue, plane: &Plane, data: &[u8], offset: u64, bytes_per_row: u32) {
+    queue.write_texture(
+        wgpu::TexelCopyTextureInfo {
+            texture: &plane.texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        data,
+        wgpu::TexelCopyBufferLayout {
+            offset,
+            bytes_per_row: Some(bytes_per_row),
+            rows_per_image: Some(plane.height),
+        },
+        extent(plane.width, plane.height),
+    );
+}
@@ -6,15 +6,71 @@ mod audio;
 mod screen;
 mod webcam;
 mod compositor;
+mod cursor;
+#[cfg(feature = "gpu")]
+mod gpu_compositor;
 mod system_audio;
 mod audio_mixer;
+mod noise_gate;
+mod dual_mic;
+mod audio_ring;
+mod metering;
+mod monitor;
+mod monitor_feed;
+mod preview_server;
+mod visualizer;
+mod webcam_preview;
+mod captions;
+mod text_overlay;
+mod thumbnail;
+mod chroma_key;
+mod capture_backend;
+mod capture_dump;
+mod synthetic_sources;
+mod channel_wait;
+mod sidecar_wav;
+mod queue_watch;
+mod queue_memory;
+mod capture_watchdog;
+mod quality_governor;
+mod pipeline_stats;
+mod loudness;
+mod silence;
+mod mastering;
+mod markers;
+mod transcode;
+mod concat;
+mod waveform;
+mod streaming;
+mod ndi;
+mod virtual_camera;
+mod media_file;
+mod output_check;
+mod devices;
+mod permissions;
+mod settings;
+mod output_paths;
+mod estimate;
+mod library;
 mod encoder;
+mod encode_benchmark;
+mod selftest;
+mod frame_compression;
+mod frame_guard;
+mod frame_pool;
+mod frame_ingest;
+mod thread_priority;
 mod manager;
 mod recording;
 mod external_recorder;
+#[cfg(all(test, feature = "e2e"))]
+mod e2e;
 
-pub use recording::{RecordingConfig, RecordingState, RecordingStatus, DeviceList, ExternalRecordingConfig};
+pub use recording::{RecordingConfig, RecordingError, RecordingState, RecordingStatus, DeviceList, ExternalRecordingConfig, StartRecordingResult, StopRecordingResult};
 use external_recorder::ExternalRecorder;
+use monitor_feed::MonitorFeed;
+use preview_server::PreviewServer;
+use webcam_preview::WebcamPreview;
 
 /// Global state for external frame recorder
 pub struct ExternalRecorderState {
@@ -33,21 +89,28 @@ impl Default for ExternalRecorderState {
 #[tauri::command]
 async fn start_external_recording(
     config: ExternalRecordingConfig,
+    window: tauri::WebviewWindow,
     state: tauri::State<'_, Arc<ExternalRecorderState>>,
-) -> Result<(), String> {
+) -> Result<StartRecordingResult, RecordingError> {
+    // Window area capture records the screen under the calling webview
+    let window_area = match config.frame_source {
+        recording::ExternalFrameSource::WindowArea => Some(screen::WindowArea::new(window)),
+        recording::ExternalFrameSource::Frontend => None,
+    };
     let mut recorder = state.recorder.lock();
-    recorder.start(config)
+    recorder.start(config, window_area)
 }
 
 /// Tauri command: Receive a video frame from the frontend
+/// Async so frames from several webviews can be ingested in parallel
 #[tauri::command]
-fn receive_video_frame(
+async fn receive_video_frame(
     data: Vec<u8>,
     width: u32,
     height: u32,
     timestamp_ms: u64,
     state: tauri::State<'_, Arc<ExternalRecorderState>>,
-) -> Result<(), String> {
+) -> Result<(), RecordingError> {
     let mut recorder = state.recorder.lock();
     recorder.receive_frame(data, width, height, timestamp_ms)
 }
@@ -55,21 +118,35 @@ fn receive_video_frame(
 /// Tauri command: Receive a video frame from the frontend (base64 encoded)
 /// This is faster than JSON array serialization for large binary data
 #[tauri::command]
-fn receive_video_frame_base64(
+async fn receive_video_frame_base64(
     data_base64: String,
     width: u32,
     height: u32,
     timestamp_ms: u64,
     state: tauri::State<'_, Arc<ExternalRecorderState>>,
-) -> Result<(), String> {
-    use base64::{Engine as _, engine::general_purpose::STANDARD};
-
-    // Decode base64 to bytes
-    let data = STANDARD.decode(&data_base64)
-        .map_err(|e| format!("Failed to decode base64: {}", e))?;
+) -> Result<(), RecordingError> {
+    let mut recorder = state.recorder.lock();
+    recorder.receive_frame_base64(data_base64, width, height, timestamp_ms)
+}
 
+/// Tauri command: Receive a video frame from the frontend as the raw request
+/// body, skipping base64 and JSON
+///
+/// Call as `invoke("receive_video_frame_raw", bytes, { headers })` with the
+/// `X-Frame-Width`, `X-Frame-Height` and `X-Frame-Timestamp-Ms` headers. The
+/// bytes are RGBA, or compressed with the recording's frame compression.
+#[tauri::command]
+async fn receive_video_frame_raw(
+    request: tauri::ipc::Request<'_>,
+    state: tauri::State<'_, Arc<ExternalRecorderState>>,
+) -> Result<(), RecordingError> {
+    let tauri::ipc::InvokeBody::Raw(data) = request.body() else {
+        return Err(RecordingError::InvalidConfig("Frame must be sent as raw bytes".to_string()));
+    };
+    let (width, height, timestamp_ms) =
+        frame_ingest::raw_frame_headers(request.headers()).map_err(RecordingError::InvalidConfig)?;
     let mut recorder = state.recorder.lock();
-    recorder.receive_frame(data, width, height, timestamp_ms)
+    recorder.receive_frame_bytes(data.to_vec(), width, height, timestamp_ms)
 }
 
 /// Tauri command: Save media recording from frontend (WebM or MP4)
@@ -80,7 +157,7 @@ fn save_media_recording(
     width: u32,
     height: u32,
     mime_type: String,
-) -> Result<String, String> {
+) -> Result<String, RecordingError> {
     use base64::{Engine as _, engine::general_purpose::STANDARD};
     use std::path::PathBuf;
 
@@ -143,9 +220,10 @@ fn save_media_recording(
 #[tauri::command]
 async fn stop_external_recording(
     state: tauri::State<'_, Arc<ExternalRecorderState>>,
-) -> Result<String, String> {
+) -> Result<StopRecordingResult, RecordingError> {
     let mut recorder = state.recorder.lock();
-    recorder.stop()
+    let result = recorder.stop();
+    result.map(|path| StopRecordingResult::new(path, recorder.take_output_verification()))
 }
 
 /// Tauri command: Get external recording status
@@ -170,26 +248,98 @@ pub fn run() {
     // Initialize external recorder state
     let external_recorder_state = Arc::new(ExternalRecorderState::default());
     
+    // Both recorders offer their output to the same monitor window feed
+    let monitor_feed = MonitorFeed::default();
+    recording_state.manager.lock().set_monitor_feed(monitor_feed.clone());
+    external_recorder_state.recorder.lock().set_monitor_feed(monitor_feed.clone());
+    
+    // The webcam preview hands the camera over when a recording starts
+    let webcam_preview = WebcamPreview::default();
+    recording_state.manager.lock().set_webcam_preview(webcam_preview.clone());
+    
+    // Recorders emit events once the app is running
+    let recording_state_setup = recording_state.clone();
+    let external_recorder_state_setup = external_recorder_state.clone();
+    
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
+        .setup(move |app| {
+            let handle = app.handle().clone();
+            recording_state_setup.manager.lock().set_app_handle(handle.clone());
+            external_recorder_state_setup.recorder.lock().set_app_handle(handle);
+            Ok(())
+        })
         .manage(recording_state)
         .manage(external_recorder_state)
+        .manage(monitor_feed)
+        .manage(webcam_preview)
+        .manage(PreviewServer::default())
         .invoke_handler(tauri::generate_handler![
             greet,
             // Legacy commands (will be deprecated)
             audio::start_audio_capture,
             screen::start_screen_capture,
             screen::check_screen_recording_permission,
+            permissions::check_camera_permission,
+            permissions::check_microphone_permission,
+            permissions::request_camera_permission,
+            permissions::request_microphone_permission,
+            screen::get_screen_thumbnail,
+            thumbnail::extract_frames,
             // New unified recording commands
             recording::get_available_devices,
             recording::get_recording_status,
             recording::get_recording_status_live,
             recording::start_recording,
             recording::stop_recording,
+            recording::stop_at,
+            recording::add_marker,
+            recording::replay_capture_dump,
+            recording::get_audio_levels,
+            recording::get_audio_latency,
+            recording::get_audio_stats,
+            recording::get_pipeline_stats,
+            recording::set_metering_config,
+            recording::update_active_config,
+            recording::set_mic_monitoring,
+            recording::set_audio_visualizer,
+            recording::start_streaming,
+            recording::stop_streaming,
+            recording::start_remote_monitor,
+            recording::get_monitor_join_token,
+            recording::stop_remote_monitor,
+            recording::get_compositor_layers,
+            recording::set_layer_properties,
+            monitor_feed::get_monitor_feed,
+            monitor_feed::stop_monitor_feed,
+            preview_server::start_preview_server,
+            preview_server::stop_preview_server,
+            webcam_preview::start_webcam_preview,
+            webcam_preview::stop_webcam_preview,
+            estimate::estimate_recording,
+            encode_benchmark::run_encode_benchmark,
+            selftest::run_pipeline_selftest,
+            loudness::normalize_recording,
+            silence::detect_silence,
+            silence::trim_silence,
+            mastering::master_recording,
+            transcode::transcode_recording,
+            transcode::extract_audio,
+            transcode::export_clip,
+            transcode::retime_recording,
+            concat::concat_recordings,
+            waveform::get_waveform_peaks,
+            waveform::render_waveform,
+            output_paths::get_allowed_output_dirs,
+            output_paths::add_allowed_output_dir,
+            output_paths::remove_allowed_output_dir,
+            library::list_recordings,
+            library::reveal_recording,
             // External frame recording commands
             start_external_recording,
             receive_video_frame,
             receive_video_frame_base64,
+            receive_video_frame_raw,
             stop_external_recording,
             get_external_recording_status,
             // MediaRecorder recording
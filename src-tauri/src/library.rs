@@ -0,0 +1,167 @@
+//! Recording Library
+//!
+//! Lists the recordings in the allowed output directories, for a "past
+//! recordings" panel. The directories are scanned on every call rather than
+//! kept in an index, so recordings moved or deleted outside the app never
+//! linger in the list. Duration and resolution are read from the container,
+//! which needs the `ffmpeg` feature; without it they are left empty.
+//!
+//! Revealing a recording selects it in Finder, Explorer or the desktop's file
+//! manager, through the opener plugin that knows how to on each platform.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tauri::command;
+
+use crate::media_file::suffix;
+use crate::output_paths::{allowed_output_dirs, check_output_path};
+use crate::recording::{OutputFormat, RecordingError};
+use crate::thumbnail::thumbnail_path;
+
+/// A recording in one of the output directories
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingInfo {
+    pub path: PathBuf,
+    /// None when the container can't be read
+    pub duration_ms: Option<u64>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub size_bytes: u64,
+    /// RFC 3339 in local time (the modification time where the file system
+    /// doesn't keep creation times)
+    pub created_at: String,
+    /// JPEG saved when the recording was finished, if there is one
+    pub thumbnail_path: Option<PathBuf>,
+}
+
+/// A finished recording, by extension, and not a work file
+fn is_recording(path: &Path) -> bool {
+    let Some(extension) = path.extension().and_then(|e| e.to_str()) else {
+        return false;
+    };
+    let formats = [OutputFormat::Mp4, OutputFormat::Webm, OutputFormat::Mkv];
+    if !formats.iter().any(|format| format.extension().eq_ignore_ascii_case(extension)) {
+        return false;
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    // Work files are `<stem>.<suffix>.<ext>`
+    !suffix::ALL.iter().any(|suffix| stem.ends_with(&format!(".{}", suffix)))
+}
+
+/// Recordings directly inside `dir`, with when each was created
+fn scan_dir(dir: &Path) -> Vec<(SystemTime, RecordingInfo)> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| is_recording(&entry.path()))
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok().filter(|m| m.is_file())?;
+            let created = metadata
+                .created()
+                .or_else(|_| metadata.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            let path = entry.path();
+            let (duration_ms, size) = probe(&path);
+            let info = RecordingInfo {
+                duration_ms,
+                width: size.map(|(width, _)| width),
+                height: size.map(|(_, height)| height),
+                size_bytes: metadata.len(),
+                created_at: chrono::DateTime::<chrono::Local>::from(created).to_rfc3339(),
+                thumbnail_path: Some(thumbnail_path(&path)).filter(|thumbnail| thumbnail.is_file()),
+                path,
+            };
+            Some((created, info))
+        })
+        .collect()
+}
+
+/// Duration and video size from the container
+#[cfg(feature = "ffmpeg")]
+fn probe(path: &Path) -> (Option<u64>, Option<(u32, u32)>) {
+    let Ok(input) = crate::media_file::open_input(path) else {
+        return (None, None);
+    };
+    let duration_ms = (input.duration() > 0)
+        .then(|| input.duration() as u64 * 1000 / ffmpeg_next::ffi::AV_TIME_BASE as u64);
+    let size = input
+        .streams()
+        .best(ffmpeg_next::media::Type::Video)
+        .and_then(|stream| ffmpeg_next::codec::context::Context::from_parameters(stream.parameters()).ok())
+        .and_then(|context| context.decoder().video().ok())
+        .map(|decoder| (decoder.width(), decoder.height()));
+    (duration_ms, size)
+}
+
+#[cfg(not(feature = "ffmpeg"))]
+fn probe(_path: &Path) -> (Option<u64>, Option<(u32, u32)>) {
+    (None, None)
+}
+
+/// Tauri command: List the recordings in the output directories, newest first
+#[command]
+pub async fn list_recordings() -> Result<Vec<RecordingInfo>, RecordingError> {
+    tauri::async_runtime::spawn_blocking(|| {
+        #[cfg(feature = "ffmpeg")]
+        ffmpeg_next::init().map_err(|e| format!("FFmpeg init failed: {}", e))?;
+
+        // The same directory may be allowed twice under different names
+        let mut dirs: Vec<PathBuf> = allowed_output_dirs()
+            .iter()
+            .filter_map(|dir| dir.canonicalize().ok())
+            .collect();
+        dirs.sort();
+        dirs.dedup();
+
+        let mut recordings: Vec<_> = dirs.iter().flat_map(|dir| scan_dir(dir)).collect();
+        recordings.sort_by_key(|(created, _)| std::cmp::Reverse(*created));
+        Ok(recordings.into_iter().map(|(_, info)| info).collect())
+    })
+    .await
+    .map_err(|e| format!("Listing recordings failed: {}", e))?
+    .map_err(RecordingError::Failed)
+}
+
+/// Tauri command: Show a recording in the file manager, selected
+///
+/// Only recordings inside the allowed output directories can be revealed.
+#[command]
+pub fn reveal_recording(path: PathBuf) -> Result<(), RecordingError> {
+    // Checked first, so paths outside the output directories can't be
+    // probed for whether they exist
+    let path = check_output_path(&path, &allowed_output_dirs())?;
+    if !path.is_file() {
+        return Err(format!("Recording not found: {}", path.display()).into());
+    }
+    tauri_plugin_opener::reveal_item_in_dir(&path)
+        .map_err(|e| RecordingError::Failed(format!("Failed to reveal {}: {}", path.display(), e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_lists_finished_recordings() {
+        let dir = std::env::temp_dir().join(format!("asmr_library_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["session.mp4", "session.normalizing.mp4", "session.appending.mkv", "session.transcoding.webm", "take.MKV", "take.wav", "notes.txt"] {
+            std::fs::write(dir.join(name), b"data").unwrap();
+        }
+
+        let recordings = scan_dir(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let mut names: Vec<String> = recordings
+            .iter()
+            .map(|(_, info)| info.path.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["session.mp4", "take.MKV"]);
+        assert!(recordings.iter().all(|(_, info)| info.size_bytes == 4 && info.duration_ms.is_none()));
+    }
+}
@@ -0,0 +1,477 @@
+//! Loudness Normalization
+//!
+//! Two-pass EBU R128 normalization of a finished recording. The first pass
+//! decodes the audio track and measures its integrated loudness and sample
+//! peak; the second applies one static gain so the file reaches the target
+//! loudness, re-encoding the audio with its original codec and copying every
+//! other stream untouched. The result replaces the original file.
+//!
+//! No compression or limiting is applied: if reaching the target would push
+//! peaks above -1 dBFS, the gain is reduced and the file ends up quieter than
+//! requested.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::command;
+
+use crate::output_paths::{allowed_output_dirs, check_output_path};
+use crate::recording::RecordingError;
+#[cfg(feature = "ffmpeg")]
+use crate::media_file::{
+    add_copy_stream, append_interleaved, copy_chapters, create_output, open_audio_decoder, open_input,
+    rewrite, suffix, write_header, AudioTrackReader,
+};
+
+/// Lowest accepted target
+pub const MIN_TARGET_LUFS: f32 = -40.0;
+
+/// Highest accepted target
+pub const MAX_TARGET_LUFS: f32 = -5.0;
+
+/// Highest sample peak allowed after normalization
+#[cfg(any(feature = "ffmpeg", test))]
+const PEAK_CEILING_DB: f64 = -1.0;
+
+/// Gains smaller than this leave the file as it is
+#[cfg(feature = "ffmpeg")]
+const MIN_GAIN_DB: f64 = 0.05;
+
+/// Outcome of a normalization pass
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoudnessReport {
+    /// Integrated loudness before normalization
+    pub input_lufs: f64,
+    /// Sample peak before normalization
+    pub input_peak_db: f64,
+    pub target_lufs: f64,
+    /// Gain applied to the audio track
+    pub gain_db: f64,
+    /// The gain was reduced to keep peaks below -1 dBFS
+    pub peak_limited: bool,
+}
+
+/// Check a target loudness is in the accepted range
+pub fn validate_target(target_lufs: f32) -> Result<(), String> {
+    if !(MIN_TARGET_LUFS..=MAX_TARGET_LUFS).contains(&target_lufs) {
+        return Err(format!(
+            "Loudness target must be {} to {} LUFS (got {})",
+            MIN_TARGET_LUFS, MAX_TARGET_LUFS, target_lufs
+        ));
+    }
+    Ok(())
+}
+
+/// Gain that brings `input_lufs` to `target_lufs` without peaks above the
+/// ceiling, and whether the ceiling reduced it
+#[cfg(any(feature = "ffmpeg", test))]
+pub fn normalization_gain(input_lufs: f64, peak_db: f64, target_lufs: f64) -> (f64, bool) {
+    let gain = target_lufs - input_lufs;
+    let headroom = PEAK_CEILING_DB - peak_db;
+    if gain > headroom {
+        (headroom, true)
+    } else {
+        (gain, false)
+    }
+}
+
+/// Measure the recording at `path` and rewrite it at `target_lufs`
+#[cfg(feature = "ffmpeg")]
+pub fn normalize_file(path: &Path, target_lufs: f32) -> Result<LoudnessReport, String> {
+    validate_target(target_lufs)?;
+    ffmpeg_next::init().map_err(|e| format!("FFmpeg init failed: {}", e))?;
+
+    let (input_lufs, input_peak_db) = measure(path)?;
+    let (gain_db, peak_limited) =
+        normalization_gain(input_lufs, input_peak_db, target_lufs as f64);
+    let report = LoudnessReport {
+        input_lufs,
+        input_peak_db,
+        target_lufs: target_lufs as f64,
+        gain_db,
+        peak_limited,
+    };
+
+    if gain_db.abs() < MIN_GAIN_DB {
+        println!("{} is already at {:.1} LUFS", path.display(), input_lufs);
+        return Ok(report);
+    }
+
+    rewrite(path, suffix::NORMALIZING, |temp_path| apply_gain(path, temp_path, gain_db, None))?;
+
+    println!(
+        "Normalized {}: {:.1} LUFS, {:+.1} dB{}",
+        path.display(),
+        input_lufs,
+        gain_db,
+        if peak_limited { " (limited by peak)" } else { "" }
+    );
+    Ok(report)
+}
+
+#[cfg(not(feature = "ffmpeg"))]
+pub fn normalize_file(_path: &Path, target_lufs: f32) -> Result<LoudnessReport, String> {
+    validate_target(target_lufs)?;
+    Err("Loudness normalization requires FFmpeg support".to_string())
+}
+
+/// First pass: integrated loudness and sample peak of the audio track
+#[cfg(feature = "ffmpeg")]
+fn measure(path: &Path) -> Result<(f64, f64), String> {
+    use crate::metering::IntegratedLoudness;
+
+    let reader = AudioTrackReader::open(path)?;
+    let mut meter = IntegratedLoudness::new(reader.rate(), reader.channels());
+    reader.read_all(|samples| meter.process(samples))?;
+
+    let lufs = meter
+        .loudness()
+        .ok_or_else(|| format!("{} has no audible audio to normalize", path.display()))?;
+    Ok((lufs, meter.peak_db() as f64))
+}
+
+/// Second pass: write `temp_path` with the audio track's gain changed, after
+/// running its samples through `filter` if one is given
+#[cfg(feature = "ffmpeg")]
+pub(crate) fn apply_gain(
+    path: &Path,
+    temp_path: &Path,
+    gain_db: f64,
+    filter: Option<SampleFilter>,
+) -> Result<(), String> {
+    let mut input = open_input(path)?;
+    let mut output = create_output(temp_path, &input)?;
+
+    let (audio_index, decoder) = open_audio_decoder(&input, path)?;
+    let global_header = output
+        .format()
+        .flags()
+        .contains(ffmpeg_next::format::flag::Flags::GLOBAL_HEADER);
+
+    // Output stream for each input stream (None: dropped)
+    let mut stream_map = Vec::new();
+    let mut stage = None;
+    for stream in input.streams() {
+        if stream.index() == audio_index {
+            let (encoder, codec) =
+                open_matching_encoder(&decoder, &stream.parameters(), global_header)?;
+            let mut audio_stream = output
+                .add_stream(codec)
+                .map_err(|e| format!("Failed to add audio stream: {}", e))?;
+            audio_stream.set_time_base(encoder.time_base());
+            audio_stream.set_parameters(&encoder);
+            audio_stream.set_metadata(stream.metadata().to_owned());
+            stream_map.push(Some(audio_stream.index()));
+            stage = Some((encoder, audio_stream.index(), stream.time_base()));
+            continue;
+        }
+
+        stream_map.push(add_copy_stream(&mut output, &stream)?);
+    }
+    let (encoder, output_index, input_time_base) = stage.ok_or("Audio stream missing")?;
+    copy_chapters(&input, &mut output, None)?;
+
+    write_header(&mut output, temp_path)?;
+
+    let mut stage = GainStage::new(
+        decoder,
+        encoder,
+        gain_db,
+        input_time_base,
+        output_index,
+        output.stream(output_index).map(|s| s.time_base()),
+    );
+    if let Some(filter) = filter {
+        stage = stage.with_filter(filter);
+    }
+    let output_time_bases: Vec<_> = output.streams().map(|s| s.time_base()).collect();
+
+    for (stream, mut packet) in input.packets() {
+        if stream.index() == audio_index {
+            stage.send_packet(&packet, &mut output)?;
+            continue;
+        }
+        let Some(Some(output_index)) = stream_map.get(stream.index()).copied() else {
+            continue;
+        };
+        packet.rescale_ts(stream.time_base(), output_time_bases[output_index]);
+        packet.set_stream(output_index);
+        packet.set_position(-1);
+        packet
+            .write_interleaved(&mut output)
+            .map_err(|e| format!("Failed to copy packet: {}", e))?;
+    }
+    stage.finish(&mut output)?;
+
+    output
+        .write_trailer()
+        .map_err(|e| format!("Failed to write trailer: {}", e))
+}
+
+/// Open an encoder with the decoded track's codec, rate, layout and bitrate
+#[cfg(feature = "ffmpeg")]
+fn open_matching_encoder(
+    decoder: &ffmpeg_next::decoder::Audio,
+    parameters: &ffmpeg_next::codec::Parameters,
+    global_header: bool,
+) -> Result<(ffmpeg_next::encoder::audio::Encoder, ffmpeg_next::Codec), String> {
+    use ffmpeg_next::codec::Id;
+
+    let codec = match decoder.id() {
+        // The native Opus encoder is experimental
+        Id::OPUS => ffmpeg_next::encoder::find_by_name("libopus")
+            .or_else(|| ffmpeg_next::encoder::find(Id::OPUS)),
+        id => ffmpeg_next::encoder::find(id),
+    }
+    .ok_or_else(|| format!("No encoder for {:?} audio", decoder.id()))?;
+
+    let bit_rate = unsafe { (*parameters.as_ptr()).bit_rate };
+    let encoder = open_audio_encoder(codec, decoder, (bit_rate > 0).then_some(bit_rate as usize), global_header)?;
+    Ok((encoder, codec))
+}
+
+/// Open `codec` at the decoded track's rate and layout
+#[cfg(feature = "ffmpeg")]
+pub(crate) fn open_audio_encoder(
+    codec: ffmpeg_next::Codec,
+    decoder: &ffmpeg_next::decoder::Audio,
+    bit_rate: Option<usize>,
+    global_header: bool,
+) -> Result<ffmpeg_next::encoder::audio::Encoder, String> {
+    use crate::encoder::audio_sample_format;
+
+    let mut encoder = ffmpeg_next::codec::context::Context::new_with_codec(codec)
+        .encoder()
+        .audio()
+        .map_err(|e| format!("Failed to create audio encoder: {}", e))?;
+
+    let format = audio_sample_format(codec);
+    encoder.set_rate(decoder.rate() as i32);
+    encoder.set_channel_layout(decoder.channel_layout());
+    encoder.set_format(format);
+    encoder.set_time_base(ffmpeg_next::Rational(1, decoder.rate() as i32));
+    if format == ffmpeg_next::format::Sample::I32(ffmpeg_next::format::sample::Type::Packed) {
+        // FLAC and 24-bit PCM, as written by the recorder
+        unsafe { (*encoder.as_mut_ptr()).bits_per_raw_sample = 24 };
+    }
+    if let Some(bit_rate) = bit_rate {
+        encoder.set_bit_rate(bit_rate);
+    }
+    if global_header {
+        encoder.set_flags(ffmpeg_next::codec::flag::Flags::GLOBAL_HEADER);
+    }
+
+    encoder
+        .open()
+        .map_err(|e| format!("Failed to open audio encoder: {}", e))
+}
+
+/// Processes decoded interleaved samples before the gain is applied,
+/// appending the result to the second argument; it may be longer or shorter
+/// than the input
+#[cfg(feature = "ffmpeg")]
+pub(crate) type SampleFilter = Box<dyn FnMut(&[f32], &mut Vec<f32>)>;
+
+/// Decodes the audio track, applies the gain and re-encodes it
+#[cfg(feature = "ffmpeg")]
+pub(crate) struct GainStage {
+    decoder: ffmpeg_next::decoder::Audio,
+    encoder: ffmpeg_next::encoder::audio::Encoder,
+    gain: f32,
+    filter: Option<SampleFilter>,
+    /// Decoded samples on their way through the filter
+    decoded: Vec<f32>,
+    /// Input time is divided by this (the filter changes the audio's speed)
+    speed: f64,
+    channels: u16,
+    samples_per_frame: usize,
+    /// Gained samples waiting for a full encoder frame
+    pending: Vec<f32>,
+    /// PTS of the first pending sample (encoder time base)
+    next_pts: Option<i64>,
+    input_time_base: ffmpeg_next::Rational,
+    output_index: usize,
+    output_time_base: ffmpeg_next::Rational,
+}
+
+#[cfg(feature = "ffmpeg")]
+impl GainStage {
+    pub(crate) fn new(
+        decoder: ffmpeg_next::decoder::Audio,
+        encoder: ffmpeg_next::encoder::audio::Encoder,
+        gain_db: f64,
+        input_time_base: ffmpeg_next::Rational,
+        output_index: usize,
+        output_time_base: Option<ffmpeg_next::Rational>,
+    ) -> Self {
+        let samples_per_frame = match encoder.frame_size() as usize {
+            0 => crate::encoder::PCM_FRAME_SIZE,
+            size => size,
+        };
+        Self {
+            channels: encoder.channels(),
+            samples_per_frame,
+            gain: 10f64.powf(gain_db / 20.0) as f32,
+            filter: None,
+            decoded: Vec::new(),
+            speed: 1.0,
+            pending: Vec::new(),
+            next_pts: None,
+            input_time_base,
+            output_index,
+            output_time_base: output_time_base.unwrap_or(encoder.time_base()),
+            decoder,
+            encoder,
+        }
+    }
+
+    /// Run decoded samples through `filter` before the gain
+    pub(crate) fn with_filter(mut self, filter: SampleFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Time the output as played `speed` times faster than the input, for a
+    /// filter that changes its speed
+    pub(crate) fn with_speed(mut self, speed: f64) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Write to the time base the muxer settled on for the stage's stream,
+    /// once the header is written
+    pub(crate) fn set_output_time_base(&mut self, output: &ffmpeg_next::format::context::Output) {
+        if let Some(stream) = output.stream(self.output_index) {
+            self.output_time_base = stream.time_base();
+        }
+    }
+
+    pub(crate) fn send_packet(
+        &mut self,
+        packet: &ffmpeg_next::Packet,
+        output: &mut ffmpeg_next::format::context::Output,
+    ) -> Result<(), String> {
+        self.decoder
+            .send_packet(packet)
+            .map_err(|e| format!("Failed to decode audio: {}", e))?;
+        self.receive_frames()?;
+        self.encode_pending(output, false)
+    }
+
+    /// Decode, apply the gain and flush everything to the output
+    pub(crate) fn finish(&mut self, output: &mut ffmpeg_next::format::context::Output) -> Result<(), String> {
+        let _ = self.decoder.send_eof();
+        self.receive_frames()?;
+        self.encode_pending(output, true)?;
+
+        self.encoder
+            .send_eof()
+            .map_err(|e| format!("Failed to send EOF: {}", e))?;
+        self.write_packets(output)
+    }
+
+    fn receive_frames(&mut self) -> Result<(), String> {
+        use ffmpeg_next::Rescale;
+
+        let mut frame = ffmpeg_next::frame::Audio::empty();
+        while self.decoder.receive_frame(&mut frame).is_ok() {
+            if self.next_pts.is_none() {
+                let pts = (frame.pts().unwrap_or(0) as f64 / self.speed).round() as i64;
+                self.next_pts = Some(pts.rescale(self.input_time_base, self.encoder.time_base()));
+            }
+            let start = self.pending.len();
+            match &mut self.filter {
+                Some(filter) => {
+                    self.decoded.clear();
+                    append_interleaved(&frame, &mut self.decoded)?;
+                    filter(&self.decoded, &mut self.pending);
+                }
+                None => append_interleaved(&frame, &mut self.pending)?,
+            }
+            for sample in &mut self.pending[start..] {
+                *sample = (*sample * self.gain).clamp(-1.0, 1.0);
+            }
+        }
+        Ok(())
+    }
+
+    /// Encode full frames, and the remainder too when `flush` is set
+    fn encode_pending(
+        &mut self,
+        output: &mut ffmpeg_next::format::context::Output,
+        flush: bool,
+    ) -> Result<(), String> {
+        use crate::encoder::fill_audio_frame;
+
+        let frame_len = self.samples_per_frame * self.channels as usize;
+        let mut frame = ffmpeg_next::frame::Audio::new(
+            self.encoder.format(),
+            self.samples_per_frame,
+            self.encoder.channel_layout(),
+        );
+
+        while self.pending.len() >= frame_len || (flush && !self.pending.is_empty()) {
+            let take = frame_len.min(self.pending.len());
+            let samples: Vec<f32> = self.pending.drain(..take).collect();
+            fill_audio_frame(&samples, self.channels, &mut frame)?;
+
+            let pts = self.next_pts.unwrap_or(0);
+            frame.set_pts(Some(pts));
+            self.next_pts = Some(pts + frame.samples() as i64);
+
+            self.encoder
+                .send_frame(&frame)
+                .map_err(|e| format!("Failed to send audio frame: {}", e))?;
+            self.write_packets(output)?;
+        }
+        Ok(())
+    }
+
+    fn write_packets(&mut self, output: &mut ffmpeg_next::format::context::Output) -> Result<(), String> {
+        let mut packet = ffmpeg_next::Packet::empty();
+        while self.encoder.receive_packet(&mut packet).is_ok() {
+            packet.set_stream(self.output_index);
+            packet.rescale_ts(self.encoder.time_base(), self.output_time_base);
+            packet
+                .write_interleaved(output)
+                .map_err(|e| format!("Failed to write audio packet: {}", e))?;
+        }
+        Ok(())
+    }
+}
+
+/// Tauri command: Normalize a finished recording to `target_lufs`
+///
+/// Runs both passes before returning, which takes a while for long
+/// recordings; the file is only replaced once the normalized copy is complete.
+#[command]
+pub async fn normalize_recording(path: String, target_lufs: f32) -> Result<LoudnessReport, RecordingError> {
+    let path = PathBuf::from(path);
+    if !path.is_file() {
+        return Err(format!("Recording not found: {}", path.display()).into());
+    }
+    // The file is rewritten in place
+    let path = check_output_path(&path, &allowed_output_dirs())?;
+    tauri::async_runtime::spawn_blocking(move || normalize_file(&path, target_lufs))
+        .await
+        .map_err(|e| format!("Normalization failed: {}", e))?
+        .map_err(RecordingError::Failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalization_gain() {
+        // Plenty of headroom: the full difference is applied
+        assert_eq!(normalization_gain(-30.0, -20.0, -16.0), (14.0, false));
+        // Turning down is never limited
+        assert_eq!(normalization_gain(-10.0, -0.1, -23.0), (-13.0, false));
+        // Peaks would pass -1 dBFS: limited to the headroom
+        assert_eq!(normalization_gain(-30.0, -6.0, -16.0), (5.0, true));
+
+        assert!(validate_target(-16.0).is_ok());
+        assert!(validate_target(0.0).is_err());
+    }
+}
@@ -1,17 +1,48 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
+use tauri::AppHandle;
 use crossbeam_channel::{bounded, Receiver, Sender};
 use parking_lot::Mutex;
 
-use crate::audio::{MicrophoneCapture, MicrophoneCaptureConfig};
-use crate::audio_mixer::{AudioMixer, AudioMixerConfig};
-use crate::compositor::{CompositeFrame, CompositorConfig, VideoCompositor};
-use crate::encoder::{Encoder, EncoderConfig};
-use crate::recording::{RecordingConfig, RecordingStatus};
-use crate::screen::{ScreenCapture, ScreenCaptureConfig, ScreenFrame};
+use crate::audio::{AudioLatency, MicrophoneCapture, MicrophoneCaptureConfig, MicrophoneState, SUPPORTED_SAMPLE_RATES};
+use crate::audio_mixer::{AudioMixer, AudioMixerConfig, MixerStats};
+use crate::channel_wait::{wait_for, wait_for_either};
+use crate::metering::{AudioLevels, MeteringConfig};
+use crate::monitor::{MicMonitor, MonitorConfig};
+use crate::visualizer::{AudioVisualizer, VisualizerConfig};
+use crate::captions::{CaptionEngine, CaptionOverlay};
+use crate::cursor::{CursorTracker, DisplayBounds};
+use crate::capture_backend::{AudioCaptureBackend, MicrophoneCaptureBackend, ScreenCaptureBackend, WebcamCaptureBackend};
+use crate::capture_dump::{CaptureDump, CaptureReplay, DumpRecord, ReplaySources};
+use crate::sidecar_wav::{SidecarWav, SidecarWavSource};
+use crate::synthetic_sources;
+use crate::ndi::NdiSink;
+use crate::virtual_camera::VirtualCamera;
+use crate::streaming::{MonitorJoin, StreamingConfig, StreamingSink, WebRtcConfig, HLS_PLAYLIST};
+use crate::text_overlay::TextOverlay;
+use crate::loudness::validate_target;
+use crate::markers::Marker;
+use crate::output_check::{self, OutputVerification};
+use crate::output_paths::{allowed_output_dirs, check_output_dir, check_output_path, preflight_output};
+use crate::monitor_feed::MonitorFeed;
+use crate::preview_server::random_token;
+use crate::webcam_preview::WebcamPreview;
+use crate::pipeline_stats::{FrameCounters, PipelineProbe, PipelineStats};
+use crate::queue_memory::{frame_bytes, QueueMemory};
+use crate::capture_watchdog::{CaptureWatchdog, WatchdogEvent, WatchedSource};
+use crate::queue_watch::{app_sink, channel_probe, QueueWatch, WatchedQueue};
+use crate::quality_governor::{self, QualityControls, QualityGovernor, QualityStep};
+use crate::compositor::{CompositeFrame, CompositorConfig, Layer, LayerUpdate, VideoCompositor};
+use crate::devices::{resolve_microphone, resolve_screen, resolve_webcam};
+use crate::dual_mic::{self, DualMicConfig};
+use crate::encoder::{Encoder, EncoderConfig, OutputFailover};
+use crate::recording::{ActiveConfigUpdate, AudioBitDepth, AudioChannelLayout, CapturePixelFormat, OutputResolution, RecordingConfig, RecordingError, RecordingEvent, RecordingStatus, StartRecordingResult, append_part_path, session_file_name, validate_audio_bitrate, validate_failover_dir, validate_pip_placement, validate_session_id};
+use crate::settings::AppSettings;
+use crate::screen::{screen_capture_error, ScreenCapture, ScreenCaptureConfig, ScreenFrame};
 use crate::system_audio::{SystemAudioCapture, SystemAudioCaptureConfig};
-use crate::webcam::{WebcamCapture, WebcamCaptureConfig, WebcamFrame};
+use crate::thread_priority::{self, ThreadRole};
+use crate::webcam::{WebcamCapture, WebcamCaptureConfig, WebcamFrame, WebcamState};
 
 /// Recording Manager - orchestrates all capture and encoding components
 pub struct RecordingManager {
@@ -22,23 +53,82 @@ pub struct RecordingManager {
     /// Stop signal
     stop_signal: Arc<Mutex<bool>>,
     /// Screen capture component
-    screen_capture: Option<ScreenCapture>,
+    screen_capture: Option<Box<dyn ScreenCaptureBackend>>,
     /// Webcam capture component
-    webcam_capture: Option<WebcamCapture>,
-    /// Microphone capture component
-    mic_capture: Option<MicrophoneCapture>,
+    webcam_capture: Option<Box<dyn WebcamCaptureBackend>>,
+    /// Microphone capture component (the left ear with two ear microphones)
+    mic_capture: Option<Box<dyn MicrophoneCaptureBackend>>,
+    /// Right ear microphone of a two-microphone binaural rig
+    right_ear_capture: Option<MicrophoneCapture>,
     /// System audio capture component
-    system_audio_capture: Option<SystemAudioCapture>,
+    system_audio_capture: Option<Box<dyn AudioCaptureBackend>>,
     /// Audio mixer component
     audio_mixer: Option<AudioMixer>,
-    /// Video compositor
-    compositor: Option<VideoCompositor>,
+    /// Live microphone monitor
+    mic_monitor: Option<MicMonitor>,
+    /// Waveform and spectrum events for the mix
+    visualizer: Option<AudioVisualizer>,
+    /// Live captions
+    captions: Option<CaptionEngine>,
+    /// Mouse poller for the cursor highlight
+    cursor: Option<CursorTracker>,
+    /// Display being captured, for turning the cursor highlight on mid-recording
+    display_index: Option<usize>,
+    /// Uncompressed copy of the audio next to the video
+    sidecar_wav: Option<SidecarWav>,
+    /// Raw capture dump being written
+    capture_dump: Option<CaptureDump>,
+    /// Dump fed to the pipeline instead of the capture devices
+    replay: Option<CaptureReplay>,
+    /// Video compositor (shared with the compositing thread)
+    compositor: Option<Arc<Mutex<VideoCompositor>>>,
+    /// Downscaled copy of the output for a monitor window
+    monitor_feed: MonitorFeed,
+    /// Webcam preview, which has to let go of the camera before recording
+    webcam_preview: WebcamPreview,
     /// Encoder
     encoder: Option<Encoder>,
+    /// Live stream fed by the encoder
+    streaming: Option<StreamingSink>,
+    /// HLS segments and playlist fed by the encoder
+    hls: Option<StreamingSink>,
+    /// WebRTC publish for remote monitoring, with where to watch it
+    webrtc: Option<(StreamingSink, MonitorJoin)>,
+    /// Audio-only stream to an Icecast server, fed by the encoder
+    icecast: Option<StreamingSink>,
+    /// NDI source fed by the encoder
+    ndi: Option<NdiSink>,
+    /// Virtual camera fed by the encoder
+    virtual_camera: Option<VirtualCamera>,
     /// Encoder error receiver
     encoder_error_receiver: Option<Receiver<String>>,
+    /// Encoder switches to the failover drive
+    encoder_failover_receiver: Option<Receiver<OutputFailover>>,
+    /// Watermark events for the encoder's input queues
+    queue_watch: Option<QueueWatch>,
+    /// Adaptive quality adjustments (None when it's off)
+    quality_controls: Option<Arc<QualityControls>>,
+    /// Steps quality down under sustained pressure
+    quality_governor: Option<QualityGovernor>,
+    /// Memory held by the pipeline's queues, against the configured cap
+    queue_memory: Option<Arc<QueueMemory>>,
+    /// Frame counters and queue depths for the pipeline statistics
+    pipeline: Option<PipelineProbe>,
+    /// Restarts capture sources that stop delivering
+    capture_watchdog: Option<CaptureWatchdog>,
+    /// Running app, for emitting events (None until the app is set up)
+    app_handle: Option<AppHandle>,
     /// Compositing thread handle
     compositor_running: Arc<Mutex<bool>>,
+    compositor_thread: Option<std::thread::JoinHandle<()>>,
+    /// Level meter configuration (kept across sessions)
+    metering_config: MeteringConfig,
+    /// Output time (ms) at which the recording ends by itself
+    scheduled_stop_ms: Option<u64>,
+    /// Output path of a recording that ended at its scheduled stop
+    completed_output: Option<String>,
+    /// How the last finished file checked out when read back
+    output_verification: Option<OutputVerification>,
 }
 
 impl RecordingManager {
@@ -51,37 +141,612 @@ impl RecordingManager {
             screen_capture: None,
             webcam_capture: None,
             mic_capture: None,
+            right_ear_capture: None,
             system_audio_capture: None,
             audio_mixer: None,
+            mic_monitor: None,
+            visualizer: None,
+            captions: None,
+            cursor: None,
+            display_index: None,
+            sidecar_wav: None,
+            capture_dump: None,
+            replay: None,
             compositor: None,
+            monitor_feed: MonitorFeed::default(),
+            webcam_preview: WebcamPreview::default(),
             encoder: None,
+            streaming: None,
+            hls: None,
+            webrtc: None,
+            icecast: None,
+            ndi: None,
+            virtual_camera: None,
             encoder_error_receiver: None,
+            encoder_failover_receiver: None,
+            queue_watch: None,
+            quality_controls: None,
+            quality_governor: None,
+            queue_memory: None,
+            pipeline: None,
+            capture_watchdog: None,
+            app_handle: None,
             compositor_running: Arc::new(Mutex::new(false)),
+            compositor_thread: None,
+            metering_config: MeteringConfig::default(),
+            scheduled_stop_ms: None,
+            completed_output: None,
+            output_verification: None,
         }
     }
     
     /// Get the current recording status
     pub fn status(&mut self) -> RecordingStatus {
+        self.handle_encoder_failover();
         self.handle_encoder_errors();
-        self.status.lock().clone()
+        self.handle_stream_errors();
+        self.handle_scheduled_stop();
+        self.handle_webcam_state();
+        self.handle_microphone_state();
+        self.handle_capture_stalls();
+        let mut status = self.status.lock().clone();
+        status.queue_memory_bytes = self.queue_memory.as_ref().map_or(0, |m| m.bytes());
+        status
+    }
+    
+    /// Get the configuration of the recording in progress
+    pub fn config(&self) -> Option<&RecordingConfig> {
+        self.config.as_ref()
+    }
+    
+    /// Get the current audio levels (None when not recording)
+    pub fn audio_levels(&self) -> Option<AudioLevels> {
+        self.audio_mixer.as_ref().map(|m| m.levels())
+    }
+    
+    /// Get the mixer's rate conversion and drift statistics (None when not recording)
+    pub fn audio_stats(&self) -> Option<MixerStats> {
+        self.audio_mixer.as_ref().map(|m| m.stats())
+    }
+    
+    /// Get frame rates, frame losses and queue depths (None when not recording)
+    pub fn pipeline_stats(&mut self) -> Option<PipelineStats> {
+        let (frames_encoded, audio_underruns) = self.encoder.as_ref()
+            .map(|e| (e.frames_encoded(), e.audio_underruns()))
+            .unwrap_or_default();
+        self.pipeline.as_mut().map(|p| p.stats(frames_encoded, audio_underruns))
+    }
+    
+    /// Get the latency of the audio path (None when not recording)
+    pub fn audio_latency(&self) -> Option<AudioLatency> {
+        self.audio_mixer.as_ref()?;
+        let config = self.config.as_ref()?;
+        Some(AudioLatency::new(
+            self.mic_capture.as_ref().and_then(|m| m.timing()),
+            config.audio_buffer.mixer_chunk_frames,
+            config.audio_sample_rate,
+        )
+        .with_noise_gate(&config.noise_gate, config.audio_sample_rate)
+        .with_monitor(self.mic_monitor.as_ref().and_then(|m| m.timing())))
+    }
+    
+    /// Change the metering configuration for this and future recordings
+    pub fn set_metering_config(&mut self, config: MeteringConfig) {
+        self.metering_config = config;
+        if let Some(ref mixer) = self.audio_mixer {
+            mixer.set_metering_config(config);
+        }
+    }
+    
+    /// Share the monitor feed that composited frames are offered to
+    pub fn set_monitor_feed(&mut self, feed: MonitorFeed) {
+        self.monitor_feed = feed;
+    }
+    
+    /// Share the webcam preview, stopped when a recording needs the camera
+    pub fn set_webcam_preview(&mut self, preview: WebcamPreview) {
+        self.webcam_preview = preview;
+    }
+    
+    /// Give the recorder the running app, for emitting events
+    pub fn set_app_handle(&mut self, app: AppHandle) {
+        self.app_handle = Some(app);
+    }
+    
+    /// Start, change or stop live microphone monitoring for the active recording
+    pub fn set_mic_monitor(&mut self, monitor_config: Option<MonitorConfig>) -> Result<(), RecordingError> {
+        let config = self.config.as_mut().ok_or(RecordingError::NotRecording)?;
+        let mixer = self.audio_mixer.as_ref().ok_or(RecordingError::NotRecording)?;
+        if !config.capture_mic {
+            return Err("Microphone is not being recorded".to_string().into());
+        }
+        
+        if let Some(monitor) = self.mic_monitor.take() {
+            mixer.set_monitor_sender(None);
+            monitor.stop();
+        }
+        
+        if let Some(ref monitor_config) = monitor_config {
+            let monitor = MicMonitor::start(
+                monitor_config.clone(),
+                config.audio_sample_rate,
+                config.audio_channels.channel_count(),
+            )?;
+            mixer.set_monitor_sender(Some(monitor.sender()));
+            self.mic_monitor = Some(monitor);
+        }
+        
+        config.mic_monitor = monitor_config;
+        Ok(())
+    }
+    
+    /// Start, change or stop the waveform and spectrum events for the active recording
+    pub fn set_audio_visualizer(&mut self, visualizer_config: Option<VisualizerConfig>) -> Result<(), RecordingError> {
+        let config = self.config.as_mut().ok_or(RecordingError::NotRecording)?;
+        let mixer = self.audio_mixer.as_ref().ok_or("No audio is being recorded")?;
+        
+        if let Some(visualizer) = self.visualizer.take() {
+            mixer.set_visualizer_sender(None);
+            visualizer.stop();
+        }
+        
+        if let Some(ref visualizer_config) = visualizer_config {
+            let app = self.app_handle.clone().ok_or("App is not ready for events")?;
+            let visualizer = AudioVisualizer::start(
+                visualizer_config.clone(),
+                config.audio_sample_rate,
+                config.audio_channels.channel_count(),
+                app,
+            )?;
+            mixer.set_visualizer_sender(Some(visualizer.sender()));
+            self.visualizer = Some(visualizer);
+        }
+        
+        config.audio_visualizer = visualizer_config;
+        Ok(())
+    }
+    
+    /// Start pushing the active recording to an RTMP server as well
+    pub fn start_streaming(&mut self, stream_config: StreamingConfig) -> Result<(), RecordingError> {
+        let encoder = self.encoder.as_ref().ok_or(RecordingError::NotRecording)?;
+        if self.streaming.is_some() {
+            return Err("Already streaming".to_string().into());
+        }
+        
+        let sink = StreamingSink::start(stream_config.target()?, encoder.stream_format())?;
+        self.streaming = Some(sink);
+        self.connect_streams();
+        self.status.lock().streaming = true;
+        Ok(())
+    }
+    
+    /// Stop the live stream, if any; the recording carries on
+    pub fn stop_streaming(&mut self) {
+        let Some(sink) = self.streaming.take() else {
+            return;
+        };
+        self.connect_streams();
+        sink.stop();
+        self.status.lock().streaming = false;
+    }
+    
+    /// Publish the active recording over WebRTC for collaborators to watch
+    pub fn start_webrtc(&mut self, webrtc_config: WebRtcConfig) -> Result<MonitorJoin, RecordingError> {
+        let encoder = self.encoder.as_ref().ok_or(RecordingError::NotRecording)?;
+        if self.webrtc.is_some() {
+            return Err("Remote monitoring is already on".to_string().into());
+        }
+        
+        let format = encoder.stream_format();
+        let token = random_token()?;
+        let sink = StreamingSink::start(webrtc_config.target(&token, format.sample_rate)?, format)?;
+        let join = webrtc_config.join(&token);
+        self.webrtc = Some((sink, join.clone()));
+        self.connect_streams();
+        Ok(join)
+    }
+    
+    /// Where the remote monitor can be watched, while it is on
+    pub fn webrtc_join(&self) -> Option<MonitorJoin> {
+        self.webrtc.as_ref().map(|(_, join)| join.clone())
+    }
+    
+    /// Stop remote monitoring, if it is on; the recording carries on
+    pub fn stop_webrtc(&mut self) {
+        if let Some((sink, _)) = self.webrtc.take() {
+            self.connect_streams();
+            sink.stop();
+        }
+    }
+    
+    /// Stop writing HLS, if it is on
+    fn stop_hls(&mut self) {
+        if let Some(sink) = self.hls.take() {
+            self.connect_streams();
+            sink.stop();
+        }
+    }
+    
+    /// Stop the Icecast stream, if it is on
+    fn stop_icecast(&mut self) {
+        if let Some(sink) = self.icecast.take() {
+            self.connect_streams();
+            sink.stop();
+        }
+    }
+    
+    /// Take the NDI source off the network, if it is on
+    fn stop_ndi(&mut self) {
+        if let Some(sink) = self.ndi.take() {
+            self.connect_streams();
+            sink.stop();
+        }
+    }
+    
+    /// Stop feeding the virtual camera, if it is on
+    fn stop_virtual_camera(&mut self) {
+        if let Some(camera) = self.virtual_camera.take() {
+            self.connect_streams();
+            camera.stop();
+        }
+    }
+    
+    /// Start the HLS preview, streams from the start, the NDI source and the
+    /// virtual camera, which are fed by `encoder` and so start with it;
+    /// returns the HLS playlist
+    fn start_sinks(
+        &mut self,
+        config: &RecordingConfig,
+        hls_dir: Option<&Path>,
+        encoder: &Encoder,
+    ) -> Result<Option<PathBuf>, RecordingError> {
+        let mut hls_playlist = None;
+        if let (Some(hls), Some(dir)) = (&config.hls, hls_dir) {
+            self.hls = Some(StreamingSink::start(hls.target(dir)?, encoder.stream_format())?);
+            hls_playlist = Some(dir.join(HLS_PLAYLIST));
+        }
+        if let Some(ref stream) = config.stream {
+            self.streaming = Some(StreamingSink::start(stream.target()?, encoder.stream_format())?);
+        }
+        if let Some(ref icecast) = config.icecast {
+            let format = encoder.stream_format();
+            self.icecast = Some(StreamingSink::start(icecast.target(format.sample_rate)?, format)?);
+        }
+        if let Some(ref ndi) = config.ndi {
+            self.ndi = Some(NdiSink::start(ndi, encoder.stream_format())?);
+        }
+        if let Some(ref camera) = config.virtual_camera {
+            self.virtual_camera = Some(VirtualCamera::start(camera, encoder.stream_format())?);
+        }
+        Ok(hls_playlist)
+    }
+    
+    /// Stop every stream and output the encoder feeds
+    fn stop_sinks(&mut self) {
+        self.stop_streaming();
+        self.stop_webrtc();
+        self.stop_hls();
+        self.stop_icecast();
+        self.stop_ndi();
+        self.stop_virtual_camera();
+    }
+    
+    /// Hand the encoder's input to every running stream
+    fn connect_streams(&self) {
+        if let Some(ref encoder) = self.encoder {
+            let inputs = self
+                .streaming
+                .iter()
+                .chain(&self.hls)
+                .chain(self.webrtc.iter().map(|(sink, _)| sink))
+                .chain(&self.icecast)
+                .map(|sink| sink.inputs())
+                .chain(self.ndi.iter().map(|sink| sink.inputs()))
+                .chain(self.virtual_camera.iter().map(|camera| camera.inputs()))
+                .collect();
+            encoder.set_stream_inputs(inputs);
+        }
+    }
+    
+    /// Schedule the recording to end exactly at `timestamp_ms` from its start
+    pub fn stop_at(&mut self, timestamp_ms: u64) -> Result<(), RecordingError> {
+        let encoder = self.encoder.as_ref().ok_or(RecordingError::NotRecording)?;
+        
+        let elapsed_ms = self.status.lock().duration_ms;
+        if timestamp_ms <= elapsed_ms {
+            return Err(format!(
+                "Stop time {}ms has already passed (recording is at {}ms)",
+                timestamp_ms, elapsed_ms
+            ).into());
+        }
+        
+        encoder.stop_at(timestamp_ms);
+        self.scheduled_stop_ms = Some(timestamp_ms);
+        println!("Recording will stop at {}ms", timestamp_ms);
+        
+        Ok(())
+    }
+    
+    /// Mark the current point of the recording; written as a chapter
+    pub fn add_marker(&mut self, label: &str) -> Result<Marker, RecordingError> {
+        let encoder = self.encoder.as_ref().ok_or(RecordingError::NotRecording)?;
+        
+        let marker = Marker {
+            at_ms: self.status.lock().duration_ms,
+            label: label.trim().to_string(),
+        };
+        encoder.add_marker(marker.clone());
+        println!("Marker '{}' at {}ms", marker.label, marker.at_ms);
+        
+        Ok(marker)
+    }
+    
+    /// Apply non-structural settings to the running pipeline
+    pub fn update_active_config(&mut self, update: &ActiveConfigUpdate) -> Result<(), RecordingError> {
+        update.validate()?;
+        
+        let config = self.config.as_ref().ok_or(RecordingError::NotRecording)?;
+        if let Some(ref visible) = update.text_overlays_visible {
+            if visible.len() != config.text_overlays.len() {
+                return Err(format!(
+                    "textOverlaysVisible needs one entry per text overlay ({} given, {} recorded)",
+                    visible.len(),
+                    config.text_overlays.len()
+                ).into());
+            }
+        }
+        let timer_index = config.timer_overlay.enabled.then_some(config.text_overlays.len());
+        
+        // The mouse is only polled once the highlight is first turned on
+        if let Some(ref highlight) = update.cursor_highlight {
+            if highlight.enabled && self.cursor.is_none() {
+                let display_index = self.display_index
+                    .ok_or("The cursor highlight needs the screen to be captured")?;
+                let app = self.app_handle.as_ref().ok_or("The cursor highlight needs the app handle")?;
+                let bounds = DisplayBounds::of_display(app, display_index)?;
+                self.cursor = Some(CursorTracker::start(bounds)?);
+            }
+        }
+        
+        let config = self.config.as_mut()
+            .ok_or(RecordingError::NotRecording)?;
+        
+        if let Some(ref mixer) = self.audio_mixer {
+            mixer.set_volumes(update.mic_volume, update.system_volume);
+        }
+        
+        if let Some(ref compositor) = self.compositor {
+            let mut compositor = compositor.lock();
+            compositor.set_pip_layout(update.webcam_position, update.webcam_size);
+            compositor.set_pip_placement(update.webcam_x, update.webcam_y, update.webcam_width);
+            if let Some(fit) = update.webcam_fit {
+                compositor.set_pip_fit(fit);
+            }
+            if let Some(shape) = update.webcam_shape {
+                compositor.set_pip_shape(shape);
+            }
+            if let Some(chroma_key) = update.webcam_chroma_key {
+                compositor.set_chroma_key(chroma_key);
+            }
+            if let Some(mirror) = update.mirror_webcam {
+                compositor.set_mirror_webcam(mirror);
+            }
+            if let Some(visible) = update.webcam_visible {
+                compositor.set_webcam_visible(visible && config.capture_webcam);
+            }
+            if let Some(visible) = update.overlays_visible {
+                compositor.set_overlays_visible(visible);
+            }
+            for (index, &visible) in update.text_overlays_visible.iter().flatten().enumerate() {
+                compositor.set_text_overlay_visible(index, visible);
+            }
+            if let (Some(visible), Some(index)) = (update.timer_visible, timer_index) {
+                compositor.set_text_overlay_visible(index, visible);
+            }
+            if let Some(visible) = update.watermark_visible {
+                compositor.set_watermark_visible(visible);
+            }
+            if let Some(ref highlight) = update.cursor_highlight {
+                let overlay = self.cursor.as_ref()
+                    .filter(|_| highlight.enabled)
+                    .map(|cursor| cursor.overlay(highlight.clone()));
+                compositor.set_cursor_overlay(overlay);
+            }
+        }
+        
+        // Keep the stored config in sync with what is being recorded
+        if let Some(volume) = update.mic_volume {
+            config.mic_volume = volume;
+        }
+        if let Some(volume) = update.system_volume {
+            config.system_volume = volume;
+        }
+        if let Some(position) = update.webcam_position {
+            config.webcam_position = position;
+            config.webcam_x = None;
+            config.webcam_y = None;
+        }
+        if let Some(size) = update.webcam_size {
+            config.webcam_size = size;
+            config.webcam_width = None;
+        }
+        if update.webcam_x.is_some() {
+            config.webcam_x = update.webcam_x;
+        }
+        if update.webcam_y.is_some() {
+            config.webcam_y = update.webcam_y;
+        }
+        if update.webcam_width.is_some() {
+            config.webcam_width = update.webcam_width;
+        }
+        if let Some(fit) = update.webcam_fit {
+            config.webcam_fit = fit;
+        }
+        if let Some(shape) = update.webcam_shape {
+            config.webcam_shape = shape;
+        }
+        if let Some(chroma_key) = update.webcam_chroma_key {
+            config.webcam_chroma_key = chroma_key;
+        }
+        if let Some(mirror) = update.mirror_webcam {
+            config.mirror_webcam = mirror;
+        }
+        if let Some(ref highlight) = update.cursor_highlight {
+            config.cursor_highlight = highlight.clone();
+        }
+        
+        println!("Active recording config updated: {:?}", update);
+        
+        Ok(())
+    }
+    
+    /// The compositor's layer stack, bottom first
+    pub fn compositor_layers(&self) -> Result<Vec<Layer>, RecordingError> {
+        let compositor = self.compositor.as_ref().ok_or(RecordingError::NotRecording)?;
+        Ok(compositor.lock().layers())
+    }
+    
+    /// Reorder layers or change their opacity, returning the new stack
+    pub fn set_layer_properties(&mut self, updates: &[LayerUpdate]) -> Result<Vec<Layer>, RecordingError> {
+        let compositor = self.compositor.as_ref().ok_or(RecordingError::NotRecording)?;
+        let mut compositor = compositor.lock();
+        compositor.set_layer_properties(updates)?;
+        Ok(compositor.layers())
+    }
+    
+    /// Check the settings that don't depend on devices or output paths
+    fn validate_config(config: &RecordingConfig) -> Result<(), String> {
+        if !SUPPORTED_SAMPLE_RATES.contains(&config.audio_sample_rate) {
+            return Err(format!("Unsupported audio sample rate: {}Hz", config.audio_sample_rate));
+        }
+        config.output_format.validate_sample_rate(config.audio_sample_rate)?;
+        config.output_format.validate_audio_codec(config.audio_codec)?;
+        config.output_format.validate_codec_sample_rate(config.audio_codec, config.audio_sample_rate)?;
+        if let Some(depth) = config.audio_bit_depth {
+            depth.validate_for(config.audio_codec)?;
+        }
+        if let Some(kbps) = config.audio_bitrate_kbps {
+            validate_audio_bitrate(kbps, config.audio_codec)?;
+        }
+        if let Some(target) = config.normalize_lufs {
+            validate_target(target)?;
+        }
+        if let Some(ref advanced) = config.advanced_quality {
+            advanced.validate(config.output_format)?;
+        }
+        validate_pip_placement(config.webcam_x, config.webcam_y, config.webcam_width)?;
+        config.webcam_chroma_key.validate()?;
+        if let Some(ref watermark) = config.watermark {
+            watermark.validate()?;
+        }
+        for overlay in &config.text_overlays {
+            overlay.validate()?;
+        }
+        if config.timer_overlay.enabled {
+            config.timer_overlay.text_overlay().validate()?;
+        }
+        config.cursor_highlight.validate()?;
+        if config.cursor_highlight.enabled && !config.capture_screen {
+            return Err("The cursor highlight needs the screen to be captured".to_string());
+        }
+        config.audio_track.validate()?;
+        config.metadata.validate()?;
+        validate_failover_dir(config.failover_dir.as_ref())?;
+        if let Some(ref hls) = config.hls {
+            hls.validate()?;
+        }
+        if let Some(ref icecast) = config.icecast {
+            icecast.target(config.audio_sample_rate)?;
+        }
+        if let Some(ref ndi) = config.ndi {
+            ndi.validate()?;
+        }
+        if let Some(ref camera) = config.virtual_camera {
+            camera.validate()?;
+        }
+        if let Some(ref stream) = config.stream {
+            stream.validate()?;
+        }
+        if let Some(ref id) = config.append_session_id {
+            validate_session_id(id)?;
+            // Per-file outputs would overwrite the earlier sitting's
+            if config.sidecar_wav.enabled || config.captions.write_vtt {
+                return Err("Sidecar WAV and caption files aren't available for append sessions".to_string());
+            }
+        }
+        config.captions.validate()?;
+        config.queue_watermarks.validate()?;
+        config.adaptive_quality.validate()?;
+        config.queue_memory.validate()?;
+        config.capture_watchdog.validate()?;
+        config.silence.validate()?;
+        config.mastering.validate()?;
+        config.noise_gate.validate()?;
+        if config.mastering.loudness_target().is_some() && config.normalize_lufs.is_some() {
+            return Err("Set the loudness target in either the mastering chain or normalizeLufs, not both".to_string());
+        }
+        config.audio_buffer.validate()?;
+        config.capture_dump.validate()?;
+        if let Some(ref dual) = config.dual_mic {
+            dual.validate()?;
+            if !config.capture_mic || config.audio_channels != AudioChannelLayout::Binaural {
+                return Err("Ear microphones need the microphone captured in binaural mode".to_string());
+            }
+            if config.mic_channel_map.is_some() {
+                return Err("A channel map can't be used with ear microphones".to_string());
+            }
+        }
+        config.synthetic_sources.validate(config.audio_sample_rate)?;
+        if config.captions.enabled && !config.capture_mic {
+            return Err("Live captions need the microphone to be captured".to_string());
+        }
+        if config.sidecar_wav.enabled
+            && config.sidecar_wav.source == SidecarWavSource::Mic
+            && !config.capture_mic
+        {
+            return Err("A microphone sidecar WAV needs the microphone to be captured".to_string());
+        }
+        Ok(())
     }
     
     /// Start recording with the given configuration
-    pub fn start(&mut self, config: RecordingConfig) -> Result<(), String> {
+    pub fn start(&mut self, config: RecordingConfig) -> Result<StartRecordingResult, RecordingError> {
         // Check if already recording
         if self.status.lock().is_recording {
-            return Err("Recording already in progress".to_string());
+            return Err(RecordingError::AlreadyRecording);
         }
         
         // Validate configuration
         if !config.capture_screen && !config.capture_webcam {
-            return Err("At least one video source must be enabled".to_string());
+            return Err(RecordingError::NoVideoSource);
+        }
+        Self::validate_config(&config).map_err(RecordingError::InvalidConfig)?;
+        
+        // Paths from the frontend must stay inside the allowed directories
+        let allowed_dirs = allowed_output_dirs();
+        let requested_path = config
+            .output_path
+            .as_deref()
+            .map(|path| check_output_path(path, &allowed_dirs))
+            .transpose()?;
+        if let Some(ref dir) = config.failover_dir {
+            check_output_dir(dir, &allowed_dirs)?;
         }
+        let hls_dir = config
+            .hls
+            .as_ref()
+            .map(|hls| check_output_dir(&hls.directory, &allowed_dirs))
+            .transpose()?;
         
         // Generate output path if not provided
-        let output_path = config.output_path.clone().unwrap_or_else(|| {
-            let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
-            let filename = format!("recording_{}.mp4", timestamp);
+        let output_path = requested_path.unwrap_or_else(|| {
+            let filename = match config.append_session_id {
+                Some(ref id) => session_file_name(id, config.output_format),
+                None => {
+                    let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
+                    format!("recording_{}.{}", timestamp, config.output_format.extension())
+                }
+            };
             
             // In debug/dev mode, save to test-results directory
             #[cfg(debug_assertions)]
@@ -107,66 +772,145 @@ impl RecordingManager {
                     .join(filename)
             }
         });
+        preflight_output(&output_path)?;
+        
+        self.completed_output = None;
+        self.output_verification = None;
         
         // Reset stop signal
         *self.stop_signal.lock() = false;
         
-        // Get output dimensions from config (always 16:9)
-        let (output_width, output_height) = config.output_resolution.dimensions();
+        // Resolve devices, preferring the requested ones, then the last used ones.
+        // Missing devices fall back to the default and are reported to the caller.
+        let remembered = AppSettings::load().last_devices;
+        let mut fallback_applied = Vec::new();
+        let mut used_devices = remembered.clone();
+        
+        // Synthetic sources stand in for every device, unless a dump is
+        // replayed instead
+        if config.synthetic_sources.enabled && self.replay.is_none() {
+            let sources = synthetic_sources::backends(&config);
+            self.screen_capture = sources.screen.map(|s| Box::new(s) as Box<dyn ScreenCaptureBackend>);
+            self.webcam_capture = sources.webcam.map(|s| Box::new(s) as Box<dyn WebcamCaptureBackend>);
+            self.mic_capture = sources.mic.map(|s| Box::new(s) as Box<dyn MicrophoneCaptureBackend>);
+            self.system_audio_capture = sources.system.map(|s| Box::new(s) as Box<dyn AudioCaptureBackend>);
+        }
         
-        // Initialize screen capture if enabled
-        if config.capture_screen {
+        // Initialize screen capture if enabled (a replay or synthetic
+        // sources have no devices)
+        let replaying = self.replay.is_some() || config.synthetic_sources.enabled;
+        if config.capture_screen && !replaying {
+            let screen_id = config.screen_device_id.clone().or(remembered.screen.clone());
+            let (display_index, fallback) = resolve_screen(screen_id.as_deref());
+            match fallback {
+                Some(notice) => fallback_applied.push(notice),
+                None => used_devices.screen = screen_id,
+            }
+            
+            // The webcam overlay composites in RGBA, so capture BGRA for it
+            let pixel_format = if config.capture_webcam
+                && config.capture_pixel_format == CapturePixelFormat::Nv12
+            {
+                println!("Webcam overlay enabled, capturing BGRA instead of NV12");
+                CapturePixelFormat::Bgra
+            } else {
+                config.capture_pixel_format
+            };
+            
             let screen_config = ScreenCaptureConfig {
                 fps: config.frame_rate.unwrap_or(30),
-                display_index: 0,
+                display_index,
+                pixel_format,
             };
             
             let screen_capture = ScreenCapture::new(screen_config)
-                .map_err(|e| {
-                    let lower = e.to_lowercase();
-                    if lower.contains("permission") || lower.contains("screen recording") {
-                        "Screen Recording permission required. Open System Settings → Privacy & Security → Screen Recording and enable access for this app.".to_string()
-                    } else {
-                        format!("Failed to initialize screen capture: {}", e)
-                    }
-                })?;
+                .map_err(|e| screen_capture_error("Failed to initialize screen capture", e))?;
             
-            self.screen_capture = Some(screen_capture);
+            self.screen_capture = Some(Box::new(screen_capture));
+            self.display_index = Some(display_index);
+            
+            if config.cursor_highlight.enabled {
+                let app = self.app_handle.as_ref().ok_or("The cursor highlight needs the app handle")?;
+                let bounds = DisplayBounds::of_display(app, display_index)?;
+                self.cursor = Some(CursorTracker::start(bounds)?);
+            }
+        }
+        
+        // Get output dimensions from config (a 16:9 preset or the captured display size)
+        let source_size = match self.replay {
+            Some(ref replay) => replay.source_size(),
+            None => self.screen_capture.as_ref().map(|capture| capture.dimensions()),
+        };
+        if config.output_resolution == OutputResolution::MatchSource && source_size.is_none() {
+            println!("No screen to match, recording at the default resolution");
         }
+        let (output_width, output_height) = config.output_resolution.resolve(source_size);
+        println!("Output resolution: {}x{}", output_width, output_height);
         
         // Initialize webcam capture if enabled
-        if config.capture_webcam {
+        if config.capture_webcam && !replaying {
+            let webcam_id = config.webcam_device_id.clone().or(remembered.webcam.clone());
+            let (device_index, fallback) = resolve_webcam(webcam_id.as_deref());
+            match fallback {
+                Some(notice) => fallback_applied.push(notice),
+                None => used_devices.webcam = webcam_id,
+            }
+            
+            // The camera can only be opened once
+            self.webcam_preview.stop();
+            
             let webcam_config = WebcamCaptureConfig {
                 fps: config.frame_rate.unwrap_or(30),
                 width: 640,
                 height: 480,
-                device_index: 0,
+                device_index,
             };
             
             let webcam_capture = WebcamCapture::new(webcam_config)
-                .map_err(|e| format!("Failed to initialize webcam: {}", e))?;
+                .map_err(|e| RecordingError::Device(format!("Failed to initialize webcam: {}", e)))?;
             
-            self.webcam_capture = Some(webcam_capture);
+            self.webcam_capture = Some(Box::new(webcam_capture));
+        }
+        
+        // Each ear from its own microphone, merged once capture starts
+        if let Some(dual) = config.dual_mic.as_ref().filter(|_| config.capture_mic && !replaying) {
+            let (left, right) = open_ear_microphones(dual, &config)?;
+            self.mic_capture = Some(Box::new(left));
+            self.right_ear_capture = Some(right);
         }
         
         // Initialize microphone capture if enabled
-        if config.capture_mic {
-            let mic_config = MicrophoneCaptureConfig::default();
+        if config.capture_mic && !replaying && config.dual_mic.is_none() {
+            let mic_id = config.mic_device_id.clone().or(remembered.microphone.clone());
+            let (device_name, fallback) = resolve_microphone(mic_id.as_deref());
+            match fallback {
+                Some(notice) => fallback_applied.push(notice),
+                None => used_devices.microphone = mic_id,
+            }
+            
+            let mic_config = MicrophoneCaptureConfig {
+                device_name,
+                sample_rate: Some(config.audio_sample_rate),
+                channel_layout: config.audio_channels,
+                channel_map: config.mic_channel_map.clone(),
+                buffer_frames: config.audio_buffer.device_buffer_frames,
+                epoch: None,
+            };
             
             let mic_capture = MicrophoneCapture::new(mic_config)
-                .map_err(|e| format!("Failed to initialize microphone: {}", e))?;
+                .map_err(|e| RecordingError::Device(format!("Failed to initialize microphone: {}", e)))?;
             
-            self.mic_capture = Some(mic_capture);
+            self.mic_capture = Some(Box::new(mic_capture));
         }
         
         // Initialize system audio capture if enabled
-        if config.capture_system_audio {
+        if config.capture_system_audio && !replaying {
             let sys_config = SystemAudioCaptureConfig::default();
             
             match SystemAudioCapture::new(sys_config) {
                 Ok(sys_capture) => {
                     if sys_capture.is_available() {
-                        self.system_audio_capture = Some(sys_capture);
+                        self.system_audio_capture = Some(Box::new(sys_capture));
                     } else {
                         println!("System audio capture not available on this platform");
                     }
@@ -184,33 +928,159 @@ impl RecordingManager {
             include_webcam: config.capture_webcam,
             pip_position: config.webcam_position,
             pip_size_percent: config.webcam_size,
+            pip_x: config.webcam_x,
+            pip_y: config.webcam_y,
+            pip_width: config.webcam_width,
             pip_padding: 20,
+            pip_fit: config.webcam_fit,
+            pip_shape: config.webcam_shape,
+            chroma_key: config.webcam_chroma_key,
+            mirror_webcam: config.mirror_webcam,
+            watermark: config.watermark.clone(),
+            use_gpu: config.gpu_compositing,
         };
         
-        self.compositor = Some(VideoCompositor::new(compositor_config));
+        let mut compositor = VideoCompositor::new(compositor_config);
+        let timer = config.timer_overlay.enabled.then(|| config.timer_overlay.text_overlay());
+        let text_overlays = config
+            .text_overlays
+            .iter()
+            .cloned()
+            .chain(timer)
+            .map(|overlay| TextOverlay::new(overlay, &config.metadata))
+            .collect::<Result<Vec<_>, _>>()?;
+        compositor.set_text_overlays(text_overlays);
+        if let Some(ref cursor) = self.cursor {
+            compositor.set_cursor_overlay(Some(cursor.overlay(config.cursor_highlight.clone())));
+        }
+        self.compositor = Some(Arc::new(Mutex::new(compositor)));
         
         // Initialize audio mixer
-        let mixer_config = AudioMixerConfig::default();
+        let mixer_config = AudioMixerConfig {
+            sample_rate: config.audio_sample_rate,
+            channels: config.audio_channels.channel_count(),
+            mic_volume: config.mic_volume,
+            system_volume: config.system_volume,
+            metering: self.metering_config,
+            binaural: config.audio_channels == AudioChannelLayout::Binaural,
+            buffer_size: config.audio_buffer.mixer_chunk_frames as usize,
+            noise_gate: config.noise_gate,
+        };
         self.audio_mixer = Some(AudioMixer::new(mixer_config));
         
+        // Start live captions before capture so no speech is missed
+        if config.captions.enabled {
+            let engine = CaptionEngine::start(
+                &config.captions,
+                &output_path,
+                config.audio_sample_rate,
+                config.audio_channels.channel_count(),
+            )?;
+            if config.captions.burn_in {
+                let overlay = CaptionOverlay::new(engine.text(), config.captions.font_path.as_deref())?;
+                if let Some(ref compositor) = self.compositor {
+                    compositor.lock().set_caption_overlay(Some(overlay));
+                }
+            }
+            if let Some(ref mixer) = self.audio_mixer {
+                mixer.set_caption_sender(Some(engine.sender()));
+            }
+            self.captions = Some(engine);
+        }
+    
+        // The sidecar WAV is fed by the mixer, so it starts with the audio
+        if config.sidecar_wav.enabled {
+            let sidecar = SidecarWav::start(
+                &config.sidecar_wav,
+                &output_path,
+                config.audio_sample_rate,
+                config.audio_channels.channel_count(),
+                config.audio_bit_depth.unwrap_or(AudioBitDepth::Float32),
+            )?;
+            if let Some(ref mixer) = self.audio_mixer {
+                mixer.set_sidecar_tap(Some(sidecar.tap()));
+            }
+            self.sidecar_wav = Some(sidecar);
+        }
+        
         // Initialize encoder with 16:9 output resolution
+        // Continuing an append session records to a part file that is joined
+        // onto the earlier sitting when the encoder finishes
+        let append_to = config.append_session_id.as_ref()
+            .and_then(|_| append_part_path(&output_path))
+            .map(|part| (part, output_path.clone()));
+        // Shared by the adaptive quality governor, the encoder and the compositor
+        self.quality_controls = config.adaptive_quality.enabled
+            .then(|| Arc::new(QualityControls::default()));
         let encoder_config = EncoderConfig {
-            output_path: output_path.to_string_lossy().to_string(),
+            output_path: append_to.as_ref()
+                .map_or(output_path.as_path(), |(part, _)| part.as_path())
+                .to_string_lossy()
+                .to_string(),
             width: output_width,
             height: output_height,
             frame_rate: config.frame_rate.unwrap_or(30),
             quality: config.video_quality,
-            audio_sample_rate: 48000,
-            audio_channels: 2,
+            audio_sample_rate: config.audio_sample_rate,
+            audio_channels: config.audio_channels.channel_count(),
+            binaural: config.audio_channels == AudioChannelLayout::Binaural,
+            hardware_acceleration: config.hardware_encoding,
+            output_format: config.output_format,
+            audio_codec: config.audio_codec,
+            audio_bitrate_kbps: config.audio_bitrate_kbps,
+            audio_bit_depth: config.audio_bit_depth,
+            normalize_lufs: config.normalize_lufs,
+            silence: config.silence.clone(),
+            mastering: config.mastering.clone(),
+            quality_controls: self.quality_controls.clone(),
+            advanced_quality: config.advanced_quality.clone(),
+            failover_dir: config.failover_dir.clone(),
+            fragmented: config.append_session_id.is_some(),
+            append_to: append_to.map(|(_, session)| session),
+            metadata: config.metadata.clone(),
+            audio_track: config.audio_track.with_default_title(
+                config.capture_mic,
+                config.capture_system_audio,
+                config.audio_channels,
+            ),
         };
         
-        self.encoder = Some(Encoder::new(encoder_config));
+        let encoder = Encoder::new(encoder_config);
+        
+        // A sink left running by a failed start would keep its connection open
+        let hls_playlist = self
+            .start_sinks(&config, hls_dir.as_deref(), &encoder)
+            .inspect_err(|_| self.stop_sinks())?;
+        self.encoder = Some(encoder);
+        self.connect_streams();
+        
+        if config.capture_dump.enabled {
+            let dump = CaptureDump::start(&output_path, &config, source_size).inspect_err(|_| self.stop_sinks())?;
+            self.capture_dump = Some(dump);
+        }
         
         // Store config BEFORE starting pipeline (needed by compositor thread)
         self.config = Some(config);
         
         // Connect components and start capture
-        self.start_capture_pipeline()?;
+        self.start_capture_pipeline().inspect_err(|_| self.stop_sinks())?;
+        
+        // Monitoring is optional; a missing output device must not stop the recording
+        let monitor_config = self.config.as_ref()
+            .filter(|c| c.capture_mic)
+            .and_then(|c| c.mic_monitor.clone());
+        if let Some(monitor_config) = monitor_config {
+            if let Err(e) = self.set_mic_monitor(Some(monitor_config)) {
+                eprintln!("Mic monitoring unavailable: {}", e);
+            }
+        }
+        
+        let visualizer_config = self.config.as_ref().and_then(|c| c.audio_visualizer.clone());
+        if let Some(visualizer_config) = visualizer_config {
+            if let Err(e) = self.set_audio_visualizer(Some(visualizer_config)) {
+                eprintln!("Audio visualizer unavailable: {}", e);
+            }
+        }
         
         // Update status
         {
@@ -218,29 +1088,98 @@ impl RecordingManager {
             status.is_recording = true;
             status.duration_ms = 0;
             status.frame_count = 0;
-            status.output_path = Some(output_path);
+            status.output_path = Some(output_path.clone());
             status.error = None;
+            status.events.clear();
+            status.streaming = self.streaming.is_some();
+            status.webcam = None;
+            status.microphone = None;
+        }
+        
+        // Remember devices for next time. Devices that fell back keep their
+        // remembered ID so they are picked up again once reconnected.
+        if let Err(e) = AppSettings::update(|s| s.last_devices = used_devices) {
+            eprintln!("Failed to save last used devices: {}", e);
         }
         
         println!("Recording manager started");
         
-        Ok(())
+        Ok(StartRecordingResult {
+            output_path: Some(output_path),
+            fallback_applied,
+            hls_playlist,
+        })
     }
     
-    /// Start the capture pipeline
-    fn start_capture_pipeline(&mut self) -> Result<(), String> {
-        // Get receivers from capture components
-        let screen_receiver = self.screen_capture.as_mut()
-            .and_then(|c| c.take_receiver());
+    /// Record a raw capture dump again, from the dump instead of the devices
+    ///
+    /// Uses the dumped recording's config with `output_path` (a new generated
+    /// name if None, never the original file). The recording stops by itself
+    /// at the end of the dump, which must be inside an allowed output
+    /// directory.
+    pub fn start_replay(
+        &mut self,
+        dump_dir: &Path,
+        output_path: Option<PathBuf>,
+    ) -> Result<StartRecordingResult, RecordingError> {
+        let dump_dir = check_output_dir(dump_dir, &allowed_output_dirs())?;
+        let replay = CaptureReplay::open(&dump_dir)?;
+        let duration_ms = replay.duration().as_millis() as u64;
         
-        let webcam_receiver = self.webcam_capture.as_mut()
-            .and_then(|c| c.take_receiver());
+        let mut config = replay.config().clone();
+        config.output_path = output_path;
+        config.append_session_id = None;
+        config.capture_dump.enabled = false;
+        config.mic_monitor = None;
         
-        let mic_receiver = self.mic_capture.as_mut()
-            .and_then(|c| c.take_receiver());
+        self.replay = Some(replay);
+        let result = self.start(config);
+        self.replay = None;
+        let result = result?;
         
-        let system_receiver = self.system_audio_capture.as_mut()
-            .and_then(|c| c.take_receiver());
+        if duration_ms > 0 {
+            self.stop_at(duration_ms)?;
+        }
+        println!("Replaying {} ({}ms)", dump_dir.display(), duration_ms);
+        
+        Ok(result)
+    }
+    
+    /// Start the capture pipeline
+    fn start_capture_pipeline(&mut self) -> Result<(), RecordingError> {
+        // Get receivers from the dump being replayed or the capture
+        // components, devices or synthetic
+        let ReplaySources {
+            screen: mut screen_receiver,
+            webcam: mut webcam_receiver,
+            mic: mut mic_receiver,
+            system: mut system_receiver,
+        } = match self.replay.take() {
+            Some(replay) => replay.start(self.stop_signal.clone()),
+            None => ReplaySources {
+                screen: self.screen_capture.as_mut().and_then(|c| c.take_receiver()),
+                webcam: self.webcam_capture.as_mut().and_then(|c| c.take_receiver()),
+                mic: self.mic_capture.as_mut().and_then(|c| c.take_receiver()),
+                system: self.system_audio_capture.as_mut().and_then(|c| c.take_receiver()),
+            },
+        };
+        
+        // Two ear microphones become one binaural microphone
+        if let Some(right) = self.right_ear_capture.as_mut().and_then(|c| c.take_receiver()) {
+            let config = self.config.as_ref().ok_or("No recording config")?;
+            mic_receiver = match (mic_receiver, config.dual_mic.as_ref()) {
+                (Some(left), Some(dual)) => Some(dual_mic::merge_ears(left, right, config.audio_sample_rate, dual)),
+                (left, _) => left,
+            };
+        }
+        
+        // Copy everything captured to the dump on the way through
+        if let Some(ref dump) = self.capture_dump {
+            screen_receiver = screen_receiver.map(|r| dump.tap(r, DumpRecord::Screen));
+            webcam_receiver = webcam_receiver.map(|r| dump.tap(r, DumpRecord::Webcam));
+            mic_receiver = mic_receiver.map(|r| dump.tap_audio(r, DumpRecord::Mic));
+            system_receiver = system_receiver.map(|r| dump.tap_audio(r, DumpRecord::System));
+        }
         
         // Connect audio sources to mixer
         if let Some(ref mut mixer) = self.audio_mixer {
@@ -260,8 +1199,56 @@ impl RecordingManager {
         // At 30fps, 120 frames = 4 seconds of buffer
         let (composite_sender, composite_receiver) = bounded::<CompositeFrame>(120);
 
+        // The encoder's input queues, watched through receiver clones
+        let mut watched_queues = vec![(WatchedQueue::Composite, channel_probe(composite_receiver.clone()))];
+        if let Some(ref receiver) = mixed_audio_receiver {
+            watched_queues.push((WatchedQueue::Audio, channel_probe(receiver.clone())));
+        }
+        let governor_queue = channel_probe(composite_receiver.clone());
+
+        // Frames are counted at the size they are captured or composited at
+        let format = self.encoder.as_ref()
+            .map(|e| e.stream_format())
+            .ok_or("Encoder not initialized")?;
+        let mut queue_memory = QueueMemory::new(self.config.as_ref().map(|c| c.queue_memory).unwrap_or_default());
+        if let Some(ref receiver) = screen_receiver {
+            let (width, height) = match self.screen_capture {
+                Some(ref capture) => capture.dimensions(),
+                None => (format.width, format.height),
+            };
+            queue_memory.watch(receiver.clone(), frame_bytes(width, height));
+        }
+        if let Some(ref receiver) = webcam_receiver {
+            // Webcam frames are 640x480 RGB
+            queue_memory.watch(receiver.clone(), 640 * 480 * 3);
+        }
+        queue_memory.watch(composite_receiver.clone(), frame_bytes(format.width, format.height));
+        if let Some(ref receiver) = mixed_audio_receiver {
+            let chunk_frames = self.config.as_ref().map_or(1024, |c| c.audio_buffer.mixer_chunk_frames);
+            queue_memory.watch(receiver.clone(), chunk_frames as u64 * format.channels as u64 * 4);
+        }
+        self.queue_memory = Some(Arc::new(queue_memory));
+        self.capture_watchdog = Some(CaptureWatchdog::new(
+            self.config.as_ref().map(|c| c.capture_watchdog).unwrap_or_default(),
+        ));
+
+        // The compositor's input is the screen, or the webcam without one
+        let capture_screen = self.config.as_ref().is_some_and(|c| c.capture_screen);
+        let compositor_queue = if capture_screen {
+            screen_receiver.clone().map(channel_probe)
+        } else {
+            webcam_receiver.clone().map(channel_probe)
+        };
+        let frame_counters = Arc::new(FrameCounters::default());
+        self.pipeline = Some(PipelineProbe::new(
+            frame_counters.clone(),
+            compositor_queue,
+            channel_probe(composite_receiver.clone()),
+        ));
+        
         // Create channel for encoder errors
         let (error_sender, error_receiver) = bounded::<String>(1);
+        let (failover_sender, failover_receiver) = bounded::<OutputFailover>(1);
         
         // Connect encoder
         if let Some(ref mut encoder) = self.encoder {
@@ -270,20 +1257,33 @@ impl RecordingManager {
                 encoder.set_audio_receiver(receiver);
             }
             encoder.set_error_sender(error_sender);
+            if let Some(ref app) = self.app_handle {
+                encoder.set_finished_sink(output_check::app_sink(app.clone()));
+            }
+            encoder.set_failover_sender(failover_sender);
         }
         self.encoder_error_receiver = Some(error_receiver);
+        self.encoder_failover_receiver = Some(failover_receiver);
         
         // Start all components
         if let Some(ref capture) = self.screen_capture {
-            capture.start()?;
+            capture.start()
+                .map_err(|e| screen_capture_error("Failed to start screen capture", e))?;
         }
         
         if let Some(ref capture) = self.webcam_capture {
-            capture.start()?;
+            capture.start()
+                .map_err(|e| RecordingError::Device(format!("Failed to start webcam: {}", e)))?;
         }
         
         if let Some(ref capture) = self.mic_capture {
-            capture.start()?;
+            capture.start()
+                .map_err(|e| RecordingError::Device(format!("Failed to start microphone: {}", e)))?;
+        }
+        
+        if let Some(ref capture) = self.right_ear_capture {
+            capture.start()
+                .map_err(|e| RecordingError::Device(format!("Failed to start right ear microphone: {}", e)))?;
         }
         
         if let Some(ref capture) = self.system_audio_capture {
@@ -295,7 +1295,27 @@ impl RecordingManager {
         }
         
         if let Some(ref encoder) = self.encoder {
-            encoder.start()?;
+            encoder.start().map_err(RecordingError::Encoder)?;
+        }
+        
+        if let Some(ref app) = self.app_handle {
+            let config = self.config.as_ref().map(|c| c.queue_watermarks).unwrap_or_default();
+            self.queue_watch = Some(QueueWatch::start(config, watched_queues, app_sink(app.clone())));
+        }
+        
+        if let Some(controls) = self.quality_controls.clone() {
+            let config = self.config.as_ref().map(|c| c.adaptive_quality).unwrap_or_default();
+            let sink = match self.app_handle {
+                Some(ref app) => quality_governor::app_sink(app.clone()),
+                None => Box::new(|_| {}),
+            };
+            self.quality_governor = Some(QualityGovernor::start(
+                config,
+                QualityStep::ALL.to_vec(),
+                controls,
+                governor_queue,
+                sink,
+            ));
         }
         
         // Start compositor thread
@@ -303,6 +1323,7 @@ impl RecordingManager {
             screen_receiver,
             webcam_receiver,
             composite_sender,
+            frame_counters,
         )?;
         
         Ok(())
@@ -314,31 +1335,26 @@ impl RecordingManager {
         screen_receiver: Option<Receiver<ScreenFrame>>,
         webcam_receiver: Option<Receiver<WebcamFrame>>,
         composite_sender: Sender<CompositeFrame>,
+        frame_counters: Arc<FrameCounters>,
     ) -> Result<(), String> {
         let config = self.config.as_ref()
             .ok_or("No recording configuration")?;
         
-        // Use configured 16:9 output resolution
-        let (width, height) = config.output_resolution.dimensions();
-        
-        let compositor_config = CompositorConfig {
-            output_width: width,
-            output_height: height,
-            include_webcam: config.capture_webcam,
-            pip_position: config.webcam_position,
-            pip_size_percent: config.webcam_size,
-            pip_padding: 20,
-        };
-        
-        let compositor = VideoCompositor::new(compositor_config);
+        let compositor = self.compositor.clone()
+            .ok_or("Compositor not initialized")?;
         let running = self.compositor_running.clone();
         let stop_signal = self.stop_signal.clone();
         let status = self.status.clone();
+        let monitor_feed = self.monitor_feed.clone();
+        let quality_controls = self.quality_controls.clone();
+        let queue_memory = self.queue_memory.clone()
+            .ok_or("Queue memory not initialized")?;
         let capture_screen = config.capture_screen;
         
         *running.lock() = true;
         
-        std::thread::spawn(move || {
+        let thread = std::thread::spawn(move || {
+            thread_priority::elevate(ThreadRole::Compositor);
             compositor_loop(
                 running,
                 stop_signal,
@@ -347,17 +1363,24 @@ impl RecordingManager {
                 screen_receiver,
                 webcam_receiver,
                 composite_sender,
+                frame_counters,
+                monitor_feed,
+                quality_controls,
+                queue_memory,
                 capture_screen,
             );
         });
+        self.compositor_thread = Some(thread);
         
         Ok(())
     }
     
     /// Stop recording
-    pub fn stop(&mut self) -> Result<String, String> {
+    pub fn stop(&mut self) -> Result<String, RecordingError> {
         if !self.status.lock().is_recording {
-            return Err("No recording in progress".to_string());
+            // A scheduled stop may already have finished the file
+            return self.completed_output.take()
+                .ok_or(RecordingError::NotRecording);
         }
         
         // Signal stop
@@ -377,20 +1400,66 @@ impl RecordingManager {
             capture.stop();
         }
         
+        if let Some(ref capture) = self.right_ear_capture {
+            capture.stop();
+        }
+        
         if let Some(ref capture) = self.system_audio_capture {
             capture.stop();
         }
         
+        // The compositor's exit closes the encoder's frame channel
+        if let Some(thread) = self.compositor_thread.take() {
+            let _ = thread.join();
+        }
+        
+        if let Some(monitor) = self.mic_monitor.take() {
+            monitor.stop();
+        }
+        
+        if let Some(visualizer) = self.visualizer.take() {
+            visualizer.stop();
+        }
+        
+        // Mixes what the captures left in the rings, then closes the
+        // encoder's audio channel
         if let Some(ref mixer) = self.audio_mixer {
             mixer.stop();
         }
         
-        if let Some(ref encoder) = self.encoder {
-            let _ = encoder.stop();
+        if let Some(captions) = self.captions.take() {
+            captions.stop();
         }
         
-        // Wait a moment for threads to finish
-        std::thread::sleep(Duration::from_millis(500));
+        if let Some(cursor) = self.cursor.take() {
+            cursor.stop();
+        }
+        self.display_index = None;
+    
+        if let Some(sidecar) = self.sidecar_wav.take() {
+            sidecar.stop();
+        }
+        
+        if let Some(watch) = self.queue_watch.take() {
+            watch.stop();
+        }
+        
+        if let Some(governor) = self.quality_governor.take() {
+            governor.stop();
+        }
+        
+        if let Some(dump) = self.capture_dump.take() {
+            dump.stop();
+        }
+        
+        self.stop_sinks();
+        
+        // The encoder drains both channels and acknowledges the flush
+        let flushed = match self.encoder {
+            Some(ref encoder) => encoder.finish(),
+            None => Ok(()),
+        };
+        self.output_verification = self.encoder.as_ref().and_then(|e| e.verification());
         
         // Get output path before clearing
         let output_path = self.status.lock().output_path.clone();
@@ -399,6 +1468,8 @@ impl RecordingManager {
         {
             let mut status = self.status.lock();
             status.is_recording = false;
+            status.webcam = None;
+            status.microphone = None;
         }
         
         // Clear components
@@ -406,21 +1477,90 @@ impl RecordingManager {
         self.screen_capture = None;
         self.webcam_capture = None;
         self.mic_capture = None;
+        self.right_ear_capture = None;
         self.system_audio_capture = None;
         self.audio_mixer = None;
         self.compositor = None;
         self.encoder = None;
         self.encoder_error_receiver = None;
+        self.encoder_failover_receiver = None;
+        self.pipeline = None;
+        self.capture_watchdog = None;
+        self.queue_memory = None;
+        self.scheduled_stop_ms = None;
         
         println!("Recording manager stopped");
         
+        flushed.map_err(RecordingError::Encoder)?;
         output_path
             .map(|p| p.to_string_lossy().to_string())
-            .ok_or_else(|| "No output path".to_string())
+            .ok_or_else(|| RecordingError::Failed("No output path".to_string()))
+    }
+    
+    /// Take how the file `stop` finished checked out when read back
+    pub fn take_output_verification(&mut self) -> Option<OutputVerification> {
+        self.output_verification.take()
     }
 }
 
 impl RecordingManager {
+    /// Log a switch to the failover drive and point the status at the new file
+    fn handle_encoder_failover(&mut self) {
+        let Some(failover) = self
+            .encoder_failover_receiver
+            .as_ref()
+            .and_then(|r| r.try_recv().ok())
+        else {
+            return;
+        };
+
+        let mut status = self.status.lock();
+        status.events.push(RecordingEvent {
+            at_ms: failover.at_ms,
+            message: format!(
+                "Writing to {} failed ({}); continuing in {}",
+                failover.failed_path, failover.reason, failover.path
+            ),
+        });
+        status.output_path = Some(PathBuf::from(failover.path));
+    }
+
+    /// Note streams that ended by themselves; the recording carries on
+    fn handle_stream_errors(&mut self) {
+        // Drops and reconnects the stream rode out
+        let mut messages = self.streaming.as_ref().map_or_else(Vec::new, |s| s.notices());
+        if let Some(error) = self.streaming.as_ref().and_then(|s| s.error()) {
+            self.stop_streaming();
+            messages.push(format!("Streaming stopped: {}", error));
+        }
+        if let Some(error) = self.hls.as_ref().and_then(|s| s.error()) {
+            self.stop_hls();
+            messages.push(format!("HLS output stopped: {}", error));
+        }
+        if let Some(error) = self.icecast.as_ref().and_then(|s| s.error()) {
+            self.stop_icecast();
+            messages.push(format!("Icecast stream stopped: {}", error));
+        }
+        if let Some(error) = self.webrtc.as_ref().and_then(|(s, _)| s.error()) {
+            self.stop_webrtc();
+            messages.push(format!("Remote monitoring stopped: {}", error));
+        }
+        if let Some(error) = self.ndi.as_ref().and_then(|s| s.error()) {
+            self.stop_ndi();
+            messages.push(format!("NDI output stopped: {}", error));
+        }
+        if let Some(error) = self.virtual_camera.as_ref().and_then(|c| c.error()) {
+            self.stop_virtual_camera();
+            messages.push(format!("Virtual camera stopped: {}", error));
+        }
+
+        let mut status = self.status.lock();
+        let at_ms = status.duration_ms;
+        for message in messages {
+            status.events.push(RecordingEvent { at_ms, message });
+        }
+    }
+
     fn handle_encoder_errors(&mut self) {
         let error_message = match self.encoder_error_receiver.as_ref() {
             Some(receiver) => receiver.try_recv().ok(),
@@ -432,6 +1572,118 @@ impl RecordingManager {
         }
     }
 
+    /// Log the webcam going away and coming back
+    fn handle_webcam_state(&mut self) {
+        let Some(state) = self.webcam_capture.as_ref().map(|c| c.state()) else {
+            return;
+        };
+        let mut status = self.status.lock();
+        if status.webcam == Some(state) {
+            return;
+        }
+        // The first report is the starting state, not a change
+        if status.webcam.is_some() || state == WebcamState::Reconnecting {
+            let message = match state {
+                WebcamState::Reconnecting => "Webcam disconnected; showing a placeholder while reconnecting",
+                WebcamState::Connected => "Webcam reconnected",
+            };
+            let at_ms = status.duration_ms;
+            status.events.push(RecordingEvent {
+                at_ms,
+                message: message.to_string(),
+            });
+        }
+        status.webcam = Some(state);
+    }
+
+    /// Log microphone losses and device switches
+    fn handle_microphone_state(&mut self) {
+        let Some(state) = self.mic_capture.as_ref().map(|c| c.state()) else {
+            return;
+        };
+        let mut status = self.status.lock();
+        if status.microphone.as_ref() == Some(&state) {
+            return;
+        }
+        // The first report is the starting device, not a change
+        let message = match (&status.microphone, &state) {
+            (_, MicrophoneState::Reconnecting) => {
+                Some("Microphone lost; reconnecting when an input device is available".to_string())
+            }
+            (Some(MicrophoneState::Reconnecting), MicrophoneState::Connected { device }) => {
+                Some(format!("Microphone reconnected on '{}'", device))
+            }
+            (Some(MicrophoneState::Connected { .. }), MicrophoneState::Connected { device }) => {
+                Some(format!("Microphone switched to '{}'", device))
+            }
+            (None, MicrophoneState::Connected { .. }) => None,
+        };
+        if let Some(message) = message {
+            let at_ms = status.duration_ms;
+            status.events.push(RecordingEvent { at_ms, message });
+        }
+        status.microphone = Some(state);
+    }
+
+    /// Restart sources that stopped delivering, keeping the recording going
+    fn handle_capture_stalls(&mut self) {
+        let Some(watchdog) = self.capture_watchdog.as_mut() else {
+            return;
+        };
+        let now = Instant::now();
+        let mut messages = Vec::new();
+
+        if let Some(ref mut capture) = self.screen_capture {
+            let event = watchdog.check(WatchedSource::Screen, capture.heartbeats(), now);
+            if let Some(message) = stall_message(WatchedSource::Screen, event, || capture.restart()) {
+                messages.push(message);
+            }
+        }
+
+        if let Some(ref capture) = self.mic_capture {
+            // A microphone that is reconnecting by itself isn't stalled
+            if capture.state() == MicrophoneState::Reconnecting {
+                watchdog.reset(WatchedSource::Microphone, now);
+            } else {
+                let chunks = self.audio_mixer.as_ref()
+                    .and_then(|m| m.stats().mic)
+                    .map_or(0, |s| s.chunks + s.overflows);
+                let event = watchdog.check(WatchedSource::Microphone, chunks, now);
+                let restart = || {
+                    capture.reopen();
+                    Ok(())
+                };
+                if let Some(message) = stall_message(WatchedSource::Microphone, event, restart) {
+                    messages.push(message);
+                }
+            }
+        }
+
+        if messages.is_empty() {
+            return;
+        }
+        let mut status = self.status.lock();
+        let at_ms = status.duration_ms;
+        for message in messages {
+            eprintln!("{}", message);
+            status.events.push(RecordingEvent { at_ms, message });
+        }
+    }
+
+    /// Finish the recording once the encoder has reached a scheduled stop
+    fn handle_scheduled_stop(&mut self) {
+        if self.scheduled_stop_ms.is_none() {
+            return;
+        }
+        let finished = self.encoder.as_ref().is_some_and(|e| !e.is_running());
+        if finished {
+            match self.stop() {
+                Ok(path) => self.completed_output = Some(path),
+                Err(e) => eprintln!("Failed to finish scheduled stop: {}", e),
+            }
+        }
+    }
+
     fn handle_encoder_failure(&mut self, message: String) {
         eprintln!("Encoder failure: {}", message);
         let _ = self.stop();
@@ -447,20 +1699,67 @@ impl Default for RecordingManager {
     }
 }
 
+/// Open each ear's microphone as a mono capture
+///
+/// Both must be present: falling back to the default device could put the
+/// same microphone in both ears.
+fn open_ear_microphones(
+    dual: &DualMicConfig,
+    config: &RecordingConfig,
+) -> Result<(MicrophoneCapture, MicrophoneCapture), RecordingError> {
+    // One clock for both, so the ears can be lined up by timestamp
+    let epoch = Instant::now();
+    let open = |ear: &str, id: &str| {
+        let (device_name, fallback) = resolve_microphone(Some(id));
+        if fallback.is_some() {
+            return Err(RecordingError::Device(format!("Microphone '{}' for the {} ear not found", id, ear)));
+        }
+        let mic_config = MicrophoneCaptureConfig {
+            device_name,
+            sample_rate: Some(config.audio_sample_rate),
+            channel_layout: AudioChannelLayout::Mono,
+            channel_map: None,
+            buffer_frames: config.audio_buffer.device_buffer_frames,
+            epoch: Some(epoch),
+        };
+        MicrophoneCapture::new(mic_config)
+            .map_err(|e| RecordingError::Device(format!("Failed to initialize {} ear microphone: {}", ear, e)))
+    };
+    Ok((open("left", &dual.left_device_id)?, open("right", &dual.right_device_id)?))
+}
+
+/// Restart a stalled source with `restart`, describing what happened
+fn stall_message(
+    source: WatchedSource,
+    event: Option<WatchdogEvent>,
+    restart: impl FnOnce() -> Result<(), String>,
+) -> Option<String> {
+    match event? {
+        WatchdogEvent::Stalled => Some(match restart() {
+            Ok(()) => format!("{} stopped delivering; restarting it", source.label()),
+            Err(e) => format!("{} stopped delivering and failed to restart: {}", source.label(), e),
+        }),
+        WatchdogEvent::Recovered => Some(format!("{} recovered", source.label())),
+    }
+}
+
 /// Compositor loop - combines screen and webcam frames
 fn compositor_loop(
     running: Arc<Mutex<bool>>,
     stop_signal: Arc<Mutex<bool>>,
     status: Arc<Mutex<RecordingStatus>>,
-    compositor: VideoCompositor,
-    screen_receiver: Option<Receiver<ScreenFrame>>,
-    webcam_receiver: Option<Receiver<WebcamFrame>>,
+    compositor: Arc<Mutex<VideoCompositor>>,
+    mut screen_receiver: Option<Receiver<ScreenFrame>>,
+    mut webcam_receiver: Option<Receiver<WebcamFrame>>,
     composite_sender: Sender<CompositeFrame>,
+    frames: Arc<FrameCounters>,
+    monitor_feed: MonitorFeed,
+    quality_controls: Option<Arc<QualityControls>>,
+    queue_memory: Arc<QueueMemory>,
     capture_screen: bool,
 ) {
     let start_time = Instant::now();
     let mut frame_count: u64 = 0;
-    let mut skipped_frames: u64 = 0;
     let mut latest_webcam: Option<WebcamFrame> = None;
     let mut last_frame_time = Instant::now();
     let mut no_frame_warning_printed = false;
@@ -472,10 +1771,42 @@ fn compositor_loop(
 
     println!("Compositor loop started (capture_screen: {})", capture_screen);
 
+    let mut render_scale = 100;
+    let mut memory_cap_reported = false;
     while *running.lock() && !*stop_signal.lock() {
+        // Adaptive quality may have reduced the size frames are drawn at
+        if let Some(ref controls) = quality_controls {
+            let scale = controls.render_scale_percent();
+            if scale != render_scale {
+                render_scale = scale;
+                compositor.lock().set_render_scale(scale);
+            }
+        }
+
+        // Past the queue memory cap new frames are dropped until the encoder catches up
+        let over_memory_cap = queue_memory.over_cap();
+        if over_memory_cap && !memory_cap_reported {
+            memory_cap_reported = true;
+            let message = format!(
+                "Queued frames reached the {} MB memory cap; dropping frames until the encoder catches up",
+                queue_memory.max_megabytes()
+            );
+            eprintln!("Compositor: {}", message);
+            status.lock().events.push(RecordingEvent {
+                at_ms: start_time.elapsed().as_millis() as u64,
+                message,
+            });
+        }
+
         // Get latest webcam frame (non-blocking)
         if let Some(ref receiver) = webcam_receiver {
             while let Ok(frame) = receiver.try_recv() {
+                if !capture_screen {
+                    frames.captured();
+                    if latest_webcam.is_some() {
+                        frames.skipped();
+                    }
+                }
                 latest_webcam = Some(frame);
             }
         }
@@ -489,8 +1820,9 @@ fn compositor_loop(
                 // Drain all available frames, keeping only the latest
                 // This implements adaptive frame skipping - we always use the most recent frame
                 while let Ok(screen_frame) = receiver.try_recv() {
+                    frames.captured();
                     if latest_screen_frame.is_some() {
-                        skipped_frames += 1;
+                        frames.skipped();
                     }
                     latest_screen_frame = Some(screen_frame);
                     received_frame = true;
@@ -507,16 +1839,18 @@ fn compositor_loop(
 
                     // Skip frames if queue is getting full (backpressure)
                     // This prevents buffer overflow and keeps latency low
-                    let should_skip = queue_pressure > 0.8
-                        && last_processed_time.elapsed() < target_frame_interval * 2;
+                    let should_skip = over_memory_cap
+                        || (queue_pressure > 0.8
+                            && last_processed_time.elapsed() < target_frame_interval * 2);
 
                     if should_skip {
-                        skipped_frames += 1;
+                        frames.dropped();
                     } else {
-                        let composite = compositor.composite(
+                        let composite = compositor.lock().composite(
                             &screen_frame,
                             latest_webcam.as_ref(),
                         );
+                        monitor_feed.offer(&composite);
 
                         // Use try_send to avoid blocking - if queue is full, skip this frame
                         match composite_sender.try_send(composite) {
@@ -532,7 +1866,7 @@ fn compositor_loop(
                                 }
                             }
                             Err(_) => {
-                                skipped_frames += 1;
+                                frames.dropped();
                             }
                         }
                     }
@@ -554,12 +1888,13 @@ fn compositor_loop(
         } else if let Some(ref webcam) = latest_webcam {
             // Webcam only mode - use same adaptive approach
             let queue_len = composite_sender.len();
-            let should_skip = queue_len > 96; // 80% of 120
+            let should_skip = over_memory_cap || queue_len > 96; // 80% of 120
 
             if should_skip {
-                skipped_frames += 1;
+                frames.dropped();
             } else {
-                let composite = compositor.composite_webcam_only(webcam);
+                let composite = compositor.lock().composite_webcam_only(webcam);
+                monitor_feed.offer(&composite);
 
                 match composite_sender.try_send(composite) {
                     Ok(()) => {
@@ -573,7 +1908,7 @@ fn compositor_loop(
                         }
                     }
                     Err(_) => {
-                        skipped_frames += 1;
+                        frames.dropped();
                     }
                 }
             }
@@ -582,7 +1917,12 @@ fn compositor_loop(
             latest_webcam = None;
         }
 
-        std::thread::sleep(Duration::from_millis(1));
+        // Sleep until the next screen or webcam frame arrives
+        if capture_screen {
+            wait_for_either(&mut screen_receiver, &mut webcam_receiver);
+        } else {
+            wait_for(&mut webcam_receiver);
+        }
     }
 
     // Final status update
@@ -596,6 +1936,29 @@ fn compositor_loop(
     let effective_fps = frame_count as f32 / duration_secs;
     println!(
         "Compositor loop stopped: {} frames in {:.1}s ({:.1} fps), {} skipped",
-        frame_count, duration_secs, effective_fps, skipped_frames
+        frame_count, duration_secs, effective_fps, frames.skipped_total()
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recording::{AudioCodec, OutputFormat};
+    
+    #[test]
+    fn test_high_sample_rate_needs_lossless_codec() {
+        // start() refuses this with InvalidConfig before opening any device
+        let config = RecordingConfig {
+            audio_sample_rate: 192000,
+            ..RecordingConfig::default()
+        };
+        assert!(RecordingManager::validate_config(&config).is_err());
+        
+        let config = RecordingConfig {
+            output_format: OutputFormat::Mkv,
+            audio_codec: Some(AudioCodec::Flac),
+            ..config
+        };
+        assert!(RecordingManager::validate_config(&config).is_ok());
+    }
+}
@@ -0,0 +1,213 @@
+//! Live Markers
+//!
+//! Markers are dropped with `add_marker` while recording, at the recording's
+//! current time. When the file is finalized they become chapters - each
+//! running to the next marker, the last to the end - written into the
+//! container (MP4 `chpl` atom, Matroska/WebM chapters) and to a
+//! `<recording>.chapters.txt` sidecar with one `HH:MM:SS.mmm label` line
+//! per chapter, for players and upload forms that don't read the atoms.
+//!
+//! Fragmented MP4 (append sessions) has its index written up front, so only
+//! the sidecar carries the chapters there. The passes that rewrite a
+//! finished recording carry its chapters over, and silence trimming moves
+//! them (and the sidecar) along with the cut.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Longest accepted marker label
+pub const MAX_LABEL_LEN: usize = 200;
+
+/// A point in the recording marked while it was made
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Marker {
+    /// Time from the start of the recording
+    pub at_ms: u64,
+    pub label: String,
+}
+
+/// A chapter of the finished file, in milliseconds from its start
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chapter {
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub title: String,
+}
+
+/// Check a label fits on one chapter line
+pub fn validate_label(label: &str) -> Result<(), String> {
+    if label.trim().is_empty() {
+        return Err("Marker label can't be empty".to_string());
+    }
+    if label.chars().count() > MAX_LABEL_LEN {
+        return Err(format!("Marker label must be at most {} characters", MAX_LABEL_LEN));
+    }
+    if label.contains(['\n', '\r']) {
+        return Err("Marker label must be a single line".to_string());
+    }
+    Ok(())
+}
+
+/// Path of the chapter list for a recording
+#[cfg_attr(not(any(feature = "ffmpeg", test)), allow(dead_code))]
+pub fn chapters_path(output_path: &Path) -> PathBuf {
+    output_path.with_extension("chapters.txt")
+}
+
+/// Chapters of a file that starts `start_ms` into the recording (negative
+/// when an earlier sitting comes first) and runs for `duration_ms`
+///
+/// Markers outside the file are dropped; of markers at the same time, the
+/// last one's label is kept.
+#[cfg_attr(not(any(feature = "ffmpeg", test)), allow(dead_code))]
+pub fn chapters(markers: &[Marker], start_ms: i64, duration_ms: u64) -> Vec<Chapter> {
+    let mut starts: Vec<(u64, &str)> = markers
+        .iter()
+        .filter_map(|marker| {
+            let at_ms = marker.at_ms as i64 - start_ms;
+            (0..duration_ms as i64)
+                .contains(&at_ms)
+                .then_some((at_ms as u64, marker.label.trim()))
+        })
+        .collect();
+    starts.sort_by_key(|(at_ms, _)| *at_ms);
+
+    let mut chapters: Vec<Chapter> = Vec::new();
+    for (start_ms, title) in starts {
+        match chapters.last_mut() {
+            Some(last) if last.start_ms == start_ms => last.title = title.to_string(),
+            Some(last) => {
+                last.end_ms = start_ms;
+                chapters.push(Chapter {
+                    start_ms,
+                    end_ms: duration_ms,
+                    title: title.to_string(),
+                });
+            }
+            None => chapters.push(Chapter {
+                start_ms,
+                end_ms: duration_ms,
+                title: title.to_string(),
+            }),
+        }
+    }
+    chapters
+}
+
+/// `chapters` of a file cut down to `kept` of it, moved to start at zero
+///
+/// Chapters wholly outside the kept range are dropped; the rest are clipped
+/// to it.
+#[cfg_attr(not(any(feature = "ffmpeg", test)), allow(dead_code))]
+pub fn trim_chapters(chapters: &[Chapter], kept: std::ops::Range<u64>) -> Vec<Chapter> {
+    chapters
+        .iter()
+        .filter(|chapter| chapter.end_ms > kept.start && chapter.start_ms < kept.end)
+        .map(|chapter| Chapter {
+            start_ms: chapter.start_ms.max(kept.start) - kept.start,
+            end_ms: chapter.end_ms.min(kept.end) - kept.start,
+            title: chapter.title.clone(),
+        })
+        .collect()
+}
+
+/// `HH:MM:SS.mmm label` lines for the sidecar
+#[cfg_attr(not(any(feature = "ffmpeg", test)), allow(dead_code))]
+fn chapter_lines(chapters: &[Chapter]) -> String {
+    chapters
+        .iter()
+        .map(|chapter| {
+            let ms = chapter.start_ms;
+            format!(
+                "{:02}:{:02}:{:02}.{:03} {}\n",
+                ms / 3_600_000,
+                ms / 60_000 % 60,
+                ms / 1000 % 60,
+                ms % 1000,
+                chapter.title
+            )
+        })
+        .collect()
+}
+
+/// Add `chapters` to a file being written; must come before its trailer
+#[cfg(feature = "ffmpeg")]
+pub fn add_chapters(output: &mut ffmpeg_next::format::context::Output, chapters: &[Chapter]) -> Result<(), String> {
+    for (id, chapter) in chapters.iter().enumerate() {
+        output
+            .add_chapter(
+                id as i64,
+                ffmpeg_next::Rational(1, 1000),
+                chapter.start_ms as i64,
+                chapter.end_ms as i64,
+                &chapter.title,
+            )
+            .map_err(|e| format!("Failed to add chapter '{}': {}", chapter.title, e))?;
+    }
+    Ok(())
+}
+
+/// Write the chapter list next to the finished recording
+#[cfg(feature = "ffmpeg")]
+pub fn write_chapters_file(output_path: &Path, chapters: &[Chapter]) -> Result<PathBuf, String> {
+    let path = chapters_path(output_path);
+    std::fs::write(&path, chapter_lines(chapters))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markers_become_chapters() {
+        assert!(validate_label("Tapping").is_ok());
+        assert!(validate_label("  ").is_err());
+        assert!(validate_label("two\nlines").is_err());
+        assert!(validate_label(&"x".repeat(MAX_LABEL_LEN + 1)).is_err());
+
+        let marker = |at_ms, label: &str| Marker {
+            at_ms,
+            label: label.to_string(),
+        };
+        let markers = [
+            marker(65_000, "Brushing"),
+            marker(1_500, "Tapping"),
+            marker(65_000, " Ear brushing "),
+            marker(200_000, "After the end"),
+        ];
+        let list = chapters(&markers, 0, 120_000);
+        assert_eq!(
+            list,
+            vec![
+                Chapter {
+                    start_ms: 1_500,
+                    end_ms: 65_000,
+                    title: "Tapping".to_string(),
+                },
+                Chapter {
+                    start_ms: 65_000,
+                    end_ms: 120_000,
+                    title: "Ear brushing".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            chapter_lines(&list),
+            "00:00:01.500 Tapping\n00:01:05.000 Ear brushing\n"
+        );
+
+        // After a 60s earlier sitting, markers move back by its length
+        let appended = chapters(&markers[1..2], -60_000, 120_000);
+        assert_eq!(appended[0].start_ms, 61_500);
+        assert_eq!(chapters_path(Path::new("/tmp/a.mkv")), PathBuf::from("/tmp/a.chapters.txt"));
+
+        // Trimming 1s off the start and cutting at 100s moves and clips them
+        let trimmed = trim_chapters(&list, 1_000..100_000);
+        assert_eq!((trimmed[0].start_ms, trimmed[0].end_ms), (500, 64_000));
+        assert_eq!((trimmed[1].start_ms, trimmed[1].end_ms), (64_000, 99_000));
+        assert!(trim_chapters(&list, 0..1_000).is_empty());
+    }
+}
@@ -0,0 +1,407 @@
+//! Mastering Chain
+//!
+//! Optional processing of a recording's audio once it is finalized, so
+//! published files need no pass through a separate editor:
+//!
+//! 1. A high-pass filter removes rumble, handling noise and desk bumps below
+//!    the voice.
+//! 2. A de-esser turns down sibilance: the band above 5 kHz is split off
+//!    with a crossover, and whenever it gets louder than the threshold it
+//!    alone is reduced
+//!    (4:1, by at most `de_ess_db`), leaving the rest of the voice untouched.
+//!    Channels share one detector so the stereo image doesn't shift.
+//! 3. The result is normalized to the target loudness like
+//!    `normalize_recording` does.
+//!
+//! The filters re-encode the audio track once, copying every other stream;
+//! normalization is a second pass. The file is only replaced once each pass
+//! has succeeded. Processing needs the `ffmpeg` feature.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::command;
+
+use crate::loudness::{normalize_file, validate_target, LoudnessReport};
+use crate::output_paths::{allowed_output_dirs, check_output_path};
+use crate::recording::RecordingError;
+
+/// Highest accepted high-pass cutoff
+const MAX_HIGH_PASS_HZ: f32 = 300.0;
+
+/// Most the de-esser can be allowed to reduce sibilance
+const MAX_DE_ESS_DB: f32 = 24.0;
+
+/// Lower edge of the band the de-esser works on
+#[cfg_attr(not(any(feature = "ffmpeg", test)), allow(dead_code))]
+const DE_ESS_HZ: f64 = 5000.0;
+
+/// De-esser reduction ratio above the threshold
+#[cfg_attr(not(any(feature = "ffmpeg", test)), allow(dead_code))]
+const DE_ESS_RATIO: f64 = 4.0;
+
+/// De-esser detector attack and release times (seconds)
+#[cfg_attr(not(any(feature = "ffmpeg", test)), allow(dead_code))]
+const DE_ESS_ATTACK: f64 = 0.001;
+#[cfg_attr(not(any(feature = "ffmpeg", test)), allow(dead_code))]
+const DE_ESS_RELEASE: f64 = 0.06;
+
+/// Mastering settings
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MasteringConfig {
+    /// Apply the chain when the recording is finalized
+    #[serde(default)]
+    pub enabled: bool,
+    /// High-pass cutoff (Hz; 0 turns the filter off)
+    #[serde(default = "default_high_pass_hz")]
+    pub high_pass_hz: f32,
+    /// Most sibilance is turned down by (dB; 0 turns the de-esser off)
+    #[serde(default = "default_de_ess_db")]
+    pub de_ess_db: f32,
+    /// Sibilance louder than this is turned down (dBFS)
+    #[serde(default = "default_de_ess_threshold_db")]
+    pub de_ess_threshold_db: f32,
+    /// Integrated loudness to normalize to last (LUFS; None keeps the level)
+    #[serde(default = "default_target_lufs")]
+    pub target_lufs: Option<f32>,
+}
+
+fn default_high_pass_hz() -> f32 {
+    80.0
+}
+
+fn default_de_ess_db() -> f32 {
+    6.0
+}
+
+fn default_de_ess_threshold_db() -> f32 {
+    -30.0
+}
+
+fn default_target_lufs() -> Option<f32> {
+    Some(-16.0)
+}
+
+impl Default for MasteringConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            high_pass_hz: default_high_pass_hz(),
+            de_ess_db: default_de_ess_db(),
+            de_ess_threshold_db: default_de_ess_threshold_db(),
+            target_lufs: default_target_lufs(),
+        }
+    }
+}
+
+impl MasteringConfig {
+    /// Check every stage's settings are in the accepted range
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=MAX_HIGH_PASS_HZ).contains(&self.high_pass_hz) {
+            return Err(format!(
+                "High-pass cutoff must be 0-{} Hz (got {})",
+                MAX_HIGH_PASS_HZ, self.high_pass_hz
+            ));
+        }
+        if !(0.0..=MAX_DE_ESS_DB).contains(&self.de_ess_db) {
+            return Err(format!(
+                "De-esser reduction must be 0-{} dB (got {})",
+                MAX_DE_ESS_DB, self.de_ess_db
+            ));
+        }
+        if !(-60.0..=0.0).contains(&self.de_ess_threshold_db) {
+            return Err(format!(
+                "De-esser threshold must be -60 to 0 dBFS (got {})",
+                self.de_ess_threshold_db
+            ));
+        }
+        if let Some(target) = self.target_lufs {
+            validate_target(target)?;
+        }
+        Ok(())
+    }
+
+    /// Loudness the chain normalizes to when the recording is finalized
+    pub fn loudness_target(&self) -> Option<f32> {
+        self.target_lufs.filter(|_| self.enabled)
+    }
+
+    /// Whether the audio needs filtering (as opposed to only normalizing)
+    fn filters(&self) -> bool {
+        self.high_pass_hz > 0.0 || self.de_ess_db > 0.0
+    }
+}
+
+/// Second-order Butterworth filter (RBJ cookbook), one channel
+#[cfg_attr(not(any(feature = "ffmpeg", test)), allow(dead_code))]
+#[derive(Clone)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    state: [f64; 2],
+}
+
+#[cfg_attr(not(any(feature = "ffmpeg", test)), allow(dead_code))]
+impl Biquad {
+    fn high_pass(cutoff_hz: f64, sample_rate: u32) -> Self {
+        Self::new(cutoff_hz, sample_rate, true)
+    }
+
+    fn low_pass(cutoff_hz: f64, sample_rate: u32) -> Self {
+        Self::new(cutoff_hz, sample_rate, false)
+    }
+
+    fn new(cutoff_hz: f64, sample_rate: u32, high: bool) -> Self {
+        // Keep the cutoff below Nyquist for low sample rates
+        let cutoff_hz = cutoff_hz.min(sample_rate as f64 * 0.45);
+        let w0 = 2.0 * std::f64::consts::PI * cutoff_hz / sample_rate as f64;
+        let alpha = w0.sin() / std::f64::consts::SQRT_2;
+        let cos = w0.cos();
+        let a0 = 1.0 + alpha;
+        let b = if high {
+            [(1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0]
+        } else {
+            [(1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0]
+        };
+        Self {
+            b: b.map(|b| b / a0),
+            a: [-2.0 * cos / a0, (1.0 - alpha) / a0],
+            state: [0.0; 2],
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        // Transposed direct form II
+        let y = self.b[0] * x + self.state[0];
+        self.state[0] = self.b[1] * x - self.a[0] * y + self.state[1];
+        self.state[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+/// Fourth-order Linkwitz-Riley crossover, one channel: the two bands stay in
+/// phase, so they add back up to the input's level whatever gain either has
+#[cfg_attr(not(any(feature = "ffmpeg", test)), allow(dead_code))]
+#[derive(Clone)]
+struct Crossover {
+    low: [Biquad; 2],
+    high: [Biquad; 2],
+}
+
+#[cfg_attr(not(any(feature = "ffmpeg", test)), allow(dead_code))]
+impl Crossover {
+    fn new(frequency_hz: f64, sample_rate: u32) -> Self {
+        let low = Biquad::low_pass(frequency_hz, sample_rate);
+        let high = Biquad::high_pass(frequency_hz, sample_rate);
+        Self {
+            low: [low.clone(), low],
+            high: [high.clone(), high],
+        }
+    }
+
+    /// Low and high band of a sample
+    fn split(&mut self, x: f64) -> (f64, f64) {
+        let cascade = |filters: &mut [Biquad; 2]| filters.iter_mut().fold(x, |y, filter| filter.process(y));
+        (cascade(&mut self.low), cascade(&mut self.high))
+    }
+}
+
+/// Split-band de-esser with one detector for all channels
+#[cfg_attr(not(any(feature = "ffmpeg", test)), allow(dead_code))]
+struct DeEsser {
+    crossovers: Vec<Crossover>,
+    threshold_db: f64,
+    max_reduction_db: f64,
+    envelope: f64,
+    attack: f64,
+    release: f64,
+}
+
+#[cfg_attr(not(any(feature = "ffmpeg", test)), allow(dead_code))]
+impl DeEsser {
+    fn new(config: &MasteringConfig, sample_rate: u32, channels: usize) -> Self {
+        let coefficient = |seconds: f64| 1.0 - (-1.0 / (seconds * sample_rate as f64)).exp();
+        Self {
+            crossovers: vec![Crossover::new(DE_ESS_HZ, sample_rate); channels],
+            threshold_db: config.de_ess_threshold_db as f64,
+            max_reduction_db: config.de_ess_db as f64,
+            envelope: 0.0,
+            attack: coefficient(DE_ESS_ATTACK),
+            release: coefficient(DE_ESS_RELEASE),
+        }
+    }
+
+    /// Turn down the sibilance band of one frame (a sample per channel)
+    fn process(&mut self, frame: &mut [f32]) {
+        let bands: Vec<(f64, f64)> = frame
+            .iter()
+            .zip(&mut self.crossovers)
+            .map(|(sample, crossover)| crossover.split(*sample as f64))
+            .collect();
+
+        let level = bands.iter().fold(0.0f64, |peak, (_, high)| peak.max(high.abs()));
+        let coefficient = if level > self.envelope { self.attack } else { self.release };
+        self.envelope += coefficient * (level - self.envelope);
+
+        let envelope_db = 20.0 * self.envelope.max(1e-9).log10();
+        let reduction_db = ((envelope_db - self.threshold_db) * (1.0 - 1.0 / DE_ESS_RATIO))
+            .clamp(0.0, self.max_reduction_db);
+        let gain = 10f64.powf(-reduction_db / 20.0);
+        for (sample, (low, high)) in frame.iter_mut().zip(&bands) {
+            *sample = (low + high * gain) as f32;
+        }
+    }
+}
+
+/// The filter stages of the chain, run over interleaved samples
+#[cfg_attr(not(any(feature = "ffmpeg", test)), allow(dead_code))]
+struct MasteringChain {
+    channels: usize,
+    high_pass: Option<Vec<Biquad>>,
+    de_esser: Option<DeEsser>,
+}
+
+#[cfg_attr(not(any(feature = "ffmpeg", test)), allow(dead_code))]
+impl MasteringChain {
+    fn new(config: &MasteringConfig, sample_rate: u32, channels: u16) -> Self {
+        let channels = channels.max(1) as usize;
+        Self {
+            channels,
+            high_pass: (config.high_pass_hz > 0.0)
+                .then(|| vec![Biquad::high_pass(config.high_pass_hz as f64, sample_rate); channels]),
+            de_esser: (config.de_ess_db > 0.0).then(|| DeEsser::new(config, sample_rate, channels)),
+        }
+    }
+
+    fn process(&mut self, samples: &mut [f32]) {
+        for frame in samples.chunks_mut(self.channels) {
+            if let Some(filters) = &mut self.high_pass {
+                for (sample, filter) in frame.iter_mut().zip(filters.iter_mut()) {
+                    *sample = filter.process(*sample as f64) as f32;
+                }
+            }
+            if let Some(de_esser) = &mut self.de_esser {
+                de_esser.process(frame);
+            }
+        }
+    }
+}
+
+/// Run the recording at `path` through the chain's filters, replacing it
+#[cfg(feature = "ffmpeg")]
+fn filter_file(path: &Path, config: &MasteringConfig) -> Result<(), String> {
+    ffmpeg_next::init().map_err(|e| format!("FFmpeg init failed: {}", e))?;
+    let (rate, channels) = {
+        let reader = crate::media_file::AudioTrackReader::open(path)?;
+        (reader.rate(), reader.channels())
+    };
+    crate::media_file::rewrite(path, crate::media_file::suffix::MASTERING, |temp_path| {
+        let mut chain = MasteringChain::new(config, rate, channels);
+        let filter = move |samples: &[f32], out: &mut Vec<f32>| {
+            let start = out.len();
+            out.extend_from_slice(samples);
+            chain.process(&mut out[start..]);
+        };
+        crate::loudness::apply_gain(path, temp_path, 0.0, Some(Box::new(filter)))
+    })?;
+    println!("Filtered {} (high-pass {} Hz, de-esser {} dB)", path.display(), config.high_pass_hz, config.de_ess_db);
+    Ok(())
+}
+
+#[cfg(not(feature = "ffmpeg"))]
+fn filter_file(_path: &Path, _config: &MasteringConfig) -> Result<(), String> {
+    Err("Mastering requires FFmpeg support".to_string())
+}
+
+/// Apply every stage of the chain to the recording at `path`, whether or not
+/// `config.enabled` is set; returns the normalization report if the chain
+/// has a loudness target
+pub fn master_file(path: &Path, config: &MasteringConfig) -> Result<Option<LoudnessReport>, String> {
+    config.validate()?;
+    if config.filters() {
+        filter_file(path, config)?;
+    }
+    config.target_lufs.map(|target| normalize_file(path, target)).transpose()
+}
+
+/// Apply the chain to a recording that was just finalized, if it's enabled
+#[cfg(feature = "ffmpeg")]
+pub fn finalize(path: &Path, config: &MasteringConfig) -> Result<(), String> {
+    if config.enabled {
+        master_file(path, config)?;
+    }
+    Ok(())
+}
+
+/// Tauri command: Run a finished recording through the mastering chain
+///
+/// `config.enabled` is ignored. The file is replaced once processing is
+/// complete.
+#[command]
+pub async fn master_recording(
+    path: String,
+    config: MasteringConfig,
+) -> Result<Option<LoudnessReport>, RecordingError> {
+    config.validate().map_err(RecordingError::InvalidConfig)?;
+    let path = PathBuf::from(path);
+    if !path.is_file() {
+        return Err(format!("Recording not found: {}", path.display()).into());
+    }
+    // The file is rewritten in place
+    let path = check_output_path(&path, &allowed_output_dirs())?;
+    tauri::async_runtime::spawn_blocking(move || master_file(&path, &config))
+        .await
+        .map_err(|e| format!("Mastering failed: {}", e))?
+        .map_err(RecordingError::Failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Level of the second half of a sine at `hz` after the chain, relative
+    /// to the input's (RMS)
+    fn gain_after(chain: &mut MasteringChain, hz: f64, amplitude: f32) -> f32 {
+        let rate = 48_000.0;
+        let mut samples: Vec<f32> = (0..48_000)
+            .flat_map(|i| {
+                let sample = amplitude * (2.0 * std::f64::consts::PI * hz * i as f64 / rate).sin() as f32;
+                [sample, sample]
+            })
+            .collect();
+        chain.process(&mut samples);
+        let tail = &samples[samples.len() / 2..];
+        let rms = (tail.iter().map(|s| s * s).sum::<f32>() / tail.len() as f32).sqrt();
+        rms / (amplitude / std::f32::consts::SQRT_2)
+    }
+
+    #[test]
+    fn test_chain_removes_rumble_and_tames_sibilance() {
+        let config = MasteringConfig {
+            enabled: true,
+            ..MasteringConfig::default()
+        };
+        assert!(config.validate().is_ok());
+        assert_eq!(config.loudness_target(), Some(-16.0));
+        assert_eq!(MasteringConfig::default().loudness_target(), None);
+        assert!(MasteringConfig { high_pass_hz: 500.0, ..config.clone() }.validate().is_err());
+        assert!(MasteringConfig { de_ess_db: -1.0, ..config.clone() }.validate().is_err());
+
+        // High-pass alone: 20 Hz rumble is cut, 1 kHz passes
+        let high_pass_only = MasteringConfig { de_ess_db: 0.0, ..config.clone() };
+        let mut chain = MasteringChain::new(&high_pass_only, 48_000, 2);
+        assert!(gain_after(&mut chain, 20.0, 0.5) < 0.1);
+        let mut chain = MasteringChain::new(&high_pass_only, 48_000, 2);
+        assert!((gain_after(&mut chain, 1000.0, 0.5) - 1.0).abs() < 0.01);
+
+        // De-esser: loud 10 kHz is turned down by up to 6 dB; quiet 10 kHz
+        // and loud 1 kHz are left alone
+        let mut chain = MasteringChain::new(&config, 48_000, 2);
+        let loud = gain_after(&mut chain, 10_000.0, 0.5);
+        assert!((0.5..0.6).contains(&loud), "loud sibilance gain {}", loud);
+        let mut chain = MasteringChain::new(&config, 48_000, 2);
+        assert!((gain_after(&mut chain, 10_000.0, 0.01) - 1.0).abs() < 0.01);
+        let mut chain = MasteringChain::new(&config, 48_000, 2);
+        assert!((gain_after(&mut chain, 1000.0, 0.5) - 1.0).abs() < 0.01);
+    }
+}
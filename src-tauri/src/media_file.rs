@@ -0,0 +1,257 @@
+//! Finished Recording Files
+//!
+//! Pieces shared by the passes that analyze or rewrite a recording once it is
+//! finalized (loudness normalization, silence trimming): decoding the audio
+//! track, copying streams untouched, and swapping a rewritten copy in for the
+//! original so a failed pass never leaves a half-written recording behind.
+
+use std::path::{Path, PathBuf};
+
+/// Suffixes of the work files written next to a recording, one per pass
+pub mod suffix {
+    pub const APPENDING: &str = "appending";
+    pub const JOINING: &str = "joining";
+    pub const NORMALIZING: &str = "normalizing";
+    pub const MASTERING: &str = "mastering";
+    pub const TRIMMING: &str = "trimming";
+    pub const TRANSCODING: &str = "transcoding";
+    pub const EXTRACTING: &str = "extracting";
+    pub const EXPORTING: &str = "exporting";
+    pub const RETIMING: &str = "retiming";
+
+    /// Every work file suffix, so half-written files can be told apart
+    pub const ALL: &[&str] = &[
+        APPENDING,
+        JOINING,
+        NORMALIZING,
+        MASTERING,
+        TRIMMING,
+        TRANSCODING,
+        EXTRACTING,
+        EXPORTING,
+        RETIMING,
+    ];
+}
+
+/// `<stem>.<suffix>.<ext>` next to `path`
+pub fn work_path(path: &Path, suffix: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    match path.extension() {
+        Some(ext) => path.with_file_name(format!("{}.{}.{}", stem, suffix, ext.to_string_lossy())),
+        None => path.with_file_name(format!("{}.{}", stem, suffix)),
+    }
+}
+
+/// Write a new version of `path` to a work file with `write`, then replace
+/// the original with it
+#[cfg(feature = "ffmpeg")]
+pub fn rewrite(
+    path: &Path,
+    suffix: &str,
+    write: impl FnOnce(&Path) -> Result<(), String>,
+) -> Result<(), String> {
+    let temp_path = work_path(path, suffix);
+    if let Err(e) = write(&temp_path) {
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(e);
+    }
+    std::fs::rename(&temp_path, path)
+        .map_err(|e| format!("Failed to replace {}: {}", path.display(), e))
+}
+
+#[cfg(feature = "ffmpeg")]
+pub fn open_input(path: &Path) -> Result<ffmpeg_next::format::context::Input, String> {
+    ffmpeg_next::format::input(&path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))
+}
+
+/// Muxer for a rewritten copy of `input`, with its metadata
+///
+/// Work files keep the original extension, so the muxer matches the original's.
+#[cfg(feature = "ffmpeg")]
+pub fn create_output(
+    temp_path: &Path,
+    input: &ffmpeg_next::format::context::Input,
+) -> Result<ffmpeg_next::format::context::Output, String> {
+    let mut output = ffmpeg_next::format::output(&temp_path)
+        .map_err(|e| format!("Failed to create {}: {}", temp_path.display(), e))?;
+    output.set_metadata(input.metadata().to_owned());
+    Ok(output)
+}
+
+/// Copy `input`'s chapters to `output`, cut down to `kept` when the copy
+/// only keeps part of the file (see `markers::trim_chapters`)
+#[cfg(feature = "ffmpeg")]
+pub fn copy_chapters(
+    input: &ffmpeg_next::format::context::Input,
+    output: &mut ffmpeg_next::format::context::Output,
+    kept: Option<std::ops::Range<u64>>,
+) -> Result<(), String> {
+    use ffmpeg_next::Rescale;
+
+    let millis = ffmpeg_next::Rational(1, 1000);
+    let chapters: Vec<crate::markers::Chapter> = input
+        .chapters()
+        .map(|chapter| crate::markers::Chapter {
+            start_ms: chapter.start().rescale(chapter.time_base(), millis).max(0) as u64,
+            end_ms: chapter.end().rescale(chapter.time_base(), millis).max(0) as u64,
+            title: chapter.metadata().get("title").unwrap_or_default().to_string(),
+        })
+        .collect();
+    let chapters = match kept {
+        Some(kept) => crate::markers::trim_chapters(&chapters, kept),
+        None => chapters,
+    };
+    crate::markers::add_chapters(output, &chapters)
+}
+
+/// Write the header (MP4 moov atom first, like the recorder's own files)
+#[cfg(feature = "ffmpeg")]
+pub fn write_header(
+    output: &mut ffmpeg_next::format::context::Output,
+    temp_path: &Path,
+) -> Result<(), String> {
+    let mut muxer_options = ffmpeg_next::Dictionary::new();
+    if temp_path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("mp4")) {
+        muxer_options.set("movflags", "+faststart");
+    }
+    output
+        .write_header_with(muxer_options)
+        .map(|_| ())
+        .map_err(|e| format!("Failed to write header: {}", e))
+}
+
+/// Add an output stream that `stream`'s packets are copied to as they are
+///
+/// Returns None for streams that aren't audio, video or subtitles.
+#[cfg(feature = "ffmpeg")]
+pub fn add_copy_stream(
+    output: &mut ffmpeg_next::format::context::Output,
+    stream: &ffmpeg_next::format::stream::Stream,
+) -> Result<Option<usize>, String> {
+    use ffmpeg_next::media::Type;
+
+    if !matches!(stream.parameters().medium(), Type::Video | Type::Audio | Type::Subtitle) {
+        return Ok(None);
+    }
+    let mut copy = output
+        .add_stream(ffmpeg_next::encoder::find(ffmpeg_next::codec::Id::None))
+        .map_err(|e| format!("Failed to add stream: {}", e))?;
+    copy.set_parameters(stream.parameters());
+    // Let the muxer pick its own tag for the codec
+    unsafe { (*copy.parameters().as_mut_ptr()).codec_tag = 0 };
+    copy.set_metadata(stream.metadata().to_owned());
+    Ok(Some(copy.index()))
+}
+
+/// Find the best audio stream and open a decoder for it
+#[cfg(feature = "ffmpeg")]
+pub fn open_audio_decoder(
+    input: &ffmpeg_next::format::context::Input,
+    path: &Path,
+) -> Result<(usize, ffmpeg_next::decoder::Audio), String> {
+    let stream = input
+        .streams()
+        .best(ffmpeg_next::media::Type::Audio)
+        .ok_or_else(|| format!("{} has no audio track", path.display()))?;
+    let decoder = ffmpeg_next::codec::context::Context::from_parameters(stream.parameters())
+        .and_then(|context| context.decoder().audio())
+        .map_err(|e| format!("Failed to open audio decoder: {}", e))?;
+    Ok((stream.index(), decoder))
+}
+
+/// Decoder for the audio track of a finished recording
+#[cfg(feature = "ffmpeg")]
+pub struct AudioTrackReader {
+    input: ffmpeg_next::format::context::Input,
+    index: usize,
+    decoder: ffmpeg_next::decoder::Audio,
+}
+
+#[cfg(feature = "ffmpeg")]
+impl AudioTrackReader {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let input = open_input(path)?;
+        let (index, decoder) = open_audio_decoder(&input, path)?;
+        Ok(Self {
+            input,
+            index,
+            decoder,
+        })
+    }
+
+    pub fn rate(&self) -> u32 {
+        self.decoder.rate()
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.decoder.channels()
+    }
+
+    /// Decode the whole track, passing each frame's samples interleaved
+    pub fn read_all(mut self, mut process: impl FnMut(&[f32])) -> Result<(), String> {
+        let mut frame = ffmpeg_next::frame::Audio::empty();
+        let mut samples = Vec::new();
+        let mut drain = |decoder: &mut ffmpeg_next::decoder::Audio| -> Result<(), String> {
+            while decoder.receive_frame(&mut frame).is_ok() {
+                samples.clear();
+                append_interleaved(&frame, &mut samples)?;
+                process(&samples);
+            }
+            Ok(())
+        };
+
+        for (stream, packet) in self.input.packets() {
+            if stream.index() == self.index {
+                self.decoder
+                    .send_packet(&packet)
+                    .map_err(|e| format!("Failed to decode audio: {}", e))?;
+                drain(&mut self.decoder)?;
+            }
+        }
+        let _ = self.decoder.send_eof();
+        drain(&mut self.decoder)
+    }
+}
+
+/// Append a decoded frame's samples, interleaved and converted to f32
+#[cfg(feature = "ffmpeg")]
+pub fn append_interleaved(frame: &ffmpeg_next::frame::Audio, out: &mut Vec<f32>) -> Result<(), String> {
+    use ffmpeg_next::format::Sample;
+
+    let (size, read): (usize, fn(&[u8]) -> f32) = match frame.format() {
+        Sample::F32(_) => (4, |b| f32::from_ne_bytes([b[0], b[1], b[2], b[3]])),
+        Sample::F64(_) => (8, |b| f64::from_ne_bytes(b.try_into().unwrap_or_default()) as f32),
+        Sample::I32(_) => (4, |b| i32::from_ne_bytes([b[0], b[1], b[2], b[3]]) as f32 / 2_147_483_648.0),
+        Sample::I16(_) => (2, |b| i16::from_ne_bytes([b[0], b[1]]) as f32 / 32_768.0),
+        other => return Err(format!("Unsupported decoded sample format {:?}", other)),
+    };
+    let channels = frame.channels() as usize;
+    let samples = frame.samples();
+    let start = out.len();
+
+    if frame.is_packed() {
+        out.extend(frame.data(0).chunks_exact(size).take(samples * channels).map(read));
+    } else {
+        out.resize(start + samples * channels, 0.0);
+        for ch in 0..channels {
+            for (i, bytes) in frame.data(ch).chunks_exact(size).take(samples).enumerate() {
+                out[start + i * channels + ch] = read(bytes);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_work_path_keeps_extension() {
+        assert_eq!(
+            work_path(Path::new("/tmp/rec.mp4"), "normalizing"),
+            PathBuf::from("/tmp/rec.normalizing.mp4")
+        );
+        assert_eq!(work_path(Path::new("rec"), "trimming"), PathBuf::from("rec.trimming"));
+    }
+}
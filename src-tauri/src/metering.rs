@@ -0,0 +1,573 @@
+//! Audio Level Metering
+//!
+//! Reports peak and RMS levels in dBFS, optionally frequency weighted
+//! (A-weighting or ITU-R BS.1770 K-weighting), with a peak-hold indicator so
+//! the values line up with what users see on their DAW meters.
+
+use serde::{Deserialize, Serialize};
+
+/// Level reported for digital silence
+pub const SILENCE_DB: f32 = -120.0;
+
+/// BS.1770 offset applied to K-weighted mean square (makes the scale LUFS)
+const K_WEIGHTING_OFFSET_DB: f32 = -0.691;
+
+/// Frequency weighting applied to the RMS measurement
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MeterWeighting {
+    /// Flat (unweighted) RMS
+    #[default]
+    None,
+    /// IEC 61672 A-weighting
+    A,
+    /// ITU-R BS.1770 K-weighting (LUFS scale)
+    K,
+}
+
+/// Metering configuration
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MeteringConfig {
+    /// Frequency weighting for the RMS value
+    #[serde(default)]
+    pub weighting: MeterWeighting,
+    /// How long the peak-hold value is kept before it falls back (ms)
+    #[serde(default = "default_peak_hold_ms")]
+    pub peak_hold_ms: u32,
+    /// RMS integration time (ms)
+    #[serde(default = "default_rms_window_ms")]
+    pub rms_window_ms: u32,
+}
+
+fn default_peak_hold_ms() -> u32 {
+    1500
+}
+
+fn default_rms_window_ms() -> u32 {
+    300
+}
+
+impl Default for MeteringConfig {
+    fn default() -> Self {
+        Self {
+            weighting: MeterWeighting::default(),
+            peak_hold_ms: default_peak_hold_ms(),
+            rms_window_ms: default_rms_window_ms(),
+        }
+    }
+}
+
+/// Level readings for a single channel
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelLevel {
+    /// Sample peak of the most recent block (dBFS, never weighted)
+    pub peak_db: f32,
+    /// RMS level (dBFS, or LUFS with K-weighting)
+    pub rms_db: f32,
+    /// Held peak (dBFS)
+    pub peak_hold_db: f32,
+    /// Whether the held peak reached full scale
+    pub clipped: bool,
+}
+
+impl Default for ChannelLevel {
+    fn default() -> Self {
+        Self {
+            peak_db: SILENCE_DB,
+            rms_db: SILENCE_DB,
+            peak_hold_db: SILENCE_DB,
+            clipped: false,
+        }
+    }
+}
+
+/// Snapshot of all meters in the audio path
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioLevels {
+    /// Weighting used for the RMS values
+    pub weighting: MeterWeighting,
+    /// Microphone after volume, per channel
+    pub mic: Vec<ChannelLevel>,
+    /// System audio after volume, per channel
+    pub system: Vec<ChannelLevel>,
+    /// Final mix sent to the encoder, per channel
+    pub output: Vec<ChannelLevel>,
+}
+
+/// Convert a linear amplitude to dBFS
+pub fn amplitude_to_db(amplitude: f32) -> f32 {
+    if amplitude <= 0.0 {
+        SILENCE_DB
+    } else {
+        (20.0 * amplitude.log10()).max(SILENCE_DB)
+    }
+}
+
+/// Second-order IIR section (transposed direct form II)
+#[derive(Debug, Clone, Copy)]
+struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+    z1: f64,
+    z2: f64,
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 3]) -> Self {
+        Self {
+            b0: b[0] / a[0],
+            b1: b[1] / a[0],
+            b2: b[2] / a[0],
+            a1: a[1] / a[0],
+            a2: a[2] / a[0],
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    /// Digitize an analog section `(b0 s² + b1 s + b2) / (a0 s² + a1 s + a2)`
+    /// using the bilinear transform
+    fn from_analog(b: [f64; 3], a: [f64; 3], sample_rate: f64) -> Self {
+        let k = 2.0 * sample_rate;
+        let k2 = k * k;
+        Self::new(
+            [
+                b[0] * k2 + b[1] * k + b[2],
+                2.0 * (b[2] - b[0] * k2),
+                b[0] * k2 - b[1] * k + b[2],
+            ],
+            [
+                a[0] * k2 + a[1] * k + a[2],
+                2.0 * (a[2] - a[0] * k2),
+                a[0] * k2 - a[1] * k + a[2],
+            ],
+        )
+    }
+
+    fn scale(mut self, gain: f64) -> Self {
+        self.b0 *= gain;
+        self.b1 *= gain;
+        self.b2 *= gain;
+        self
+    }
+
+    /// Magnitude response at `freq` Hz
+    fn magnitude(&self, freq: f64, sample_rate: f64) -> f64 {
+        let w = 2.0 * std::f64::consts::PI * freq / sample_rate;
+        let (c1, s1) = (w.cos(), -w.sin());
+        let (c2, s2) = ((2.0 * w).cos(), -(2.0 * w).sin());
+        let num_re = self.b0 + self.b1 * c1 + self.b2 * c2;
+        let num_im = self.b1 * s1 + self.b2 * s2;
+        let den_re = 1.0 + self.a1 * c1 + self.a2 * c2;
+        let den_im = self.a1 * s1 + self.a2 * s2;
+        (num_re.hypot(num_im)) / (den_re.hypot(den_im))
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b0 * x + self.z1;
+        self.z1 = self.b1 * x - self.a1 * y + self.z2;
+        self.z2 = self.b2 * x - self.a2 * y;
+        y
+    }
+}
+
+/// Build the filter cascade for a weighting curve
+fn weighting_filters(weighting: MeterWeighting, sample_rate: u32) -> Vec<Biquad> {
+    let fs = sample_rate as f64;
+    match weighting {
+        MeterWeighting::None => Vec::new(),
+        MeterWeighting::A => {
+            use std::f64::consts::PI;
+            let w1 = 2.0 * PI * 20.598997;
+            let w2 = 2.0 * PI * 107.65265;
+            let w3 = 2.0 * PI * 737.86223;
+            let w4 = 2.0 * PI * 12194.217;
+            let mut filters = vec![
+                Biquad::from_analog([1.0, 0.0, 0.0], [1.0, 2.0 * w1, w1 * w1], fs),
+                Biquad::from_analog([1.0, 0.0, 0.0], [1.0, w2 + w3, w2 * w3], fs),
+                Biquad::from_analog([0.0, 0.0, 1.0], [1.0, 2.0 * w4, w4 * w4], fs),
+            ];
+            // Normalize to 0 dB at 1 kHz
+            let gain: f64 = filters.iter().map(|f| f.magnitude(1000.0, fs)).product();
+            filters[0] = filters[0].scale(1.0 / gain);
+            filters
+        }
+        MeterWeighting::K => {
+            // Coefficients per ITU-R BS.1770, recomputed for the actual rate
+            let f0 = 1681.974450955533;
+            let g = 3.999843853973347;
+            let q = 0.7071752369554196;
+            let k = (std::f64::consts::PI * f0 / fs).tan();
+            let vh = 10f64.powf(g / 20.0);
+            let vb = vh.powf(0.4996667741545416);
+            let a0 = 1.0 + k / q + k * k;
+            let shelf = Biquad::new(
+                [
+                    (vh + vb * k / q + k * k) / a0,
+                    2.0 * (k * k - vh) / a0,
+                    (vh - vb * k / q + k * k) / a0,
+                ],
+                [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            );
+
+            let f0 = 38.13547087602444;
+            let q = 0.5003270373238773;
+            let k = (std::f64::consts::PI * f0 / fs).tan();
+            let a0 = 1.0 + k / q + k * k;
+            let highpass = Biquad::new(
+                [1.0, -2.0, 1.0],
+                [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            );
+
+            vec![shelf, highpass]
+        }
+    }
+}
+
+/// Per-channel meter state
+#[derive(Clone)]
+struct ChannelMeter {
+    filters: Vec<Biquad>,
+    mean_square: f64,
+    peak: f32,
+    held_peak: f32,
+    samples_since_hold: u64,
+}
+
+/// Multi-channel level meter for interleaved f32 audio
+#[derive(Clone)]
+pub struct LevelMeter {
+    config: MeteringConfig,
+    sample_rate: u32,
+    channels: Vec<ChannelMeter>,
+    /// EMA coefficient for the RMS integration window
+    rms_coeff: f64,
+    hold_samples: u64,
+}
+
+impl LevelMeter {
+    /// Create a meter for the given stream format
+    pub fn new(config: MeteringConfig, sample_rate: u32, channels: u16) -> Self {
+        let filters = weighting_filters(config.weighting, sample_rate);
+        let window_samples = (config.rms_window_ms.max(1) as f64 / 1000.0) * sample_rate as f64;
+
+        Self {
+            config,
+            sample_rate,
+            channels: (0..channels.max(1))
+                .map(|_| ChannelMeter {
+                    filters: filters.clone(),
+                    mean_square: 0.0,
+                    peak: 0.0,
+                    held_peak: 0.0,
+                    samples_since_hold: 0,
+                })
+                .collect(),
+            rms_coeff: 1.0 - (-1.0 / window_samples).exp(),
+            hold_samples: config.peak_hold_ms as u64 * sample_rate as u64 / 1000,
+        }
+    }
+
+    /// Feed a block of interleaved samples
+    pub fn process(&mut self, samples: &[f32]) {
+        let num_channels = self.channels.len();
+        let frames = samples.len() / num_channels;
+        if frames == 0 {
+            return;
+        }
+
+        for (ch, meter) in self.channels.iter_mut().enumerate() {
+            let mut block_peak = 0.0f32;
+
+            for frame in 0..frames {
+                let sample = samples[frame * num_channels + ch];
+                block_peak = block_peak.max(sample.abs());
+
+                let mut weighted = sample as f64;
+                for filter in &mut meter.filters {
+                    weighted = filter.process(weighted);
+                }
+                meter.mean_square += (weighted * weighted - meter.mean_square) * self.rms_coeff;
+            }
+
+            meter.peak = block_peak;
+            meter.samples_since_hold += frames as u64;
+            if block_peak >= meter.held_peak || meter.samples_since_hold > self.hold_samples {
+                meter.held_peak = block_peak;
+                meter.samples_since_hold = 0;
+            }
+        }
+    }
+
+    /// Current readings for every channel
+    pub fn levels(&self) -> Vec<ChannelLevel> {
+        let offset = if self.config.weighting == MeterWeighting::K {
+            K_WEIGHTING_OFFSET_DB
+        } else {
+            0.0
+        };
+
+        self.channels
+            .iter()
+            .map(|meter| {
+                let rms = meter.mean_square.sqrt() as f32;
+                ChannelLevel {
+                    peak_db: amplitude_to_db(meter.peak),
+                    rms_db: if rms > 0.0 {
+                        (amplitude_to_db(rms) + offset).max(SILENCE_DB)
+                    } else {
+                        SILENCE_DB
+                    },
+                    peak_hold_db: amplitude_to_db(meter.held_peak),
+                    clipped: meter.held_peak >= 1.0,
+                }
+            })
+            .collect()
+    }
+
+    /// Sample rate this meter was built for
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+}
+
+/// Length of a loudness measurement step
+#[cfg(any(feature = "ffmpeg", test))]
+const LOUDNESS_STEP_MS: u32 = 100;
+
+/// Gating blocks are four steps (400 ms) with 75% overlap
+#[cfg(any(feature = "ffmpeg", test))]
+const LOUDNESS_BLOCK_STEPS: usize = 4;
+
+/// Integrated (programme) loudness per ITU-R BS.1770-4 / EBU R128
+///
+/// K-weighted power is averaged over overlapping 400 ms blocks, then gated:
+/// blocks below -70 LUFS are ignored, as are blocks more than 10 LU below
+/// the loudness of the remaining ones. All channels are weighted 1.0, which
+/// is exact for mono and stereo.
+#[cfg(any(feature = "ffmpeg", test))]
+pub struct IntegratedLoudness {
+    filters: Vec<Vec<Biquad>>,
+    step_len: usize,
+    step_power: f64,
+    step_frames: usize,
+    /// Mean power of the most recent steps
+    recent_steps: Vec<f64>,
+    /// Mean power of every block so far
+    blocks: Vec<f64>,
+    peak: f32,
+}
+
+#[cfg(any(feature = "ffmpeg", test))]
+impl IntegratedLoudness {
+    /// Create a measurement for the given stream format
+    pub fn new(sample_rate: u32, channels: u16) -> Self {
+        let filters = weighting_filters(MeterWeighting::K, sample_rate);
+        Self {
+            filters: (0..channels.max(1)).map(|_| filters.clone()).collect(),
+            step_len: (sample_rate * LOUDNESS_STEP_MS / 1000).max(1) as usize,
+            step_power: 0.0,
+            step_frames: 0,
+            recent_steps: Vec::with_capacity(LOUDNESS_BLOCK_STEPS),
+            blocks: Vec::new(),
+            peak: 0.0,
+        }
+    }
+
+    /// Feed a block of interleaved samples
+    pub fn process(&mut self, samples: &[f32]) {
+        let num_channels = self.filters.len();
+        for frame in samples.chunks_exact(num_channels) {
+            for (sample, filters) in frame.iter().zip(&mut self.filters) {
+                self.peak = self.peak.max(sample.abs());
+                let mut weighted = *sample as f64;
+                for filter in filters.iter_mut() {
+                    weighted = filter.process(weighted);
+                }
+                self.step_power += weighted * weighted;
+            }
+
+            self.step_frames += 1;
+            if self.step_frames == self.step_len {
+                if self.recent_steps.len() == LOUDNESS_BLOCK_STEPS {
+                    self.recent_steps.remove(0);
+                }
+                self.recent_steps.push(self.step_power / self.step_len as f64);
+                if self.recent_steps.len() == LOUDNESS_BLOCK_STEPS {
+                    self.blocks
+                        .push(self.recent_steps.iter().sum::<f64>() / LOUDNESS_BLOCK_STEPS as f64);
+                }
+                self.step_power = 0.0;
+                self.step_frames = 0;
+            }
+        }
+    }
+
+    /// Gated integrated loudness in LUFS (None if everything so far is below
+    /// the absolute gate, or shorter than one block)
+    pub fn loudness(&self) -> Option<f64> {
+        let to_lufs = |power: f64| K_WEIGHTING_OFFSET_DB as f64 + 10.0 * power.log10();
+        let gated_mean = |threshold: f64| {
+            let gated: Vec<f64> = self
+                .blocks
+                .iter()
+                .copied()
+                .filter(|&power| power > 0.0 && to_lufs(power) > threshold)
+                .collect();
+            (!gated.is_empty()).then(|| gated.iter().sum::<f64>() / gated.len() as f64)
+        };
+
+        let ungated = gated_mean(-70.0)?;
+        gated_mean(to_lufs(ungated) - 10.0).map(to_lufs)
+    }
+
+    /// Highest sample magnitude so far, in dBFS
+    pub fn peak_db(&self) -> f32 {
+        amplitude_to_db(self.peak)
+    }
+}
+
+/// The set of meters maintained by the audio mixer
+pub struct MeterBank {
+    config: MeteringConfig,
+    mic: Option<LevelMeter>,
+    system: Option<LevelMeter>,
+    output: LevelMeter,
+    channels: u16,
+}
+
+impl MeterBank {
+    /// Create meters for the mixer's output format
+    pub fn new(config: MeteringConfig, sample_rate: u32, channels: u16) -> Self {
+        Self {
+            config,
+            mic: None,
+            system: None,
+            output: LevelMeter::new(config, sample_rate, channels),
+            channels,
+        }
+    }
+
+    /// Replace the metering configuration, resetting all meters
+    pub fn set_config(&mut self, config: MeteringConfig) {
+        let sample_rate = self.output.sample_rate();
+        self.config = config;
+        self.output = LevelMeter::new(config, sample_rate, self.channels);
+        if self.mic.is_some() {
+            self.mic = Some(LevelMeter::new(config, sample_rate, self.channels));
+        }
+        if self.system.is_some() {
+            self.system = Some(LevelMeter::new(config, sample_rate, self.channels));
+        }
+    }
+
+    /// Meter processed microphone samples
+    pub fn process_mic(&mut self, samples: &[f32]) {
+        let (config, sample_rate, channels) =
+            (self.config, self.output.sample_rate(), self.channels);
+        self.mic
+            .get_or_insert_with(|| LevelMeter::new(config, sample_rate, channels))
+            .process(samples);
+    }
+
+    /// Meter processed system audio samples
+    pub fn process_system(&mut self, samples: &[f32]) {
+        let (config, sample_rate, channels) =
+            (self.config, self.output.sample_rate(), self.channels);
+        self.system
+            .get_or_insert_with(|| LevelMeter::new(config, sample_rate, channels))
+            .process(samples);
+    }
+
+    /// Meter the final mix
+    pub fn process_output(&mut self, samples: &[f32]) {
+        self.output.process(samples);
+    }
+
+    /// Snapshot of all current levels
+    pub fn snapshot(&self) -> AudioLevels {
+        AudioLevels {
+            weighting: self.config.weighting,
+            mic: self.mic.as_ref().map(|m| m.levels()).unwrap_or_default(),
+            system: self.system.as_ref().map(|m| m.levels()).unwrap_or_default(),
+            output: self.output.levels(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(freq: f32, amplitude: f32, sample_rate: u32, seconds: f32) -> Vec<f32> {
+        let count = (sample_rate as f32 * seconds) as usize;
+        (0..count)
+            .map(|i| {
+                amplitude
+                    * (2.0 * std::f32::consts::PI * freq * i as f32 / sample_rate as f32).sin()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_unweighted_sine_levels() {
+        let mut meter = LevelMeter::new(MeteringConfig::default(), 48000, 1);
+        meter.process(&sine(1000.0, 1.0, 48000, 3.0));
+        let level = meter.levels()[0];
+        assert!(level.peak_db.abs() < 0.1);
+        assert!((level.rms_db + 3.01).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_weighted_1khz_matches_flat() {
+        // Both curves are referenced to 1 kHz, so a 1 kHz tone reads the same
+        for weighting in [MeterWeighting::A, MeterWeighting::K] {
+            let config = MeteringConfig { weighting, ..MeteringConfig::default() };
+            let mut meter = LevelMeter::new(config, 48000, 1);
+            meter.process(&sine(1000.0, 0.5, 48000, 3.0));
+            let level = meter.levels()[0];
+            assert!((level.rms_db + 9.03).abs() < 0.2, "{:?}: {}", weighting, level.rms_db);
+        }
+
+        // A-weighting strongly attenuates low frequencies
+        let config = MeteringConfig { weighting: MeterWeighting::A, ..MeteringConfig::default() };
+        let mut meter = LevelMeter::new(config, 48000, 1);
+        meter.process(&sine(50.0, 0.5, 48000, 1.0));
+        assert!(meter.levels()[0].rms_db < -35.0);
+    }
+
+    #[test]
+    fn test_peak_hold_expires() {
+        let config = MeteringConfig { peak_hold_ms: 100, ..MeteringConfig::default() };
+        let mut meter = LevelMeter::new(config, 1000, 1);
+        meter.process(&[0.5; 10]);
+        meter.process(&[0.1; 50]);
+        assert!((meter.levels()[0].peak_hold_db - amplitude_to_db(0.5)).abs() < 0.01);
+        meter.process(&[0.1; 60]);
+        assert!((meter.levels()[0].peak_hold_db - amplitude_to_db(0.1)).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_integrated_loudness() {
+        // EBU Tech 3341 case 1: a stereo 1 kHz tone at -23 dBFS reads -23 LUFS
+        let tone = sine(1000.0, 10f32.powf(-23.0 / 20.0), 48000, 5.0);
+        let stereo: Vec<f32> = tone.iter().flat_map(|&s| [s, s]).collect();
+        let mut loudness = IntegratedLoudness::new(48000, 2);
+        loudness.process(&stereo);
+        let lufs = loudness.loudness().unwrap();
+        assert!((lufs + 23.0).abs() < 0.1, "{}", lufs);
+        assert!((loudness.peak_db() + 23.0).abs() < 0.1);
+
+        // Silence after the tone is gated out (only the blocks overlapping
+        // the end of the tone count)
+        loudness.process(&vec![0.0; 48000 * 2 * 5]);
+        assert!((loudness.loudness().unwrap() - lufs).abs() < 0.2);
+        assert_eq!(IntegratedLoudness::new(48000, 2).loudness(), None);
+    }
+}
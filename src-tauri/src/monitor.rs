@@ -0,0 +1,376 @@
+//! Live Microphone Monitoring
+//!
+//! Plays the processed microphone signal (after gain) back to an output
+//! device so creators can hear themselves while recording. The mixer sends
+//! chunks through a channel; a cpal output stream drains a small buffer that
+//! is kept close to the configured latency.
+//!
+//! For monitoring below ~20ms round trip, lower `latencyMs`, request a small
+//! output buffer with `outputBufferFrames` and a small microphone buffer with
+//! `audioBuffer.deviceBufferFrames`. `get_audio_latency` reports the round
+//! trip the devices actually run at.
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{SampleFormat, SampleRate, StreamConfig, SupportedStreamConfig};
+use crossbeam_channel::{bounded, Receiver, Sender};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::audio::{stream_config, AudioChunk};
+use crate::audio_mixer::{convert_channels, resample};
+
+/// Accepted monitoring latency range in milliseconds
+pub const MONITOR_LATENCY_RANGE_MS: (u32, u32) = (5, 500);
+
+/// Microphone monitoring configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MonitorConfig {
+    /// Output device name (None for the default output)
+    #[serde(default)]
+    pub output_device: Option<String>,
+    /// Target delay between capture and playback (5-500ms, default 50ms)
+    #[serde(default = "default_latency_ms")]
+    pub latency_ms: u32,
+    /// Monitor volume (0.0 - 2.0, default 1.0)
+    #[serde(default = "default_monitor_volume")]
+    pub volume: f32,
+    /// Frames per output callback to request (None: the device default,
+    /// often far larger than the latency target)
+    #[serde(default)]
+    pub output_buffer_frames: Option<u32>,
+}
+
+fn default_latency_ms() -> u32 {
+    50
+}
+
+fn default_monitor_volume() -> f32 {
+    1.0
+}
+
+impl Default for MonitorConfig {
+    fn default() -> Self {
+        Self {
+            output_device: None,
+            latency_ms: default_latency_ms(),
+            volume: default_monitor_volume(),
+            output_buffer_frames: None,
+        }
+    }
+}
+
+impl MonitorConfig {
+    /// Check latency and volume ranges; the output buffer is checked against
+    /// what the device supports when monitoring starts
+    pub fn validate(&self) -> Result<(), String> {
+        let (min, max) = MONITOR_LATENCY_RANGE_MS;
+        if !(min..=max).contains(&self.latency_ms) {
+            return Err(format!(
+                "Monitoring latency must be between {} and {}ms, got {}",
+                min, max, self.latency_ms
+            ));
+        }
+        if !(0.0..=2.0).contains(&self.volume) {
+            return Err(format!("Monitor volume must be between 0.0 and 2.0, got {}", self.volume));
+        }
+        if self.output_buffer_frames == Some(0) {
+            return Err("Monitor output buffer must be at least one frame".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Size and delay of the latest monitor output callback
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MonitorTiming {
+    pub sample_rate: u32,
+    pub buffer_frames: u32,
+    /// Time from callback to playback, where the host reports it
+    pub playback_delay: Option<Duration>,
+    /// Configured capture-to-playback delay
+    pub latency_ms: u32,
+}
+
+/// Plays the microphone back to an output device
+pub struct MicMonitor {
+    running: Arc<Mutex<bool>>,
+    sender: Sender<AudioChunk>,
+    timing: Arc<Mutex<Option<MonitorTiming>>>,
+}
+
+impl MicMonitor {
+    /// Open the output device and start playback
+    ///
+    /// `sample_rate` and `channels` describe the chunks the mixer will send.
+    pub fn start(config: MonitorConfig, sample_rate: u32, channels: u16) -> Result<Self, String> {
+        config.validate()?;
+
+        let device = find_output_device(config.output_device.as_deref())?;
+        let output_config = select_output_config(&device, sample_rate, channels)?;
+        let stream_config = stream_config(&output_config, config.output_buffer_frames)?;
+
+        let (sender, receiver) = bounded(30);
+        let running = Arc::new(Mutex::new(true));
+        let timing = Arc::new(Mutex::new(None));
+
+        let running_clone = running.clone();
+        let timing_clone = timing.clone();
+        std::thread::spawn(move || {
+            let output = MonitorOutput {
+                device,
+                sample_format: output_config.sample_format(),
+                stream_config,
+                timing: timing_clone,
+            };
+            if let Err(e) = run_monitor(running_clone, receiver, output, config) {
+                eprintln!("Mic monitor error: {}", e);
+            }
+        });
+
+        Ok(Self { running, sender, timing })
+    }
+
+    /// Sender for the mixer's monitor tap
+    pub fn sender(&self) -> Sender<AudioChunk> {
+        self.sender.clone()
+    }
+
+    /// Timing of the latest output callback (None until the first)
+    pub fn timing(&self) -> Option<MonitorTiming> {
+        *self.timing.lock()
+    }
+
+    /// Stop playback
+    pub fn stop(&self) {
+        *self.running.lock() = false;
+        println!("Mic monitor stopped");
+    }
+}
+
+impl Drop for MicMonitor {
+    fn drop(&mut self) {
+        *self.running.lock() = false;
+    }
+}
+
+fn find_output_device(name: Option<&str>) -> Result<cpal::Device, String> {
+    let host = cpal::default_host();
+    match name {
+        Some(name) => host
+            .output_devices()
+            .map_err(|e| format!("Failed to enumerate output devices: {}", e))?
+            .find(|d| d.name().map(|n| n == name).unwrap_or(false))
+            .ok_or_else(|| format!("Output device '{}' not found", name)),
+        None => host
+            .default_output_device()
+            .ok_or_else(|| "No default output device available".to_string()),
+    }
+}
+
+/// Prefer an output config matching the mixer so no conversion is needed
+fn select_output_config(
+    device: &cpal::Device,
+    sample_rate: u32,
+    channels: u16,
+) -> Result<SupportedStreamConfig, String> {
+    let matching = device.supported_output_configs().ok().and_then(|mut configs| {
+        configs.find(|c| {
+            c.channels() == channels
+                && c.sample_format() == SampleFormat::F32
+                && c.min_sample_rate().0 <= sample_rate
+                && sample_rate <= c.max_sample_rate().0
+        })
+    });
+
+    match matching {
+        Some(range) => Ok(range.with_sample_rate(SampleRate(sample_rate))),
+        None => device
+            .default_output_config()
+            .map_err(|e| format!("Failed to get output config: {}", e)),
+    }
+}
+
+/// Buffer between the mixer and the output callback
+struct MonitorBuffer {
+    samples: VecDeque<f32>,
+    /// Samples to accumulate before playback (re)starts
+    target: usize,
+    primed: bool,
+}
+
+impl MonitorBuffer {
+    fn new(target: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(target * 4),
+            target,
+            primed: false,
+        }
+    }
+
+    /// Append samples, dropping the oldest if latency grows past twice the target
+    fn push(&mut self, samples: &[f32]) {
+        self.samples.extend(samples);
+        let limit = self.target * 2;
+        if self.samples.len() > limit {
+            let excess = self.samples.len() - self.target;
+            self.samples.drain(..excess);
+        }
+    }
+
+    /// Next sample for the output, or silence while (re)filling
+    fn pop(&mut self) -> f32 {
+        if !self.primed {
+            if self.samples.len() < self.target {
+                return 0.0;
+            }
+            self.primed = true;
+        }
+        match self.samples.pop_front() {
+            Some(sample) => sample,
+            None => {
+                self.primed = false;
+                0.0
+            }
+        }
+    }
+}
+
+/// The output stream to open, and where it records its timing
+struct MonitorOutput {
+    device: cpal::Device,
+    sample_format: SampleFormat,
+    stream_config: StreamConfig,
+    timing: Arc<Mutex<Option<MonitorTiming>>>,
+}
+
+/// Record the size and delay of an output callback's buffer
+fn record_timing(
+    timing: &Mutex<Option<MonitorTiming>>,
+    info: &cpal::OutputCallbackInfo,
+    samples: usize,
+    channels: u16,
+    sample_rate: u32,
+    latency_ms: u32,
+) {
+    let timestamp = info.timestamp();
+    *timing.lock() = Some(MonitorTiming {
+        sample_rate,
+        buffer_frames: (samples / channels.max(1) as usize) as u32,
+        playback_delay: timestamp.playback.duration_since(&timestamp.callback),
+        latency_ms,
+    });
+}
+
+/// Run the output stream until stopped
+fn run_monitor(
+    running: Arc<Mutex<bool>>,
+    receiver: Receiver<AudioChunk>,
+    output: MonitorOutput,
+    config: MonitorConfig,
+) -> Result<(), String> {
+    let MonitorOutput { device, sample_format, stream_config, timing } = output;
+    let out_rate = stream_config.sample_rate.0;
+    let out_channels = stream_config.channels;
+    let latency_ms = config.latency_ms;
+
+    let target = (out_rate as usize * out_channels as usize * config.latency_ms as usize) / 1000;
+    let buffer = Arc::new(Mutex::new(MonitorBuffer::new(target.max(out_channels as usize))));
+
+    let err_fn = |err| eprintln!("Monitor stream error: {}", err);
+    let playback = buffer.clone();
+
+    let stream = match sample_format {
+        SampleFormat::F32 => device.build_output_stream(
+            &stream_config,
+            move |data: &mut [f32], info: &cpal::OutputCallbackInfo| {
+                record_timing(&timing, info, data.len(), out_channels, out_rate, latency_ms);
+                let mut buffer = playback.lock();
+                for sample in data.iter_mut() {
+                    *sample = buffer.pop();
+                }
+            },
+            err_fn,
+            None,
+        ),
+        SampleFormat::I16 => device.build_output_stream(
+            &stream_config,
+            move |data: &mut [i16], info: &cpal::OutputCallbackInfo| {
+                record_timing(&timing, info, data.len(), out_channels, out_rate, latency_ms);
+                let mut buffer = playback.lock();
+                for sample in data.iter_mut() {
+                    *sample = (buffer.pop().clamp(-1.0, 1.0) * 32767.0) as i16;
+                }
+            },
+            err_fn,
+            None,
+        ),
+        SampleFormat::U16 => device.build_output_stream(
+            &stream_config,
+            move |data: &mut [u16], info: &cpal::OutputCallbackInfo| {
+                record_timing(&timing, info, data.len(), out_channels, out_rate, latency_ms);
+                let mut buffer = playback.lock();
+                for sample in data.iter_mut() {
+                    *sample = (buffer.pop().clamp(-1.0, 1.0) * 32767.0 + 32768.0) as u16;
+                }
+            },
+            err_fn,
+            None,
+        ),
+        _ => return Err(format!("Unsupported output sample format: {:?}", sample_format)),
+    }
+    .map_err(|e| format!("Failed to build monitor stream: {}", e))?;
+
+    stream.play().map_err(|e| format!("Failed to start monitor stream: {}", e))?;
+
+    println!(
+        "Mic monitor started: {}Hz, {} channels, {}ms latency",
+        out_rate, out_channels, config.latency_ms
+    );
+
+    while *running.lock() {
+        match receiver.recv_timeout(Duration::from_millis(100)) {
+            Ok(chunk) => {
+                let mut samples = chunk.samples;
+                if chunk.channels != out_channels {
+                    samples = convert_channels(&samples, chunk.channels, out_channels);
+                }
+                if chunk.sample_rate != out_rate {
+                    samples = resample(&samples, chunk.sample_rate, out_rate, out_channels);
+                }
+                for sample in &mut samples {
+                    *sample *= config.volume;
+                }
+                buffer.lock().push(&samples);
+            }
+            Err(crossbeam_channel::RecvTimeoutError::Timeout) => {}
+            Err(crossbeam_channel::RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    // Stream is dropped when function returns
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_monitor_buffer_primes_and_bounds_latency() {
+        let mut buffer = MonitorBuffer::new(4);
+
+        // Silence until the target latency is buffered
+        buffer.push(&[0.1, 0.2]);
+        assert_eq!(buffer.pop(), 0.0);
+        buffer.push(&[0.3, 0.4]);
+        assert_eq!(buffer.pop(), 0.1);
+
+        // Backlog beyond twice the target is trimmed back to the target
+        buffer.push(&[1.0; 10]);
+        assert_eq!(buffer.samples.len(), 4);
+    }
+}
@@ -0,0 +1,328 @@
+//! Confidence Monitor Feed
+//!
+//! Streams a downscaled copy of the final composited output to a separate
+//! monitor window over a Tauri channel. The recording threads only compare a
+//! timestamp and, when a feed frame is due, hand a copy to the feed's own
+//! worker; scaling and JPEG encoding happen there, and frames are dropped
+//! rather than queued so the feed never holds up the encoder.
+//!
+//! The remote preview server subscribes alongside the monitor window, each
+//! with its own rate cap and worker.
+//!
+//! Each channel message is raw bytes: width and height as little-endian u32,
+//! followed by the JPEG file or the tightly packed RGBA pixels.
+
+use crossbeam_channel::{bounded, Receiver, Sender, TrySendError};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::ipc::{Channel, InvokeResponseBody};
+use tauri::command;
+
+use crate::compositor::{CompositeFrame, FrameFormat};
+use crate::recording::RecordingError;
+use crate::screen::nv12_to_rgb;
+
+/// Encoding of feed frames
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MonitorFeedFormat {
+    /// JPEG (MJPEG-style stream)
+    #[default]
+    Jpeg,
+    /// Uncompressed RGBA
+    Raw,
+}
+
+/// Monitor feed settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MonitorFeedConfig {
+    /// Largest feed width; frames are scaled down keeping the aspect ratio
+    #[serde(default = "default_max_width")]
+    pub max_width: u32,
+    /// Feed frame rate cap (1-30)
+    #[serde(default = "default_frame_rate")]
+    pub frame_rate: u32,
+    #[serde(default)]
+    pub format: MonitorFeedFormat,
+    /// JPEG quality (1-100)
+    #[serde(default = "default_jpeg_quality")]
+    pub jpeg_quality: u8,
+}
+
+fn default_max_width() -> u32 {
+    640
+}
+
+fn default_frame_rate() -> u32 {
+    15
+}
+
+fn default_jpeg_quality() -> u8 {
+    70
+}
+
+impl Default for MonitorFeedConfig {
+    fn default() -> Self {
+        Self {
+            max_width: default_max_width(),
+            frame_rate: default_frame_rate(),
+            format: MonitorFeedFormat::default(),
+            jpeg_quality: default_jpeg_quality(),
+        }
+    }
+}
+
+impl MonitorFeedConfig {
+    /// Check the settings are in range
+    pub fn validate(&self) -> Result<(), String> {
+        if !(16..=3840).contains(&self.max_width) {
+            return Err(format!("Monitor feed width must be 16-3840 (got {})", self.max_width));
+        }
+        if !(1..=30).contains(&self.frame_rate) {
+            return Err(format!("Monitor feed frame rate must be 1-30 (got {})", self.frame_rate));
+        }
+        if !(1..=100).contains(&self.jpeg_quality) {
+            return Err(format!("JPEG quality must be 1-100 (got {})", self.jpeg_quality));
+        }
+        Ok(())
+    }
+}
+
+/// Receives encoded feed messages; an error ends the subscription
+pub(crate) type FeedSink = Box<dyn Fn(Vec<u8>) -> Result<(), String> + Send>;
+
+/// Who a feed subscription is for; each has its own slot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FeedClient {
+    /// The confidence monitor window (or the webcam preview's own feed)
+    Window,
+    /// The remote preview server
+    Remote,
+}
+
+struct Subscriber {
+    client: FeedClient,
+    interval: Duration,
+    last_offered: Option<Instant>,
+    sender: Sender<CompositeFrame>,
+}
+
+/// Shared handle to the monitor feed, held by both recorders
+///
+/// One subscription per client; a new subscription replaces that client's.
+#[derive(Clone, Default)]
+pub struct MonitorFeed {
+    subscribers: Arc<Mutex<Vec<Subscriber>>>,
+}
+
+impl MonitorFeed {
+    /// Start sending feed frames to `sink`
+    pub(crate) fn subscribe(&self, client: FeedClient, config: MonitorFeedConfig, sink: FeedSink) -> Result<(), String> {
+        config.validate()?;
+
+        // One slot: a frame waiting here is replaced by skipping the next offers
+        let (sender, receiver) = bounded(1);
+        let interval = Duration::from_secs_f64(1.0 / config.frame_rate as f64);
+
+        std::thread::spawn(move || {
+            feed_loop(config, receiver, sink);
+        });
+
+        // Dropping the old subscriber's sender ends its worker
+        let mut subscribers = self.subscribers.lock();
+        subscribers.retain(|subscriber| subscriber.client != client);
+        subscribers.push(Subscriber {
+            client,
+            interval,
+            last_offered: None,
+            sender,
+        });
+        Ok(())
+    }
+
+    /// Stop the feed to `client`
+    pub(crate) fn unsubscribe(&self, client: FeedClient) {
+        self.subscribers.lock().retain(|subscriber| subscriber.client != client);
+    }
+
+    /// Offer a composited frame; passed on (sharing its pixels) only when a
+    /// feed frame is due
+    pub fn offer(&self, frame: &CompositeFrame) {
+        let now = Instant::now();
+        self.subscribers.lock().retain_mut(|subscriber| {
+            if subscriber
+                .last_offered
+                .is_some_and(|last| now.duration_since(last) < subscriber.interval)
+            {
+                return true;
+            }
+            // Skip the frame entirely while the worker is still busy
+            if subscriber.sender.is_full() {
+                return true;
+            }
+
+            match subscriber.sender.try_send(frame.clone()) {
+                Ok(()) => subscriber.last_offered = Some(now),
+                Err(TrySendError::Full(_)) => {}
+                Err(TrySendError::Disconnected(_)) => return false,
+            }
+            true
+        });
+    }
+}
+
+fn feed_loop(config: MonitorFeedConfig, receiver: Receiver<CompositeFrame>, sink: FeedSink) {
+    println!(
+        "Monitor feed started ({}px wide, {}fps, {:?})",
+        config.max_width, config.frame_rate, config.format
+    );
+
+    for frame in receiver {
+        let (width, height, rgba) = downscale_to_rgba(&frame, config.max_width);
+
+        let mut message = Vec::with_capacity(8 + rgba.len());
+        message.extend_from_slice(&width.to_le_bytes());
+        message.extend_from_slice(&height.to_le_bytes());
+
+        match config.format {
+            MonitorFeedFormat::Raw => message.extend_from_slice(&rgba),
+            MonitorFeedFormat::Jpeg => {
+                let rgb: Vec<u8> = rgba
+                    .chunks_exact(4)
+                    .flat_map(|p| [p[0], p[1], p[2]])
+                    .collect();
+                let mut encoder =
+                    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut message, config.jpeg_quality);
+                if let Err(e) = encoder.encode(&rgb, width, height, image::ExtendedColorType::Rgb8) {
+                    eprintln!("Monitor feed JPEG encoding failed: {}", e);
+                    continue;
+                }
+            }
+        }
+
+        // The window went away
+        if let Err(e) = sink(message) {
+            eprintln!("Monitor feed closed: {}", e);
+            break;
+        }
+    }
+
+    println!("Monitor feed stopped");
+}
+
+/// Nearest-neighbour downscale of any composited format to RGBA
+///
+/// Frames narrower than `max_width` keep their size.
+fn downscale_to_rgba(frame: &CompositeFrame, max_width: u32) -> (u32, u32, Vec<u8>) {
+    let (src_w, src_h) = (frame.width as usize, frame.height as usize);
+    let width = frame.width.min(max_width).max(1);
+    let height = ((frame.height as u64 * width as u64 / frame.width.max(1) as u64) as u32).max(1);
+
+    let mut rgba = Vec::with_capacity(width as usize * height as usize * 4);
+    for y in 0..height as usize {
+        let sy = y * src_h / height as usize;
+        for x in 0..width as usize {
+            let sx = x * src_w / width as usize;
+            let pixel = match frame.format {
+                FrameFormat::Rgba => {
+                    let i = (sy * src_w + sx) * 4;
+                    [frame.data[i], frame.data[i + 1], frame.data[i + 2]]
+                }
+                FrameFormat::Bgra => {
+                    let i = (sy * src_w + sx) * 4;
+                    [frame.data[i + 2], frame.data[i + 1], frame.data[i]]
+                }
+                FrameFormat::Nv12 => {
+                    let uv = src_w * src_h + (sy / 2) * src_w + (sx & !1);
+                    nv12_to_rgb(frame.data[sy * src_w + sx], frame.data[uv], frame.data[uv + 1])
+                }
+            };
+            rgba.extend_from_slice(&pixel);
+            rgba.push(255);
+        }
+    }
+
+    (width, height, rgba)
+}
+
+/// Tauri command: Stream the composited output to a monitor window
+///
+/// Replaces any existing subscription. The feed runs across recordings and
+/// only sends frames while one is in progress.
+#[command]
+pub fn get_monitor_feed(
+    config: Option<MonitorFeedConfig>,
+    on_frame: Channel<InvokeResponseBody>,
+    feed: tauri::State<'_, MonitorFeed>,
+) -> Result<(), RecordingError> {
+    feed.subscribe(
+        FeedClient::Window,
+        config.unwrap_or_default(),
+        Box::new(move |message| {
+            on_frame
+                .send(InvokeResponseBody::Raw(message))
+                .map_err(|e| e.to_string())
+        }),
+    )
+    .map_err(RecordingError::InvalidConfig)
+}
+
+/// Tauri command: Stop the monitor feed
+#[command]
+pub fn stop_monitor_feed(feed: tauri::State<'_, MonitorFeed>) {
+    feed.unsubscribe(FeedClient::Window);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_monitor_feed_downscales_and_rate_limits() {
+        // 4x2 BGRA: left half red, right half blue
+        let mut data = Vec::new();
+        for _ in 0..2 {
+            data.extend_from_slice(&[0, 0, 255, 255, 0, 0, 255, 255, 255, 0, 0, 255, 255, 0, 0, 255]);
+        }
+        let frame = CompositeFrame {
+            data: crate::frame_pool::FrameBuffer::shared(data),
+            width: 4,
+            height: 2,
+            timestamp: Duration::ZERO,
+            format: FrameFormat::Bgra,
+        };
+
+        let (width, height, rgba) = downscale_to_rgba(&frame, 2);
+        assert_eq!((width, height), (2, 1));
+        assert_eq!(rgba, vec![255, 0, 0, 255, 0, 0, 255, 255]);
+
+        let (sink_sender, messages) = bounded(8);
+        let feed = MonitorFeed::default();
+        let config = MonitorFeedConfig {
+            max_width: 16,
+            frame_rate: 1,
+            format: MonitorFeedFormat::Raw,
+            ..MonitorFeedConfig::default()
+        };
+        feed.subscribe(
+            FeedClient::Window,
+            config,
+            Box::new(move |message| sink_sender.send(message).map_err(|e| e.to_string())),
+        )
+        .unwrap();
+
+        // Only the first of a burst is within the 1fps cap
+        for _ in 0..5 {
+            feed.offer(&frame);
+        }
+        feed.unsubscribe(FeedClient::Window);
+
+        let messages: Vec<Vec<u8>> = messages.iter().collect();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(&messages[0][..8], &[4, 0, 0, 0, 2, 0, 0, 0]);
+        assert_eq!(messages[0].len(), 8 + 4 * 2 * 4);
+    }
+}
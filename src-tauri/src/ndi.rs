@@ -0,0 +1,325 @@
+//! NDI Output
+//!
+//! Publishes the composited video and mixed audio of the recording in
+//! progress as an NDI source on the local network, so OBS, vMix or a
+//! hardware switcher can take the recorder's output without a capture card.
+//! The encoder hands frames and audio to the sink's inputs like it does for
+//! the streaming sinks; frames go out uncompressed in their composited layout
+//! (NDI compresses them itself), and audio as planar float.
+//!
+//! Sending needs the `ndi` feature, which links against the NDI runtime
+//! (`libndi` on Linux and macOS, `Processing.NDI.Lib.x64` on Windows);
+//! without it, starting the sink fails.
+
+use crossbeam_channel::Receiver;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use crate::streaming::{StreamFormat, StreamInputs};
+
+#[cfg(not(feature = "ndi"))]
+const FEATURE_MISSING: &str = "NDI output requires a build with the `ndi` feature";
+
+/// NDI source settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NdiConfig {
+    /// Name receivers list the source under (after the machine name)
+    #[serde(default = "default_source_name")]
+    pub source_name: String,
+    /// Comma-separated NDI groups to publish in (default: the public group)
+    #[serde(default)]
+    pub groups: Option<String>,
+}
+
+fn default_source_name() -> String {
+    "ASMR Recorder".to_string()
+}
+
+impl Default for NdiConfig {
+    fn default() -> Self {
+        Self {
+            source_name: default_source_name(),
+            groups: None,
+        }
+    }
+}
+
+impl NdiConfig {
+    /// Check the names can be handed to the NDI runtime
+    pub fn validate(&self) -> Result<(), String> {
+        let name = self.source_name.trim();
+        if name.is_empty() {
+            return Err("NDI source name is empty".to_string());
+        }
+        if name.len() > 128 {
+            return Err(format!("NDI source name must be at most 128 bytes (got {})", name.len()));
+        }
+        let names = std::iter::once(name).chain(self.groups.as_deref());
+        if names.clone().any(|name| name.contains('\0')) {
+            return Err("NDI names can't contain NUL characters".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// The recording published as an NDI source
+pub struct NdiSink {
+    inputs: StreamInputs,
+    running: Arc<Mutex<bool>>,
+    thread: Option<JoinHandle<()>>,
+    error_receiver: Receiver<String>,
+}
+
+impl NdiSink {
+    /// Create the NDI sender and start sending on a new thread
+    #[cfg(feature = "ndi")]
+    pub fn start(config: &NdiConfig, format: StreamFormat) -> Result<Self, String> {
+        config.validate()?;
+        let (inputs, video_receiver, audio_receiver) = StreamInputs::channels();
+        let (error_sender, error_receiver) = crossbeam_channel::bounded(1);
+        let running = Arc::new(Mutex::new(true));
+        let thread_running = running.clone();
+        let config = config.clone();
+
+        let thread = std::thread::Builder::new()
+            .name("ndi-send".to_string())
+            .spawn(move || {
+                let result = send_loop(&config, format, &thread_running, video_receiver, audio_receiver);
+                *thread_running.lock() = false;
+                match result {
+                    Ok(()) => println!("NDI source \"{}\" closed", config.source_name),
+                    Err(e) => {
+                        eprintln!("NDI source \"{}\" failed: {}", config.source_name, e);
+                        let _ = error_sender.try_send(e);
+                    }
+                }
+            })
+            .map_err(|e| format!("Failed to start NDI output: {}", e))?;
+
+        Ok(Self {
+            inputs,
+            running,
+            thread: Some(thread),
+            error_receiver,
+        })
+    }
+
+    #[cfg(not(feature = "ndi"))]
+    pub fn start(config: &NdiConfig, _format: StreamFormat) -> Result<Self, String> {
+        config.validate()?;
+        Err(FEATURE_MISSING.to_string())
+    }
+
+    /// Inputs for the encoder to feed
+    pub fn inputs(&self) -> StreamInputs {
+        self.inputs.clone()
+    }
+
+    /// Why the source closed by itself, if it did
+    pub fn error(&self) -> Option<String> {
+        self.error_receiver.try_recv().ok()
+    }
+
+    /// Take the source off the network
+    pub fn stop(&self) {
+        *self.running.lock() = false;
+    }
+}
+
+impl Drop for NdiSink {
+    fn drop(&mut self) {
+        self.stop();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Bindings for the parts of the NDI SDK's sending API used here
+#[cfg(feature = "ndi")]
+mod ffi {
+    use std::os::raw::{c_char, c_float, c_int, c_void};
+
+    pub type SendInstance = *mut c_void;
+
+    #[repr(C)]
+    pub struct SendCreate {
+        pub p_ndi_name: *const c_char,
+        pub p_groups: *const c_char,
+        pub clock_video: bool,
+        pub clock_audio: bool,
+    }
+
+    #[repr(C)]
+    pub struct VideoFrameV2 {
+        pub xres: c_int,
+        pub yres: c_int,
+        pub four_cc: u32,
+        pub frame_rate_n: c_int,
+        pub frame_rate_d: c_int,
+        pub picture_aspect_ratio: c_float,
+        pub frame_format_type: c_int,
+        pub timecode: i64,
+        pub p_data: *const u8,
+        pub line_stride_in_bytes: c_int,
+        pub p_metadata: *const c_char,
+        pub timestamp: i64,
+    }
+
+    #[repr(C)]
+    pub struct AudioFrameV2 {
+        pub sample_rate: c_int,
+        pub no_channels: c_int,
+        pub no_samples: c_int,
+        pub timecode: i64,
+        pub p_data: *const c_float,
+        pub channel_stride_in_bytes: c_int,
+        pub p_metadata: *const c_char,
+        pub timestamp: i64,
+    }
+
+    /// `NDIlib_frame_format_type_progressive`
+    pub const FRAME_FORMAT_PROGRESSIVE: c_int = 1;
+
+    /// `NDIlib_send_timecode_synthesize`: let the runtime fill it in
+    pub const TIMESTAMP_UNDEFINED: i64 = i64::MAX;
+
+    /// `NDI_LIB_FOURCC`
+    pub const fn four_cc(code: &[u8; 4]) -> u32 {
+        u32::from_le_bytes(*code)
+    }
+
+    #[cfg_attr(windows, link(name = "Processing.NDI.Lib.x64"))]
+    #[cfg_attr(not(windows), link(name = "ndi"))]
+    extern "C" {
+        pub fn NDIlib_initialize() -> bool;
+        pub fn NDIlib_send_create(settings: *const SendCreate) -> SendInstance;
+        pub fn NDIlib_send_destroy(instance: SendInstance);
+        pub fn NDIlib_send_send_video_v2(instance: SendInstance, frame: *const VideoFrameV2);
+        pub fn NDIlib_send_send_audio_v2(instance: SendInstance, frame: *const AudioFrameV2);
+    }
+}
+
+/// Send until stopped or the inputs close
+#[cfg(feature = "ndi")]
+fn send_loop(
+    config: &NdiConfig,
+    format: StreamFormat,
+    running: &Mutex<bool>,
+    video_receiver: Receiver<crate::compositor::CompositeFrame>,
+    audio_receiver: Receiver<crate::audio_mixer::MixedAudioChunk>,
+) -> Result<(), String> {
+    use crate::compositor::FrameFormat;
+    use std::ffi::CString;
+    use std::time::Duration;
+
+    // SAFETY: takes no arguments; false means the CPU isn't supported
+    if !unsafe { ffi::NDIlib_initialize() } {
+        return Err("The NDI runtime doesn't support this CPU".to_string());
+    }
+
+    let name = CString::new(config.source_name.trim()).map_err(|e| e.to_string())?;
+    let groups = config
+        .groups
+        .as_deref()
+        .map(CString::new)
+        .transpose()
+        .map_err(|e| e.to_string())?;
+    // Frames are sent as the recording produces them; the runtime mustn't
+    // hold them back to its own clock
+    let settings = ffi::SendCreate {
+        p_ndi_name: name.as_ptr(),
+        p_groups: groups.as_ref().map_or(std::ptr::null(), |groups| groups.as_ptr()),
+        clock_video: false,
+        clock_audio: false,
+    };
+    // SAFETY: the strings outlive the call, which copies them
+    let sender = unsafe { ffi::NDIlib_send_create(&settings) };
+    if sender.is_null() {
+        return Err("Failed to create the NDI sender".to_string());
+    }
+    println!(
+        "NDI source \"{}\": {}x{} @ {}fps",
+        config.source_name, format.width, format.height, format.frame_rate
+    );
+
+    let mut planar = Vec::new();
+    while *running.lock() {
+        crossbeam_channel::select! {
+            recv(video_receiver) -> frame => {
+                let Ok(frame) = frame else { break };
+                let (four_cc, stride) = match frame.format {
+                    FrameFormat::Bgra => (ffi::four_cc(b"BGRA"), frame.width * 4),
+                    FrameFormat::Rgba => (ffi::four_cc(b"RGBA"), frame.width * 4),
+                    // The UV plane follows the Y plane at the same stride
+                    FrameFormat::Nv12 => (ffi::four_cc(b"NV12"), frame.width),
+                };
+                let video = ffi::VideoFrameV2 {
+                    xres: frame.width as i32,
+                    yres: frame.height as i32,
+                    four_cc,
+                    frame_rate_n: format.frame_rate as i32,
+                    frame_rate_d: 1,
+                    picture_aspect_ratio: frame.width as f32 / frame.height.max(1) as f32,
+                    frame_format_type: ffi::FRAME_FORMAT_PROGRESSIVE,
+                    timecode: (frame.timestamp.as_nanos() / 100) as i64,
+                    p_data: frame.data.as_ptr(),
+                    line_stride_in_bytes: stride as i32,
+                    p_metadata: std::ptr::null(),
+                    timestamp: ffi::TIMESTAMP_UNDEFINED,
+                };
+                // SAFETY: the synchronous send is done with the data when it returns
+                unsafe { ffi::NDIlib_send_send_video_v2(sender, &video) };
+            }
+            recv(audio_receiver) -> chunk => {
+                let Ok(chunk) = chunk else { break };
+                let channels = chunk.channels.max(1) as usize;
+                let samples = chunk.samples.len() / channels;
+                planar.clear();
+                for channel in 0..channels {
+                    planar.extend(chunk.samples.iter().skip(channel).step_by(channels).take(samples));
+                }
+                let audio = ffi::AudioFrameV2 {
+                    sample_rate: chunk.sample_rate as i32,
+                    no_channels: channels as i32,
+                    no_samples: samples as i32,
+                    timecode: (chunk.timestamp.as_nanos() / 100) as i64,
+                    p_data: planar.as_ptr(),
+                    channel_stride_in_bytes: (samples * 4) as i32,
+                    p_metadata: std::ptr::null(),
+                    timestamp: ffi::TIMESTAMP_UNDEFINED,
+                };
+                // SAFETY: as for video
+                unsafe { ffi::NDIlib_send_send_audio_v2(sender, &audio) };
+            }
+            default(Duration::from_millis(100)) => {}
+        }
+    }
+
+    // SAFETY: created above and no longer used
+    unsafe { ffi::NDIlib_send_destroy(sender) };
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ndi_config_needs_a_source_name() {
+        assert!(NdiConfig::default().validate().is_ok());
+        let blank = NdiConfig {
+            source_name: "  ".to_string(),
+            ..NdiConfig::default()
+        };
+        assert!(blank.validate().is_err());
+        let nul = NdiConfig {
+            groups: Some("studio\0b".to_string()),
+            ..NdiConfig::default()
+        };
+        assert!(nul.validate().is_err());
+    }
+}
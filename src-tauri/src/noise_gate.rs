@@ -0,0 +1,337 @@
+//! Spectral Noise Gate
+//!
+//! Whispered ASMR sits only a little above the room: fan hum, traffic and
+//! preamp hiss fill every pause between phrases. A hard gate cuts that
+//! tone but also the breathy tails of the words, and its chattering is
+//! easy to hear. This gate instead turns down, gently, only the frequency
+//! bands that are below the threshold at the moment. Each band is expanded
+//! 2:1 below the threshold and by at most `reduction_db`, so room tone is
+//! lowered rather than muted. A whisper's consonants open their bands
+//! within milliseconds. A band closes again only over `release_ms`, so a
+//! fading tail stays in.
+//!
+//! The gate works on the microphone in the mixer, before monitoring,
+//! captions, meters and the mix. It analyses overlapping frames of
+//! `FRAME` samples, which delays the microphone by `HOP` samples. The mixer
+//! delays system audio to match and stamps the mix that much earlier, so
+//! sync with the video is unchanged.
+
+use serde::{Deserialize, Serialize};
+
+use crate::visualizer::fft;
+
+/// Analysis frame length in samples (21ms at 48kHz)
+const FRAME: usize = 1024;
+
+/// Samples between frames; also the delay the gate adds
+pub const HOP: usize = FRAME / 2;
+
+/// Delay the gate adds at `sample_rate`, in milliseconds
+pub fn delay_ms(sample_rate: u32) -> f32 {
+    HOP as f32 * 1000.0 / sample_rate.max(1) as f32
+}
+
+/// Expansion ratio below the threshold
+const RATIO: f32 = 2.0;
+
+/// How fast a band opens once it is above the threshold
+const ATTACK_MS: f32 = 5.0;
+
+/// Allowed thresholds (dBFS per band)
+pub const THRESHOLD_DB_RANGE: std::ops::RangeInclusive<f32> = -100.0..=-30.0;
+
+/// Allowed release times
+pub const RELEASE_MS_RANGE: std::ops::RangeInclusive<u32> = 50..=3000;
+
+/// Allowed maximum reductions
+pub const REDUCTION_DB_RANGE: std::ops::RangeInclusive<f32> = 1.0..=40.0;
+
+/// Microphone noise gate settings
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct NoiseGateConfig {
+    /// Gate the microphone while recording
+    #[serde(default)]
+    pub enabled: bool,
+    /// Bands quieter than this are turned down (dBFS, measured like a sine
+    /// in the band)
+    #[serde(default = "default_threshold_db")]
+    pub threshold_db: f32,
+    /// How long a band takes to close after falling below the threshold
+    #[serde(default = "default_release_ms")]
+    pub release_ms: u32,
+    /// Most a band is turned down by (dB)
+    #[serde(default = "default_reduction_db")]
+    pub reduction_db: f32,
+}
+
+fn default_threshold_db() -> f32 {
+    -70.0
+}
+
+fn default_release_ms() -> u32 {
+    400
+}
+
+fn default_reduction_db() -> f32 {
+    12.0
+}
+
+impl Default for NoiseGateConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold_db: default_threshold_db(),
+            release_ms: default_release_ms(),
+            reduction_db: default_reduction_db(),
+        }
+    }
+}
+
+impl NoiseGateConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !THRESHOLD_DB_RANGE.contains(&self.threshold_db) {
+            return Err(format!(
+                "Noise gate threshold must be {} to {} dBFS (got {})",
+                THRESHOLD_DB_RANGE.start(),
+                THRESHOLD_DB_RANGE.end(),
+                self.threshold_db
+            ));
+        }
+        if !RELEASE_MS_RANGE.contains(&self.release_ms) {
+            return Err(format!(
+                "Noise gate release must be {}-{} ms (got {})",
+                RELEASE_MS_RANGE.start(),
+                RELEASE_MS_RANGE.end(),
+                self.release_ms
+            ));
+        }
+        if !REDUCTION_DB_RANGE.contains(&self.reduction_db) {
+            return Err(format!(
+                "Noise gate reduction must be {}-{} dB (got {})",
+                REDUCTION_DB_RANGE.start(),
+                REDUCTION_DB_RANGE.end(),
+                self.reduction_db
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The gate for one interleaved stream
+///
+/// Output lags input by exactly `HOP` frames; the first `HOP` frames out
+/// are silence.
+pub struct SpectralGate {
+    channels: usize,
+    threshold_db: f32,
+    reduction_db: f32,
+    /// Per-frame smoothing of a band's gain while opening and closing
+    attack: f32,
+    release: f32,
+    /// Square-root Hann, applied before analysis and after resynthesis so
+    /// overlapping frames add back up to the input
+    window: Vec<f32>,
+    /// Scales a bin's magnitude to the amplitude of a sine in it
+    magnitude_scale: f32,
+    lanes: Vec<GateLane>,
+    input_frames: u64,
+    output_frames: u64,
+}
+
+/// One channel's frames and band gains
+struct GateLane {
+    /// Samples not yet analysed, starting `HOP` before the next output
+    input: Vec<f32>,
+    /// Overlap-added output of the frames so far
+    overlap: Vec<f32>,
+    /// Smoothed gain of each bin up to half the frame
+    gains: Vec<f32>,
+}
+
+impl SpectralGate {
+    pub fn new(config: &NoiseGateConfig, sample_rate: u32, channels: u16) -> Self {
+        let window: Vec<f32> = (0..FRAME)
+            .map(|i| (0.5 - 0.5 * (std::f32::consts::TAU * i as f32 / FRAME as f32).cos()).sqrt())
+            .collect();
+        let window_sum: f32 = window.iter().sum();
+        let frame_secs = HOP as f32 / sample_rate.max(1) as f32;
+        let smoothing = |ms: f32| (-frame_secs / (ms / 1000.0)).exp();
+
+        Self {
+            channels: channels.max(1) as usize,
+            threshold_db: config.threshold_db,
+            reduction_db: config.reduction_db,
+            attack: smoothing(ATTACK_MS),
+            release: smoothing(config.release_ms as f32),
+            window,
+            magnitude_scale: 2.0 / window_sum,
+            lanes: (0..channels.max(1))
+                .map(|_| GateLane {
+                    input: vec![0.0; HOP],
+                    overlap: vec![0.0; FRAME],
+                    gains: vec![1.0; FRAME / 2 + 1],
+                })
+                .collect(),
+            input_frames: 0,
+            output_frames: 0,
+        }
+    }
+
+    /// Gate interleaved samples, returning what is ready of the output
+    pub fn process(&mut self, samples: &[f32]) -> Vec<f32> {
+        for frame in samples.chunks_exact(self.channels) {
+            for (lane, &sample) in self.lanes.iter_mut().zip(frame) {
+                lane.input.push(sample);
+            }
+        }
+        self.input_frames += (samples.len() / self.channels) as u64;
+        self.run_frames()
+    }
+
+    /// The rest of the output, so it covers all of the input; the gate is
+    /// done after this
+    pub fn flush(&mut self) -> Vec<f32> {
+        let owed = (self.input_frames + HOP as u64).saturating_sub(self.output_frames) as usize;
+        let mut out = Vec::with_capacity(owed * self.channels);
+        while out.len() < owed * self.channels {
+            for lane in &mut self.lanes {
+                lane.input.resize(lane.input.len() + HOP, 0.0);
+            }
+            out.extend(self.run_frames());
+        }
+        out.truncate(owed * self.channels);
+        self.output_frames = self.input_frames + HOP as u64;
+        out
+    }
+
+    /// Analyse every complete frame, interleaving `HOP` output frames for each
+    fn run_frames(&mut self) -> Vec<f32> {
+        let ready = self.lanes[0].input.len().saturating_sub(HOP) / HOP;
+        let mut out = vec![0.0; ready * HOP * self.channels];
+        for (channel, lane) in self.lanes.iter_mut().enumerate() {
+            for block in 0..ready {
+                let start = block * HOP;
+                let frame = lane.input[start..start + FRAME].to_vec();
+                let gated = lane.gate_frame(
+                    &frame,
+                    &self.window,
+                    self.magnitude_scale,
+                    (self.threshold_db, self.reduction_db),
+                    (self.attack, self.release),
+                );
+                for (i, sample) in gated.into_iter().enumerate() {
+                    out[(start + i) * self.channels + channel] = sample;
+                }
+            }
+            lane.input.drain(..ready * HOP);
+        }
+        self.output_frames += (ready * HOP) as u64;
+        out
+    }
+}
+
+impl GateLane {
+    /// Gate one frame, add it to the overlap and return the finished `HOP`
+    /// samples at its start
+    fn gate_frame(
+        &mut self,
+        frame: &[f32],
+        window: &[f32],
+        magnitude_scale: f32,
+        (threshold_db, reduction_db): (f32, f32),
+        (attack, release): (f32, f32),
+    ) -> Vec<f32> {
+        let mut bins: Vec<(f32, f32)> = frame.iter().zip(window).map(|(s, w)| (s * w, 0.0)).collect();
+        fft(&mut bins);
+
+        for (k, gain) in self.gains.iter_mut().enumerate() {
+            let (re, im) = bins[k];
+            let level_db = 20.0 * (re.hypot(im) * magnitude_scale).max(1e-10).log10();
+            let below = threshold_db - level_db;
+            let target_db = if below > 0.0 { -(below * (RATIO - 1.0)).min(reduction_db) } else { 0.0 };
+            let target = 10f32.powf(target_db / 20.0);
+            let smoothing = if target > *gain { attack } else { release };
+            *gain = target + (*gain - target) * smoothing;
+        }
+
+        // Neighbouring bins are averaged, so single bins don't flicker open
+        // and closed ("musical noise")
+        let last = self.gains.len() - 1;
+        for k in 0..=last {
+            let gain = (self.gains[k.saturating_sub(1)] + self.gains[k] + self.gains[(k + 1).min(last)]) / 3.0;
+            bins[k].0 *= gain;
+            bins[k].1 *= gain;
+            if k > 0 && k < last {
+                bins[FRAME - k].0 *= gain;
+                bins[FRAME - k].1 *= gain;
+            }
+        }
+
+        // Inverse transform by conjugating around the forward one
+        for bin in &mut bins {
+            bin.1 = -bin.1;
+        }
+        fft(&mut bins);
+        for (i, (re, _)) in bins.into_iter().enumerate() {
+            self.overlap[i] += re / FRAME as f32 * window[i];
+        }
+
+        let done: Vec<f32> = self.overlap.drain(..HOP).collect();
+        self.overlap.resize(FRAME, 0.0);
+        done
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(amplitude: f32, frames: usize) -> Vec<f32> {
+        (0..frames)
+            .map(|i| (std::f32::consts::TAU * 1000.0 * i as f32 / 48000.0).sin() * amplitude)
+            .collect()
+    }
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples.iter().fold(0.0, |peak, s| peak.max(s.abs()))
+    }
+
+    #[test]
+    fn test_gate_lowers_room_tone_and_keeps_speech_and_tails() {
+        let config = NoiseGateConfig {
+            enabled: true,
+            ..NoiseGateConfig::default()
+        };
+        let mut gate = SpectralGate::new(&config, 48000, 1);
+
+        // A whisper-level tone passes unchanged, delayed by HOP
+        let speech = sine(0.05, 48000);
+        let mut out = gate.process(&speech[..10000]);
+        out.extend(gate.process(&speech[10000..]));
+        assert_eq!(out.len(), 48000 / HOP * HOP);
+        assert!(peak(&out[..HOP]) < 1e-3);
+        for i in 20000..20100 {
+            assert!((out[i + HOP] - speech[i]).abs() < 1e-3);
+        }
+
+        // Room tone 20dB under the threshold loses 12dB once its band has
+        // closed, and only gradually after the speech stops
+        let room = sine(0.00003, 144000);
+        out.extend(gate.process(&room));
+        let after_speech = 48000 + HOP;
+        let tail = peak(&out[after_speech + 2400..after_speech + 4800]) / 0.00003;
+        let settled = peak(&out[after_speech + 120000..]) / 0.00003;
+        assert!(tail > 0.7, "the tail is kept (gain {})", tail);
+        assert!((settled - 0.25).abs() < 0.03, "room tone reduced by 12dB (gain {})", settled);
+
+        // Flushing passes on the rest of the input
+        out.extend(gate.flush());
+        assert_eq!(out.len(), 192000 + HOP);
+
+        assert!(config.validate().is_ok());
+        assert!(NoiseGateConfig { threshold_db: -10.0, ..config }.validate().is_err());
+        assert!(NoiseGateConfig { release_ms: 10, ..config }.validate().is_err());
+        assert!(NoiseGateConfig { reduction_db: 60.0, ..config }.validate().is_err());
+    }
+}
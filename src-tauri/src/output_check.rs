@@ -0,0 +1,259 @@
+//! Output Verification
+//!
+//! Once the trailer is written, the recording is opened again and its tail
+//! read back: the container's duration, its streams and where each stream's
+//! last packet ends. Those are held against what the encoder wrote, so a
+//! file cut short by a failing disk or a muxer problem, or one whose audio
+//! and video drifted apart, is reported as soon as recording stops rather
+//! than when someone plays it back.
+//!
+//! That check is reported when recording stops. Silence trimming, mastering
+//! and normalization rewrite the file after that, so once they have run the
+//! final file is checked again and reported with `recording-finished`.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tauri::{AppHandle, Emitter};
+
+/// Event emitted once a recording's finishing passes are done
+pub const FINISHED_EVENT: &str = "recording-finished";
+
+/// How far a stream may end before what was written, or apart from the
+/// other stream, before it counts as a problem (covers encoder delay and
+/// audio priming)
+#[cfg_attr(not(any(feature = "ffmpeg", test)), allow(dead_code))]
+const END_TOLERANCE_MS: u64 = 1000;
+
+/// How much of the end of the file is read back for the last packets
+#[cfg(feature = "ffmpeg")]
+const TAIL_US: i64 = 10_000_000;
+
+/// What the encoder wrote to the file, in milliseconds from its start
+#[cfg_attr(not(any(feature = "ffmpeg", test)), allow(dead_code))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WrittenOutput {
+    pub video_ms: u64,
+    /// None if no audio reached the encoder
+    pub audio_ms: Option<u64>,
+}
+
+impl WrittenOutput {
+    /// What is left once only `kept` of the file is kept
+    #[cfg_attr(not(any(feature = "ffmpeg", test)), allow(dead_code))]
+    pub fn within(&self, kept: std::ops::Range<u64>) -> Self {
+        let cut = |ms: u64| ms.min(kept.end).saturating_sub(kept.start);
+        Self {
+            video_ms: cut(self.video_ms),
+            audio_ms: self.audio_ms.map(cut),
+        }
+    }
+}
+
+/// What was read back from the file
+#[cfg_attr(not(any(feature = "ffmpeg", test)), allow(dead_code))]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProbedOutput {
+    /// Duration the container reports
+    pub duration_ms: Option<u64>,
+    pub streams: u32,
+    /// End of the last video packet (None without a video stream or packets)
+    pub video_end_ms: Option<u64>,
+    /// End of the last audio packet (None without an audio stream or packets)
+    pub audio_end_ms: Option<u64>,
+}
+
+/// Result of reading a finished recording back
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OutputVerification {
+    /// The file opened and matched what was written
+    pub verified: bool,
+    /// Duration the file reports
+    pub duration_ms: Option<u64>,
+    /// What is wrong with the file, if anything
+    pub issues: Vec<String>,
+}
+
+/// Payload of `recording-finished`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FinishedRecording {
+    pub output_path: PathBuf,
+    /// The file as the finishing passes left it
+    #[serde(flatten)]
+    pub verification: OutputVerification,
+}
+
+/// Receives the final check of a recording
+pub type FinishedSink = Box<dyn FnOnce(FinishedRecording) + Send>;
+
+/// Sink emitting the event to every window
+pub fn app_sink(app: AppHandle) -> FinishedSink {
+    Box::new(move |payload| {
+        if let Err(e) = app.emit(FINISHED_EVENT, payload) {
+            eprintln!("Failed to emit {}: {}", FINISHED_EVENT, e);
+        }
+    })
+}
+
+/// Compare a probed file with what was written to it
+#[cfg_attr(not(any(feature = "ffmpeg", test)), allow(dead_code))]
+pub fn check(probed: &ProbedOutput, written: &WrittenOutput) -> OutputVerification {
+    let secs = |ms: u64| ms as f64 / 1000.0;
+    let mut issues = Vec::new();
+
+    if probed.duration_ms.is_none_or(|ms| ms == 0) {
+        issues.push("The file reports no duration".to_string());
+    }
+    match probed.video_end_ms {
+        None => issues.push("No video found in the file".to_string()),
+        Some(end) if end + END_TOLERANCE_MS < written.video_ms => issues.push(format!(
+            "Video is truncated: it ends at {:.1}s, {:.1}s were recorded",
+            secs(end),
+            secs(written.video_ms)
+        )),
+        Some(_) => {}
+    }
+    if let Some(written_audio) = written.audio_ms {
+        match probed.audio_end_ms {
+            None => issues.push("No audio found in the file".to_string()),
+            Some(end) if end + END_TOLERANCE_MS < written_audio => issues.push(format!(
+                "Audio is truncated: it ends at {:.1}s, {:.1}s were recorded",
+                secs(end),
+                secs(written_audio)
+            )),
+            Some(_) => {}
+        }
+    }
+    if let (Some(video), Some(audio)) = (probed.video_end_ms, probed.audio_end_ms) {
+        if written.audio_ms.is_some() && video.abs_diff(audio) > END_TOLERANCE_MS {
+            issues.push(format!(
+                "Audio and video are out of sync: audio ends at {:.1}s, video at {:.1}s",
+                secs(audio),
+                secs(video)
+            ));
+        }
+    }
+
+    OutputVerification {
+        verified: issues.is_empty(),
+        duration_ms: probed.duration_ms,
+        issues,
+    }
+}
+
+/// Read the end of a finished recording back and check it against what was
+/// written
+#[cfg(feature = "ffmpeg")]
+pub fn verify(path: &std::path::Path, written: &WrittenOutput) -> OutputVerification {
+    match probe(path) {
+        Ok(probed) => check(&probed, written),
+        Err(e) => OutputVerification {
+            verified: false,
+            duration_ms: None,
+            issues: vec![e],
+        },
+    }
+}
+
+#[cfg(feature = "ffmpeg")]
+fn probe(path: &std::path::Path) -> Result<ProbedOutput, String> {
+    use ffmpeg_next::media::Type;
+    use ffmpeg_next::Rescale;
+
+    let mut input = crate::media_file::open_input(path)?;
+    let duration_us = input.duration();
+    let video = input.streams().best(Type::Video).map(|s| s.index());
+    let audio = input.streams().best(Type::Audio).map(|s| s.index());
+    let mut probed = ProbedOutput {
+        duration_ms: (duration_us > 0).then(|| duration_us as u64 / 1000),
+        streams: input.streams().count() as u32,
+        ..ProbedOutput::default()
+    };
+
+    // Only the tail is needed; if seeking fails the whole file is read
+    if duration_us > TAIL_US {
+        let target = duration_us - TAIL_US;
+        let _ = input.seek(target, ..target);
+    }
+    for (stream, packet) in input.packets() {
+        let Some(pts) = packet.pts() else {
+            continue;
+        };
+        let end = (pts + packet.duration())
+            .rescale(stream.time_base(), ffmpeg_next::Rational(1, 1000))
+            .max(0) as u64;
+        let slot = match Some(stream.index()) {
+            index if index == video => &mut probed.video_end_ms,
+            index if index == audio => &mut probed.audio_end_ms,
+            _ => continue,
+        };
+        *slot = Some(slot.map_or(end, |last| last.max(end)));
+    }
+    Ok(probed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_flags_truncated_and_desynced_files() {
+        let written = WrittenOutput {
+            video_ms: 60_000,
+            audio_ms: Some(60_000),
+        };
+        let good = ProbedOutput {
+            duration_ms: Some(60_020),
+            streams: 2,
+            video_end_ms: Some(60_000),
+            audio_end_ms: Some(60_020),
+        };
+        let verification = check(&good, &written);
+        assert!(verification.verified, "{:?}", verification.issues);
+        assert_eq!(verification.duration_ms, Some(60_020));
+
+        let truncated = ProbedOutput {
+            video_end_ms: Some(41_000),
+            audio_end_ms: Some(41_000),
+            ..good.clone()
+        };
+        let verification = check(&truncated, &written);
+        assert!(!verification.verified);
+        assert_eq!(verification.issues.len(), 2);
+
+        let desynced = ProbedOutput {
+            audio_end_ms: Some(62_500),
+            ..good.clone()
+        };
+        assert!(check(&desynced, &written).issues[0].contains("out of sync"));
+
+        // Without captured audio the audio track isn't held to anything
+        let silent = ProbedOutput {
+            audio_end_ms: None,
+            ..good
+        };
+        let video_only = WrittenOutput {
+            audio_ms: None,
+            ..written
+        };
+        assert!(check(&silent, &video_only).verified);
+        assert!(!check(&ProbedOutput::default(), &video_only).verified);
+    }
+
+    #[test]
+    fn test_trimmed_output_is_held_to_what_was_kept() {
+        let written = WrittenOutput {
+            video_ms: 60_000,
+            audio_ms: Some(59_500),
+        };
+        assert_eq!(
+            written.within(2_000..55_000),
+            WrittenOutput {
+                video_ms: 53_000,
+                audio_ms: Some(53_000),
+            }
+        );
+        assert_eq!(written.within(2_000..60_000).audio_ms, Some(57_500));
+    }
+}
@@ -0,0 +1,270 @@
+//! Output Path Allow-List
+//!
+//! Paths from the frontend are canonicalized and must lie inside an allowed
+//! directory: the user's videos directory (plus test-results in dev builds)
+//! or a directory the user chose, persisted in the settings file. Resolving
+//! `..` and symlinks first means neither can lead out of an allowed directory.
+//! The filesystem root, the home directory (or anything containing it) and
+//! system directories can't be allowed, so the frontend can't open up the
+//! whole disk.
+//!
+//! Before capture starts, the output directory is also checked to exist,
+//! accept new files and have room, so a bad location fails the start with a
+//! clear error instead of failing inside the encoder mid-recording.
+
+use std::path::{Path, PathBuf};
+use tauri::command;
+
+use crate::recording::RecordingError;
+use crate::settings::AppSettings;
+
+/// Free space a recording needs on its volume to start
+pub const MIN_FREE_SPACE: u64 = 500 * 1024 * 1024;
+
+/// Directories recordings may always be written to
+fn default_output_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = dirs::video_dir().into_iter().collect();
+    #[cfg(debug_assertions)]
+    dirs.push(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../test-results"));
+    dirs
+}
+
+/// The default directories and the ones from settings
+pub fn allowed_output_dirs() -> Vec<PathBuf> {
+    let mut dirs = default_output_dirs();
+    dirs.extend(AppSettings::load().allowed_output_dirs);
+    dirs
+}
+
+/// System directories recordings must never be allowed into
+#[cfg(target_os = "windows")]
+fn system_dirs() -> Vec<PathBuf> {
+    ["SystemRoot", "ProgramFiles", "ProgramFiles(x86)", "ProgramData"]
+        .iter()
+        .filter_map(|var| std::env::var_os(var))
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// System directories recordings must never be allowed into (the macOS ones
+/// simply don't exist elsewhere)
+#[cfg(not(target_os = "windows"))]
+fn system_dirs() -> Vec<PathBuf> {
+    [
+        "/bin", "/boot", "/dev", "/etc", "/lib", "/lib64", "/opt", "/proc", "/root", "/run",
+        "/sbin", "/sys", "/usr", "/var", "/System", "/Library", "/Applications", "/private/etc",
+        "/private/var",
+    ]
+    .iter()
+    .map(PathBuf::from)
+    .collect()
+}
+
+/// Whether a canonical directory is too broad or too sensitive to allow:
+/// the root, anything containing the home directory, or a system directory
+fn protected_dir(dir: &Path, home: Option<&Path>, system: &[PathBuf]) -> bool {
+    dir.parent().is_none()
+        || home.is_some_and(|home| home.starts_with(dir))
+        || system
+            .iter()
+            .filter_map(|system| system.canonicalize().ok())
+            .any(|system| dir.starts_with(system))
+}
+
+/// Canonicalize an existing directory and check it is inside `allowed`
+pub fn check_output_dir(dir: &Path, allowed: &[PathBuf]) -> Result<PathBuf, RecordingError> {
+    let canonical = dir.canonicalize()?;
+    let inside = allowed
+        .iter()
+        .filter_map(|allowed| allowed.canonicalize().ok())
+        .any(|allowed| canonical.starts_with(allowed));
+    if inside {
+        Ok(canonical)
+    } else {
+        Err(RecordingError::PathNotAllowed(dir.to_path_buf()))
+    }
+}
+
+/// Canonicalize a file path whose directory exists and check it is inside
+/// `allowed`
+///
+/// The file itself may not exist yet; if it does, it must not be a symlink.
+pub fn check_output_path(path: &Path, allowed: &[PathBuf]) -> Result<PathBuf, RecordingError> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| RecordingError::PathNotAllowed(path.to_path_buf()))?;
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    let canonical = check_output_dir(parent, allowed)?.join(file_name);
+    if canonical.symlink_metadata().is_ok_and(|m| m.file_type().is_symlink()) {
+        return Err(RecordingError::PathNotAllowed(path.to_path_buf()));
+    }
+    Ok(canonical)
+}
+
+/// Check that a recording can be written to `path` before capture starts:
+/// its directory exists, new files can be created in it and its volume has
+/// at least `MIN_FREE_SPACE` free
+pub fn preflight_output(path: &Path) -> Result<(), RecordingError> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    if !dir.is_dir() {
+        return Err(RecordingError::OutputUnavailable(format!(
+            "Output directory {} does not exist",
+            dir.display()
+        )));
+    }
+
+    // Permissions alone don't tell (ACLs, read-only mounts): create a file
+    let probe = dir.join(format!(".asmr_write_test_{}", std::process::id()));
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)
+        .map_err(|e| RecordingError::OutputUnavailable(format!(
+            "Output directory {} is not writable: {}",
+            dir.display(),
+            e
+        )))?;
+    let _ = std::fs::remove_file(&probe);
+
+    check_free_space(dir, fs2::available_space(dir).ok())
+}
+
+fn check_free_space(dir: &Path, available: Option<u64>) -> Result<(), RecordingError> {
+    match available {
+        Some(bytes) if bytes < MIN_FREE_SPACE => Err(RecordingError::OutputUnavailable(format!(
+            "Only {} MB free on the volume of {} ({} MB needed to start recording)",
+            bytes / (1024 * 1024),
+            dir.display(),
+            MIN_FREE_SPACE / (1024 * 1024)
+        ))),
+        // Unknown free space is no reason to refuse
+        _ => Ok(()),
+    }
+}
+
+/// Tauri command: Directories recordings may be written to
+#[command]
+pub fn get_allowed_output_dirs() -> Vec<PathBuf> {
+    allowed_output_dirs()
+}
+
+/// Tauri command: Allow recordings in a directory the user chose
+///
+/// The filesystem root, the home directory itself and system directories are
+/// refused. Returns the updated list.
+#[command]
+pub fn add_allowed_output_dir(dir: PathBuf) -> Result<Vec<PathBuf>, RecordingError> {
+    if !dir.is_dir() {
+        return Err(RecordingError::InvalidConfig(format!("{} is not a directory", dir.display())));
+    }
+    let dir = dir
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve {}: {}", dir.display(), e))?;
+    let home = dirs::home_dir().and_then(|home| home.canonicalize().ok());
+    if protected_dir(&dir, home.as_deref(), &system_dirs()) {
+        return Err(RecordingError::PathNotAllowed(dir));
+    }
+
+    AppSettings::update(|settings| {
+        if !settings.allowed_output_dirs.contains(&dir) {
+            settings.allowed_output_dirs.push(dir);
+        }
+    })?;
+    Ok(allowed_output_dirs())
+}
+
+/// Tauri command: Stop allowing a directory added with `add_allowed_output_dir`
+///
+/// The default directories can't be removed. Returns the updated list.
+#[command]
+pub fn remove_allowed_output_dir(dir: PathBuf) -> Result<Vec<PathBuf>, RecordingError> {
+    let canonical = dir.canonicalize().ok();
+    AppSettings::update(|settings| {
+        settings
+            .allowed_output_dirs
+            .retain(|allowed| *allowed != dir && Some(allowed) != canonical.as_ref());
+    })?;
+    Ok(allowed_output_dirs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_paths_stay_inside_allowed_dirs() {
+        let root = std::env::temp_dir().join(format!("asmr_paths_{}", std::process::id()));
+        let allowed_dir = root.join("allowed");
+        let other_dir = root.join("other");
+        std::fs::create_dir_all(&allowed_dir).unwrap();
+        std::fs::create_dir_all(&other_dir).unwrap();
+        let allowed = vec![allowed_dir.clone()];
+
+        let checked = check_output_path(&allowed_dir.join("take.mp4"), &allowed).unwrap();
+        assert_eq!(checked, allowed_dir.canonicalize().unwrap().join("take.mp4"));
+
+        for path in [
+            other_dir.join("take.mp4"),
+            allowed_dir.join("../other/take.mp4"),
+            allowed_dir.join(".."),
+        ] {
+            assert!(
+                matches!(check_output_path(&path, &allowed), Err(RecordingError::PathNotAllowed(_))),
+                "{}",
+                path.display()
+            );
+        }
+        assert!(check_output_path(&allowed_dir.join("missing/take.mp4"), &allowed).is_err());
+        assert!(check_output_dir(&allowed_dir, &allowed).is_ok());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_broad_and_system_dirs_cannot_be_allowed() {
+        let root = std::env::temp_dir().join(format!("asmr_protected_{}", std::process::id()));
+        let home = root.join("home");
+        let system = root.join("system");
+        std::fs::create_dir_all(home.join("Videos")).unwrap();
+        std::fs::create_dir_all(system.join("config")).unwrap();
+        let home = home.canonicalize().unwrap();
+        let systems = vec![system.clone()];
+
+        assert!(protected_dir(Path::new("/"), Some(&home), &systems));
+        assert!(protected_dir(&home, Some(&home), &systems));
+        assert!(protected_dir(home.parent().unwrap(), Some(&home), &systems));
+        assert!(protected_dir(&system.canonicalize().unwrap().join("config"), Some(&home), &systems));
+        assert!(!protected_dir(&home.join("Videos"), Some(&home), &systems));
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_preflight_rejects_missing_dir_and_full_volume() {
+        let root = std::env::temp_dir().join(format!("asmr_preflight_{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+
+        assert!(matches!(
+            preflight_output(&root.join("missing/take.mp4")),
+            Err(RecordingError::OutputUnavailable(_))
+        ));
+        assert!(matches!(
+            check_free_space(&root, Some(MIN_FREE_SPACE - 1)),
+            Err(RecordingError::OutputUnavailable(_))
+        ));
+        assert!(check_free_space(&root, Some(MIN_FREE_SPACE)).is_ok());
+        assert!(check_free_space(&root, None).is_ok());
+        // The write probe leaves nothing behind
+        let _ = preflight_output(&root.join("take.mp4"));
+        assert_eq!(std::fs::read_dir(&root).unwrap().count(), 0);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
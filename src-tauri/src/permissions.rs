@@ -0,0 +1,367 @@
+//! Camera and Microphone Permissions
+//!
+//! Lets the UI find out whether the OS will hand the app the camera and
+//! microphone before a recording fails on them. On macOS the answer comes
+//! from AVFoundation (TCC), which can also show the system prompt once. On
+//! Windows it comes from the privacy consent store that the Settings app
+//! writes; Windows has no prompt for desktop apps, so a request opens the
+//! privacy page instead. Other platforms have no per-app permission and
+//! always report access as granted.
+
+use serde::{Deserialize, Serialize};
+use tauri::command;
+
+use crate::recording::RecordingError;
+
+/// Whether the app may use a capture device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PermissionStatus {
+    /// Access is allowed
+    Granted,
+    /// The user turned access off; only they can turn it back on in the
+    /// system settings
+    Denied,
+    /// Access is blocked by policy (parental controls, MDM, or the
+    /// device-wide switch on Windows) and the user may not be able to change it
+    Restricted,
+    /// The user hasn't been asked yet; requesting shows the system prompt
+    NotDetermined,
+}
+
+/// Kind of capture device a permission covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MediaKind {
+    Camera,
+    Microphone,
+}
+
+/// Tauri command: Whether the app may use the camera
+#[command]
+pub fn check_camera_permission() -> Result<PermissionStatus, RecordingError> {
+    platform::status(MediaKind::Camera).map_err(RecordingError::Failed)
+}
+
+/// Tauri command: Whether the app may use the microphone
+#[command]
+pub fn check_microphone_permission() -> Result<PermissionStatus, RecordingError> {
+    platform::status(MediaKind::Microphone).map_err(RecordingError::Failed)
+}
+
+/// Tauri command: Ask for camera access
+///
+/// Shows the system prompt if the user hasn't been asked yet and returns
+/// their answer; when access is off, opens the privacy settings page and
+/// returns the current status.
+#[command]
+pub async fn request_camera_permission() -> Result<PermissionStatus, RecordingError> {
+    request(MediaKind::Camera).await
+}
+
+/// Tauri command: Ask for microphone access (see `request_camera_permission`)
+#[command]
+pub async fn request_microphone_permission() -> Result<PermissionStatus, RecordingError> {
+    request(MediaKind::Microphone).await
+}
+
+async fn request(kind: MediaKind) -> Result<PermissionStatus, RecordingError> {
+    let status = tauri::async_runtime::spawn_blocking(move || platform::request(kind))
+        .await
+        .map_err(|e| format!("Permission request failed: {}", e))?
+        .map_err(RecordingError::Failed)?;
+    if matches!(status, PermissionStatus::Denied | PermissionStatus::Restricted) {
+        if let Some(url) = platform::settings_url(kind) {
+            tauri_plugin_opener::open_url(url, None::<&str>)
+                .map_err(|e| RecordingError::Failed(format!("Failed to open privacy settings: {}", e)))?;
+        }
+    }
+    Ok(status)
+}
+
+/// Map `AVAuthorizationStatus`
+#[cfg(any(target_os = "macos", test))]
+fn av_authorization_status(value: isize) -> PermissionStatus {
+    match value {
+        0 => PermissionStatus::NotDetermined,
+        1 => PermissionStatus::Restricted,
+        2 => PermissionStatus::Denied,
+        _ => PermissionStatus::Granted,
+    }
+}
+
+/// Combine the consent store's switches: the device-wide one (set by an
+/// administrator), the one for all apps and the one for desktop apps
+///
+/// A missing value means the switch was never touched, which leaves access on.
+#[cfg(any(target_os = "windows", test))]
+fn consent_status(
+    device_wide: Option<&str>,
+    all_apps: Option<&str>,
+    desktop_apps: Option<&str>,
+) -> PermissionStatus {
+    let denied = |value: Option<&str>| value.is_some_and(|v| v.eq_ignore_ascii_case("deny"));
+    if denied(device_wide) {
+        PermissionStatus::Restricted
+    } else if denied(all_apps) || denied(desktop_apps) {
+        PermissionStatus::Denied
+    } else {
+        PermissionStatus::Granted
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::ffi::{c_char, c_void, CStr};
+    use std::time::{Duration, Instant};
+
+    use super::{av_authorization_status, MediaKind, PermissionStatus};
+
+    /// How long to wait for the user to answer the system prompt
+    const PROMPT_TIMEOUT: Duration = Duration::from_secs(120);
+
+    type Id = *mut c_void;
+    type Sel = *mut c_void;
+
+    /// `BLOCK_IS_GLOBAL`: the block captures nothing and is never copied
+    const BLOCK_IS_GLOBAL: i32 = 1 << 28;
+
+    #[repr(C)]
+    struct BlockDescriptor {
+        reserved: usize,
+        size: usize,
+    }
+
+    /// Layout of an Objective-C block taking a `BOOL`
+    #[repr(C)]
+    struct Block {
+        isa: *const c_void,
+        flags: i32,
+        reserved: i32,
+        invoke: unsafe extern "C" fn(*mut Block, u8),
+        descriptor: *const BlockDescriptor,
+    }
+
+    static DESCRIPTOR: BlockDescriptor = BlockDescriptor {
+        reserved: 0,
+        size: std::mem::size_of::<Block>(),
+    };
+
+    #[link(name = "objc")]
+    extern "C" {
+        fn objc_getClass(name: *const c_char) -> Id;
+        fn sel_registerName(name: *const c_char) -> Sel;
+        fn objc_msgSend();
+    }
+
+    #[link(name = "System")]
+    extern "C" {
+        static _NSConcreteGlobalBlock: *const c_void;
+    }
+
+    #[link(name = "AVFoundation", kind = "framework")]
+    extern "C" {
+        static AVMediaTypeVideo: Id;
+        static AVMediaTypeAudio: Id;
+    }
+
+    /// The answer is read back with `authorizationStatusForMediaType:`
+    unsafe extern "C" fn ignore_answer(_block: *mut Block, _granted: u8) {}
+
+    fn media_type(kind: MediaKind) -> Id {
+        // SAFETY: framework constants, initialized when AVFoundation loads
+        unsafe {
+            match kind {
+                MediaKind::Camera => AVMediaTypeVideo,
+                MediaKind::Microphone => AVMediaTypeAudio,
+            }
+        }
+    }
+
+    fn capture_device_class() -> Result<Id, String> {
+        // SAFETY: NUL-terminated name; returns nil if the class is unknown
+        let class = unsafe { objc_getClass(c"AVCaptureDevice".as_ptr()) };
+        if class.is_null() {
+            return Err("AVFoundation is not available".to_string());
+        }
+        Ok(class)
+    }
+
+    fn selector(name: &CStr) -> Sel {
+        // SAFETY: NUL-terminated name
+        unsafe { sel_registerName(name.as_ptr()) }
+    }
+
+    pub fn status(kind: MediaKind) -> Result<PermissionStatus, String> {
+        let class = capture_device_class()?;
+        let sel = selector(c"authorizationStatusForMediaType:");
+        // SAFETY: `+[AVCaptureDevice authorizationStatusForMediaType:]`
+        // takes an AVMediaType and returns an NSInteger
+        let value = unsafe {
+            let send: unsafe extern "C" fn(Id, Sel, Id) -> isize =
+                std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+            send(class, sel, media_type(kind))
+        };
+        Ok(av_authorization_status(value))
+    }
+
+    pub fn request(kind: MediaKind) -> Result<PermissionStatus, String> {
+        let current = status(kind)?;
+        if current != PermissionStatus::NotDetermined {
+            return Ok(current);
+        }
+
+        let class = capture_device_class()?;
+        let sel = selector(c"requestAccessForMediaType:completionHandler:");
+        // Global blocks are used in place, so it has to outlive the prompt
+        let handler = Box::leak(Box::new(Block {
+            isa: std::ptr::addr_of!(_NSConcreteGlobalBlock).cast(),
+            flags: BLOCK_IS_GLOBAL,
+            reserved: 0,
+            invoke: ignore_answer,
+            descriptor: &DESCRIPTOR,
+        }));
+        // SAFETY: `+[AVCaptureDevice requestAccessForMediaType:completionHandler:]`
+        // takes an AVMediaType and a block, and returns immediately
+        unsafe {
+            let send: unsafe extern "C" fn(Id, Sel, Id, *mut Block) =
+                std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+            send(class, sel, media_type(kind), handler);
+        }
+
+        let started = Instant::now();
+        loop {
+            let current = status(kind)?;
+            if current != PermissionStatus::NotDetermined || started.elapsed() >= PROMPT_TIMEOUT {
+                return Ok(current);
+            }
+            std::thread::sleep(Duration::from_millis(200));
+        }
+    }
+
+    pub fn settings_url(kind: MediaKind) -> Option<&'static str> {
+        Some(match kind {
+            MediaKind::Camera => "x-apple.systempreferences:com.apple.preference.security?Privacy_Camera",
+            MediaKind::Microphone => "x-apple.systempreferences:com.apple.preference.security?Privacy_Microphone",
+        })
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use std::ffi::c_void;
+
+    use super::{consent_status, MediaKind, PermissionStatus};
+
+    const HKEY_CURRENT_USER: isize = 0x8000_0001u32 as i32 as isize;
+    const HKEY_LOCAL_MACHINE: isize = 0x8000_0002u32 as i32 as isize;
+    const RRF_RT_REG_SZ: u32 = 0x0000_0002;
+    const ERROR_SUCCESS: i32 = 0;
+
+    #[link(name = "advapi32")]
+    extern "system" {
+        fn RegGetValueW(
+            key: isize,
+            sub_key: *const u16,
+            value: *const u16,
+            flags: u32,
+            value_type: *mut u32,
+            data: *mut c_void,
+            data_len: *mut u32,
+        ) -> i32;
+    }
+
+    fn wide(s: &str) -> Vec<u16> {
+        s.encode_utf16().chain(std::iter::once(0)).collect()
+    }
+
+    /// Read the `Value` string of a consent store key
+    fn consent_value(root: isize, sub_key: &str) -> Option<String> {
+        let sub_key = wide(sub_key);
+        let value = wide("Value");
+        let mut data = [0u16; 32];
+        let mut len = std::mem::size_of_val(&data) as u32;
+        // SAFETY: NUL-terminated strings; `len` holds the buffer size in bytes
+        let result = unsafe {
+            RegGetValueW(
+                root,
+                sub_key.as_ptr(),
+                value.as_ptr(),
+                RRF_RT_REG_SZ,
+                std::ptr::null_mut(),
+                data.as_mut_ptr().cast(),
+                &mut len,
+            )
+        };
+        if result != ERROR_SUCCESS {
+            return None;
+        }
+        let chars = (len as usize / 2).min(data.len());
+        Some(String::from_utf16_lossy(&data[..chars]).trim_end_matches('\0').to_string())
+    }
+
+    pub fn status(kind: MediaKind) -> Result<PermissionStatus, String> {
+        let capability = match kind {
+            MediaKind::Camera => "webcam",
+            MediaKind::Microphone => "microphone",
+        };
+        let key = format!(
+            r"Software\Microsoft\Windows\CurrentVersion\CapabilityAccessManager\ConsentStore\{}",
+            capability
+        );
+        Ok(consent_status(
+            consent_value(HKEY_LOCAL_MACHINE, &key).as_deref(),
+            consent_value(HKEY_CURRENT_USER, &key).as_deref(),
+            consent_value(HKEY_CURRENT_USER, &format!(r"{}\NonPackaged", key)).as_deref(),
+        ))
+    }
+
+    /// Desktop apps get no prompt; access is whatever the switches say
+    pub fn request(kind: MediaKind) -> Result<PermissionStatus, String> {
+        status(kind)
+    }
+
+    pub fn settings_url(kind: MediaKind) -> Option<&'static str> {
+        Some(match kind {
+            MediaKind::Camera => "ms-settings:privacy-webcam",
+            MediaKind::Microphone => "ms-settings:privacy-microphone",
+        })
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+mod platform {
+    use super::{MediaKind, PermissionStatus};
+
+    pub fn status(_kind: MediaKind) -> Result<PermissionStatus, String> {
+        Ok(PermissionStatus::Granted)
+    }
+
+    pub fn request(kind: MediaKind) -> Result<PermissionStatus, String> {
+        status(kind)
+    }
+
+    pub fn settings_url(_kind: MediaKind) -> Option<&'static str> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permission_status_mapping() {
+        assert_eq!(av_authorization_status(0), PermissionStatus::NotDetermined);
+        assert_eq!(av_authorization_status(1), PermissionStatus::Restricted);
+        assert_eq!(av_authorization_status(2), PermissionStatus::Denied);
+        assert_eq!(av_authorization_status(3), PermissionStatus::Granted);
+
+        assert_eq!(consent_status(None, None, None), PermissionStatus::Granted);
+        assert_eq!(consent_status(Some("Allow"), Some("Allow"), Some("Deny")), PermissionStatus::Denied);
+        assert_eq!(consent_status(Some("Deny"), Some("Allow"), None), PermissionStatus::Restricted);
+        assert_eq!(
+            serde_json::to_value(PermissionStatus::NotDetermined).unwrap(),
+            serde_json::json!("notDetermined")
+        );
+    }
+}
@@ -0,0 +1,156 @@
+//! Pipeline Statistics
+//!
+//! Health numbers for the recording in progress: how fast frames arrive and
+//! leave the encoder, how many were skipped or dropped on the way, gaps the
+//! encoder had to fill in the audio, and how full the queues between the
+//! stages are. The threads keep plain counters; rates are worked out when the
+//! frontend asks, over the time since it last did.
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::queue_watch::QueueProbe;
+
+/// Shortest window the frame rates are measured over; polling faster
+/// reports the previous window's rates again
+const MIN_RATE_WINDOW: Duration = Duration::from_millis(500);
+
+/// Snapshot returned by `get_pipeline_stats`
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineStats {
+    /// Frames per second arriving at the compositor
+    pub captured_fps: f64,
+    /// Frames per second leaving the encoder
+    pub encoded_fps: f64,
+    pub frames_captured: u64,
+    pub frames_encoded: u64,
+    /// Captured frames replaced by a newer one before they were composited
+    pub frames_skipped: u64,
+    /// Frames dropped because the encoder queue was (nearly) full
+    pub frames_dropped: u64,
+    /// Gaps in the audio the encoder filled with silence
+    pub audio_underruns: u64,
+    /// Captured frames waiting for the compositor
+    pub compositor_queue_depth: usize,
+    /// Composited frames waiting for the encoder
+    pub encoder_queue_depth: usize,
+}
+
+/// Frame counters kept by the thread feeding the encoder
+#[derive(Default)]
+pub struct FrameCounters {
+    captured: AtomicU64,
+    skipped: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl FrameCounters {
+    /// A frame arrived from the capture
+    pub fn captured(&self) {
+        self.captured.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A newer frame arrived before this one was composited
+    pub fn skipped(&self) {
+        self.skipped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A frame was left out to relieve the encoder
+    pub fn dropped(&self) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Skipped and dropped frames together
+    pub fn skipped_total(&self) -> u64 {
+        self.skipped.load(Ordering::Relaxed) + self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// Frames per second between successive readings of two counters
+struct RateMeter {
+    since: Instant,
+    counts: (u64, u64),
+    fps: (f64, f64),
+}
+
+impl RateMeter {
+    fn new(now: Instant) -> Self {
+        Self {
+            since: now,
+            counts: (0, 0),
+            fps: (0.0, 0.0),
+        }
+    }
+
+    fn update(&mut self, now: Instant, counts: (u64, u64)) -> (f64, f64) {
+        let elapsed = now.saturating_duration_since(self.since);
+        if elapsed >= MIN_RATE_WINDOW {
+            let secs = elapsed.as_secs_f64();
+            self.fps = (
+                counts.0.saturating_sub(self.counts.0) as f64 / secs,
+                counts.1.saturating_sub(self.counts.1) as f64 / secs,
+            );
+            self.since = now;
+            self.counts = counts;
+        }
+        self.fps
+    }
+}
+
+/// Frame counters and the queues on either side of them
+pub struct PipelineProbe {
+    frames: Arc<FrameCounters>,
+    compositor_queue: Option<QueueProbe>,
+    encoder_queue: QueueProbe,
+    meter: RateMeter,
+}
+
+impl PipelineProbe {
+    pub fn new(frames: Arc<FrameCounters>, compositor_queue: Option<QueueProbe>, encoder_queue: QueueProbe) -> Self {
+        Self {
+            frames,
+            compositor_queue,
+            encoder_queue,
+            meter: RateMeter::new(Instant::now()),
+        }
+    }
+
+    /// Current numbers, with the encoder's own counts
+    pub fn stats(&mut self, frames_encoded: u64, audio_underruns: u64) -> PipelineStats {
+        let frames_captured = self.frames.captured.load(Ordering::Relaxed);
+        let (captured_fps, encoded_fps) = self.meter.update(Instant::now(), (frames_captured, frames_encoded));
+        PipelineStats {
+            captured_fps,
+            encoded_fps,
+            frames_captured,
+            frames_encoded,
+            frames_skipped: self.frames.skipped.load(Ordering::Relaxed),
+            frames_dropped: self.frames.dropped.load(Ordering::Relaxed),
+            audio_underruns,
+            compositor_queue_depth: self.compositor_queue.as_ref().map_or(0, |probe| probe().0),
+            encoder_queue_depth: (self.encoder_queue)().0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rates_are_measured_over_windows() {
+        let start = Instant::now();
+        let mut meter = RateMeter::new(start);
+
+        // Too soon to tell
+        assert_eq!(meter.update(start + Duration::from_millis(100), (3, 3)), (0.0, 0.0));
+
+        assert_eq!(meter.update(start + Duration::from_secs(1), (30, 24)), (30.0, 24.0));
+        // A quick second poll repeats the last window's rates
+        assert_eq!(meter.update(start + Duration::from_millis(1200), (36, 29)), (30.0, 24.0));
+        assert_eq!(meter.update(start + Duration::from_secs(2), (60, 54)), (30.0, 30.0));
+    }
+}
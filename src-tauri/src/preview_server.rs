@@ -0,0 +1,434 @@
+//! Remote Preview Server
+//!
+//! Serves the confidence monitor feed as MJPEG over HTTP on the local
+//! network, so a phone or tablet can watch the composited output by opening
+//! a URL in its browser. Frames come from the monitor feed as a second
+//! subscriber, so the rate cap, scaling and JPEG encoding are shared with the
+//! monitor window and the recording threads are never held up.
+//!
+//! The URL carries a random token; other paths get a 404, so only devices
+//! given the URL can watch. Each viewer gets its own thread, which sends the
+//! newest frame whenever one arrives and skips any it was too slow for.
+//! Connections beyond `MAX_VIEWERS` are turned away with a 503, so a flood of
+//! them from the network can't pile up threads.
+
+use parking_lot::{Condvar, Mutex};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{IpAddr, Ipv4Addr, TcpListener, TcpStream, UdpSocket};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::command;
+
+use crate::monitor_feed::{FeedClient, MonitorFeed, MonitorFeedConfig, MonitorFeedFormat};
+use crate::recording::RecordingError;
+
+/// Multipart boundary between JPEG parts
+const BOUNDARY: &str = "frame";
+
+/// How often the accept loop and idle viewers check the server is still running
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// A viewer that can't take a frame within this long is dropped
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A connection that doesn't send its request line within this long is closed
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Most connections served at once, viewers and unanswered requests alike
+const MAX_VIEWERS: usize = 8;
+
+/// Remote preview settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewServerConfig {
+    /// Port to listen on; 0 picks a free one
+    #[serde(default = "default_port")]
+    pub port: u16,
+    /// Largest preview width; frames are scaled down keeping the aspect ratio
+    #[serde(default = "default_max_width")]
+    pub max_width: u32,
+    /// Preview frame rate cap (1-30)
+    #[serde(default = "default_frame_rate")]
+    pub frame_rate: u32,
+    /// JPEG quality (1-100)
+    #[serde(default = "default_jpeg_quality")]
+    pub jpeg_quality: u8,
+}
+
+fn default_port() -> u16 {
+    8765
+}
+
+fn default_max_width() -> u32 {
+    640
+}
+
+fn default_frame_rate() -> u32 {
+    10
+}
+
+fn default_jpeg_quality() -> u8 {
+    60
+}
+
+impl Default for PreviewServerConfig {
+    fn default() -> Self {
+        Self {
+            port: default_port(),
+            max_width: default_max_width(),
+            frame_rate: default_frame_rate(),
+            jpeg_quality: default_jpeg_quality(),
+        }
+    }
+}
+
+impl PreviewServerConfig {
+    fn feed_config(&self) -> MonitorFeedConfig {
+        MonitorFeedConfig {
+            max_width: self.max_width,
+            frame_rate: self.frame_rate,
+            format: MonitorFeedFormat::Jpeg,
+            jpeg_quality: self.jpeg_quality,
+        }
+    }
+}
+
+/// Where the preview can be watched, returned by `start_preview_server`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewServerInfo {
+    /// Page to open on the other device, using this machine's LAN address
+    pub url: String,
+    pub port: u16,
+}
+
+/// Newest JPEG, numbered so viewers can tell when it changes
+#[derive(Default)]
+struct LatestFrame {
+    frame: Mutex<(u64, Arc<Vec<u8>>)>,
+    changed: Condvar,
+}
+
+struct ServerSession {
+    running: Arc<Mutex<bool>>,
+    info: PreviewServerInfo,
+}
+
+/// Shared handle to the remote preview server
+#[derive(Clone, Default)]
+pub struct PreviewServer {
+    session: Arc<Mutex<Option<ServerSession>>>,
+}
+
+impl PreviewServer {
+    /// Start serving `feed`, replacing any server already running
+    fn start(&self, config: PreviewServerConfig, feed: &MonitorFeed) -> Result<PreviewServerInfo, String> {
+        let feed_config = config.feed_config();
+        feed_config.validate()?;
+        self.stop(feed);
+
+        let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, config.port))
+            .map_err(|e| format!("Failed to listen on port {}: {}", config.port, e))?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| format!("Failed to configure preview server: {}", e))?;
+        let port = listener
+            .local_addr()
+            .map_err(|e| format!("Failed to configure preview server: {}", e))?
+            .port();
+
+        let token = random_token()?;
+        let running = Arc::new(Mutex::new(true));
+        let latest = Arc::new(LatestFrame::default());
+
+        let sink_latest = latest.clone();
+        let sink_running = running.clone();
+        feed.subscribe(
+            FeedClient::Remote,
+            feed_config,
+            Box::new(move |message| {
+                if !*sink_running.lock() {
+                    return Err("Preview server stopped".to_string());
+                }
+                // Strip the width/height header; browsers only want the JPEG
+                let jpeg = Arc::new(message.get(8..).unwrap_or_default().to_vec());
+                let mut frame = sink_latest.frame.lock();
+                *frame = (frame.0 + 1, jpeg);
+                sink_latest.changed.notify_all();
+                Ok(())
+            }),
+        )?;
+
+        let accept_running = running.clone();
+        let path = format!("/{}", token);
+        std::thread::spawn(move || {
+            accept_loop(listener, path, latest, accept_running);
+        });
+
+        let info = PreviewServerInfo {
+            url: format!("http://{}:{}/{}", local_ip(), port, token),
+            port,
+        };
+        println!("Preview server listening on port {}", port);
+        *self.session.lock() = Some(ServerSession {
+            running,
+            info: info.clone(),
+        });
+        Ok(info)
+    }
+
+    /// Stop serving; does nothing when no server is running
+    fn stop(&self, feed: &MonitorFeed) {
+        if let Some(session) = self.session.lock().take() {
+            *session.running.lock() = false;
+            feed.unsubscribe(FeedClient::Remote);
+            println!("Preview server on port {} stopped", session.info.port);
+        }
+    }
+}
+
+/// Counts a connection as served until dropped
+struct ViewerSlot(Arc<AtomicUsize>);
+
+impl ViewerSlot {
+    /// Take a slot, or None when `MAX_VIEWERS` are already connected
+    fn take(viewers: &Arc<AtomicUsize>) -> Option<Self> {
+        viewers
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| (n < MAX_VIEWERS).then_some(n + 1))
+            .ok()
+            .map(|_| Self(viewers.clone()))
+    }
+}
+
+impl Drop for ViewerSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+fn accept_loop(listener: TcpListener, path: String, latest: Arc<LatestFrame>, running: Arc<Mutex<bool>>) {
+    let viewers = Arc::new(AtomicUsize::new(0));
+    while *running.lock() {
+        match listener.accept() {
+            Ok((mut stream, _)) => {
+                let Some(slot) = ViewerSlot::take(&viewers) else {
+                    // Still non-blocking, so a stalled peer can't hold up the loop
+                    let _ = stream.write_all(
+                        b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    );
+                    continue;
+                };
+                let path = path.clone();
+                let latest = latest.clone();
+                let running = running.clone();
+                std::thread::spawn(move || {
+                    let _slot = slot;
+                    if let Err(e) = serve_viewer(stream, &path, &latest, &running) {
+                        println!("Preview viewer disconnected: {}", e);
+                    }
+                });
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => std::thread::sleep(POLL_INTERVAL),
+            Err(e) => {
+                eprintln!("Preview server accept failed: {}", e);
+                std::thread::sleep(POLL_INTERVAL);
+            }
+        }
+    }
+}
+
+/// Answer one request, streaming frames until the viewer leaves or the
+/// server stops
+fn serve_viewer(
+    mut stream: TcpStream,
+    path: &str,
+    latest: &LatestFrame,
+    running: &Mutex<bool>,
+) -> std::io::Result<()> {
+    // The listener is non-blocking; its connections needn't be
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    if request_path(&request_line) != Some(path) {
+        return stream.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+    }
+
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: multipart/x-mixed-replace; boundary={}\r\n\
+         Cache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        BOUNDARY
+    )?;
+
+    let mut sent = 0;
+    while *running.lock() {
+        let jpeg = {
+            let mut frame = latest.frame.lock();
+            if frame.0 == sent {
+                latest.changed.wait_for(&mut frame, POLL_INTERVAL);
+            }
+            if frame.0 == sent {
+                continue;
+            }
+            sent = frame.0;
+            frame.1.clone()
+        };
+        write!(
+            stream,
+            "--{}\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n",
+            BOUNDARY,
+            jpeg.len()
+        )?;
+        stream.write_all(&jpeg)?;
+        stream.write_all(b"\r\n")?;
+    }
+    Ok(())
+}
+
+/// Path of a GET request line (`GET /path HTTP/1.1`)
+fn request_path(request_line: &str) -> Option<&str> {
+    let mut parts = request_line.split_whitespace();
+    match (parts.next(), parts.next()) {
+        (Some("GET"), Some(path)) => Some(path),
+        _ => None,
+    }
+}
+
+/// 32 hex characters from the operating system's random source
+pub(crate) fn random_token() -> Result<String, String> {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes).map_err(|e| format!("Failed to generate a random token: {}", e))?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Address other devices on the network reach this machine at
+///
+/// Connecting a UDP socket sends nothing; it only picks the interface that
+/// routes outward. Falls back to loopback when there's no network.
+fn local_ip() -> IpAddr {
+    UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+        .and_then(|socket| {
+            socket.connect((Ipv4Addr::new(192, 0, 2, 1), 9))?;
+            socket.local_addr()
+        })
+        .map(|addr| addr.ip())
+        .unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST))
+}
+
+/// Tauri command: Serve the composited output to other devices on the network
+///
+/// Replaces any server already running. Like the monitor feed, the server
+/// runs across recordings and only sends frames while one is in progress.
+#[command]
+pub fn start_preview_server(
+    config: Option<PreviewServerConfig>,
+    server: tauri::State<'_, PreviewServer>,
+    feed: tauri::State<'_, MonitorFeed>,
+) -> Result<PreviewServerInfo, RecordingError> {
+    server
+        .start(config.unwrap_or_default(), &feed)
+        .map_err(RecordingError::InvalidConfig)
+}
+
+/// Tauri command: Stop the remote preview server
+#[command]
+pub fn stop_preview_server(server: tauri::State<'_, PreviewServer>, feed: tauri::State<'_, MonitorFeed>) {
+    server.stop(&feed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compositor::{CompositeFrame, FrameFormat};
+    use std::io::Read;
+
+    #[test]
+    fn test_preview_server_streams_jpeg_to_token_path() {
+        let feed = MonitorFeed::default();
+        let server = PreviewServer::default();
+        let info = server
+            .start(
+                PreviewServerConfig {
+                    port: 0,
+                    ..PreviewServerConfig::default()
+                },
+                &feed,
+            )
+            .unwrap();
+        let token = info.url.rsplit('/').next().unwrap().to_string();
+        assert_eq!(token.len(), 32);
+
+        let request = |path: &str| {
+            let mut stream = TcpStream::connect((Ipv4Addr::LOCALHOST, info.port)).unwrap();
+            stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+            stream
+        };
+
+        let mut response = String::new();
+        request("/guess").read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 404"));
+
+        let mut viewer = request(&format!("/{}", token));
+        let frame = CompositeFrame {
+            data: crate::frame_pool::FrameBuffer::shared(vec![128; 16 * 8 * 4]),
+            width: 16,
+            height: 8,
+            timestamp: Duration::ZERO,
+            format: FrameFormat::Rgba,
+        };
+        let mut received = Vec::new();
+        let mut buf = [0; 4096];
+        while !received.windows(2).any(|w| w == [0xFF, 0xD8]) {
+            feed.offer(&frame);
+            let n = viewer.read(&mut buf).unwrap();
+            assert!(n > 0);
+            received.extend_from_slice(&buf[..n]);
+        }
+        server.stop(&feed);
+
+        let text = String::from_utf8_lossy(&received);
+        assert!(text.starts_with("HTTP/1.1 200 OK"));
+        assert!(text.contains("Content-Type: image/jpeg"));
+    }
+
+    #[test]
+    fn test_preview_server_turns_away_viewers_over_the_cap() {
+        let feed = MonitorFeed::default();
+        let server = PreviewServer::default();
+        let info = server
+            .start(
+                PreviewServerConfig {
+                    port: 0,
+                    ..PreviewServerConfig::default()
+                },
+                &feed,
+            )
+            .unwrap();
+
+        // Idle connections hold their slots until the request timeout
+        let _idle: Vec<_> = (0..MAX_VIEWERS)
+            .map(|_| TcpStream::connect((Ipv4Addr::LOCALHOST, info.port)).unwrap())
+            .collect();
+        let mut extra = TcpStream::connect((Ipv4Addr::LOCALHOST, info.port)).unwrap();
+        extra.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let mut response = String::new();
+        extra.read_to_string(&mut response).unwrap();
+        server.stop(&feed);
+
+        assert!(response.starts_with("HTTP/1.1 503"));
+    }
+
+    #[test]
+    fn test_random_token() {
+        let token = random_token().unwrap();
+        assert_eq!(token.len(), 32);
+        assert!(token.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(token, random_token().unwrap());
+    }
+}
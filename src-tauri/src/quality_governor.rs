@@ -0,0 +1,431 @@
+//! Adaptive Quality
+//!
+//! When the machine can't keep up, frames pile up in front of the encoder
+//! and the queues start dropping them, for as long as the overload lasts.
+//! With adaptive quality on, a governor watches the composite frame queue
+//! instead, and once it stays full for a couple of seconds it steps the
+//! recording down one level and emits `quality-stepped-down`. High CPU usage
+//! only brings that forward, to a half-full queue: a busy CPU that keeps up
+//! is no reason to lower quality. The steps:
+//!
+//! 1. CRF raised by 6 (libx264 picks it up between frames)
+//! 2. Frame rate halved (the encoder keeps every other frame slot)
+//! 3. Frames composited at two thirds of the output size; the encoder scales
+//!    them back up, since a file's frame size can't change mid-recording
+//!
+//! After each step the governor waits for the queue to drain before judging
+//! again. Levels are never raised back within a recording.
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+use crate::queue_watch::QueueProbe;
+
+/// Event emitted each time the recording steps down a level
+pub const STEPPED_DOWN_EVENT: &str = "quality-stepped-down";
+
+/// How often the queue and CPU usage are sampled
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Samples under pressure before stepping down (2 seconds)
+const SUSTAIN_SAMPLES: u32 = 8;
+
+/// Samples ignored after a step while the queue drains (5 seconds)
+const SETTLE_SAMPLES: u32 = 20;
+
+/// CRF added by the first step
+const CRF_STEP: u32 = 6;
+
+/// Frame slots each kept frame holds after the frame rate step
+const FRAME_RATE_DIVISOR: u32 = 2;
+
+/// Composited size after the resolution step, in percent of the output size
+const RENDER_SCALE_PERCENT: u32 = 67;
+
+/// When the governor steps in
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdaptiveQualityConfig {
+    /// Off by default: quality stays as configured and frames are dropped
+    #[serde(default)]
+    pub enabled: bool,
+    /// Composite queue fill (percent of its capacity) that counts as pressure
+    #[serde(default = "default_queue_percent")]
+    pub queue_percent: u8,
+    /// Process CPU usage (percent of all cores) that counts as pressure
+    /// together with a queue at least half `queue_percent` full
+    #[serde(default = "default_cpu_percent")]
+    pub cpu_percent: u8,
+}
+
+fn default_queue_percent() -> u8 {
+    60
+}
+
+fn default_cpu_percent() -> u8 {
+    90
+}
+
+impl Default for AdaptiveQualityConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            queue_percent: default_queue_percent(),
+            cpu_percent: default_cpu_percent(),
+        }
+    }
+}
+
+impl AdaptiveQualityConfig {
+    /// Check the thresholds are percentages
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=100).contains(&self.queue_percent) {
+            return Err(format!("Adaptive quality queue threshold must be 1-100% (got {})", self.queue_percent));
+        }
+        if !(1..=100).contains(&self.cpu_percent) {
+            return Err(format!("Adaptive quality CPU threshold must be 1-100% (got {})", self.cpu_percent));
+        }
+        Ok(())
+    }
+}
+
+/// One level down, in the order they are taken
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum QualityStep {
+    HigherCrf,
+    HalfFrameRate,
+    ReducedResolution,
+}
+
+impl QualityStep {
+    /// Every step, for recordings that composite their own frames
+    pub const ALL: [QualityStep; 3] = [Self::HigherCrf, Self::HalfFrameRate, Self::ReducedResolution];
+}
+
+/// What was under pressure when a step was taken
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PressureSource {
+    /// The composite frame queue stayed full
+    Queue,
+    /// The queue was filling while the process kept the CPU busy
+    Cpu,
+}
+
+/// Payload of `quality-stepped-down`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct QualityStepEvent {
+    pub step: QualityStep,
+    /// Levels stepped down so far (1 after the first step)
+    pub level: u8,
+    pub source: PressureSource,
+    pub queue_fill_percent: u8,
+    pub cpu_percent: u8,
+}
+
+/// Adjustments currently in force, read by the encoder and compositor
+#[derive(Debug, Default)]
+pub struct QualityControls {
+    crf_offset: AtomicU32,
+    frame_divisor: AtomicU32,
+    render_scale_percent: AtomicU32,
+}
+
+impl QualityControls {
+    /// CRF to add to the configured one
+    #[cfg_attr(not(any(feature = "ffmpeg", test)), allow(dead_code))]
+    pub fn crf_offset(&self) -> u32 {
+        self.crf_offset.load(Ordering::Relaxed)
+    }
+
+    /// Frame slots each encoded frame holds (1: every frame is kept)
+    #[cfg_attr(not(any(feature = "ffmpeg", test)), allow(dead_code))]
+    pub fn frame_divisor(&self) -> u32 {
+        self.frame_divisor.load(Ordering::Relaxed).max(1)
+    }
+
+    /// Size to composite at, in percent of the output size
+    pub fn render_scale_percent(&self) -> u32 {
+        match self.render_scale_percent.load(Ordering::Relaxed) {
+            0 => 100,
+            percent => percent,
+        }
+    }
+
+    fn apply(&self, step: QualityStep) {
+        match step {
+            QualityStep::HigherCrf => self.crf_offset.store(CRF_STEP, Ordering::Relaxed),
+            QualityStep::HalfFrameRate => self.frame_divisor.store(FRAME_RATE_DIVISOR, Ordering::Relaxed),
+            QualityStep::ReducedResolution => {
+                self.render_scale_percent.store(RENDER_SCALE_PERCENT, Ordering::Relaxed)
+            }
+        }
+    }
+}
+
+/// Receives each step as it is taken
+pub type StepSink = Box<dyn Fn(QualityStepEvent) + Send>;
+
+/// Sink emitting the event to every window
+pub fn app_sink(app: AppHandle) -> StepSink {
+    Box::new(move |payload| {
+        if let Err(e) = app.emit(STEPPED_DOWN_EVENT, payload) {
+            eprintln!("Failed to emit {}: {}", STEPPED_DOWN_EVENT, e);
+        }
+    })
+}
+
+/// Decides when pressure has lasted long enough to step down
+struct Pressure {
+    queue_percent: u8,
+    cpu_percent: u8,
+    /// Consecutive samples under pressure
+    sustained: u32,
+    /// Samples still ignored after the last step
+    settling: u32,
+}
+
+impl Pressure {
+    fn new(config: &AdaptiveQualityConfig) -> Self {
+        Self {
+            queue_percent: config.queue_percent,
+            cpu_percent: config.cpu_percent,
+            sustained: 0,
+            settling: 0,
+        }
+    }
+
+    /// Take a sample; returns what is under pressure when it's time to step down
+    fn sample(&mut self, queue_fill_percent: u8, cpu_percent: u8) -> Option<PressureSource> {
+        if self.settling > 0 {
+            self.settling -= 1;
+            return None;
+        }
+        let source = if queue_fill_percent >= self.queue_percent {
+            PressureSource::Queue
+        } else if cpu_percent >= self.cpu_percent && queue_fill_percent >= self.queue_percent / 2 {
+            PressureSource::Cpu
+        } else {
+            self.sustained = 0;
+            return None;
+        };
+
+        self.sustained += 1;
+        if self.sustained < SUSTAIN_SAMPLES {
+            return None;
+        }
+        self.sustained = 0;
+        self.settling = SETTLE_SAMPLES;
+        Some(source)
+    }
+}
+
+/// Share of all cores this process used between samples
+struct CpuUsage {
+    cores: u32,
+    last: Option<(Instant, Duration)>,
+}
+
+impl CpuUsage {
+    fn new() -> Self {
+        Self {
+            cores: std::thread::available_parallelism().map_or(1, |n| n.get() as u32),
+            last: None,
+        }
+    }
+
+    /// Percent of all cores used since the last call (0 on the first)
+    fn sample(&mut self) -> u8 {
+        let Some(cpu_time) = process_cpu_time() else {
+            return 0;
+        };
+        let now = Instant::now();
+        let percent = match self.last {
+            Some((at, used)) => {
+                let wall = now.duration_since(at).as_secs_f64() * self.cores as f64;
+                let used = cpu_time.saturating_sub(used).as_secs_f64();
+                if wall > 0.0 { (used / wall * 100.0).min(100.0) as u8 } else { 0 }
+            }
+            None => 0,
+        };
+        self.last = Some((now, cpu_time));
+        percent
+    }
+}
+
+/// User and system CPU time used by this process so far
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn process_cpu_time() -> Option<Duration> {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } != 0 {
+        return None;
+    }
+    let time = |t: libc::timeval| Duration::new(t.tv_sec as u64, t.tv_usec as u32 * 1000);
+    Some(time(usage.ru_utime) + time(usage.ru_stime))
+}
+
+#[cfg(target_os = "windows")]
+fn process_cpu_time() -> Option<Duration> {
+    use std::ffi::c_void;
+
+    /// `FILETIME`: 100ns units split into two halves
+    #[repr(C)]
+    #[derive(Default)]
+    struct FileTime {
+        low: u32,
+        high: u32,
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCurrentProcess() -> *mut c_void;
+        fn GetProcessTimes(
+            process: *mut c_void,
+            creation: *mut FileTime,
+            exit: *mut FileTime,
+            kernel: *mut FileTime,
+            user: *mut FileTime,
+        ) -> i32;
+    }
+
+    let (mut creation, mut exit, mut kernel, mut user) =
+        (FileTime::default(), FileTime::default(), FileTime::default(), FileTime::default());
+    if unsafe { GetProcessTimes(GetCurrentProcess(), &mut creation, &mut exit, &mut kernel, &mut user) } == 0 {
+        return None;
+    }
+    let ticks = |t: FileTime| (u64::from(t.high) << 32) | u64::from(t.low);
+    Some(Duration::from_nanos((ticks(kernel) + ticks(user)) * 100))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn process_cpu_time() -> Option<Duration> {
+    None
+}
+
+/// Background governor for one recording
+pub struct QualityGovernor {
+    running: Arc<Mutex<bool>>,
+}
+
+impl QualityGovernor {
+    /// Start watching `queue`, taking `steps` in order through `controls`
+    pub fn start(
+        config: AdaptiveQualityConfig,
+        steps: Vec<QualityStep>,
+        controls: Arc<QualityControls>,
+        queue: QueueProbe,
+        sink: StepSink,
+    ) -> Self {
+        let running = Arc::new(Mutex::new(true));
+        let running_clone = running.clone();
+        std::thread::spawn(move || {
+            governor_loop(running_clone, config, steps, controls, queue, sink);
+        });
+        Self { running }
+    }
+
+    /// Stop watching; adjustments already made stay in force
+    pub fn stop(&self) {
+        *self.running.lock() = false;
+    }
+}
+
+impl Drop for QualityGovernor {
+    fn drop(&mut self) {
+        *self.running.lock() = false;
+    }
+}
+
+fn governor_loop(
+    running: Arc<Mutex<bool>>,
+    config: AdaptiveQualityConfig,
+    steps: Vec<QualityStep>,
+    controls: Arc<QualityControls>,
+    queue: QueueProbe,
+    sink: StepSink,
+) {
+    let mut pressure = Pressure::new(&config);
+    let mut cpu = CpuUsage::new();
+    let mut steps = steps.into_iter();
+    let mut level: u8 = 0;
+
+    while *running.lock() {
+        std::thread::sleep(POLL_INTERVAL);
+        let (len, capacity) = queue();
+        let queue_fill_percent = (len * 100).checked_div(capacity).map_or(0, |percent| percent.min(100) as u8);
+        let cpu_percent = cpu.sample();
+
+        let Some(source) = pressure.sample(queue_fill_percent, cpu_percent) else {
+            continue;
+        };
+        let Some(step) = steps.next() else {
+            println!("Adaptive quality: lowest level reached");
+            break;
+        };
+        controls.apply(step);
+        level += 1;
+        println!(
+            "Adaptive quality: {:?} (level {}, queue {}% full, CPU {}%)",
+            step, level, queue_fill_percent, cpu_percent
+        );
+        sink(QualityStepEvent {
+            step,
+            level,
+            source,
+            queue_fill_percent,
+            cpu_percent,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_steps_after_sustained_pressure_then_settles() {
+        let config = AdaptiveQualityConfig::default();
+        let mut pressure = Pressure::new(&config);
+
+        // A short spike is forgiven
+        for _ in 0..SUSTAIN_SAMPLES - 1 {
+            assert_eq!(pressure.sample(100, 0), None);
+        }
+        assert_eq!(pressure.sample(10, 10), None);
+
+        // A busy CPU alone never steps down
+        for _ in 0..SUSTAIN_SAMPLES * 2 {
+            assert_eq!(pressure.sample(10, 100), None);
+        }
+
+        for _ in 0..SUSTAIN_SAMPLES - 1 {
+            assert_eq!(pressure.sample(30, 95), None);
+        }
+        assert_eq!(pressure.sample(30, 95), Some(PressureSource::Cpu));
+
+        // The queue gets time to drain before the next step
+        for _ in 0..SETTLE_SAMPLES + SUSTAIN_SAMPLES - 1 {
+            assert_eq!(pressure.sample(100, 100), None);
+        }
+        assert_eq!(pressure.sample(100, 100), Some(PressureSource::Queue));
+
+        let controls = QualityControls::default();
+        assert_eq!((controls.crf_offset(), controls.frame_divisor(), controls.render_scale_percent()), (0, 1, 100));
+        for step in QualityStep::ALL {
+            controls.apply(step);
+        }
+        assert_eq!(
+            (controls.crf_offset(), controls.frame_divisor(), controls.render_scale_percent()),
+            (CRF_STEP, FRAME_RATE_DIVISOR, RENDER_SCALE_PERCENT)
+        );
+
+        assert!(AdaptiveQualityConfig { cpu_percent: 0, ..config }.validate().is_err());
+        assert!(config.validate().is_ok());
+    }
+}
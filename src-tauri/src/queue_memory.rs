@@ -0,0 +1,127 @@
+//! Queue Memory
+//!
+//! Frames waiting in the pipeline's queues are most of a recording's memory:
+//! 120 composited 4K frames come to 4 GB. `QueueMemory` adds up what the
+//! queues hold (items queued times their size) for `RecordingStatus`, and
+//! once the total reaches the configured cap the compositor and frame ingest
+//! drop new frames, before the queues themselves are full.
+//!
+//! Item sizes are worked out from the recording's dimensions when the queue
+//! is registered, so the total is an upper bound while adaptive quality
+//! draws smaller frames.
+
+use crossbeam_channel::Receiver;
+use serde::{Deserialize, Serialize};
+
+/// Allowed caps, in megabytes
+pub const QUEUE_MEMORY_RANGE_MB: std::ops::RangeInclusive<u32> = 256..=65536;
+
+/// Queue memory settings
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueMemoryConfig {
+    /// Memory the frame and audio queues may hold before frames are dropped
+    #[serde(default = "default_max_megabytes")]
+    pub max_megabytes: u32,
+}
+
+fn default_max_megabytes() -> u32 {
+    2048
+}
+
+impl Default for QueueMemoryConfig {
+    fn default() -> Self {
+        Self {
+            max_megabytes: default_max_megabytes(),
+        }
+    }
+}
+
+impl QueueMemoryConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !QUEUE_MEMORY_RANGE_MB.contains(&self.max_megabytes) {
+            return Err(format!(
+                "Queue memory cap must be {}-{} MB (got {})",
+                QUEUE_MEMORY_RANGE_MB.start(),
+                QUEUE_MEMORY_RANGE_MB.end(),
+                self.max_megabytes
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Bytes held by one queue
+type ByteProbe = Box<dyn Fn() -> u64 + Send + Sync>;
+
+/// Total memory of the recording's queues, against a cap
+pub struct QueueMemory {
+    queues: Vec<ByteProbe>,
+    max_bytes: u64,
+}
+
+impl QueueMemory {
+    pub fn new(config: QueueMemoryConfig) -> Self {
+        Self {
+            queues: Vec::new(),
+            max_bytes: config.max_megabytes as u64 * 1024 * 1024,
+        }
+    }
+
+    /// Count a channel whose items take `item_bytes` each, through a
+    /// receiver clone that is never read
+    pub fn watch<T: Send + 'static>(&mut self, receiver: Receiver<T>, item_bytes: u64) {
+        self.queues.push(Box::new(move || receiver.len() as u64 * item_bytes));
+    }
+
+    /// Bytes queued right now
+    pub fn bytes(&self) -> u64 {
+        self.queues.iter().map(|queue| queue()).sum()
+    }
+
+    /// Whether the queues have reached the cap; frames should be dropped
+    pub fn over_cap(&self) -> bool {
+        self.bytes() >= self.max_bytes
+    }
+
+    /// The cap, for log messages
+    pub fn max_megabytes(&self) -> u64 {
+        self.max_bytes / (1024 * 1024)
+    }
+}
+
+/// Bytes of a `width` x `height` frame with four bytes per pixel
+pub fn frame_bytes(width: u32, height: u32) -> u64 {
+    width as u64 * height as u64 * 4
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossbeam_channel::bounded;
+
+    #[test]
+    fn test_queue_memory_sums_queues_against_cap() {
+        let (frames, frame_queue) = bounded::<u8>(16);
+        let (audio, audio_queue) = bounded::<u8>(8);
+        let mut memory = QueueMemory::new(QueueMemoryConfig { max_megabytes: 256 });
+        memory.watch(frame_queue, frame_bytes(3840, 2160));
+        memory.watch(audio_queue, 8192);
+        assert_eq!(memory.bytes(), 0);
+
+        frames.send(0).unwrap();
+        audio.send(0).unwrap();
+        audio.send(0).unwrap();
+        assert_eq!(memory.bytes(), 3840 * 2160 * 4 + 2 * 8192);
+        assert!(!memory.over_cap());
+
+        // Nine 4K frames are 285 MB, past the 256 MB cap
+        for _ in 0..8 {
+            frames.send(0).unwrap();
+        }
+        assert!(memory.over_cap());
+
+        assert!(QueueMemoryConfig { max_megabytes: 64 }.validate().is_err());
+        assert!(QueueMemoryConfig::default().validate().is_ok());
+    }
+}
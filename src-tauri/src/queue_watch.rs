@@ -0,0 +1,246 @@
+//! Queue Watermark Events
+//!
+//! Polls how full the composite frame and mixed audio queues are and emits
+//! `queue-high-watermark` when one fills past the high threshold, then
+//! `queue-recovered` once it drains below the low threshold. Frames and audio
+//! start being dropped when these queues are full, so the UI can show that the
+//! machine is struggling while it happens. The gap between the thresholds
+//! keeps a queue hovering near one level from producing a stream of events.
+
+use crossbeam_channel::Receiver;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+/// Event emitted when a queue fills past the high threshold
+pub const HIGH_WATERMARK_EVENT: &str = "queue-high-watermark";
+
+/// Event emitted when a queue drains below the low threshold again
+pub const RECOVERED_EVENT: &str = "queue-recovered";
+
+/// How often queue lengths are sampled
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Fill thresholds, in percent of each queue's capacity
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueWatermarkConfig {
+    #[serde(default = "default_high_percent")]
+    pub high_percent: u8,
+    #[serde(default = "default_low_percent")]
+    pub low_percent: u8,
+}
+
+fn default_high_percent() -> u8 {
+    75
+}
+
+fn default_low_percent() -> u8 {
+    25
+}
+
+impl Default for QueueWatermarkConfig {
+    fn default() -> Self {
+        Self {
+            high_percent: default_high_percent(),
+            low_percent: default_low_percent(),
+        }
+    }
+}
+
+impl QueueWatermarkConfig {
+    /// Check the thresholds are in range and ordered
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=100).contains(&self.high_percent) {
+            return Err(format!("High watermark must be 1-100% (got {})", self.high_percent));
+        }
+        if self.low_percent >= self.high_percent {
+            return Err(format!(
+                "Low watermark ({}%) must be below the high watermark ({}%)",
+                self.low_percent, self.high_percent
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Queue an event refers to
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchedQueue {
+    /// Composited video frames waiting for the encoder
+    Composite,
+    /// Mixed audio waiting for the encoder
+    Audio,
+}
+
+/// Payload of both events
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueWatermarkEvent {
+    pub queue: WatchedQueue,
+    pub len: usize,
+    pub capacity: usize,
+    pub fill_percent: u8,
+}
+
+/// Reports the current length and capacity of a queue
+pub type QueueProbe = Box<dyn Fn() -> (usize, usize) + Send>;
+
+/// Receives `(event name, payload)` pairs
+pub type WatermarkSink = Box<dyn Fn(&'static str, QueueWatermarkEvent) + Send>;
+
+/// Probe for a bounded channel, through a receiver clone that is never read
+pub fn channel_probe<T: Send + 'static>(receiver: Receiver<T>) -> QueueProbe {
+    Box::new(move || (receiver.len(), receiver.capacity().unwrap_or(0)))
+}
+
+/// Sink emitting the events to every window
+pub fn app_sink(app: AppHandle) -> WatermarkSink {
+    Box::new(move |event, payload| {
+        if let Err(e) = app.emit(event, payload) {
+            eprintln!("Failed to emit {}: {}", event, e);
+        }
+    })
+}
+
+/// Above/below state of one queue
+struct QueueState {
+    queue: WatchedQueue,
+    probe: QueueProbe,
+    high: bool,
+}
+
+impl QueueState {
+    /// Sample the queue; returns an event if it crossed a threshold
+    fn poll(&mut self, config: &QueueWatermarkConfig) -> Option<(&'static str, QueueWatermarkEvent)> {
+        let (len, capacity) = (self.probe)();
+        if capacity == 0 {
+            return None;
+        }
+        let fill_percent = (len * 100 / capacity).min(100) as u8;
+
+        let event = if !self.high && fill_percent >= config.high_percent {
+            HIGH_WATERMARK_EVENT
+        } else if self.high && fill_percent <= config.low_percent {
+            RECOVERED_EVENT
+        } else {
+            return None;
+        };
+        self.high = !self.high;
+
+        Some((
+            event,
+            QueueWatermarkEvent {
+                queue: self.queue,
+                len,
+                capacity,
+                fill_percent,
+            },
+        ))
+    }
+}
+
+/// Background poller for the recording's queues
+pub struct QueueWatch {
+    running: Arc<Mutex<bool>>,
+}
+
+impl QueueWatch {
+    /// Start polling `queues`
+    pub fn start(
+        config: QueueWatermarkConfig,
+        queues: Vec<(WatchedQueue, QueueProbe)>,
+        sink: WatermarkSink,
+    ) -> Self {
+        let running = Arc::new(Mutex::new(true));
+        let states = queues
+            .into_iter()
+            .map(|(queue, probe)| QueueState {
+                queue,
+                probe,
+                high: false,
+            })
+            .collect();
+
+        let running_clone = running.clone();
+        std::thread::spawn(move || {
+            watch_loop(running_clone, config, states, sink);
+        });
+
+        Self { running }
+    }
+
+    /// Stop polling
+    pub fn stop(&self) {
+        *self.running.lock() = false;
+    }
+}
+
+impl Drop for QueueWatch {
+    fn drop(&mut self) {
+        *self.running.lock() = false;
+    }
+}
+
+fn watch_loop(
+    running: Arc<Mutex<bool>>,
+    config: QueueWatermarkConfig,
+    mut states: Vec<QueueState>,
+    sink: WatermarkSink,
+) {
+    while *running.lock() {
+        for state in &mut states {
+            if let Some((event, payload)) = state.poll(&config) {
+                println!(
+                    "{}: {:?} queue {}% full",
+                    event, payload.queue, payload.fill_percent
+                );
+                sink(event, payload);
+            }
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossbeam_channel::bounded;
+
+    #[test]
+    fn test_watermark_hysteresis() {
+        let (sender, receiver) = bounded::<u8>(4);
+        let mut state = QueueState {
+            queue: WatchedQueue::Audio,
+            probe: channel_probe(receiver.clone()),
+            high: false,
+        };
+        let config = QueueWatermarkConfig {
+            high_percent: 75,
+            low_percent: 25,
+        };
+
+        sender.send(0).unwrap();
+        sender.send(0).unwrap();
+        assert_eq!(state.poll(&config), None);
+
+        sender.send(0).unwrap();
+        let (event, payload) = state.poll(&config).unwrap();
+        assert_eq!(event, HIGH_WATERMARK_EVENT);
+        assert_eq!((payload.len, payload.capacity, payload.fill_percent), (3, 4, 75));
+        // Still high: no repeat
+        assert_eq!(state.poll(&config), None);
+
+        // Halfway down is not yet recovered
+        receiver.recv().unwrap();
+        assert_eq!(state.poll(&config), None);
+        receiver.recv().unwrap();
+        assert_eq!(state.poll(&config).unwrap().0, RECOVERED_EVENT);
+
+        assert!(QueueWatermarkConfig { high_percent: 50, low_percent: 50 }.validate().is_err());
+        assert!(QueueWatermarkConfig::default().validate().is_ok());
+    }
+}
@@ -1,11 +1,44 @@
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use parking_lot::{Mutex, RwLock};
 use tauri::command;
 use thiserror::Error;
 
+use crate::captions::CaptionsConfig;
+use crate::capture_dump::CaptureDumpConfig;
+use crate::chroma_key::ChromaKeyConfig;
+use crate::compositor::{Layer, LayerUpdate};
+use crate::cursor::CursorHighlightConfig;
+use crate::devices::list_webcams;
+use crate::dual_mic::DualMicConfig;
+use crate::frame_compression::FrameCompression;
+use crate::frame_guard::FrameRejections;
 use crate::manager::RecordingManager;
+use crate::output_check::OutputVerification;
+use crate::audio::{AudioBufferConfig, AudioLatency, MicrophoneState};
+use crate::audio_mixer::MixerStats;
+use crate::pipeline_stats::PipelineStats;
+use crate::visualizer::VisualizerConfig;
+use crate::webcam::WebcamState;
+use crate::metering::{AudioLevels, MeteringConfig};
+use crate::noise_gate::NoiseGateConfig;
+use crate::monitor::MonitorConfig;
+use crate::queue_watch::QueueWatermarkConfig;
+use crate::capture_watchdog::CaptureWatchdogConfig;
+use crate::quality_governor::AdaptiveQualityConfig;
+use crate::queue_memory::QueueMemoryConfig;
+use crate::sidecar_wav::SidecarWavConfig;
+use crate::synthetic_sources::SyntheticSourcesConfig;
+use crate::markers::{validate_label, Marker};
+use crate::mastering::MasteringConfig;
+use crate::silence::SilenceConfig;
+use crate::ndi::NdiConfig;
+use crate::streaming::{HlsConfig, IcecastConfig, MonitorJoin, StreamingConfig, WebRtcConfig};
+use crate::virtual_camera::{is_virtual_camera_available, VirtualCameraConfig};
+use crate::text_overlay::{TextOverlayConfig, TimerOverlayConfig};
+use crate::thumbnail::thumbnail_path;
+use crate::ExternalRecorderState;
 use crate::system_audio::is_system_audio_available;
 
 /// Position for picture-in-picture webcam overlay
@@ -19,6 +52,83 @@ pub enum PipPosition {
     BottomLeft,
 }
 
+/// How the webcam picture fills the PiP area (4:3, sized by `webcam_size`)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PipFit {
+    /// The whole picture at its own aspect ratio, as large as the area allows
+    #[default]
+    Contain,
+    /// Fill the area, cropping the picture's edges evenly
+    #[serde(alias = "crop-to-fill")]
+    Cover,
+    /// Fill the area, distorting pictures that aren't 4:3
+    Stretch,
+}
+
+/// Outline of the webcam PiP
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PipShape {
+    #[default]
+    Rectangle,
+    /// Rounded corners, `radius` in output pixels
+    RoundedRect { radius: u32 },
+    /// A circle as tall as the PiP area; the picture is cropped to fill it
+    Circle,
+}
+
+/// Logo or watermark drawn over every recorded frame
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatermarkConfig {
+    /// PNG image, drawn at its own size (scaled down if larger than the output)
+    pub path: PathBuf,
+    /// Corner it is drawn in
+    #[serde(default = "default_watermark_position")]
+    pub position: PipPosition,
+    /// 0.0 - 1.0, on top of the image's own alpha
+    #[serde(default = "default_volume")]
+    pub opacity: f32,
+}
+
+fn default_watermark_position() -> PipPosition {
+    PipPosition::BottomRight
+}
+
+impl WatermarkConfig {
+    /// Check the opacity and that the image is a readable PNG
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.opacity) {
+            return Err(format!("Watermark opacity must be between 0.0 and 1.0, got {}", self.opacity));
+        }
+        if !self.path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("png")) {
+            return Err(format!("Watermark must be a PNG image: {}", self.path.display()));
+        }
+        image::image_dimensions(&self.path)
+            .map(|_| ())
+            .map_err(|e| format!("Failed to read watermark {}: {}", self.path.display(), e))
+    }
+}
+
+/// Check free-form PiP placement: `x`/`y` are the top-left corner and `width`
+/// the PiP width, all as fractions of the output (0-1)
+pub fn validate_pip_placement(x: Option<f32>, y: Option<f32>, width: Option<f32>) -> Result<(), String> {
+    for (name, value) in [("x", x), ("y", y)] {
+        if let Some(value) = value {
+            if !(0.0..=1.0).contains(&value) {
+                return Err(format!("Webcam {} must be between 0.0 and 1.0, got {}", name, value));
+            }
+        }
+    }
+    if let Some(width) = width {
+        if !(width > 0.0 && width <= 1.0) {
+            return Err(format!("Webcam width must be above 0.0 and at most 1.0, got {}", width));
+        }
+    }
+    Ok(())
+}
+
 /// Video quality preset
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, Default)]
 #[serde(rename_all = "lowercase")]
@@ -40,6 +150,15 @@ impl VideoQuality {
         }
     }
 
+    /// Get the CRF value for VP9 encoding (0-63 scale)
+    pub fn vp9_crf(&self) -> u32 {
+        match self {
+            VideoQuality::Low => 40,
+            VideoQuality::Medium => 33,
+            VideoQuality::High => 28,
+        }
+    }
+
     /// Get the bitrate in kbps for encoding
     pub fn video_bitrate(&self) -> u32 {
         match self {
@@ -59,8 +178,374 @@ impl VideoQuality {
     }
 }
 
-/// Output resolution preset for 16:9 aspect ratio
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default)]
+/// x264 presets accepted in `AdvancedQuality::preset`
+pub const X264_PRESETS: [&str; 9] = [
+    "ultrafast", "superfast", "veryfast", "faster", "fast", "medium", "slow", "slower", "veryslow",
+];
+
+/// x264 tunings accepted in `AdvancedQuality::tune`
+pub const X264_TUNES: [&str; 8] = [
+    "zerolatency", "film", "animation", "grain", "stillimage", "fastdecode", "psnr", "ssim",
+];
+
+/// Most software encoder threads that can be asked for
+pub const MAX_ENCODER_THREADS: u32 = 64;
+
+/// Most slices an H.264 frame can be split into
+pub const MAX_ENCODER_SLICES: u32 = 32;
+
+/// Explicit encoder settings; each field set here overrides the `VideoQuality` preset
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AdvancedQuality {
+    /// Constant Rate Factor (0-51 for H.264, 0-63 for VP9)
+    #[serde(default)]
+    pub crf: Option<u32>,
+    /// Target video bitrate in kbps
+    #[serde(default)]
+    pub bitrate_kbps: Option<u32>,
+    /// Peak video bitrate in kbps (VBV cap, must be >= the target)
+    #[serde(default)]
+    pub max_bitrate_kbps: Option<u32>,
+    /// x264 speed preset, e.g. "veryfast" (libx264 only; default "ultrafast")
+    #[serde(default)]
+    pub preset: Option<String>,
+    /// Software encoder threads (libx264 and VP9; default: from the core
+    /// count and frame size)
+    #[serde(default)]
+    pub threads: Option<u32>,
+    /// Slices each H.264 frame is split into for parallel encoding (libx264
+    /// only; default: one per thread with "zerolatency")
+    #[serde(default)]
+    pub slices: Option<u32>,
+    /// x264 tuning, e.g. "film" (libx264 only; default "zerolatency", which
+    /// encodes each frame's slices in parallel without buffering frames)
+    #[serde(default)]
+    pub tune: Option<String>,
+}
+
+impl AdvancedQuality {
+    /// Check ranges for the given output format
+    pub fn validate(&self, format: OutputFormat) -> Result<(), String> {
+        let max_crf = match format {
+            OutputFormat::Mp4 | OutputFormat::Mkv => 51,
+            OutputFormat::Webm => 63,
+        };
+        if let Some(crf) = self.crf {
+            if crf > max_crf {
+                return Err(format!("CRF must be between 0 and {}, got {}", max_crf, crf));
+            }
+        }
+        if self.bitrate_kbps == Some(0) || self.max_bitrate_kbps == Some(0) {
+            return Err("Video bitrate must be greater than 0".to_string());
+        }
+        if let (Some(target), Some(max)) = (self.bitrate_kbps, self.max_bitrate_kbps) {
+            if max < target {
+                return Err(format!(
+                    "Max bitrate ({}kbps) must not be below the target bitrate ({}kbps)",
+                    max, target
+                ));
+            }
+        }
+        if let Some(ref preset) = self.preset {
+            if !X264_PRESETS.contains(&preset.as_str()) {
+                return Err(format!(
+                    "Unknown encoder preset '{}' (expected one of: {})",
+                    preset,
+                    X264_PRESETS.join(", ")
+                ));
+            }
+        }
+        if let Some(threads) = self.threads {
+            if !(1..=MAX_ENCODER_THREADS).contains(&threads) {
+                return Err(format!("Encoder threads must be 1-{}, got {}", MAX_ENCODER_THREADS, threads));
+            }
+        }
+        if let Some(slices) = self.slices {
+            if !(1..=MAX_ENCODER_SLICES).contains(&slices) {
+                return Err(format!("Encoder slices must be 1-{}, got {}", MAX_ENCODER_SLICES, slices));
+            }
+        }
+        if let Some(ref tune) = self.tune {
+            if !X264_TUNES.contains(&tune.as_str()) {
+                return Err(format!(
+                    "Unknown encoder tune '{}' (expected one of: {})",
+                    tune,
+                    X264_TUNES.join(", ")
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Output channel layout for recorded audio
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioChannelLayout {
+    Mono,
+    #[default]
+    Stereo,
+    /// Two mic inputs kept as independent left/right ears: never downmixed,
+    /// duplicated from one input, or resampled
+    Binaural,
+}
+
+impl AudioChannelLayout {
+    /// Get the number of output channels for this layout
+    pub fn channel_count(&self) -> u16 {
+        match self {
+            AudioChannelLayout::Mono => 1,
+            AudioChannelLayout::Stereo => 2,
+            AudioChannelLayout::Binaural => 2,
+        }
+    }
+}
+
+/// Title and language tags for the recorded audio track
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackMetadata {
+    /// Track name shown by players and editors (default describes the sources)
+    #[serde(default)]
+    pub title: Option<String>,
+    /// ISO 639-2 language code, e.g. "eng" or "jpn" (default undetermined)
+    #[serde(default)]
+    pub language: Option<String>,
+}
+
+impl TrackMetadata {
+    /// Check the language tag is one MP4 and WebM can store
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(ref language) = self.language {
+            if language.len() != 3 || !language.bytes().all(|b| b.is_ascii_lowercase()) {
+                return Err(format!(
+                    "Track language must be a three-letter ISO 639-2 code like \"eng\", got \"{}\"",
+                    language
+                ));
+            }
+        }
+        Ok(())
+    }
+    
+    /// Fill in a title describing the recorded sources if none is set
+    pub fn with_default_title(&self, capture_mic: bool, capture_system: bool, layout: AudioChannelLayout) -> Self {
+        let title = self.title.clone().unwrap_or_else(|| {
+            let sources = match (capture_mic, capture_system) {
+                (true, true) => "Microphone + System Audio",
+                (true, false) => "Microphone",
+                (false, true) => "System Audio",
+                (false, false) => "Silence",
+            };
+            match layout {
+                AudioChannelLayout::Binaural => format!("{} (Binaural L/R)", sources),
+                _ => sources.to_string(),
+            }
+        });
+        Self {
+            title: Some(title),
+            language: self.language.clone(),
+        }
+    }
+}
+
+/// Tags written into the output file, shown by media players
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct FileMetadata {
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub artist: Option<String>,
+    #[serde(default)]
+    pub comment: Option<String>,
+    /// Free-form, e.g. "2024-05-01"
+    #[serde(default)]
+    pub date: Option<String>,
+}
+
+impl FileMetadata {
+    /// Tags that are set, by FFmpeg key
+    pub fn entries(&self) -> Vec<(&'static str, &str)> {
+        [
+            ("title", &self.title),
+            ("artist", &self.artist),
+            ("comment", &self.comment),
+            ("date", &self.date),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| value.as_deref().map(|value| (key, value)))
+        .collect()
+    }
+    
+    /// Check every tag can be stored
+    pub fn validate(&self) -> Result<(), String> {
+        for (key, value) in self.entries() {
+            if value.contains('\0') {
+                return Err(format!("Metadata {} must not contain NUL characters", key));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Output container and codecs
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// MP4 with H.264 video and AAC audio (faststart: index at the front)
+    #[default]
+    Mp4,
+    /// WebM with VP9 video and Opus audio (requires 48kHz audio)
+    Webm,
+    /// Matroska with H.264 video and AAC, FLAC or PCM audio
+    Mkv,
+}
+
+impl OutputFormat {
+    /// File extension for generated output paths
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Mp4 => "mp4",
+            OutputFormat::Webm => "webm",
+            OutputFormat::Mkv => "mkv",
+        }
+    }
+
+    /// Check that the audio codec can be carried by this format
+    pub fn validate_audio_codec(&self, codec: Option<AudioCodec>) -> Result<(), String> {
+        match (self, codec) {
+            (_, None) | (OutputFormat::Mp4, Some(AudioCodec::Aac)) | (OutputFormat::Mkv, Some(_)) => Ok(()),
+            (OutputFormat::Webm, Some(codec)) => Err(format!(
+                "WebM recordings always use Opus audio ({:?} requested)",
+                codec
+            )),
+            (OutputFormat::Mp4, Some(codec)) => Err(format!(
+                "{:?} audio needs the MKV output format",
+                codec
+            )),
+        }
+    }
+
+    /// Check that the audio sample rate can be carried by this format
+    pub fn validate_sample_rate(&self, sample_rate: u32) -> Result<(), String> {
+        if *self == OutputFormat::Webm && sample_rate != 48000 {
+            return Err(format!(
+                "WebM recordings use Opus audio, which requires 48000Hz (got {}Hz)",
+                sample_rate
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check that the audio codec, or this format's default, supports the
+    /// sample rate; higher rates need FLAC or PCM
+    pub fn validate_codec_sample_rate(&self, codec: Option<AudioCodec>, sample_rate: u32) -> Result<(), String> {
+        let aac = match codec {
+            Some(codec) => codec == AudioCodec::Aac,
+            None => *self != OutputFormat::Webm,
+        };
+        if aac && sample_rate > MAX_AAC_SAMPLE_RATE {
+            return Err(format!(
+                "AAC audio supports up to {}Hz (got {}Hz); record FLAC or PCM audio to MKV for higher rates",
+                MAX_AAC_SAMPLE_RATE, sample_rate
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Highest sample rate FFmpeg's AAC encoder accepts
+pub const MAX_AAC_SAMPLE_RATE: u32 = 96000;
+
+/// Audio codec for H.264 outputs (None in a config means the format's default:
+/// AAC for MP4 and MKV, Opus for WebM)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioCodec {
+    /// AAC at `audio_bitrate_kbps` or the quality preset's bitrate
+    Aac,
+    /// 24-bit lossless FLAC (MKV only)
+    Flac,
+    /// Uncompressed PCM at the configured bit depth (MKV only)
+    Pcm,
+}
+
+/// Accepted AAC/Opus bitrates in kbps
+pub const AUDIO_BITRATE_RANGE_KBPS: std::ops::RangeInclusive<u32> = 32..=512;
+
+/// Check an explicit audio bitrate is in range and the codec uses one
+pub fn validate_audio_bitrate(kbps: u32, codec: Option<AudioCodec>) -> Result<(), String> {
+    if matches!(codec, Some(AudioCodec::Flac | AudioCodec::Pcm)) {
+        return Err("Audio bitrate doesn't apply to lossless FLAC or PCM audio".to_string());
+    }
+    if !AUDIO_BITRATE_RANGE_KBPS.contains(&kbps) {
+        return Err(format!(
+            "Audio bitrate must be {}-{} kbps, got {}",
+            AUDIO_BITRATE_RANGE_KBPS.start(),
+            AUDIO_BITRATE_RANGE_KBPS.end(),
+            kbps
+        ));
+    }
+    Ok(())
+}
+
+/// Sample format of PCM audio and sidecar WAV files (None in a config means
+/// 32-bit float, the mixer's own format; FLAC is always 24-bit)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioBitDepth {
+    /// 24-bit integer
+    Int24,
+    /// 32-bit float
+    Float32,
+}
+
+impl AudioBitDepth {
+    /// Bits per sample
+    pub fn bits(&self) -> u32 {
+        match self {
+            AudioBitDepth::Int24 => 24,
+            AudioBitDepth::Float32 => 32,
+        }
+    }
+
+    /// Check that the audio codec can store this bit depth
+    pub fn validate_for(&self, codec: Option<AudioCodec>) -> Result<(), String> {
+        if *self == AudioBitDepth::Float32 && codec == Some(AudioCodec::Flac) {
+            return Err("FLAC stores integer samples; use 24-bit or PCM audio".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Pixel format requested from native screen capture
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CapturePixelFormat {
+    /// 32-bit BGRA
+    #[default]
+    Bgra,
+    /// 4:2:0 biplanar YUV (half the bandwidth of BGRA; macOS only, others fall back to BGRA)
+    Nv12,
+}
+
+/// Where an external recording's video frames come from
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ExternalFrameSource {
+    /// Frames sent by the frontend over IPC
+    #[default]
+    Frontend,
+    /// Native capture of the screen area the app's webview covers, followed
+    /// as the window moves and resizes (no frames over IPC). This is screen
+    /// capture, not window capture: windows in front of the app are recorded
+    /// too.
+    WindowArea,
+}
+
+/// Output resolution preset for 16:9 aspect ratio, or the captured size
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum OutputResolution {
     /// 1280x720 (720p)
@@ -72,19 +557,34 @@ pub enum OutputResolution {
     Qhd1440,
     /// 3840x2160 (4K)
     Uhd4k,
+    /// Size of the captured display, rounded down to even (no scaling)
+    MatchSource,
 }
 
 impl OutputResolution {
     /// Get the width and height for this resolution
+    ///
+    /// `MatchSource` reports 1080p here; use `resolve` when the source is known.
     pub fn dimensions(&self) -> (u32, u32) {
         match self {
             OutputResolution::Hd720 => (1280, 720),
-            OutputResolution::Hd1080 => (1920, 1080),
+            OutputResolution::Hd1080 | OutputResolution::MatchSource => (1920, 1080),
             OutputResolution::Qhd1440 => (2560, 1440),
             OutputResolution::Uhd4k => (3840, 2160),
         }
     }
     
+    /// Get the output size for a capture source of `source` dimensions, if any
+    pub fn resolve(&self, source: Option<(u32, u32)>) -> (u32, u32) {
+        match (self, source) {
+            // H.264 and VP9 need even dimensions for 4:2:0 chroma
+            (OutputResolution::MatchSource, Some((width, height))) => {
+                ((width & !1).max(2), (height & !1).max(2))
+            }
+            _ => self.dimensions(),
+        }
+    }
+    
     /// Get just the width
     pub fn width(&self) -> u32 {
         self.dimensions().0
@@ -112,6 +612,51 @@ pub struct RecordingConfig {
     /// Size of webcam as percentage of screen (10-50)
     pub webcam_size: u32,
     
+    /// Left edge of the PiP as a fraction of the output width (0-1), instead
+    /// of the `webcam_position` corner; kept inside the frame
+    #[serde(default)]
+    pub webcam_x: Option<f32>,
+    
+    /// Top edge of the PiP as a fraction of the output height (0-1)
+    #[serde(default)]
+    pub webcam_y: Option<f32>,
+    
+    /// PiP width as a fraction of the output width, instead of `webcam_size`
+    #[serde(default)]
+    pub webcam_width: Option<f32>,
+    
+    /// How the webcam picture fills the PiP (default: contain, keeping its aspect ratio)
+    #[serde(default)]
+    pub webcam_fit: PipFit,
+    
+    /// Outline of the PiP (default: rectangle)
+    #[serde(default)]
+    pub webcam_shape: PipShape,
+    
+    /// Green-screen keying of the webcam, so only the subject is overlaid
+    #[serde(default)]
+    pub webcam_chroma_key: ChromaKeyConfig,
+    
+    /// Flip the webcam horizontally, like the selfie view of a camera app
+    #[serde(default)]
+    pub mirror_webcam: bool,
+    
+    /// Logo burned into every frame
+    #[serde(default)]
+    pub watermark: Option<WatermarkConfig>,
+    
+    /// Text burned into every frame, e.g. the session title or a timestamp
+    #[serde(default)]
+    pub text_overlays: Vec<TextOverlayConfig>,
+    
+    /// Elapsed-time readout burned into every frame
+    #[serde(default)]
+    pub timer_overlay: TimerOverlayConfig,
+    
+    /// Draw a ring around the mouse cursor and ripples for clicks
+    #[serde(default)]
+    pub cursor_highlight: CursorHighlightConfig,
+    
     /// Whether to capture microphone audio
     pub capture_mic: bool,
     
@@ -121,15 +666,200 @@ pub struct RecordingConfig {
     /// Output file path (optional, will generate if not provided)
     pub output_path: Option<PathBuf>,
     
+    /// Directory on another drive to continue in if writes to the output fail
+    #[serde(default)]
+    pub failover_dir: Option<PathBuf>,
+    
+    /// Append session: recordings with the same ID continue one file
+    /// (`session_<id>.<ext>` unless `output_path` is set)
+    #[serde(default)]
+    pub append_session_id: Option<String>,
+    
     /// Video quality preset
     pub video_quality: VideoQuality,
     
+    /// Explicit CRF/bitrate/preset overriding `video_quality` where set
+    #[serde(default)]
+    pub advanced_quality: Option<AdvancedQuality>,
+    
     /// Target frame rate (default 30)
     pub frame_rate: Option<u32>,
     
-    /// Output resolution (default 1080p, always 16:9)
+    /// Output resolution (default 1080p; a 16:9 preset or the captured display size)
     #[serde(default)]
     pub output_resolution: OutputResolution,
+    
+    /// Container and codecs (default MP4 with H.264/AAC)
+    #[serde(default)]
+    pub output_format: OutputFormat,
+    
+    /// Audio codec (default: AAC, or Opus for WebM)
+    #[serde(default)]
+    pub audio_codec: Option<AudioCodec>,
+    
+    /// AAC/Opus bitrate in kbps (default: the video quality preset's)
+    #[serde(default)]
+    pub audio_bitrate_kbps: Option<u32>,
+    
+    /// Bit depth of PCM audio and the sidecar WAV (default 32-bit float)
+    #[serde(default)]
+    pub audio_bit_depth: Option<AudioBitDepth>,
+    
+    /// Normalize the finished file to this integrated loudness (EBU R128, LUFS)
+    #[serde(default)]
+    pub normalize_lufs: Option<f32>,
+    
+    /// Silence markers or trimming when the recording is finalized
+    #[serde(default)]
+    pub silence: SilenceConfig,
+    
+    /// High-pass, de-esser and normalization when the recording is finalized
+    #[serde(default)]
+    pub mastering: MasteringConfig,
+    
+    /// Screen capture pixel format (NV12 is ignored while the webcam is captured)
+    #[serde(default)]
+    pub capture_pixel_format: CapturePixelFormat,
+    
+    /// Output audio channel layout (default stereo)
+    #[serde(default)]
+    pub audio_channels: AudioChannelLayout,
+    
+    /// Title and language of the audio track
+    #[serde(default)]
+    pub audio_track: TrackMetadata,
+    
+    /// Title, artist, comment and date tags for the file
+    #[serde(default)]
+    pub metadata: FileMetadata,
+    
+    /// Microphone input channels to record (zero-based, e.g. [2, 3] for inputs 3+4).
+    /// Defaults to the first one or two channels of the device.
+    #[serde(default)]
+    pub mic_channel_map: Option<Vec<u16>>,
+    
+    /// Audio sample rate for the whole mixer/encoder path (default 48kHz)
+    #[serde(default = "default_audio_sample_rate")]
+    pub audio_sample_rate: u32,
+    
+    /// Device buffer and mixer chunk sizes (latency vs dropout resistance)
+    #[serde(default)]
+    pub audio_buffer: AudioBufferConfig,
+    
+    /// Microphone ID from `get_available_devices` (default: last used, then system default)
+    #[serde(default)]
+    pub mic_device_id: Option<String>,
+    
+    /// Capture each ear from its own microphone (binaural only; replaces
+    /// `mic_device_id`)
+    #[serde(default)]
+    pub dual_mic: Option<DualMicConfig>,
+    
+    /// Webcam ID from `get_available_devices` (default: last used, then first camera)
+    #[serde(default)]
+    pub webcam_device_id: Option<String>,
+    
+    /// Screen ID from `get_available_devices` (default: last used, then primary display)
+    #[serde(default)]
+    pub screen_device_id: Option<String>,
+    
+    /// Microphone volume (0.0 - 2.0, default 1.0)
+    #[serde(default = "default_volume")]
+    pub mic_volume: f32,
+    
+    /// System audio volume (0.0 - 2.0, default 1.0)
+    #[serde(default = "default_volume")]
+    pub system_volume: f32,
+    
+    /// Spectral gate lowering room tone between whispers on the microphone
+    #[serde(default)]
+    pub noise_gate: NoiseGateConfig,
+    
+    /// Play the microphone back to an output device while recording
+    #[serde(default)]
+    pub mic_monitor: Option<MonitorConfig>,
+    
+    /// Emit waveform and spectrum events for the mix while recording
+    #[serde(default)]
+    pub audio_visualizer: Option<VisualizerConfig>,
+    
+    /// Live captions from speech recognition on the microphone
+    #[serde(default)]
+    pub captions: CaptionsConfig,
+    
+    /// Also write the mix or the microphone to a WAV file next to the video
+    #[serde(default)]
+    pub sidecar_wav: SidecarWavConfig,
+    
+    /// Also write HLS segments and a playlist, for a live preview
+    #[serde(default)]
+    pub hls: Option<HlsConfig>,
+    
+    /// Also stream the mixed audio alone to an Icecast or Shoutcast server
+    #[serde(default)]
+    pub icecast: Option<IcecastConfig>,
+    
+    /// Also publish the output as an NDI source on the local network
+    #[serde(default)]
+    pub ndi: Option<NdiConfig>,
+    
+    /// Also publish the output as a virtual camera for video-call apps
+    #[serde(default)]
+    pub virtual_camera: Option<VirtualCameraConfig>,
+    
+    /// Stream to an RTMP or SRT server from the start, encoded separately at
+    /// the stream's own bitrate
+    #[serde(default)]
+    pub stream: Option<StreamingConfig>,
+    
+    /// Queue fill levels that trigger the `queue-high-watermark` and
+    /// `queue-recovered` events
+    #[serde(default)]
+    pub queue_watermarks: QueueWatermarkConfig,
+    
+    /// Step CRF, frame rate and resolution down when the machine can't keep up
+    #[serde(default)]
+    pub adaptive_quality: AdaptiveQualityConfig,
+    
+    /// Memory the frame and audio queues may hold before frames are dropped
+    #[serde(default)]
+    pub queue_memory: QueueMemoryConfig,
+    
+    /// Restart the screen capture or microphone when it stops delivering
+    #[serde(default)]
+    pub capture_watchdog: CaptureWatchdogConfig,
+    
+    /// Write raw captured frames and audio to `<recording>.dump/` for replay
+    #[serde(default)]
+    pub capture_dump: CaptureDumpConfig,
+    
+    /// Record a generated test pattern and tones instead of the devices
+    #[serde(default)]
+    pub synthetic_sources: SyntheticSourcesConfig,
+    
+    /// Prefer a hardware H.264 encoder (falls back to libx264)
+    #[serde(default = "default_hardware_encoding")]
+    pub hardware_encoding: bool,
+    
+    /// Scale and blend frames on the GPU when available (falls back to the CPU)
+    #[serde(default = "default_gpu_compositing")]
+    pub gpu_compositing: bool,
+}
+
+fn default_audio_sample_rate() -> u32 {
+    48000
+}
+
+fn default_volume() -> f32 {
+    1.0
+}
+
+fn default_hardware_encoding() -> bool {
+    true
+}
+
+fn default_gpu_compositing() -> bool {
+    true
 }
 
 impl Default for RecordingConfig {
@@ -139,12 +869,64 @@ impl Default for RecordingConfig {
             capture_webcam: false,
             webcam_position: PipPosition::default(),
             webcam_size: 25,
+            webcam_x: None,
+            webcam_y: None,
+            webcam_width: None,
+            webcam_fit: PipFit::default(),
+            webcam_shape: PipShape::default(),
+            webcam_chroma_key: ChromaKeyConfig::default(),
+            mirror_webcam: false,
+            watermark: None,
+            text_overlays: Vec::new(),
+            timer_overlay: TimerOverlayConfig::default(),
+            cursor_highlight: CursorHighlightConfig::default(),
             capture_mic: true,
             capture_system_audio: false,
             output_path: None,
+            failover_dir: None,
+            append_session_id: None,
             video_quality: VideoQuality::default(),
+            advanced_quality: None,
             frame_rate: Some(30),
             output_resolution: OutputResolution::default(),
+            output_format: OutputFormat::default(),
+            audio_codec: None,
+            audio_bitrate_kbps: None,
+            audio_bit_depth: None,
+            normalize_lufs: None,
+            silence: SilenceConfig::default(),
+            mastering: MasteringConfig::default(),
+            capture_pixel_format: CapturePixelFormat::default(),
+            audio_channels: AudioChannelLayout::default(),
+            audio_track: TrackMetadata::default(),
+            metadata: FileMetadata::default(),
+            mic_channel_map: None,
+            audio_sample_rate: default_audio_sample_rate(),
+            audio_buffer: AudioBufferConfig::default(),
+            mic_device_id: None,
+            dual_mic: None,
+            webcam_device_id: None,
+            screen_device_id: None,
+            mic_volume: default_volume(),
+            system_volume: default_volume(),
+            noise_gate: NoiseGateConfig::default(),
+            mic_monitor: None,
+            audio_visualizer: None,
+            captions: CaptionsConfig::default(),
+            sidecar_wav: SidecarWavConfig::default(),
+            hls: None,
+            icecast: None,
+            ndi: None,
+            virtual_camera: None,
+            stream: None,
+            queue_watermarks: QueueWatermarkConfig::default(),
+            adaptive_quality: AdaptiveQualityConfig::default(),
+            queue_memory: QueueMemoryConfig::default(),
+            capture_watchdog: CaptureWatchdogConfig::default(),
+            capture_dump: CaptureDumpConfig::default(),
+            synthetic_sources: SyntheticSourcesConfig::default(),
+            hardware_encoding: true,
+            gpu_compositing: default_gpu_compositing(),
         }
     }
 }
@@ -162,21 +944,156 @@ pub struct ExternalRecordingConfig {
     /// Output file path (optional, will generate if not provided)
     pub output_path: Option<PathBuf>,
     
+    /// Directory on another drive to continue in if writes to the output fail
+    #[serde(default)]
+    pub failover_dir: Option<PathBuf>,
+    
+    /// Append session: recordings with the same ID continue one file
+    /// (`session_<id>.<ext>` unless `output_path` is set)
+    #[serde(default)]
+    pub append_session_id: Option<String>,
+    
     /// Video quality preset
     pub video_quality: VideoQuality,
     
-    /// Target frame rate (default 30)
-    pub frame_rate: Option<u32>,
+    /// Explicit CRF/bitrate/preset overriding `video_quality` where set
+    #[serde(default)]
+    pub advanced_quality: Option<AdvancedQuality>,
+    
+    /// Target frame rate (default 30)
+    pub frame_rate: Option<u32>,
+    
+    /// Output resolution (default 1080p, always 16:9)
+    #[serde(default)]
+    pub output_resolution: OutputResolution,
+    
+    /// Container and codecs (default MP4 with H.264/AAC)
+    #[serde(default)]
+    pub output_format: OutputFormat,
+    
+    /// Output width in pixels (must match frames sent from frontend)
+    pub output_width: u32,
+    
+    /// Output height in pixels (must match frames sent from frontend)
+    pub output_height: u32,
+    
+    /// Frontend-sent frames or native capture of the webview (default frontend)
+    #[serde(default)]
+    pub frame_source: ExternalFrameSource,
+    
+    /// Compression the frontend applies to base64 frames (default none)
+    #[serde(default)]
+    pub frame_compression: FrameCompression,
+    
+    /// Audio codec (default: AAC, or Opus for WebM)
+    #[serde(default)]
+    pub audio_codec: Option<AudioCodec>,
+    
+    /// AAC/Opus bitrate in kbps (default: the video quality preset's)
+    #[serde(default)]
+    pub audio_bitrate_kbps: Option<u32>,
+    
+    /// Bit depth of PCM audio and the sidecar WAV (default 32-bit float)
+    #[serde(default)]
+    pub audio_bit_depth: Option<AudioBitDepth>,
+    
+    /// Normalize the finished file to this integrated loudness (EBU R128, LUFS)
+    #[serde(default)]
+    pub normalize_lufs: Option<f32>,
+    
+    /// Silence markers or trimming when the recording is finalized
+    #[serde(default)]
+    pub silence: SilenceConfig,
+    
+    /// High-pass, de-esser and normalization when the recording is finalized
+    #[serde(default)]
+    pub mastering: MasteringConfig,
+    
+    /// Output audio channel layout (default stereo)
+    #[serde(default)]
+    pub audio_channels: AudioChannelLayout,
+    
+    /// Title and language of the audio track
+    #[serde(default)]
+    pub audio_track: TrackMetadata,
+    
+    /// Title, artist, comment and date tags for the file
+    #[serde(default)]
+    pub metadata: FileMetadata,
+    
+    /// Microphone input channels to record (zero-based)
+    #[serde(default)]
+    pub mic_channel_map: Option<Vec<u16>>,
+    
+    /// Audio sample rate for the whole mixer/encoder path (default 48kHz)
+    #[serde(default = "default_audio_sample_rate")]
+    pub audio_sample_rate: u32,
+    
+    /// Device buffer and mixer chunk sizes (latency vs dropout resistance)
+    #[serde(default)]
+    pub audio_buffer: AudioBufferConfig,
+    
+    /// Microphone ID from `get_available_devices` (default: last used, then system default)
+    #[serde(default)]
+    pub mic_device_id: Option<String>,
+    
+    /// Microphone volume (0.0 - 2.0, default 1.0)
+    #[serde(default = "default_volume")]
+    pub mic_volume: f32,
+    
+    /// System audio volume (0.0 - 2.0, default 1.0)
+    #[serde(default = "default_volume")]
+    pub system_volume: f32,
+    
+    /// Spectral gate lowering room tone between whispers on the microphone
+    #[serde(default)]
+    pub noise_gate: NoiseGateConfig,
+    
+    /// Play the microphone back to an output device while recording
+    #[serde(default)]
+    pub mic_monitor: Option<MonitorConfig>,
+    
+    /// Emit waveform and spectrum events for the mix while recording
+    #[serde(default)]
+    pub audio_visualizer: Option<VisualizerConfig>,
+    
+    /// Live captions from speech recognition on the microphone
+    #[serde(default)]
+    pub captions: CaptionsConfig,
+    
+    /// Also write the mix or the microphone to a WAV file next to the video
+    #[serde(default)]
+    pub sidecar_wav: SidecarWavConfig,
+    
+    /// Also write HLS segments and a playlist, for a live preview
+    #[serde(default)]
+    pub hls: Option<HlsConfig>,
+    
+    /// Also stream the mixed audio alone to an Icecast or Shoutcast server
+    #[serde(default)]
+    pub icecast: Option<IcecastConfig>,
+    
+    /// Also publish the output as an NDI source on the local network
+    #[serde(default)]
+    pub ndi: Option<NdiConfig>,
     
-    /// Output resolution (default 1080p, always 16:9)
+    /// Queue fill levels that trigger the `queue-high-watermark` and
+    /// `queue-recovered` events
     #[serde(default)]
-    pub output_resolution: OutputResolution,
+    pub queue_watermarks: QueueWatermarkConfig,
     
-    /// Output width in pixels (must match frames sent from frontend)
-    pub output_width: u32,
+    /// Step CRF and frame rate down when the machine can't keep up (frames
+    /// arrive at the frontend's size, so resolution isn't stepped)
+    #[serde(default)]
+    pub adaptive_quality: AdaptiveQualityConfig,
     
-    /// Output height in pixels (must match frames sent from frontend)
-    pub output_height: u32,
+    /// Memory the frame and audio queues may hold before frames are dropped
+    #[serde(default)]
+    pub queue_memory: QueueMemoryConfig,
+    
+    /// Prefer a hardware H.264 encoder (falls back to libx264)
+    #[serde(default = "default_hardware_encoding")]
+    pub hardware_encoding: bool,
 }
 
 impl Default for ExternalRecordingConfig {
@@ -185,12 +1102,114 @@ impl Default for ExternalRecordingConfig {
             capture_mic: true,
             capture_system_audio: false,
             output_path: None,
+            failover_dir: None,
+            append_session_id: None,
             video_quality: VideoQuality::default(),
+            advanced_quality: None,
             frame_rate: Some(30),
             output_resolution: OutputResolution::default(),
+            output_format: OutputFormat::default(),
             output_width: 1920,
             output_height: 1080,
+            frame_source: ExternalFrameSource::default(),
+            frame_compression: FrameCompression::None,
+            audio_codec: None,
+            audio_bitrate_kbps: None,
+            audio_bit_depth: None,
+            normalize_lufs: None,
+            silence: SilenceConfig::default(),
+            mastering: MasteringConfig::default(),
+            audio_channels: AudioChannelLayout::default(),
+            audio_track: TrackMetadata::default(),
+            metadata: FileMetadata::default(),
+            mic_channel_map: None,
+            audio_sample_rate: default_audio_sample_rate(),
+            audio_buffer: AudioBufferConfig::default(),
+            mic_device_id: None,
+            mic_volume: default_volume(),
+            system_volume: default_volume(),
+            noise_gate: NoiseGateConfig::default(),
+            mic_monitor: None,
+            audio_visualizer: None,
+            captions: CaptionsConfig::default(),
+            sidecar_wav: SidecarWavConfig::default(),
+            hls: None,
+            icecast: None,
+            ndi: None,
+            queue_watermarks: QueueWatermarkConfig::default(),
+            adaptive_quality: AdaptiveQualityConfig::default(),
+            queue_memory: QueueMemoryConfig::default(),
+            hardware_encoding: true,
+        }
+    }
+}
+
+/// Settings that can be changed while recording (`update_active_config`)
+///
+/// All fields are optional; only the ones present are applied. Any other
+/// `RecordingConfig` field changes the pipeline structure and is rejected.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActiveConfigUpdate {
+    pub mic_volume: Option<f32>,
+    pub system_volume: Option<f32>,
+    /// Show or hide the webcam overlay
+    pub webcam_visible: Option<bool>,
+    /// Snap the PiP to a corner, dropping any free-form `webcam_x`/`webcam_y`
+    pub webcam_position: Option<PipPosition>,
+    /// Size the PiP by percentage, dropping any free-form `webcam_width`
+    pub webcam_size: Option<u32>,
+    pub webcam_x: Option<f32>,
+    pub webcam_y: Option<f32>,
+    pub webcam_width: Option<f32>,
+    pub webcam_fit: Option<PipFit>,
+    pub webcam_shape: Option<PipShape>,
+    pub webcam_chroma_key: Option<ChromaKeyConfig>,
+    pub mirror_webcam: Option<bool>,
+    /// Show or hide the text overlays, timer and watermark together
+    pub overlays_visible: Option<bool>,
+    /// Show or hide each of `text_overlays`, in their order
+    pub text_overlays_visible: Option<Vec<bool>>,
+    pub timer_visible: Option<bool>,
+    pub watermark_visible: Option<bool>,
+    /// Replace the cursor highlight settings (`enabled: false` hides it)
+    pub cursor_highlight: Option<CursorHighlightConfig>,
+    /// Fields that cannot be changed mid-recording
+    #[serde(flatten)]
+    pub structural: std::collections::BTreeMap<String, serde_json::Value>,
+}
+
+impl ActiveConfigUpdate {
+    /// Reject structural fields and out-of-range values
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.structural.is_empty() {
+            let fields: Vec<&str> = self.structural.keys().map(String::as_str).collect();
+            return Err(format!(
+                "Cannot change {} while recording; stop the recording first",
+                fields.join(", ")
+            ));
+        }
+        
+        for volume in [self.mic_volume, self.system_volume].into_iter().flatten() {
+            if !(0.0..=2.0).contains(&volume) {
+                return Err(format!("Volume must be between 0.0 and 2.0, got {}", volume));
+            }
+        }
+        
+        if let Some(size) = self.webcam_size {
+            if !(10..=50).contains(&size) {
+                return Err(format!("Webcam size must be between 10 and 50%, got {}", size));
+            }
+        }
+        validate_pip_placement(self.webcam_x, self.webcam_y, self.webcam_width)?;
+        if let Some(ref chroma_key) = self.webcam_chroma_key {
+            chroma_key.validate()?;
         }
+        if let Some(ref highlight) = self.cursor_highlight {
+            highlight.validate()?;
+        }
+        
+        Ok(())
     }
 }
 
@@ -212,6 +1231,31 @@ pub struct RecordingStatus {
     
     /// Any error message
     pub error: Option<String>,
+    
+    /// Frames rejected by the external recorder
+    #[serde(default)]
+    pub frame_rejections: FrameRejections,
+    
+    /// Notable events during the recording (e.g. failover), oldest first
+    #[serde(default)]
+    pub events: Vec<RecordingEvent>,
+    
+    /// Whether the recording is also being streamed
+    #[serde(default)]
+    pub streaming: bool,
+    
+    /// Bytes of frames and audio waiting in the pipeline's queues
+    #[serde(default)]
+    pub queue_memory_bytes: u64,
+    
+    /// Whether the webcam is delivering frames (None without a webcam)
+    #[serde(default)]
+    pub webcam: Option<WebcamState>,
+    
+    /// Whether the microphone stream is open, and on which device (None
+    /// without a microphone)
+    #[serde(default)]
+    pub microphone: Option<MicrophoneState>,
 }
 
 impl Default for RecordingStatus {
@@ -222,10 +1266,64 @@ impl Default for RecordingStatus {
             frame_count: 0,
             output_path: None,
             error: None,
+            frame_rejections: FrameRejections::default(),
+            events: Vec::new(),
+            streaming: false,
+            queue_memory_bytes: 0,
+            webcam: None,
+            microphone: None,
         }
     }
 }
 
+/// Entry in the recording's event log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingEvent {
+    /// Recording time of the event in milliseconds
+    pub at_ms: u64,
+    pub message: String,
+}
+
+/// Check an append session ID can be used in a file name
+pub fn validate_session_id(id: &str) -> Result<(), String> {
+    let valid = !id.is_empty()
+        && id.len() <= 64
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(format!(
+            "Session ID must be 1-64 letters, digits, '-' or '_', got \"{}\"",
+            id
+        ));
+    }
+    Ok(())
+}
+
+/// File name of an append session's recording
+pub fn session_file_name(id: &str, format: OutputFormat) -> String {
+    format!("session_{}.{}", id, format.extension())
+}
+
+/// Where the encoder writes while continuing `session_path`, if the session
+/// already has a recording (the joined file then replaces it)
+pub fn append_part_path(session_path: &Path) -> Option<PathBuf> {
+    if !session_path.exists() || session_path.extension().is_none() {
+        return None;
+    }
+    Some(crate::media_file::work_path(session_path, crate::media_file::suffix::APPENDING))
+}
+
+/// Check that a failover directory exists and is a directory
+pub fn validate_failover_dir(dir: Option<&PathBuf>) -> Result<(), String> {
+    match dir {
+        Some(dir) if !dir.is_dir() => Err(format!(
+            "Failover directory {} does not exist",
+            dir.display()
+        )),
+        _ => Ok(()),
+    }
+}
+
 /// Information about available devices
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -241,10 +1339,102 @@ pub struct DeviceList {
     pub screens: Vec<DeviceInfo>,
     pub webcams: Vec<DeviceInfo>,
     pub microphones: Vec<DeviceInfo>,
+    /// Output devices usable for mic monitoring
+    pub audio_outputs: Vec<DeviceInfo>,
     pub has_system_audio: bool,
+    /// Whether `virtual_camera` can be used on this platform
+    pub has_virtual_camera: bool,
+}
+
+/// Kind of capture device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeviceKind {
+    Microphone,
+    Webcam,
+    Screen,
+}
+
+impl DeviceKind {
+    /// Human-readable name for notices
+    pub fn label(&self) -> &'static str {
+        match self {
+            DeviceKind::Microphone => "Microphone",
+            DeviceKind::Webcam => "Webcam",
+            DeviceKind::Screen => "Screen",
+        }
+    }
+}
+
+/// Notice that a requested or remembered device was missing and the default was used
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceFallback {
+    pub kind: DeviceKind,
+    /// The device ID that could not be found
+    pub requested_id: String,
+    pub message: String,
 }
 
-/// Recording errors
+/// Result of starting a recording
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct StartRecordingResult {
+    /// Output file path
+    pub output_path: Option<PathBuf>,
+    
+    /// Devices that were replaced by the system default (empty if none)
+    pub fallback_applied: Vec<DeviceFallback>,
+    
+    /// HLS playlist being written, when HLS output is on
+    pub hls_playlist: Option<PathBuf>,
+}
+
+/// Result of stopping a recording
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StopRecordingResult {
+    pub output_path: String,
+    
+    /// Where the encoder saves the thumbnail once it has finished the file
+    /// (None without FFmpeg support)
+    pub thumbnail_path: Option<PathBuf>,
+    
+    /// The file was read back after finalizing and matched what was recorded
+    ///
+    /// This is the file as encoded. Silence trimming, mastering and
+    /// normalization run afterwards; `recording-finished` reports the file
+    /// they leave.
+    pub verified: bool,
+    
+    /// Duration the finished file reports, in milliseconds
+    pub duration_ms: Option<u64>,
+    
+    /// Why the file didn't verify (truncated or out-of-sync streams)
+    pub issues: Vec<String>,
+}
+
+impl StopRecordingResult {
+    pub fn new(output_path: String, verification: Option<OutputVerification>) -> Self {
+        let thumbnail_path = cfg!(feature = "ffmpeg").then(|| thumbnail_path(Path::new(&output_path)));
+        let verification = verification.unwrap_or_else(|| OutputVerification {
+            issues: vec!["The file could not be read back (no FFmpeg support)".to_string()],
+            ..OutputVerification::default()
+        });
+        Self {
+            output_path,
+            thumbnail_path,
+            verified: verification.verified,
+            duration_ms: verification.duration_ms,
+            issues: verification.issues,
+        }
+    }
+}
+
+/// Error returned by the commands
+///
+/// Serialized as `{ code, message, recoverable }`, so the frontend can tell
+/// permission, device and encoder failures apart without parsing messages.
 #[derive(Error, Debug)]
 pub enum RecordingError {
     #[error("Recording already in progress")]
@@ -256,8 +1446,89 @@ pub enum RecordingError {
     #[error("No video source selected")]
     NoVideoSource,
     
+    #[error("Output path not allowed: {} is outside the allowed output directories", .0.display())]
+    PathNotAllowed(PathBuf),
+    
+    /// The output directory is missing, read-only or nearly full
+    #[error("{0}")]
+    OutputUnavailable(String),
+    
+    /// A setting is out of range or conflicts with another
+    #[error("{0}")]
+    InvalidConfig(String),
+    
+    /// The OS refused access, e.g. to screen recording
+    #[error("{0}")]
+    PermissionDenied(String),
+    
+    /// A capture device could not be opened or started
+    #[error("{0}")]
+    Device(String),
+    
+    /// The encoder could not be set up or started
+    #[error("{0}")]
+    Encoder(String),
+    
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
+    
+    /// Anything not classified further
+    #[error("{0}")]
+    Failed(String),
+}
+
+/// Kind of a `RecordingError`, for the frontend to branch on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorCode {
+    AlreadyRecording,
+    NotRecording,
+    NoVideoSource,
+    PathNotAllowed,
+    OutputUnavailable,
+    InvalidConfig,
+    PermissionDenied,
+    Device,
+    Encoder,
+    Io,
+    Failed,
+}
+
+impl RecordingError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::AlreadyRecording => ErrorCode::AlreadyRecording,
+            Self::NotRecording => ErrorCode::NotRecording,
+            Self::NoVideoSource => ErrorCode::NoVideoSource,
+            Self::PathNotAllowed(_) => ErrorCode::PathNotAllowed,
+            Self::OutputUnavailable(_) => ErrorCode::OutputUnavailable,
+            Self::InvalidConfig(_) => ErrorCode::InvalidConfig,
+            Self::PermissionDenied(_) => ErrorCode::PermissionDenied,
+            Self::Device(_) => ErrorCode::Device,
+            Self::Encoder(_) => ErrorCode::Encoder,
+            Self::Io(_) => ErrorCode::Io,
+            Self::Failed(_) => ErrorCode::Failed,
+        }
+    }
+    
+    /// Whether trying again can succeed once the cause is dealt with (access
+    /// granted, device plugged in, setting changed); encoder and file system
+    /// failures usually need more than that
+    pub fn recoverable(&self) -> bool {
+        !matches!(self, Self::Encoder(_) | Self::Io(_))
+    }
+}
+
+impl From<String> for RecordingError {
+    fn from(message: String) -> Self {
+        Self::Failed(message)
+    }
+}
+
+impl From<&str> for RecordingError {
+    fn from(message: &str) -> Self {
+        Self::Failed(message.to_string())
+    }
 }
 
 impl Serialize for RecordingError {
@@ -265,7 +1536,13 @@ impl Serialize for RecordingError {
     where
         S: serde::Serializer,
     {
-        serializer.serialize_str(&self.to_string())
+        use serde::ser::SerializeStruct;
+        
+        let mut error = serializer.serialize_struct("RecordingError", 3)?;
+        error.serialize_field("code", &self.code())?;
+        error.serialize_field("message", &self.to_string())?;
+        error.serialize_field("recoverable", &self.recoverable())?;
+        error.end()
     }
 }
 
@@ -290,7 +1567,7 @@ impl Default for RecordingState {
 
 /// Tauri command: Get list of available capture devices
 #[command]
-pub fn get_available_devices() -> Result<DeviceList, String> {
+pub fn get_available_devices() -> Result<DeviceList, RecordingError> {
     use cpal::traits::{DeviceTrait, HostTrait};
 
     let mut device_list = DeviceList::default();
@@ -357,15 +1634,24 @@ pub fn get_available_devices() -> Result<DeviceList, String> {
         }
     }
     
+    // Get available output devices (for monitoring)
+    if let Ok(devices) = host.output_devices() {
+        for device in devices {
+            if let Ok(name) = device.name() {
+                device_list.audio_outputs.push(DeviceInfo {
+                    id: name.clone(),
+                    name,
+                });
+            }
+        }
+    }
+    
     // Check for system audio capability (platform-specific)
     device_list.has_system_audio = is_system_audio_available();
+    device_list.has_virtual_camera = is_virtual_camera_available();
     
-    // Note: Webcam enumeration will be added when nokhwa is properly configured
-    // For now, we'll try to detect if any webcam is available
-    device_list.webcams.push(DeviceInfo {
-        id: "default".to_string(),
-        name: "Default Camera".to_string(),
-    });
+    // Get available webcams
+    device_list.webcams = list_webcams();
     
     Ok(device_list)
 }
@@ -381,18 +1667,18 @@ pub fn get_recording_status(state: tauri::State<'_, Arc<RecordingState>>) -> Rec
 pub async fn start_recording(
     config: RecordingConfig,
     state: tauri::State<'_, Arc<RecordingState>>,
-) -> Result<(), String> {
+) -> Result<StartRecordingResult, RecordingError> {
     // Check if already recording
     {
         let status = state.status.read();
         if status.is_recording {
-            return Err(RecordingError::AlreadyRecording.to_string());
+            return Err(RecordingError::AlreadyRecording);
         }
     }
     
     // Validate configuration
     if !config.capture_screen && !config.capture_webcam {
-        return Err(RecordingError::NoVideoSource.to_string());
+        return Err(RecordingError::NoVideoSource);
     }
     
     // Store config
@@ -413,7 +1699,7 @@ pub async fn start_recording(
     };
     
     match result {
-        Ok(()) => {
+        Ok(start_result) => {
             // Update status from manager
             let manager_status = {
                 let mut manager = state.manager.lock();
@@ -424,7 +1710,7 @@ pub async fn start_recording(
             *status = manager_status;
             
             println!("Recording started successfully");
-            Ok(())
+            Ok(start_result)
         }
         Err(e) => {
             // Clear config on error
@@ -436,15 +1722,48 @@ pub async fn start_recording(
     }
 }
 
+/// Tauri command: Record a raw capture dump again through the pipeline
+///
+/// The dump's recording config is used, writing to `output_path` (or a new
+/// generated name). The recording stops by itself at the end of the dump.
+/// `dump_dir` must be inside an allowed output directory.
+#[command]
+pub async fn replay_capture_dump(
+    dump_dir: PathBuf,
+    output_path: Option<PathBuf>,
+    state: tauri::State<'_, Arc<RecordingState>>,
+) -> Result<StartRecordingResult, RecordingError> {
+    if state.status.read().is_recording {
+        return Err(RecordingError::AlreadyRecording);
+    }
+    
+    {
+        let mut stop = state.stop_signal.write();
+        *stop = false;
+    }
+    
+    let (start_result, manager_status, config) = {
+        let mut manager = state.manager.lock();
+        let start_result = manager.start_replay(&dump_dir, output_path)?;
+        (start_result, manager.status(), manager.config().cloned())
+    };
+    
+    *state.config.write() = config;
+    *state.status.write() = manager_status;
+    
+    println!("Replay started successfully");
+    Ok(start_result)
+}
+
 /// Tauri command: Stop recording and finalize the output file
 #[command]
 pub async fn stop_recording(
     state: tauri::State<'_, Arc<RecordingState>>,
-) -> Result<String, String> {
+) -> Result<StopRecordingResult, RecordingError> {
     {
         let status = state.status.read();
         if !status.is_recording {
-            return Err(RecordingError::NotRecording.to_string());
+            return Err(RecordingError::NotRecording);
         }
     }
     
@@ -455,9 +1774,10 @@ pub async fn stop_recording(
     }
     
     // Stop recording using the manager
-    let result = {
+    let (result, verification) = {
         let mut manager = state.manager.lock();
-        manager.stop()
+        let result = manager.stop();
+        (result, manager.take_output_verification())
     };
     
     // Update status
@@ -474,7 +1794,7 @@ pub async fn stop_recording(
     
     println!("Recording stopped");
     
-    result
+    result.map(|path| StopRecordingResult::new(path, verification))
 }
 
 /// Tauri command: Get current recording status (refreshed from manager)
@@ -483,3 +1803,430 @@ pub fn get_recording_status_live(state: tauri::State<'_, Arc<RecordingState>>) -
     let mut manager = state.manager.lock();
     manager.status()
 }
+
+/// Tauri command: End the active recording exactly at `timestamp_ms` from its start
+///
+/// The encoder keeps running until that point, drops anything later and
+/// finalizes the file; `stop_recording` afterwards returns its path.
+#[command]
+pub fn stop_at(
+    timestamp_ms: u64,
+    state: tauri::State<'_, Arc<RecordingState>>,
+    external: tauri::State<'_, Arc<ExternalRecorderState>>,
+) -> Result<(), RecordingError> {
+    if state.status.read().is_recording {
+        return state.manager.lock().stop_at(timestamp_ms);
+    }
+    
+    let mut recorder = external.recorder.lock();
+    if recorder.status().is_recording {
+        return recorder.stop_at(timestamp_ms);
+    }
+    
+    Err(RecordingError::NotRecording)
+}
+
+/// Tauri command: Mark the current point of the active recording
+///
+/// Markers become chapters of the finished file and lines of its
+/// `.chapters.txt`.
+#[command]
+pub fn add_marker(
+    label: String,
+    state: tauri::State<'_, Arc<RecordingState>>,
+    external: tauri::State<'_, Arc<ExternalRecorderState>>,
+) -> Result<Marker, RecordingError> {
+    validate_label(&label).map_err(RecordingError::InvalidConfig)?;
+    
+    if state.status.read().is_recording {
+        return state.manager.lock().add_marker(&label);
+    }
+    
+    let mut recorder = external.recorder.lock();
+    if recorder.status().is_recording {
+        return recorder.add_marker(&label);
+    }
+    
+    Err(RecordingError::NotRecording)
+}
+
+/// Tauri command: Change non-structural settings of the active recording
+#[command]
+pub fn update_active_config(
+    partial: ActiveConfigUpdate,
+    state: tauri::State<'_, Arc<RecordingState>>,
+    external: tauri::State<'_, Arc<ExternalRecorderState>>,
+) -> Result<(), RecordingError> {
+    partial.validate().map_err(RecordingError::InvalidConfig)?;
+    
+    if state.status.read().is_recording {
+        return state.manager.lock().update_active_config(&partial);
+    }
+    
+    let mut recorder = external.recorder.lock();
+    if recorder.status().is_recording {
+        return recorder.update_active_config(&partial);
+    }
+    
+    Err(RecordingError::NotRecording)
+}
+
+/// Tauri command: Get the compositor's layer stack, bottom first
+#[command]
+pub fn get_compositor_layers(
+    state: tauri::State<'_, Arc<RecordingState>>,
+) -> Result<Vec<Layer>, RecordingError> {
+    if state.status.read().is_recording {
+        return state.manager.lock().compositor_layers();
+    }
+    Err(RecordingError::NotRecording)
+}
+
+/// Tauri command: Change z-order and opacity of compositor layers
+///
+/// Only native recordings are composited; external recordings get their
+/// layout from the frontend. Returns the resulting layer stack.
+#[command]
+pub fn set_layer_properties(
+    layers: Vec<LayerUpdate>,
+    state: tauri::State<'_, Arc<RecordingState>>,
+) -> Result<Vec<Layer>, RecordingError> {
+    if state.status.read().is_recording {
+        return state.manager.lock().set_layer_properties(&layers);
+    }
+    Err(RecordingError::NotRecording)
+}
+
+/// Tauri command: Start, change or stop mic monitoring on the active recording
+#[command]
+pub fn set_mic_monitoring(
+    config: Option<MonitorConfig>,
+    state: tauri::State<'_, Arc<RecordingState>>,
+    external: tauri::State<'_, Arc<ExternalRecorderState>>,
+) -> Result<(), RecordingError> {
+    if state.status.read().is_recording {
+        return state.manager.lock().set_mic_monitor(config);
+    }
+    
+    let mut recorder = external.recorder.lock();
+    if recorder.status().is_recording {
+        return recorder.set_mic_monitor(config);
+    }
+    
+    Err(RecordingError::NotRecording)
+}
+
+/// Tauri command: Start, change or stop the waveform/spectrum events on the active recording
+#[command]
+pub fn set_audio_visualizer(
+    config: Option<VisualizerConfig>,
+    state: tauri::State<'_, Arc<RecordingState>>,
+    external: tauri::State<'_, Arc<ExternalRecorderState>>,
+) -> Result<(), RecordingError> {
+    if state.status.read().is_recording {
+        return state.manager.lock().set_audio_visualizer(config);
+    }
+    
+    let mut recorder = external.recorder.lock();
+    if recorder.status().is_recording {
+        return recorder.set_audio_visualizer(config);
+    }
+    
+    Err(RecordingError::NotRecording)
+}
+
+/// Tauri command: Also stream the active recording to an RTMP server
+#[command]
+pub fn start_streaming(
+    config: StreamingConfig,
+    state: tauri::State<'_, Arc<RecordingState>>,
+    external: tauri::State<'_, Arc<ExternalRecorderState>>,
+) -> Result<(), RecordingError> {
+    config.validate().map_err(RecordingError::InvalidConfig)?;
+    
+    if state.status.read().is_recording {
+        return state.manager.lock().start_streaming(config);
+    }
+    
+    let mut recorder = external.recorder.lock();
+    if recorder.status().is_recording {
+        return recorder.start_streaming(config);
+    }
+    
+    Err(RecordingError::NotRecording)
+}
+
+/// Tauri command: Stop streaming; the recording carries on
+#[command]
+pub fn stop_streaming(
+    state: tauri::State<'_, Arc<RecordingState>>,
+    external: tauri::State<'_, Arc<ExternalRecorderState>>,
+) {
+    state.manager.lock().stop_streaming();
+    external.recorder.lock().stop_streaming();
+}
+
+/// Tauri command: Publish the active recording over WebRTC for remote
+/// monitoring, returning the link collaborators watch it at
+#[command]
+pub fn start_remote_monitor(
+    config: WebRtcConfig,
+    state: tauri::State<'_, Arc<RecordingState>>,
+    external: tauri::State<'_, Arc<ExternalRecorderState>>,
+) -> Result<MonitorJoin, RecordingError> {
+    config.validate().map_err(RecordingError::InvalidConfig)?;
+    
+    if state.status.read().is_recording {
+        return state.manager.lock().start_webrtc(config);
+    }
+    
+    let mut recorder = external.recorder.lock();
+    if recorder.status().is_recording {
+        return recorder.start_webrtc(config);
+    }
+    
+    Err(RecordingError::NotRecording)
+}
+
+/// Tauri command: Get the join token and link of the running remote monitor
+#[command]
+pub fn get_monitor_join_token(
+    state: tauri::State<'_, Arc<RecordingState>>,
+    external: tauri::State<'_, Arc<ExternalRecorderState>>,
+) -> Result<MonitorJoin, RecordingError> {
+    let join = state.manager.lock().webrtc_join();
+    join.or_else(|| external.recorder.lock().webrtc_join())
+        .ok_or_else(|| RecordingError::Failed("Remote monitoring is off".to_string()))
+}
+
+/// Tauri command: Stop remote monitoring; the recording carries on
+#[command]
+pub fn stop_remote_monitor(
+    state: tauri::State<'_, Arc<RecordingState>>,
+    external: tauri::State<'_, Arc<ExternalRecorderState>>,
+) {
+    state.manager.lock().stop_webrtc();
+    external.recorder.lock().stop_webrtc();
+}
+
+/// Tauri command: Get live audio levels from whichever recorder is active
+#[command]
+pub fn get_audio_levels(
+    state: tauri::State<'_, Arc<RecordingState>>,
+    external: tauri::State<'_, Arc<ExternalRecorderState>>,
+) -> Option<AudioLevels> {
+    let levels = state.manager.lock().audio_levels();
+    levels.or_else(|| external.recorder.lock().audio_levels())
+}
+
+/// Tauri command: Get rate conversion and drift statistics from whichever recorder is active
+#[command]
+pub fn get_audio_stats(
+    state: tauri::State<'_, Arc<RecordingState>>,
+    external: tauri::State<'_, Arc<ExternalRecorderState>>,
+) -> Option<MixerStats> {
+    let stats = state.manager.lock().audio_stats();
+    stats.or_else(|| external.recorder.lock().audio_stats())
+}
+
+/// Tauri command: Get frame rates, dropped frames and queue depths of whichever recorder is active
+#[command]
+pub fn get_pipeline_stats(
+    state: tauri::State<'_, Arc<RecordingState>>,
+    external: tauri::State<'_, Arc<ExternalRecorderState>>,
+) -> Option<PipelineStats> {
+    let stats = state.manager.lock().pipeline_stats();
+    stats.or_else(|| external.recorder.lock().pipeline_stats())
+}
+
+/// Tauri command: Get the negotiated audio latency of whichever recorder is active
+#[command]
+pub fn get_audio_latency(
+    state: tauri::State<'_, Arc<RecordingState>>,
+    external: tauri::State<'_, Arc<ExternalRecorderState>>,
+) -> Option<AudioLatency> {
+    let latency = state.manager.lock().audio_latency();
+    latency.or_else(|| external.recorder.lock().audio_latency())
+}
+
+/// Tauri command: Configure level metering (weighting, peak hold, RMS window)
+#[command]
+pub fn set_metering_config(
+    config: MeteringConfig,
+    state: tauri::State<'_, Arc<RecordingState>>,
+    external: tauri::State<'_, Arc<ExternalRecorderState>>,
+) {
+    state.manager.lock().set_metering_config(config);
+    external.recorder.lock().set_metering_config(config);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_active_config_update_rejects_structural_fields() {
+        let update: ActiveConfigUpdate =
+            serde_json::from_str(r#"{"micVolume": 0.5, "webcamPosition": "bottom-left"}"#).unwrap();
+        assert!(update.validate().is_ok());
+        
+        let update: ActiveConfigUpdate =
+            serde_json::from_str(r#"{"micVolume": 0.5, "outputResolution": "uhd4k"}"#).unwrap();
+        let err = update.validate().unwrap_err();
+        assert!(err.contains("outputResolution"));
+        
+        let update: ActiveConfigUpdate = serde_json::from_str(r#"{"webcamSize": 80}"#).unwrap();
+        assert!(update.validate().is_err());
+        
+        // Overlays and the cursor highlight change live
+        let update: ActiveConfigUpdate = serde_json::from_str(
+            r#"{"overlaysVisible": false, "textOverlaysVisible": [true, false], "timerVisible": true,
+                "watermarkVisible": false, "cursorHighlight": {"enabled": true, "radius": 30}}"#,
+        )
+        .unwrap();
+        assert!(update.validate().is_ok());
+        assert_eq!(update.text_overlays_visible, Some(vec![true, false]));
+        let update: ActiveConfigUpdate =
+            serde_json::from_str(r#"{"cursorHighlight": {"enabled": true, "radius": 500}}"#).unwrap();
+        assert!(update.validate().is_err());
+    }
+    
+    #[test]
+    fn test_advanced_quality_validation() {
+        let advanced = AdvancedQuality {
+            crf: Some(55),
+            bitrate_kbps: Some(8000),
+            max_bitrate_kbps: Some(12000),
+            preset: Some("veryfast".to_string()),
+            threads: Some(8),
+            slices: Some(4),
+            tune: Some("film".to_string()),
+        };
+        assert!(advanced.validate(OutputFormat::Mp4).is_err());
+        assert!(advanced.validate(OutputFormat::Webm).is_ok());
+        
+        let advanced = AdvancedQuality {
+            max_bitrate_kbps: Some(4000),
+            ..advanced
+        };
+        assert!(advanced.validate(OutputFormat::Webm).is_err());
+        
+        let advanced = AdvancedQuality {
+            preset: Some("turbo".to_string()),
+            ..AdvancedQuality::default()
+        };
+        assert!(advanced.validate(OutputFormat::Mp4).is_err());
+        
+        for advanced in [
+            AdvancedQuality { threads: Some(0), ..AdvancedQuality::default() },
+            AdvancedQuality { slices: Some(MAX_ENCODER_SLICES + 1), ..AdvancedQuality::default() },
+            AdvancedQuality { tune: Some("fast".to_string()), ..AdvancedQuality::default() },
+        ] {
+            assert!(advanced.validate(OutputFormat::Mp4).is_err(), "{:?}", advanced);
+        }
+    }
+    
+    #[test]
+    fn test_track_metadata() {
+        let track = TrackMetadata::default().with_default_title(true, true, AudioChannelLayout::Stereo);
+        assert_eq!(track.title.as_deref(), Some("Microphone + System Audio"));
+        
+        let track = TrackMetadata::default().with_default_title(true, false, AudioChannelLayout::Binaural);
+        assert_eq!(track.title.as_deref(), Some("Microphone (Binaural L/R)"));
+        
+        let track = TrackMetadata {
+            title: Some("Whisper take 3".to_string()),
+            language: Some("jpn".to_string()),
+        };
+        assert_eq!(track.with_default_title(true, false, AudioChannelLayout::Stereo), track);
+        assert!(track.validate().is_ok());
+        
+        let track = TrackMetadata { language: Some("en".to_string()), ..track };
+        assert!(track.validate().is_err());
+        
+        let metadata = FileMetadata {
+            title: Some("Rain on a tent".to_string()),
+            date: Some("2024-05-01".to_string()),
+            ..FileMetadata::default()
+        };
+        assert_eq!(metadata.entries(), vec![("title", "Rain on a tent"), ("date", "2024-05-01")]);
+        assert!(metadata.validate().is_ok());
+        let metadata = FileMetadata { artist: Some("a\0b".to_string()), ..metadata };
+        assert!(metadata.validate().is_err());
+    }
+    
+    #[test]
+    fn test_audio_codec_per_format() {
+        assert!(OutputFormat::Mkv.validate_audio_codec(Some(AudioCodec::Flac)).is_ok());
+        assert!(OutputFormat::Mkv.validate_audio_codec(Some(AudioCodec::Pcm)).is_ok());
+        assert!(OutputFormat::Mp4.validate_audio_codec(Some(AudioCodec::Aac)).is_ok());
+        assert!(OutputFormat::Mp4.validate_audio_codec(Some(AudioCodec::Flac)).is_err());
+        assert!(OutputFormat::Webm.validate_audio_codec(Some(AudioCodec::Aac)).is_err());
+        assert!(OutputFormat::Webm.validate_audio_codec(None).is_ok());
+        assert!(AudioBitDepth::Float32.validate_for(Some(AudioCodec::Flac)).is_err());
+        assert!(AudioBitDepth::Int24.validate_for(Some(AudioCodec::Pcm)).is_ok());
+        assert!(validate_audio_bitrate(256, None).is_ok());
+        assert!(validate_audio_bitrate(1000, Some(AudioCodec::Aac)).is_err());
+        assert!(validate_audio_bitrate(256, Some(AudioCodec::Flac)).is_err());
+        
+        let codec: AudioCodec = serde_json::from_str(r#""pcm""#).unwrap();
+        assert_eq!(codec, AudioCodec::Pcm);
+        assert_eq!(OutputFormat::Mkv.extension(), "mkv");
+    }
+    
+    #[test]
+    fn test_high_sample_rates_need_lossless_audio() {
+        assert!(OutputFormat::Mp4.validate_codec_sample_rate(None, 96000).is_ok());
+        assert!(OutputFormat::Mp4.validate_codec_sample_rate(None, 192000).is_err());
+        assert!(OutputFormat::Mkv.validate_codec_sample_rate(Some(AudioCodec::Aac), 176400).is_err());
+        assert!(OutputFormat::Mkv.validate_codec_sample_rate(Some(AudioCodec::Flac), 192000).is_ok());
+        assert!(OutputFormat::Mkv.validate_codec_sample_rate(Some(AudioCodec::Pcm), 192000).is_ok());
+    }
+    
+    #[test]
+    fn test_append_session_paths() {
+        assert!(validate_session_id("podcast-ep_12").is_ok());
+        assert!(validate_session_id("../escape").is_err());
+        assert!(validate_session_id("").is_err());
+        assert_eq!(session_file_name("ep1", OutputFormat::Mkv), "session_ep1.mkv");
+        
+        let dir = std::env::temp_dir();
+        let session = dir.join(format!("asmr_session_{}.mp4", std::process::id()));
+        assert_eq!(append_part_path(&session), None);
+        std::fs::write(&session, b"").unwrap();
+        let part = append_part_path(&session);
+        let _ = std::fs::remove_file(&session);
+        assert_eq!(
+            part,
+            Some(dir.join(format!("asmr_session_{}.appending.mp4", std::process::id())))
+        );
+    }
+    
+    #[test]
+    fn test_match_source_resolution() {
+        let ultrawide = Some((3441, 1439));
+        assert_eq!(OutputResolution::MatchSource.resolve(ultrawide), (3440, 1438));
+        assert_eq!(OutputResolution::MatchSource.resolve(None), (1920, 1080));
+        assert_eq!(OutputResolution::Hd720.resolve(ultrawide), (1280, 720));
+    }
+    
+    #[test]
+    fn test_errors_serialize_with_code() {
+        let json = serde_json::to_value(RecordingError::PermissionDenied("Screen Recording permission required".to_string())).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "code": "permission-denied",
+                "message": "Screen Recording permission required",
+                "recoverable": true,
+            })
+        );
+        
+        let json = serde_json::to_value(RecordingError::Encoder("Encoder failed".to_string())).unwrap();
+        assert_eq!(json["code"], "encoder");
+        assert_eq!(json["recoverable"], false);
+        
+        let error: RecordingError = "No recording configuration".into();
+        assert_eq!(error.code(), ErrorCode::Failed);
+    }
+}
@@ -1,11 +1,27 @@
+use std::sync::Arc;
 use std::time::Duration;
 use tauri::command;
 
+use crate::frame_pool::{FrameBuffer, FramePool};
+use crate::recording::{CapturePixelFormat, RecordingError};
+
+/// Default width of display picker thumbnails
+const THUMBNAIL_WIDTH: u32 = 320;
+
+const THUMBNAIL_JPEG_QUALITY: u8 = 75;
+
+/// Longest wait for a display's first frame
+const THUMBNAIL_TIMEOUT: Duration = Duration::from_secs(3);
+
 /// Represents a captured screen frame
 #[derive(Clone)]
 pub struct ScreenFrame {
-    /// Raw BGRA pixel data (may include row padding)
-    pub data: Vec<u8>,
+    /// Raw pixel data (may include row padding), shared with the frames
+    /// passed on without a copy
+    ///
+    /// BGRA, or for NV12 the Y plane followed by the interleaved UV plane,
+    /// both using `stride`.
+    pub data: Arc<FrameBuffer>,
     /// Frame width in pixels
     pub width: u32,
     /// Frame height in pixels
@@ -14,6 +30,8 @@ pub struct ScreenFrame {
     pub stride: usize,
     /// Timestamp when frame was captured
     pub timestamp: Duration,
+    /// Layout of `data`
+    pub pixel_format: CapturePixelFormat,
 }
 
 impl ScreenFrame {
@@ -22,8 +40,17 @@ impl ScreenFrame {
     /// This method properly handles row stride/padding by iterating row-by-row
     /// rather than assuming tightly-packed pixel data.
     pub fn to_rgba(&self) -> Vec<u8> {
-        let output_size = (self.width * self.height * 4) as usize;
-        let mut rgba = Vec::with_capacity(output_size);
+        let mut rgba = Vec::with_capacity((self.width * self.height * 4) as usize);
+        self.append_rgba(&mut rgba);
+        rgba
+    }
+
+    /// Convert to RGBA at the end of `rgba` (e.g. a pooled buffer)
+    pub fn append_rgba(&self, rgba: &mut Vec<u8>) {
+        if self.pixel_format == CapturePixelFormat::Nv12 {
+            self.nv12_to_rgba(rgba);
+            return;
+        }
 
         for y in 0..self.height as usize {
             let row_start = y * self.stride;
@@ -49,36 +76,180 @@ impl ScreenFrame {
                 }
             }
         }
-
-        rgba
     }
 
     /// Get BGRA data with stride padding removed (fast path - no color conversion)
     ///
     /// This is much faster than to_rgba() because it only handles stride alignment
     /// without doing any per-pixel color channel swapping.
-    pub fn to_packed_bgra(&self) -> Vec<u8> {
+    pub fn packed_bgra(&self, pool: &FramePool) -> Arc<FrameBuffer> {
         let row_bytes = (self.width * 4) as usize;
+        self.packed(pool, row_bytes, self.height as usize)
+    }
+
+    /// Get NV12 data with stride padding removed (Y plane, then UV plane)
+    pub fn packed_nv12(&self, pool: &FramePool) -> Arc<FrameBuffer> {
+        let rows = self.height as usize + self.height as usize / 2;
+        self.packed(pool, self.width as usize, rows)
+    }
 
-        // Fast path: if stride matches expected row size, data is already tightly packed
-        if self.stride == row_bytes {
-            // Just return a clone of the relevant portion
-            let total_bytes = row_bytes * self.height as usize;
-            return self.data[..total_bytes].to_vec();
+    /// `rows` rows of `row_bytes` without the stride padding
+    fn packed(&self, pool: &FramePool, row_bytes: usize, rows: usize) -> Arc<FrameBuffer> {
+        // Tightly packed already: share the captured buffer
+        if self.stride == row_bytes && self.data.len() == row_bytes * rows {
+            return Arc::clone(&self.data);
         }
 
         // Slow path: need to remove padding from each row
-        let output_size = row_bytes * self.height as usize;
-        let mut bgra = Vec::with_capacity(output_size);
-
-        for y in 0..self.height as usize {
+        let mut packed = pool.take(row_bytes * rows);
+        for y in 0..rows {
             let row_start = y * self.stride;
-            bgra.extend_from_slice(&self.data[row_start..row_start + row_bytes]);
+            packed.extend_from_slice(&self.data[row_start..row_start + row_bytes]);
+        }
+        pool.share(packed)
+    }
+
+    /// Copy out the part of a BGRA frame covered by `region`
+    pub fn crop_bgra(&self, region: &CaptureRegion, pool: &FramePool) -> ScreenFrame {
+        let (x, y, width, height) = region.pixels(self.width, self.height);
+        let row_bytes = width as usize * 4;
+        let mut data = pool.take(row_bytes * height as usize);
+
+        for row in y as usize..(y + height) as usize {
+            let start = row * self.stride + x as usize * 4;
+            data.extend_from_slice(&self.data[start..start + row_bytes]);
+        }
+
+        ScreenFrame {
+            data: pool.share(data),
+            width,
+            height,
+            stride: row_bytes,
+            timestamp: self.timestamp,
+            pixel_format: CapturePixelFormat::Bgra,
         }
+    }
+
+    /// Convert video-range BT.709 NV12 (ScreenCaptureKit's 420v) to RGBA
+    fn nv12_to_rgba(&self, rgba: &mut Vec<u8>) {
+        let width = self.width as usize;
+        let height = self.height as usize;
+        let uv_offset = self.stride * height;
+
+        for y in 0..height {
+            let y_row = y * self.stride;
+            let uv_row = uv_offset + (y / 2) * self.stride;
+            for x in 0..width {
+                let uv = uv_row + (x & !1);
+                let rgb = nv12_to_rgb(self.data[y_row + x], self.data[uv], self.data[uv + 1]);
+                rgba.extend_from_slice(&rgb);
+                rgba.push(255);
+            }
+        }
+    }
+
+    /// Scale down to at most `max_width` pixels wide, keeping the aspect
+    /// ratio, and encode as a JPEG
+    pub fn to_thumbnail_jpeg(&self, max_width: u32, quality: u8) -> Result<Vec<u8>, String> {
+        let image = image::RgbaImage::from_raw(self.width, self.height, self.to_rgba())
+            .ok_or("Screen frame is smaller than its dimensions")?;
+        let width = self.width.min(max_width).max(1);
+        let height = ((self.height as u64 * width as u64 / self.width.max(1) as u64) as u32).max(1);
+        let thumbnail = image::imageops::thumbnail(&image, width, height);
+        let rgb = image::DynamicImage::ImageRgba8(thumbnail).to_rgb8();
+
+        let mut jpeg = Vec::new();
+        image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg, quality)
+            .encode_image(&rgb)
+            .map_err(|e| format!("Thumbnail JPEG encoding failed: {}", e))?;
+        Ok(jpeg)
+    }
+}
+
+/// Convert one video-range BT.709 YUV sample to RGB
+pub(crate) fn nv12_to_rgb(y: u8, u: u8, v: u8) -> [u8; 3] {
+    let luma = (y as f32 - 16.0) * 1.164;
+    let u = u as f32 - 128.0;
+    let v = v as f32 - 128.0;
+    [
+        (luma + 1.793 * v).round().clamp(0.0, 255.0) as u8,
+        (luma - 0.213 * u - 0.533 * v).round().clamp(0.0, 255.0) as u8,
+        (luma + 2.112 * u).round().clamp(0.0, 255.0) as u8,
+    ]
+}
+
+/// Part of a display, as fractions of its size
+///
+/// Fractions keep the region valid whatever scale the capture backend
+/// delivers the display at (points on macOS, pixels elsewhere).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CaptureRegion {
+    /// Display index the region is on (0 = primary)
+    pub display_index: usize,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl CaptureRegion {
+    /// Locate a webview's content area on its monitor, as it is now
+    pub fn of_window(window: &tauri::WebviewWindow) -> Result<Self, String> {
+        let position = window.inner_position()
+            .map_err(|e| format!("Failed to get window position: {}", e))?;
+        let size = window.inner_size()
+            .map_err(|e| format!("Failed to get window size: {}", e))?;
+        let monitor = window.current_monitor()
+            .map_err(|e| format!("Failed to get window monitor: {}", e))?
+            .ok_or("Window is not on any monitor")?;
+        let monitors = window.available_monitors()
+            .map_err(|e| format!("Failed to list monitors: {}", e))?;
+
+        let display_index = monitors
+            .iter()
+            .position(|m| m.position() == monitor.position() && m.size() == monitor.size())
+            .unwrap_or(0);
+        let origin = monitor.position();
+        let display = monitor.size();
 
-        bgra
+        Ok(Self {
+            display_index,
+            x: (position.x - origin.x) as f64 / display.width as f64,
+            y: (position.y - origin.y) as f64 / display.height as f64,
+            width: size.width as f64 / display.width as f64,
+            height: size.height as f64 / display.height as f64,
+        })
     }
 
+    /// Pixel rectangle (x, y, width, height) of the region in a `width` x `height`
+    /// frame, clipped to the frame and at least 1x1
+    pub fn pixels(&self, width: u32, height: u32) -> (u32, u32, u32, u32) {
+        let to_px = |fraction: f64, size: u32| (fraction * size as f64).round().clamp(0.0, size as f64) as u32;
+
+        let x = to_px(self.x, width).min(width - 1);
+        let y = to_px(self.y, height).min(height - 1);
+        let right = to_px(self.x + self.width, width).max(x + 1);
+        let bottom = to_px(self.y + self.height, height).max(y + 1);
+        (x, y, right - x, bottom - y)
+    }
+}
+
+/// The screen area under a webview's content, followed as the window moves
+/// and resizes
+#[derive(Clone)]
+pub struct WindowArea {
+    window: tauri::WebviewWindow,
+}
+
+impl WindowArea {
+    pub fn new(window: tauri::WebviewWindow) -> Self {
+        Self { window }
+    }
+
+    /// Where the content area is now
+    pub fn region(&self) -> Result<CaptureRegion, String> {
+        CaptureRegion::of_window(&self.window)
+    }
 }
 
 /// Screen capture configuration
@@ -87,6 +258,8 @@ pub struct ScreenCaptureConfig {
     pub fps: u32,
     /// Display index to capture (0 = primary)
     pub display_index: usize,
+    /// Requested pixel format (platforms without NV12 capture deliver BGRA)
+    pub pixel_format: CapturePixelFormat,
 }
 
 impl Default for ScreenCaptureConfig {
@@ -94,6 +267,7 @@ impl Default for ScreenCaptureConfig {
         Self {
             fps: 30,
             display_index: 0,
+            pixel_format: CapturePixelFormat::Bgra,
         }
     }
 }
@@ -157,14 +331,61 @@ pub fn start_screen_capture() {
     }
 }
 
+/// Classify a screen capture failure, telling a refused Screen Recording
+/// permission apart from other problems with the display
+pub(crate) fn screen_capture_error(context: &str, error: String) -> RecordingError {
+    let lower = error.to_lowercase();
+    if lower.contains("permission") || lower.contains("screen recording") {
+        RecordingError::PermissionDenied(
+            "Screen Recording permission required. Open System Settings → Privacy & Security → Screen Recording and enable access for this app.".to_string(),
+        )
+    } else {
+        RecordingError::Device(format!("{}: {}", context, error))
+    }
+}
+
+/// Tauri command: Grab one frame of a display as a JPEG, for the display picker
+///
+/// `display_id` is a screen ID from `get_available_devices`; the thumbnail is
+/// at most `max_width` pixels wide (default 320). Returns the JPEG file bytes.
+#[command]
+pub async fn get_screen_thumbnail(
+    display_id: String,
+    max_width: Option<u32>,
+) -> Result<tauri::ipc::Response, RecordingError> {
+    let display_index = crate::devices::screen_index(&display_id)
+        .ok_or_else(|| RecordingError::Device(format!("Display \"{}\" is not available", display_id)))?;
+    let max_width = max_width.unwrap_or(THUMBNAIL_WIDTH).clamp(16, 3840);
+    let jpeg = tauri::async_runtime::spawn_blocking(move || grab_thumbnail(display_index, max_width))
+        .await
+        .map_err(|e| format!("Screen thumbnail failed: {}", e))?
+        .map_err(|e| screen_capture_error("Screen thumbnail failed", e))?;
+    Ok(tauri::ipc::Response::new(jpeg))
+}
+
+/// Capture until the first frame arrives
+fn grab_thumbnail(display_index: usize, max_width: u32) -> Result<Vec<u8>, String> {
+    let mut capture = ScreenCapture::new(ScreenCaptureConfig {
+        display_index,
+        ..ScreenCaptureConfig::default()
+    })?;
+    let receiver = capture.take_receiver().ok_or("Screen capture receiver not available")?;
+    capture.start()?;
+    let frame = receiver.recv_timeout(THUMBNAIL_TIMEOUT);
+    capture.stop();
+
+    let frame = frame.map_err(|_| format!("No frame from display {} within {}s", display_index, THUMBNAIL_TIMEOUT.as_secs()))?;
+    frame.to_thumbnail_jpeg(max_width, THUMBNAIL_JPEG_QUALITY)
+}
+
 /// Check if screen recording permission is granted
 #[command]
-pub fn check_screen_recording_permission() -> Result<bool, String> {
+pub fn check_screen_recording_permission() -> Result<bool, RecordingError> {
     #[cfg(target_os = "macos")]
     {
         match screencapturekit::prelude::SCShareableContent::get() {
             Ok(content) => Ok(!content.displays().is_empty()),
-            Err(e) => Err(format!("Failed to access displays: {}", e)),
+            Err(e) => Err(RecordingError::Device(format!("Failed to access displays: {}", e))),
         }
     }
 
@@ -180,7 +401,7 @@ pub fn check_screen_recording_permission() -> Result<bool, String> {
                 Ok(_) => Ok(true),
                 Err(_) => Ok(false),
             },
-            Err(e) => Err(format!("Failed to access display: {}", e)),
+            Err(e) => Err(RecordingError::Device(format!("Failed to access display: {}", e))),
         }
     }
 }
@@ -193,11 +414,12 @@ mod tests {
     fn test_screen_frame_conversion() {
         // Test with tightly-packed data (stride = width * 4)
         let frame = ScreenFrame {
-            data: vec![255, 128, 64, 255], // One BGRA pixel (on non-macOS) or ARGB (on macOS)
+            data: FrameBuffer::shared(vec![255, 128, 64, 255]), // One BGRA pixel (on non-macOS) or ARGB (on macOS)
             width: 1,
             height: 1,
             stride: 4, // 1 pixel * 4 bytes per pixel
             timestamp: Duration::from_secs(0),
+            pixel_format: CapturePixelFormat::Bgra,
         };
         
         let rgba = frame.to_rgba();
@@ -214,7 +436,7 @@ mod tests {
         let frame = ScreenFrame {
             // Row 0: 2 BGRA pixels + 8 bytes padding
             // Row 1: 2 BGRA pixels + 8 bytes padding
-            data: vec![
+            data: FrameBuffer::shared(vec![
                 // Row 0
                 0, 255, 0, 255,     // Pixel (0,0): BGRA = green
                 255, 0, 0, 255,     // Pixel (1,0): BGRA = blue
@@ -223,11 +445,12 @@ mod tests {
                 0, 0, 255, 255,     // Pixel (0,1): BGRA = red
                 255, 255, 255, 255, // Pixel (1,1): BGRA = white
                 0, 0, 0, 0, 0, 0, 0, 0, // 8 bytes padding
-            ],
+            ]),
             width: 2,
             height: 2,
             stride: 16, // 2 pixels * 4 bytes + 8 bytes padding = 16 bytes
             timestamp: Duration::from_secs(0),
+            pixel_format: CapturePixelFormat::Bgra,
         };
         
         #[cfg(not(target_os = "macos"))]
@@ -240,4 +463,80 @@ mod tests {
             assert_eq!(&rgba[4..8], &[0, 0, 255, 255]);
         }
     }
+    
+    #[test]
+    fn test_nv12_frame_conversion() {
+        // 2x2 NV12 with 4-byte stride: Y plane (2 rows) then one UV row
+        let frame = ScreenFrame {
+            data: FrameBuffer::shared(vec![
+                16, 235, 0, 0,   // Y row 0: black, white + padding
+                126, 126, 0, 0,  // Y row 1: mid gray
+                128, 128, 0, 0,  // UV row: neutral chroma
+            ]),
+            width: 2,
+            height: 2,
+            stride: 4,
+            timestamp: Duration::from_secs(0),
+            pixel_format: CapturePixelFormat::Nv12,
+        };
+        
+        assert_eq!(&frame.packed_nv12(&FramePool::new())[..], &[16, 235, 126, 126, 128, 128]);
+        
+        let rgba = frame.to_rgba();
+        assert_eq!(rgba.len(), 16);
+        assert_eq!(&rgba[0..4], &[0, 0, 0, 255]);
+        assert_eq!(&rgba[4..8], &[255, 255, 255, 255]);
+        assert!(rgba[8..11].iter().all(|&c| (127..=129).contains(&c)));
+    }
+    
+    #[test]
+    fn test_crop_to_region() {
+        // 4x2 BGRA frame where each pixel's first byte is its x + 10 * y, 20-byte stride
+        let mut data = vec![0u8; 40];
+        for y in 0..2 {
+            for x in 0..4 {
+                data[y * 20 + x * 4] = (x + 10 * y) as u8;
+            }
+        }
+        let frame = ScreenFrame {
+            data: FrameBuffer::shared(data),
+            width: 4,
+            height: 2,
+            stride: 20,
+            timestamp: Duration::from_secs(0),
+            pixel_format: CapturePixelFormat::Bgra,
+        };
+        
+        // Right half, bottom row
+        let region = CaptureRegion { display_index: 0, x: 0.5, y: 0.5, width: 0.5, height: 0.5 };
+        let pool = FramePool::new();
+        let cropped = frame.crop_bgra(&region, &pool);
+        assert_eq!((cropped.width, cropped.height, cropped.stride), (2, 1, 8));
+        assert_eq!((cropped.data[0], cropped.data[4]), (12, 13));
+        // A packed frame is passed on as it is
+        assert!(Arc::ptr_eq(&cropped.packed_bgra(&pool), &cropped.data));
+        
+        // Regions hanging off the display are clipped
+        let region = CaptureRegion { display_index: 0, x: 0.75, y: -0.5, width: 1.0, height: 1.0 };
+        assert_eq!(region.pixels(4, 2), (3, 0, 1, 1));
+    }
+    
+    #[test]
+    fn test_thumbnail_is_downscaled_jpeg() {
+        // 64x32 solid red BGRA
+        let frame = ScreenFrame {
+            data: FrameBuffer::shared([0, 0, 255, 255].repeat(64 * 32)),
+            width: 64,
+            height: 32,
+            stride: 256,
+            timestamp: Duration::from_secs(0),
+            pixel_format: CapturePixelFormat::Bgra,
+        };
+        
+        let jpeg = frame.to_thumbnail_jpeg(16, 90).unwrap();
+        let thumbnail = image::load_from_memory(&jpeg).unwrap().to_rgb8();
+        assert_eq!(thumbnail.dimensions(), (16, 8));
+        let [r, g, b] = thumbnail.get_pixel(8, 4).0;
+        assert!(r > 240 && g < 16 && b < 16, "{:?}", (r, g, b));
+    }
 }
@@ -1,5 +1,6 @@
 use scrap::{Capturer, Display};
 use std::io::ErrorKind;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
@@ -7,6 +8,9 @@ use crossbeam_channel::{bounded, Receiver, Sender};
 use parking_lot::Mutex;
 
 use super::{ScreenCaptureConfig, ScreenFrame};
+use crate::frame_pool::FramePool;
+use crate::recording::CapturePixelFormat;
+use crate::thread_priority::{self, ThreadRole};
 
 /// Manages continuous screen capture (fallback for non-macOS/Windows)
 pub struct ScreenCapture {
@@ -14,6 +18,10 @@ pub struct ScreenCapture {
     width: u32,
     height: u32,
     running: Arc<Mutex<bool>>,
+    /// When capture first started; timestamps count from here
+    started_at: Mutex<Option<Instant>>,
+    /// Frames and "nothing changed" results, for the stall watchdog
+    heartbeats: Arc<AtomicU64>,
     frame_sender: Option<Sender<ScreenFrame>>,
     frame_receiver: Option<Receiver<ScreenFrame>>,
 }
@@ -31,6 +39,10 @@ impl ScreenCapture {
         let width = display.width() as u32;
         let height = display.height() as u32;
 
+        if config.pixel_format == CapturePixelFormat::Nv12 {
+            println!("NV12 screen capture is not available on this platform, using BGRA");
+        }
+
         let (sender, receiver) = bounded(5);
 
         Ok(Self {
@@ -38,6 +50,8 @@ impl ScreenCapture {
             width,
             height,
             running: Arc::new(Mutex::new(false)),
+            started_at: Mutex::new(None),
+            heartbeats: Arc::new(AtomicU64::new(0)),
             frame_sender: Some(sender),
             frame_receiver: Some(receiver),
         })
@@ -69,9 +83,12 @@ impl ScreenCapture {
             .ok_or("Frame sender not available")?;
         let fps = self.config.fps;
         let display_index = self.config.display_index;
+        let start_time = *self.started_at.lock().get_or_insert_with(Instant::now);
+        let heartbeats = self.heartbeats.clone();
 
         std::thread::spawn(move || {
-            if let Err(e) = capture_loop(running_clone, sender, fps, display_index) {
+            thread_priority::elevate(ThreadRole::Capture);
+            if let Err(e) = capture_loop(running_clone, sender, fps, display_index, start_time, heartbeats) {
                 eprintln!("Screen capture error: {}", e);
             }
         });
@@ -89,6 +106,22 @@ impl ScreenCapture {
     pub fn is_running(&self) -> bool {
         *self.running.lock()
     }
+
+    /// Signs of life so far: frames, and polls that found the screen unchanged
+    pub fn heartbeats(&self) -> u64 {
+        self.heartbeats.load(Ordering::Relaxed)
+    }
+
+    /// Stop and start again, e.g. after frames stopped coming
+    ///
+    /// Frames keep their timestamps from the first start. A capture thread
+    /// stuck in the OS keeps its own (now cleared) running flag and exits
+    /// whenever it returns.
+    pub fn restart(&mut self) -> Result<(), String> {
+        self.stop();
+        self.running = Arc::new(Mutex::new(false));
+        self.start()
+    }
 }
 
 fn capture_loop(
@@ -96,6 +129,8 @@ fn capture_loop(
     sender: Sender<ScreenFrame>,
     fps: u32,
     display_index: usize,
+    start_time: Instant,
+    heartbeats: Arc<AtomicU64>,
 ) -> Result<(), String> {
     let displays = Display::all().map_err(|e| format!("Failed to get displays: {}", e))?;
     let display = displays
@@ -116,7 +151,8 @@ fn capture_loop(
     })?;
 
     let frame_duration = Duration::from_secs_f64(1.0 / fps as f64);
-    let start_time = Instant::now();
+    // Captured frames are copied into recycled buffers
+    let pool = FramePool::new();
 
     println!("Screen capture started: {}x{} @ {}fps", width, height, fps);
 
@@ -128,16 +164,20 @@ fn capture_loop(
                 let timestamp = start_time.elapsed();
                 let stride = frame.len() / height as usize;
                 let screen_frame = ScreenFrame {
-                    data: frame.to_vec(),
+                    data: pool.copy(&frame),
                     width,
                     height,
                     stride,
                     timestamp,
+                    pixel_format: CapturePixelFormat::Bgra,
                 };
 
                 let _ = sender.try_send(screen_frame);
+                heartbeats.fetch_add(1, Ordering::Relaxed);
             }
             Err(ref e) if e.kind() == ErrorKind::WouldBlock => {
+                // No change since the last frame
+                heartbeats.fetch_add(1, Ordering::Relaxed);
                 std::thread::sleep(Duration::from_millis(1));
                 continue;
             }
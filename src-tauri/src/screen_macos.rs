@@ -4,10 +4,13 @@ use std::time::Instant;
 
 use crossbeam_channel::{bounded, Receiver, Sender};
 use parking_lot::Mutex;
-use screencapturekit::cv::CVPixelBufferLockFlags;
+use screencapturekit::cv::{CVPixelBuffer, CVPixelBufferLockFlags, CVPixelBufferLockGuard};
+use screencapturekit::dispatch_queue::{DispatchQoS, DispatchQueue};
 use screencapturekit::prelude::*;
 
 use super::{ScreenCaptureConfig, ScreenFrame};
+use crate::frame_pool::FramePool;
+use crate::recording::CapturePixelFormat;
 
 /// Channel capacity for frame buffer - larger buffer absorbs processing delays
 /// At 30fps, 120 frames = 4 seconds of buffer
@@ -18,6 +21,10 @@ pub struct ScreenCapture {
     width: u32,
     height: u32,
     running: Arc<Mutex<bool>>,
+    /// When capture first started; timestamps count from here
+    started_at: Mutex<Option<Instant>>,
+    /// Frames and "nothing changed" results, for the stall watchdog
+    heartbeats: Arc<AtomicU64>,
     frame_sender: Option<Sender<ScreenFrame>>,
     frame_receiver: Option<Receiver<ScreenFrame>>,
     stream: Arc<Mutex<Option<SCStream>>>,
@@ -27,8 +34,12 @@ pub struct ScreenCapture {
 
 struct FrameHandler {
     sender: Sender<ScreenFrame>,
+    pixel_format: CapturePixelFormat,
+    /// Buffers pixel buffers are copied into
+    pool: FramePool,
     start_time: Instant,
     frame_count: Arc<AtomicU64>,
+    heartbeats: Arc<AtomicU64>,
     /// Counter for callbacks with no image buffer (for diagnostics)
     empty_buffer_count: AtomicU64,
 }
@@ -55,6 +66,8 @@ impl SCStreamOutputTrait for FrameHandler {
         if of_type != SCStreamOutputType::Screen {
             return;
         }
+        // Callbacks without an image (an unchanged screen) show the stream is alive too
+        self.heartbeats.fetch_add(1, Ordering::Relaxed);
 
         // Get image buffer - may be None for some callback types (expected behavior)
         let Some(buffer) = sample.image_buffer() else {
@@ -71,12 +84,22 @@ impl SCStreamOutputTrait for FrameHandler {
             return;
         };
 
-        let frame = ScreenFrame {
-            data: guard.as_slice().to_vec(),
-            width: guard.width() as u32,
-            height: guard.height() as u32,
-            stride: guard.bytes_per_row(),
-            timestamp: self.start_time.elapsed(),
+        let frame = match self.pixel_format {
+            CapturePixelFormat::Bgra => ScreenFrame {
+                data: self.pool.copy(guard.as_slice()),
+                width: guard.width() as u32,
+                height: guard.height() as u32,
+                stride: guard.bytes_per_row(),
+                timestamp: self.start_time.elapsed(),
+                pixel_format: CapturePixelFormat::Bgra,
+            },
+            CapturePixelFormat::Nv12 => {
+                let Some(frame) = nv12_frame(&buffer, &guard, &self.pool, self.start_time.elapsed()) else {
+                    eprintln!("Screen capture: NV12 buffer missing planes");
+                    return;
+                };
+                frame
+            }
         };
 
         // Track frame count
@@ -108,6 +131,39 @@ impl SCStreamOutputTrait for FrameHandler {
     }
 }
 
+/// Copy a biplanar 420v buffer into one NV12 frame
+///
+/// The Y and UV planes may have different row padding, so rows are packed
+/// to `width` bytes.
+fn nv12_frame(
+    buffer: &CVPixelBuffer,
+    guard: &CVPixelBufferLockGuard<'_>,
+    pool: &FramePool,
+    timestamp: std::time::Duration,
+) -> Option<ScreenFrame> {
+    let width = buffer.width() as usize;
+    let height = buffer.height() as usize;
+    let mut data = pool.take(width * (height + height / 2));
+
+    for (plane, rows) in [(0, height), (1, height / 2)] {
+        let plane_data = guard.plane_data(plane)?;
+        let stride = buffer.bytes_per_row_of_plane(plane);
+        for y in 0..rows {
+            let start = y * stride;
+            data.extend_from_slice(plane_data.get(start..start + width)?);
+        }
+    }
+
+    Some(ScreenFrame {
+        data: pool.share(data),
+        width: width as u32,
+        height: height as u32,
+        stride: width,
+        timestamp,
+        pixel_format: CapturePixelFormat::Nv12,
+    })
+}
+
 impl ScreenCapture {
     pub fn new(config: ScreenCaptureConfig) -> Result<Self, String> {
         let content = SCShareableContent::get()
@@ -124,6 +180,8 @@ impl ScreenCapture {
             width: display.width(),
             height: display.height(),
             running: Arc::new(Mutex::new(false)),
+            started_at: Mutex::new(None),
+            heartbeats: Arc::new(AtomicU64::new(0)),
             frame_sender: Some(sender),
             frame_receiver: Some(receiver),
             stream: Arc::new(Mutex::new(None)),
@@ -131,6 +189,11 @@ impl ScreenCapture {
         })
     }
 
+    /// Get the capture dimensions
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
     pub fn take_receiver(&mut self) -> Option<Receiver<ScreenFrame>> {
         self.frame_receiver.take()
     }
@@ -161,11 +224,17 @@ impl ScreenCapture {
             .with_excluding_windows(&[])
             .build();
 
+        // 420v is video-range BT.709 NV12, which the encoder converts without a color pass
+        let pixel_format = match self.config.pixel_format {
+            CapturePixelFormat::Bgra => PixelFormat::BGRA,
+            CapturePixelFormat::Nv12 => PixelFormat::YCbCr_420v,
+        };
+
         let frame_interval = CMTime::new(1, self.config.fps as i32);
         let stream_config = SCStreamConfiguration::new()
             .with_width(self.width)
             .with_height(self.height)
-            .with_pixel_format(PixelFormat::BGRA)
+            .with_pixel_format(pixel_format)
             .with_minimum_frame_interval(&frame_interval)
             .with_shows_cursor(true);
 
@@ -179,12 +248,18 @@ impl ScreenCapture {
                 .frame_sender
                 .clone()
                 .ok_or("Frame sender not available")?,
-            start_time: Instant::now(),
+            pixel_format: self.config.pixel_format,
+            pool: FramePool::new(),
+            start_time: *self.started_at.lock().get_or_insert_with(Instant::now),
             frame_count: self.frame_count.clone(),
+            heartbeats: self.heartbeats.clone(),
             empty_buffer_count: AtomicU64::new(0),
         };
 
-        stream.add_output_handler(handler, SCStreamOutputType::Screen);
+        // Frames are delivered on a dispatch queue rather than a thread of
+        // ours, so the queue carries the capture QoS class
+        let queue = DispatchQueue::new("asmr-recorder.screen-capture", DispatchQoS::UserInteractive);
+        stream.add_output_handler_with_queue(handler, SCStreamOutputType::Screen, Some(&queue));
         stream
             .start_capture()
             .map_err(|e| format!("Failed to start capture: {}", e))?;
@@ -193,8 +268,8 @@ impl ScreenCapture {
         *stream_guard = Some(stream);
 
         println!(
-            "Screen capture started: {}x{} @ {}fps",
-            self.width, self.height, self.config.fps
+            "Screen capture started: {}x{} @ {}fps ({:?})",
+            self.width, self.height, self.config.fps, self.config.pixel_format
         );
 
         Ok(())
@@ -213,4 +288,16 @@ impl ScreenCapture {
         println!("Screen capture stopped: {} total frames captured", total_frames);
     }
 
+    /// Signs of life so far: frames, and polls that found the screen unchanged
+    pub fn heartbeats(&self) -> u64 {
+        self.heartbeats.load(Ordering::Relaxed)
+    }
+
+    /// Stop and start again, e.g. after frames stopped coming; frames keep
+    /// their timestamps from the first start
+    pub fn restart(&mut self) -> Result<(), String> {
+        self.stop();
+        self.start()
+    }
+
 }
@@ -1,18 +1,26 @@
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use crossbeam_channel::{bounded, Receiver, Sender};
 use parking_lot::Mutex;
-use windows_capture::dxgi_duplication_api::DxgiDuplicationApi;
+use windows_capture::dxgi_duplication_api::{self, DxgiDuplicationApi};
 use windows_capture::monitor::Monitor;
 
 use super::{ScreenCaptureConfig, ScreenFrame};
+use crate::frame_pool::FramePool;
+use crate::recording::CapturePixelFormat;
+use crate::thread_priority::{self, ThreadRole};
 
 pub struct ScreenCapture {
     config: ScreenCaptureConfig,
     width: u32,
     height: u32,
     running: Arc<Mutex<bool>>,
+    /// When capture first started; timestamps count from here
+    started_at: Mutex<Option<Instant>>,
+    /// Frames and "nothing changed" results, for the stall watchdog
+    heartbeats: Arc<AtomicU64>,
     frame_sender: Option<Sender<ScreenFrame>>,
     frame_receiver: Option<Receiver<ScreenFrame>>,
 }
@@ -26,6 +34,11 @@ impl ScreenCapture {
         let width = monitor.width();
         let height = monitor.height();
 
+        // Desktop duplication only hands out B8G8R8A8 surfaces
+        if config.pixel_format == CapturePixelFormat::Nv12 {
+            println!("NV12 screen capture is not available with DXGI duplication, using BGRA");
+        }
+
         let (sender, receiver) = bounded(5);
 
         Ok(Self {
@@ -33,11 +46,14 @@ impl ScreenCapture {
             width,
             height,
             running: Arc::new(Mutex::new(false)),
+            started_at: Mutex::new(None),
+            heartbeats: Arc::new(AtomicU64::new(0)),
             frame_sender: Some(sender),
             frame_receiver: Some(receiver),
         })
     }
 
+    /// Get the capture dimensions
     pub fn dimensions(&self) -> (u32, u32) {
         (self.width, self.height)
     }
@@ -61,11 +77,15 @@ impl ScreenCapture {
             .ok_or("Frame sender not available")?;
         let fps = self.config.fps;
         let display_index = self.config.display_index;
+        let start_time = *self.started_at.lock().get_or_insert_with(Instant::now);
+        let heartbeats = self.heartbeats.clone();
         let width = self.width;
         let height = self.height;
 
         std::thread::spawn(move || {
-            if let Err(e) = capture_loop(running_clone, sender, fps, display_index, width, height)
+            thread_priority::elevate(ThreadRole::Capture);
+            if let Err(e) =
+                capture_loop(running_clone, sender, fps, display_index, width, height, start_time, heartbeats)
             {
                 eprintln!("Screen capture error: {}", e);
             }
@@ -82,8 +102,25 @@ impl ScreenCapture {
     pub fn is_running(&self) -> bool {
         *self.running.lock()
     }
+
+    /// Signs of life so far: frames, and polls that found the screen unchanged
+    pub fn heartbeats(&self) -> u64 {
+        self.heartbeats.load(Ordering::Relaxed)
+    }
+
+    /// Stop and start again, e.g. after frames stopped coming
+    ///
+    /// Frames keep their timestamps from the first start. A capture thread
+    /// stuck in the OS keeps its own (now cleared) running flag and exits
+    /// whenever it returns.
+    pub fn restart(&mut self) -> Result<(), String> {
+        self.stop();
+        self.running = Arc::new(Mutex::new(false));
+        self.start()
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn capture_loop(
     running: Arc<Mutex<bool>>,
     sender: Sender<ScreenFrame>,
@@ -91,6 +128,8 @@ fn capture_loop(
     display_index: usize,
     width: u32,
     height: u32,
+    start_time: Instant,
+    heartbeats: Arc<AtomicU64>,
 ) -> Result<(), String> {
     let monitor = Monitor::from_index(display_index)
         .or_else(|_| Monitor::primary())
@@ -100,7 +139,8 @@ fn capture_loop(
         DxgiDuplicationApi::new(monitor).map_err(|e| format!("DXGI init failed: {}", e))?;
 
     let frame_duration = Duration::from_secs_f64(1.0 / fps as f64);
-    let start_time = Instant::now();
+    // Captured frames are copied into recycled buffers
+    let pool = FramePool::new();
 
     println!(
         "Screen capture started: {}x{} @ {}fps",
@@ -114,18 +154,23 @@ fn capture_loop(
             Ok(mut frame) => {
                 if let Ok(buffer) = frame.buffer() {
                     let screen_frame = ScreenFrame {
-                        data: buffer.to_vec(),
+                        data: pool.copy(&buffer),
                         width: frame.width(),
                         height: frame.height(),
                         stride: frame.width() as usize * 4,
                         timestamp: start_time.elapsed(),
+                        pixel_format: CapturePixelFormat::Bgra,
                     };
                     let _ = sender.try_send(screen_frame);
                 }
+                heartbeats.fetch_add(1, Ordering::Relaxed);
             }
-            Err(_) => {
-                std::thread::sleep(Duration::from_millis(10));
+            // The screen didn't change in time, which is still a sign of life
+            Err(dxgi_duplication_api::Error::Timeout) => {
+                heartbeats.fetch_add(1, Ordering::Relaxed);
             }
+            // Anything else (e.g. access lost after a mode change) is not
+            Err(_) => std::thread::sleep(Duration::from_millis(10)),
         }
 
         let elapsed = frame_start.elapsed();
@@ -0,0 +1,305 @@
+//! Pipeline Self-Test
+//!
+//! A one-click answer to "will recording work on this machine". The
+//! self-test records `SELFTEST_MS` from the synthetic sources with a
+//! recording manager of its own, so it needs no camera, microphone or
+//! permission. The compositor, mixer and encoder are the ones a real
+//! recording would use. The finished file is then read back, and a video
+//! frame and the whole audio track are decoded. Each step is reported with
+//! what it found.
+//!
+//! The file goes to the recordings folder, so that folder's free space and
+//! permissions are part of the test. It is removed afterwards. Run the
+//! self-test while nothing is recording; it is refused otherwise.
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tauri::command;
+
+use crate::recording::{RecordingError, RecordingState};
+use crate::ExternalRecorderState;
+
+/// Length of the test recording
+#[cfg_attr(not(any(feature = "ffmpeg", test)), allow(dead_code))]
+pub const SELFTEST_MS: u64 = 5000;
+
+/// How far the file's duration may be from `SELFTEST_MS`
+#[cfg_attr(not(any(feature = "ffmpeg", test)), allow(dead_code))]
+const DURATION_TOLERANCE_MS: u64 = 500;
+
+/// Longest the recording may take to start, stop or finish its file
+#[cfg(feature = "ffmpeg")]
+const STEP_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// One step of the self-test
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelfTestStep {
+    pub name: String,
+    pub passed: bool,
+    /// What the step found, or why it failed
+    pub detail: String,
+}
+
+/// Result of `run_pipeline_selftest`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelfTestReport {
+    /// Every step passed
+    pub passed: bool,
+    /// Steps in the order they ran; the test ends at the first one that
+    /// leaves nothing to check further
+    pub steps: Vec<SelfTestStep>,
+    /// Duration the test file reported
+    pub duration_ms: Option<u64>,
+    /// How long the self-test took
+    pub elapsed_ms: u64,
+}
+
+#[cfg_attr(not(any(feature = "ffmpeg", test)), allow(dead_code))]
+impl SelfTestReport {
+    /// Record a step; returns whether it passed
+    fn step(&mut self, name: &str, result: Result<String, String>) -> bool {
+        let passed = result.is_ok();
+        self.steps.push(SelfTestStep {
+            name: name.to_string(),
+            passed,
+            detail: result.unwrap_or_else(|e| e),
+        });
+        self.passed = self.steps.iter().all(|s| s.passed);
+        passed
+    }
+}
+
+/// Check the test file's reported duration against `SELFTEST_MS`
+#[cfg_attr(not(any(feature = "ffmpeg", test)), allow(dead_code))]
+fn check_duration(duration_ms: Option<u64>) -> Result<String, String> {
+    match duration_ms {
+        Some(ms) if ms.abs_diff(SELFTEST_MS) <= DURATION_TOLERANCE_MS => {
+            Ok(format!("The file is {:.2}s long", ms as f64 / 1000.0))
+        }
+        Some(ms) => Err(format!(
+            "The file is {:.2}s long, {:.2}s were recorded",
+            ms as f64 / 1000.0,
+            SELFTEST_MS as f64 / 1000.0
+        )),
+        None => Err("The file reports no duration".to_string()),
+    }
+}
+
+/// Record, finalize, verify and decode a synthetic recording
+#[cfg(feature = "ffmpeg")]
+pub fn run_selftest() -> Result<SelfTestReport, String> {
+    let started = std::time::Instant::now();
+    let mut report = SelfTestReport::default();
+    run_steps(&mut report);
+    report.elapsed_ms = started.elapsed().as_millis() as u64;
+    Ok(report)
+}
+
+#[cfg(not(feature = "ffmpeg"))]
+pub fn run_selftest() -> Result<SelfTestReport, String> {
+    Err("Pipeline self-test requires FFmpeg support".to_string())
+}
+
+/// Run the steps until one leaves nothing further to check
+#[cfg(feature = "ffmpeg")]
+fn run_steps(report: &mut SelfTestReport) {
+    use crate::manager::RecordingManager;
+    use crate::recording::RecordingConfig;
+    use crate::synthetic_sources::SyntheticSourcesConfig;
+    use crate::thumbnail::thumbnail_path;
+    use std::path::PathBuf;
+    use std::time::{Duration, Instant};
+
+    let output_path = match selftest_output_path() {
+        Ok(path) => path,
+        Err(e) => {
+            report.step("Recordings folder", Err(e));
+            return;
+        }
+    };
+
+    // Every source, so the webcam overlay and the mix are exercised too
+    let mut manager = RecordingManager::new();
+    let config = RecordingConfig {
+        capture_webcam: true,
+        capture_system_audio: true,
+        output_path: Some(output_path.clone()),
+        synthetic_sources: SyntheticSourcesConfig {
+            enabled: true,
+            width: 1280,
+            height: 720,
+            ..SyntheticSourcesConfig::default()
+        },
+        ..RecordingConfig::default()
+    };
+    let start = manager
+        .start(config)
+        .map_err(|e| e.to_string())
+        .and_then(|result| {
+            manager.stop_at(SELFTEST_MS)?;
+            Ok(result.output_path)
+        });
+    let output_path = match start {
+        Ok(Some(path)) => path,
+        Ok(None) => output_path,
+        Err(e) => {
+            report.step("Start recording", Err(e));
+            let _ = manager.stop();
+            return;
+        }
+    };
+    report.step("Start recording", Ok(format!("Recording to {}", output_path.display())));
+
+    // The recording ends by itself at the scheduled stop
+    let deadline = Instant::now() + Duration::from_millis(SELFTEST_MS) + STEP_TIMEOUT;
+    let mut status = manager.status();
+    while status.is_recording && status.error.is_none() && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(100));
+        status = manager.status();
+    }
+    let record = match status.error {
+        Some(e) => Err(e),
+        None if status.is_recording => Err("The recording didn't stop at its scheduled end".to_string()),
+        None => Ok(format!(
+            "{} frames in {:.1}s",
+            status.frame_count,
+            status.duration_ms as f64 / 1000.0
+        )),
+    };
+    report.step("Record", record);
+
+    let finalized = manager.stop();
+    let verification = manager.take_output_verification();
+    let output_path = match finalized {
+        Ok(path) => {
+            report.step("Finalize file", Ok("The file was finalized".to_string()));
+            PathBuf::from(path)
+        }
+        Err(e) => {
+            report.step("Finalize file", Err(e.to_string()));
+            remove_selftest_files(&output_path);
+            return;
+        }
+    };
+
+    let verify = match verification {
+        Some(v) if !v.verified => Err(v.issues.join("; ")),
+        Some(v) => {
+            report.duration_ms = v.duration_ms;
+            check_duration(v.duration_ms)
+        }
+        None => Err("The file could not be read back".to_string()),
+    };
+    report.step("Verify file", verify);
+
+    // The thumbnail is the last thing written after the file is finished
+    let thumbnail = thumbnail_path(&output_path);
+    let deadline = Instant::now() + STEP_TIMEOUT;
+    while !thumbnail.exists() && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(100));
+    }
+
+    let video = crate::thumbnail::extract_frames_at(&output_path, &[SELFTEST_MS / 2], None)
+        .and_then(|frames| frames.into_iter().next().ok_or_else(|| "No frame was decoded".to_string()))
+        .map(|frame| {
+            format!(
+                "Decoded a {}x{} frame at {:.1}s",
+                frame.width,
+                frame.height,
+                frame.at_ms as f64 / 1000.0
+            )
+        });
+    report.step("Decode video", video);
+    report.step("Decode audio", decode_audio(&output_path));
+
+    remove_selftest_files(&output_path);
+}
+
+/// A file name in the first recordings folder that exists
+#[cfg(feature = "ffmpeg")]
+fn selftest_output_path() -> Result<std::path::PathBuf, String> {
+    let dir = crate::output_paths::allowed_output_dirs()
+        .into_iter()
+        .find(|dir| dir.is_dir())
+        .ok_or("No recordings folder exists to write the test file to")?;
+    Ok(dir.join(format!("asmr_selftest_{}.mp4", std::process::id())))
+}
+
+/// Decode the whole audio track and check it isn't silent
+#[cfg(feature = "ffmpeg")]
+fn decode_audio(path: &std::path::Path) -> Result<String, String> {
+    let reader = crate::media_file::AudioTrackReader::open(path)?;
+    let rate = reader.rate();
+    let channels = reader.channels().max(1) as usize;
+    let mut samples = 0usize;
+    let mut peak = 0.0f32;
+    reader.read_all(|chunk| {
+        samples += chunk.len();
+        peak = chunk.iter().fold(peak, |peak, s| peak.max(s.abs()));
+    })?;
+
+    if samples == 0 {
+        return Err("The audio track holds no samples".to_string());
+    }
+    if peak < 0.01 {
+        return Err("The audio track is silent".to_string());
+    }
+    Ok(format!(
+        "Decoded {:.1}s of {} Hz audio (peak {:.2})",
+        (samples / channels) as f64 / rate as f64,
+        rate,
+        peak
+    ))
+}
+
+#[cfg(feature = "ffmpeg")]
+fn remove_selftest_files(path: &std::path::Path) {
+    for file in [path.to_path_buf(), crate::thumbnail::thumbnail_path(path)] {
+        match std::fs::remove_file(&file) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => eprintln!("Failed to remove self-test file {}: {}", file.display(), e),
+        }
+    }
+}
+
+/// Tauri command: Record a few seconds from synthetic sources and check the
+/// file, reporting each step
+///
+/// Takes about `SELFTEST_MS` plus the time to finish and decode the file.
+#[command]
+pub async fn run_pipeline_selftest(
+    state: tauri::State<'_, Arc<RecordingState>>,
+    external: tauri::State<'_, Arc<ExternalRecorderState>>,
+) -> Result<SelfTestReport, RecordingError> {
+    if state.status.read().is_recording || external.recorder.lock().status().is_recording {
+        return Err(RecordingError::AlreadyRecording);
+    }
+    tauri::async_runtime::spawn_blocking(run_selftest)
+        .await
+        .map_err(|e| format!("Pipeline self-test failed: {}", e))?
+        .map_err(RecordingError::Failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_fails_on_any_step_and_checks_duration() {
+        let mut report = SelfTestReport::default();
+        assert!(report.step("Start recording", Ok("Recording".to_string())));
+        assert!(report.passed);
+        assert!(!report.step("Verify file", check_duration(Some(3200))));
+        assert!(report.step("Decode audio", Ok("Decoded".to_string())));
+        assert!(!report.passed);
+        assert_eq!(report.steps[1].detail, "The file is 3.20s long, 5.00s were recorded");
+
+        assert!(check_duration(Some(SELFTEST_MS + 40)).is_ok());
+        assert!(check_duration(Some(SELFTEST_MS - 600)).is_err());
+        assert!(check_duration(None).is_err());
+    }
+}
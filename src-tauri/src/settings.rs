@@ -0,0 +1,88 @@
+//! Persistent Application Settings
+//!
+//! Settings are stored as JSON in the platform config directory under the app
+//! identifier (the same location Tauri uses for `app_config_dir`).
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Bundle identifier from tauri.conf.json
+const APP_IDENTIFIER: &str = "com.asmr-recorder.app";
+
+/// Device IDs used by the last successful recording
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LastUsedDevices {
+    pub microphone: Option<String>,
+    pub webcam: Option<String>,
+    pub screen: Option<String>,
+}
+
+/// Settings persisted between app launches
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AppSettings {
+    /// Devices to preselect at next start
+    pub last_devices: LastUsedDevices,
+    /// Directories the user allowed recordings to be written to, besides
+    /// the videos directory
+    pub allowed_output_dirs: Vec<PathBuf>,
+}
+
+/// Path of the settings file
+pub fn settings_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(APP_IDENTIFIER).join("settings.json"))
+}
+
+impl AppSettings {
+    /// Load settings from disk, falling back to defaults if missing or unreadable
+    pub fn load() -> Self {
+        let Some(path) = settings_path() else {
+            return Self::default();
+        };
+
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                eprintln!("Ignoring invalid settings file {:?}: {}", path, e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// Write settings to disk
+    pub fn save(&self) -> Result<(), String> {
+        let path = settings_path().ok_or("Could not find config directory")?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create settings directory: {}", e))?;
+        }
+
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+        std::fs::write(&path, contents).map_err(|e| format!("Failed to write settings: {}", e))
+    }
+
+    /// Load, modify and save settings in one step
+    pub fn update(modify: impl FnOnce(&mut AppSettings)) -> Result<(), String> {
+        let mut settings = Self::load();
+        modify(&mut settings);
+        settings.save()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_settings_use_defaults() {
+        let settings: AppSettings =
+            serde_json::from_str(r#"{"lastDevices":{"microphone":"USB Mic"}}"#).unwrap();
+        assert_eq!(settings.last_devices.microphone.as_deref(), Some("USB Mic"));
+        assert!(settings.last_devices.webcam.is_none());
+
+        let empty: AppSettings = serde_json::from_str("{}").unwrap();
+        assert!(empty.last_devices.screen.is_none());
+    }
+}
@@ -0,0 +1,278 @@
+//! Sidecar WAV Export
+//!
+//! Writes the mixed output or the processed microphone signal to an
+//! uncompressed WAV file next to the video while recording, giving an audio
+//! master for post-production without extracting it from the container.
+//!
+//! Samples are stored as 32-bit float, the mixer's own format, or as 24-bit
+//! integers. The RIFF header limits the file to 4 GiB (about 3 hours of
+//! 48kHz stereo float); audio past that point is only in the video.
+
+use crossbeam_channel::{bounded, Receiver, RecvTimeoutError, Sender};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::audio::AudioChunk;
+use crate::recording::AudioBitDepth;
+
+/// Bytes before the sample data
+const HEADER_LEN: u32 = 58;
+
+/// Signal written to the sidecar file
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SidecarWavSource {
+    /// The final mix, as sent to the encoder
+    #[default]
+    Mixed,
+    /// The microphone alone, after volume and format conversion
+    Mic,
+}
+
+/// Sidecar WAV configuration
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SidecarWavConfig {
+    /// Write `<recording>.wav` while recording
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub source: SidecarWavSource,
+}
+
+/// Path of the sidecar file for a recording
+pub fn sidecar_wav_path(output_path: &Path) -> PathBuf {
+    output_path.with_extension("wav")
+}
+
+/// 24-bit integer or 32-bit float WAV file, finalized when closed
+pub struct WavWriter {
+    writer: BufWriter<File>,
+    channels: u16,
+    bit_depth: AudioBitDepth,
+    data_len: u32,
+    full: bool,
+}
+
+impl WavWriter {
+    /// Create the file and write a header for an empty recording
+    pub fn create(
+        path: &Path,
+        sample_rate: u32,
+        channels: u16,
+        bit_depth: AudioBitDepth,
+    ) -> Result<Self, String> {
+        let file = File::create(path)
+            .map_err(|e| format!("Failed to create WAV file {}: {}", path.display(), e))?;
+        let mut wav = Self {
+            writer: BufWriter::new(file),
+            channels,
+            bit_depth,
+            data_len: 0,
+            full: false,
+        };
+        wav.write_header(sample_rate)
+            .map_err(|e| format!("Failed to write WAV file: {}", e))?;
+        Ok(wav)
+    }
+
+    fn bytes_per_sample(&self) -> u16 {
+        self.bit_depth.bits() as u16 / 8
+    }
+
+    fn write_header(&mut self, sample_rate: u32) -> std::io::Result<()> {
+        let block_align = self.channels * self.bytes_per_sample();
+        // WAVE_FORMAT_PCM or WAVE_FORMAT_IEEE_FLOAT
+        let format_tag: u16 = match self.bit_depth {
+            AudioBitDepth::Int24 => 1,
+            AudioBitDepth::Float32 => 3,
+        };
+        let bits = self.bit_depth.bits() as u16;
+        let w = &mut self.writer;
+        w.write_all(b"RIFF")?;
+        w.write_all(&(HEADER_LEN - 8).to_le_bytes())?;
+        w.write_all(b"WAVE")?;
+        // Both formats use the extended fmt chunk so the layout is fixed
+        w.write_all(b"fmt ")?;
+        w.write_all(&18u32.to_le_bytes())?;
+        w.write_all(&format_tag.to_le_bytes())?;
+        w.write_all(&self.channels.to_le_bytes())?;
+        w.write_all(&sample_rate.to_le_bytes())?;
+        w.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+        w.write_all(&block_align.to_le_bytes())?;
+        w.write_all(&bits.to_le_bytes())?;
+        w.write_all(&0u16.to_le_bytes())?;
+        // Required for float, allowed for PCM
+        w.write_all(b"fact")?;
+        w.write_all(&4u32.to_le_bytes())?;
+        w.write_all(&0u32.to_le_bytes())?;
+        w.write_all(b"data")?;
+        w.write_all(&0u32.to_le_bytes())
+    }
+
+    /// Append interleaved samples
+    pub fn write_samples(&mut self, samples: &[f32]) -> Result<(), String> {
+        let len = samples.len() as u64 * self.bytes_per_sample() as u64;
+        if self.data_len as u64 + len > (u32::MAX - HEADER_LEN) as u64 {
+            if !self.full {
+                eprintln!("Sidecar WAV reached the 4 GiB limit; later audio is only in the video");
+                self.full = true;
+            }
+            return Ok(());
+        }
+
+        for sample in samples {
+            let written = match self.bit_depth {
+                AudioBitDepth::Float32 => self.writer.write_all(&sample.to_le_bytes()),
+                AudioBitDepth::Int24 => {
+                    let value = (sample.clamp(-1.0, 1.0) * 8_388_607.0).round() as i32;
+                    self.writer.write_all(&value.to_le_bytes()[..3])
+                }
+            };
+            written.map_err(|e| format!("Failed to write WAV file: {}", e))?;
+        }
+        self.data_len += len as u32;
+        Ok(())
+    }
+
+    /// Fill in the chunk sizes and close the file
+    pub fn finalize(mut self) -> Result<(), String> {
+        let frames = self.data_len / (self.channels.max(1) as u32 * self.bytes_per_sample() as u32);
+        let patch = |w: &mut BufWriter<File>, offset: u64, value: u32| -> std::io::Result<()> {
+            w.seek(SeekFrom::Start(offset))?;
+            w.write_all(&value.to_le_bytes())
+        };
+        patch(&mut self.writer, 4, HEADER_LEN - 8 + self.data_len)
+            .and_then(|_| patch(&mut self.writer, 46, frames))
+            .and_then(|_| patch(&mut self.writer, 54, self.data_len))
+            .and_then(|_| self.writer.flush())
+            .map_err(|e| format!("Failed to finalize WAV file: {}", e))
+    }
+}
+
+/// Background writer fed by the mixer's sidecar tap
+pub struct SidecarWav {
+    running: Arc<Mutex<bool>>,
+    sender: Sender<AudioChunk>,
+    source: SidecarWavSource,
+}
+
+impl SidecarWav {
+    /// Create `<recording>.wav` and start writing
+    ///
+    /// `sample_rate` and `channels` describe the chunks the mixer will send.
+    pub fn start(
+        config: &SidecarWavConfig,
+        output_path: &Path,
+        sample_rate: u32,
+        channels: u16,
+        bit_depth: AudioBitDepth,
+    ) -> Result<Self, String> {
+        let path = sidecar_wav_path(output_path);
+        let wav = WavWriter::create(&path, sample_rate, channels, bit_depth)?;
+
+        // Generous queue: a slow disk must not make the mixer drop audio
+        let (sender, receiver) = bounded(500);
+        let running = Arc::new(Mutex::new(true));
+
+        let running_clone = running.clone();
+        std::thread::spawn(move || {
+            wav_loop(running_clone, receiver, wav);
+        });
+
+        println!(
+            "Sidecar WAV started: {} ({:?}, {}-bit)",
+            path.display(),
+            config.source,
+            bit_depth.bits()
+        );
+
+        Ok(Self {
+            running,
+            sender,
+            source: config.source,
+        })
+    }
+
+    /// Tap for the mixer: the signal to record and where to send it
+    pub fn tap(&self) -> (SidecarWavSource, Sender<AudioChunk>) {
+        (self.source, self.sender.clone())
+    }
+
+    /// Stop writing and finalize the file
+    pub fn stop(&self) {
+        *self.running.lock() = false;
+    }
+}
+
+impl Drop for SidecarWav {
+    fn drop(&mut self) {
+        *self.running.lock() = false;
+    }
+}
+
+fn wav_loop(running: Arc<Mutex<bool>>, receiver: Receiver<AudioChunk>, mut wav: WavWriter) {
+    while *running.lock() {
+        let chunk = match receiver.recv_timeout(Duration::from_millis(100)) {
+            Ok(chunk) => chunk,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        if let Err(e) = wav.write_samples(&chunk.samples) {
+            eprintln!("{}", e);
+            break;
+        }
+    }
+
+    // Audio still queued when the recording stopped belongs in the file
+    for chunk in receiver.try_iter() {
+        if wav.write_samples(&chunk.samples).is_err() {
+            break;
+        }
+    }
+
+    match wav.finalize() {
+        Ok(()) => println!("Sidecar WAV finished"),
+        Err(e) => eprintln!("{}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wav_writer_header() {
+        let path = std::env::temp_dir().join(format!("asmr_sidecar_{}.wav", std::process::id()));
+        let mut wav = WavWriter::create(&path, 48000, 2, AudioBitDepth::Float32).unwrap();
+        wav.write_samples(&[0.5, -0.5, 0.25, -0.25]).unwrap();
+        wav.finalize().unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let u32_at = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        assert_eq!(bytes.len(), HEADER_LEN as usize + 16);
+        assert_eq!(&bytes[..4], b"RIFF");
+        assert_eq!(u32_at(4), HEADER_LEN - 8 + 16);
+        assert_eq!(u32_at(24), 48000);
+        assert_eq!(u32_at(46), 2);
+        assert_eq!(u32_at(54), 16);
+        assert_eq!(&bytes[58..62], &0.5f32.to_le_bytes());
+
+        let mut wav = WavWriter::create(&path, 44100, 1, AudioBitDepth::Int24).unwrap();
+        wav.write_samples(&[1.0, -1.0]).unwrap();
+        wav.finalize().unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(u16::from_le_bytes([bytes[20], bytes[21]]), 1);
+        assert_eq!(u16::from_le_bytes([bytes[34], bytes[35]]), 24);
+        assert_eq!(&bytes[58..], &[0xff, 0xff, 0x7f, 0x01, 0x00, 0x80]);
+    }
+}
@@ -0,0 +1,508 @@
+//! Silence Detection and Trimming
+//!
+//! Scans the audio track of a finished recording in 10 ms blocks and reports
+//! where it is silent: before the first sound, after the last, and optionally
+//! long pauses in between. The spans can be written next to the recording as
+//! markers for the editor, or the leading and trailing silence trimmed off.
+//!
+//! Trimming copies packets without re-encoding, so the start is cut at the
+//! last video keyframe before the sound begins and may keep a little more
+//! silence than the padding asks for.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::command;
+
+use crate::output_paths::{allowed_output_dirs, check_output_path};
+use crate::recording::RecordingError;
+#[cfg(feature = "ffmpeg")]
+use crate::media_file::{add_copy_stream, copy_chapters, create_output, open_input, rewrite, suffix, write_header, AudioTrackReader};
+
+/// Length of one analysis block
+#[cfg(any(feature = "ffmpeg", test))]
+const BLOCK_MS: u64 = 10;
+
+/// Quietest accepted threshold
+pub const MIN_THRESHOLD_DB: f32 = -90.0;
+
+/// Loudest accepted threshold
+pub const MAX_THRESHOLD_DB: f32 = -20.0;
+
+/// What to do with the silence when a recording is finalized
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SilenceAction {
+    /// No analysis
+    #[default]
+    Off,
+    /// Write the silent spans to `<recording>.silence.json`
+    Markers,
+    /// Cut leading and trailing silence from the file
+    Trim,
+}
+
+/// Silence detection settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SilenceConfig {
+    /// Applied when the recording is finalized
+    #[serde(default)]
+    pub on_finalize: SilenceAction,
+    /// Blocks whose sample peak stays below this are silent (dBFS)
+    #[serde(default = "default_threshold_db")]
+    pub threshold_db: f32,
+    /// Also report silences between the first and last sound
+    #[serde(default)]
+    pub detect_internal: bool,
+    /// Shortest internal silence reported
+    #[serde(default = "default_min_internal_ms")]
+    pub min_internal_ms: u64,
+    /// Silence kept before the first and after the last sound when trimming
+    #[serde(default = "default_padding_ms")]
+    pub padding_ms: u64,
+}
+
+fn default_threshold_db() -> f32 {
+    -50.0
+}
+
+fn default_min_internal_ms() -> u64 {
+    3000
+}
+
+fn default_padding_ms() -> u64 {
+    250
+}
+
+impl Default for SilenceConfig {
+    fn default() -> Self {
+        Self {
+            on_finalize: SilenceAction::Off,
+            threshold_db: default_threshold_db(),
+            detect_internal: false,
+            min_internal_ms: default_min_internal_ms(),
+            padding_ms: default_padding_ms(),
+        }
+    }
+}
+
+impl SilenceConfig {
+    /// Check the threshold is in the accepted range
+    pub fn validate(&self) -> Result<(), String> {
+        if !(MIN_THRESHOLD_DB..=MAX_THRESHOLD_DB).contains(&self.threshold_db) {
+            return Err(format!(
+                "Silence threshold must be {} to {} dBFS (got {})",
+                MIN_THRESHOLD_DB, MAX_THRESHOLD_DB, self.threshold_db
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Silent span, in milliseconds from the start of the recording
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SilenceSpan {
+    pub start_ms: u64,
+    pub end_ms: u64,
+}
+
+/// Silence found in a recording
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SilenceReport {
+    /// Length of the analyzed audio
+    pub duration_ms: u64,
+    /// Silence before the first sound (the whole recording if it is silent)
+    pub leading: Option<SilenceSpan>,
+    /// Silence after the last sound
+    pub trailing: Option<SilenceSpan>,
+    /// Long silences in between, if requested
+    pub internal: Vec<SilenceSpan>,
+    /// Part of the original recording left after trimming
+    pub kept: Option<SilenceSpan>,
+}
+
+#[cfg(any(feature = "ffmpeg", test))]
+impl SilenceReport {
+    /// Range to keep, with `padding_ms` of silence on either side of the
+    /// sound; None if there is no sound at all
+    pub fn sound_range(&self, padding_ms: u64) -> Option<SilenceSpan> {
+        let start = match self.leading {
+            Some(span) if span.end_ms >= self.duration_ms => return None,
+            Some(span) => span.end_ms.saturating_sub(padding_ms),
+            None => 0,
+        };
+        let end = match self.trailing {
+            Some(span) => (span.start_ms + padding_ms).min(self.duration_ms),
+            None => self.duration_ms,
+        };
+        Some(SilenceSpan {
+            start_ms: start,
+            end_ms: end,
+        })
+    }
+}
+
+/// Path of the marker file for a recording
+pub fn markers_path(output_path: &Path) -> PathBuf {
+    output_path.with_extension("silence.json")
+}
+
+/// Finds silent runs in interleaved audio
+#[cfg(any(feature = "ffmpeg", test))]
+pub struct SilenceDetector {
+    threshold: f32,
+    channels: usize,
+    block_samples: usize,
+    /// Samples of the current block seen so far, and their peak
+    block_filled: usize,
+    block_peak: f32,
+    blocks: u64,
+    /// First block of the current silent run
+    run_start: Option<u64>,
+    /// Silent runs that ended, in blocks
+    runs: Vec<(u64, u64)>,
+}
+
+#[cfg(any(feature = "ffmpeg", test))]
+impl SilenceDetector {
+    pub fn new(threshold_db: f32, sample_rate: u32, channels: u16) -> Self {
+        let channels = channels.max(1) as usize;
+        Self {
+            threshold: 10f32.powf(threshold_db / 20.0),
+            channels,
+            block_samples: (sample_rate as u64 * BLOCK_MS / 1000).max(1) as usize * channels,
+            block_filled: 0,
+            block_peak: 0.0,
+            blocks: 0,
+            run_start: None,
+            runs: Vec::new(),
+        }
+    }
+
+    /// Feed interleaved samples
+    pub fn process(&mut self, samples: &[f32]) {
+        for sample in samples {
+            self.block_peak = self.block_peak.max(sample.abs());
+            self.block_filled += 1;
+            if self.block_filled == self.block_samples {
+                self.end_block();
+            }
+        }
+    }
+
+    fn end_block(&mut self) {
+        let silent = self.block_peak < self.threshold;
+        match (silent, self.run_start) {
+            (true, None) => self.run_start = Some(self.blocks),
+            (false, Some(start)) => {
+                self.runs.push((start, self.blocks));
+                self.run_start = None;
+            }
+            _ => {}
+        }
+        self.blocks += 1;
+        self.block_filled = 0;
+        self.block_peak = 0.0;
+    }
+
+    /// Report the silence found, counting a final partial block
+    pub fn finish(mut self, config: &SilenceConfig) -> SilenceReport {
+        // Whole frames only: a trailing partial frame doesn't count
+        if self.block_filled >= self.channels {
+            self.end_block();
+        }
+        if let Some(start) = self.run_start.take() {
+            self.runs.push((start, self.blocks));
+        }
+
+        let duration_ms = self.blocks * BLOCK_MS;
+        let mut spans: Vec<SilenceSpan> = self
+            .runs
+            .iter()
+            .map(|&(start, end)| SilenceSpan {
+                start_ms: start * BLOCK_MS,
+                end_ms: end * BLOCK_MS,
+            })
+            .collect();
+
+        let leading = match spans.first() {
+            Some(span) if span.start_ms == 0 => Some(spans.remove(0)),
+            _ => None,
+        };
+        let trailing = match spans.last() {
+            Some(span) if span.end_ms == duration_ms => spans.pop(),
+            _ => None,
+        };
+        let internal = if config.detect_internal {
+            spans.retain(|span| span.end_ms - span.start_ms >= config.min_internal_ms);
+            spans
+        } else {
+            Vec::new()
+        };
+
+        SilenceReport {
+            duration_ms,
+            leading,
+            trailing,
+            internal,
+            kept: None,
+        }
+    }
+}
+
+/// Find the silence in the recording at `path`
+#[cfg(feature = "ffmpeg")]
+pub fn analyze_file(path: &Path, config: &SilenceConfig) -> Result<SilenceReport, String> {
+    config.validate()?;
+    ffmpeg_next::init().map_err(|e| format!("FFmpeg init failed: {}", e))?;
+
+    let reader = AudioTrackReader::open(path)?;
+    let mut detector = SilenceDetector::new(config.threshold_db, reader.rate(), reader.channels());
+    reader.read_all(|samples| detector.process(samples))?;
+    Ok(detector.finish(config))
+}
+
+#[cfg(not(feature = "ffmpeg"))]
+pub fn analyze_file(_path: &Path, config: &SilenceConfig) -> Result<SilenceReport, String> {
+    config.validate()?;
+    Err("Silence detection requires FFmpeg support".to_string())
+}
+
+/// Cut the leading and trailing silence from the recording at `path`
+#[cfg(feature = "ffmpeg")]
+pub fn trim_file(path: &Path, config: &SilenceConfig) -> Result<SilenceReport, String> {
+    let mut report = analyze_file(path, config)?;
+    let range = report
+        .sound_range(config.padding_ms)
+        .ok_or_else(|| format!("{} is silent; not trimming it", path.display()))?;
+
+    if range.start_ms == 0 && range.end_ms >= report.duration_ms {
+        report.kept = Some(range);
+        return Ok(report);
+    }
+
+    let cut_ms = keyframe_before(path, range.start_ms)?;
+    rewrite(path, suffix::TRIMMING, |temp_path| {
+        copy_range(path, temp_path, cut_ms, range.end_ms)
+    })?;
+
+    report.kept = Some(SilenceSpan {
+        start_ms: cut_ms,
+        end_ms: range.end_ms,
+    });
+    println!(
+        "Trimmed {}: kept {} ms to {} ms of {} ms",
+        path.display(),
+        cut_ms,
+        range.end_ms,
+        report.duration_ms
+    );
+    Ok(report)
+}
+
+#[cfg(not(feature = "ffmpeg"))]
+pub fn trim_file(_path: &Path, config: &SilenceConfig) -> Result<SilenceReport, String> {
+    config.validate()?;
+    Err("Silence trimming requires FFmpeg support".to_string())
+}
+
+/// Time of the last video keyframe at or before `time_ms` (`time_ms` itself
+/// for recordings without video)
+#[cfg(feature = "ffmpeg")]
+fn keyframe_before(path: &Path, time_ms: u64) -> Result<u64, String> {
+    use ffmpeg_next::Rescale;
+
+    let mut input = open_input(path)?;
+    let Some(video_index) = input
+        .streams()
+        .best(ffmpeg_next::media::Type::Video)
+        .map(|stream| stream.index())
+    else {
+        return Ok(time_ms);
+    };
+
+    let millis = ffmpeg_next::Rational(1, 1000);
+    let mut keyframe_ms = 0;
+    for (stream, packet) in input.packets() {
+        if stream.index() != video_index || !packet.is_key() {
+            continue;
+        }
+        let Some(dts) = packet.dts().or(packet.pts()) else {
+            continue;
+        };
+        let packet_ms = dts.rescale(stream.time_base(), millis).max(0) as u64;
+        if packet_ms > time_ms {
+            break;
+        }
+        keyframe_ms = packet_ms;
+    }
+    Ok(keyframe_ms)
+}
+
+/// Write `temp_path` with the packets from `start_ms` up to `end_ms`, moved to
+/// start at zero
+#[cfg(feature = "ffmpeg")]
+fn copy_range(path: &Path, temp_path: &Path, start_ms: u64, end_ms: u64) -> Result<(), String> {
+    use ffmpeg_next::Rescale;
+
+    let mut input = open_input(path)?;
+    let mut output = create_output(temp_path, &input)?;
+
+    let mut stream_map = Vec::new();
+    for stream in input.streams() {
+        stream_map.push(add_copy_stream(&mut output, &stream)?);
+    }
+    copy_chapters(&input, &mut output, Some(start_ms..end_ms))?;
+    write_header(&mut output, temp_path)?;
+    let output_time_bases: Vec<_> = output.streams().map(|s| s.time_base()).collect();
+
+    let millis = ffmpeg_next::Rational(1, 1000);
+    for (stream, mut packet) in input.packets() {
+        let Some(Some(output_index)) = stream_map.get(stream.index()).copied() else {
+            continue;
+        };
+        let Some(dts) = packet.dts().or(packet.pts()) else {
+            continue;
+        };
+        let packet_ms = dts.rescale(stream.time_base(), millis);
+        if packet_ms < start_ms as i64 || packet_ms >= end_ms as i64 {
+            continue;
+        }
+
+        let offset = (start_ms as i64).rescale(millis, stream.time_base());
+        packet.set_pts(packet.pts().map(|pts| pts - offset));
+        packet.set_dts(packet.dts().map(|dts| dts - offset));
+        packet.rescale_ts(stream.time_base(), output_time_bases[output_index]);
+        packet.set_stream(output_index);
+        packet.set_position(-1);
+        packet
+            .write_interleaved(&mut output)
+            .map_err(|e| format!("Failed to copy packet: {}", e))?;
+    }
+
+    output
+        .write_trailer()
+        .map_err(|e| format!("Failed to write trailer: {}", e))
+}
+
+/// Apply `config.on_finalize` to a recording that was just finalized;
+/// returns the span kept when it was trimmed
+#[cfg(feature = "ffmpeg")]
+pub fn finalize(path: &Path, config: &SilenceConfig) -> Result<Option<SilenceSpan>, String> {
+    match config.on_finalize {
+        SilenceAction::Off => Ok(None),
+        SilenceAction::Markers => {
+            let report = analyze_file(path, config)?;
+            write_markers(path, &report).map(|_| None)
+        }
+        SilenceAction::Trim => trim_file(path, config).map(|report| report.kept),
+    }
+}
+
+/// Write `report` to the recording's marker file
+pub fn write_markers(path: &Path, report: &SilenceReport) -> Result<(), String> {
+    let markers = markers_path(path);
+    let contents = serde_json::to_string_pretty(report)
+        .map_err(|e| format!("Failed to serialize silence markers: {}", e))?;
+    std::fs::write(&markers, contents)
+        .map_err(|e| format!("Failed to write {}: {}", markers.display(), e))?;
+    println!("Silence markers written: {}", markers.display());
+    Ok(())
+}
+
+/// Check a recording passed from the frontend exists and may be written next to
+fn checked_recording(path: String) -> Result<PathBuf, RecordingError> {
+    let path = PathBuf::from(path);
+    if !path.is_file() {
+        return Err(format!("Recording not found: {}", path.display()).into());
+    }
+    check_output_path(&path, &allowed_output_dirs())
+}
+
+/// Tauri command: Find the silence in a finished recording
+///
+/// With `write_markers` set, the report is also saved as the recording's
+/// marker file.
+#[command]
+pub async fn detect_silence(
+    path: String,
+    config: Option<SilenceConfig>,
+    write_markers: Option<bool>,
+) -> Result<SilenceReport, RecordingError> {
+    let path = checked_recording(path)?;
+    let config = config.unwrap_or_default();
+    tauri::async_runtime::spawn_blocking(move || {
+        let report = analyze_file(&path, &config)?;
+        if write_markers.unwrap_or(false) {
+            self::write_markers(&path, &report)?;
+        }
+        Ok(report)
+    })
+    .await
+    .map_err(|e| format!("Silence detection failed: {}", e))?
+    .map_err(RecordingError::Failed)
+}
+
+/// Tauri command: Cut the leading and trailing silence from a finished
+/// recording
+///
+/// The file is only replaced once the trimmed copy is complete.
+#[command]
+pub async fn trim_silence(path: String, config: Option<SilenceConfig>) -> Result<SilenceReport, RecordingError> {
+    let path = checked_recording(path)?;
+    let config = config.unwrap_or_default();
+    tauri::async_runtime::spawn_blocking(move || trim_file(&path, &config))
+        .await
+        .map_err(|e| format!("Silence trimming failed: {}", e))?
+        .map_err(RecordingError::Failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_silence_detector_spans() {
+        let rate = 1000;
+        let tone = |ms: usize| (0..ms).map(|i| if i % 2 == 0 { 0.5 } else { -0.5 });
+        let silence = |ms: usize| std::iter::repeat_n(0.001, ms);
+        let signal: Vec<f32> = silence(1000)
+            .chain(tone(2000))
+            .chain(silence(4000))
+            .chain(tone(1000))
+            .chain(silence(500))
+            .collect();
+
+        let mut config = SilenceConfig {
+            detect_internal: true,
+            ..SilenceConfig::default()
+        };
+        let mut detector = SilenceDetector::new(config.threshold_db, rate, 1);
+        detector.process(&signal);
+        let report = detector.finish(&config);
+
+        assert_eq!(report.duration_ms, 8500);
+        assert_eq!(report.leading, Some(SilenceSpan { start_ms: 0, end_ms: 1000 }));
+        assert_eq!(report.trailing, Some(SilenceSpan { start_ms: 8000, end_ms: 8500 }));
+        assert_eq!(report.internal, vec![SilenceSpan { start_ms: 3000, end_ms: 7000 }]);
+        assert_eq!(
+            report.sound_range(250),
+            Some(SilenceSpan { start_ms: 750, end_ms: 8250 })
+        );
+
+        // Shorter than the minimum: not reported
+        config.min_internal_ms = 5000;
+        let mut detector = SilenceDetector::new(config.threshold_db, rate, 1);
+        detector.process(&signal);
+        assert!(detector.finish(&config).internal.is_empty());
+
+        let mut detector = SilenceDetector::new(config.threshold_db, rate, 1);
+        detector.process(&[0.0; 500]);
+        assert_eq!(detector.finish(&config).sound_range(250), None);
+
+        assert!(SilenceConfig { threshold_db: 0.0, ..SilenceConfig::default() }.validate().is_err());
+        assert_eq!(markers_path(Path::new("/tmp/rec.mp4")), PathBuf::from("/tmp/rec.silence.json"));
+    }
+}
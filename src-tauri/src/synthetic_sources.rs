@@ -0,0 +1,509 @@
+//! Synthetic Capture Sources
+//!
+//! With `syntheticSources.enabled` a recording runs without any capture
+//! devices. The screen becomes colour bars with a marker sweeping across
+//! them, the webcam a scrolling checkerboard, the microphone a sine tone and
+//! system audio a quieter tone a fifth above it. Each is a capture backend
+//! (see `capture_backend`) the manager drives exactly like the device it
+//! replaces, so the compositor, mixer, encoder and outputs all run for real:
+//! on a CI machine, or on one without a camera, microphone or screen
+//! recording permission.
+
+use std::f32::consts::TAU;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::{bounded, Receiver, Sender, TrySendError};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::audio::{AudioChunk, DeviceTiming, MicrophoneState};
+use crate::audio_ring::{audio_ring, ring_capacity, AudioConsumer, AudioProducer};
+use crate::capture_backend::{AudioCaptureBackend, MicrophoneCaptureBackend, ScreenCaptureBackend, WebcamCaptureBackend};
+use crate::frame_pool::{FrameBuffer, FramePool};
+use crate::recording::{CapturePixelFormat, RecordingConfig};
+use crate::screen::ScreenFrame;
+use crate::webcam::{WebcamFrame, WebcamState};
+
+/// Allowed test pattern sizes, in pixels
+pub const WIDTH_RANGE: std::ops::RangeInclusive<u32> = 320..=7680;
+pub const HEIGHT_RANGE: std::ops::RangeInclusive<u32> = 240..=4320;
+
+/// Allowed tone frequencies, in Hz
+pub const TONE_HZ_RANGE: std::ops::RangeInclusive<f32> = 20.0..=20000.0;
+
+/// Size of the webcam pattern, as the real webcam is opened
+const WEBCAM_SIZE: (u32, u32) = (640, 480);
+
+/// Length of each audio chunk
+const CHUNK_MS: u32 = 10;
+
+/// Device name the microphone tone reports
+const SYNTHETIC_MIC_NAME: &str = "Synthetic tone";
+
+/// The system audio tone's frequency, relative to the microphone's
+const SYSTEM_TONE_RATIO: f32 = 1.5;
+
+/// Capacity of the frame channels, as for the real captures
+const FRAME_QUEUE: usize = 30;
+
+/// SMPTE-style bars, left to right (BGRA)
+const BARS: [[u8; 4]; 7] = [
+    [192, 192, 192, 255],
+    [0, 192, 192, 255],
+    [192, 192, 0, 255],
+    [0, 192, 0, 255],
+    [192, 0, 192, 255],
+    [0, 0, 192, 255],
+    [192, 0, 0, 255],
+];
+
+/// Synthetic source settings
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyntheticSourcesConfig {
+    /// Use generated sources instead of the capture devices
+    #[serde(default)]
+    pub enabled: bool,
+    /// Size of the screen test pattern
+    #[serde(default = "default_width")]
+    pub width: u32,
+    #[serde(default = "default_height")]
+    pub height: u32,
+    /// Frequency of the microphone tone
+    #[serde(default = "default_tone_hz")]
+    pub tone_hz: f32,
+}
+
+fn default_width() -> u32 {
+    1920
+}
+
+fn default_height() -> u32 {
+    1080
+}
+
+fn default_tone_hz() -> f32 {
+    440.0
+}
+
+impl Default for SyntheticSourcesConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            width: default_width(),
+            height: default_height(),
+            tone_hz: default_tone_hz(),
+        }
+    }
+}
+
+impl SyntheticSourcesConfig {
+    /// Check the settings; both tones must fit under the Nyquist frequency
+    /// of `sample_rate`
+    pub fn validate(&self, sample_rate: u32) -> Result<(), String> {
+        if !WIDTH_RANGE.contains(&self.width) || !HEIGHT_RANGE.contains(&self.height) {
+            return Err(format!(
+                "Synthetic screen size must be {}-{} by {}-{} pixels (got {}x{})",
+                WIDTH_RANGE.start(),
+                WIDTH_RANGE.end(),
+                HEIGHT_RANGE.start(),
+                HEIGHT_RANGE.end(),
+                self.width,
+                self.height
+            ));
+        }
+        if !self.width.is_multiple_of(2) || !self.height.is_multiple_of(2) {
+            return Err(format!(
+                "Synthetic screen size must be even (got {}x{})",
+                self.width, self.height
+            ));
+        }
+        if !TONE_HZ_RANGE.contains(&self.tone_hz) {
+            return Err(format!(
+                "Synthetic tone must be {}-{} Hz (got {})",
+                TONE_HZ_RANGE.start(),
+                TONE_HZ_RANGE.end(),
+                self.tone_hz
+            ));
+        }
+        let nyquist = sample_rate as f32 / 2.0;
+        if self.tone_hz * SYSTEM_TONE_RATIO >= nyquist {
+            return Err(format!(
+                "Synthetic tone must be below {:.0} Hz at {} Hz, so the system audio tone a fifth above it fits (got {})",
+                nyquist / SYSTEM_TONE_RATIO,
+                sample_rate,
+                self.tone_hz
+            ));
+        }
+        Ok(())
+    }
+
+    /// Size of the screen test pattern
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+}
+
+/// Shared start of the generated timeline, set by the first source started
+type Epoch = Arc<OnceLock<Instant>>;
+
+/// Generators for the sources a recording captures
+pub struct SyntheticSources {
+    pub screen: Option<SyntheticFrames<ScreenFrame>>,
+    pub webcam: Option<SyntheticFrames<WebcamFrame>>,
+    pub mic: Option<SyntheticTone>,
+    pub system: Option<SyntheticTone>,
+}
+
+/// Generators for every source `config` captures, on one timeline
+///
+/// Timestamps count from the first `start`, like a capture's.
+pub fn backends(config: &RecordingConfig) -> SyntheticSources {
+    let settings = config.synthetic_sources;
+    let fps = config.frame_rate.unwrap_or(30).max(1);
+    let epoch = Epoch::default();
+
+    let screen = config.capture_screen.then(|| {
+        let (width, height) = settings.size();
+        let pool = FramePool::new();
+        SyntheticFrames::new((width, height), fps, epoch.clone(), move |index, timestamp| ScreenFrame {
+            data: test_pattern(width, height, index, &pool),
+            width,
+            height,
+            stride: width as usize * 4,
+            timestamp,
+            pixel_format: CapturePixelFormat::Bgra,
+        })
+    });
+    let webcam = config.capture_webcam.then(|| {
+        let (width, height) = WEBCAM_SIZE;
+        SyntheticFrames::new(WEBCAM_SIZE, fps, epoch.clone(), move |index, timestamp| WebcamFrame {
+            data: checkerboard(width, height, index),
+            width,
+            height,
+            timestamp,
+        })
+    });
+
+    let sample_rate = config.audio_sample_rate;
+    let channels = config.audio_channels.channel_count();
+    let mic = config
+        .capture_mic
+        .then(|| SyntheticTone::new(Tone::new(settings.tone_hz, 0.25, sample_rate, channels), epoch.clone()));
+    let system = config
+        .capture_system_audio
+        .then(|| SyntheticTone::new(Tone::new(settings.tone_hz * SYSTEM_TONE_RATIO, 0.1, sample_rate, channels), epoch));
+
+    SyntheticSources {
+        screen,
+        webcam,
+        mic,
+        system,
+    }
+}
+
+/// Frames made by `make` at a steady rate, dropped when the receiver is
+/// behind like a capture's
+pub struct SyntheticFrames<T> {
+    size: (u32, u32),
+    fps: u32,
+    epoch: Epoch,
+    make: Arc<dyn Fn(u64, Duration) -> T + Send + Sync>,
+    sender: Sender<T>,
+    receiver: Option<Receiver<T>>,
+    running: Arc<Mutex<bool>>,
+    frames: Arc<AtomicU64>,
+}
+
+impl<T: Send + 'static> SyntheticFrames<T> {
+    fn new(
+        size: (u32, u32),
+        fps: u32,
+        epoch: Epoch,
+        make: impl Fn(u64, Duration) -> T + Send + Sync + 'static,
+    ) -> Self {
+        let (sender, receiver) = bounded(FRAME_QUEUE);
+        Self {
+            size,
+            fps,
+            epoch,
+            make: Arc::new(make),
+            sender,
+            receiver: Some(receiver),
+            running: Arc::new(Mutex::new(false)),
+            frames: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    fn start(&self) -> Result<(), String> {
+        if std::mem::replace(&mut *self.running.lock(), true) {
+            return Ok(());
+        }
+        let (sender, make, running, frames) =
+            (self.sender.clone(), self.make.clone(), self.running.clone(), self.frames.clone());
+        let epoch = *self.epoch.get_or_init(Instant::now);
+        let interval = Duration::from_secs(1) / self.fps;
+
+        std::thread::spawn(move || {
+            let started = Instant::now();
+            let mut index: u64 = 0;
+
+            while *running.lock() {
+                match sender.try_send(make(index, epoch.elapsed())) {
+                    Ok(()) | Err(TrySendError::Full(_)) => {}
+                    Err(TrySendError::Disconnected(_)) => break,
+                }
+                frames.fetch_add(1, Ordering::Relaxed);
+
+                index += 1;
+                if let Some(wait) = (interval * index as u32).checked_sub(started.elapsed()) {
+                    std::thread::sleep(wait);
+                }
+            }
+        });
+        Ok(())
+    }
+
+    fn stop(&self) {
+        *self.running.lock() = false;
+    }
+}
+
+impl ScreenCaptureBackend for SyntheticFrames<ScreenFrame> {
+    fn dimensions(&self) -> (u32, u32) {
+        self.size
+    }
+
+    fn take_receiver(&mut self) -> Option<Receiver<ScreenFrame>> {
+        self.receiver.take()
+    }
+
+    fn start(&self) -> Result<(), String> {
+        SyntheticFrames::start(self)
+    }
+
+    fn stop(&self) {
+        SyntheticFrames::stop(self)
+    }
+
+    fn heartbeats(&self) -> u64 {
+        self.frames.load(Ordering::Relaxed)
+    }
+
+    /// A generator doesn't stall, so there is nothing to start over
+    fn restart(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+impl WebcamCaptureBackend for SyntheticFrames<WebcamFrame> {
+    fn take_receiver(&mut self) -> Option<Receiver<WebcamFrame>> {
+        self.receiver.take()
+    }
+
+    fn start(&self) -> Result<(), String> {
+        SyntheticFrames::start(self)
+    }
+
+    fn stop(&self) {
+        SyntheticFrames::stop(self)
+    }
+
+    fn state(&self) -> WebcamState {
+        WebcamState::Connected
+    }
+}
+
+/// A tone pushed in 10ms chunks, paced in real time
+pub struct SyntheticTone {
+    tone: Mutex<Option<(Tone, AudioProducer)>>,
+    consumer: Option<AudioConsumer>,
+    epoch: Epoch,
+    running: Arc<Mutex<bool>>,
+}
+
+impl SyntheticTone {
+    fn new(tone: Tone, epoch: Epoch) -> Self {
+        let (producer, consumer) = audio_ring(ring_capacity(tone.sample_rate, tone.channels));
+        Self {
+            tone: Mutex::new(Some((tone, producer))),
+            consumer: Some(consumer),
+            epoch,
+            running: Arc::new(Mutex::new(false)),
+        }
+    }
+}
+
+impl AudioCaptureBackend for SyntheticTone {
+    fn take_receiver(&mut self) -> Option<AudioConsumer> {
+        self.consumer.take()
+    }
+
+    /// Starts once; the tone can't be picked up again after `stop`
+    fn start(&self) -> Result<(), String> {
+        let Some((mut tone, mut producer)) = self.tone.lock().take() else {
+            return Ok(());
+        };
+        *self.running.lock() = true;
+        let running = self.running.clone();
+        let epoch = *self.epoch.get_or_init(Instant::now);
+
+        std::thread::spawn(move || {
+            let frames = (tone.sample_rate * CHUNK_MS / 1000) as usize;
+            let interval = Duration::from_millis(CHUNK_MS as u64);
+            let started = Instant::now();
+            let mut chunks: u32 = 0;
+
+            while *running.lock() && !producer.is_disconnected() {
+                let chunk = AudioChunk {
+                    samples: tone.next_samples(frames),
+                    sample_rate: tone.sample_rate,
+                    channels: tone.channels,
+                    timestamp: epoch.elapsed(),
+                };
+                producer.push_chunk(&chunk);
+
+                chunks += 1;
+                if let Some(wait) = (interval * chunks).checked_sub(started.elapsed()) {
+                    std::thread::sleep(wait);
+                }
+            }
+        });
+        Ok(())
+    }
+
+    fn stop(&self) {
+        *self.running.lock() = false;
+    }
+}
+
+impl MicrophoneCaptureBackend for SyntheticTone {
+    fn state(&self) -> MicrophoneState {
+        MicrophoneState::Connected {
+            device: SYNTHETIC_MIC_NAME.to_string(),
+        }
+    }
+
+    fn timing(&self) -> Option<DeviceTiming> {
+        None
+    }
+
+    fn reopen(&self) {}
+}
+
+/// A sine tone, continuous across chunks
+struct Tone {
+    hz: f32,
+    amplitude: f32,
+    sample_rate: u32,
+    channels: u16,
+    /// Frames generated so far
+    position: u64,
+}
+
+impl Tone {
+    fn new(hz: f32, amplitude: f32, sample_rate: u32, channels: u16) -> Self {
+        Self {
+            hz,
+            amplitude,
+            sample_rate,
+            channels,
+            position: 0,
+        }
+    }
+
+    /// The next `frames` frames, the same value on every channel
+    fn next_samples(&mut self, frames: usize) -> Vec<f32> {
+        let period = self.sample_rate as f64 / self.hz as f64;
+        let mut samples = Vec::with_capacity(frames * self.channels as usize);
+        for i in 0..frames as u64 {
+            // Phase from the position within the period, so it stays exact
+            // over long recordings
+            let phase = ((self.position + i) as f64 % period) / period;
+            let value = (TAU * phase as f32).sin() * self.amplitude;
+            samples.extend(std::iter::repeat_n(value, self.channels as usize));
+        }
+        self.position += frames as u64;
+        samples
+    }
+}
+
+/// BGRA colour bars with a white marker sweeping across once every 4 seconds
+/// at 30fps, in a buffer from `pool`
+fn test_pattern(width: u32, height: u32, index: u64, pool: &FramePool) -> Arc<FrameBuffer> {
+    let marker_width = (width / 64).max(2);
+    let marker_x = (index * width as u64 / 120 % width as u64) as u32;
+    let mut row = Vec::with_capacity(width as usize * 4);
+    for x in 0..width {
+        let pixel = if x >= marker_x && x < marker_x + marker_width {
+            [255, 255, 255, 255]
+        } else {
+            BARS[(x * BARS.len() as u32 / width) as usize]
+        };
+        row.extend_from_slice(&pixel);
+    }
+    let mut data = pool.take(row.len() * height as usize);
+    for _ in 0..height {
+        data.extend_from_slice(&row);
+    }
+    pool.share(data)
+}
+
+/// RGB checkerboard scrolling diagonally, one pixel per frame
+fn checkerboard(width: u32, height: u32, index: u64) -> Vec<u8> {
+    const SQUARE: u64 = 40;
+    let mut data = Vec::with_capacity((width * height * 3) as usize);
+    for y in 0..height as u64 {
+        for x in 0..width as u64 {
+            let light = ((x + index) / SQUARE + (y + index) / SQUARE).is_multiple_of(2);
+            data.extend_from_slice(if light { &[224, 160, 96] } else { &[48, 32, 64] });
+        }
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_synthetic_sources_generate_expected_signals() {
+        // A 1kHz tone at 48kHz repeats every 48 frames, across chunk boundaries
+        let mut tone = Tone::new(1000.0, 0.5, 48000, 2);
+        let first = tone.next_samples(480);
+        let second = tone.next_samples(480);
+        assert_eq!(first.len(), 960);
+        assert!((first[0] - second[0]).abs() < 1e-4);
+        assert!((first[24] - 0.5).abs() < 1e-3, "peak at a quarter period");
+        assert_eq!(first[24], first[25], "same value on both channels");
+
+        let pool = FramePool::new();
+        let pattern = test_pattern(640, 360, 0, &pool);
+        assert_eq!(pattern.len(), 640 * 360 * 4);
+        assert_eq!(&pattern[..4], &[255, 255, 255, 255], "marker starts at the left");
+        assert_eq!(&pattern[639 * 4..640 * 4], &BARS[6]);
+        assert_ne!(test_pattern(640, 360, 30, &pool)[..], pattern[..]);
+        assert_eq!(checkerboard(640, 480, 0).len(), 640 * 480 * 3);
+
+        assert!(SyntheticSourcesConfig::default().validate(48000).is_ok());
+        let odd = SyntheticSourcesConfig {
+            width: 1281,
+            ..SyntheticSourcesConfig::default()
+        };
+        assert!(odd.validate(48000).is_err());
+        let ultrasonic = SyntheticSourcesConfig {
+            tone_hz: 30000.0,
+            ..SyntheticSourcesConfig::default()
+        };
+        assert!(ultrasonic.validate(48000).is_err());
+        // 15kHz fits at 44.1kHz, but the system tone at 22.5kHz doesn't
+        let aliased = SyntheticSourcesConfig {
+            tone_hz: 15000.0,
+            ..SyntheticSourcesConfig::default()
+        };
+        assert!(aliased.validate(44100).is_err());
+        assert!(aliased.validate(96000).is_ok());
+    }
+}
@@ -1,12 +1,12 @@
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::SampleFormat;
 use std::sync::Arc;
+use std::thread::JoinHandle;
 use std::time::{Duration, Instant};
 
-use crossbeam_channel::{bounded, Receiver, Sender};
 use parking_lot::Mutex;
 
-use crate::audio::AudioChunk;
+use crate::audio_ring::{audio_ring, ring_capacity, AudioConsumer, AudioProducer};
 
 use super::SystemAudioCaptureConfig;
 
@@ -23,26 +23,27 @@ pub struct SystemAudioCapture {
     actual_sample_rate: u32,
     actual_channels: u16,
     running: Arc<Mutex<bool>>,
-    chunk_sender: Option<Sender<AudioChunk>>,
-    chunk_receiver: Option<Receiver<AudioChunk>>,
+    chunk_producer: Mutex<Option<AudioProducer>>,
+    chunk_consumer: Option<AudioConsumer>,
+    thread: Mutex<Option<JoinHandle<()>>>,
     is_available: bool,
 }
 
 impl SystemAudioCapture {
     /// Create a new system audio capture instance
     pub fn new(config: SystemAudioCaptureConfig) -> Result<Self, String> {
-        let (sender, receiver) = bounded(30);
-
         let (is_available, actual_sample_rate, actual_channels) =
             Self::check_availability(&config)?;
+        let (producer, consumer) = audio_ring(ring_capacity(actual_sample_rate, actual_channels));
 
         Ok(Self {
             config,
             actual_sample_rate,
             actual_channels,
             running: Arc::new(Mutex::new(false)),
-            chunk_sender: Some(sender),
-            chunk_receiver: Some(receiver),
+            chunk_producer: Mutex::new(Some(producer)),
+            chunk_consumer: Some(consumer),
+            thread: Mutex::new(None),
             is_available,
         })
     }
@@ -119,9 +120,9 @@ impl SystemAudioCapture {
         (self.actual_sample_rate, self.actual_channels)
     }
 
-    /// Get a receiver for audio chunks
-    pub fn take_receiver(&mut self) -> Option<Receiver<AudioChunk>> {
-        self.chunk_receiver.take()
+    /// Get the reading end of the audio ring
+    pub fn take_receiver(&mut self) -> Option<AudioConsumer> {
+        self.chunk_consumer.take()
     }
 
     /// Start capturing system audio
@@ -138,16 +139,17 @@ impl SystemAudioCapture {
         drop(running);
 
         let running_clone = self.running.clone();
-        let sender = self.chunk_sender.clone().ok_or("Chunk sender not available")?;
+        let producer = self.chunk_producer.lock().take().ok_or("Audio ring not available")?;
         let sample_rate = self.actual_sample_rate;
         let channels = self.actual_channels;
 
-        std::thread::spawn(move || {
-            if let Err(e) = run_system_audio_capture(running_clone, sender, sample_rate, channels)
+        let thread = std::thread::spawn(move || {
+            if let Err(e) = run_system_audio_capture(running_clone, producer, sample_rate, channels)
             {
                 eprintln!("System audio capture error: {}", e);
             }
         });
+        *self.thread.lock() = Some(thread);
 
         println!(
             "System audio capture started: {}Hz, {} channels",
@@ -157,10 +159,12 @@ impl SystemAudioCapture {
         Ok(())
     }
 
-    /// Stop capturing
+    /// Stop capturing; returns once the stream is closed, which closes the ring
     pub fn stop(&self) {
-        let mut running = self.running.lock();
-        *running = false;
+        *self.running.lock() = false;
+        if let Some(thread) = self.thread.lock().take() {
+            let _ = thread.join();
+        }
         println!("System audio capture stopped");
     }
 
@@ -174,7 +178,7 @@ impl SystemAudioCapture {
 /// Run system audio capture in a background thread
 fn run_system_audio_capture(
     running: Arc<Mutex<bool>>,
-    sender: Sender<AudioChunk>,
+    mut producer: AudioProducer,
     sample_rate: u32,
     channels: u16,
 ) -> Result<(), String> {
@@ -230,13 +234,13 @@ fn run_system_audio_capture(
                 if !*running_for_callback.lock() {
                     return;
                 }
-                let chunk = AudioChunk {
-                    samples: data.to_vec(),
+                producer.push(
+                    start_time.elapsed(),
                     sample_rate,
                     channels,
-                    timestamp: start_time.elapsed(),
-                };
-                let _ = sender.try_send(chunk);
+                    data.len(),
+                    data.iter().copied(),
+                );
             },
             err_fn,
             None,
@@ -247,14 +251,13 @@ fn run_system_audio_capture(
                 if !*running_for_callback.lock() {
                     return;
                 }
-                let samples: Vec<f32> = data.iter().map(|&s| s as f32 / 32768.0).collect();
-                let chunk = AudioChunk {
-                    samples,
+                producer.push(
+                    start_time.elapsed(),
                     sample_rate,
                     channels,
-                    timestamp: start_time.elapsed(),
-                };
-                let _ = sender.try_send(chunk);
+                    data.len(),
+                    data.iter().map(|&s| s as f32 / 32768.0),
+                );
             },
             err_fn,
             None,
@@ -1,25 +1,28 @@
 use std::sync::Arc;
 use std::time::Instant;
 
-use crossbeam_channel::{bounded, Receiver, Sender};
 use parking_lot::Mutex;
 use screencapturekit::prelude::*;
 
-use crate::audio::AudioChunk;
+use crate::audio_ring::{audio_ring, ring_capacity, AudioConsumer, AudioProducer};
 
 use super::SystemAudioCaptureConfig;
 
 pub struct SystemAudioCapture {
     config: SystemAudioCaptureConfig,
     running: Arc<Mutex<bool>>,
-    chunk_sender: Option<Sender<AudioChunk>>,
-    chunk_receiver: Option<Receiver<AudioChunk>>,
+    chunk_producer: Mutex<Option<AudioProducer>>,
+    chunk_consumer: Option<AudioConsumer>,
     stream: Arc<Mutex<Option<SCStream>>>,
     is_available: bool,
 }
 
+/// Most planar buffers interleaved per sample buffer
+const MAX_PLANES: usize = 8;
+
 struct AudioHandler {
-    sender: Sender<AudioChunk>,
+    /// Only ScreenCaptureKit's delivery queue locks this, so it never waits
+    producer: Mutex<AudioProducer>,
     start_time: Instant,
     sample_rate: u32,
     channels: u16,
@@ -31,30 +34,51 @@ impl SCStreamOutputTrait for AudioHandler {
             return;
         }
 
-        let Some(samples) = extract_audio_samples(&sample) else {
+        let Some(audio_list) = sample.audio_buffer_list() else {
             return;
         };
 
-        let chunk = AudioChunk {
-            samples,
-            sample_rate: self.sample_rate,
-            channels: self.channels,
-            timestamp: self.start_time.elapsed(),
-        };
+        // Interleave planar buffers straight into the ring, without allocating
+        let mut planes: [&[f32]; MAX_PLANES] = [&[]; MAX_PLANES];
+        let mut plane_count = 0;
+        for buffer in audio_list.iter() {
+            let bytes = buffer.data();
+            if bytes.is_empty() || plane_count == MAX_PLANES {
+                continue;
+            }
+            planes[plane_count] = unsafe {
+                std::slice::from_raw_parts(
+                    bytes.as_ptr() as *const f32,
+                    bytes.len() / std::mem::size_of::<f32>(),
+                )
+            };
+            plane_count += 1;
+        }
+        if plane_count == 0 {
+            return;
+        }
 
-        let _ = self.sender.try_send(chunk);
+        let planes = &planes[..plane_count];
+        let frame_count = planes.iter().map(|plane| plane.len()).min().unwrap_or(0);
+        self.producer.lock().push(
+            self.start_time.elapsed(),
+            self.sample_rate,
+            self.channels,
+            frame_count * plane_count,
+            (0..frame_count).flat_map(|i| planes.iter().map(move |plane| plane[i])),
+        );
     }
 }
 
 impl SystemAudioCapture {
     pub fn new(config: SystemAudioCaptureConfig) -> Result<Self, String> {
-        let (sender, receiver) = bounded(30);
+        let (producer, consumer) = audio_ring(ring_capacity(config.sample_rate, config.channels));
 
         Ok(Self {
             config,
             running: Arc::new(Mutex::new(false)),
-            chunk_sender: Some(sender),
-            chunk_receiver: Some(receiver),
+            chunk_producer: Mutex::new(Some(producer)),
+            chunk_consumer: Some(consumer),
             stream: Arc::new(Mutex::new(None)),
             is_available: true,
         })
@@ -64,8 +88,8 @@ impl SystemAudioCapture {
         self.is_available
     }
 
-    pub fn take_receiver(&mut self) -> Option<Receiver<AudioChunk>> {
-        self.chunk_receiver.take()
+    pub fn take_receiver(&mut self) -> Option<AudioConsumer> {
+        self.chunk_consumer.take()
     }
 
     pub fn start(&self) -> Result<(), String> {
@@ -98,10 +122,12 @@ impl SystemAudioCapture {
         let mut stream = SCStream::new(&filter, &stream_config);
 
         let handler = AudioHandler {
-            sender: self
-                .chunk_sender
-                .clone()
-                .ok_or("Chunk sender not available")?,
+            producer: Mutex::new(
+                self.chunk_producer
+                    .lock()
+                    .take()
+                    .ok_or("Audio ring not available")?,
+            ),
             start_time: Instant::now(),
             sample_rate: self.config.sample_rate,
             channels: self.config.channels,
@@ -140,46 +166,3 @@ impl SystemAudioCapture {
 pub fn is_system_audio_available() -> bool {
     true
 }
-
-fn extract_audio_samples(sample: &CMSampleBuffer) -> Option<Vec<f32>> {
-    let audio_list = sample.audio_buffer_list()?;
-    let mut channel_buffers: Vec<Vec<f32>> = Vec::new();
-
-    for buffer in audio_list.iter() {
-        let bytes = buffer.data();
-        if bytes.is_empty() {
-            continue;
-        }
-
-        let samples = unsafe {
-            std::slice::from_raw_parts(
-                bytes.as_ptr() as *const f32,
-                bytes.len() / std::mem::size_of::<f32>(),
-            )
-        };
-        channel_buffers.push(samples.to_vec());
-    }
-
-    if channel_buffers.is_empty() {
-        return None;
-    }
-
-    if channel_buffers.len() == 1 {
-        return Some(channel_buffers[0].clone());
-    }
-
-    let frame_count = channel_buffers
-        .iter()
-        .map(|samples| samples.len())
-        .min()
-        .unwrap_or(0);
-
-    let mut interleaved = Vec::with_capacity(frame_count * channel_buffers.len());
-    for frame_index in 0..frame_count {
-        for channel in &channel_buffers {
-            interleaved.push(channel[frame_index]);
-        }
-    }
-
-    Some(interleaved)
-}
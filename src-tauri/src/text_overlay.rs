@@ -0,0 +1,318 @@
+//! Text Overlays
+//!
+//! Lines of text burned into the recording by the compositor, such as a
+//! session title or a running timestamp. The text is a template expanded on
+//! every frame:
+//!
+//! - `{title}`, `{artist}`: from the recording's file metadata
+//! - `{date}`, `{time}`: the wall clock (YYYY-MM-DD, HH:MM:SS)
+//! - `{elapsed}`: time since the recording started (H:MM:SS)
+//! - `{timecode}`: the same to the millisecond (H:MM:SS.mmm)
+//!
+//! Elapsed time comes from the frame timestamps, i.e. the pipeline clock, so
+//! it matches the recording's own timeline rather than the wall clock.
+//!
+//! Rendering (ab_glyph) needs the `text-overlay` feature, which `captions`
+//! also enables; without it, text overlays fail when the recording starts.
+
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::recording::{FileMetadata, PipPosition};
+
+/// Distance from the frame edges, in pixels
+#[cfg(feature = "text-overlay")]
+const TEXT_PADDING: u32 = 20;
+
+/// Smallest and largest accepted text size, in pixels
+const SIZE_RANGE: std::ops::RangeInclusive<f32> = 6.0..=400.0;
+
+#[cfg(not(feature = "text-overlay"))]
+const FEATURE_MISSING: &str = "Text overlays require a build with the `text-overlay` feature";
+
+/// Fonts tried when none is configured
+#[cfg(feature = "text-overlay")]
+const SYSTEM_FONTS: &[&str] = &[
+    "/System/Library/Fonts/Helvetica.ttc",
+    "/System/Library/Fonts/Supplemental/Arial.ttf",
+    "C:\\Windows\\Fonts\\segoeui.ttf",
+    "C:\\Windows\\Fonts\\arial.ttf",
+    "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/TTF/DejaVuSans.ttf",
+    "/usr/share/fonts/dejavu-sans-fonts/DejaVuSans.ttf",
+];
+
+/// A block of text burned into every frame
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextOverlayConfig {
+    /// Template, e.g. "{title} - {elapsed}"; newlines start new lines
+    pub text: String,
+    /// TrueType/OpenType font (default: a system sans-serif)
+    #[serde(default)]
+    pub font_path: Option<PathBuf>,
+    /// Line height in output pixels
+    #[serde(default = "default_size")]
+    pub size: f32,
+    /// RGB
+    #[serde(default = "default_color")]
+    pub color: [u8; 3],
+    /// Corner the text is drawn in
+    #[serde(default)]
+    pub position: PipPosition,
+    /// Dark band behind the text, for legibility over busy content
+    #[serde(default)]
+    pub background: bool,
+}
+
+fn default_size() -> f32 {
+    32.0
+}
+
+fn default_timer_position() -> PipPosition {
+    PipPosition::BottomLeft
+}
+
+fn default_color() -> [u8; 3] {
+    [255, 255, 255]
+}
+
+impl TextOverlayConfig {
+    /// Check there is text and its size is sensible
+    pub fn validate(&self) -> Result<(), String> {
+        if self.text.trim().is_empty() {
+            return Err("Text overlay text must not be empty".to_string());
+        }
+        if !SIZE_RANGE.contains(&self.size) {
+            return Err(format!(
+                "Text overlay size must be {}-{} pixels, got {}",
+                SIZE_RANGE.start(),
+                SIZE_RANGE.end(),
+                self.size
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Built-in elapsed-time readout, so raw footage shows how far into the
+/// session each moment is
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimerOverlayConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Corner the timer is drawn in
+    #[serde(default = "default_timer_position")]
+    pub position: PipPosition,
+    /// Line height in output pixels
+    #[serde(default = "default_size")]
+    pub size: f32,
+}
+
+impl Default for TimerOverlayConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            position: default_timer_position(),
+            size: default_size(),
+        }
+    }
+}
+
+impl TimerOverlayConfig {
+    /// The text overlay that draws the timer
+    pub fn text_overlay(&self) -> TextOverlayConfig {
+        TextOverlayConfig {
+            text: "{timecode}".to_string(),
+            font_path: None,
+            size: self.size,
+            color: default_color(),
+            position: self.position,
+            background: true,
+        }
+    }
+}
+
+/// Load `path`, or the first system font found
+#[cfg(feature = "text-overlay")]
+pub fn load_font(path: Option<&std::path::Path>) -> Option<ab_glyph::FontVec> {
+    let candidates: Vec<PathBuf> = match path {
+        Some(path) => vec![path.to_path_buf()],
+        None => SYSTEM_FONTS.iter().map(PathBuf::from).collect(),
+    };
+    candidates.iter().find_map(|path| {
+        let bytes = std::fs::read(path).ok()?;
+        ab_glyph::FontVec::try_from_vec(bytes).ok()
+    })
+}
+
+/// Replace each `{name}` in `template` with its value; unknown names are kept
+#[cfg(any(feature = "text-overlay", test))]
+pub fn expand_template(template: &str, values: &[(&str, &str)]) -> String {
+    let mut text = template.to_string();
+    for (name, value) in values {
+        text = text.replace(&format!("{{{}}}", name), value);
+    }
+    text
+}
+
+/// H:MM:SS
+#[cfg(any(feature = "text-overlay", test))]
+pub fn format_elapsed(elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs();
+    format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+/// H:MM:SS.mmm
+#[cfg(any(feature = "text-overlay", test))]
+pub fn format_timecode(elapsed: Duration) -> String {
+    format!("{}.{:03}", format_elapsed(elapsed), elapsed.subsec_millis())
+}
+
+/// A text overlay ready to draw, with its font loaded
+pub struct TextOverlay {
+    #[cfg(feature = "text-overlay")]
+    config: TextOverlayConfig,
+    #[cfg(feature = "text-overlay")]
+    title: String,
+    #[cfg(feature = "text-overlay")]
+    artist: String,
+    #[cfg(feature = "text-overlay")]
+    font: ab_glyph::FontVec,
+}
+
+impl TextOverlay {
+    /// Load the overlay's font; `metadata` fills `{title}` and `{artist}`
+    #[cfg(feature = "text-overlay")]
+    pub fn new(config: TextOverlayConfig, metadata: &FileMetadata) -> Result<Self, String> {
+        let font = load_font(config.font_path.as_deref())
+            .ok_or("No usable text overlay font found (set fontPath)")?;
+        Ok(Self {
+            title: metadata.title.clone().unwrap_or_default(),
+            artist: metadata.artist.clone().unwrap_or_default(),
+            config,
+            font,
+        })
+    }
+
+    #[cfg(not(feature = "text-overlay"))]
+    pub fn new(_config: TextOverlayConfig, _metadata: &FileMetadata) -> Result<Self, String> {
+        Err(FEATURE_MISSING.to_string())
+    }
+
+    /// The text for a frame `elapsed` into the recording
+    #[cfg(feature = "text-overlay")]
+    pub fn text(&self, elapsed: Duration) -> String {
+        let now = chrono::Local::now();
+        expand_template(
+            &self.config.text,
+            &[
+                ("title", &self.title),
+                ("artist", &self.artist),
+                ("date", &now.format("%Y-%m-%d").to_string()),
+                ("time", &now.format("%H:%M:%S").to_string()),
+                ("elapsed", &format_elapsed(elapsed)),
+                ("timecode", &format_timecode(elapsed)),
+            ],
+        )
+    }
+
+    /// Draw the text for a frame `elapsed` into the recording, faded by `opacity`
+    #[cfg(feature = "text-overlay")]
+    pub fn draw(&self, image: &mut RgbaImage, elapsed: Duration, opacity: f32) {
+        use ab_glyph::{point, Font, PxScale, ScaleFont};
+        use image::Rgba;
+
+        let text = self.text(elapsed);
+        let lines: Vec<&str> = text.lines().collect();
+        let scale = PxScale::from(self.config.size);
+        let font = self.font.as_scaled(scale);
+        let measure = |s: &str| -> f32 {
+            s.chars().map(|c| font.h_advance(font.glyph_id(c))).sum()
+        };
+
+        let (width, height) = image.dimensions();
+        let line_height = font.height() + font.line_gap();
+        let block_width = lines.iter().map(|line| measure(line)).fold(0.0, f32::max).ceil() as u32;
+        let block_height = (line_height * lines.len() as f32).ceil() as u32;
+        let (left, top) = crate::compositor::VideoCompositor::calculate_pip_position(
+            width,
+            height,
+            block_width,
+            block_height,
+            self.config.position,
+            TEXT_PADDING,
+        );
+
+        if self.config.background {
+            let padding = (self.config.size * 0.3).ceil() as u32;
+            let x1 = (left + block_width + padding).min(width);
+            let y1 = (top + block_height + padding).min(height);
+            for y in top.saturating_sub(padding)..y1 {
+                for x in left.saturating_sub(padding)..x1 {
+                    crate::compositor::blend_pixel(image, x, y, Rgba([0, 0, 0, 255]), 0.6 * opacity);
+                }
+            }
+        }
+
+        // Lines line up with the edge the block is against
+        let right_aligned = matches!(self.config.position, PipPosition::TopRight | PipPosition::BottomRight);
+        let [r, g, b] = self.config.color;
+        for (index, line) in lines.iter().enumerate() {
+            let mut caret = if right_aligned {
+                left as f32 + block_width as f32 - measure(line)
+            } else {
+                left as f32
+            };
+            let baseline = top as f32 + line_height * index as f32 + font.ascent();
+            for c in line.chars() {
+                let id = font.glyph_id(c);
+                let glyph = id.with_scale_and_position(scale, point(caret, baseline));
+                caret += font.h_advance(id);
+                let Some(outlined) = self.font.outline_glyph(glyph) else {
+                    continue;
+                };
+                let bounds = outlined.px_bounds();
+                outlined.draw(|gx, gy, coverage| {
+                    let x = bounds.min.x as i32 + gx as i32;
+                    let y = bounds.min.y as i32 + gy as i32;
+                    if x >= 0 && y >= 0 && (x as u32) < width && (y as u32) < height {
+                        let color = Rgba([r, g, b, 255]);
+                        crate::compositor::blend_pixel(image, x as u32, y as u32, color, coverage * opacity);
+                    }
+                });
+            }
+        }
+    }
+
+    #[cfg(not(feature = "text-overlay"))]
+    pub fn draw(&self, _image: &mut RgbaImage, _elapsed: Duration, _opacity: f32) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template_placeholders() {
+        let text = expand_template(
+            "{title} by {artist} - {elapsed} {unknown}",
+            &[("title", "Rain"), ("artist", "Mika"), ("elapsed", &format_elapsed(Duration::from_secs(3725)))],
+        );
+        assert_eq!(text, "Rain by Mika - 1:02:05 {unknown}");
+        assert_eq!(format_timecode(Duration::from_millis(61_042)), "0:01:01.042");
+
+        let config = TextOverlayConfig {
+            text: "{time}".to_string(),
+            font_path: None,
+            size: 2.0,
+            color: default_color(),
+            position: PipPosition::TopLeft,
+            background: false,
+        };
+        assert!(config.validate().is_err());
+    }
+}
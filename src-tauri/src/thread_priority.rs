@@ -0,0 +1,93 @@
+//! Thread Priority
+//!
+//! Capture, compositing and encoding threads ask the OS to schedule them
+//! ahead of ordinary work, so a busy machine delays frames less and the
+//! queues between the stages rarely have to drop them. On macOS the threads
+//! get a QoS class, on Windows a raised thread priority and on Linux a lower
+//! nice value (which needs `CAP_SYS_NICE`; without it the threads keep the
+//! default priority).
+//!
+//! Raising priority is best effort: a failure is logged and recording
+//! carries on at normal priority.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// What a thread does in the pipeline, which decides how much it is raised
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadRole {
+    /// Pulls frames from a capture device; late frames are lost
+    Capture,
+    /// Combines the sources into the output frame
+    Compositor,
+    /// Encodes and muxes; has queue room to catch up
+    Encoder,
+}
+
+/// Only the first failure is logged
+static FAILURE_LOGGED: AtomicBool = AtomicBool::new(false);
+
+/// Raise the calling thread's priority for `role`
+pub fn elevate(role: ThreadRole) {
+    if let Err(e) = set_current(role) {
+        if !FAILURE_LOGGED.swap(true, Ordering::Relaxed) {
+            eprintln!("Could not raise {:?} thread priority: {}", role, e);
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn set_current(role: ThreadRole) -> Result<(), String> {
+    use libc::qos_class_t;
+
+    let class = match role {
+        ThreadRole::Capture | ThreadRole::Compositor => qos_class_t::QOS_CLASS_USER_INTERACTIVE,
+        ThreadRole::Encoder => qos_class_t::QOS_CLASS_USER_INITIATED,
+    };
+    let result = unsafe { libc::pthread_set_qos_class_self_np(class, 0) };
+    if result != 0 {
+        return Err(format!("pthread_set_qos_class_self_np returned {}", result));
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+fn set_current(role: ThreadRole) -> Result<(), String> {
+    use std::ffi::c_void;
+
+    const THREAD_PRIORITY_ABOVE_NORMAL: i32 = 1;
+    const THREAD_PRIORITY_HIGHEST: i32 = 2;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCurrentThread() -> *mut c_void;
+        fn SetThreadPriority(thread: *mut c_void, priority: i32) -> i32;
+    }
+
+    let priority = match role {
+        ThreadRole::Capture => THREAD_PRIORITY_HIGHEST,
+        ThreadRole::Compositor | ThreadRole::Encoder => THREAD_PRIORITY_ABOVE_NORMAL,
+    };
+    // GetCurrentThread returns a pseudo handle that needs no closing
+    if unsafe { SetThreadPriority(GetCurrentThread(), priority) } == 0 {
+        return Err(std::io::Error::last_os_error().to_string());
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn set_current(role: ThreadRole) -> Result<(), String> {
+    // On Linux a `who` of 0 with PRIO_PROCESS means the calling thread only
+    let nice = match role {
+        ThreadRole::Capture => -10,
+        ThreadRole::Compositor | ThreadRole::Encoder => -5,
+    };
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) } != 0 {
+        return Err(std::io::Error::last_os_error().to_string());
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+fn set_current(_role: ThreadRole) -> Result<(), String> {
+    Ok(())
+}
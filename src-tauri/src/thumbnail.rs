@@ -0,0 +1,328 @@
+//! Recording Thumbnails
+//!
+//! Once the encoder has finished a recording (after silence trimming and
+//! loudness normalization), a frame from 10% into it is saved as a JPEG next
+//! to the file, for the past recordings list. Decoding the frame needs the
+//! `ffmpeg` feature; without it no thumbnail is written.
+//!
+//! `extract_frames` decodes frames at any times the frontend asks for -
+//! for thumbnail pickers and chapter artwork - and returns them as PNG data
+//! URLs. Each is the frame on screen at that time: decoding starts at the
+//! keyframe before it and runs up to it.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::command;
+
+use crate::output_paths::{allowed_output_dirs, check_output_path};
+use crate::recording::RecordingError;
+
+/// Width thumbnails are scaled down to
+#[cfg(any(feature = "ffmpeg", test))]
+const THUMBNAIL_WIDTH: u32 = 480;
+
+#[cfg(any(feature = "ffmpeg", test))]
+const JPEG_QUALITY: u8 = 80;
+
+/// Fraction of the recording the frame is taken from; the very start is
+/// often a blank screen or the recorder itself
+#[cfg(feature = "ffmpeg")]
+const THUMBNAIL_POSITION: f64 = 0.1;
+
+/// Most frames one `extract_frames` call can ask for
+pub const MAX_EXTRACTED_FRAMES: usize = 64;
+
+/// A frame decoded for the frontend
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtractedFrame {
+    /// Time it was asked for, from the start of the recording
+    pub at_ms: u64,
+    pub width: u32,
+    pub height: u32,
+    /// `data:image/png;base64,...`, usable directly as an image source
+    pub data_url: String,
+}
+
+/// Path of the thumbnail for a recording
+pub fn thumbnail_path(output_path: &Path) -> PathBuf {
+    output_path.with_extension("jpg")
+}
+
+/// `image` scaled down to at most `max_width` wide, keeping its aspect ratio
+#[cfg(any(feature = "ffmpeg", test))]
+fn scale_to_width(image: &image::RgbImage, max_width: u32) -> image::RgbImage {
+    let width = image.width().clamp(1, max_width.max(1));
+    let height = ((image.height() as u64 * width as u64 / image.width().max(1) as u64) as u32).max(1);
+    image::imageops::thumbnail(image, width, height)
+}
+
+/// Save `image` as the JPEG at `path`, scaled down to the thumbnail width
+#[cfg(any(feature = "ffmpeg", test))]
+fn save_thumbnail(image: &image::RgbImage, path: &Path) -> Result<(), String> {
+    let thumbnail = scale_to_width(image, THUMBNAIL_WIDTH);
+
+    let file = std::fs::File::create(path)
+        .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    image::codecs::jpeg::JpegEncoder::new_with_quality(std::io::BufWriter::new(file), JPEG_QUALITY)
+        .encode_image(&thumbnail)
+        .map_err(|e| format!("Thumbnail JPEG encoding failed: {}", e))
+}
+
+/// Check the times asked of `extract_frames`
+fn validate_timestamps(timestamps: &[u64]) -> Result<(), String> {
+    if timestamps.is_empty() {
+        return Err("No frame times given".to_string());
+    }
+    if timestamps.len() > MAX_EXTRACTED_FRAMES {
+        return Err(format!("At most {} frames can be extracted at once", MAX_EXTRACTED_FRAMES));
+    }
+    Ok(())
+}
+
+/// `image` as a PNG data URL
+#[cfg(any(feature = "ffmpeg", test))]
+fn png_data_url(image: &image::RgbImage) -> Result<String, String> {
+    use base64::{engine::general_purpose::STANDARD, Engine as _};
+
+    let mut png = Vec::new();
+    image
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .map_err(|e| format!("Frame PNG encoding failed: {}", e))?;
+    Ok(format!("data:image/png;base64,{}", STANDARD.encode(png)))
+}
+
+/// Decodes frames of a recording's video track at chosen times
+#[cfg(feature = "ffmpeg")]
+struct FrameReader {
+    input: ffmpeg_next::format::context::Input,
+    index: usize,
+    time_base: ffmpeg_next::Rational,
+    decoder: ffmpeg_next::decoder::Video,
+    path: PathBuf,
+}
+
+#[cfg(feature = "ffmpeg")]
+impl FrameReader {
+    fn open(path: &Path) -> Result<Self, String> {
+        let input = crate::media_file::open_input(path)?;
+        let stream = input
+            .streams()
+            .best(ffmpeg_next::media::Type::Video)
+            .ok_or_else(|| format!("{} has no video track", path.display()))?;
+        let (index, time_base) = (stream.index(), stream.time_base());
+        let decoder = ffmpeg_next::codec::context::Context::from_parameters(stream.parameters())
+            .and_then(|context| context.decoder().video())
+            .map_err(|e| format!("Failed to open video decoder: {}", e))?;
+        Ok(Self {
+            input,
+            index,
+            time_base,
+            decoder,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Length of the recording in microseconds
+    fn duration_us(&self) -> i64 {
+        self.input.duration().max(0)
+    }
+
+    /// The frame on screen `position_us` into the recording; past the end,
+    /// the last frame
+    fn frame_at(&mut self, position_us: i64) -> Result<image::RgbImage, String> {
+        use ffmpeg_next::Rescale;
+
+        // Lands on the keyframe before the position (in AV_TIME_BASE units)
+        self.input
+            .seek(position_us, ..position_us)
+            .map_err(|e| format!("Failed to seek {}: {}", self.path.display(), e))?;
+        self.decoder.flush();
+        let target = position_us.rescale(ffmpeg_next::Rational(1, 1_000_000), self.time_base);
+
+        let mut shown = None;
+        let mut found = false;
+        for (stream, packet) in self.input.packets() {
+            if stream.index() != self.index {
+                continue;
+            }
+            self.decoder
+                .send_packet(&packet)
+                .map_err(|e| format!("Failed to decode video: {}", e))?;
+            found = receive_until(&mut self.decoder, target, &mut shown);
+            if found {
+                break;
+            }
+        }
+        if !found {
+            // Short recordings may only produce frames once the decoder is drained
+            self.decoder
+                .send_eof()
+                .map_err(|e| format!("Failed to decode video: {}", e))?;
+            receive_until(&mut self.decoder, target, &mut shown);
+        }
+        let frame = shown.ok_or_else(|| format!("No video frame found in {}", self.path.display()))?;
+        rgb_image(&frame)
+    }
+}
+
+/// Take frames from `decoder` into `shown` up to the one on screen at
+/// `target`, returning whether it has been reached
+#[cfg(feature = "ffmpeg")]
+fn receive_until(
+    decoder: &mut ffmpeg_next::decoder::Video,
+    target: i64,
+    shown: &mut Option<ffmpeg_next::frame::Video>,
+) -> bool {
+    let mut frame = ffmpeg_next::frame::Video::empty();
+    while decoder.receive_frame(&mut frame).is_ok() {
+        match frame.timestamp() {
+            Some(time) if time < target => *shown = Some(std::mem::replace(&mut frame, ffmpeg_next::frame::Video::empty())),
+            // The frame before is still on screen at the target
+            Some(time) if time > target && shown.is_some() => return true,
+            _ => {
+                *shown = Some(frame);
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// Convert a decoded frame to an RGB image
+#[cfg(feature = "ffmpeg")]
+fn rgb_image(frame: &ffmpeg_next::frame::Video) -> Result<image::RgbImage, String> {
+    use ffmpeg_next::{format::Pixel, software::scaling};
+
+    let (width, height) = (frame.width(), frame.height());
+    let mut scaler = scaling::Context::get(
+        frame.format(),
+        width,
+        height,
+        Pixel::RGB24,
+        width,
+        height,
+        scaling::Flags::BILINEAR,
+    )
+    .map_err(|e| format!("Failed to create scaler: {}", e))?;
+    let mut rgb = ffmpeg_next::frame::Video::empty();
+    scaler
+        .run(frame, &mut rgb)
+        .map_err(|e| format!("Failed to convert frame: {}", e))?;
+
+    // Rows are padded out to the stride
+    let row_bytes = width as usize * 3;
+    let mut pixels = Vec::with_capacity(row_bytes * height as usize);
+    for row in rgb.data(0).chunks(rgb.stride(0)).take(height as usize) {
+        pixels.extend_from_slice(&row[..row_bytes]);
+    }
+    image::RgbImage::from_raw(width, height, pixels)
+        .ok_or_else(|| "Decoded frame is smaller than its dimensions".to_string())
+}
+
+/// Write the thumbnail for a finished recording, returning its path
+#[cfg(feature = "ffmpeg")]
+pub fn write_thumbnail(recording: &Path) -> Result<PathBuf, String> {
+    let mut reader = FrameReader::open(recording)?;
+    let position = (reader.duration_us() as f64 * THUMBNAIL_POSITION) as i64;
+    let image = reader.frame_at(position)?;
+
+    let path = thumbnail_path(recording);
+    save_thumbnail(&image, &path)?;
+    Ok(path)
+}
+
+/// Decode the frames of the recording at `path` at each of `timestamps`
+/// (milliseconds), scaled down to at most `max_width` wide
+#[cfg(feature = "ffmpeg")]
+pub fn extract_frames_at(path: &Path, timestamps: &[u64], max_width: Option<u32>) -> Result<Vec<ExtractedFrame>, String> {
+    validate_timestamps(timestamps)?;
+    ffmpeg_next::init().map_err(|e| format!("FFmpeg init failed: {}", e))?;
+
+    let mut reader = FrameReader::open(path)?;
+    let duration_ms = (reader.duration_us() / 1000) as u64;
+    timestamps
+        .iter()
+        .map(|&at_ms| {
+            if at_ms > duration_ms {
+                return Err(format!("{} ms is past the end of {} ({} ms)", at_ms, path.display(), duration_ms));
+            }
+            let mut image = reader.frame_at(at_ms as i64 * 1000)?;
+            if let Some(max_width) = max_width {
+                image = scale_to_width(&image, max_width);
+            }
+            Ok(ExtractedFrame {
+                at_ms,
+                width: image.width(),
+                height: image.height(),
+                data_url: png_data_url(&image)?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(not(feature = "ffmpeg"))]
+pub fn extract_frames_at(_path: &Path, timestamps: &[u64], _max_width: Option<u32>) -> Result<Vec<ExtractedFrame>, String> {
+    validate_timestamps(timestamps)?;
+    Err("Frame extraction requires FFmpeg support".to_string())
+}
+
+/// Tauri command: Frames of a recording at the given times (milliseconds), as
+/// PNG data URLs in the order asked for
+///
+/// `max_width` scales frames down keeping their aspect ratio (default: full
+/// size).
+#[command]
+pub async fn extract_frames(
+    path: String,
+    timestamps: Vec<u64>,
+    max_width: Option<u32>,
+) -> Result<Vec<ExtractedFrame>, RecordingError> {
+    validate_timestamps(&timestamps).map_err(RecordingError::InvalidConfig)?;
+    if max_width.is_some_and(|width| !(16..=7680).contains(&width)) {
+        return Err(RecordingError::InvalidConfig("Frame width must be 16-7680".to_string()));
+    }
+    let path = PathBuf::from(path);
+    if !path.is_file() {
+        return Err(format!("Recording not found: {}", path.display()).into());
+    }
+    let path = check_output_path(&path, &allowed_output_dirs())?;
+    tauri::async_runtime::spawn_blocking(move || extract_frames_at(&path, &timestamps, max_width))
+        .await
+        .map_err(|e| format!("Frame extraction failed: {}", e))?
+        .map_err(RecordingError::Failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thumbnail_is_scaled_jpeg_next_to_recording() {
+        let dir = std::env::temp_dir().join(format!("asmr_thumbnail_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = thumbnail_path(&dir.join("session.mp4"));
+        assert_eq!(path, dir.join("session.jpg"));
+
+        save_thumbnail(&image::RgbImage::new(1920, 1080), &path).unwrap();
+        let saved = image::open(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!((saved.width(), saved.height()), (480, 270));
+    }
+
+    #[test]
+    fn test_extracted_frames_are_png_data_urls() {
+        use base64::{engine::general_purpose::STANDARD, Engine as _};
+
+        assert!(validate_timestamps(&[]).is_err());
+        assert!(validate_timestamps(&[0; MAX_EXTRACTED_FRAMES + 1]).is_err());
+        assert!(validate_timestamps(&[0, 1500]).is_ok());
+
+        let frame = scale_to_width(&image::RgbImage::from_pixel(1280, 720, image::Rgb([10, 20, 30])), 320);
+        let url = png_data_url(&frame).unwrap();
+        let png = STANDARD.decode(url.strip_prefix("data:image/png;base64,").unwrap()).unwrap();
+        let decoded = image::load_from_memory_with_format(&png, image::ImageFormat::Png).unwrap().to_rgb8();
+        assert_eq!(decoded.dimensions(), (320, 180));
+        assert_eq!(decoded.get_pixel(100, 100).0, [10, 20, 30]);
+    }
+}
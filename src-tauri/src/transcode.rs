@@ -0,0 +1,1314 @@
+//! Transcoding
+//!
+//! Converts a finished recording to another format - typically the WebM
+//! files MediaRecorder saves, to MP4 - optionally scaling the video down or
+//! re-encoding it at a set bitrate. Streams the new format can carry as they
+//! are, at the same size, are copied untouched; the rest are re-encoded with
+//! the format's codecs (H.264 and AAC for MP4 and MKV, VP9 and Opus for
+//! WebM). Only the main video and audio tracks are kept.
+//!
+//! The audio track alone can also be extracted, for publishing it separately:
+//! as 24-bit WAV, or as FLAC or MP3 (copied if it already is one). And a short
+//! part of a recording can be exported as an animated GIF or a small MP4 clip,
+//! for sharing previews.
+//!
+//! Long sessions can be condensed by re-timing them - 8x speed, say. The
+//! video is re-encoded with frames dropped so it keeps its original frame
+//! rate; the audio is either left out or time-stretched, which keeps its
+//! pitch: short windowed grains taken `speed` times further apart than they
+//! are laid back down, overlapping so they add up to the original level.
+//!
+//! The copy is written next to the original, which is left in place. While a
+//! conversion runs, `transcode-progress` events report how far through the
+//! original it is.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::{command, AppHandle, Emitter};
+
+#[cfg(any(feature = "ffmpeg", test))]
+use crate::media_file::work_path;
+use crate::output_paths::{allowed_output_dirs, check_output_path};
+use crate::recording::{OutputFormat, RecordingError};
+
+/// Event emitted as a transcode progresses
+pub const TRANSCODE_PROGRESS_EVENT: &str = "transcode-progress";
+
+/// Conversion settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TranscodeOptions {
+    /// Format of the copy (default: MP4)
+    #[serde(default)]
+    pub format: OutputFormat,
+    /// Largest video height; taller video is scaled down keeping the aspect
+    /// ratio (144-4320)
+    #[serde(default)]
+    pub max_height: Option<u32>,
+    /// Video bitrate when re-encoding, 300-50000 kbps (default: constant quality)
+    #[serde(default)]
+    pub video_bitrate_kbps: Option<u32>,
+    /// Re-encode every stream, even those the format could carry as they are
+    #[serde(default)]
+    pub reencode: bool,
+}
+
+impl TranscodeOptions {
+    /// Check the settings are in range
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(height) = self.max_height {
+            if !(144..=4320).contains(&height) {
+                return Err(format!("Maximum height must be 144-4320 (got {})", height));
+            }
+        }
+        if let Some(kbps) = self.video_bitrate_kbps {
+            if !(300..=50_000).contains(&kbps) {
+                return Err(format!("Video bitrate must be 300-50000 kbps (got {})", kbps));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Format audio is extracted to
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioExportFormat {
+    /// 24-bit PCM
+    #[default]
+    Wav,
+    /// Lossless, 24-bit
+    Flac,
+    /// 256 kbps
+    Mp3,
+}
+
+#[cfg_attr(not(feature = "ffmpeg"), allow(dead_code))]
+impl AudioExportFormat {
+    /// Extension of the extracted file
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AudioExportFormat::Wav => "wav",
+            AudioExportFormat::Flac => "flac",
+            AudioExportFormat::Mp3 => "mp3",
+        }
+    }
+}
+
+/// Longest clip `export_clip` writes
+pub const MAX_CLIP_MS: u64 = 60_000;
+
+/// Width GIF clips are scaled down to
+#[cfg(feature = "ffmpeg")]
+const GIF_WIDTH: u32 = 480;
+
+#[cfg(feature = "ffmpeg")]
+const GIF_FRAME_RATE: u32 = 12;
+
+/// Height MP4 clips are scaled down to
+#[cfg(feature = "ffmpeg")]
+const CLIP_MAX_HEIGHT: u32 = 720;
+
+/// Format of an exported clip
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ClipFormat {
+    /// Animated GIF, 480 pixels wide at 12 fps, without sound
+    #[default]
+    Gif,
+    /// H.264 and AAC, at most 720 pixels tall
+    Mp4,
+}
+
+/// Check a clip's range is ordered and short enough
+fn validate_clip(start_ms: u64, end_ms: u64) -> Result<(), String> {
+    if end_ms <= start_ms {
+        return Err(format!("Clip must end after it starts ({} to {} ms)", start_ms, end_ms));
+    }
+    if end_ms - start_ms > MAX_CLIP_MS {
+        return Err(format!(
+            "Clips can be at most {} seconds (got {:.1})",
+            MAX_CLIP_MS / 1000,
+            (end_ms - start_ms) as f64 / 1000.0
+        ));
+    }
+    Ok(())
+}
+
+/// `<stem>.clip-<start>-<end>.<gif|mp4>` next to `path`, the range in
+/// milliseconds
+#[cfg_attr(not(feature = "ffmpeg"), allow(dead_code))]
+fn clip_path(path: &Path, start_ms: u64, end_ms: u64, format: ClipFormat) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = match format {
+        ClipFormat::Gif => "gif",
+        ClipFormat::Mp4 => "mp4",
+    };
+    path.with_file_name(format!("{}.clip-{}-{}.{}", stem, start_ms, end_ms, extension))
+}
+
+/// Slowest and fastest re-timing
+pub const MIN_SPEED: f32 = 0.25;
+pub const MAX_SPEED: f32 = 64.0;
+
+/// What happens to the audio when a recording is re-timed
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RetimeAudio {
+    /// Left out
+    #[default]
+    Drop,
+    /// Time-stretched to the new speed at its original pitch
+    Preserve,
+}
+
+/// Re-timing settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetimeOptions {
+    /// Playback speed relative to the original, 0.25-64 (8 condenses an hour
+    /// into 7.5 minutes)
+    pub speed: f32,
+    #[serde(default)]
+    pub audio: RetimeAudio,
+    /// Format of the copy (default: MP4)
+    #[serde(default)]
+    pub format: OutputFormat,
+}
+
+impl RetimeOptions {
+    /// Check the speed is in range and actually changes something
+    pub fn validate(&self) -> Result<(), String> {
+        if !(MIN_SPEED..=MAX_SPEED).contains(&self.speed) {
+            return Err(format!("Speed must be {}-{}x (got {})", MIN_SPEED, MAX_SPEED, self.speed));
+        }
+        if self.speed == 1.0 {
+            return Err("Speed must differ from 1x; use transcode_recording to convert".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// `<stem>.<speed>x.<ext>` next to `path`
+#[cfg_attr(not(feature = "ffmpeg"), allow(dead_code))]
+fn retime_path(path: &Path, speed: f32, format: OutputFormat) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}.{}x.{}", stem, speed, format.extension()))
+}
+
+/// Payload of the progress event
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TranscodeProgress {
+    /// Recording being converted
+    pub path: String,
+    /// How far through it the conversion is, 0-100
+    pub percent: u8,
+}
+
+/// Where the copy of `path` in `format` is written: the same name with the
+/// format's extension, or `<stem>.converted.<ext>` if that is the original
+#[cfg(any(feature = "ffmpeg", test))]
+pub fn transcode_path(path: &Path, format: OutputFormat) -> PathBuf {
+    let output = path.with_extension(format.extension());
+    if output == path {
+        work_path(path, "converted")
+    } else {
+        output
+    }
+}
+
+/// Video size after scaling down to `max_height`, rounded to even numbers as
+/// 4:2:0 video needs
+#[cfg_attr(not(feature = "ffmpeg"), allow(dead_code))]
+fn scaled_size(width: u32, height: u32, max_height: Option<u32>) -> (u32, u32) {
+    let (width, height) = match max_height {
+        Some(max) if height > max => ((width as u64 * max as u64 / height.max(1) as u64) as u32, max),
+        _ => (width, height),
+    };
+    ((width & !1).max(2), (height & !1).max(2))
+}
+
+/// Convert the recording at `path`, returning the path of the copy
+///
+/// `progress` is called with the percentage done whenever it changes.
+#[cfg(feature = "ffmpeg")]
+pub fn transcode_file(
+    path: &Path,
+    options: &TranscodeOptions,
+    mut progress: impl FnMut(u8),
+) -> Result<PathBuf, String> {
+    use crate::media_file::{create_output, open_input, rewrite, suffix};
+
+    options.validate()?;
+    ffmpeg_next::init().map_err(|e| format!("FFmpeg init failed: {}", e))?;
+
+    let output_path = transcode_path(path, options.format);
+    if output_path.exists() {
+        return Err(format!("{} already exists", output_path.display()));
+    }
+    let size = std::fs::metadata(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?
+        .len()
+        .max(1);
+
+    rewrite(&output_path, suffix::TRANSCODING, |temp_path| {
+        let mut input = open_input(path)?;
+        let mut output = create_output(temp_path, &input)?;
+        let mut routes = plan_routes(&input, &mut output, path, options)?;
+
+        let mut percent = 0;
+        progress(percent);
+        write_routes(&mut input, &mut output, temp_path, &mut routes, None, |position| {
+            // How far through the file the packet was read from
+            let done = (position * 100 / size).min(99) as u8;
+            if done > percent {
+                percent = done;
+                progress(percent);
+            }
+        })
+    })?;
+
+    progress(100);
+    println!("Transcoded {} to {}", path.display(), output_path.display());
+    Ok(output_path)
+}
+
+#[cfg(not(feature = "ffmpeg"))]
+pub fn transcode_file(
+    _path: &Path,
+    options: &TranscodeOptions,
+    _progress: impl FnMut(u8),
+) -> Result<PathBuf, String> {
+    options.validate()?;
+    Err("Transcoding requires FFmpeg support".to_string())
+}
+
+/// Write the audio track of the recording at `path` to a file of its own,
+/// returning that file's path
+#[cfg(feature = "ffmpeg")]
+pub fn extract_audio_file(path: &Path, format: AudioExportFormat) -> Result<PathBuf, String> {
+    use crate::media_file::{add_copy_stream, create_output, open_input, rewrite, suffix, AudioTrackReader};
+    use crate::recording::AudioBitDepth;
+    use crate::sidecar_wav::WavWriter;
+
+    ffmpeg_next::init().map_err(|e| format!("FFmpeg init failed: {}", e))?;
+    let output_path = path.with_extension(format.extension());
+    if output_path.exists() {
+        return Err(format!("{} already exists", output_path.display()));
+    }
+
+    rewrite(&output_path, suffix::EXTRACTING, |temp_path| {
+        if format == AudioExportFormat::Wav {
+            let reader = AudioTrackReader::open(path)?;
+            let mut wav = WavWriter::create(temp_path, reader.rate(), reader.channels(), AudioBitDepth::Int24)?;
+            let mut result = Ok(());
+            reader.read_all(|samples| {
+                if result.is_ok() {
+                    result = wav.write_samples(samples);
+                }
+            })?;
+            result?;
+            return wav.finalize();
+        }
+
+        let mut input = open_input(path)?;
+        let mut output = create_output(temp_path, &input)?;
+        let stream = input
+            .streams()
+            .best(ffmpeg_next::media::Type::Audio)
+            .ok_or_else(|| format!("{} has no audio track", path.display()))?;
+        let (id, encoder) = match format {
+            AudioExportFormat::Flac => (ffmpeg_next::codec::Id::FLAC, ("flac", None)),
+            _ => (ffmpeg_next::codec::Id::MP3, ("libmp3lame", Some(256_000))),
+        };
+        let route = if stream.parameters().id() == id {
+            add_copy_stream(&mut output, &stream)?.map(Route::Copy)
+        } else {
+            Some(Route::Audio(Box::new(open_audio_stage(
+                &input,
+                path,
+                &stream,
+                &mut output,
+                encoder,
+                false,
+            )?)))
+        };
+        let mut routes: Vec<Option<Route>> = (0..input.nb_streams()).map(|_| None).collect();
+        routes[stream.index()] = route;
+        write_routes(&mut input, &mut output, temp_path, &mut routes, None, |_| {})
+    })?;
+
+    println!("Extracted audio of {} to {}", path.display(), output_path.display());
+    Ok(output_path)
+}
+
+#[cfg(not(feature = "ffmpeg"))]
+pub fn extract_audio_file(_path: &Path, _format: AudioExportFormat) -> Result<PathBuf, String> {
+    Err("Audio extraction requires FFmpeg support".to_string())
+}
+
+/// Decide what happens to each input stream: copied, re-encoded, or
+/// dropped (None)
+#[cfg(feature = "ffmpeg")]
+fn plan_routes(
+    input: &ffmpeg_next::format::context::Input,
+    output: &mut ffmpeg_next::format::context::Output,
+    path: &Path,
+    options: &TranscodeOptions,
+) -> Result<Vec<Option<Route>>, String> {
+    use crate::media_file::add_copy_stream;
+
+    let global_header = output
+        .format()
+        .flags()
+        .contains(ffmpeg_next::format::flag::Flags::GLOBAL_HEADER);
+
+    let video_index = input.streams().best(ffmpeg_next::media::Type::Video).map(|s| s.index());
+    let audio_index = input.streams().best(ffmpeg_next::media::Type::Audio).map(|s| s.index());
+
+    let mut routes = Vec::new();
+    for stream in input.streams() {
+        let route = if Some(stream.index()) == video_index {
+            let parameters = stream.parameters();
+            let (width, height) = unsafe { ((*parameters.as_ptr()).width, (*parameters.as_ptr()).height) };
+            let scaled = scaled_size(width as u32, height as u32, options.max_height);
+            if !options.reencode
+                && options.video_bitrate_kbps.is_none()
+                && scaled == (width as u32, height as u32)
+                && carries_video(options.format, parameters.id())
+            {
+                add_copy_stream(output, &stream)?.map(Route::Copy)
+            } else {
+                Some(Route::Video(Box::new(VideoStage::new(
+                    &stream,
+                    output,
+                    options,
+                    scaled,
+                    global_header,
+                )?)))
+            }
+        } else if Some(stream.index()) == audio_index {
+            if !options.reencode && carries_audio(options.format, stream.parameters().id()) {
+                add_copy_stream(output, &stream)?.map(Route::Copy)
+            } else {
+                Some(Route::Audio(Box::new(open_audio_stage(
+                    input,
+                    path,
+                    &stream,
+                    output,
+                    audio_encoder(options.format),
+                    global_header,
+                )?)))
+            }
+        } else {
+            None
+        };
+        routes.push(route);
+    }
+    Ok(routes)
+}
+
+/// Export `start_ms` to `end_ms` of the recording at `path` as a clip,
+/// returning the clip's path
+#[cfg(feature = "ffmpeg")]
+pub fn export_clip_file(path: &Path, start_ms: u64, end_ms: u64, format: ClipFormat) -> Result<PathBuf, String> {
+    use crate::media_file::{create_output, open_input, rewrite, suffix};
+
+    validate_clip(start_ms, end_ms)?;
+    ffmpeg_next::init().map_err(|e| format!("FFmpeg init failed: {}", e))?;
+    let output_path = clip_path(path, start_ms, end_ms, format);
+    if output_path.exists() {
+        return Err(format!("{} already exists", output_path.display()));
+    }
+    let window = Window {
+        start_us: start_ms as i64 * 1000,
+        end_us: end_ms as i64 * 1000,
+    };
+
+    rewrite(&output_path, suffix::EXPORTING, |temp_path| match format {
+        ClipFormat::Gif => write_gif(path, temp_path, window),
+        ClipFormat::Mp4 => {
+            let options = TranscodeOptions {
+                format: OutputFormat::Mp4,
+                max_height: Some(CLIP_MAX_HEIGHT),
+                video_bitrate_kbps: None,
+                reencode: true,
+            };
+            let mut input = open_input(path)?;
+            let mut output = create_output(temp_path, &input)?;
+            let mut routes = plan_routes(&input, &mut output, path, &options)?;
+            // Lands on the keyframe before the start
+            input
+                .seek(window.start_us, ..window.start_us)
+                .map_err(|e| format!("Failed to seek {}: {}", path.display(), e))?;
+            write_routes(&mut input, &mut output, temp_path, &mut routes, Some(window), |_| {})
+        }
+    })?;
+
+    println!("Exported {} to {} ms of {} to {}", start_ms, end_ms, path.display(), output_path.display());
+    Ok(output_path)
+}
+
+#[cfg(not(feature = "ffmpeg"))]
+pub fn export_clip_file(_path: &Path, start_ms: u64, end_ms: u64, _format: ClipFormat) -> Result<PathBuf, String> {
+    validate_clip(start_ms, end_ms)?;
+    Err("Clip export requires FFmpeg support".to_string())
+}
+
+/// Write a copy of the recording at `path` played at `options.speed`,
+/// returning the copy's path
+///
+/// `progress` is called with the percentage done whenever it changes.
+#[cfg(feature = "ffmpeg")]
+pub fn retime_file(path: &Path, options: &RetimeOptions, mut progress: impl FnMut(u8)) -> Result<PathBuf, String> {
+    use crate::media_file::{create_output, open_input, rewrite, suffix};
+
+    options.validate()?;
+    ffmpeg_next::init().map_err(|e| format!("FFmpeg init failed: {}", e))?;
+    let output_path = retime_path(path, options.speed, options.format);
+    if output_path.exists() {
+        return Err(format!("{} already exists", output_path.display()));
+    }
+    let size = std::fs::metadata(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?
+        .len()
+        .max(1);
+
+    rewrite(&output_path, suffix::RETIMING, |temp_path| {
+        let mut input = open_input(path)?;
+        let mut output = create_output(temp_path, &input)?;
+        let mut routes = plan_retime_routes(&input, &mut output, path, options)?;
+
+        let mut percent = 0;
+        progress(percent);
+        write_routes(&mut input, &mut output, temp_path, &mut routes, None, |position| {
+            let done = (position * 100 / size).min(99) as u8;
+            if done > percent {
+                percent = done;
+                progress(percent);
+            }
+        })
+    })?;
+
+    progress(100);
+    println!("Re-timed {} at {}x to {}", path.display(), options.speed, output_path.display());
+    Ok(output_path)
+}
+
+#[cfg(not(feature = "ffmpeg"))]
+pub fn retime_file(_path: &Path, options: &RetimeOptions, _progress: impl FnMut(u8)) -> Result<PathBuf, String> {
+    options.validate()?;
+    Err("Re-timing requires FFmpeg support".to_string())
+}
+
+/// Re-encode the video at the new speed and, if it is kept, time-stretch
+/// the audio; everything else is dropped
+#[cfg(feature = "ffmpeg")]
+fn plan_retime_routes(
+    input: &ffmpeg_next::format::context::Input,
+    output: &mut ffmpeg_next::format::context::Output,
+    path: &Path,
+    options: &RetimeOptions,
+) -> Result<Vec<Option<Route>>, String> {
+    use ffmpeg_next::media::Type;
+    use ffmpeg_next::Rescale;
+
+    let global_header = output
+        .format()
+        .flags()
+        .contains(ffmpeg_next::format::flag::Flags::GLOBAL_HEADER);
+    let speed = options.speed as f64;
+    let mut routes: Vec<Option<Route>> = (0..input.nb_streams()).map(|_| None).collect();
+
+    let video = input
+        .streams()
+        .best(Type::Video)
+        .ok_or_else(|| format!("{} has no video track", path.display()))?;
+    let transcode = TranscodeOptions {
+        format: options.format,
+        reencode: true,
+        ..TranscodeOptions::default()
+    };
+    let parameters = video.parameters();
+    let (width, height) = unsafe { ((*parameters.as_ptr()).width, (*parameters.as_ptr()).height) };
+    let mut stage = VideoStage::new(
+        &video,
+        output,
+        &transcode,
+        scaled_size(width as u32, height as u32, None),
+        global_header,
+    )?;
+    let frame_rate = match video.avg_frame_rate() {
+        rate if rate.numerator() > 0 => rate,
+        _ => ffmpeg_next::Rational(30, 1),
+    };
+    stage.retime = Some(Retime::new(speed, 1i64.rescale(frame_rate.invert(), video.time_base()).max(1)));
+    routes[video.index()] = Some(Route::Video(Box::new(stage)));
+
+    if options.audio == RetimeAudio::Preserve {
+        if let Some(stream) = input.streams().best(Type::Audio) {
+            let decoder = ffmpeg_next::codec::context::Context::from_parameters(stream.parameters())
+                .and_then(|context| context.decoder().audio())
+                .map_err(|e| format!("Failed to read audio of {}: {}", path.display(), e))?;
+            let mut stretch = TimeStretch::new(speed, decoder.rate(), decoder.channels());
+            let stage = open_audio_stage(input, path, &stream, output, audio_encoder(options.format), global_header)?
+                .with_filter(Box::new(move |samples, out| stretch.process(samples, out)))
+                .with_speed(speed);
+            routes[stream.index()] = Some(Route::Audio(Box::new(stage)));
+        }
+    }
+    Ok(routes)
+}
+
+/// Maps input frame times to the re-timed output, dropping frames that
+/// would come faster than the original frame rate
+#[cfg_attr(not(feature = "ffmpeg"), allow(dead_code))]
+struct Retime {
+    speed: f64,
+    /// Shortest gap between output frames (stream time base)
+    interval: i64,
+    /// Output time before which frames are dropped
+    next_due: Option<i64>,
+}
+
+#[cfg_attr(not(feature = "ffmpeg"), allow(dead_code))]
+impl Retime {
+    fn new(speed: f64, interval: i64) -> Self {
+        Self {
+            speed,
+            interval: interval.max(1),
+            next_due: None,
+        }
+    }
+
+    /// Output time of the frame at input `time`, or None to drop it
+    fn take(&mut self, time: i64) -> Option<i64> {
+        let time = (time as f64 / self.speed).round() as i64;
+        let due = self.next_due.unwrap_or(time);
+        if time < due {
+            return None;
+        }
+        self.next_due = Some(due + self.interval * ((time - due) / self.interval + 1));
+        Some(time)
+    }
+}
+
+/// Changes the speed of audio keeping its pitch: Hann-windowed grains are
+/// read `speed` hops apart and overlap-added one hop apart
+#[cfg_attr(not(feature = "ffmpeg"), allow(dead_code))]
+struct TimeStretch {
+    channels: usize,
+    speed: f64,
+    /// Output frames between grains; grains are two hops long
+    hop: usize,
+    window: Vec<f32>,
+    /// Input not yet read by every grain that needs it
+    input: Vec<f32>,
+    /// Frame of `input` the next grain starts at
+    position: f64,
+    /// Sum of the grains laid down so far that isn't complete yet
+    overlap: Vec<f32>,
+}
+
+#[cfg_attr(not(feature = "ffmpeg"), allow(dead_code))]
+impl TimeStretch {
+    fn new(speed: f64, sample_rate: u32, channels: u16) -> Self {
+        let channels = channels.max(1) as usize;
+        // 20ms hops, 40ms grains
+        let hop = (sample_rate as usize / 50).max(1);
+        let window = (0..hop * 2)
+            .map(|i| (0.5 - 0.5 * (std::f64::consts::PI * i as f64 / hop as f64).cos()) as f32)
+            .collect();
+        Self {
+            channels,
+            speed,
+            hop,
+            window,
+            input: Vec::new(),
+            position: 0.0,
+            overlap: vec![0.0; hop * 2 * channels],
+        }
+    }
+
+    /// Take interleaved samples in, appending the stretched output to `out`
+    ///
+    /// Input shorter than a grain at the very end is never played.
+    fn process(&mut self, samples: &[f32], out: &mut Vec<f32>) {
+        let channels = self.channels;
+        let grain = self.hop * 2;
+        self.input.extend_from_slice(samples);
+
+        while (self.position as usize + grain) * channels <= self.input.len() {
+            let start = self.position as usize * channels;
+            let source = &self.input[start..start + grain * channels];
+            for (i, (sum, sample)) in self.overlap.iter_mut().zip(source).enumerate() {
+                *sum += sample * self.window[i / channels];
+            }
+            out.extend(self.overlap.drain(..self.hop * channels));
+            self.overlap.resize(grain * channels, 0.0);
+            self.position += self.speed * self.hop as f64;
+        }
+
+        // No later grain reads before the next one's start
+        let used = (self.position as usize).min(self.input.len() / channels);
+        self.input.drain(..used * channels);
+        self.position -= used as f64;
+    }
+}
+
+/// Write the part of the video in `window` as an endlessly looping GIF
+#[cfg(feature = "ffmpeg")]
+fn write_gif(path: &Path, temp_path: &Path, window: Window) -> Result<(), String> {
+    use ffmpeg_next::{format::Pixel, software::scaling, Rescale};
+    use image::codecs::gif::{GifEncoder, Repeat};
+
+    let mut input = crate::media_file::open_input(path)?;
+    let (index, time_base, mut decoder) = {
+        let stream = input
+            .streams()
+            .best(ffmpeg_next::media::Type::Video)
+            .ok_or_else(|| format!("{} has no video track", path.display()))?;
+        let decoder = ffmpeg_next::codec::context::Context::from_parameters(stream.parameters())
+            .and_then(|context| context.decoder().video())
+            .map_err(|e| format!("Failed to open video decoder: {}", e))?;
+        (stream.index(), stream.time_base(), decoder)
+    };
+    let width = decoder.width().clamp(1, GIF_WIDTH);
+    let height = ((decoder.height() as u64 * width as u64 / decoder.width().max(1) as u64) as u32).max(1);
+    let mut scaler = scaling::Context::get(
+        decoder.format(),
+        decoder.width(),
+        decoder.height(),
+        Pixel::RGBA,
+        width,
+        height,
+        scaling::Flags::BILINEAR,
+    )
+    .map_err(|e| format!("Failed to create scaler: {}", e))?;
+
+    input
+        .seek(window.start_us, ..window.start_us)
+        .map_err(|e| format!("Failed to seek {}: {}", path.display(), e))?;
+    let file = std::fs::File::create(temp_path)
+        .map_err(|e| format!("Failed to create {}: {}", temp_path.display(), e))?;
+    let mut encoder = GifEncoder::new_with_speed(std::io::BufWriter::new(file), 10);
+    encoder
+        .set_repeat(Repeat::Infinite)
+        .map_err(|e| format!("GIF encoding failed: {}", e))?;
+
+    // Frames are taken at the GIF's rate, each shown until the next is due
+    let frame_interval = (1_000_000 / GIF_FRAME_RATE as i64)
+        .rescale(Window::MICROS, time_base)
+        .max(1);
+    let delay = image::Delay::from_numer_denom_ms(1000, GIF_FRAME_RATE);
+    let mut next_due = window.start(time_base);
+    let mut decoded = ffmpeg_next::frame::Video::empty();
+    let mut rgba = ffmpeg_next::frame::Video::empty();
+    let mut packets = input.packets();
+    loop {
+        let end_of_file = match packets.next() {
+            Some((stream, packet)) if stream.index() == index => {
+                decoder
+                    .send_packet(&packet)
+                    .map_err(|e| format!("Failed to decode video: {}", e))?;
+                false
+            }
+            Some(_) => continue,
+            None => {
+                let _ = decoder.send_eof();
+                true
+            }
+        };
+        while decoder.receive_frame(&mut decoded).is_ok() {
+            let Some(time) = decoded.timestamp() else {
+                continue;
+            };
+            if time >= window.end(time_base) {
+                return Ok(());
+            }
+            if time < next_due {
+                continue;
+            }
+            next_due += frame_interval * ((time - next_due) / frame_interval + 1);
+
+            scaler
+                .run(&decoded, &mut rgba)
+                .map_err(|e| format!("Failed to convert frame: {}", e))?;
+            // Rows are padded out to the stride
+            let row_bytes = width as usize * 4;
+            let mut pixels = Vec::with_capacity(row_bytes * height as usize);
+            for row in rgba.data(0).chunks(rgba.stride(0)).take(height as usize) {
+                pixels.extend_from_slice(&row[..row_bytes]);
+            }
+            let image = image::RgbaImage::from_raw(width, height, pixels)
+                .ok_or("Scaled frame is smaller than its dimensions")?;
+            encoder
+                .encode_frame(image::Frame::from_parts(image, 0, 0, delay))
+                .map_err(|e| format!("GIF encoding failed: {}", e))?;
+        }
+        if end_of_file {
+            return Ok(());
+        }
+    }
+}
+
+/// Write the header, send every packet of `input` along its route and
+/// finish the output
+///
+/// With a `window`, only that part of the input is kept, moved to the start
+/// of the output; `input` should already be at its start. `on_position` is
+/// called with the byte offset each packet was read from.
+#[cfg(feature = "ffmpeg")]
+fn write_routes(
+    input: &mut ffmpeg_next::format::context::Input,
+    output: &mut ffmpeg_next::format::context::Output,
+    temp_path: &Path,
+    routes: &mut [Option<Route>],
+    window: Option<Window>,
+    mut on_position: impl FnMut(u64),
+) -> Result<(), String> {
+    crate::media_file::write_header(output, temp_path)?;
+    let output_time_bases: Vec<_> = output.streams().map(|s| s.time_base()).collect();
+    for route in routes.iter_mut().flatten() {
+        route.set_output_time_base(output);
+        if let Route::Video(stage) = route {
+            stage.window = window;
+        }
+    }
+
+    for (stream, mut packet) in input.packets() {
+        if packet.position() > 0 {
+            on_position(packet.position() as u64);
+        }
+        let time_base = stream.time_base();
+        if let Some(window) = window {
+            // Packets come in decoding order, so none after this is in the window
+            if packet.dts().is_some_and(|dts| dts >= window.end(time_base)) {
+                break;
+            }
+            // Video is cut frame by frame once decoded
+            let decoded = matches!(routes.get(stream.index()), Some(Some(Route::Video(_))));
+            if !decoded && !window.take(&mut packet, time_base) {
+                continue;
+            }
+        }
+        match routes.get_mut(stream.index()) {
+            Some(Some(Route::Copy(output_index))) => {
+                packet.rescale_ts(stream.time_base(), output_time_bases[*output_index]);
+                packet.set_stream(*output_index);
+                packet.set_position(-1);
+                packet
+                    .write_interleaved(output)
+                    .map_err(|e| format!("Failed to copy packet: {}", e))?;
+            }
+            Some(Some(Route::Video(stage))) => stage.send_packet(&packet, output)?,
+            Some(Some(Route::Audio(stage))) => stage.send_packet(&packet, output)?,
+            _ => {}
+        }
+    }
+    for route in routes.iter_mut().flatten() {
+        match route {
+            Route::Video(stage) => stage.finish(output)?,
+            Route::Audio(stage) => stage.finish(output)?,
+            Route::Copy(_) => {}
+        }
+    }
+
+    output
+        .write_trailer()
+        .map_err(|e| format!("Failed to write trailer: {}", e))
+}
+
+/// Whether `format` can carry video in `codec` as it is
+#[cfg(feature = "ffmpeg")]
+fn carries_video(format: OutputFormat, codec: ffmpeg_next::codec::Id) -> bool {
+    use ffmpeg_next::codec::Id;
+
+    match format {
+        OutputFormat::Mp4 => matches!(codec, Id::H264 | Id::HEVC | Id::AV1),
+        OutputFormat::Webm => matches!(codec, Id::VP8 | Id::VP9 | Id::AV1),
+        OutputFormat::Mkv => true,
+    }
+}
+
+/// Whether `format` can carry audio in `codec` as it is
+#[cfg(feature = "ffmpeg")]
+fn carries_audio(format: OutputFormat, codec: ffmpeg_next::codec::Id) -> bool {
+    use ffmpeg_next::codec::Id;
+
+    match format {
+        OutputFormat::Mp4 => matches!(codec, Id::AAC | Id::MP3),
+        OutputFormat::Webm => matches!(codec, Id::OPUS | Id::VORBIS),
+        OutputFormat::Mkv => true,
+    }
+}
+
+/// Part of the input to keep, in microseconds
+#[cfg(feature = "ffmpeg")]
+#[derive(Debug, Clone, Copy)]
+struct Window {
+    start_us: i64,
+    end_us: i64,
+}
+
+#[cfg(feature = "ffmpeg")]
+impl Window {
+    const MICROS: ffmpeg_next::Rational = ffmpeg_next::Rational(1, 1_000_000);
+
+    fn start(&self, time_base: ffmpeg_next::Rational) -> i64 {
+        use ffmpeg_next::Rescale;
+        self.start_us.rescale(Self::MICROS, time_base)
+    }
+
+    fn end(&self, time_base: ffmpeg_next::Rational) -> i64 {
+        use ffmpeg_next::Rescale;
+        self.end_us.rescale(Self::MICROS, time_base)
+    }
+
+    fn contains(&self, time: i64, time_base: ffmpeg_next::Rational) -> bool {
+        (self.start(time_base)..self.end(time_base)).contains(&time)
+    }
+
+    /// Whether a packet starts in the window; if so, its timestamps are moved
+    /// to count from the window's start
+    fn take(&self, packet: &mut ffmpeg_next::Packet, time_base: ffmpeg_next::Rational) -> bool {
+        match packet.pts() {
+            Some(pts) if self.contains(pts, time_base) => {
+                let start = self.start(time_base);
+                packet.set_pts(Some(pts - start));
+                packet.set_dts(packet.dts().map(|dts| dts - start));
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// What happens to an input stream's packets
+#[cfg(feature = "ffmpeg")]
+enum Route {
+    /// Copied to the output stream at this index
+    Copy(usize),
+    Video(Box<VideoStage>),
+    Audio(Box<crate::loudness::GainStage>),
+}
+
+#[cfg(feature = "ffmpeg")]
+impl Route {
+    /// Point a re-encoding stage at the time base the muxer settled on
+    fn set_output_time_base(&mut self, output: &ffmpeg_next::format::context::Output) {
+        match self {
+            Route::Video(stage) => {
+                if let Some(stream) = output.stream(stage.output_index) {
+                    stage.output_time_base = stream.time_base();
+                }
+            }
+            Route::Audio(stage) => stage.set_output_time_base(output),
+            Route::Copy(_) => {}
+        }
+    }
+}
+
+/// Encoder name and bitrate for audio re-encoded to `format`
+#[cfg(feature = "ffmpeg")]
+fn audio_encoder(format: OutputFormat) -> (&'static str, Option<usize>) {
+    match format {
+        OutputFormat::Webm => ("libopus", Some(128_000)),
+        OutputFormat::Mp4 | OutputFormat::Mkv => ("aac", Some(192_000)),
+    }
+}
+
+/// Re-encode the audio with the named encoder and bitrate, at unity gain
+#[cfg(feature = "ffmpeg")]
+fn open_audio_stage(
+    input: &ffmpeg_next::format::context::Input,
+    path: &Path,
+    stream: &ffmpeg_next::format::stream::Stream,
+    output: &mut ffmpeg_next::format::context::Output,
+    (name, bit_rate): (&str, Option<usize>),
+    global_header: bool,
+) -> Result<crate::loudness::GainStage, String> {
+    use crate::loudness::{open_audio_encoder, GainStage};
+    use crate::media_file::open_audio_decoder;
+
+    let (_, decoder) = open_audio_decoder(input, path)?;
+    if name == "libopus" && decoder.rate() != 48_000 {
+        return Err(format!("Opus needs 48kHz audio ({}Hz in {})", decoder.rate(), path.display()));
+    }
+    let codec = ffmpeg_next::encoder::find_by_name(name).ok_or_else(|| format!("{} encoder not found", name))?;
+    let encoder = open_audio_encoder(codec, &decoder, bit_rate, global_header)?;
+
+    let mut audio_stream = output
+        .add_stream(codec)
+        .map_err(|e| format!("Failed to add audio stream: {}", e))?;
+    audio_stream.set_time_base(encoder.time_base());
+    audio_stream.set_parameters(&encoder);
+    audio_stream.set_metadata(stream.metadata().to_owned());
+    let output_index = audio_stream.index();
+    // The muxer's time base is only known once the header is written
+    Ok(GainStage::new(decoder, encoder, 0.0, stream.time_base(), output_index, None))
+}
+
+/// Decodes the video track, scales it and re-encodes it
+#[cfg(feature = "ffmpeg")]
+struct VideoStage {
+    decoder: ffmpeg_next::decoder::Video,
+    scaler: ffmpeg_next::software::scaling::Context,
+    encoder: ffmpeg_next::encoder::video::Encoder,
+    /// Time base of the input stream, which the encoder keeps
+    time_base: ffmpeg_next::Rational,
+    /// Only frames in this part of the input are kept
+    window: Option<Window>,
+    /// Frames are re-timed to another speed
+    retime: Option<Retime>,
+    output_index: usize,
+    output_time_base: ffmpeg_next::Rational,
+}
+
+#[cfg(feature = "ffmpeg")]
+impl VideoStage {
+    /// Open the decoder, scaler and encoder and add the output stream
+    fn new(
+        stream: &ffmpeg_next::format::stream::Stream,
+        output: &mut ffmpeg_next::format::context::Output,
+        options: &TranscodeOptions,
+        (width, height): (u32, u32),
+        global_header: bool,
+    ) -> Result<Self, String> {
+        use ffmpeg_next::format::Pixel;
+
+        let decoder = ffmpeg_next::codec::context::Context::from_parameters(stream.parameters())
+            .and_then(|context| context.decoder().video())
+            .map_err(|e| format!("Failed to open video decoder: {}", e))?;
+        let scaler = ffmpeg_next::software::scaling::Context::get(
+            decoder.format(),
+            decoder.width(),
+            decoder.height(),
+            Pixel::YUV420P,
+            width,
+            height,
+            ffmpeg_next::software::scaling::Flags::BICUBIC,
+        )
+        .map_err(|e| format!("Failed to create scaler: {}", e))?;
+
+        let name = match options.format {
+            OutputFormat::Webm => "libvpx-vp9",
+            OutputFormat::Mp4 | OutputFormat::Mkv => "libx264",
+        };
+        let codec = ffmpeg_next::encoder::find_by_name(name).ok_or_else(|| format!("{} encoder not found", name))?;
+        let mut context = ffmpeg_next::codec::context::Context::new_with_codec(codec);
+        if global_header {
+            context.set_flags(ffmpeg_next::codec::flag::Flags::GLOBAL_HEADER);
+        }
+        let mut encoder = context
+            .encoder()
+            .video()
+            .map_err(|e| format!("Failed to create video encoder: {}", e))?;
+        encoder.set_width(width);
+        encoder.set_height(height);
+        encoder.set_format(Pixel::YUV420P);
+        // Decoded frames keep the input's timestamps, which MediaRecorder
+        // leaves variable
+        encoder.set_time_base(stream.time_base());
+        if stream.avg_frame_rate().numerator() > 0 {
+            encoder.set_frame_rate(Some(stream.avg_frame_rate()));
+        }
+
+        let mut encoder_options = ffmpeg_next::Dictionary::new();
+        match options.video_bitrate_kbps {
+            Some(kbps) => encoder.set_bit_rate(kbps as usize * 1000),
+            // Constant quality; VP9 only does that with no target bitrate
+            None if options.format == OutputFormat::Webm => {
+                encoder_options.set("crf", "32");
+                encoder_options.set("b", "0");
+            }
+            None => encoder_options.set("crf", "20"),
+        }
+        if options.format == OutputFormat::Webm {
+            encoder_options.set("deadline", "good");
+            encoder_options.set("cpu-used", "4");
+            encoder_options.set("row-mt", "1");
+        } else {
+            encoder_options.set("preset", "medium");
+        }
+        let encoder = encoder
+            .open_with(encoder_options)
+            .map_err(|e| format!("Failed to open video encoder: {}", e))?;
+
+        let mut video_stream = output
+            .add_stream(codec)
+            .map_err(|e| format!("Failed to add video stream: {}", e))?;
+        video_stream.set_time_base(encoder.time_base());
+        video_stream.set_parameters(&encoder);
+        video_stream.set_metadata(stream.metadata().to_owned());
+
+        Ok(Self {
+            output_time_base: encoder.time_base(),
+            output_index: video_stream.index(),
+            time_base: stream.time_base(),
+            window: None,
+            retime: None,
+            decoder,
+            scaler,
+            encoder,
+        })
+    }
+
+    fn send_packet(
+        &mut self,
+        packet: &ffmpeg_next::Packet,
+        output: &mut ffmpeg_next::format::context::Output,
+    ) -> Result<(), String> {
+        self.decoder
+            .send_packet(packet)
+            .map_err(|e| format!("Failed to decode video: {}", e))?;
+        self.encode_frames(output)
+    }
+
+    /// Decode, scale and flush everything to the output
+    fn finish(&mut self, output: &mut ffmpeg_next::format::context::Output) -> Result<(), String> {
+        let _ = self.decoder.send_eof();
+        self.encode_frames(output)?;
+        self.encoder
+            .send_eof()
+            .map_err(|e| format!("Failed to send EOF: {}", e))?;
+        self.write_packets(output)
+    }
+
+    fn encode_frames(&mut self, output: &mut ffmpeg_next::format::context::Output) -> Result<(), String> {
+        let mut frame = ffmpeg_next::frame::Video::empty();
+        while self.decoder.receive_frame(&mut frame).is_ok() {
+            let mut scaled = ffmpeg_next::frame::Video::empty();
+            let mut pts = frame.timestamp();
+            if let (Some(window), Some(time)) = (self.window, pts) {
+                if !window.contains(time, self.time_base) {
+                    continue;
+                }
+                pts = Some(time - window.start(self.time_base));
+            }
+            if let (Some(retime), Some(time)) = (&mut self.retime, pts) {
+                let Some(time) = retime.take(time) else {
+                    continue;
+                };
+                pts = Some(time);
+            }
+            self.scaler
+                .run(&frame, &mut scaled)
+                .map_err(|e| format!("Failed to scale frame: {}", e))?;
+            scaled.set_pts(pts);
+            self.encoder
+                .send_frame(&scaled)
+                .map_err(|e| format!("Failed to send video frame: {}", e))?;
+            self.write_packets(output)?;
+        }
+        Ok(())
+    }
+
+    fn write_packets(&mut self, output: &mut ffmpeg_next::format::context::Output) -> Result<(), String> {
+        let mut packet = ffmpeg_next::Packet::empty();
+        while self.encoder.receive_packet(&mut packet).is_ok() {
+            packet.set_stream(self.output_index);
+            packet.rescale_ts(self.encoder.time_base(), self.output_time_base);
+            packet
+                .write_interleaved(output)
+                .map_err(|e| format!("Failed to write video packet: {}", e))?;
+        }
+        Ok(())
+    }
+}
+
+/// Tauri command: Convert a finished recording to another format or size
+///
+/// Emits `transcode-progress` while it runs and returns the path of the
+/// copy once it is complete; the original is left as it is.
+#[command]
+pub async fn transcode_recording(
+    app: AppHandle,
+    path: String,
+    options: Option<TranscodeOptions>,
+) -> Result<String, RecordingError> {
+    let path = PathBuf::from(path);
+    if !path.is_file() {
+        return Err(format!("Recording not found: {}", path.display()).into());
+    }
+    // The copy is written next to the original
+    let path = check_output_path(&path, &allowed_output_dirs())?;
+    let options = options.unwrap_or_default();
+    options.validate().map_err(RecordingError::InvalidConfig)?;
+
+    let event_path = path.to_string_lossy().to_string();
+    tauri::async_runtime::spawn_blocking(move || {
+        transcode_file(&path, &options, |percent| {
+            let payload = TranscodeProgress {
+                path: event_path.clone(),
+                percent,
+            };
+            if let Err(e) = app.emit(TRANSCODE_PROGRESS_EVENT, payload) {
+                eprintln!("Failed to emit {}: {}", TRANSCODE_PROGRESS_EVENT, e);
+            }
+        })
+    })
+    .await
+    .map_err(|e| format!("Transcoding failed: {}", e))?
+    .map(|output| output.to_string_lossy().to_string())
+    .map_err(RecordingError::Failed)
+}
+
+/// Tauri command: Save the audio track of a recording as WAV (default), FLAC
+/// or MP3 next to it
+///
+/// Returns the path of the audio file.
+#[command]
+pub async fn extract_audio(path: String, format: Option<AudioExportFormat>) -> Result<String, RecordingError> {
+    let path = PathBuf::from(path);
+    if !path.is_file() {
+        return Err(format!("Recording not found: {}", path.display()).into());
+    }
+    let path = check_output_path(&path, &allowed_output_dirs())?;
+    let format = format.unwrap_or_default();
+    tauri::async_runtime::spawn_blocking(move || extract_audio_file(&path, format))
+        .await
+        .map_err(|e| format!("Audio extraction failed: {}", e))?
+        .map(|output| output.to_string_lossy().to_string())
+        .map_err(RecordingError::Failed)
+}
+
+/// Tauri command: Export part of a recording as a GIF (default) or MP4 clip
+/// for sharing
+///
+/// Clips are at most a minute long and written next to the recording;
+/// returns the clip's path.
+#[command]
+pub async fn export_clip(
+    path: String,
+    start_ms: u64,
+    end_ms: u64,
+    format: Option<ClipFormat>,
+) -> Result<String, RecordingError> {
+    validate_clip(start_ms, end_ms).map_err(RecordingError::InvalidConfig)?;
+    let path = PathBuf::from(path);
+    if !path.is_file() {
+        return Err(format!("Recording not found: {}", path.display()).into());
+    }
+    let path = check_output_path(&path, &allowed_output_dirs())?;
+    let format = format.unwrap_or_default();
+    tauri::async_runtime::spawn_blocking(move || export_clip_file(&path, start_ms, end_ms, format))
+        .await
+        .map_err(|e| format!("Clip export failed: {}", e))?
+        .map(|output| output.to_string_lossy().to_string())
+        .map_err(RecordingError::Failed)
+}
+
+/// Tauri command: Write a copy of a recording sped up (or slowed down),
+/// with its audio dropped or time-stretched
+///
+/// Emits `transcode-progress` like `transcode_recording` and returns the
+/// path of the copy, `<recording>.<speed>x.<ext>`.
+#[command]
+pub async fn retime_recording(
+    app: AppHandle,
+    path: String,
+    options: RetimeOptions,
+) -> Result<String, RecordingError> {
+    options.validate().map_err(RecordingError::InvalidConfig)?;
+    let path = PathBuf::from(path);
+    if !path.is_file() {
+        return Err(format!("Recording not found: {}", path.display()).into());
+    }
+    let path = check_output_path(&path, &allowed_output_dirs())?;
+
+    let event_path = path.to_string_lossy().to_string();
+    tauri::async_runtime::spawn_blocking(move || {
+        retime_file(&path, &options, |percent| {
+            let payload = TranscodeProgress {
+                path: event_path.clone(),
+                percent,
+            };
+            if let Err(e) = app.emit(TRANSCODE_PROGRESS_EVENT, payload) {
+                eprintln!("Failed to emit {}: {}", TRANSCODE_PROGRESS_EVENT, e);
+            }
+        })
+    })
+    .await
+    .map_err(|e| format!("Re-timing failed: {}", e))?
+    .map(|output| output.to_string_lossy().to_string())
+    .map_err(RecordingError::Failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transcode_output_and_scaling() {
+        assert_eq!(
+            transcode_path(Path::new("/tmp/recording.webm"), OutputFormat::Mp4),
+            PathBuf::from("/tmp/recording.mp4")
+        );
+        assert_eq!(
+            transcode_path(Path::new("/tmp/recording.mp4"), OutputFormat::Mp4),
+            PathBuf::from("/tmp/recording.converted.mp4")
+        );
+
+        assert_eq!(scaled_size(1920, 1080, Some(720)), (1280, 720));
+        assert_eq!(scaled_size(1921, 1081, None), (1920, 1080));
+        assert_eq!(scaled_size(1280, 720, Some(1080)), (1280, 720));
+
+        let tiny = TranscodeOptions {
+            max_height: Some(100),
+            ..TranscodeOptions::default()
+        };
+        assert!(tiny.validate().is_err());
+    }
+
+    #[test]
+    fn test_clip_range_and_path() {
+        assert!(validate_clip(5_000, 20_000).is_ok());
+        assert!(validate_clip(20_000, 5_000).is_err());
+        assert!(validate_clip(0, MAX_CLIP_MS + 1).is_err());
+        assert_eq!(
+            clip_path(Path::new("/tmp/session.webm"), 5_000, 20_000, ClipFormat::Gif),
+            PathBuf::from("/tmp/session.clip-5000-20000.gif")
+        );
+    }
+
+    #[test]
+    fn test_retime_frames_and_stretched_audio() {
+        let options = RetimeOptions {
+            speed: 8.0,
+            audio: RetimeAudio::Preserve,
+            format: OutputFormat::Mp4,
+        };
+        assert!(options.validate().is_ok());
+        assert!(RetimeOptions { speed: 1.0, ..options.clone() }.validate().is_err());
+        assert!(RetimeOptions { speed: 100.0, ..options.clone() }.validate().is_err());
+        assert_eq!(
+            retime_path(Path::new("/tmp/session.webm"), 8.0, OutputFormat::Mp4),
+            PathBuf::from("/tmp/session.8x.mp4")
+        );
+        assert_eq!(
+            retime_path(Path::new("/tmp/session.mp4"), 0.5, OutputFormat::Mp4),
+            PathBuf::from("/tmp/session.0.5x.mp4")
+        );
+
+        // 30 fps at 4x: every fourth frame is kept, still 1/30s apart
+        let mut retime = Retime::new(4.0, 33);
+        let kept: Vec<i64> = (0..12).filter_map(|frame| retime.take(frame * 33)).collect();
+        assert_eq!(kept, vec![0, 33, 66]);
+        // Slowed down, every frame is kept further apart
+        let mut retime = Retime::new(0.5, 33);
+        let kept: Vec<i64> = (0..3).filter_map(|frame| retime.take(frame * 33)).collect();
+        assert_eq!(kept, vec![0, 66, 132]);
+
+        // A second of steady stereo at 4x lasts a quarter second, at the same level
+        let mut stretch = TimeStretch::new(4.0, 48_000, 2);
+        let mut out = Vec::new();
+        for chunk in vec![0.5f32; 96_000].chunks(2048) {
+            stretch.process(chunk, &mut out);
+        }
+        let frames = out.len() / 2;
+        assert!((11_000..=12_500).contains(&frames), "{} frames", frames);
+        assert!(out[4_000..out.len() - 4_000].iter().all(|s| (s - 0.5).abs() < 1e-3));
+    }
+}
@@ -0,0 +1,366 @@
+//! Virtual Camera Output
+//!
+//! Publishes the composited output - the screen with the webcam
+//! picture-in-picture - as a camera other apps can pick, so a session can go
+//! straight into Zoom or Meet. The encoder hands frames to the camera's
+//! inputs like it does for the streaming sinks, and they are written out as
+//! YUYV, the format video-call apps take most readily.
+//!
+//! On Linux the camera is a v4l2loopback device (`modprobe v4l2loopback
+//! exclusive_caps=1`); the first one found is used unless a device is given.
+//! Other platforms need a signed camera extension or driver, so the camera
+//! is refused there and `get_available_devices` reports it as missing.
+
+use crossbeam_channel::Receiver;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use crate::compositor::{CompositeFrame, FrameFormat};
+use crate::streaming::{StreamFormat, StreamInputs};
+
+/// Virtual camera settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VirtualCameraConfig {
+    /// Loopback device to write to (default: the first one found)
+    #[serde(default)]
+    pub device: Option<PathBuf>,
+}
+
+/// Whether this platform can publish a virtual camera
+pub fn is_virtual_camera_available() -> bool {
+    cfg!(target_os = "linux")
+}
+
+impl VirtualCameraConfig {
+    /// Check the platform has a virtual camera and the device is a device node
+    pub fn validate(&self) -> Result<(), String> {
+        if !is_virtual_camera_available() {
+            return Err("The virtual camera is only available on Linux, through v4l2loopback".to_string());
+        }
+        match self.device {
+            Some(ref device) if !device.starts_with("/dev") => {
+                Err(format!("Virtual camera device must be under /dev (got {})", device.display()))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The composited output published as a camera
+pub struct VirtualCamera {
+    inputs: StreamInputs,
+    running: Arc<Mutex<bool>>,
+    thread: Option<JoinHandle<()>>,
+    error_receiver: Receiver<String>,
+}
+
+impl VirtualCamera {
+    /// Open the loopback device, set its format and start writing frames on
+    /// a new thread
+    #[cfg(target_os = "linux")]
+    pub fn start(config: &VirtualCameraConfig, format: StreamFormat) -> Result<Self, String> {
+        config.validate()?;
+        let device = match config.device {
+            Some(ref device) => device.clone(),
+            None => v4l2::find_loopback_device()?,
+        };
+        let width = format.width & !1;
+        let file = v4l2::open_output(&device, width, format.height)?;
+
+        // Audio has nowhere to go; dropping its receiver turns it away
+        let (inputs, video_receiver, _) = StreamInputs::channels();
+        let (error_sender, error_receiver) = crossbeam_channel::bounded(1);
+        let running = Arc::new(Mutex::new(true));
+        let thread_running = running.clone();
+
+        let thread = std::thread::Builder::new()
+            .name("virtual-camera".to_string())
+            .spawn(move || {
+                println!("Virtual camera on {}: {}x{}", device.display(), width, format.height);
+                let result = write_loop(file, (width, format.height), &thread_running, video_receiver);
+                *thread_running.lock() = false;
+                match result {
+                    Ok(()) => println!("Virtual camera on {} closed", device.display()),
+                    Err(e) => {
+                        eprintln!("Virtual camera on {} failed: {}", device.display(), e);
+                        let _ = error_sender.try_send(e);
+                    }
+                }
+            })
+            .map_err(|e| format!("Failed to start virtual camera: {}", e))?;
+
+        Ok(Self {
+            inputs,
+            running,
+            thread: Some(thread),
+            error_receiver,
+        })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn start(config: &VirtualCameraConfig, _format: StreamFormat) -> Result<Self, String> {
+        config.validate()?;
+        Err("The virtual camera is only available on Linux, through v4l2loopback".to_string())
+    }
+
+    /// Inputs for the encoder to feed
+    pub fn inputs(&self) -> StreamInputs {
+        self.inputs.clone()
+    }
+
+    /// Why the camera closed by itself, if it did
+    pub fn error(&self) -> Option<String> {
+        self.error_receiver.try_recv().ok()
+    }
+
+    /// Stop writing frames; apps watching the camera see it freeze
+    pub fn stop(&self) {
+        *self.running.lock() = false;
+    }
+}
+
+impl Drop for VirtualCamera {
+    fn drop(&mut self) {
+        self.stop();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Write frames until stopped or the inputs close
+#[cfg(target_os = "linux")]
+fn write_loop(
+    mut file: std::fs::File,
+    size: (u32, u32),
+    running: &Mutex<bool>,
+    video_receiver: Receiver<CompositeFrame>,
+) -> Result<(), String> {
+    use crossbeam_channel::RecvTimeoutError;
+    use std::io::Write;
+    use std::time::Duration;
+
+    let mut yuyv = Vec::new();
+    while *running.lock() {
+        let frame = match video_receiver.recv_timeout(Duration::from_millis(100)) {
+            Ok(frame) => frame,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        // The device's format is fixed once opened; frames composited smaller
+        // by adaptive quality are scaled up to it
+        let frame = if (frame.width & !1, frame.height) == size {
+            frame
+        } else {
+            match fit_frame(&frame, size) {
+                Some(frame) => frame,
+                None => continue,
+            }
+        };
+        to_yuyv(&frame, &mut yuyv);
+        file.write_all(&yuyv).map_err(|e| format!("Write failed: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Scale an RGBA or BGRA frame to the device size (NV12 frames are skipped)
+#[cfg(target_os = "linux")]
+fn fit_frame(frame: &CompositeFrame, (width, height): (u32, u32)) -> Option<CompositeFrame> {
+    use crate::frame_pool::FrameBuffer;
+    use image::{imageops, ImageBuffer, Rgba};
+
+    if frame.format == FrameFormat::Nv12 {
+        return None;
+    }
+    // Nearest neighbour keeps this cheap; the byte order passes straight through
+    let source = ImageBuffer::<Rgba<u8>, &[u8]>::from_raw(frame.width, frame.height, &frame.data[..])?;
+    let scaled = imageops::resize(&source, width, height, imageops::FilterType::Nearest);
+    Some(CompositeFrame {
+        data: FrameBuffer::shared(scaled.into_raw()),
+        width,
+        height,
+        timestamp: frame.timestamp,
+        format: frame.format,
+    })
+}
+
+/// Convert a composited frame to video-range BT.709 YUYV (4:2:2), dropping
+/// the last column of odd-width frames
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn to_yuyv(frame: &CompositeFrame, out: &mut Vec<u8>) {
+    let (width, height) = (frame.width as usize, frame.height as usize);
+    out.clear();
+    out.reserve((width & !1) * height * 2);
+
+    for y in 0..height {
+        for x in (0..width & !1).step_by(2) {
+            let (y0, y1, u, v) = match frame.format {
+                FrameFormat::Nv12 => {
+                    let uv = width * height + (y / 2) * width + x;
+                    let row = y * width + x;
+                    (frame.data[row], frame.data[row + 1], frame.data[uv], frame.data[uv + 1])
+                }
+                FrameFormat::Rgba | FrameFormat::Bgra => {
+                    let i = (y * width + x) * 4;
+                    let rgb = |i: usize| {
+                        let p = &frame.data[i..i + 3];
+                        match frame.format {
+                            FrameFormat::Bgra => [p[2] as f32, p[1] as f32, p[0] as f32],
+                            _ => [p[0] as f32, p[1] as f32, p[2] as f32],
+                        }
+                    };
+                    let (left, right) = (rgb(i), rgb(i + 4));
+                    let luma = |[r, g, b]: [f32; 3]| (16.0 + 0.183 * r + 0.614 * g + 0.062 * b).round() as u8;
+                    // Chroma is shared by the pair
+                    let [r, g, b] = [0, 1, 2].map(|c| (left[c] + right[c]) / 2.0);
+                    (
+                        luma(left),
+                        luma(right),
+                        (128.0 - 0.101 * r - 0.339 * g + 0.439 * b).round() as u8,
+                        (128.0 + 0.439 * r - 0.399 * g - 0.040 * b).round() as u8,
+                    )
+                }
+            };
+            out.extend_from_slice(&[y0, u, y1, v]);
+        }
+    }
+}
+
+/// The bits of the V4L2 API needed to set up a loopback output
+#[cfg(target_os = "linux")]
+mod v4l2 {
+    use std::fs::{File, OpenOptions};
+    use std::os::fd::AsRawFd;
+    use std::path::{Path, PathBuf};
+
+    const BUF_TYPE_VIDEO_OUTPUT: u32 = 2;
+    const FIELD_NONE: u32 = 1;
+    const COLORSPACE_REC709: u32 = 3;
+
+    /// `struct v4l2_pix_format`
+    #[repr(C)]
+    #[derive(Default)]
+    struct PixFormat {
+        width: u32,
+        height: u32,
+        pixelformat: u32,
+        field: u32,
+        bytesperline: u32,
+        sizeimage: u32,
+        colorspace: u32,
+        private: u32,
+        flags: u32,
+        ycbcr_enc: u32,
+        quantization: u32,
+        xfer_func: u32,
+    }
+
+    /// `struct v4l2_format` as laid out on 64-bit targets, where the format
+    /// union (200 bytes) is 8-byte aligned
+    #[repr(C)]
+    struct Format {
+        kind: u32,
+        _align: u32,
+        pix: PixFormat,
+        _rest: [u8; 200 - std::mem::size_of::<PixFormat>()],
+    }
+
+    /// `VIDIOC_S_FMT`: `_IOWR('V', 5, struct v4l2_format)`
+    const VIDIOC_S_FMT: u64 =
+        (3 << 30) | ((std::mem::size_of::<Format>() as u64) << 16) | ((b'V' as u64) << 8) | 5;
+
+    /// First v4l2loopback device, the only driver that gives its devices a
+    /// `max_openers` attribute
+    pub fn find_loopback_device() -> Result<PathBuf, String> {
+        let mut names: Vec<String> = std::fs::read_dir("/sys/class/video4linux")
+            .map_err(|_| "No video devices found; is v4l2loopback loaded?".to_string())?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().join("max_openers").exists())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort_by_key(|name| name.trim_start_matches("video").parse::<u32>().unwrap_or(u32::MAX));
+        names
+            .first()
+            .map(|name| Path::new("/dev").join(name))
+            .ok_or_else(|| "No v4l2loopback device found; load it with `modprobe v4l2loopback`".to_string())
+    }
+
+    /// Open `device` for writing YUYV frames of the given size
+    pub fn open_output(device: &Path, width: u32, height: u32) -> Result<File, String> {
+        let file = OpenOptions::new()
+            .write(true)
+            .open(device)
+            .map_err(|e| format!("Failed to open {}: {}", device.display(), e))?;
+
+        let mut format = Format {
+            kind: BUF_TYPE_VIDEO_OUTPUT,
+            _align: 0,
+            pix: PixFormat {
+                width,
+                height,
+                pixelformat: u32::from_le_bytes(*b"YUYV"),
+                field: FIELD_NONE,
+                bytesperline: width * 2,
+                sizeimage: width * height * 2,
+                colorspace: COLORSPACE_REC709,
+                ..PixFormat::default()
+            },
+            _rest: [0; 200 - std::mem::size_of::<PixFormat>()],
+        };
+        // SAFETY: `format` is a valid v4l2_format that outlives the call
+        let result = unsafe { libc::ioctl(file.as_raw_fd(), VIDIOC_S_FMT as libc::Ioctl, &mut format) };
+        if result < 0 {
+            return Err(format!(
+                "{} won't take {}x{} YUYV frames: {}",
+                device.display(),
+                width,
+                height,
+                std::io::Error::last_os_error()
+            ));
+        }
+        Ok(file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame_pool::FrameBuffer;
+    use std::time::Duration;
+
+    #[test]
+    fn test_frames_convert_to_yuyv() {
+        // One white and one black pixel, as BGRA
+        let frame = CompositeFrame {
+            data: FrameBuffer::shared(vec![255, 255, 255, 255, 0, 0, 0, 255]),
+            width: 2,
+            height: 1,
+            timestamp: Duration::ZERO,
+            format: FrameFormat::Bgra,
+        };
+        let mut yuyv = Vec::new();
+        to_yuyv(&frame, &mut yuyv);
+        assert_eq!(yuyv, vec![235, 128, 16, 128]);
+
+        // NV12 samples carry straight over
+        let frame = CompositeFrame {
+            data: FrameBuffer::shared(vec![50, 60, 70, 80, 100, 200]),
+            width: 2,
+            height: 2,
+            timestamp: Duration::ZERO,
+            format: FrameFormat::Nv12,
+        };
+        to_yuyv(&frame, &mut yuyv);
+        assert_eq!(yuyv, vec![50, 100, 60, 200, 70, 100, 80, 200]);
+
+        assert!(VirtualCameraConfig {
+            device: Some(PathBuf::from("/tmp/video0")),
+        }
+        .validate()
+        .is_err());
+    }
+}
@@ -0,0 +1,374 @@
+//! Audio Visualization
+//!
+//! Streams the mix to the UI as `audio-visualization` events, each carrying a
+//! downsampled waveform of the audio since the previous event and a magnitude
+//! spectrum in log-spaced bands, so creators can see triggers and background
+//! noise while recording. The mixer hands chunks to a tap; the analysis runs
+//! on its own thread and chunks are dropped rather than queued when it falls
+//! behind.
+
+use crossbeam_channel::{bounded, Receiver, RecvTimeoutError, Sender};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+use crate::audio::AudioChunk;
+use crate::metering::SILENCE_DB;
+
+/// Event carrying an `AudioVisualization`
+pub const VISUALIZATION_EVENT: &str = "audio-visualization";
+
+/// Lowest frequency shown in the spectrum
+const MIN_FREQUENCY_HZ: f32 = 20.0;
+
+/// Audio visualization settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VisualizerConfig {
+    /// Time between events (16-1000ms, default 50)
+    #[serde(default = "default_interval_ms")]
+    pub interval_ms: u32,
+    /// FFT length in samples, a power of two (256-8192, default 2048)
+    #[serde(default = "default_fft_size")]
+    pub fft_size: usize,
+    /// Spectrum bands, log-spaced from 20Hz to half the sample rate (8-256, default 64)
+    #[serde(default = "default_bands")]
+    pub bands: usize,
+    /// Waveform columns per event (16-2048, default 256)
+    #[serde(default = "default_waveform_points")]
+    pub waveform_points: usize,
+}
+
+fn default_interval_ms() -> u32 {
+    50
+}
+
+fn default_fft_size() -> usize {
+    2048
+}
+
+fn default_bands() -> usize {
+    64
+}
+
+fn default_waveform_points() -> usize {
+    256
+}
+
+impl Default for VisualizerConfig {
+    fn default() -> Self {
+        Self {
+            interval_ms: default_interval_ms(),
+            fft_size: default_fft_size(),
+            bands: default_bands(),
+            waveform_points: default_waveform_points(),
+        }
+    }
+}
+
+impl VisualizerConfig {
+    /// Check the settings are in range
+    pub fn validate(&self) -> Result<(), String> {
+        if !(16..=1000).contains(&self.interval_ms) {
+            return Err(format!("Visualizer interval must be 16-1000ms (got {})", self.interval_ms));
+        }
+        if !(256..=8192).contains(&self.fft_size) || !self.fft_size.is_power_of_two() {
+            return Err(format!("FFT size must be a power of two from 256 to 8192 (got {})", self.fft_size));
+        }
+        if !(8..=256).contains(&self.bands) {
+            return Err(format!("Spectrum bands must be 8-256 (got {})", self.bands));
+        }
+        if !(16..=2048).contains(&self.waveform_points) {
+            return Err(format!("Waveform points must be 16-2048 (got {})", self.waveform_points));
+        }
+        Ok(())
+    }
+}
+
+/// Payload of `audio-visualization`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioVisualization {
+    /// Capture time of the newest sample, since the recording started
+    pub timestamp_ms: u64,
+    /// Minimum and maximum of each column, interleaved (mono, -1.0 to 1.0)
+    pub waveform: Vec<f32>,
+    /// Peak level of each band in dBFS, lowest band first
+    pub spectrum: Vec<f32>,
+    /// Center frequency of each band in Hz
+    pub band_frequencies: Vec<f32>,
+}
+
+/// Receives the events
+type VisualizationSink = Box<dyn Fn(AudioVisualization) + Send>;
+
+/// Analyzes the mix on its own thread
+pub struct AudioVisualizer {
+    running: Arc<Mutex<bool>>,
+    sender: Sender<AudioChunk>,
+}
+
+impl AudioVisualizer {
+    /// Start emitting events to every window
+    ///
+    /// `sample_rate` and `channels` describe the chunks the mixer will send.
+    pub fn start(config: VisualizerConfig, sample_rate: u32, channels: u16, app: AppHandle) -> Result<Self, String> {
+        config.validate()?;
+
+        let (sender, receiver) = bounded(30);
+        let running = Arc::new(Mutex::new(true));
+        let analyzer = Analyzer::new(&config, sample_rate, channels);
+        let sink: VisualizationSink = Box::new(move |payload| {
+            if let Err(e) = app.emit(VISUALIZATION_EVENT, payload) {
+                eprintln!("Failed to emit {}: {}", VISUALIZATION_EVENT, e);
+            }
+        });
+
+        let running_clone = running.clone();
+        let interval = Duration::from_millis(config.interval_ms as u64);
+        std::thread::spawn(move || {
+            visualizer_loop(running_clone, receiver, analyzer, interval, sink);
+        });
+
+        println!(
+            "Audio visualizer started ({}ms, {}-point FFT, {} bands)",
+            config.interval_ms, config.fft_size, config.bands
+        );
+        Ok(Self { running, sender })
+    }
+
+    /// Sender for the mixer's visualizer tap
+    pub fn sender(&self) -> Sender<AudioChunk> {
+        self.sender.clone()
+    }
+
+    pub fn stop(&self) {
+        *self.running.lock() = false;
+        println!("Audio visualizer stopped");
+    }
+}
+
+impl Drop for AudioVisualizer {
+    fn drop(&mut self) {
+        *self.running.lock() = false;
+    }
+}
+
+fn visualizer_loop(
+    running: Arc<Mutex<bool>>,
+    receiver: Receiver<AudioChunk>,
+    mut analyzer: Analyzer,
+    interval: Duration,
+    sink: VisualizationSink,
+) {
+    let mut last_emit = Instant::now();
+    let mut latest = Duration::ZERO;
+
+    while *running.lock() {
+        match receiver.recv_timeout(interval) {
+            Ok(chunk) => {
+                analyzer.push(&chunk.samples);
+                latest = chunk.timestamp;
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        if last_emit.elapsed() >= interval && analyzer.has_new_samples() {
+            sink(analyzer.take(latest));
+            last_emit = Instant::now();
+        }
+    }
+}
+
+/// Turns interleaved samples into waveform columns and spectrum bands
+struct Analyzer {
+    channels: usize,
+    waveform_points: usize,
+    /// The newest `fft_size` mono samples
+    history: VecDeque<f32>,
+    /// Mono samples since the last event
+    recent: Vec<f32>,
+    window: Vec<f32>,
+    /// Scales FFT magnitudes so a full-scale sine reads 0 dBFS
+    magnitude_scale: f32,
+    /// First and one-past-last FFT bin of each band
+    band_bins: Vec<(usize, usize)>,
+    band_frequencies: Vec<f32>,
+}
+
+impl Analyzer {
+    fn new(config: &VisualizerConfig, sample_rate: u32, channels: u16) -> Self {
+        let size = config.fft_size;
+        // Hann window
+        let window: Vec<f32> = (0..size)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / size as f32).cos())
+            .collect();
+        let magnitude_scale = 2.0 / window.iter().sum::<f32>();
+
+        let nyquist = sample_rate as f32 / 2.0;
+        let bin_hz = sample_rate as f32 / size as f32;
+        let max_bin = size / 2;
+        let edge = |band: usize| MIN_FREQUENCY_HZ * (nyquist / MIN_FREQUENCY_HZ).powf(band as f32 / config.bands as f32);
+        let mut band_bins = Vec::with_capacity(config.bands);
+        let mut band_frequencies = Vec::with_capacity(config.bands);
+        for band in 0..config.bands {
+            let (low, high) = (edge(band), edge(band + 1));
+            // Narrow low bands share the bin they fall in
+            let first = ((low / bin_hz).round() as usize).min(max_bin - 1);
+            let last = ((high / bin_hz).round() as usize).clamp(first + 1, max_bin);
+            band_bins.push((first, last));
+            band_frequencies.push((low * high).sqrt());
+        }
+
+        Self {
+            channels: channels.max(1) as usize,
+            waveform_points: config.waveform_points,
+            history: VecDeque::from(vec![0.0; size]),
+            recent: Vec::new(),
+            window,
+            magnitude_scale,
+            band_bins,
+            band_frequencies,
+        }
+    }
+
+    /// Add interleaved samples, downmixed to mono
+    fn push(&mut self, samples: &[f32]) {
+        for frame in samples.chunks_exact(self.channels) {
+            let mono = frame.iter().sum::<f32>() / self.channels as f32;
+            self.history.pop_front();
+            self.history.push_back(mono);
+            self.recent.push(mono);
+        }
+    }
+
+    fn has_new_samples(&self) -> bool {
+        !self.recent.is_empty()
+    }
+
+    /// The event for everything pushed since the last one
+    fn take(&mut self, timestamp: Duration) -> AudioVisualization {
+        let waveform = self.waveform();
+        self.recent.clear();
+        AudioVisualization {
+            timestamp_ms: timestamp.as_millis() as u64,
+            waveform,
+            spectrum: self.spectrum(),
+            band_frequencies: self.band_frequencies.clone(),
+        }
+    }
+
+    /// Min and max of each column of the recent samples
+    fn waveform(&self) -> Vec<f32> {
+        let points = self.waveform_points.min(self.recent.len());
+        let mut waveform = Vec::with_capacity(points * 2);
+        for point in 0..points {
+            let start = point * self.recent.len() / points;
+            let end = (point + 1) * self.recent.len() / points;
+            let column = &self.recent[start..end];
+            waveform.push(column.iter().copied().fold(f32::MAX, f32::min));
+            waveform.push(column.iter().copied().fold(f32::MIN, f32::max));
+        }
+        waveform
+    }
+
+    /// Peak dBFS of each band over the newest `fft_size` samples
+    fn spectrum(&self) -> Vec<f32> {
+        let mut bins: Vec<(f32, f32)> = self
+            .history
+            .iter()
+            .zip(&self.window)
+            .map(|(sample, weight)| (sample * weight, 0.0))
+            .collect();
+        fft(&mut bins);
+
+        self.band_bins
+            .iter()
+            .map(|&(first, last)| {
+                let peak = bins[first..last]
+                    .iter()
+                    .map(|(re, im)| re.hypot(*im) * self.magnitude_scale)
+                    .fold(0.0, f32::max);
+                if peak > 0.0 {
+                    (20.0 * peak.log10()).max(SILENCE_DB)
+                } else {
+                    SILENCE_DB
+                }
+            })
+            .collect()
+    }
+}
+
+/// In-place radix-2 FFT of (re, im) pairs; the length must be a power of two
+pub(crate) fn fft(data: &mut [(f32, f32)]) {
+    let n = data.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let (re, im) = data[start + k + len / 2];
+                let twiddled = (re * cos - im * sin, re * sin + im * cos);
+                let even = data[start + k];
+                data[start + k] = (even.0 + twiddled.0, even.1 + twiddled.1);
+                data[start + k + len / 2] = (even.0 - twiddled.0, even.1 - twiddled.1);
+            }
+        }
+        len <<= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sine_peaks_in_its_band() {
+        let config = VisualizerConfig::default();
+        let mut analyzer = Analyzer::new(&config, 48000, 2);
+
+        // 0.1s of a full-scale 1kHz sine, same on both channels
+        let samples: Vec<f32> = (0..4800)
+            .flat_map(|i| {
+                let s = (2.0 * PI * 1000.0 * i as f32 / 48000.0).sin();
+                [s, s]
+            })
+            .collect();
+        analyzer.push(&samples);
+        let event = analyzer.take(Duration::from_millis(100));
+
+        assert_eq!(event.waveform.len(), 2 * config.waveform_points);
+        assert!(event.waveform.iter().all(|s| s.abs() <= 1.0));
+        assert!(event.waveform[1] > 0.9);
+
+        // Loudest band is around 1kHz, at about 0 dBFS
+        let (band, level) = event.spectrum.iter().copied().enumerate()
+            .fold((0, SILENCE_DB), |best, (i, level)| if level > best.1 { (i, level) } else { best });
+        assert!(level > -3.0, "{}", level);
+        assert!((800.0..1250.0).contains(&event.band_frequencies[band]));
+        assert!(event.spectrum[0] < -40.0);
+        assert!(event.spectrum[config.bands - 1] < -40.0);
+        assert!(!analyzer.has_new_samples());
+
+        assert!(VisualizerConfig { fft_size: 1000, ..VisualizerConfig::default() }.validate().is_err());
+    }
+}
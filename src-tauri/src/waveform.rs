@@ -0,0 +1,246 @@
+//! Waveforms
+//!
+//! Reduces a finished recording's audio track to peaks - the lowest and
+//! highest sample in each slice of time, across all channels - for the
+//! frontend to draw as a scrub bar, or renders them as a PNG next to the
+//! recording for use as a thumbnail. The track is read once at a fixed
+//! resolution of 100 peaks a second, then merged down to the requested
+//! number. Reading the audio needs the `ffmpeg` feature.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::command;
+
+use crate::output_paths::{allowed_output_dirs, check_output_path};
+use crate::recording::RecordingError;
+
+/// Peaks per second of audio collected while reading the track
+#[cfg_attr(not(feature = "ffmpeg"), allow(dead_code))]
+const PEAKS_PER_SECOND: u32 = 100;
+
+/// Most peaks or pixels a waveform can be asked for
+pub const MAX_WAVEFORM_WIDTH: u32 = 8192;
+
+/// Color waveforms are drawn in (RGBA); the background is transparent
+const WAVEFORM_COLOR: [u8; 4] = [0x8b, 0x9c, 0xf7, 0xff];
+
+/// Peaks of a recording's audio, as returned to the frontend
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WaveformPeaks {
+    pub duration_ms: u64,
+    /// `[min, max]` sample (-1.0 to 1.0) of each equal slice of the recording
+    pub peaks: Vec<[f32; 2]>,
+}
+
+/// Check a requested width or peak count
+fn validate_width(width: u32) -> Result<(), String> {
+    if !(1..=MAX_WAVEFORM_WIDTH).contains(&width) {
+        return Err(format!("Waveform width must be 1-{} (got {})", MAX_WAVEFORM_WIDTH, width));
+    }
+    Ok(())
+}
+
+/// Path of the waveform image for a recording
+pub fn waveform_path(output_path: &Path) -> PathBuf {
+    output_path.with_extension("waveform.png")
+}
+
+/// Collects the min and max of every block of interleaved samples
+#[cfg_attr(not(feature = "ffmpeg"), allow(dead_code))]
+struct PeakCollector {
+    /// Samples (across all channels) per block
+    block_len: usize,
+    in_block: usize,
+    current: [f32; 2],
+    peaks: Vec<[f32; 2]>,
+    frames: u64,
+    channels: usize,
+}
+
+#[cfg_attr(not(feature = "ffmpeg"), allow(dead_code))]
+impl PeakCollector {
+    fn new(sample_rate: u32, channels: u16) -> Self {
+        let channels = channels.max(1) as usize;
+        Self {
+            block_len: (sample_rate / PEAKS_PER_SECOND).max(1) as usize * channels,
+            in_block: 0,
+            current: [0.0, 0.0],
+            peaks: Vec::new(),
+            frames: 0,
+            channels,
+        }
+    }
+
+    fn process(&mut self, samples: &[f32]) {
+        self.frames += (samples.len() / self.channels) as u64;
+        for &sample in samples {
+            self.current = [self.current[0].min(sample), self.current[1].max(sample)];
+            self.in_block += 1;
+            if self.in_block == self.block_len {
+                self.peaks.push(self.current);
+                self.current = [0.0, 0.0];
+                self.in_block = 0;
+            }
+        }
+    }
+
+    /// Peaks of the whole track, the last partial block included, and its
+    /// length in frames
+    fn finish(mut self) -> (Vec<[f32; 2]>, u64) {
+        if self.in_block > 0 {
+            self.peaks.push(self.current);
+        }
+        (self.peaks, self.frames)
+    }
+}
+
+/// Resample `peaks` to `count` equal slices: merged when there are more,
+/// stretched (each repeated) when there are fewer
+fn merge_peaks(peaks: &[[f32; 2]], count: usize) -> Vec<[f32; 2]> {
+    if peaks.is_empty() {
+        return Vec::new();
+    }
+    (0..count)
+        .map(|i| {
+            let start = i * peaks.len() / count;
+            let end = ((i + 1) * peaks.len() / count).max(start + 1);
+            peaks[start..end]
+                .iter()
+                .fold([0.0f32, 0.0f32], |[min, max], [lo, hi]| [min.min(*lo), max.max(*hi)])
+        })
+        .collect()
+}
+
+/// Draw peaks as a mirrored waveform across the full width
+fn draw_peaks(peaks: &[[f32; 2]], width: u32, height: u32) -> image::RgbaImage {
+    let mut image = image::RgbaImage::new(width, height);
+    let middle = (height as f32 - 1.0) / 2.0;
+    for (x, [min, max]) in merge_peaks(peaks, width as usize).iter().enumerate() {
+        let top = (middle - max.clamp(-1.0, 1.0) * middle).round() as u32;
+        let bottom = (middle - min.clamp(-1.0, 1.0) * middle).round() as u32;
+        // Silence still shows as a line through the middle
+        for y in top.min(bottom)..=bottom.max(top).min(height - 1) {
+            image.put_pixel(x as u32, y, image::Rgba(WAVEFORM_COLOR));
+        }
+    }
+    image
+}
+
+/// Read the audio track of the recording at `path` and reduce it to `count`
+/// peaks
+#[cfg(feature = "ffmpeg")]
+pub fn waveform_peaks(path: &Path, count: u32) -> Result<WaveformPeaks, String> {
+    validate_width(count)?;
+    ffmpeg_next::init().map_err(|e| format!("FFmpeg init failed: {}", e))?;
+
+    let reader = crate::media_file::AudioTrackReader::open(path)?;
+    let rate = reader.rate().max(1);
+    let mut collector = PeakCollector::new(rate, reader.channels());
+    reader.read_all(|samples| collector.process(samples))?;
+    let (peaks, frames) = collector.finish();
+
+    Ok(WaveformPeaks {
+        duration_ms: frames * 1000 / rate as u64,
+        peaks: merge_peaks(&peaks, count as usize),
+    })
+}
+
+#[cfg(not(feature = "ffmpeg"))]
+pub fn waveform_peaks(_path: &Path, count: u32) -> Result<WaveformPeaks, String> {
+    validate_width(count)?;
+    Err("Waveforms require FFmpeg support".to_string())
+}
+
+/// Render the waveform of the recording at `path` as a PNG next to it,
+/// returning the image's path
+pub fn write_waveform(path: &Path, width: u32, height: u32) -> Result<PathBuf, String> {
+    if !(16..=2048).contains(&height) {
+        return Err(format!("Waveform height must be 16-2048 (got {})", height));
+    }
+    let waveform = waveform_peaks(path, width)?;
+    let image = draw_peaks(&waveform.peaks, width, height);
+    let output = waveform_path(path);
+    image
+        .save_with_format(&output, image::ImageFormat::Png)
+        .map_err(|e| format!("Failed to write {}: {}", output.display(), e))?;
+    Ok(output)
+}
+
+/// Check a recording passed from the frontend exists and may be written next to
+fn checked_recording(path: String) -> Result<PathBuf, RecordingError> {
+    let path = PathBuf::from(path);
+    if !path.is_file() {
+        return Err(format!("Recording not found: {}", path.display()).into());
+    }
+    check_output_path(&path, &allowed_output_dirs())
+}
+
+/// Tauri command: Peaks of a recording's audio, for drawing a scrub bar
+///
+/// `count` is the number of slices the recording is divided into (default
+/// 1000).
+#[command]
+pub async fn get_waveform_peaks(path: String, count: Option<u32>) -> Result<WaveformPeaks, RecordingError> {
+    let path = checked_recording(path)?;
+    let count = count.unwrap_or(1000);
+    validate_width(count).map_err(RecordingError::InvalidConfig)?;
+    tauri::async_runtime::spawn_blocking(move || waveform_peaks(&path, count))
+        .await
+        .map_err(|e| format!("Waveform failed: {}", e))?
+        .map_err(RecordingError::Failed)
+}
+
+/// Tauri command: Render a recording's waveform as `<recording>.waveform.png`
+/// (default 1200x200), returning the image's path
+#[command]
+pub async fn render_waveform(
+    path: String,
+    width: Option<u32>,
+    height: Option<u32>,
+) -> Result<String, RecordingError> {
+    let path = checked_recording(path)?;
+    let (width, height) = (width.unwrap_or(1200), height.unwrap_or(200));
+    validate_width(width).map_err(RecordingError::InvalidConfig)?;
+    tauri::async_runtime::spawn_blocking(move || write_waveform(&path, width, height))
+        .await
+        .map_err(|e| format!("Waveform failed: {}", e))?
+        .map(|output| output.to_string_lossy().to_string())
+        .map_err(RecordingError::Failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peaks_are_collected_merged_and_drawn() {
+        // Two blocks of 10ms at 1kHz stereo
+        let mut collector = PeakCollector::new(1000, 2);
+        let mut samples = vec![0.0; 20];
+        samples[3] = 0.5;
+        samples.extend(vec![-0.25; 20]);
+        collector.process(&samples);
+        collector.process(&[0.1, 0.1]);
+        let (peaks, frames) = collector.finish();
+        assert_eq!(peaks, vec![[0.0, 0.5], [-0.25, 0.0], [0.0, 0.1]]);
+        assert_eq!(frames, 21);
+
+        assert_eq!(merge_peaks(&peaks, 1), vec![[-0.25, 0.5]]);
+        let stretched = merge_peaks(&peaks, 6);
+        assert_eq!(stretched.len(), 6);
+        assert_eq!((stretched[0], stretched[1], stretched[5]), (peaks[0], peaks[0], peaks[2]));
+
+        // Two peaks fill four columns
+        let image = draw_peaks(&[[-1.0, 1.0], [0.0, 0.0]], 4, 9);
+        for x in 0..2 {
+            assert_eq!(image.get_pixel(x, 0).0, WAVEFORM_COLOR);
+            assert_eq!(image.get_pixel(x, 8).0, WAVEFORM_COLOR);
+        }
+        for x in 2..4 {
+            assert_eq!(image.get_pixel(x, 4).0, WAVEFORM_COLOR);
+            assert_eq!(image.get_pixel(x, 0).0, [0, 0, 0, 0]);
+        }
+        assert_eq!(waveform_path(Path::new("/tmp/a.mp4")), PathBuf::from("/tmp/a.waveform.png"));
+    }
+}
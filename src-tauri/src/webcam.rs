@@ -2,6 +2,32 @@ use std::sync::Arc;
 use std::time::{Duration, Instant};
 use crossbeam_channel::{bounded, Receiver, Sender};
 use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::thread_priority::{self, ThreadRole};
+
+/// Consecutive failed reads after which the camera counts as disconnected
+const MAX_FRAME_ERRORS: u32 = 30;
+
+/// Time without a frame after which the camera counts as disconnected
+const FRAME_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Wait between attempts to reopen a disconnected camera
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Interval of placeholder frames while the camera is away
+const PLACEHOLDER_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Whether the camera is delivering frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebcamState {
+    /// Frames are coming in
+    Connected,
+    /// The camera failed or was unplugged: a placeholder card is sent in
+    /// its place while it is reopened
+    Reconnecting,
+}
 
 /// Represents a captured webcam frame
 #[derive(Clone)]
@@ -35,6 +61,7 @@ impl WebcamFrame {
 }
 
 /// Webcam capture configuration
+#[derive(Clone)]
 pub struct WebcamCaptureConfig {
     /// Target frames per second
     pub fps: u32,
@@ -67,6 +94,7 @@ pub struct WebcamCapture {
     actual_width: u32,
     actual_height: u32,
     running: Arc<Mutex<bool>>,
+    state: Arc<Mutex<WebcamState>>,
     frame_sender: Option<Sender<WebcamFrame>>,
     frame_receiver: Option<Receiver<WebcamFrame>>,
 }
@@ -90,6 +118,7 @@ impl WebcamCapture {
             actual_width,
             actual_height,
             running: Arc::new(Mutex::new(false)),
+            state: Arc::new(Mutex::new(WebcamState::Connected)),
             frame_sender: Some(sender),
             frame_receiver: Some(receiver),
         })
@@ -100,6 +129,11 @@ impl WebcamCapture {
         self.frame_receiver.take()
     }
     
+    /// Whether the camera is delivering frames or being reconnected
+    pub fn state(&self) -> WebcamState {
+        *self.state.lock()
+    }
+    
     /// Start capturing frames in a background thread
     pub fn start(&self) -> Result<(), String> {
         let mut running = self.running.lock();
@@ -110,6 +144,7 @@ impl WebcamCapture {
         drop(running);
         
         let running_clone = self.running.clone();
+        let state = self.state.clone();
         let sender = self.frame_sender.clone()
             .ok_or("Frame sender not available")?;
         let config = WebcamCaptureConfig {
@@ -120,9 +155,8 @@ impl WebcamCapture {
         };
         
         std::thread::spawn(move || {
-            if let Err(e) = capture_loop(running_clone, sender, config) {
-                eprintln!("Webcam capture error: {}", e);
-            }
+            thread_priority::elevate(ThreadRole::Capture);
+            capture_loop(running_clone, sender, config, state);
         });
         
         Ok(())
@@ -138,13 +172,87 @@ impl WebcamCapture {
 
 /// The main webcam capture loop
 /// 
-/// This function runs in a background thread and captures frames from the webcam.
-/// Due to nokhwa's complexity with different backends, we use a simplified approach
-/// that works across platforms.
+/// Reopens the camera whenever it fails or disappears (e.g. is unplugged),
+/// sending a placeholder card instead of its frames until it is back, so
+/// the picture-in-picture doesn't freeze on the last frame. The camera is
+/// read on its own thread and watched from here, so a read that hangs
+/// instead of failing is noticed too.
 fn capture_loop(
     running: Arc<Mutex<bool>>,
     sender: Sender<WebcamFrame>,
     config: WebcamCaptureConfig,
+    state: Arc<Mutex<WebcamState>>,
+) {
+    let start_time = Instant::now();
+    let placeholder = placeholder_card(config.width, config.height);
+    
+    while *running.lock() {
+        // Set once the stream is open, so a slow open isn't taken for a hang
+        let watch = Arc::new(Mutex::new(None));
+        let abandoned = Arc::new(Mutex::new(false));
+        let mut reader = Some({
+            let (running, sender, config, state) = (running.clone(), sender.clone(), config.clone(), state.clone());
+            let (watch, abandoned) = (watch.clone(), abandoned.clone());
+            std::thread::spawn(move || {
+                thread_priority::elevate(ThreadRole::Capture);
+                run_camera(&running, &abandoned, &sender, &config, &state, &watch, start_time)
+            })
+        });
+        
+        // Watchdog: the reader's own checks only run when a read returns
+        let result = loop {
+            if let Some(finished) = reader.take_if(|reader| reader.is_finished()) {
+                break finished.join().unwrap_or_else(|_| Err("Webcam reader panicked".to_string()));
+            }
+            if !*running.lock() {
+                break Ok(());
+            }
+            if watch.lock().as_ref().is_some_and(|watch: &FrameWatch| watch.timed_out(Instant::now())) {
+                *abandoned.lock() = true;
+                break Err("Camera stopped delivering frames".to_string());
+            }
+            std::thread::sleep(PLACEHOLDER_INTERVAL);
+        };
+        match result {
+            Ok(()) => break,
+            Err(e) => {
+                eprintln!("Webcam capture error: {}; reconnecting", e);
+                *state.lock() = WebcamState::Reconnecting;
+            }
+        }
+        
+        // A hung reader still holds the camera, so it can't be reopened
+        // until its read returns
+        let retry_at = Instant::now() + RECONNECT_INTERVAL;
+        while *running.lock()
+            && (Instant::now() < retry_at || reader.as_ref().is_some_and(|reader| !reader.is_finished()))
+        {
+            let _ = sender.try_send(WebcamFrame {
+                data: placeholder.clone(),
+                width: config.width,
+                height: config.height,
+                timestamp: start_time.elapsed(),
+            });
+            std::thread::sleep(PLACEHOLDER_INTERVAL);
+        }
+    }
+    
+    println!("Webcam capture stopped");
+}
+
+/// Open the camera and capture until stopped (Ok), the camera is lost, or
+/// the watchdog gives up on it (`abandoned`)
+/// 
+/// Due to nokhwa's complexity with different backends, we use a simplified approach
+/// that works across platforms.
+fn run_camera(
+    running: &Mutex<bool>,
+    abandoned: &Mutex<bool>,
+    sender: &Sender<WebcamFrame>,
+    config: &WebcamCaptureConfig,
+    state: &Mutex<WebcamState>,
+    watch: &Mutex<Option<FrameWatch>>,
+    start_time: Instant,
 ) -> Result<(), String> {
     use nokhwa::pixel_format::RgbFormat;
     use nokhwa::utils::{CameraIndex, RequestedFormat, RequestedFormatType};
@@ -170,16 +278,28 @@ fn capture_loop(
         .map_err(|e| format!("Failed to open camera stream: {}", e))?;
     
     let frame_duration = Duration::from_secs_f64(1.0 / config.fps as f64);
-    let start_time = Instant::now();
+    *watch.lock() = Some(FrameWatch::new(Instant::now()));
     
-    println!("Webcam capture started: {}x{} @ {}fps", width, height, config.fps);
+    if std::mem::replace(&mut *state.lock(), WebcamState::Connected) == WebcamState::Reconnecting {
+        println!("Webcam reconnected: {}x{} @ {}fps", width, height, config.fps);
+    } else {
+        println!("Webcam capture started: {}x{} @ {}fps", width, height, config.fps);
+    }
     
     while *running.lock() {
         let frame_start = Instant::now();
         
         // Capture a frame
-        match camera.frame() {
+        let frame = camera.frame();
+        if *abandoned.lock() {
+            let _ = camera.stop_stream();
+            return Err("Camera stopped delivering frames".to_string());
+        }
+        match frame {
             Ok(frame) => {
+                if let Some(ref mut watch) = *watch.lock() {
+                    watch.frame(Instant::now());
+                }
                 let timestamp = start_time.elapsed();
                 let buffer = frame.buffer();
                 
@@ -195,6 +315,10 @@ fn capture_loop(
             }
             Err(e) => {
                 eprintln!("Webcam frame error: {}", e);
+                if watch.lock().as_mut().is_some_and(|watch| watch.error(Instant::now())) {
+                    let _ = camera.stop_stream();
+                    return Err("Camera stopped delivering frames".to_string());
+                }
                 std::thread::sleep(Duration::from_millis(10));
                 continue;
             }
@@ -209,11 +333,77 @@ fn capture_loop(
     
     // Close the stream
     let _ = camera.stop_stream();
-    
-    println!("Webcam capture stopped");
     Ok(())
 }
 
+/// Tells a camera that has gone away from one that failed a few reads
+struct FrameWatch {
+    errors: u32,
+    last_frame: Instant,
+}
+
+impl FrameWatch {
+    fn new(now: Instant) -> Self {
+        Self {
+            errors: 0,
+            last_frame: now,
+        }
+    }
+    
+    /// A frame arrived
+    fn frame(&mut self, now: Instant) {
+        self.errors = 0;
+        self.last_frame = now;
+    }
+    
+    /// A read failed; true once the camera counts as disconnected
+    fn error(&mut self, now: Instant) -> bool {
+        self.errors += 1;
+        self.errors >= MAX_FRAME_ERRORS || self.timed_out(now)
+    }
+    
+    /// No frame for too long, whether reads fail or never return
+    fn timed_out(&self, now: Instant) -> bool {
+        now.duration_since(self.last_frame) >= FRAME_TIMEOUT
+    }
+}
+
+/// RGB card shown in the camera's place: a crossed-out camera on dark grey
+fn placeholder_card(width: u32, height: u32) -> Vec<u8> {
+    let (w, h) = (width as f32, height as f32);
+    let (cx, cy) = (w / 2.0, h / 2.0);
+    // Camera body, with the lens housing to its right
+    let body_w = w * 0.22;
+    let body_h = h * 0.22;
+    let body_x = cx - body_w * 0.65;
+    let stroke = (h * 0.02).max(2.0);
+    
+    let mut data = Vec::with_capacity(width as usize * height as usize * 3);
+    for y in 0..height {
+        for x in 0..width {
+            let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+            let in_body = px >= body_x && px <= body_x + body_w && (py - cy).abs() <= body_h / 2.0;
+            // Triangle widening to the right of the body
+            let lens_x = px - (body_x + body_w);
+            let in_lens = lens_x >= 0.0 && lens_x <= body_w * 0.3
+                && (py - cy).abs() <= body_h * 0.2 + lens_x;
+            // Diagonal slash through the icon
+            let slash_t = ((px - cx) + (py - cy)) / 2.0;
+            let on_slash = ((px - cx) - (py - cy)).abs() <= stroke * 1.4
+                && slash_t.abs() <= body_w * 0.75;
+            let pixel = if on_slash {
+                [200, 72, 72]
+            } else if in_body || in_lens {
+                [128, 132, 140]
+            } else {
+                [36, 38, 44]
+            };
+            data.extend_from_slice(&pixel);
+        }
+    }
+    data
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -230,4 +420,33 @@ mod tests {
         let rgba = frame.to_rgba();
         assert_eq!(rgba, vec![255, 128, 64, 255]); // RGBA with full alpha
     }
+    
+    #[test]
+    fn test_frame_watch_detects_lost_camera() {
+        let start = Instant::now();
+        let mut watch = FrameWatch::new(start);
+        // A few failed reads are tolerated
+        assert!(!watch.error(start));
+        watch.frame(start);
+        for _ in 1..MAX_FRAME_ERRORS {
+            assert!(!watch.error(start));
+        }
+        assert!(watch.error(start));
+        
+        // As is a long silence, however few errors
+        let mut watch = FrameWatch::new(start);
+        assert!(watch.error(start + FRAME_TIMEOUT));
+        
+        // Which the watchdog notices without any read returning
+        let watch = FrameWatch::new(start);
+        assert!(!watch.timed_out(start + FRAME_TIMEOUT / 2));
+        assert!(watch.timed_out(start + FRAME_TIMEOUT));
+        
+        let card = placeholder_card(64, 48);
+        assert_eq!(card.len(), 64 * 48 * 3);
+        // Background in the corner, slash through the middle
+        assert_eq!(card[..3], [36, 38, 44]);
+        let center = (24 * 64 + 32) * 3;
+        assert_eq!(card[center..center + 3], [200, 72, 72]);
+    }
 }
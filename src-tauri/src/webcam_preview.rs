@@ -0,0 +1,117 @@
+//! Webcam Preview
+//!
+//! Runs the webcam without recording so users can frame their shot, and
+//! streams downscaled frames to the frontend over a Tauri channel. Frames go
+//! through a monitor feed of their own, so the rate cap, scaling and message
+//! format (width and height as little-endian u32, then the JPEG) are the same
+//! as the confidence monitor's.
+//!
+//! Only one program can hold the camera: a recording that captures the
+//! webcam ends the preview, and the preview can't start during one.
+
+use parking_lot::Mutex;
+use std::sync::Arc;
+use tauri::ipc::{Channel, InvokeResponseBody};
+use tauri::command;
+
+use crate::compositor::{CompositeFrame, FrameFormat};
+use crate::devices::resolve_webcam;
+use crate::frame_pool::FrameBuffer;
+use crate::monitor_feed::{FeedClient, FeedSink, MonitorFeed, MonitorFeedConfig};
+use crate::recording::{RecordingError, RecordingState};
+use crate::webcam::{WebcamCapture, WebcamCaptureConfig, WebcamFrame};
+
+struct PreviewSession {
+    capture: WebcamCapture,
+    feed: MonitorFeed,
+}
+
+/// Shared handle to the webcam preview
+#[derive(Clone, Default)]
+pub struct WebcamPreview {
+    session: Arc<Mutex<Option<PreviewSession>>>,
+}
+
+impl WebcamPreview {
+    /// Open camera `device_index` and send its frames to `sink`, replacing
+    /// any preview already running
+    fn start(&self, device_index: usize, config: MonitorFeedConfig, sink: FeedSink) -> Result<(), String> {
+        self.stop();
+
+        let mut capture = WebcamCapture::new(WebcamCaptureConfig {
+            fps: config.frame_rate,
+            device_index,
+            ..WebcamCaptureConfig::default()
+        })?;
+        let receiver = capture.take_receiver().ok_or("Webcam receiver not available")?;
+        let feed = MonitorFeed::default();
+        feed.subscribe(FeedClient::Window, config, sink)?;
+        capture.start()?;
+
+        // Ends once the capture stops and its sender is dropped
+        let forward = feed.clone();
+        std::thread::spawn(move || {
+            for frame in receiver {
+                forward.offer(&preview_frame(&frame));
+            }
+        });
+
+        println!("Webcam preview started (camera {})", device_index);
+        *self.session.lock() = Some(PreviewSession { capture, feed });
+        Ok(())
+    }
+
+    /// Release the camera; does nothing when no preview is running
+    pub fn stop(&self) {
+        if let Some(session) = self.session.lock().take() {
+            session.capture.stop();
+            session.feed.unsubscribe(FeedClient::Window);
+            println!("Webcam preview stopped");
+        }
+    }
+}
+
+fn preview_frame(frame: &WebcamFrame) -> CompositeFrame {
+    CompositeFrame {
+        data: FrameBuffer::shared(frame.to_rgba()),
+        width: frame.width,
+        height: frame.height,
+        timestamp: frame.timestamp,
+        format: FrameFormat::Rgba,
+    }
+}
+
+/// Tauri command: Stream a webcam to the frontend without recording
+///
+/// `device_id` is a webcam ID from `get_available_devices` (default camera
+/// when absent or unplugged); `config` defaults to 640px JPEG at 15fps.
+#[command]
+pub fn start_webcam_preview(
+    device_id: Option<String>,
+    config: Option<MonitorFeedConfig>,
+    on_frame: Channel<InvokeResponseBody>,
+    preview: tauri::State<'_, WebcamPreview>,
+    recording: tauri::State<'_, Arc<RecordingState>>,
+) -> Result<(), RecordingError> {
+    if recording.manager.lock().config().is_some_and(|c| c.capture_webcam) {
+        return Err(RecordingError::Device("The webcam is being recorded".to_string()));
+    }
+    let (device_index, _) = resolve_webcam(device_id.as_deref());
+    preview.start(
+        device_index,
+        config.unwrap_or_default(),
+        Box::new(move |message| {
+            on_frame
+                .send(InvokeResponseBody::Raw(message))
+                .map_err(|e| e.to_string())
+        }),
+    )
+    .map_err(RecordingError::Device)
+}
+
+/// Tauri command: Stop the webcam preview
+#[command]
+pub fn stop_webcam_preview(preview: tauri::State<'_, WebcamPreview>) {
+    preview.stop();
+}
+